@@ -0,0 +1,142 @@
+//! # cargo-ruva
+//! Scaffolding tool that generates aggregate, commands, events, handlers and repository following Ruva's conventions.
+//!
+//! ```sh
+//! cargo install --path ruva-cli
+//! cargo ruva new aggregate Order --path src/domain
+//! ```
+//! This creates `src/domain/order/{mod,aggregate,commands,events,handlers,repository}.rs`
+//! and prints entries for `register_uow_services!` and `init_event_handler!`.
+mod templates;
+
+use std::path::{Path, PathBuf};
+
+const USAGE: &str = "Usage: cargo ruva new aggregate <Name> [--path <dir>] [--force]";
+
+#[derive(Debug, PartialEq, Eq)]
+struct NewAggregate {
+	name: String,
+	path: PathBuf,
+	force: bool,
+}
+
+fn parse_args(args: impl IntoIterator<Item = String>) -> Result<NewAggregate, String> {
+	let mut args = args.into_iter().peekable();
+	// When invoked as `cargo ruva`, cargo passes `ruva` as the first argument
+	if args.peek().map(String::as_str) == Some("ruva") {
+		args.next();
+	}
+	match (args.next().as_deref(), args.next().as_deref()) {
+		(Some("new"), Some("aggregate")) => {}
+		_ => return Err(USAGE.to_string()),
+	}
+	let name = args.next().ok_or_else(|| USAGE.to_string())?;
+	if name.is_empty() || !name.chars().all(|c| c.is_ascii_alphanumeric()) || !name.starts_with(|c: char| c.is_ascii_uppercase()) {
+		return Err(format!("Aggregate name must be PascalCase: {}", name));
+	}
+
+	let mut path = PathBuf::from("src");
+	let mut force = false;
+	while let Some(arg) = args.next() {
+		match arg.as_str() {
+			"--path" => path = args.next().map(PathBuf::from).ok_or_else(|| USAGE.to_string())?,
+			"--force" => force = true,
+			_ => return Err(format!("Unknown argument: {}\n{}", arg, USAGE)),
+		}
+	}
+	Ok(NewAggregate { name, path, force })
+}
+
+fn to_snake_case(name: &str) -> String {
+	let mut snake = String::with_capacity(name.len() + 4);
+	for (i, c) in name.chars().enumerate() {
+		if c.is_ascii_uppercase() {
+			if i != 0 {
+				snake.push('_');
+			}
+			snake.push(c.to_ascii_lowercase());
+		} else {
+			snake.push(c);
+		}
+	}
+	snake
+}
+
+fn render(template: &str, name: &str) -> String {
+	template.replace("{Name}", name).replace("{name}", &to_snake_case(name))
+}
+
+/// Files to generate, relative to the module directory
+fn files(name: &str) -> Vec<(&'static str, String)> {
+	vec![
+		("mod.rs", render(templates::MOD, name)),
+		("aggregate.rs", render(templates::AGGREGATE, name)),
+		("commands.rs", render(templates::COMMANDS, name)),
+		("events.rs", render(templates::EVENTS, name)),
+		("handlers.rs", render(templates::HANDLERS, name)),
+		("repository.rs", render(templates::REPOSITORY, name)),
+	]
+}
+
+fn generate(cmd: &NewAggregate) -> Result<PathBuf, String> {
+	let dir = cmd.path.join(to_snake_case(&cmd.name));
+	if dir.exists() && !cmd.force {
+		return Err(format!("{} already exists. Use --force to overwrite.", dir.display()));
+	}
+	std::fs::create_dir_all(&dir).map_err(|err| err.to_string())?;
+	for (file_name, content) in files(&cmd.name) {
+		write_file(&dir.join(file_name), &content)?;
+	}
+	Ok(dir)
+}
+
+fn write_file(path: &Path, content: &str) -> Result<(), String> {
+	std::fs::write(path, content).map_err(|err| format!("Failed to write {}: {}", path.display(), err))?;
+	println!("    created {}", path.display());
+	Ok(())
+}
+
+fn main() {
+	let cmd = match parse_args(std::env::args().skip(1)) {
+		Ok(cmd) => cmd,
+		Err(err) => {
+			eprintln!("{}", err);
+			std::process::exit(1);
+		}
+	};
+	match generate(&cmd) {
+		Ok(dir) => {
+			println!("\nGenerated {}.", dir.display());
+			println!("Add `pub mod {};` to {}/mod.rs and register the following:\n", to_snake_case(&cmd.name), cmd.path.display());
+			println!("{}", render(templates::REGISTRATION, &cmd.name));
+		}
+		Err(err) => {
+			eprintln!("{}", err);
+			std::process::exit(1);
+		}
+	}
+}
+
+#[test]
+fn test_parse_args() {
+	let args = ["ruva", "new", "aggregate", "OrderItem", "--path", "src/domain"].map(String::from);
+	assert_eq!(
+		parse_args(args).unwrap(),
+		NewAggregate {
+			name: "OrderItem".into(),
+			path: "src/domain".into(),
+			force: false
+		}
+	);
+	assert!(parse_args(["new", "aggregate", "order_item"].map(String::from)).is_err());
+	assert!(parse_args(["new", "command"].map(String::from)).is_err());
+}
+
+#[test]
+fn test_render_templates() {
+	assert_eq!(to_snake_case("OrderItem"), "order_item");
+	let files = files("OrderItem");
+	let (_, handlers) = files.iter().find(|(name, _)| *name == "handlers.rs").unwrap();
+	assert!(handlers.contains("pub async fn create_order_item(cmd: CreateOrderItem, uow: &mut Context)"));
+	assert!(!files.iter().any(|(_, content)| content.contains("{Name}") || content.contains("{name}")));
+}
@@ -0,0 +1,120 @@
+//! Templates of files generated by `cargo ruva new`.
+//! `{Name}` is replaced with the name given in PascalCase and `{name}` with the one in snake_case.
+
+pub(crate) const MOD: &str = r#"pub mod aggregate;
+pub mod commands;
+pub mod events;
+pub mod handlers;
+pub mod repository;
+"#;
+
+pub(crate) const AGGREGATE: &str = r#"use ruva::*;
+
+use super::commands::Create{Name};
+use super::events::{Name}Created;
+
+#[aggregate(Deserialize, Clone)]
+pub struct {Name} {
+	#[adapter_ignore]
+	pub(crate) id: i64,
+}
+
+impl {Name} {
+	pub fn create(_cmd: Create{Name}) -> Self {
+		let mut {name} = Self {
+			id: *SnowFlake::generate(),
+			..Default::default()
+		};
+		{name}.raise_event({Name}Created { id: {name}.id }.to_message());
+		{name}
+	}
+}
+"#;
+
+pub(crate) const COMMANDS: &str = r#"use ruva::*;
+
+#[into_command]
+pub struct Create{Name} {}
+"#;
+
+pub(crate) const EVENTS: &str = r#"use ruva::*;
+
+use super::aggregate::{Name};
+
+#[derive(Serialize, Deserialize, Clone, TEvent)]
+#[externally_notifiable({Name})]
+#[internally_notifiable]
+pub struct {Name}Created {
+	#[identifier]
+	pub id: i64,
+}
+"#;
+
+pub(crate) const HANDLERS: &str = r#"use ruva::*;
+
+use super::aggregate::{Name};
+use super::commands::Create{Name};
+use super::events::{Name}Created;
+use super::repository::T{Name}Repository;
+// TODO replace with your application response and error
+use crate::{ServiceError, ServiceResponse};
+
+pub async fn create_{name}(cmd: Create{Name}, uow: &mut Context) -> Result<ServiceResponse, ServiceError> {
+	let mut {name} = {Name}::create(cmd);
+	uow.add(&mut {name}).await?;
+	todo!("Return response")
+}
+
+pub struct {Name}EventHandler;
+impl {Name}EventHandler {
+	pub async fn on_{name}_created(self, _event: {Name}Created) -> Result<(), ServiceError> {
+		Ok(())
+	}
+}
+"#;
+
+pub(crate) const REPOSITORY: &str = r#"use ruva::*;
+
+use super::aggregate::{{Name}, {Name}Adapter};
+
+pub trait T{Name}Repository: Send + Sync {
+	fn add(&mut self, {name}: &mut {Name}) -> impl std::future::Future<Output = Result<i64, BaseError>> + Send;
+	fn get(&self, id: i64) -> impl std::future::Future<Output = Result<{Name}, BaseError>> + Send;
+	fn update(&mut self, {name}: &mut {Name}) -> impl std::future::Future<Output = Result<(), BaseError>> + Send;
+}
+
+impl T{Name}Repository for Context {
+	#[event_hook]
+	async fn add(&mut self, {name}: &mut {Name}) -> Result<i64, BaseError> {
+		let _adapter = {Name}Adapter::from({name}.clone());
+		todo!("Insert {name}")
+	}
+
+	async fn get(&self, _id: i64) -> Result<{Name}, BaseError> {
+		todo!("Fetch {name} and convert it from {Name}Adapter")
+	}
+
+	#[event_hook]
+	async fn update(&mut self, {name}: &mut {Name}) -> Result<(), BaseError> {
+		let _changed_columns = {name}.dirty_fields();
+		todo!("Update {name}")
+	}
+}
+"#;
+
+pub(crate) const REGISTRATION: &str = r#"// Register command handler
+ruva::register_uow_services!(
+	ServiceResponse,
+	ServiceError,
+
+	Create{Name} => create_{name}
+);
+
+// Register event handler
+init_event_handler!(
+	ServiceError,
+	|_ctx| {Name}EventHandler,
+
+	{Name}Created: [on_{name}_created],
+);
+"#;
@@ -0,0 +1,211 @@
+//! ### Axum integration
+//! Enabled by `ruva-axum` feature. [CommandExtractor] takes command out of JSON body, [BusState] dispatches it on `MessageBus`
+//! and [HttpError] answers the error of the command with status code of [THttpStatus].
+//!
+//! ```rust,ignore
+//! async fn make_order(bus: BusState, CommandExtractor(cmd): CommandExtractor<MakeOrder>) -> Result<Json<ServiceResponse>, HttpError<ServiceError>> {
+//!     Ok(Json(bus.dispatch(cmd).await?))
+//! }
+//!
+//! let app = Router::new().route("/orders", post(make_order)).layer(Extension(BusState::new(conn)));
+//! ```
+//! [CurrentUser] put on request extensions by auth middleware is extracted as well, and [BusState::dispatch_as] runs the command as the user.
+use axum::{
+	extract::{rejection::JsonRejection, FromRequest, FromRequestParts, Request},
+	http::{request::Parts, StatusCode},
+	response::{IntoResponse, Response},
+	Json,
+};
+use serde::de::DeserializeOwned;
+
+use crate::prelude::{current_trace_id, ApplicationError, BaseError, ContextManager, CurrentUser, ErrorResponse, MessageBus, TCommand, TCommandSpec, TConnection, THttpStatus, TMessageBus};
+
+/// Connection the commands of the request are dispatched with. Put it on the router with `Extension` layer.
+#[derive(Clone)]
+pub struct BusState {
+	conn: &'static dyn TConnection,
+}
+
+impl BusState {
+	pub fn new(conn: &'static dyn TConnection) -> Self {
+		Self { conn }
+	}
+
+	pub async fn dispatch<C>(&self, command: C) -> Result<C::Response, HttpError<C::Error>>
+	where
+		C: TCommandSpec,
+		C::Error: std::convert::From<BaseError>,
+		BaseError: std::convert::From<C::Error>,
+		MessageBus: TMessageBus<C::Response, C::Error, C>,
+	{
+		self.dispatch_with(command, ContextManager::new(self.conn)).await
+	}
+
+	/// Same as `dispatch` but with context manager prepared by caller, for example, with actor of the request.
+	pub async fn dispatch_with<C>(&self, command: C, context_manager: ContextManager) -> Result<C::Response, HttpError<C::Error>>
+	where
+		C: TCommandSpec,
+		C::Error: std::convert::From<BaseError>,
+		BaseError: std::convert::From<C::Error>,
+		MessageBus: TMessageBus<C::Response, C::Error, C>,
+	{
+		MessageBus.dispatch_with(command, context_manager).await.map_err(HttpError)
+	}
+
+	/// Same as `dispatch` but as `user`. See [ContextManager::with_current_user].
+	pub async fn dispatch_as<C>(&self, user: CurrentUser, command: C) -> Result<C::Response, HttpError<C::Error>>
+	where
+		C: TCommandSpec,
+		C::Error: std::convert::From<BaseError>,
+		BaseError: std::convert::From<C::Error>,
+		MessageBus: TMessageBus<C::Response, C::Error, C>,
+	{
+		self.dispatch_with(command, ContextManager::new(self.conn).with_current_user(user)).await
+	}
+
+	pub fn conn(&self) -> &'static dyn TConnection {
+		self.conn
+	}
+}
+
+impl<S: Send + Sync> FromRequestParts<S> for BusState {
+	type Rejection = (StatusCode, &'static str);
+
+	async fn from_request_parts(parts: &mut Parts, _: &S) -> Result<Self, Self::Rejection> {
+		parts.extensions.get::<BusState>().cloned().ok_or((StatusCode::INTERNAL_SERVER_ERROR, "BusState Is Not Given!"))
+	}
+}
+
+/// Put on request extensions by auth middleware once the token is validated. See [CurrentUser::from_jwt_claims].
+impl<S: Send + Sync> FromRequestParts<S> for CurrentUser {
+	type Rejection = (StatusCode, &'static str);
+
+	async fn from_request_parts(parts: &mut Parts, _: &S) -> Result<Self, Self::Rejection> {
+		parts.extensions.get::<CurrentUser>().cloned().ok_or((StatusCode::UNAUTHORIZED, "Not Authenticated!"))
+	}
+}
+
+/// Command deserialized from JSON body of the request
+pub struct CommandExtractor<C>(pub C);
+
+impl<S, C> FromRequest<S> for CommandExtractor<C>
+where
+	S: Send + Sync,
+	C: TCommand + DeserializeOwned,
+{
+	type Rejection = JsonRejection;
+
+	async fn from_request(req: Request, state: &S) -> Result<Self, Self::Rejection> {
+		let Json(command) = Json::<C>::from_request(req, state).await?;
+		Ok(Self(command))
+	}
+}
+
+/// Error of command answered as `{"error": .., "details": .., "trace_id": ..}` with status code of [THttpStatus].
+/// Body carries [THttpStatus::public_message] and [THttpStatus::public_details] only. The error itself is logged.
+#[derive(Debug)]
+pub struct HttpError<E>(pub E);
+
+impl<E: ApplicationError> From<E> for HttpError<E> {
+	fn from(error: E) -> Self {
+		Self(error)
+	}
+}
+
+impl<E: ApplicationError + THttpStatus> IntoResponse for HttpError<E> {
+	fn into_response(self) -> Response {
+		let status = StatusCode::from_u16(self.0.http_status()).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR);
+		match status.is_server_error() {
+			true => tracing::error!(status = status.as_u16(), "Command failed! {:?}", self.0),
+			false => tracing::warn!(status = status.as_u16(), "Command failed. {:?}", self.0),
+		}
+		let body = ErrorResponse {
+			error: self.0.public_message(),
+			details: self.0.public_details(),
+			trace_id: current_trace_id(),
+		};
+		(status, Json(body)).into_response()
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use super::*;
+	use axum::body::Body;
+
+	struct Connection;
+	impl TConnection for Connection {}
+
+	#[derive(Debug, serde::Deserialize)]
+	struct MakeOrder {
+		user_id: i64,
+	}
+	impl TCommand for MakeOrder {}
+
+	fn json_request(body: &'static str) -> Request {
+		Request::builder().method("POST").header("content-type", "application/json").body(Body::from(body)).unwrap()
+	}
+
+	#[tokio::test]
+	async fn test_command_extractor() {
+		let CommandExtractor(cmd) = CommandExtractor::<MakeOrder>::from_request(json_request(r#"{"user_id":1}"#), &()).await.unwrap();
+		assert_eq!(cmd.user_id, 1);
+
+		let rejection = CommandExtractor::<MakeOrder>::from_request(json_request(r#"{"user":1}"#), &()).await.err().unwrap();
+		assert_eq!(rejection.status(), StatusCode::UNPROCESSABLE_ENTITY);
+	}
+
+	#[tokio::test]
+	async fn test_bus_state_from_extension() {
+		let (mut parts, _) = json_request("{}").into_parts();
+		assert!(BusState::from_request_parts(&mut parts, &()).await.is_err());
+
+		parts.extensions.insert(BusState::new(&Connection));
+		assert!(BusState::from_request_parts(&mut parts, &()).await.is_ok());
+	}
+
+	#[tokio::test]
+	async fn test_current_user_from_extension() {
+		let (mut parts, _) = json_request("{}").into_parts();
+		let rejection = CurrentUser::from_request_parts(&mut parts, &()).await.err().unwrap();
+		assert_eq!(rejection.0, StatusCode::UNAUTHORIZED);
+
+		parts.extensions.insert(CurrentUser::new("migo"));
+		assert_eq!(CurrentUser::from_request_parts(&mut parts, &()).await.unwrap().id, "migo");
+	}
+
+	#[test]
+	fn test_http_error_status() {
+		assert_eq!(HttpError(BaseError::NotFound).into_response().status(), StatusCode::NOT_FOUND);
+		assert_eq!(HttpError(BaseError::TransactionConflict("deadlock".into())).into_response().status(), StatusCode::CONFLICT);
+		assert_eq!(
+			HttpError(BaseError::Overloaded {
+				command: "MakeOrder".into(),
+				limit: 1
+			})
+			.into_response()
+			.status(),
+			StatusCode::SERVICE_UNAVAILABLE
+		);
+		assert_eq!(HttpError(BaseError::ServiceError).into_response().status(), StatusCode::INTERNAL_SERVER_ERROR);
+	}
+
+	#[tokio::test]
+	async fn test_http_error_body() {
+		async fn body(error: BaseError) -> serde_json::Value {
+			let bytes = axum::body::to_bytes(HttpError(error).into_response().into_body(), usize::MAX).await.unwrap();
+			serde_json::from_slice(&bytes).unwrap()
+		}
+
+		// Internals are not sent to the client
+		let body_of_database_error = body(BaseError::DatabaseError("duplicate key value violates unique constraint".into())).await;
+		assert_eq!(body_of_database_error, serde_json::json!({"error": "Internal Server Error"}));
+
+		let mut errors = crate::prelude::ValidationErrors::default();
+		errors.add("user_id", "required", "user_id is required");
+		assert_eq!(
+			body(BaseError::ValidationFailed(errors)).await,
+			serde_json::json!({"error": "Validation Failed", "details": [{"field": "user_id", "code": "required", "message": "user_id is required"}]})
+		);
+	}
+}
@@ -0,0 +1,195 @@
+//! ### Kafka publisher
+//! [KafkaEventPublisher] publishes externally notifiable events to Kafka. Enabled by `ruva-kafka` feature.
+//! Topic is `EventMetadata::topic` and payload is state of `OutBox` encoded by [event_serializer](crate::prelude::event_serializer),
+//! with its MIME type in `content-type` header.
+//! Aggregate id is used as the record key by default so that events of an aggregate land on the same partition, in order.
+//!
+//! ```rust,ignore
+//! let publisher = KafkaEventPublisher::from_brokers("localhost:9092")?.with_topic_prefix("order-service.");
+//! let _handle = OutboxRelay::new(pool.clone(), publisher).spawn(bus_shutdown_token().clone());
+//! ```
+use std::time::Duration;
+
+use async_trait::async_trait;
+use rdkafka::{
+	message::{Header, OwnedHeaders},
+	producer::{FutureProducer, FutureRecord},
+	ClientConfig,
+};
+
+use crate::prelude::{namespaced_topic, AnalyticsRecord, BaseError, OutBox, TAnalyticsSink, TEvent, TOutboxPublisher};
+
+/// How the record key, and therefore the partition, is chosen
+#[derive(Clone, Copy)]
+pub enum PartitionKey {
+	/// Events of an aggregate keep their order
+	AggregateId,
+	/// Aggregate name and id joined with `:`. For topics shared by several aggregates whose ids may collide.
+	AggregateNameAndId,
+	/// No key. Records are spread over partitions by the producer.
+	None,
+	Custom(fn(&OutBox) -> Option<String>),
+}
+
+pub struct KafkaEventPublisher {
+	producer: FutureProducer,
+	partition_key: PartitionKey,
+	topic_prefix: String,
+	timeout: Duration,
+}
+
+impl KafkaEventPublisher {
+	pub fn new(producer: FutureProducer) -> Self {
+		Self {
+			producer,
+			partition_key: PartitionKey::AggregateId,
+			topic_prefix: String::new(),
+			timeout: Duration::from_secs(5),
+		}
+	}
+
+	/// Producer with idempotence enabled so that retries by the producer don't duplicate or reorder records.
+	pub fn from_brokers(brokers: &str) -> Result<Self, BaseError> {
+		let producer = ClientConfig::new()
+			.set("bootstrap.servers", brokers)
+			.set("enable.idempotence", "true")
+			.create()
+			.map_err(|err| BaseError::DeliveryError(err.to_string()))?;
+		Ok(Self::new(producer))
+	}
+
+	pub fn with_partition_key(mut self, partition_key: PartitionKey) -> Self {
+		self.partition_key = partition_key;
+		self
+	}
+
+	/// Prepended to the topic of every event
+	pub fn with_topic_prefix(mut self, prefix: impl Into<String>) -> Self {
+		self.topic_prefix = prefix.into();
+		self
+	}
+
+	/// How long a record may wait in the producer queue
+	pub fn with_timeout(mut self, timeout: Duration) -> Self {
+		self.timeout = timeout;
+		self
+	}
+
+	fn record_key(&self, outbox: &OutBox) -> Option<String> {
+		match self.partition_key {
+			PartitionKey::AggregateId => Some(outbox.aggregate_id.clone()),
+			PartitionKey::AggregateNameAndId => Some(format!("{}:{}", outbox.aggregate_name, outbox.aggregate_id)),
+			PartitionKey::None => None,
+			PartitionKey::Custom(f) => f(outbox),
+		}
+	}
+
+	/// Publish event directly, bypassing the outbox. Delivery is not guaranteed if the process crashes.
+	/// Topic is namespaced as the relay does. See [topic_namespace](crate::prelude::topic_namespace).
+	pub async fn publish_event(&self, event: &dyn TEvent) -> Result<(), BaseError> {
+		let outbox = event.outbox()?;
+		self.publish(&OutBox {
+			topic: namespaced_topic(&outbox.topic),
+			..outbox
+		})
+		.await
+	}
+}
+
+#[async_trait]
+impl TOutboxPublisher for KafkaEventPublisher {
+	async fn publish(&self, outbox: &OutBox) -> Result<(), BaseError> {
+		let topic = format!("{}{}", self.topic_prefix, outbox.topic);
+		let id = outbox.id.to_string();
+		let version = outbox.version.to_string();
+		let headers = OwnedHeaders::new()
+			.insert(Header { key: "event_id", value: Some(&id) })
+			.insert(Header {
+				key: "aggregate_name",
+				value: Some(&outbox.aggregate_name),
+			})
+			.insert(Header {
+				key: "event_version",
+				value: Some(&version),
+			})
+			.insert(Header {
+				key: "content-type",
+				value: Some(&outbox.content_type),
+			});
+		let headers = match outbox.trace_context.as_ref() {
+			Some(trace_context) => headers.insert(Header {
+				key: "traceparent",
+				value: Some(trace_context),
+			}),
+			None => headers,
+		};
+		let headers = [("correlation_id", &outbox.correlation_id), ("causation_id", &outbox.causation_id)]
+			.into_iter()
+			.fold(headers, |headers, (key, value)| match value.as_ref() {
+				Some(value) => headers.insert(Header { key, value: Some(value) }),
+				None => headers,
+			});
+		let key = self.record_key(outbox);
+		let mut record = FutureRecord::to(&topic).payload(&outbox.state).headers(headers);
+		if let Some(key) = key.as_ref() {
+			record = record.key(key);
+		}
+		self.producer.send(record, self.timeout).await.map_err(|(err, _)| {
+			tracing::error!(topic = %topic, id = outbox.id, "Failed to publish to kafka! {}", err);
+			BaseError::DeliveryError(err.to_string())
+		})?;
+		Ok(())
+	}
+}
+
+/// Sends analytics records to `topic` as JSON. See [Analytics](crate::prelude::Analytics).
+pub struct KafkaAnalyticsSink {
+	producer: FutureProducer,
+	topic: String,
+	timeout: Duration,
+}
+
+impl KafkaAnalyticsSink {
+	pub fn new(producer: FutureProducer, topic: impl Into<String>) -> Self {
+		Self {
+			producer,
+			topic: topic.into(),
+			timeout: Duration::from_secs(5),
+		}
+	}
+
+	pub fn from_brokers(brokers: &str, topic: impl Into<String>) -> Result<Self, BaseError> {
+		let producer = ClientConfig::new()
+			.set("bootstrap.servers", brokers)
+			.create()
+			.map_err(|err| BaseError::DeliveryError(err.to_string()))?;
+		Ok(Self::new(producer, topic))
+	}
+}
+
+#[async_trait]
+impl TAnalyticsSink for KafkaAnalyticsSink {
+	async fn send(&self, record: &AnalyticsRecord) -> Result<(), BaseError> {
+		let payload = serde_json::to_string(record).map_err(|err| BaseError::DeliveryError(err.to_string()))?;
+		// Records of a tenant stay in order
+		let mut kafka_record = FutureRecord::<String, String>::to(&self.topic).payload(&payload);
+		if let Some(tenant) = record.tenant.as_ref() {
+			kafka_record = kafka_record.key(tenant);
+		}
+		self.producer.send(kafka_record, self.timeout).await.map_err(|(err, _)| BaseError::DeliveryError(err.to_string()))?;
+		Ok(())
+	}
+}
+
+#[test]
+fn test_record_key() {
+	let outbox = OutBox::new("1".into(), "Order".into(), "OrderPlaced".into(), "{}".into());
+	let publisher = KafkaEventPublisher::from_brokers("localhost:9092").unwrap();
+	assert_eq!(publisher.record_key(&outbox), Some("1".to_string()));
+
+	let publisher = publisher.with_partition_key(PartitionKey::AggregateNameAndId);
+	assert_eq!(publisher.record_key(&outbox), Some("Order:1".to_string()));
+
+	let publisher = publisher.with_partition_key(PartitionKey::Custom(|outbox| Some(outbox.topic.clone())));
+	assert_eq!(publisher.record_key(&outbox), Some("OrderPlaced".to_string()));
+}
@@ -0,0 +1,129 @@
+//! ### Metrics
+//! [MetricsObserver] records bus operations through the `metrics` crate facade, so that any exporter installed as its recorder -
+//! Prometheus, OTLP and so on - picks them up. Enabled by `metrics` feature.
+//!
+//! ```rust,ignore
+//! // On boot
+//! PrometheusBuilder::new().install()?;
+//! MetricsObserver::describe();
+//! register_bus_observer(MetricsObserver);
+//! ```
+//!
+//! | Metric | Type | Labels |
+//! |---|---|---|
+//! | `ruva_commands_total` | counter | `command`, `outcome` |
+//! | `ruva_command_duration_seconds` | histogram | `command` |
+//! | `ruva_handler_duration_seconds` | histogram | `topic`, `outcome` |
+//! | `ruva_event_queue_depth` | histogram | |
+//! | `ruva_uow_commits_total` | counter | |
+//! | `ruva_uow_rollbacks_total` | counter | |
+//! | `ruva_backlog` | gauge | `source` |
+//! | `ruva_backlog_oldest_age_seconds` | gauge | `source` |
+//! | `ruva_messages_processed_total` | counter | `source`, `topic` |
+//!
+//! `outcome` is either `success` or `failure`.
+use std::time::Duration;
+
+use metrics::{counter, describe_counter, describe_gauge, describe_histogram, gauge, histogram, Unit};
+
+use crate::prelude::{Backlog, MessageSource, TBusObserver};
+
+pub struct MetricsObserver;
+
+impl MetricsObserver {
+	/// Register unit and description of the metrics with the installed recorder. Optional, but exporters show them when given.
+	pub fn describe() {
+		describe_counter!("ruva_commands_total", Unit::Count, "Commands handled");
+		describe_histogram!("ruva_command_duration_seconds", Unit::Seconds, "Time taken to handle a command, excluding its event handlers");
+		describe_histogram!("ruva_handler_duration_seconds", Unit::Seconds, "Time taken by an event handler");
+		describe_histogram!("ruva_event_queue_depth", Unit::Count, "Events left in the event queue when an event is taken off it");
+		describe_counter!("ruva_uow_commits_total", Unit::Count, "Unit of work commits");
+		describe_counter!("ruva_uow_rollbacks_total", Unit::Count, "Unit of work rollbacks");
+		describe_gauge!("ruva_backlog", Unit::Count, "Messages waiting in the outbox or to be received by the inbox");
+		describe_gauge!("ruva_backlog_oldest_age_seconds", Unit::Seconds, "Age of the oldest waiting message");
+		describe_counter!("ruva_messages_processed_total", Unit::Count, "Messages published from the outbox or handled from the inbox");
+	}
+}
+
+fn outcome(succeeded: bool) -> &'static str {
+	match succeeded {
+		true => "success",
+		false => "failure",
+	}
+}
+
+impl TBusObserver for MetricsObserver {
+	fn command_finished(&self, command: &str, elapsed: Duration, succeeded: bool) {
+		counter!("ruva_commands_total", "command" => command.to_string(), "outcome" => outcome(succeeded)).increment(1);
+		histogram!("ruva_command_duration_seconds", "command" => command.to_string()).record(elapsed);
+	}
+
+	fn handler_finished(&self, topic: &str, _index: usize, elapsed: Duration, succeeded: bool) {
+		histogram!("ruva_handler_duration_seconds", "topic" => topic.to_string(), "outcome" => outcome(succeeded)).record(elapsed);
+	}
+
+	fn event_dequeued(&self, _topic: &str, depth: usize) {
+		histogram!("ruva_event_queue_depth").record(depth as f64);
+	}
+
+	fn commit(&self) {
+		counter!("ruva_uow_commits_total").increment(1);
+	}
+
+	fn rollback(&self) {
+		counter!("ruva_uow_rollbacks_total").increment(1);
+	}
+
+	fn backlog_sampled(&self, source: MessageSource, backlog: &Backlog) {
+		gauge!("ruva_backlog", "source" => source.as_str()).set(backlog.count as f64);
+		gauge!("ruva_backlog_oldest_age_seconds", "source" => source.as_str()).set(backlog.oldest_age.unwrap_or_default());
+	}
+
+	fn message_processed(&self, source: MessageSource, topic: &str) {
+		counter!("ruva_messages_processed_total", "source" => source.as_str(), "topic" => topic.to_string()).increment(1);
+	}
+}
+
+#[test]
+fn test_metrics_observer() {
+	use metrics_util::debugging::{DebugValue, DebuggingRecorder};
+
+	let recorder = DebuggingRecorder::new();
+	let snapshotter = recorder.snapshotter();
+	metrics::with_local_recorder(&recorder, || {
+		MetricsObserver.command_finished("MakeOrder", Duration::from_millis(20), true);
+		MetricsObserver.command_finished("MakeOrder", Duration::from_millis(30), false);
+		MetricsObserver.handler_finished("OrderPlaced", 0, Duration::from_millis(5), true);
+		MetricsObserver.event_dequeued("OrderPlaced", 2);
+		MetricsObserver.commit();
+		MetricsObserver.rollback();
+		MetricsObserver.backlog_sampled(
+			MessageSource::Outbox,
+			&Backlog {
+				count: 7,
+				oldest_age: Some(Duration::from_secs(3)),
+			},
+		);
+	});
+
+	let metrics = snapshotter
+		.snapshot()
+		.into_vec()
+		.into_iter()
+		.map(|(key, _, _, value)| {
+			let labels = key.key().labels().map(|label| format!("{}={}", label.key(), label.value())).collect::<Vec<_>>();
+			((key.key().name().to_string(), labels.join(",")), value)
+		})
+		.collect::<std::collections::HashMap<_, _>>();
+	let value = |name: &str, labels: &str| metrics.get(&(name.to_string(), labels.to_string()));
+
+	assert_eq!(value("ruva_commands_total", "command=MakeOrder,outcome=success"), Some(&DebugValue::Counter(1)));
+	assert_eq!(value("ruva_commands_total", "command=MakeOrder,outcome=failure"), Some(&DebugValue::Counter(1)));
+	assert!(matches!(value("ruva_command_duration_seconds", "command=MakeOrder"), Some(DebugValue::Histogram(values)) if values.len() == 2));
+	assert!(matches!(value("ruva_handler_duration_seconds", "topic=OrderPlaced,outcome=success"), Some(DebugValue::Histogram(values)) if values.len() == 1));
+	assert!(matches!(value("ruva_event_queue_depth", ""), Some(DebugValue::Histogram(values)) if values[0].into_inner() == 2.0));
+	assert_eq!(value("ruva_uow_commits_total", ""), Some(&DebugValue::Counter(1)));
+	assert_eq!(value("ruva_uow_rollbacks_total", ""), Some(&DebugValue::Counter(1)));
+	assert!(matches!(value("ruva_backlog", "source=outbox"), Some(DebugValue::Gauge(count)) if count.into_inner() == 7.0));
+	assert!(matches!(value("ruva_backlog_oldest_age_seconds", "source=outbox"), Some(DebugValue::Gauge(age)) if age.into_inner() == 3.0));
+}
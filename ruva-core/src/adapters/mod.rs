@@ -1,2 +1,17 @@
-#[cfg(feature = "sqlx-postgres")]
+#[cfg(feature = "ruva-axum")]
+pub mod axum;
+#[cfg(feature = "ruva-kafka")]
+pub mod kafka;
+#[cfg(feature = "metrics")]
+pub mod metrics;
+#[cfg(feature = "mongodb")]
+pub mod mongo;
+#[cfg(feature = "opentelemetry")]
+pub mod otel;
+#[cfg(feature = "redis")]
+pub mod redis;
+#[cfg(any(feature = "sqlx-postgres", feature = "sqlx-sqlite"))]
 pub mod sqlx;
+#[cfg(feature = "ruva-tonic")]
+pub mod tonic;
+mod unit_of_work;
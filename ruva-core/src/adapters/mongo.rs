@@ -0,0 +1,350 @@
+//! ### MongoDB
+//! With `mongodb` feature, [Context] runs its unit of work as multi-document transaction on `mongodb::Database`,
+//! and [MongoRepository] keeps aggregates as documents in it.
+//!
+//! ```rust,ignore
+//! // On boot. Transactions require replica set or sharded cluster.
+//! let database: &'static Database = Box::leak(Box::new(Client::with_uri_str(uri).await?.database("shop")));
+//!
+//! // In command handler
+//! pub async fn make_order(cmd: MakeOrder, ctx: &mut Context) -> Result<ServiceResponse, ServiceError> {
+//!     let mut repository = MongoRepository::<Order>::new(ctx, "orders");
+//!     let mut order = Order::new(cmd);
+//!     repository.save(&order.id.to_string(), &mut order).await?;
+//!     Ok(order.id.into())
+//! }
+//! ```
+//! Events of the saved aggregate are collected with [Context::event_hook] as in the other repositories, and externally notifiable
+//! ones are inserted into `service_outbox` collection in the same transaction. `mongodb::Database` implements `TOutboxStore` to relay them.
+//! Sequence of [enable_outbox_sequence](crate::prelude::enable_outbox_sequence) is not assigned on MongoDB.
+use bson::{doc, Document};
+use futures::TryStreamExt;
+use mongodb::error::{ErrorKind, WriteFailure};
+use mongodb::{ClientSession, Collection, Database};
+use serde::{de::DeserializeOwned, Serialize};
+
+use crate::bus_components::contexts::Context;
+use crate::prelude::{clock, Backlog, BaseError, IdempotencyRecord, JournalOutcome, OutBox, PublishClass, TAggregate, TIdempotencyStore, TLoadAggregate, TOutboxStore, JSON_CONTENT_TYPE};
+
+const OUTBOX_COLLECTION: &str = "service_outbox";
+const JOURNAL_COLLECTION: &str = "command_log";
+const IDEMPOTENCY_COLLECTION: &str = "command_idempotency";
+/// Error code of unique index violation
+const DUPLICATE_KEY: i32 = 11000;
+
+impl From<mongodb::error::Error> for BaseError {
+	fn from(value: mongodb::error::Error) -> Self {
+		tracing::error!("{:?}", value);
+		if value.contains_label(mongodb::error::TRANSIENT_TRANSACTION_ERROR) {
+			return Self::TransactionConflict(value.to_string());
+		}
+		if let ErrorKind::Write(WriteFailure::WriteError(err)) = value.kind.as_ref() {
+			if err.code == DUPLICATE_KEY {
+				return Self::ConstraintViolation {
+					constraint: "_id".to_string(),
+					message: value.to_string(),
+				};
+			}
+		}
+		Self::DatabaseError(value.to_string())
+	}
+}
+
+fn serde_error(err: impl std::fmt::Display) -> BaseError {
+	BaseError::DatabaseError(err.to_string())
+}
+
+impl Context {
+	pub fn mongo_session(&mut self) -> &mut ClientSession {
+		match self.mongo_session.as_mut() {
+			Some(session) => session,
+			None => panic!("Transaction Has Not Begun!"),
+		}
+	}
+
+	pub fn mongo_database(&self) -> &'static Database {
+		let conn = self.super_ctx.conn;
+		match conn.downcast_ref::<&Database>().copied().or(conn.downcast_ref::<Database>()) {
+			Some(database) => database,
+			None => panic!("Connection Is Not mongodb::Database!"),
+		}
+	}
+
+	pub(crate) async fn save_mongo_outbox(&mut self) -> Result<(), BaseError> {
+		let outboxes = self.pending_outboxes()?;
+		if outboxes.is_empty() {
+			return Ok(());
+		}
+		let collection = self.mongo_database().collection::<Document>(OUTBOX_COLLECTION);
+		collection.insert_many(outboxes.iter().map(outbox_document)).session(self.mongo_session()).await?;
+		Ok(())
+	}
+
+	/// Mark idempotency key claimed by the command committed in its transaction. See [IdempotencyAspect](crate::prelude::IdempotencyAspect).
+	pub(crate) async fn save_mongo_idempotency_claim(&mut self) -> Result<(), BaseError> {
+		let Some(claim) = self.super_ctx.take_idempotency_claim() else {
+			return Ok(());
+		};
+		let collection = self.mongo_database().collection::<Document>(IDEMPOTENCY_COLLECTION);
+		let res = collection
+			.update_one(doc! { "_id": &claim.key, "command": claim.command, "committed": false }, doc! { "$set": { "committed": true } })
+			.session(self.mongo_session())
+			.await?;
+		if res.modified_count == 0 {
+			return Err(BaseError::ConcurrencyConflict(format!("Idempotency key {} is not claimed by the command", claim.key)));
+		}
+		Ok(())
+	}
+
+	pub(crate) async fn save_mongo_journal_entry(&mut self) -> Result<(), BaseError> {
+		let Some(entry) = self.super_ctx.take_journal_entry() else {
+			return Ok(());
+		};
+		let error = match &entry.outcome {
+			JournalOutcome::Succeeded => None,
+			JournalOutcome::Failed(error) => Some(error.clone()),
+		};
+		let document = doc! {
+			"_id": entry.id,
+			"command": &entry.command,
+			"payload": &entry.payload,
+			"actor": serde_json::to_string(&entry.actor).map_err(serde_error)?,
+			"correlation_id": &entry.correlation_id,
+			"outcome": entry.outcome.as_str(),
+			"error": error,
+			"recorded_at": bson::DateTime::from_chrono(entry.recorded_at),
+		};
+		let collection = self.mongo_database().collection::<Document>(JOURNAL_COLLECTION);
+		collection.insert_one(document).session(self.mongo_session()).await?;
+		Ok(())
+	}
+}
+
+fn outbox_document(outbox: &OutBox) -> Document {
+	doc! {
+		"_id": outbox.id,
+		"aggregate_id": &outbox.aggregate_id,
+		"aggregate_name": &outbox.aggregate_name,
+		"topic": &outbox.topic,
+		"state": bson::Binary { subtype: bson::spec::BinarySubtype::Generic, bytes: outbox.state.clone() },
+		"content_type": &outbox.content_type,
+		"processed": outbox.processed,
+		"create_dt": bson::DateTime::from_chrono(outbox.create_dt),
+		"version": outbox.version as i64,
+		"publish_class": outbox.publish_class.priority() as i32,
+		"trace_context": &outbox.trace_context,
+		"correlation_id": &outbox.correlation_id,
+		"causation_id": &outbox.causation_id,
+	}
+}
+
+fn outbox_from_document(document: &Document) -> Result<OutBox, BaseError> {
+	Ok(OutBox {
+		id: document.get_i64("_id").map_err(serde_error)?,
+		aggregate_id: document.get_str("aggregate_id").map_err(serde_error)?.to_string(),
+		aggregate_name: document.get_str("aggregate_name").map_err(serde_error)?.to_string(),
+		topic: document.get_str("topic").map_err(serde_error)?.to_string(),
+		// JSON text in documents written before state is encoded
+		state: match document.get_str("state") {
+			Ok(state) => state.as_bytes().to_vec(),
+			Err(_) => document.get_binary_generic("state").map_err(serde_error)?.clone(),
+		},
+		content_type: document.get_str("content_type").unwrap_or(JSON_CONTENT_TYPE).to_string(),
+		processed: document.get_bool("processed").map_err(serde_error)?,
+		create_dt: document.get_datetime("create_dt").map_err(serde_error)?.to_chrono(),
+		sequence: None,
+		version: document.get_i64("version").map_err(serde_error)? as u32,
+		// Absent in documents written before publish class
+		publish_class: PublishClass::from_priority(document.get_i32("publish_class").unwrap_or_default() as i16),
+		trace_context: document.get_str("trace_context").ok().map(ToString::to_string),
+		correlation_id: document.get_str("correlation_id").ok().map(ToString::to_string),
+		causation_id: document.get_str("causation_id").ok().map(ToString::to_string),
+	})
+}
+
+/// Aggregates of `A` kept as documents of a collection, keyed by `_id`
+pub struct MongoRepository<'a, A> {
+	ctx: &'a mut Context,
+	collection: Collection<Document>,
+	_aggregate: std::marker::PhantomData<fn() -> A>,
+}
+
+impl<'a, A: TAggregate + Serialize + DeserializeOwned + 'static> MongoRepository<'a, A> {
+	pub fn new(ctx: &'a mut Context, collection: &str) -> Self {
+		let collection = ctx.mongo_database().collection(collection);
+		Self {
+			ctx,
+			collection,
+			_aggregate: std::marker::PhantomData,
+		}
+	}
+
+	/// ## Errors
+	/// [BaseError::NotFound] if there is no document of `id`.
+	pub async fn get(&mut self, id: &str) -> Result<A, BaseError> {
+		let document = self.collection.find_one(doc! { "_id": id }).session(self.ctx.mongo_session()).await?;
+		let Some(document) = document else {
+			return Err(BaseError::NotFound);
+		};
+		bson::from_document(document).map_err(serde_error)
+	}
+
+	/// Insert or replace the document of `id` and collect events of `aggregate`.
+	pub async fn save(&mut self, id: &str, aggregate: &mut A) -> Result<(), BaseError> {
+		let mut document = bson::to_document(aggregate).map_err(serde_error)?;
+		document.insert("_id", id);
+		self.collection.replace_one(doc! { "_id": id }, document).upsert(true).session(self.ctx.mongo_session()).await?;
+		self.ctx.event_hook(aggregate);
+		Ok(())
+	}
+
+	/// Delete the document of `id` and collect events of `aggregate`, such as the one announcing the deletion.
+	pub async fn delete(&mut self, id: &str, aggregate: &mut A) -> Result<(), BaseError> {
+		self.collection.delete_one(doc! { "_id": id }).session(self.ctx.mongo_session()).await?;
+		self.ctx.event_hook(aggregate);
+		Ok(())
+	}
+}
+
+impl<A: TAggregate + Serialize + DeserializeOwned + 'static> TLoadAggregate<A, str> for MongoRepository<'_, A> {
+	fn load_aggregate(&mut self, id: &str) -> impl std::future::Future<Output = Result<A, BaseError>> + Send {
+		self.get(id)
+	}
+}
+
+fn idempotency_document(key: &str, command: &str, recorded_at: chrono::DateTime<chrono::Utc>) -> Document {
+	doc! {
+		"_id": key,
+		"command": command,
+		"response": bson::Bson::Null,
+		"committed": false,
+		"recorded_at": bson::DateTime::from_chrono(recorded_at),
+	}
+}
+
+/// Keys are claimed in `command_idempotency` collection, keyed by `_id`, and marked committed in the transaction of the command.
+#[async_trait::async_trait]
+impl TIdempotencyStore for Database {
+	async fn get(&self, key: &str) -> Result<Option<IdempotencyRecord>, BaseError> {
+		let Some(document) = self.collection::<Document>(IDEMPOTENCY_COLLECTION).find_one(doc! { "_id": key }).await? else {
+			return Ok(None);
+		};
+		Ok(Some(IdempotencyRecord {
+			key: key.to_string(),
+			command: document.get_str("command").map_err(serde_error)?.to_string(),
+			response: document.get_str("response").ok().map(ToString::to_string),
+			committed: document.get_bool("committed").unwrap_or_default(),
+			recorded_at: document.get_datetime("recorded_at").map_err(serde_error)?.to_chrono(),
+		}))
+	}
+
+	async fn claim(&self, key: &str, command: &str) -> Result<bool, BaseError> {
+		match self
+			.collection::<Document>(IDEMPOTENCY_COLLECTION)
+			.insert_one(idempotency_document(key, command, clock().now()))
+			.await
+			.map_err(BaseError::from)
+		{
+			Ok(_) => Ok(true),
+			Err(BaseError::ConstraintViolation { .. }) => Ok(false),
+			Err(err) => Err(err),
+		}
+	}
+
+	async fn complete(&self, key: &str, response: &str) -> Result<(), BaseError> {
+		self.collection::<Document>(IDEMPOTENCY_COLLECTION)
+			.update_one(doc! { "_id": key }, doc! { "$set": { "response": response } })
+			.await?;
+		Ok(())
+	}
+
+	async fn release(&self, key: &str) -> Result<bool, BaseError> {
+		let res = self
+			.collection::<Document>(IDEMPOTENCY_COLLECTION)
+			.delete_one(doc! { "_id": key, "response": bson::Bson::Null, "committed": false })
+			.await?;
+		Ok(res.deleted_count > 0)
+	}
+}
+
+/// Rows are published by publish class and then in the order of creation.
+#[async_trait::async_trait]
+impl TOutboxStore for Database {
+	async fn fetch_unprocessed(&self, limit: usize) -> Result<Vec<OutBox>, BaseError> {
+		let documents: Vec<Document> = self
+			.collection::<Document>(OUTBOX_COLLECTION)
+			.find(doc! { "processed": false })
+			.sort(doc! { "publish_class": 1, "create_dt": 1, "_id": 1 })
+			.limit(limit as i64)
+			.await?
+			.try_collect()
+			.await?;
+		documents.iter().map(outbox_from_document).collect()
+	}
+
+	async fn mark_processed(&self, id: i64) -> Result<(), BaseError> {
+		self.collection::<Document>(OUTBOX_COLLECTION)
+			.update_one(doc! { "_id": id }, doc! { "$set": { "processed": true } })
+			.await?;
+		Ok(())
+	}
+
+	async fn backlog(&self) -> Result<Option<Backlog>, BaseError> {
+		let collection = self.collection::<Document>(OUTBOX_COLLECTION);
+		let count = collection.count_documents(doc! { "processed": false }).await?;
+		let oldest = collection.find_one(doc! { "processed": false }).sort(doc! { "create_dt": 1 }).await?;
+		let oldest = oldest.map(|document| outbox_from_document(&document)).transpose()?;
+		Ok(Some(Backlog {
+			count,
+			oldest_age: oldest.and_then(|oldest| (clock().now() - oldest.create_dt).to_std().ok()),
+		}))
+	}
+}
+
+#[test]
+fn test_outbox_document_roundtrip() {
+	let outbox = OutBox {
+		version: 2,
+		publish_class: PublishClass::Bulk,
+		trace_context: Some("00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01".into()),
+		correlation_id: Some("request-1".into()),
+		causation_id: Some("7".into()),
+		..OutBox::new("1".into(), "Order".into(), "OrderPlaced".into(), r#"{"id":1}"#.into())
+	};
+	let document = outbox_document(&outbox);
+	assert_eq!(document.get_i64("_id").unwrap(), outbox.id);
+
+	let restored = outbox_from_document(&document).unwrap();
+	// BSON datetime is in milliseconds
+	assert_eq!(restored.create_dt.timestamp_millis(), outbox.create_dt.timestamp_millis());
+	assert_eq!(
+		(
+			restored.id,
+			restored.aggregate_id,
+			restored.aggregate_name,
+			restored.topic,
+			restored.state,
+			restored.content_type,
+			restored.processed,
+			restored.version,
+			restored.publish_class,
+			restored.trace_context,
+			restored.correlation_id,
+			restored.causation_id
+		),
+		(
+			outbox.id,
+			outbox.aggregate_id,
+			outbox.aggregate_name,
+			outbox.topic,
+			outbox.state,
+			outbox.content_type,
+			outbox.processed,
+			outbox.version,
+			outbox.publish_class,
+			outbox.trace_context,
+			outbox.correlation_id,
+			outbox.causation_id
+		)
+	);
+}
@@ -0,0 +1,90 @@
+//! ### OpenTelemetry
+//! [OtelPropagator] carries OpenTelemetry context of `tracing` spans as W3C `traceparent`, and [otel_trace_id] reads its trace id
+//! for [ErrorResponse](crate::prelude::ErrorResponse). Enabled by `opentelemetry` feature. Spans get OpenTelemetry context from
+//! `tracing-opentelemetry` layer.
+//!
+//! ```rust,ignore
+//! // On boot, after `tracing_subscriber::registry().with(tracing_opentelemetry::layer().with_tracer(tracer)).init()`
+//! set_trace_propagator(OtelPropagator);
+//! set_trace_id_provider(otel_trace_id);
+//! enable_outbox_trace_context();
+//! ```
+//! Without the layer or a tracer provider, span context is invalid and nothing is captured.
+use opentelemetry::trace::{SpanContext, SpanId, TraceContextExt, TraceFlags, TraceId, TraceState};
+use opentelemetry::Context;
+use tracing::Span;
+use tracing_opentelemetry::OpenTelemetrySpanExt;
+
+use crate::prelude::TTracePropagator;
+
+pub struct OtelPropagator;
+
+impl OtelPropagator {
+	fn traceparent(span_context: &SpanContext) -> Option<String> {
+		span_context
+			.is_valid()
+			.then(|| format!("00-{}-{}-{:02x}", span_context.trace_id(), span_context.span_id(), span_context.trace_flags().to_u8()))
+	}
+
+	fn parse(traceparent: &str) -> Option<SpanContext> {
+		let mut parts = traceparent.split('-');
+		let (Some("00"), Some(trace_id), Some(span_id), Some(flags), None) = (parts.next(), parts.next(), parts.next(), parts.next(), parts.next()) else {
+			return None;
+		};
+		if trace_id.len() != 32 || span_id.len() != 16 || flags.len() != 2 {
+			return None;
+		}
+		let span_context = SpanContext::new(
+			TraceId::from_hex(trace_id).ok()?,
+			SpanId::from_hex(span_id).ok()?,
+			TraceFlags::new(u8::from_str_radix(flags, 16).ok()?),
+			true,
+			TraceState::default(),
+		);
+		span_context.is_valid().then_some(span_context)
+	}
+}
+
+impl TTracePropagator for OtelPropagator {
+	fn inject(&self) -> Option<String> {
+		Self::traceparent(Span::current().context().span().span_context())
+	}
+
+	fn extract(&self, span: &Span, carrier: &str) {
+		let Some(span_context) = Self::parse(carrier) else {
+			tracing::warn!("Malformed traceparent {} skipped.", carrier);
+			return;
+		};
+		if let Err(err) = span.set_parent(Context::new().with_remote_span_context(span_context)) {
+			tracing::warn!("Failed to continue trace of {}: {:?}", carrier, err);
+		}
+	}
+}
+
+/// Trace id of OpenTelemetry context of the current span, for [set_trace_id_provider](crate::prelude::set_trace_id_provider).
+pub fn otel_trace_id() -> Option<String> {
+	let context = Span::current().context();
+	let span = context.span();
+	let span_context = span.span_context();
+	span_context.is_valid().then(|| span_context.trace_id().to_string())
+}
+
+#[test]
+fn test_traceparent_roundtrip() {
+	let traceparent = "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01";
+	let span_context = OtelPropagator::parse(traceparent).unwrap();
+	assert!(span_context.is_remote() && span_context.is_sampled());
+	assert_eq!(OtelPropagator::traceparent(&span_context).unwrap(), traceparent);
+
+	for malformed in [
+		"",
+		"01-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01",
+		"00-00000000000000000000000000000000-00f067aa0ba902b7-01",
+		"00-4bf92f35-00f067aa0ba902b7-01",
+	] {
+		assert!(OtelPropagator::parse(malformed).is_none());
+	}
+	// No `tracing-opentelemetry` layer installed
+	assert_eq!(OtelPropagator.inject(), None);
+	assert_eq!(otel_trace_id(), None);
+}
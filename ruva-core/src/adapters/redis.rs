@@ -0,0 +1,101 @@
+//! ### Redis cache store
+//! [RedisCacheStore] keeps responses cached by [CacheAspect](crate::prelude::CacheAspect) in Redis, so that instances behind
+//! a load balancer answer from the same cache and an event handled by one of them invalidates the responses cached by all.
+//! Enabled by `redis` feature.
+//!
+//! ```rust,no_run
+//! # use ruva_core::prelude::*;
+//! # async fn example() -> Result<(), BaseError> {
+//! // On boot
+//! set_cache_store(RedisCacheStore::connect("redis://127.0.0.1/").await?.with_prefix("order-service:"));
+//! # Ok(())
+//! # }
+//! ```
+//! Response is stored with `PX` of its TTL and its key is added to a set per tag. Tag set lives as long as the longest
+//! response in it, using `PEXPIRE` with `NX` and `GT` options, which requires Redis 7.0 or later.
+use std::time::Duration;
+
+use async_trait::async_trait;
+use redis::aio::MultiplexedConnection;
+
+use crate::prelude::{BaseError, TCacheStore};
+
+#[derive(Clone)]
+pub struct RedisCacheStore {
+	conn: MultiplexedConnection,
+	prefix: String,
+}
+
+impl RedisCacheStore {
+	pub fn new(conn: MultiplexedConnection) -> Self {
+		Self { conn, prefix: String::new() }
+	}
+
+	pub async fn connect(url: &str) -> Result<Self, BaseError> {
+		let client = redis::Client::open(url).map_err(redis_error)?;
+		Ok(Self::new(client.get_multiplexed_async_connection().await.map_err(redis_error)?))
+	}
+
+	/// Prepended to every key, for the services sharing a Redis
+	pub fn with_prefix(mut self, prefix: impl Into<String>) -> Self {
+		self.prefix = prefix.into();
+		self
+	}
+
+	fn key(&self, key: &str) -> String {
+		format!("{}response:{}", self.prefix, key)
+	}
+
+	fn tag_key(&self, tag: &str) -> String {
+		format!("{}tag:{}", self.prefix, tag)
+	}
+}
+
+fn redis_error(err: redis::RedisError) -> BaseError {
+	BaseError::DatabaseError(err.to_string())
+}
+
+#[async_trait]
+impl TCacheStore for RedisCacheStore {
+	async fn get(&self, key: &str) -> Result<Option<String>, BaseError> {
+		redis::cmd("GET").arg(self.key(key)).query_async(&mut self.conn.clone()).await.map_err(redis_error)
+	}
+
+	async fn set(&self, key: &str, value: String, ttl: Duration, tags: &[&str]) -> Result<(), BaseError> {
+		// Redis rejects zero expiry, and the response would be expired right away anyway
+		let ttl = ttl.as_millis() as u64;
+		if ttl == 0 {
+			return Ok(());
+		}
+		let key = self.key(key);
+		let mut pipe = redis::pipe();
+		pipe.atomic().cmd("SET").arg(&key).arg(value).arg("PX").arg(ttl).ignore();
+		for tag in tags {
+			let tag_key = self.tag_key(tag);
+			pipe.cmd("SADD").arg(&tag_key).arg(&key).ignore();
+			pipe.cmd("PEXPIRE").arg(&tag_key).arg(ttl).arg("NX").ignore();
+			pipe.cmd("PEXPIRE").arg(&tag_key).arg(ttl).arg("GT").ignore();
+		}
+		pipe.query_async::<()>(&mut self.conn.clone()).await.map_err(redis_error)
+	}
+
+	async fn invalidate(&self, tag: &str) -> Result<(), BaseError> {
+		let mut conn = self.conn.clone();
+		let tag_key = self.tag_key(tag);
+		// * Members are taken and the set is removed at once, so that key added in between is not dropped without being invalidated.
+		let (keys,): (Vec<String>,) = redis::pipe()
+			.atomic()
+			.cmd("SMEMBERS")
+			.arg(&tag_key)
+			.cmd("DEL")
+			.arg(&tag_key)
+			.ignore()
+			.query_async(&mut conn)
+			.await
+			.map_err(redis_error)?;
+		if keys.is_empty() {
+			return Ok(());
+		}
+		redis::cmd("DEL").arg(keys).query_async::<()>(&mut conn).await.map_err(redis_error)
+	}
+}
@@ -32,6 +32,16 @@ impl sqlx::Type<Postgres> for SnowFlake {
 impl From<sqlx::Error> for BaseError {
 	fn from(value: sqlx::Error) -> Self {
 		tracing::error!("{:?}", value);
+		// SQLSTATE class 40: serialization_failure(40001), deadlock_detected(40P01) and so on
+		if value.as_database_error().and_then(|err| err.code()).is_some_and(|code| code.starts_with("40")) {
+			return Self::TransactionConflict(value.to_string());
+		}
+		if let Some(constraint) = value.as_database_error().and_then(|err| err.constraint()) {
+			return Self::ConstraintViolation {
+				constraint: constraint.to_string(),
+				message: value.to_string(),
+			};
+		}
 		Self::DatabaseError(value.to_string())
 	}
 }
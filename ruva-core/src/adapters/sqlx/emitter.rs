@@ -0,0 +1,41 @@
+//! ### Emission outside command dispatch
+//! Background jobs and consumers can emit events without a synthetic command.
+//! Outbox rows are written within the transaction the caller supplies, so they are committed together with the caller's changes.
+//!
+//! ```rust,ignore
+//! let mut trx = pool.begin().await?;
+//! // ... changes of the job
+//! let events = vec![InvoiceExpired { id }.to_message()];
+//! EventEmitter::emit_all(&events, &mut trx).await?;
+//! trx.commit().await?;
+//!
+//! // Optionally, trigger internal handlers
+//! MessageBus.handle_events(events, ContextManager::new(conn)).await?;
+//! ```
+use std::sync::Arc;
+
+use sqlx::PgConnection;
+
+use crate::prelude::{BaseError, OutBox, TEvent};
+
+pub struct EventEmitter;
+
+impl EventEmitter {
+	/// Write outbox row of `event` if it is externally notifiable.
+	pub async fn emit(event: &dyn TEvent, executor: &mut PgConnection) -> Result<(), BaseError> {
+		if !event.externally_notifiable() {
+			tracing::warn!("{} is not externally notifiable. Nothing is emitted.", event.metadata().topic);
+			return Ok(());
+		}
+		OutBox::insert_all(&[event.outbox()?], executor).await
+	}
+
+	/// Write outbox rows of externally notifiable events among `events`.
+	pub async fn emit_all(events: &[Arc<dyn TEvent>], executor: &mut PgConnection) -> Result<(), BaseError> {
+		let outboxes = events.iter().filter(|e| e.externally_notifiable()).map(|e| e.outbox()).collect::<Result<Vec<_>, _>>()?;
+		if outboxes.is_empty() {
+			return Ok(());
+		}
+		OutBox::insert_all(&outboxes, executor).await
+	}
+}
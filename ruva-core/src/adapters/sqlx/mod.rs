@@ -1,2 +1,80 @@
 pub mod conversion;
+#[cfg(feature = "sqlx-postgres")]
+pub mod emitter;
+#[cfg(feature = "sqlx-postgres")]
+pub mod partition;
+#[cfg(feature = "sqlx-postgres")]
 pub mod postgres;
+#[cfg(feature = "sqlx-postgres")]
+pub mod reservation;
+#[cfg(feature = "sqlx-sqlite")]
+pub mod sqlite;
+#[cfg(feature = "sqlx-postgres")]
+pub mod timeout;
+
+use chrono::{DateTime, Utc};
+
+use crate::prelude::{OutBox, PublishClass};
+
+/// Columns of `service_outbox` that exist only once enabled, with the value read in their place otherwise.
+/// Cast, so that the type matches the one of the column.
+pub(crate) const OPTIONAL_OUTBOX_COLUMNS: [(&str, &str); 6] = [
+	("sequence", "CAST(NULL AS BIGINT)"),
+	("version", "CAST(1 AS INTEGER)"),
+	("publish_class", "CAST(0 AS SMALLINT)"),
+	("trace_context", "CAST(NULL AS TEXT)"),
+	("correlation_id", "CAST(NULL AS TEXT)"),
+	("causation_id", "CAST(NULL AS TEXT)"),
+];
+
+/// Select list of [OutBoxRow]. `enabled` tells whether the optional column exists.
+pub(crate) fn outbox_columns(enabled: impl Fn(&str) -> bool) -> String {
+	let mut columns = String::from("id, aggregate_id, aggregate_name, topic, state, content_type, processed, create_dt");
+	for (column, default) in OPTIONAL_OUTBOX_COLUMNS {
+		match enabled(column) {
+			true => columns.push_str(&format!(", {}", column)),
+			false => columns.push_str(&format!(", {} AS {}", default, column)),
+		}
+	}
+	columns
+}
+
+/// Row of `service_outbox` as every sqlx backend reads it, selected with [outbox_columns]
+#[derive(sqlx::FromRow)]
+pub(crate) struct OutBoxRow {
+	id: i64,
+	aggregate_id: String,
+	aggregate_name: String,
+	topic: String,
+	state: Vec<u8>,
+	content_type: String,
+	processed: bool,
+	create_dt: DateTime<Utc>,
+	sequence: Option<i64>,
+	version: i32,
+	publish_class: i16,
+	trace_context: Option<String>,
+	correlation_id: Option<String>,
+	causation_id: Option<String>,
+}
+
+impl From<OutBoxRow> for OutBox {
+	fn from(row: OutBoxRow) -> Self {
+		OutBox {
+			id: row.id,
+			aggregate_id: row.aggregate_id,
+			aggregate_name: row.aggregate_name,
+			topic: row.topic,
+			state: row.state,
+			content_type: row.content_type,
+			processed: row.processed,
+			create_dt: row.create_dt,
+			sequence: row.sequence,
+			version: row.version as u32,
+			publish_class: PublishClass::from_priority(row.publish_class),
+			trace_context: row.trace_context,
+			correlation_id: row.correlation_id,
+			causation_id: row.causation_id,
+		}
+	}
+}
@@ -0,0 +1,100 @@
+//! ### Time-partitioned tables
+//! Outbox(or event store) table grows indefinitely. Declaring it as a table partitioned by month on `create_dt`
+//! keeps index size manageable and lets old partitions be detached or dropped cheaply.
+//!
+//! ```rust,ignore
+//! // Once, in migration
+//! sqlx::query(&partitioned_outbox_ddl("service_outbox")).execute(&pool).await?;
+//!
+//! // On boot - creates partitions for this month and next month, then keeps doing so every day.
+//! let _handle = spawn_partition_maintenance(pool.clone(), vec!["service_outbox".into()], std::time::Duration::from_secs(86400));
+//! ```
+use chrono::{DateTime, Datelike, NaiveDate, Utc};
+use sqlx::PgPool;
+
+use super::postgres::PG_OUTBOX_COLUMNS;
+use crate::prelude::{clock, BaseError, TClock};
+
+/// DDL of outbox table partitioned by month on `create_dt`, with the same columns as [PG_OUTBOX_SCHEMA](crate::prelude::PG_OUTBOX_SCHEMA).
+/// As partition key must be part of primary key, primary key is `(id, create_dt)`.
+pub fn partitioned_outbox_ddl(table: &str) -> String {
+	format!(
+		"
+CREATE TABLE IF NOT EXISTS {table} (
+    id BIGINT NOT NULL,{PG_OUTBOX_COLUMNS},
+    PRIMARY KEY (id, create_dt)
+) PARTITION BY RANGE (create_dt)
+"
+	)
+}
+
+fn first_day_of_month(date: NaiveDate) -> NaiveDate {
+	NaiveDate::from_ymd_opt(date.year(), date.month(), 1).expect("First day of month always exists")
+}
+
+fn first_day_of_next_month(date: NaiveDate) -> NaiveDate {
+	let (year, month) = if date.month() == 12 { (date.year() + 1, 1) } else { (date.year(), date.month() + 1) };
+	NaiveDate::from_ymd_opt(year, month, 1).expect("First day of month always exists")
+}
+
+/// Name of the monthly partition that contains `date`. For example, `service_outbox_y2024m05`.
+pub fn monthly_partition_name(table: &str, date: NaiveDate) -> String {
+	format!("{}_y{:04}m{:02}", table, date.year(), date.month())
+}
+
+/// DDL that creates monthly partition of `table` containing `date`, if it doesn't exist.
+pub fn monthly_partition_ddl(table: &str, date: NaiveDate) -> String {
+	let from = first_day_of_month(date);
+	let to = first_day_of_next_month(date);
+	format!(
+		"CREATE TABLE IF NOT EXISTS {} PARTITION OF {} FOR VALUES FROM ('{}') TO ('{}')",
+		monthly_partition_name(table, date),
+		table,
+		from,
+		to
+	)
+}
+
+/// Create partitions for the month of `now` and the following month.
+pub async fn ensure_monthly_partitions(pool: &PgPool, table: &str, now: DateTime<Utc>) -> Result<(), BaseError> {
+	let this_month = now.date_naive();
+	for date in [this_month, first_day_of_next_month(this_month)] {
+		sqlx::query(&monthly_partition_ddl(table, date)).execute(pool).await?;
+	}
+	Ok(())
+}
+
+/// Maintenance task that creates upcoming partitions of given tables at every `interval`.
+pub fn spawn_partition_maintenance(pool: PgPool, tables: Vec<String>, interval: std::time::Duration) -> tokio::task::JoinHandle<()> {
+	tokio::spawn(async move {
+		loop {
+			for table in tables.iter() {
+				if let Err(err) = ensure_monthly_partitions(&pool, table, clock().now()).await {
+					tracing::error!("Failed to create partition of {}! {:?}", table, err);
+				}
+			}
+			tokio::time::sleep(interval).await;
+		}
+	})
+}
+
+#[test]
+fn test_monthly_partition_ddl() {
+	let date = NaiveDate::from_ymd_opt(2024, 12, 15).unwrap();
+	assert_eq!(monthly_partition_name("service_outbox", date), "service_outbox_y2024m12");
+	assert_eq!(
+		monthly_partition_ddl("service_outbox", date),
+		"CREATE TABLE IF NOT EXISTS service_outbox_y2024m12 PARTITION OF service_outbox FOR VALUES FROM ('2024-12-01') TO ('2025-01-01')"
+	);
+}
+
+#[test]
+fn test_partitioned_outbox_ddl_has_every_outbox_column() {
+	let schema = crate::prelude::PG_OUTBOX_SCHEMA;
+	let table = &schema[schema.find("service_outbox (").unwrap()..schema.find(");").unwrap()];
+	let ddl = partitioned_outbox_ddl("service_outbox");
+	for column in table.lines().skip(1).map(|line| line.trim().trim_end_matches(',')).filter(|line| !line.is_empty()) {
+		let column = column.replace(" PRIMARY KEY", " NOT NULL");
+		assert!(ddl.contains(&column), "{} is missing in partitioned outbox", column);
+	}
+}
@@ -1,9 +1,16 @@
-use crate::bus_components::contexts::Context;
+use super::{outbox_columns, OutBoxRow};
+use crate::bus_components::contexts::{Context, ReadContext, TReadRepository};
 use crate::{
-	prelude::{BaseError, TUnitOfWork},
+	prelude::{
+		clock, outbox_correlation_enabled, outbox_publish_class_enabled, outbox_sequence_enabled, outbox_trace_context_enabled, outbox_version_enabled, Backlog, BaseError, DeadLetter, DeliveryStatus,
+		IdempotencyRecord, JournalEntry, JournalOutcome, OutBox, ReconciliationReport, RedeliveryFilter, SagaRecord, StoredEvent, Subscription, TBackfillCheckpoint, TCheckpointStore, TCommandJournal,
+		TCommandQueueStore, TDeadLetterStore, TDeliveryLedger, TEventStore, TIdempotencyStore, TInboxStore, TOutboxHistory, TOutboxStore, TRemapStore, TRewriteOutbox, TSagaRepository,
+		TSubscriptionStore, TVersioned, Ticket, TicketStatus,
+	},
 	prepare_bulk_operation,
 };
-use sqlx::{PgConnection, PgPool};
+use chrono::{DateTime, Utc};
+use sqlx::{PgConnection, PgExecutor, PgPool};
 
 impl Context {
 	pub fn transaction(&mut self) -> &mut PgConnection {
@@ -13,93 +20,931 @@ impl Context {
 		}
 	}
 
-	pub(crate) async fn save_outbox(&mut self) -> Result<(), BaseError> {
-		let outboxes = self.curr_events.iter().filter(|e| e.externally_notifiable()).map(|o| o.outbox()).collect::<Vec<_>>();
+	/// Externally notifiable events of the unit of work, written in its transaction right before commit
+	pub(crate) async fn save_pg_outbox(&mut self) -> Result<(), BaseError> {
+		let outboxes = self.pending_outboxes()?;
+		OutBox::insert_all(&outboxes, self.transaction()).await
+	}
+
+	/// Run `UPDATE` of `aggregate` in the transaction and bump its version. The statement must match the row by the version
+	/// the aggregate was loaded at and bump the column, as [versioned_set_clause](crate::prelude::versioned_set_clause) does.
+	/// ## Errors
+	/// [BaseError::ConcurrencyConflict] if no row is updated, that is, the aggregate is changed since it was loaded.
+	pub async fn update_versioned<A: TVersioned>(&mut self, aggregate: &mut A, query: sqlx::query::Query<'_, sqlx::Postgres, sqlx::postgres::PgArguments>) -> Result<(), BaseError> {
+		let res = query.execute(self.transaction()).await?;
+		if res.rows_affected() == 0 {
+			return Err(BaseError::ConcurrencyConflict(format!("{} at version {}", std::any::type_name::<A>(), aggregate.version())));
+		}
+		aggregate.bump_version();
+		Ok(())
+	}
+
+	/// Write journal entry of the command in its transaction. See [CommandJournalAspect](crate::prelude::CommandJournalAspect).
+	pub(crate) async fn save_pg_journal_entry(&mut self) -> Result<(), BaseError> {
+		match self.super_ctx.take_journal_entry() {
+			Some(entry) => JournalEntry::insert(&entry, self.transaction()).await,
+			None => Ok(()),
+		}
+	}
+
+	/// Mark idempotency key claimed by the command committed in its transaction. See [IdempotencyAspect](crate::prelude::IdempotencyAspect).
+	pub(crate) async fn save_pg_idempotency_claim(&mut self) -> Result<(), BaseError> {
+		let Some(claim) = self.super_ctx.take_idempotency_claim() else {
+			return Ok(());
+		};
+		let committed = sqlx::query("UPDATE command_idempotency SET committed = TRUE WHERE idempotency_key = $1 AND command = $2 AND NOT committed")
+			.bind(&claim.key)
+			.bind(claim.command)
+			.execute(self.transaction())
+			.await?
+			.rows_affected();
+		if committed == 0 {
+			return Err(BaseError::ConcurrencyConflict(format!("Idempotency key {} is not claimed by the command", claim.key)));
+		}
+		Ok(())
+	}
+}
+
+impl ReadContext {
+	pub fn pg_pool(&self) -> &'static PgPool {
+		let conn = self.connection();
+		match conn.downcast_ref::<&PgPool>().copied().or(conn.downcast_ref::<PgPool>()) {
+			Some(pool) => pool,
+			None => panic!("Connection Is Not PgPool!"),
+		}
+	}
+}
+
+/// Columns of `service_outbox` but `id`, whose primary key differs by whether the table is partitioned
+macro_rules! pg_outbox_columns {
+	() => {
+		"
+    aggregate_id TEXT NOT NULL,
+    aggregate_name TEXT NOT NULL,
+    topic TEXT NOT NULL,
+    state BYTEA NOT NULL,
+    content_type TEXT NOT NULL DEFAULT 'application/json',
+    processed BOOLEAN NOT NULL DEFAULT false,
+    create_dt TIMESTAMPTZ NOT NULL DEFAULT now(),
+    sequence BIGINT,
+    version INTEGER NOT NULL DEFAULT 1,
+    publish_class SMALLINT NOT NULL DEFAULT 0,
+    trace_context TEXT,
+    correlation_id TEXT,
+    causation_id TEXT"
+	};
+}
+
+/// Shared by [PG_OUTBOX_SCHEMA] and [partitioned_outbox_ddl](super::partition::partitioned_outbox_ddl), so that columns added to one are not missed in the other
+pub(crate) const PG_OUTBOX_COLUMNS: &str = pg_outbox_columns!();
+
+/// Outbox tables [Context] writes to, created by [create_pg_outbox_schema].
+/// Columns of [enable_outbox_sequence](crate::prelude::enable_outbox_sequence), [enable_outbox_version](crate::prelude::enable_outbox_version)
+/// [enable_outbox_publish_class](crate::prelude::enable_outbox_publish_class), [enable_outbox_trace_context](crate::prelude::enable_outbox_trace_context)
+/// and [enable_outbox_correlation](crate::prelude::enable_outbox_correlation) are included, so enabling them later needs no migration.
+///
+/// `state` is kept as encoded by [event_serializer](crate::prelude::event_serializer), along with its `content_type`.
+/// Table created with `state TEXT` is migrated with:
+/// ```sql
+/// ALTER TABLE service_outbox
+///     ALTER COLUMN state TYPE BYTEA USING convert_to(state, 'UTF8'),
+///     ADD COLUMN content_type TEXT NOT NULL DEFAULT 'application/json';
+/// ```
+pub const PG_OUTBOX_SCHEMA: &str = concat!(
+	"
+CREATE TABLE IF NOT EXISTS service_outbox (
+    id BIGINT PRIMARY KEY,",
+	pg_outbox_columns!(),
+	r#"
+);
+CREATE INDEX IF NOT EXISTS service_outbox_unprocessed ON service_outbox (publish_class, create_dt) WHERE processed = false;
+CREATE TABLE IF NOT EXISTS service_outbox_sequence (
+    aggregate_name TEXT NOT NULL,
+    aggregate_id TEXT NOT NULL,
+    last_sequence BIGINT NOT NULL,
+    PRIMARY KEY (aggregate_name, aggregate_id)
+);
+"#
+);
+
+/// Whether optional column of `service_outbox` is enabled. See [OPTIONAL_OUTBOX_COLUMNS](super::OPTIONAL_OUTBOX_COLUMNS).
+fn pg_outbox_column_enabled(column: &str) -> bool {
+	match column {
+		"sequence" => outbox_sequence_enabled(),
+		"version" => outbox_version_enabled(),
+		"publish_class" => outbox_publish_class_enabled(),
+		"trace_context" => outbox_trace_context_enabled(),
+		"correlation_id" | "causation_id" => outbox_correlation_enabled(),
+		_ => false,
+	}
+}
+
+/// Run [PG_OUTBOX_SCHEMA]. Prefer copying it to the migrations of the service where they are managed.
+pub async fn create_pg_outbox_schema(pool: &PgPool) -> Result<(), BaseError> {
+	sqlx::raw_sql(PG_OUTBOX_SCHEMA).execute(pool).await?;
+	Ok(())
+}
 
+impl OutBox {
+	/// Insert all rows with a single `INSERT .. SELECT * FROM UNNEST`, on the connection of the transaction
+	/// that changes the aggregates, so that events are recorded if and only if the changes are committed.
+	pub(crate) async fn insert_all(outboxes: &[OutBox], executor: &mut PgConnection) -> Result<(), BaseError> {
 		prepare_bulk_operation!(
-			&outboxes,
+			outboxes,
 			id: i64,
 			aggregate_id: String,
 			aggregate_name:String,
 			topic: String,
-			state: String
+			state: Vec<u8>,
+			content_type: String,
+			create_dt: DateTime<Utc>
 		);
-		sqlx::query(
-			r#"
-            INSERT INTO service_outbox
-                (id, aggregate_id, topic, state, aggregate_name)
-            SELECT * FROM UNNEST
-                ($1::BIGINT[], $2::text[],  $3::text[], $4::text[], $5::text[])
-            "#,
-		)
-		.bind(&id)
-		.bind(&aggregate_id)
-		.bind(&topic)
-		.bind(&state)
-		.bind(&aggregate_name)
-		.execute(self.transaction())
-		.await
-		.map_err(|err| {
+		let sequence = match outbox_sequence_enabled() {
+			true => Some(Self::next_sequences(&aggregate_name, &aggregate_id, &mut *executor).await?),
+			false => None,
+		};
+		// `sequence`, `version`, `publish_class`, `trace_context` and correlation columns exist only when enabled
+		let mut columns = String::from("id, aggregate_id, topic, state, content_type, aggregate_name, create_dt");
+		let mut arrays = String::from("$1::BIGINT[], $2::text[], $3::text[], $4::BYTEA[], $5::text[], $6::text[], $7::TIMESTAMPTZ[]");
+		let mut placeholder = 7;
+		for (column, array_type) in [
+			("sequence", "BIGINT[]"),
+			("version", "INTEGER[]"),
+			("publish_class", "SMALLINT[]"),
+			("trace_context", "text[]"),
+			("correlation_id", "text[]"),
+			("causation_id", "text[]"),
+		] {
+			if pg_outbox_column_enabled(column) {
+				placeholder += 1;
+				columns.push_str(&format!(", {}", column));
+				arrays.push_str(&format!(", ${}::{}", placeholder, array_type));
+			}
+		}
+		let statement = format!("INSERT INTO service_outbox ({}) SELECT * FROM UNNEST ({})", columns, arrays);
+		let query = sqlx::query(&statement)
+			.bind(&id)
+			.bind(&aggregate_id)
+			.bind(&topic)
+			.bind(&state)
+			.bind(&content_type)
+			.bind(&aggregate_name)
+			.bind(&create_dt);
+		let query = match sequence {
+			Some(sequence) => query.bind(sequence),
+			None => query,
+		};
+		let query = match outbox_version_enabled() {
+			true => query.bind(outboxes.iter().map(|outbox| outbox.version as i32).collect::<Vec<_>>()),
+			false => query,
+		};
+		let query = match outbox_publish_class_enabled() {
+			true => query.bind(outboxes.iter().map(|outbox| outbox.publish_class.priority()).collect::<Vec<_>>()),
+			false => query,
+		};
+		let query = match outbox_trace_context_enabled() {
+			true => query.bind(outboxes.iter().map(|outbox| outbox.trace_context.clone()).collect::<Vec<_>>()),
+			false => query,
+		};
+		let query = match outbox_correlation_enabled() {
+			true => query
+				.bind(outboxes.iter().map(|outbox| outbox.correlation_id.clone()).collect::<Vec<_>>())
+				.bind(outboxes.iter().map(|outbox| outbox.causation_id.clone()).collect::<Vec<_>>()),
+			false => query,
+		};
+		query.execute(executor).await.map_err(|err| {
 			tracing::error!("failed to insert outbox! {}", err);
 			BaseError::DatabaseError(err.to_string())
 		})?;
 		Ok(())
 	}
-}
 
-impl TUnitOfWork for Context {
-	async fn begin(&mut self) -> Result<(), BaseError> {
-		match self.pg_transaction.as_mut() {
-			None => {
-				let trx = self.super_ctx.conn;
-
-				if let Some(trx) = trx.downcast_ref::<&PgPool>().or(trx.downcast_ref::<PgPool>().as_ref()) {
-					self.pg_transaction = Some(trx.begin().await?);
-				} else {
-					tracing::error!("Transaction Error!");
-					return Err(BaseError::TransactionError);
-				}
-				// simplify above
-
-				Ok(())
-			}
-			Some(_trx) => {
-				tracing::warn!("Transaction Begun Already!");
-				Err(BaseError::TransactionError)?
-			}
-		}
+	/// Reserve sequences of each aggregate in `service_outbox_sequence` and return them in the order of given rows.
+	/// Counter row is locked until the transaction ends, so sequences of an aggregate are never interleaved.
+	/// ```sql
+	/// ALTER TABLE service_outbox ADD COLUMN sequence BIGINT;
+	/// CREATE TABLE service_outbox_sequence (
+	///     aggregate_name TEXT NOT NULL,
+	///     aggregate_id TEXT NOT NULL,
+	///     last_sequence BIGINT NOT NULL,
+	///     PRIMARY KEY (aggregate_name, aggregate_id)
+	/// );
+	/// ```
+	async fn next_sequences(aggregate_name: &[String], aggregate_id: &[String], executor: &mut PgConnection) -> Result<Vec<i64>, BaseError> {
+		// Counter rows are locked in the order of keys, so that transactions sharing aggregates can't deadlock each other
+		let mut counts = std::collections::BTreeMap::<(String, String), i64>::new();
+		aggregate_name
+			.iter()
+			.zip(aggregate_id)
+			.for_each(|(name, id)| *counts.entry((name.clone(), id.clone())).or_default() += 1);
+		let (names, ids): (Vec<_>, Vec<_>) = counts.keys().cloned().unzip();
+		let count = counts.values().copied().collect::<Vec<_>>();
+
+		let reserved = sqlx::query_as::<_, (String, String, i64)>(
+			r#"
+            INSERT INTO service_outbox_sequence (aggregate_name, aggregate_id, last_sequence)
+            SELECT * FROM UNNEST ($1::text[], $2::text[], $3::BIGINT[])
+            ON CONFLICT (aggregate_name, aggregate_id)
+            DO UPDATE SET last_sequence = service_outbox_sequence.last_sequence + EXCLUDED.last_sequence
+            RETURNING aggregate_name, aggregate_id, last_sequence
+            "#,
+		)
+		.bind(&names)
+		.bind(&ids)
+		.bind(&count)
+		.fetch_all(executor)
+		.await?;
+
+		// Next sequence to hand out per aggregate = last reserved - reserved count + 1
+		let mut next = reserved
+			.into_iter()
+			.map(|(name, id, last)| ((name.clone(), id.clone()), last - counts[&(name, id)] + 1))
+			.collect::<hashbrown::HashMap<_, _>>();
+		Ok(aggregate_name
+			.iter()
+			.zip(aggregate_id)
+			.map(|(name, id)| {
+				let sequence = next.get_mut(&(name.clone(), id.clone())).expect("Sequence is reserved for every aggregate");
+				*sequence += 1;
+				*sequence - 1
+			})
+			.collect())
+	}
+
+	/// Distinct topics of outbox rows that are not processed yet. Used for preflight check.
+	pub async fn pending_topics(pool: &PgPool) -> Result<Vec<String>, BaseError> {
+		let topics = sqlx::query_scalar::<_, String>(
+			r#"
+            SELECT DISTINCT topic FROM service_outbox
+            WHERE processed = false
+            "#,
+		)
+		.fetch_all(pool)
+		.await?;
+		Ok(topics)
 	}
 
-	async fn _commit(&mut self) -> Result<(), BaseError> {
-		match self.pg_transaction.take() {
-			None => panic!("Tranasction Has Not Begun!"),
-			Some(trx) => Ok(trx.commit().await?),
+	/// Reconcile rows processed within `[from, to)` with what `ledger` received over the same window.
+	pub async fn reconcile(pool: &PgPool, ledger: &impl TDeliveryLedger, from: DateTime<Utc>, to: DateTime<Utc>) -> Result<ReconciliationReport, BaseError> {
+		let published = sqlx::query_scalar::<_, i64>(
+			r#"
+            SELECT id FROM service_outbox
+            WHERE processed = true AND create_dt >= $1 AND create_dt < $2
+            "#,
+		)
+		.bind(from)
+		.bind(to)
+		.fetch_all(pool)
+		.await?;
+		let delivered = ledger.delivered(from, to).await?;
+		Ok(ReconciliationReport::new(from, to, published, delivered))
+	}
+
+	/// Mark rows selected by `filter` as unprocessed so that the relay publishes them again. Returns the number of re-queued rows.
+	pub async fn redeliver(pool: &PgPool, filter: &RedeliveryFilter) -> Result<u64, BaseError> {
+		if filter.is_empty() {
+			tracing::warn!("Redelivering every outbox row!");
+		}
+		let mut query = sqlx::QueryBuilder::<sqlx::Postgres>::new("UPDATE service_outbox SET processed = false WHERE true");
+		if let Some(topic) = &filter.topic {
+			query.push(" AND topic = ").push_bind(topic);
+		}
+		if let Some(aggregate_id) = &filter.aggregate_id {
+			query.push(" AND aggregate_id = ").push_bind(aggregate_id);
 		}
+		if let Some(from) = filter.created_from {
+			query.push(" AND create_dt >= ").push_bind(from);
+		}
+		if let Some(to) = filter.created_to {
+			query.push(" AND create_dt < ").push_bind(to);
+		}
+		if let Some(status) = filter.status {
+			query.push(" AND processed = ").push_bind(status == DeliveryStatus::Processed);
+		}
+		let requeued = query.build().execute(pool).await?.rows_affected();
+		tracing::info!("{} outbox rows re-queued for redelivery. {:?}", requeued, filter);
+		Ok(requeued)
 	}
+}
 
-	async fn rollback(&mut self) -> Result<(), BaseError> {
-		self.curr_events.clear();
-		match self.pg_transaction.take() {
-			None => panic!("Tranasction Has Not Begun!"),
-			Some(trx) => Ok(trx.rollback().await?),
+/// Rows are published in the order of creation. With [enable_outbox_sequence](crate::prelude::enable_outbox_sequence), sequence breaks ties.
+/// With [enable_outbox_publish_class](crate::prelude::enable_outbox_publish_class), rows of higher class come first.
+#[async_trait::async_trait]
+impl TOutboxStore for PgPool {
+	async fn fetch_unprocessed(&self, limit: usize) -> Result<Vec<OutBox>, BaseError> {
+		let mut order = match outbox_sequence_enabled() {
+			true => "create_dt, sequence, id".to_string(),
+			false => "create_dt, id".to_string(),
+		};
+		if outbox_publish_class_enabled() {
+			order = format!("publish_class, {}", order);
 		}
+		let query = format!(
+			r#"
+            SELECT {} FROM service_outbox
+            WHERE processed = false
+            ORDER BY {}
+            LIMIT $1
+            "#,
+			outbox_columns(pg_outbox_column_enabled),
+			order
+		);
+		let rows = sqlx::query_as::<_, OutBoxRow>(&query).bind(limit as i64).fetch_all(self).await?;
+		Ok(rows.into_iter().map(OutBox::from).collect())
+	}
+
+	async fn mark_processed(&self, id: i64) -> Result<(), BaseError> {
+		sqlx::query("UPDATE service_outbox SET processed = true WHERE id = $1").bind(id).execute(self).await?;
+		Ok(())
+	}
+
+	async fn backlog(&self) -> Result<Option<Backlog>, BaseError> {
+		let (count, oldest) = sqlx::query_as::<_, (i64, Option<DateTime<Utc>>)>("SELECT count(*), min(create_dt) FROM service_outbox WHERE processed = false")
+			.fetch_one(self)
+			.await?;
+		Ok(Some(Backlog {
+			count: count as u64,
+			oldest_age: oldest.and_then(|oldest| (clock().now() - oldest).to_std().ok()),
+		}))
+	}
+}
+
+/// Inbound events are recorded in `service_inbox` table.
+/// ```sql
+/// CREATE TABLE service_inbox (event_id TEXT PRIMARY KEY, topic TEXT NOT NULL, received_at TIMESTAMPTZ NOT NULL);
+/// ```
+#[async_trait::async_trait]
+impl TInboxStore for PgPool {
+	async fn try_record(&self, event_id: &str, topic: &str) -> Result<bool, BaseError> {
+		let recorded = sqlx::query("INSERT INTO service_inbox (event_id, topic, received_at) VALUES ($1, $2, $3) ON CONFLICT (event_id) DO NOTHING")
+			.bind(event_id)
+			.bind(topic)
+			.bind(clock().now())
+			.execute(self)
+			.await?
+			.rows_affected();
+		Ok(recorded > 0)
+	}
+
+	async fn forget(&self, event_id: &str) -> Result<(), BaseError> {
+		sqlx::query("DELETE FROM service_inbox WHERE event_id = $1").bind(event_id).execute(self).await?;
+		Ok(())
+	}
+}
+
+/// Rows of `service_outbox` are remapped in place by id.
+#[async_trait::async_trait]
+impl TRemapStore for PgPool {
+	async fn fetch_aggregate_rows(&self, aggregate_names: &[String], after: Option<i64>, limit: usize) -> Result<Vec<OutBox>, BaseError> {
+		let query = format!(
+			r#"
+            SELECT {} FROM service_outbox
+            WHERE aggregate_name = ANY($1) AND id > $2
+            ORDER BY id
+            LIMIT $3
+            "#,
+			outbox_columns(pg_outbox_column_enabled)
+		);
+		let rows = sqlx::query_as::<_, OutBoxRow>(&query)
+			.bind(aggregate_names)
+			.bind(after.unwrap_or(i64::MIN))
+			.bind(limit as i64)
+			.fetch_all(self)
+			.await?;
+		Ok(rows.into_iter().map(OutBox::from).collect())
 	}
-	async fn close(&mut self) {
-		match self.pg_transaction.take() {
-			None => (),
-			Some(trx) => {
-				let _ = trx.rollback().await;
+}
+
+/// Rows are rewritten in the transaction of the context, which also saves the checkpoint of the remap.
+impl TRewriteOutbox for Context {
+	async fn rewrite_rows(&mut self, mut rows: Vec<OutBox>) -> Result<(), BaseError> {
+		let query = match outbox_sequence_enabled() {
+			true => {
+				// Moved rows continue the sequence of their new aggregate
+				let (names, ids): (Vec<_>, Vec<_>) = rows
+					.iter()
+					.filter(|row| row.sequence.is_none())
+					.map(|row| (row.aggregate_name.clone(), row.aggregate_id.clone()))
+					.unzip();
+				let mut sequences = OutBox::next_sequences(&names, &ids, self.transaction()).await?.into_iter();
+				rows.iter_mut().filter(|row| row.sequence.is_none()).for_each(|row| row.sequence = sequences.next());
+				"UPDATE service_outbox SET aggregate_name = $2, aggregate_id = $3, topic = $4, state = $5, content_type = $6, sequence = $7 WHERE id = $1"
 			}
+			false => "UPDATE service_outbox SET aggregate_name = $2, aggregate_id = $3, topic = $4, state = $5, content_type = $6 WHERE id = $1",
+		};
+		for row in rows {
+			let update = sqlx::query(query)
+				.bind(row.id)
+				.bind(row.aggregate_name)
+				.bind(row.aggregate_id)
+				.bind(row.topic)
+				.bind(row.state)
+				.bind(row.content_type);
+			let update = match outbox_sequence_enabled() {
+				true => update.bind(row.sequence),
+				false => update,
+			};
+			update.execute(self.transaction()).await?;
 		}
+		Ok(())
 	}
+}
 
-	async fn process_internal_events(&mut self) -> Result<(), BaseError> {
-		self.send_internally_notifiable_messages().await;
+/// Every row of `service_outbox`, processed or not
+#[async_trait::async_trait]
+impl TOutboxHistory for PgPool {
+	async fn fetch_history(&self, after: Option<i64>, limit: usize) -> Result<Vec<OutBox>, BaseError> {
+		let query = format!(
+			r#"
+            SELECT {} FROM service_outbox
+            WHERE id > $1
+            ORDER BY id
+            LIMIT $2
+            "#,
+			outbox_columns(pg_outbox_column_enabled)
+		);
+		let rows = sqlx::query_as::<_, OutBoxRow>(&query).bind(after.unwrap_or(i64::MIN)).bind(limit as i64).fetch_all(self).await?;
+		Ok(rows.into_iter().map(OutBox::from).collect())
+	}
+}
+
+/// Checkpoints are kept in `service_backfill_checkpoint` table.
+/// ```sql
+/// CREATE TABLE service_backfill_checkpoint (name TEXT PRIMARY KEY, cursor BIGINT NOT NULL, updated_at TIMESTAMPTZ NOT NULL DEFAULT now());
+/// ```
+#[async_trait::async_trait]
+impl TCheckpointStore for PgPool {
+	async fn load(&self, name: &str) -> Result<Option<i64>, BaseError> {
+		let cursor = sqlx::query_scalar::<_, i64>("SELECT cursor FROM service_backfill_checkpoint WHERE name = $1")
+			.bind(name)
+			.fetch_optional(self)
+			.await?;
+		Ok(cursor)
+	}
+
+	async fn save(&self, name: &str, cursor: i64) -> Result<(), BaseError> {
+		save_checkpoint(name, cursor, self).await
+	}
+}
+
+/// Subscriptions are kept in `service_outbox_subscription` table, shared by the instances of the service.
+/// ```sql
+/// CREATE TABLE service_outbox_subscription (topic TEXT NOT NULL, destination TEXT NOT NULL, PRIMARY KEY (topic, destination));
+/// ```
+#[async_trait::async_trait]
+impl TSubscriptionStore for PgPool {
+	async fn load(&self) -> Result<Vec<Subscription>, BaseError> {
+		let rows = sqlx::query_as::<_, (String, String)>("SELECT topic, destination FROM service_outbox_subscription ORDER BY topic, destination")
+			.fetch_all(self)
+			.await?;
+		Ok(rows.into_iter().map(|(topic, destination)| Subscription { topic, destination }).collect())
+	}
+
+	async fn save(&self, subscriptions: Vec<Subscription>) -> Result<(), BaseError> {
+		let mut trx = self.begin().await?;
+		sqlx::query("DELETE FROM service_outbox_subscription").execute(&mut *trx).await?;
+		let (topics, destinations): (Vec<_>, Vec<_>) = subscriptions.into_iter().map(|subscription| (subscription.topic, subscription.destination)).unzip();
+		sqlx::query("INSERT INTO service_outbox_subscription (topic, destination) SELECT * FROM UNNEST($1::TEXT[], $2::TEXT[]) ON CONFLICT DO NOTHING")
+			.bind(topics)
+			.bind(destinations)
+			.execute(&mut *trx)
+			.await?;
+		trx.commit().await?;
+		Ok(())
+	}
+
+	async fn add(&self, subscription: &Subscription) -> Result<(), BaseError> {
+		sqlx::query("INSERT INTO service_outbox_subscription (topic, destination) VALUES ($1, $2) ON CONFLICT DO NOTHING")
+			.bind(&subscription.topic)
+			.bind(&subscription.destination)
+			.execute(self)
+			.await?;
+		Ok(())
+	}
+
+	async fn remove(&self, subscription: &Subscription) -> Result<(), BaseError> {
+		sqlx::query("DELETE FROM service_outbox_subscription WHERE topic = $1 AND destination = $2")
+			.bind(&subscription.topic)
+			.bind(&subscription.destination)
+			.execute(self)
+			.await?;
+		Ok(())
+	}
+}
+
+/// Checkpoints of backfills run in command handlers, saved in the transaction of the command
+impl TBackfillCheckpoint for Context {
+	async fn load_checkpoint(&mut self, name: &str) -> Result<Option<i64>, BaseError> {
+		let cursor = sqlx::query_scalar::<_, i64>("SELECT cursor FROM service_backfill_checkpoint WHERE name = $1")
+			.bind(name)
+			.fetch_optional(self.transaction())
+			.await?;
+		Ok(cursor)
+	}
+
+	async fn save_checkpoint(&mut self, name: &str, cursor: i64) -> Result<(), BaseError> {
+		save_checkpoint(name, cursor, self.transaction()).await
+	}
+}
+
+async fn save_checkpoint(name: &str, cursor: i64, executor: impl PgExecutor<'_>) -> Result<(), BaseError> {
+	sqlx::query(
+		r#"
+        INSERT INTO service_backfill_checkpoint (name, cursor) VALUES ($1, $2)
+        ON CONFLICT (name) DO UPDATE SET cursor = EXCLUDED.cursor, updated_at = now()
+        "#,
+	)
+	.bind(name)
+	.bind(cursor)
+	.execute(executor)
+	.await?;
+	Ok(())
+}
+
+/// Saga instances are kept in `service_saga` table, in the transaction of the step.
+/// ```sql
+/// CREATE TABLE service_saga (
+///     name TEXT NOT NULL,
+///     id TEXT NOT NULL,
+///     status TEXT NOT NULL,
+///     state TEXT NOT NULL,
+///     updated_at TIMESTAMPTZ NOT NULL,
+///     PRIMARY KEY (name, id)
+/// );
+/// ```
+impl TSagaRepository for Context {
+	async fn load_saga(&mut self, name: &str, id: &str) -> Result<Option<SagaRecord>, BaseError> {
+		let row = sqlx::query_as::<_, (String, String)>("SELECT status, state FROM service_saga WHERE name = $1 AND id = $2 FOR UPDATE")
+			.bind(name)
+			.bind(id)
+			.fetch_optional(self.transaction())
+			.await?;
+		Ok(row.map(|(status, state)| SagaRecord {
+			name: name.to_string(),
+			id: id.to_string(),
+			status,
+			state,
+		}))
+	}
+
+	async fn save_saga(&mut self, record: &SagaRecord) -> Result<(), BaseError> {
+		let now = self.now();
+		sqlx::query(
+			r#"
+            INSERT INTO service_saga (name, id, status, state, updated_at) VALUES ($1, $2, $3, $4, $5)
+            ON CONFLICT (name, id) DO UPDATE SET status = EXCLUDED.status, state = EXCLUDED.state, updated_at = EXCLUDED.updated_at
+            "#,
+		)
+		.bind(&record.name)
+		.bind(&record.id)
+		.bind(&record.status)
+		.bind(&record.state)
+		.bind(now)
+		.execute(self.transaction())
+		.await?;
+		Ok(())
+	}
+}
+
+/// Dead letters of event handlers are kept in `service_dead_letter` table.
+/// ```sql
+/// CREATE TABLE service_dead_letter (
+///     id BIGINT PRIMARY KEY,
+///     topic TEXT NOT NULL,
+///     handler_index INT,
+///     payload TEXT NOT NULL,
+///     error TEXT NOT NULL,
+///     failed_at TIMESTAMPTZ NOT NULL
+/// );
+/// ```
+/// Version of the payload is kept along with [enable_outbox_version](crate::prelude::enable_outbox_version), which requires `version` column.
+/// ```sql
+/// ALTER TABLE service_dead_letter ADD COLUMN version INT NOT NULL DEFAULT 1;
+/// ```
+#[async_trait::async_trait]
+impl TDeadLetterStore for PgPool {
+	async fn push(&self, dead_letter: &DeadLetter) -> Result<(), BaseError> {
+		let query = match outbox_version_enabled() {
+			true => sqlx::query("INSERT INTO service_dead_letter (id, topic, handler_index, payload, error, failed_at, version) VALUES ($1, $2, $3, $4, $5, $6, $7)"),
+			false => sqlx::query("INSERT INTO service_dead_letter (id, topic, handler_index, payload, error, failed_at) VALUES ($1, $2, $3, $4, $5, $6)"),
+		};
+		let query = query
+			.bind(dead_letter.id)
+			.bind(&dead_letter.topic)
+			.bind(dead_letter.handler_index.map(|index| index as i32))
+			.bind(&dead_letter.payload)
+			.bind(&dead_letter.error)
+			.bind(dead_letter.failed_at);
+		let query = match outbox_version_enabled() {
+			true => query.bind(dead_letter.version as i32),
+			false => query,
+		};
+		query.execute(self).await?;
 		Ok(())
 	}
 
-	async fn process_external_events(&mut self) -> Result<(), BaseError> {
-		self.save_outbox().await?;
+	async fn fetch(&self, limit: usize) -> Result<Vec<DeadLetter>, BaseError> {
+		let version = match outbox_version_enabled() {
+			true => "version",
+			false => "1",
+		};
+		let statement = format!(
+			"SELECT id, topic, handler_index, payload, {}, error, failed_at FROM service_dead_letter ORDER BY failed_at, id LIMIT $1",
+			version
+		);
+		let rows = sqlx::query_as::<_, (i64, String, Option<i32>, String, i32, String, DateTime<Utc>)>(&statement)
+			.bind(limit as i64)
+			.fetch_all(self)
+			.await?;
+		Ok(rows
+			.into_iter()
+			.map(|(id, topic, handler_index, payload, version, error, failed_at)| DeadLetter {
+				id,
+				topic,
+				handler_index: handler_index.map(|index| index as usize),
+				payload,
+				version: version as u32,
+				error,
+				failed_at,
+			})
+			.collect())
+	}
+
+	async fn remove(&self, id: i64) -> Result<(), BaseError> {
+		sqlx::query("DELETE FROM service_dead_letter WHERE id = $1").bind(id).execute(self).await?;
 		Ok(())
 	}
 }
+
+impl JournalEntry {
+	async fn insert(entry: &JournalEntry, executor: impl sqlx::PgExecutor<'_>) -> Result<(), BaseError> {
+		let (outcome, error) = match &entry.outcome {
+			JournalOutcome::Succeeded => (entry.outcome.as_str(), None),
+			JournalOutcome::Failed(error) => (entry.outcome.as_str(), Some(error)),
+		};
+		sqlx::query(
+			r#"
+            INSERT INTO command_log (id, command, payload, actor, correlation_id, outcome, error, recorded_at)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
+            "#,
+		)
+		.bind(entry.id)
+		.bind(&entry.command)
+		.bind(&entry.payload)
+		.bind(serde_json::to_string(&entry.actor).map_err(|err| BaseError::DatabaseError(err.to_string()))?)
+		.bind(&entry.correlation_id)
+		.bind(outcome)
+		.bind(error)
+		.bind(entry.recorded_at)
+		.execute(executor)
+		.await?;
+		Ok(())
+	}
+}
+
+/// Returns `false` if the key is claimed already.
+async fn claim_idempotency_key(key: &str, command: &str, recorded_at: DateTime<Utc>, executor: impl sqlx::PgExecutor<'_>) -> Result<bool, BaseError> {
+	let claimed = sqlx::query("INSERT INTO command_idempotency (idempotency_key, command, recorded_at) VALUES ($1, $2, $3) ON CONFLICT (idempotency_key) DO NOTHING")
+		.bind(key)
+		.bind(command)
+		.bind(recorded_at)
+		.execute(executor)
+		.await?
+		.rows_affected();
+	Ok(claimed > 0)
+}
+
+/// Keys are claimed in `command_idempotency` table before the command runs, and marked committed in the transaction of the command.
+/// ```sql
+/// CREATE TABLE command_idempotency (
+///     idempotency_key TEXT PRIMARY KEY,
+///     command TEXT NOT NULL,
+///     response TEXT,
+///     committed BOOLEAN NOT NULL DEFAULT FALSE,
+///     recorded_at TIMESTAMPTZ NOT NULL
+/// );
+/// ```
+#[async_trait::async_trait]
+impl TIdempotencyStore for PgPool {
+	async fn get(&self, key: &str) -> Result<Option<IdempotencyRecord>, BaseError> {
+		let row = sqlx::query_as::<_, (String, String, Option<String>, bool, DateTime<Utc>)>(
+			"SELECT idempotency_key, command, response, committed, recorded_at FROM command_idempotency WHERE idempotency_key = $1",
+		)
+		.bind(key)
+		.fetch_optional(self)
+		.await?;
+		Ok(row.map(|(key, command, response, committed, recorded_at)| IdempotencyRecord {
+			key,
+			command,
+			response,
+			committed,
+			recorded_at,
+		}))
+	}
+
+	async fn claim(&self, key: &str, command: &str) -> Result<bool, BaseError> {
+		claim_idempotency_key(key, command, clock().now(), self).await
+	}
+
+	async fn complete(&self, key: &str, response: &str) -> Result<(), BaseError> {
+		sqlx::query("UPDATE command_idempotency SET response = $2 WHERE idempotency_key = $1")
+			.bind(key)
+			.bind(response)
+			.execute(self)
+			.await?;
+		Ok(())
+	}
+
+	async fn release(&self, key: &str) -> Result<bool, BaseError> {
+		let released = sqlx::query("DELETE FROM command_idempotency WHERE idempotency_key = $1 AND response IS NULL AND NOT committed")
+			.bind(key)
+			.execute(self)
+			.await?
+			.rows_affected();
+		Ok(released > 0)
+	}
+}
+
+type TicketRow = (i64, String, String, String, String, Option<DateTime<Utc>>, Option<String>, Option<String>, DateTime<Utc>, DateTime<Utc>);
+
+fn ticket((id, command, payload, status, owner, claimed_until, response, error, queued_at, updated_at): TicketRow) -> Result<Ticket, BaseError> {
+	let status = TicketStatus::parse(&status).ok_or_else(|| BaseError::DatabaseError(format!("Unknown ticket status {}", status)))?;
+	Ok(Ticket {
+		id,
+		command,
+		payload,
+		status,
+		owner: serde_json::from_str(&owner).map_err(|err| BaseError::DatabaseError(err.to_string()))?,
+		claimed_until,
+		response,
+		error,
+		queued_at,
+		updated_at,
+	})
+}
+
+/// Commands are queued in `command_queue` table. Ticket is claimed with `FOR UPDATE SKIP LOCKED`, so workers of any number can share the queue.
+/// ```sql
+/// CREATE TABLE command_queue (
+///     id BIGINT PRIMARY KEY,
+///     command TEXT NOT NULL,
+///     payload TEXT NOT NULL,
+///     status TEXT NOT NULL,
+///     owner TEXT NOT NULL,
+///     claimed_until TIMESTAMPTZ,
+///     response TEXT,
+///     error TEXT,
+///     queued_at TIMESTAMPTZ NOT NULL,
+///     updated_at TIMESTAMPTZ NOT NULL
+/// );
+/// CREATE INDEX command_queue_claimable ON command_queue (id) WHERE status IN ('queued', 'running');
+/// ```
+#[async_trait::async_trait]
+impl TCommandQueueStore for PgPool {
+	async fn enqueue(&self, ticket: &Ticket) -> Result<(), BaseError> {
+		sqlx::query("INSERT INTO command_queue (id, command, payload, status, owner, queued_at, updated_at) VALUES ($1, $2, $3, $4, $5, $6, $7)")
+			.bind(ticket.id)
+			.bind(&ticket.command)
+			.bind(&ticket.payload)
+			.bind(ticket.status.as_str())
+			.bind(serde_json::to_string(&ticket.owner).map_err(|err| BaseError::DatabaseError(err.to_string()))?)
+			.bind(ticket.queued_at)
+			.bind(ticket.updated_at)
+			.execute(self)
+			.await?;
+		Ok(())
+	}
+
+	async fn claim_next(&self, claimed_until: DateTime<Utc>) -> Result<Option<Ticket>, BaseError> {
+		let row = sqlx::query_as::<_, TicketRow>(
+			r#"
+			UPDATE command_queue SET status = 'running', claimed_until = $2, updated_at = $1
+			WHERE id = (
+				SELECT id FROM command_queue WHERE status = 'queued' OR (status = 'running' AND claimed_until < $1)
+				ORDER BY id LIMIT 1 FOR UPDATE SKIP LOCKED
+			)
+			RETURNING id, command, payload, status, owner, claimed_until, response, error, queued_at, updated_at
+			"#,
+		)
+		.bind(clock().now())
+		.bind(claimed_until)
+		.fetch_optional(self)
+		.await?;
+		row.map(ticket).transpose()
+	}
+
+	async fn finish(&self, id: i64, claimed_until: DateTime<Utc>, result: Result<String, String>) -> Result<bool, BaseError> {
+		let (status, response, error) = match result {
+			Ok(response) => (TicketStatus::Succeeded, Some(response), None),
+			Err(error) => (TicketStatus::Failed, None, Some(error)),
+		};
+		let finished =
+			sqlx::query("UPDATE command_queue SET status = $2, response = $3, error = $4, updated_at = $5, claimed_until = NULL WHERE id = $1 AND status = 'running' AND claimed_until = $6")
+				.bind(id)
+				.bind(status.as_str())
+				.bind(response)
+				.bind(error)
+				.bind(clock().now())
+				.bind(claimed_until)
+				.execute(self)
+				.await?
+				.rows_affected();
+		Ok(finished > 0)
+	}
+
+	async fn get_ticket(&self, id: i64) -> Result<Option<Ticket>, BaseError> {
+		let row = sqlx::query_as::<_, TicketRow>("SELECT id, command, payload, status, owner, claimed_until, response, error, queued_at, updated_at FROM command_queue WHERE id = $1")
+			.bind(id)
+			.fetch_optional(self)
+			.await?;
+		row.map(ticket).transpose()
+	}
+}
+
+/// Commands are journaled in `command_log` table. Entry of successful command is written in the transaction of the command.
+/// ```sql
+/// CREATE TABLE command_log (
+///     id BIGINT PRIMARY KEY,
+///     command TEXT NOT NULL,
+///     payload TEXT NOT NULL,
+///     actor TEXT NOT NULL,
+///     correlation_id TEXT,
+///     outcome TEXT NOT NULL,
+///     error TEXT,
+///     recorded_at TIMESTAMPTZ NOT NULL
+/// );
+/// ```
+#[async_trait::async_trait]
+impl TCommandJournal for PgPool {
+	async fn record(&self, entry: &JournalEntry) -> Result<(), BaseError> {
+		JournalEntry::insert(entry, self).await
+	}
+
+	async fn get(&self, id: i64) -> Result<Option<JournalEntry>, BaseError> {
+		let row = sqlx::query_as::<_, (i64, String, String, String, Option<String>, Option<String>, DateTime<Utc>)>(
+			"SELECT id, command, payload, actor, correlation_id, error, recorded_at FROM command_log WHERE id = $1",
+		)
+		.bind(id)
+		.fetch_optional(self)
+		.await?;
+		row.map(|(id, command, payload, actor, correlation_id, error, recorded_at)| {
+			Ok(JournalEntry {
+				id,
+				command,
+				payload,
+				actor: serde_json::from_str(&actor).map_err(|err| BaseError::DatabaseError(err.to_string()))?,
+				correlation_id,
+				outcome: match error {
+					Some(error) => JournalOutcome::Failed(error),
+					None => JournalOutcome::Succeeded,
+				},
+				recorded_at,
+			})
+		})
+		.transpose()
+	}
+}
+
+/// Event streams of event sourced aggregates are kept in `events` table.
+/// ```sql
+/// CREATE TABLE events (
+///     aggregate_id TEXT NOT NULL,
+///     seq BIGINT NOT NULL,
+///     payload TEXT NOT NULL,
+///     version INT NOT NULL DEFAULT 1,
+///     PRIMARY KEY (aggregate_id, seq)
+/// );
+/// ```
+/// Concurrent append is caught by the primary key `events_pkey`, as both transactions try to insert the same `seq`.
+impl TEventStore for Context {
+	async fn load_stream(&mut self, aggregate_id: &str) -> Result<Vec<StoredEvent>, BaseError> {
+		let rows = sqlx::query_as::<_, (i64, String, i32)>("SELECT seq, payload, version FROM events WHERE aggregate_id = $1 ORDER BY seq")
+			.bind(aggregate_id)
+			.fetch_all(self.transaction())
+			.await?;
+		Ok(rows
+			.into_iter()
+			.map(|(seq, payload, version)| StoredEvent {
+				aggregate_id: aggregate_id.to_string(),
+				seq,
+				payload,
+				version: version as u32,
+			})
+			.collect())
+	}
+
+	async fn append_stream(&mut self, aggregate_id: &str, expected_seq: i64, version: u32, payloads: Vec<String>) -> Result<(), BaseError> {
+		let seq = (expected_seq + 1..).take(payloads.len()).collect::<Vec<_>>();
+		let res = sqlx::query("INSERT INTO events (aggregate_id, version, seq, payload) SELECT $1, $2, * FROM UNNEST ($3::BIGINT[], $4::text[])")
+			.bind(aggregate_id)
+			.bind(version as i32)
+			.bind(&seq)
+			.bind(&payloads)
+			.execute(self.transaction())
+			.await;
+		match res.map_err(BaseError::from) {
+			Err(BaseError::ConstraintViolation { constraint, message }) if constraint == "events_pkey" => Err(BaseError::ConcurrencyConflict(message)),
+			res => res.map(|_| ()),
+		}
+	}
+}
@@ -0,0 +1,238 @@
+//! ### Reservation
+//! "Reserve → confirm/cancel" pair of commands, such as holding inventory until payment is done.
+//! Reservation record is persisted in the command's transaction with the time it expires at. Reservation that is neither confirmed
+//! nor cancelled in time is marked expired by [expire_due_reservations], which raises [ReservationExpired] for the local handlers
+//! and other services alike.
+//!
+//! ```rust,ignore
+//! // Command handler of `ReserveStock`
+//! let reservation = ReservationHandler::new(ctx, chrono::Duration::minutes(15)).reserve(format!("sku:{}", cmd.sku), &cmd).await?;
+//!
+//! // Command handler of `ConfirmStock` / `CancelStock`
+//! ReservationHandler::new(ctx, chrono::Duration::minutes(15)).confirm(cmd.reservation_id).await?;
+//! ReservationHandler::new(ctx, chrono::Duration::minutes(15)).cancel(cmd.reservation_id).await?;
+//!
+//! // Event handler of `ReservationExpired` compensates - restocks the item.
+//! init_event_handler!(ServiceError, |ctx| Context::new(ctx), ReservationExpired: [restock]);
+//!
+//! // On boot
+//! tokio::spawn(async move {
+//!     loop {
+//!         if let Err(err) = expire_due_reservations(&MessageBus, pool, clock().now()).await {
+//!             tracing::error!("Failed to expire reservations! {:?}", err);
+//!         }
+//!         tokio::time::sleep(Duration::from_secs(10)).await;
+//!     }
+//! });
+//! ```
+//! Expiry is safe to run on every instance - a due reservation is expired by exactly one of them.
+//!
+//! ```sql
+//! CREATE TABLE service_reservation (
+//!     id BIGINT PRIMARY KEY,
+//!     resource TEXT NOT NULL,
+//!     state TEXT NOT NULL,
+//!     status TEXT NOT NULL,
+//!     expires_at TIMESTAMPTZ NOT NULL
+//! );
+//! ```
+use std::sync::Arc;
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use sqlx::PgPool;
+
+use crate::bus_components::actor::Actor;
+use crate::bus_components::contexts::{Context, ContextManager};
+use crate::prelude::{ApplicationError, BaseError, SnowFlake, TEvent, TEventBus, TTopic, TUnitOfWork};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReservationStatus {
+	Reserved,
+	Confirmed,
+	Cancelled,
+	Expired,
+}
+
+impl ReservationStatus {
+	pub fn as_str(&self) -> &'static str {
+		match self {
+			Self::Reserved => "reserved",
+			Self::Confirmed => "confirmed",
+			Self::Cancelled => "cancelled",
+			Self::Expired => "expired",
+		}
+	}
+}
+
+impl std::str::FromStr for ReservationStatus {
+	type Err = BaseError;
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		match s {
+			"reserved" => Ok(Self::Reserved),
+			"confirmed" => Ok(Self::Confirmed),
+			"cancelled" => Ok(Self::Cancelled),
+			"expired" => Ok(Self::Expired),
+			_ => Err(BaseError::DatabaseError(format!("Unknown reservation status: {}", s))),
+		}
+	}
+}
+
+#[derive(Debug, Clone)]
+pub struct Reservation {
+	pub id: i64,
+	pub resource: String,
+	/// Serialized payload given on reserve
+	pub state: String,
+	pub status: ReservationStatus,
+	pub expires_at: DateTime<Utc>,
+}
+
+impl Reservation {
+	pub fn is_expired(&self, now: DateTime<Utc>) -> bool {
+		self.status == ReservationStatus::Reserved && self.expires_at <= now
+	}
+}
+
+/// Raised when reservation is neither confirmed nor cancelled until it expires. Handled locally and published to other services.
+#[derive(Debug, Clone, Serialize)]
+pub struct ReservationExpired {
+	pub reservation_id: i64,
+	pub resource: String,
+}
+
+impl TTopic for ReservationExpired {
+	const TOPIC: &'static str = "ReservationExpired";
+}
+
+impl TEvent for ReservationExpired {
+	fn externally_notifiable(&self) -> bool {
+		true
+	}
+	fn internally_notifiable(&self) -> bool {
+		true
+	}
+	fn state(&self) -> String {
+		serde_json::to_string(self).expect("Failed to serialize")
+	}
+}
+
+pub struct ReservationHandler<'a> {
+	ctx: &'a mut Context,
+	ttl: chrono::Duration,
+}
+
+impl<'a> ReservationHandler<'a> {
+	pub fn new(ctx: &'a mut Context, ttl: chrono::Duration) -> Self {
+		Self { ctx, ttl }
+	}
+
+	pub async fn reserve(&mut self, resource: impl Into<String>, state: &impl Serialize) -> Result<Reservation, BaseError> {
+		let reservation = Reservation {
+			id: *SnowFlake::generate(),
+			resource: resource.into(),
+			state: serde_json::to_string(state).map_err(|err| BaseError::DatabaseError(err.to_string()))?,
+			status: ReservationStatus::Reserved,
+			expires_at: self.ctx.now() + self.ttl,
+		};
+		sqlx::query(
+			r#"
+            INSERT INTO service_reservation (id, resource, state, status, expires_at)
+            VALUES ($1, $2, $3, $4, $5)
+            "#,
+		)
+		.bind(reservation.id)
+		.bind(&reservation.resource)
+		.bind(&reservation.state)
+		.bind(reservation.status.as_str())
+		.bind(reservation.expires_at)
+		.execute(self.ctx.transaction())
+		.await?;
+		Ok(reservation)
+	}
+
+	pub async fn confirm(&mut self, id: i64) -> Result<Reservation, BaseError> {
+		self.settle(id, ReservationStatus::Confirmed).await
+	}
+
+	pub async fn cancel(&mut self, id: i64) -> Result<Reservation, BaseError> {
+		self.settle(id, ReservationStatus::Cancelled).await
+	}
+
+	/// Only reservation that is still reserved and not expired can be settled. Otherwise, `BaseError::NotFound` is returned.
+	async fn settle(&mut self, id: i64, status: ReservationStatus) -> Result<Reservation, BaseError> {
+		let row = sqlx::query_as::<_, (i64, String, String, String, DateTime<Utc>)>(
+			r#"
+            UPDATE service_reservation SET status = $2
+            WHERE id = $1 AND status = 'reserved' AND expires_at > $3
+            RETURNING id, resource, state, status, expires_at
+            "#,
+		)
+		.bind(id)
+		.bind(status.as_str())
+		.bind(self.ctx.now())
+		.fetch_optional(self.ctx.transaction())
+		.await?
+		.ok_or(BaseError::NotFound)?;
+
+		Ok(Reservation {
+			id: row.0,
+			resource: row.1,
+			state: row.2,
+			status: row.3.parse()?,
+			expires_at: row.4,
+		})
+	}
+}
+
+/// Mark reservations due at `now` expired and raise [ReservationExpired] for each of them, written to the outbox in the same transaction
+/// and handled on `bus` once it is committed. Handler that fails is dead-lettered as any other. Returns the number of expired reservations.
+pub async fn expire_due_reservations<E>(bus: &(impl TEventBus<E> + Sync), pool: &'static PgPool, now: DateTime<Utc>) -> Result<u64, E>
+where
+	E: ApplicationError + std::convert::From<BaseError>,
+	BaseError: std::convert::From<E>,
+{
+	let actor = Actor::System("reservation-expiry".into());
+	let mut ctx = Context::new(Arc::new(ContextManager::new(pool).with_actor(actor.clone())));
+	ctx.begin().await?;
+	let expired = sqlx::query_as::<_, (i64, String)>(
+		r#"
+        UPDATE service_reservation SET status = 'expired'
+        WHERE status = 'reserved' AND expires_at <= $1
+        RETURNING id, resource
+        "#,
+	)
+	.bind(now)
+	.fetch_all(ctx.transaction())
+	.await
+	.map_err(Into::<BaseError>::into)?;
+	let events = expired
+		.into_iter()
+		.map(|(reservation_id, resource)| Arc::new(ReservationExpired { reservation_id, resource }) as Arc<dyn TEvent>)
+		.collect::<Vec<_>>();
+	if events.is_empty() {
+		ctx.rollback().await?;
+		return Ok(0);
+	}
+	ctx.raise_all(events.clone());
+	ctx.commit().await?;
+
+	let count = events.len() as u64;
+	bus.handle_events(events, ContextManager::new(pool).with_actor(actor)).await?;
+	Ok(count)
+}
+
+#[test]
+fn test_reservation_status() {
+	for status in [ReservationStatus::Reserved, ReservationStatus::Confirmed, ReservationStatus::Cancelled, ReservationStatus::Expired] {
+		assert_eq!(status.as_str().parse::<ReservationStatus>().unwrap(), status);
+	}
+	let reservation = Reservation {
+		id: 1,
+		resource: "sku:1".into(),
+		state: "{}".into(),
+		status: ReservationStatus::Reserved,
+		expires_at: Utc::now(),
+	};
+	assert!(reservation.is_expired(Utc::now() + chrono::Duration::seconds(1)));
+}
@@ -0,0 +1,421 @@
+//! ### SQLite
+//! With `sqlx-sqlite` feature, [Context] runs its unit of work on `SqlitePool` the same way it does on `PgPool`,
+//! so integration tests and demos can run the bus without Postgres instance.
+//!
+//! ```rust,ignore
+//! let pool = SqlitePoolOptions::new().max_connections(1).connect("sqlite::memory:").await?;
+//! create_sqlite_schema(&pool).await?;
+//! let pool: &'static SqlitePool = Box::leak(Box::new(pool));
+//!
+//! let res = MessageBus.execute_and_wait(MakeOrder { .. }, pool).await?;
+//! let published = pool.fetch_unprocessed(100).await?;
+//! ```
+//! In-memory database lives as long as its connection, so keep the pool to a single connection as above.
+//! `sequence`, `version`, `publish_class`, `trace_context`, `correlation_id` and `causation_id` columns are part of [SQLITE_SCHEMA], and rows are always fetched by publish class. As writes are serialized by SQLite, sequence of
+//! [enable_outbox_sequence](crate::prelude::enable_outbox_sequence) is a plain `MAX + 1` of the aggregate.
+use chrono::{DateTime, Utc};
+use sqlx::error::BoxDynError;
+use sqlx::sqlite::{SqliteTypeInfo, SqliteValueRef};
+use sqlx::{Encode, Sqlite, SqliteConnection, SqlitePool, Type};
+
+use super::{outbox_columns, OutBoxRow};
+use crate::bus_components::contexts::{Context, ReadContext, TReadRepository};
+use crate::prelude::{
+	clock, outbox_sequence_enabled, Backlog, BaseError, IdempotencyRecord, JournalOutcome, OutBox, Subscription, TCommandQueueStore, TIdempotencyStore, TOutboxHistory, TOutboxStore,
+	TSubscriptionStore, Ticket, TicketStatus,
+};
+use crate::snowflake::SnowFlake;
+
+/// Tables the bus writes to, created by [create_sqlite_schema]
+pub const SQLITE_SCHEMA: &str = r#"
+CREATE TABLE IF NOT EXISTS service_outbox (
+    id INTEGER PRIMARY KEY,
+    aggregate_id TEXT NOT NULL,
+    aggregate_name TEXT NOT NULL,
+    topic TEXT NOT NULL,
+    state BLOB NOT NULL,
+    content_type TEXT NOT NULL DEFAULT 'application/json',
+    processed BOOLEAN NOT NULL DEFAULT FALSE,
+    create_dt TEXT NOT NULL,
+    sequence INTEGER,
+    version INTEGER NOT NULL DEFAULT 1,
+    publish_class INTEGER NOT NULL DEFAULT 0,
+    trace_context TEXT,
+    correlation_id TEXT,
+    causation_id TEXT
+);
+CREATE INDEX IF NOT EXISTS service_outbox_unprocessed ON service_outbox (processed, publish_class, create_dt);
+CREATE TABLE IF NOT EXISTS command_log (
+    id INTEGER PRIMARY KEY,
+    command TEXT NOT NULL,
+    payload TEXT NOT NULL,
+    actor TEXT NOT NULL,
+    correlation_id TEXT,
+    outcome TEXT NOT NULL,
+    error TEXT,
+    recorded_at TEXT NOT NULL
+);
+CREATE TABLE IF NOT EXISTS command_idempotency (
+    idempotency_key TEXT PRIMARY KEY,
+    command TEXT NOT NULL,
+    response TEXT,
+    committed BOOLEAN NOT NULL DEFAULT FALSE,
+    recorded_at TEXT NOT NULL
+);
+CREATE TABLE IF NOT EXISTS command_queue (
+    id INTEGER PRIMARY KEY,
+    command TEXT NOT NULL,
+    payload TEXT NOT NULL,
+    status TEXT NOT NULL,
+    owner TEXT NOT NULL,
+    claimed_until TEXT,
+    response TEXT,
+    error TEXT,
+    queued_at TEXT NOT NULL,
+    updated_at TEXT NOT NULL
+);
+CREATE TABLE IF NOT EXISTS service_outbox_subscription (
+    topic TEXT NOT NULL,
+    destination TEXT NOT NULL,
+    PRIMARY KEY (topic, destination)
+);
+"#;
+
+pub async fn create_sqlite_schema(pool: &SqlitePool) -> Result<(), BaseError> {
+	sqlx::raw_sql(SQLITE_SCHEMA).execute(pool).await?;
+	Ok(())
+}
+
+impl Encode<'_, Sqlite> for SnowFlake {
+	fn encode_by_ref(&self, buf: &mut <Sqlite as sqlx::Database>::ArgumentBuffer<'_>) -> Result<sqlx::encode::IsNull, BoxDynError> {
+		<i64 as Encode<Sqlite>>::encode(self.0, buf)
+	}
+}
+
+impl<'r> sqlx::Decode<'r, Sqlite> for SnowFlake {
+	fn decode(value: SqliteValueRef<'r>) -> Result<Self, BoxDynError> {
+		Ok(SnowFlake(<i64 as sqlx::Decode<Sqlite>>::decode(value)?))
+	}
+}
+
+impl Type<Sqlite> for SnowFlake {
+	fn type_info() -> SqliteTypeInfo {
+		<i64 as Type<Sqlite>>::type_info()
+	}
+
+	fn compatible(ty: &SqliteTypeInfo) -> bool {
+		<i64 as Type<Sqlite>>::compatible(ty)
+	}
+}
+
+impl Context {
+	pub fn sqlite_transaction(&mut self) -> &mut SqliteConnection {
+		match self.sqlite_transaction.as_mut() {
+			Some(trx) => trx,
+			None => panic!("Transaction Has Not Begun!"),
+		}
+	}
+
+	pub(crate) async fn save_sqlite_outbox(&mut self) -> Result<(), BaseError> {
+		let outboxes = self.pending_outboxes()?;
+		OutBox::insert_all_sqlite(&outboxes, self.sqlite_transaction()).await
+	}
+
+	/// Mark idempotency key claimed by the command committed in its transaction. See [IdempotencyAspect](crate::prelude::IdempotencyAspect).
+	pub(crate) async fn save_sqlite_idempotency_claim(&mut self) -> Result<(), BaseError> {
+		let Some(claim) = self.super_ctx.take_idempotency_claim() else {
+			return Ok(());
+		};
+		let committed = sqlx::query("UPDATE command_idempotency SET committed = TRUE WHERE idempotency_key = ? AND command = ? AND NOT committed")
+			.bind(&claim.key)
+			.bind(claim.command)
+			.execute(self.sqlite_transaction())
+			.await?
+			.rows_affected();
+		if committed == 0 {
+			return Err(BaseError::ConcurrencyConflict(format!("Idempotency key {} is not claimed by the command", claim.key)));
+		}
+		Ok(())
+	}
+
+	pub(crate) async fn save_sqlite_journal_entry(&mut self) -> Result<(), BaseError> {
+		let Some(entry) = self.super_ctx.take_journal_entry() else {
+			return Ok(());
+		};
+		let (outcome, error) = match &entry.outcome {
+			JournalOutcome::Succeeded => (entry.outcome.as_str(), None),
+			JournalOutcome::Failed(error) => (entry.outcome.as_str(), Some(error)),
+		};
+		sqlx::query("INSERT INTO command_log (id, command, payload, actor, correlation_id, outcome, error, recorded_at) VALUES (?, ?, ?, ?, ?, ?, ?, ?)")
+			.bind(entry.id)
+			.bind(&entry.command)
+			.bind(&entry.payload)
+			.bind(serde_json::to_string(&entry.actor).map_err(|err| BaseError::DatabaseError(err.to_string()))?)
+			.bind(&entry.correlation_id)
+			.bind(outcome)
+			.bind(error)
+			.bind(entry.recorded_at)
+			.execute(self.sqlite_transaction())
+			.await?;
+		Ok(())
+	}
+}
+
+impl ReadContext {
+	pub fn sqlite_pool(&self) -> &'static SqlitePool {
+		let conn = self.connection();
+		match conn.downcast_ref::<&SqlitePool>().copied().or(conn.downcast_ref::<SqlitePool>()) {
+			Some(pool) => pool,
+			None => panic!("Connection Is Not SqlitePool!"),
+		}
+	}
+}
+
+impl OutBox {
+	pub(crate) async fn insert_all_sqlite(outboxes: &[OutBox], executor: &mut SqliteConnection) -> Result<(), BaseError> {
+		let sequence = match outbox_sequence_enabled() {
+			true => "(SELECT COALESCE(MAX(sequence), 0) + 1 FROM service_outbox WHERE aggregate_name = ?3 AND aggregate_id = ?2)",
+			false => "NULL",
+		};
+		let statement = format!(
+			"INSERT INTO service_outbox (id, aggregate_id, aggregate_name, topic, state, content_type, create_dt, version, publish_class, trace_context, correlation_id, causation_id, sequence) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, {})",
+			sequence
+		);
+		for outbox in outboxes {
+			sqlx::query(&statement)
+				.bind(outbox.id)
+				.bind(&outbox.aggregate_id)
+				.bind(&outbox.aggregate_name)
+				.bind(&outbox.topic)
+				.bind(&outbox.state)
+				.bind(&outbox.content_type)
+				.bind(outbox.create_dt)
+				.bind(outbox.version as i64)
+				.bind(outbox.publish_class.priority())
+				.bind(&outbox.trace_context)
+				.bind(&outbox.correlation_id)
+				.bind(&outbox.causation_id)
+				.execute(&mut *executor)
+				.await
+				.map_err(|err| {
+					tracing::error!("failed to insert outbox! {}", err);
+					BaseError::DatabaseError(err.to_string())
+				})?;
+		}
+		Ok(())
+	}
+}
+
+/// Returns `false` if the key is claimed already.
+async fn claim_idempotency_key(key: &str, command: &str, recorded_at: DateTime<Utc>, executor: impl sqlx::SqliteExecutor<'_>) -> Result<bool, BaseError> {
+	let claimed = sqlx::query("INSERT OR IGNORE INTO command_idempotency (idempotency_key, command, recorded_at) VALUES (?, ?, ?)")
+		.bind(key)
+		.bind(command)
+		.bind(recorded_at)
+		.execute(executor)
+		.await?
+		.rows_affected();
+	Ok(claimed > 0)
+}
+
+#[async_trait::async_trait]
+impl TIdempotencyStore for SqlitePool {
+	async fn get(&self, key: &str) -> Result<Option<IdempotencyRecord>, BaseError> {
+		let row = sqlx::query_as::<_, (String, String, Option<String>, bool, DateTime<Utc>)>(
+			"SELECT idempotency_key, command, response, committed, recorded_at FROM command_idempotency WHERE idempotency_key = ?",
+		)
+		.bind(key)
+		.fetch_optional(self)
+		.await?;
+		Ok(row.map(|(key, command, response, committed, recorded_at)| IdempotencyRecord {
+			key,
+			command,
+			response,
+			committed,
+			recorded_at,
+		}))
+	}
+
+	async fn claim(&self, key: &str, command: &str) -> Result<bool, BaseError> {
+		claim_idempotency_key(key, command, clock().now(), self).await
+	}
+
+	async fn complete(&self, key: &str, response: &str) -> Result<(), BaseError> {
+		sqlx::query("UPDATE command_idempotency SET response = ? WHERE idempotency_key = ?")
+			.bind(response)
+			.bind(key)
+			.execute(self)
+			.await?;
+		Ok(())
+	}
+
+	async fn release(&self, key: &str) -> Result<bool, BaseError> {
+		let released = sqlx::query("DELETE FROM command_idempotency WHERE idempotency_key = ? AND response IS NULL AND NOT committed")
+			.bind(key)
+			.execute(self)
+			.await?
+			.rows_affected();
+		Ok(released > 0)
+	}
+}
+
+type TicketRow = (i64, String, String, String, String, Option<DateTime<Utc>>, Option<String>, Option<String>, DateTime<Utc>, DateTime<Utc>);
+
+fn ticket((id, command, payload, status, owner, claimed_until, response, error, queued_at, updated_at): TicketRow) -> Result<Ticket, BaseError> {
+	let status = TicketStatus::parse(&status).ok_or_else(|| BaseError::DatabaseError(format!("Unknown ticket status {}", status)))?;
+	Ok(Ticket {
+		id,
+		command,
+		payload,
+		status,
+		owner: serde_json::from_str(&owner).map_err(|err| BaseError::DatabaseError(err.to_string()))?,
+		claimed_until,
+		response,
+		error,
+		queued_at,
+		updated_at,
+	})
+}
+
+/// Commands are queued in `command_queue` table of [SQLITE_SCHEMA]
+#[async_trait::async_trait]
+impl TCommandQueueStore for SqlitePool {
+	async fn enqueue(&self, ticket: &Ticket) -> Result<(), BaseError> {
+		sqlx::query("INSERT INTO command_queue (id, command, payload, status, owner, queued_at, updated_at) VALUES (?, ?, ?, ?, ?, ?, ?)")
+			.bind(ticket.id)
+			.bind(&ticket.command)
+			.bind(&ticket.payload)
+			.bind(ticket.status.as_str())
+			.bind(serde_json::to_string(&ticket.owner).map_err(|err| BaseError::DatabaseError(err.to_string()))?)
+			.bind(ticket.queued_at)
+			.bind(ticket.updated_at)
+			.execute(self)
+			.await?;
+		Ok(())
+	}
+
+	async fn claim_next(&self, claimed_until: DateTime<Utc>) -> Result<Option<Ticket>, BaseError> {
+		let now = clock().now();
+		let row = sqlx::query_as::<_, TicketRow>(
+			r#"
+			UPDATE command_queue SET status = 'running', claimed_until = ?, updated_at = ?
+			WHERE id = (SELECT id FROM command_queue WHERE status = 'queued' OR (status = 'running' AND claimed_until < ?) ORDER BY id LIMIT 1)
+			RETURNING id, command, payload, status, owner, claimed_until, response, error, queued_at, updated_at
+			"#,
+		)
+		.bind(claimed_until)
+		.bind(now)
+		.bind(now)
+		.fetch_optional(self)
+		.await?;
+		row.map(ticket).transpose()
+	}
+
+	async fn finish(&self, id: i64, claimed_until: DateTime<Utc>, result: Result<String, String>) -> Result<bool, BaseError> {
+		let (status, response, error) = match result {
+			Ok(response) => (TicketStatus::Succeeded, Some(response), None),
+			Err(error) => (TicketStatus::Failed, None, Some(error)),
+		};
+		let finished = sqlx::query("UPDATE command_queue SET status = ?, response = ?, error = ?, updated_at = ?, claimed_until = NULL WHERE id = ? AND status = 'running' AND claimed_until = ?")
+			.bind(status.as_str())
+			.bind(response)
+			.bind(error)
+			.bind(clock().now())
+			.bind(id)
+			.bind(claimed_until)
+			.execute(self)
+			.await?
+			.rows_affected();
+		Ok(finished > 0)
+	}
+
+	async fn get_ticket(&self, id: i64) -> Result<Option<Ticket>, BaseError> {
+		let row = sqlx::query_as::<_, TicketRow>("SELECT id, command, payload, status, owner, claimed_until, response, error, queued_at, updated_at FROM command_queue WHERE id = ?")
+			.bind(id)
+			.fetch_optional(self)
+			.await?;
+		row.map(ticket).transpose()
+	}
+}
+
+/// Subscriptions are kept in `service_outbox_subscription` table of [SQLITE_SCHEMA]
+#[async_trait::async_trait]
+impl TSubscriptionStore for SqlitePool {
+	async fn load(&self) -> Result<Vec<Subscription>, BaseError> {
+		let rows = sqlx::query_as::<_, (String, String)>("SELECT topic, destination FROM service_outbox_subscription ORDER BY topic, destination")
+			.fetch_all(self)
+			.await?;
+		Ok(rows.into_iter().map(|(topic, destination)| Subscription { topic, destination }).collect())
+	}
+
+	async fn save(&self, subscriptions: Vec<Subscription>) -> Result<(), BaseError> {
+		let mut trx = self.begin().await?;
+		sqlx::query("DELETE FROM service_outbox_subscription").execute(&mut *trx).await?;
+		for subscription in subscriptions {
+			sqlx::query("INSERT OR IGNORE INTO service_outbox_subscription (topic, destination) VALUES (?, ?)")
+				.bind(subscription.topic)
+				.bind(subscription.destination)
+				.execute(&mut *trx)
+				.await?;
+		}
+		trx.commit().await?;
+		Ok(())
+	}
+
+	async fn add(&self, subscription: &Subscription) -> Result<(), BaseError> {
+		sqlx::query("INSERT OR IGNORE INTO service_outbox_subscription (topic, destination) VALUES (?, ?)")
+			.bind(&subscription.topic)
+			.bind(&subscription.destination)
+			.execute(self)
+			.await?;
+		Ok(())
+	}
+
+	async fn remove(&self, subscription: &Subscription) -> Result<(), BaseError> {
+		sqlx::query("DELETE FROM service_outbox_subscription WHERE topic = ? AND destination = ?")
+			.bind(&subscription.topic)
+			.bind(&subscription.destination)
+			.execute(self)
+			.await?;
+		Ok(())
+	}
+}
+
+#[async_trait::async_trait]
+impl TOutboxStore for SqlitePool {
+	async fn fetch_unprocessed(&self, limit: usize) -> Result<Vec<OutBox>, BaseError> {
+		let order = match outbox_sequence_enabled() {
+			true => "publish_class, create_dt, sequence, id",
+			false => "publish_class, create_dt, id",
+		};
+		// Every optional column is part of `SQLITE_SCHEMA`
+		let query = format!("SELECT {} FROM service_outbox WHERE processed = FALSE ORDER BY {} LIMIT ?", outbox_columns(|_| true), order);
+		let rows = sqlx::query_as::<_, OutBoxRow>(&query).bind(limit as i64).fetch_all(self).await?;
+		Ok(rows.into_iter().map(OutBox::from).collect())
+	}
+
+	async fn mark_processed(&self, id: i64) -> Result<(), BaseError> {
+		sqlx::query("UPDATE service_outbox SET processed = TRUE WHERE id = ?").bind(id).execute(self).await?;
+		Ok(())
+	}
+
+	async fn backlog(&self) -> Result<Option<Backlog>, BaseError> {
+		let (count, oldest) = sqlx::query_as::<_, (i64, Option<DateTime<Utc>>)>("SELECT COUNT(*), MIN(create_dt) FROM service_outbox WHERE processed = FALSE")
+			.fetch_one(self)
+			.await?;
+		Ok(Some(Backlog {
+			count: count as u64,
+			oldest_age: oldest.and_then(|oldest| (clock().now() - oldest).to_std().ok()),
+		}))
+	}
+}
+
+/// Every row of `service_outbox`, processed or not
+#[async_trait::async_trait]
+impl TOutboxHistory for SqlitePool {
+	async fn fetch_history(&self, after: Option<i64>, limit: usize) -> Result<Vec<OutBox>, BaseError> {
+		let query = format!("SELECT {} FROM service_outbox WHERE id > ? ORDER BY id LIMIT ?", outbox_columns(|_| true));
+		let rows = sqlx::query_as::<_, OutBoxRow>(&query).bind(after.unwrap_or(i64::MIN)).bind(limit as i64).fetch_all(self).await?;
+		Ok(rows.into_iter().map(OutBox::from).collect())
+	}
+}
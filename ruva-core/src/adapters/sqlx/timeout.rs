@@ -0,0 +1,190 @@
+//! ### Persistent workflow timeouts
+//! "If `PaymentConfirmed` is not received within 2h, emit `PaymentTimedOut`."
+//! Timeout is stored in `service_timeout` table in the same transaction as the command, so it survives restarts.
+//! When it is due, the event is moved to `service_outbox` in a single transaction and published by the relay like any other event,
+//! with the version, publish class, trace context and correlation of the dispatch that scheduled it.
+//!
+//! ```rust,ignore
+//! // In the command handler that starts the workflow
+//! let timed_out = PaymentTimedOut { order_id: order.id };
+//! ctx.schedule_timeout(ScheduledTimeout::new(order.id.to_string(), &timed_out, chrono::Duration::hours(2))).await?;
+//!
+//! // In the event handler of PaymentConfirmed
+//! ctx.cancel_timeout(&order.id.to_string(), "PaymentTimedOut").await?;
+//!
+//! // On boot
+//! let _handle = spawn_timeout_firing(pool.clone(), std::time::Duration::from_secs(10), clock());
+//! ```
+//! Firing is safe to run on every instance - a due timeout is deleted by exactly one of them, so no leader election is required.
+//!
+//! ```sql
+//! CREATE TABLE service_timeout (
+//!     id BIGINT PRIMARY KEY,
+//!     key TEXT NOT NULL,
+//!     aggregate_name TEXT NOT NULL,
+//!     topic TEXT NOT NULL,
+//!     state TEXT NOT NULL,
+//!     version INTEGER NOT NULL DEFAULT 1,
+//!     publish_class SMALLINT NOT NULL DEFAULT 0,
+//!     trace_context TEXT,
+//!     correlation_id TEXT,
+//!     causation_id TEXT,
+//!     due_at TIMESTAMPTZ NOT NULL
+//! );
+//! CREATE INDEX ON service_timeout (due_at);
+//! CREATE INDEX ON service_timeout (key, topic);
+//! ```
+use std::sync::Arc;
+
+use chrono::{DateTime, Utc};
+use sqlx::PgPool;
+
+use crate::bus_components::contexts::Context;
+use crate::prelude::{inject_trace_context, BaseError, OutBox, PublishClass, SnowFlake, TClock, TEvent};
+
+#[derive(Debug, Clone)]
+pub struct ScheduledTimeout {
+	pub id: i64,
+	/// Identifies the workflow instance, e.g. order id. Used with topic to cancel the timeout.
+	pub key: String,
+	pub aggregate_name: String,
+	pub topic: String,
+	pub state: String,
+	pub version: u32,
+	pub publish_class: PublishClass,
+	/// Counted from the time it is scheduled by the clock of the dispatch
+	pub after: chrono::Duration,
+}
+
+impl ScheduledTimeout {
+	/// Schedule `event` to be emitted after `after`.
+	pub fn new(key: impl Into<String>, event: &impl TEvent, after: chrono::Duration) -> Self {
+		let metadata = event.metadata();
+		Self {
+			id: *SnowFlake::generate(),
+			key: key.into(),
+			aggregate_name: metadata.aggregate_name,
+			topic: metadata.topic,
+			state: event.state(),
+			version: metadata.version,
+			publish_class: metadata.publish_class,
+			after,
+		}
+	}
+}
+
+impl Context {
+	pub async fn schedule_timeout(&mut self, timeout: ScheduledTimeout) -> Result<(), BaseError> {
+		let due_at = self.now() + timeout.after;
+		let (correlation_id, causation_id) = (self.super_ctx.correlation_id.clone(), self.super_ctx.message_id.clone());
+		sqlx::query(
+			r#"
+            INSERT INTO service_timeout (id, key, aggregate_name, topic, state, version, publish_class, trace_context, correlation_id, causation_id, due_at)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11)
+            "#,
+		)
+		.bind(timeout.id)
+		.bind(timeout.key)
+		.bind(timeout.aggregate_name)
+		.bind(timeout.topic)
+		.bind(timeout.state)
+		.bind(timeout.version as i32)
+		.bind(timeout.publish_class.priority())
+		.bind(inject_trace_context())
+		.bind(correlation_id)
+		.bind(causation_id)
+		.bind(due_at)
+		.execute(self.transaction())
+		.await?;
+		Ok(())
+	}
+
+	/// Cancel pending timeouts of `topic` for workflow instance `key`. Returns whether any timeout was cancelled.
+	pub async fn cancel_timeout(&mut self, key: &str, topic: &str) -> Result<bool, BaseError> {
+		let cancelled = sqlx::query("DELETE FROM service_timeout WHERE key = $1 AND topic = $2")
+			.bind(key)
+			.bind(topic)
+			.execute(self.transaction())
+			.await?
+			.rows_affected();
+		Ok(cancelled > 0)
+	}
+}
+
+#[derive(sqlx::FromRow)]
+struct DueTimeout {
+	id: i64,
+	key: String,
+	aggregate_name: String,
+	topic: String,
+	state: String,
+	version: i32,
+	publish_class: i16,
+	trace_context: Option<String>,
+	correlation_id: Option<String>,
+	causation_id: Option<String>,
+}
+
+/// Move timeouts due at `now` to outbox, as rows created at `now`. Returns the number of fired timeouts.
+pub async fn fire_due_timeouts(pool: &PgPool, now: DateTime<Utc>) -> Result<u64, BaseError> {
+	let mut trx = pool.begin().await?;
+	let due = sqlx::query_as::<_, DueTimeout>(
+		r#"
+        DELETE FROM service_timeout WHERE due_at <= $1
+        RETURNING id, key, aggregate_name, topic, state, version, publish_class, trace_context, correlation_id, causation_id
+        "#,
+	)
+	.bind(now)
+	.fetch_all(&mut *trx)
+	.await?;
+	let outboxes = due
+		.into_iter()
+		.map(|timeout| {
+			Ok(OutBox {
+				id: timeout.id,
+				create_dt: now,
+				version: timeout.version as u32,
+				publish_class: PublishClass::from_priority(timeout.publish_class),
+				trace_context: timeout.trace_context,
+				correlation_id: timeout.correlation_id,
+				causation_id: timeout.causation_id,
+				..OutBox::encoded(timeout.key, timeout.aggregate_name, timeout.topic, &timeout.state)?
+			})
+		})
+		.collect::<Result<Vec<_>, BaseError>>()?;
+	if !outboxes.is_empty() {
+		OutBox::insert_all(&outboxes, &mut trx).await?;
+	}
+	trx.commit().await?;
+	Ok(outboxes.len() as u64)
+}
+
+/// Fire due timeouts every `interval`, telling the time by `clock` - usually the one of the dispatches, [clock()](crate::prelude::clock).
+pub fn spawn_timeout_firing(pool: PgPool, interval: std::time::Duration, clock: Arc<dyn TClock>) -> tokio::task::JoinHandle<()> {
+	tokio::spawn(async move {
+		loop {
+			match fire_due_timeouts(&pool, clock.now()).await {
+				Ok(0) => {}
+				Ok(fired) => tracing::info!("{} timeouts fired", fired),
+				Err(err) => tracing::error!("Failed to fire timeouts! {:?}", err),
+			}
+			tokio::time::sleep(interval).await;
+		}
+	})
+}
+
+#[test]
+fn test_scheduled_timeout() {
+	struct PaymentTimedOut;
+	impl TEvent for PaymentTimedOut {
+		fn state(&self) -> String {
+			"{}".into()
+		}
+	}
+
+	let timeout = ScheduledTimeout::new("order-1", &PaymentTimedOut, chrono::Duration::hours(2));
+	assert_eq!(timeout.topic, "PaymentTimedOut");
+	assert_eq!(timeout.key, "order-1");
+	assert_eq!(timeout.after, chrono::Duration::hours(2));
+	assert_eq!(timeout.version, crate::prelude::INITIAL_EVENT_VERSION);
+}
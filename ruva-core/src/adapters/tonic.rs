@@ -0,0 +1,216 @@
+//! ### gRPC service
+//! Enabled by `ruva-tonic` feature. `grpc_command_service!` exposes commands as unary RPCs of a tonic service,
+//! one method per command named after its type. Messages are JSON encoded with [JsonCodec], so commands only need
+//! `Serialize`/`Deserialize` and no `.proto` file is compiled. Error of the command is answered with [grpc_status].
+//!
+//! ```rust,ignore
+//! grpc_command_service!(pub OrderService = "order.OrderService" { MakeOrder, CancelOrder });
+//!
+//! // POST /order.OrderService/MakeOrder
+//! Server::builder().add_service(OrderService::new(conn)).serve(addr).await?;
+//! ```
+use std::marker::PhantomData;
+
+use bytes::{Buf, BufMut};
+use serde::{de::DeserializeOwned, Serialize};
+use tonic::{
+	body::Body,
+	codec::{Codec, DecodeBuf, Decoder, EncodeBuf, Encoder},
+	codegen::{http, BoxFuture, Context as TaskContext, Poll, Service, StdError},
+	server::Grpc,
+	Code, Request, Response, Status,
+};
+
+use crate::prelude::{ApplicationError, BaseError, MessageBus, TCommandSpec, TConnection, THttpStatus, TMessageBus};
+
+/// Encodes `E` and decodes `D` as JSON
+pub struct JsonCodec<E, D>(PhantomData<fn(E, D)>);
+
+impl<E, D> Default for JsonCodec<E, D> {
+	fn default() -> Self {
+		Self(PhantomData)
+	}
+}
+
+impl<E, D> Codec for JsonCodec<E, D>
+where
+	E: Serialize + Send + 'static,
+	D: DeserializeOwned + Send + 'static,
+{
+	type Encode = E;
+	type Decode = D;
+	type Encoder = JsonEncoder<E>;
+	type Decoder = JsonDecoder<D>;
+
+	fn encoder(&mut self) -> Self::Encoder {
+		JsonEncoder(PhantomData)
+	}
+	fn decoder(&mut self) -> Self::Decoder {
+		JsonDecoder(PhantomData)
+	}
+}
+
+pub struct JsonEncoder<E>(PhantomData<fn(E)>);
+
+impl<E: Serialize> Encoder for JsonEncoder<E> {
+	type Item = E;
+	type Error = Status;
+
+	fn encode(&mut self, item: Self::Item, dst: &mut EncodeBuf<'_>) -> Result<(), Self::Error> {
+		serde_json::to_writer(dst.writer(), &item).map_err(|err| Status::internal(err.to_string()))
+	}
+}
+
+pub struct JsonDecoder<D>(PhantomData<fn() -> D>);
+
+impl<D: DeserializeOwned> Decoder for JsonDecoder<D> {
+	type Item = D;
+	type Error = Status;
+
+	fn decode(&mut self, src: &mut DecodeBuf<'_>) -> Result<Option<Self::Item>, Self::Error> {
+		serde_json::from_reader(src.reader()).map(Some).map_err(|err| Status::invalid_argument(err.to_string()))
+	}
+}
+
+/// gRPC status of `error`, translated from its [THttpStatus]. Message is [THttpStatus::public_message] and details are
+/// [THttpStatus::public_details] as JSON. The error itself is logged, not sent.
+pub fn grpc_status<E: ApplicationError + THttpStatus>(error: &E) -> Status {
+	let code = match error.http_status() {
+		400 => Code::InvalidArgument,
+		401 => Code::Unauthenticated,
+		403 => Code::PermissionDenied,
+		404 => Code::NotFound,
+		409 => Code::Aborted,
+		412 | 422 => Code::FailedPrecondition,
+		429 => Code::ResourceExhausted,
+		501 => Code::Unimplemented,
+		502 | 503 => Code::Unavailable,
+		504 => Code::DeadlineExceeded,
+		_ => Code::Internal,
+	};
+	match code {
+		Code::Internal | Code::Unavailable | Code::DeadlineExceeded => tracing::error!(?code, "Command failed! {:?}", error),
+		_ => tracing::warn!(?code, "Command failed. {:?}", error),
+	}
+	match error.public_details() {
+		Some(details) => Status::with_details(code, error.public_message(), details.to_string().into()),
+		None => Status::new(code, error.public_message()),
+	}
+}
+
+/// Name of the RPC method of `C`, that is, its type name without path
+pub fn rpc_method_name<C>() -> &'static str {
+	let name = std::any::type_name::<C>();
+	name.rsplit("::").next().unwrap_or(name)
+}
+
+/// Unary RPC dispatching `C` on `MessageBus`
+pub struct CommandRpc<C> {
+	conn: &'static dyn TConnection,
+	_command: PhantomData<fn(C)>,
+}
+
+impl<C> CommandRpc<C> {
+	pub fn new(conn: &'static dyn TConnection) -> Self {
+		Self { conn, _command: PhantomData }
+	}
+}
+
+impl<C> Service<Request<C>> for CommandRpc<C>
+where
+	C: TCommandSpec,
+	C::Error: THttpStatus + std::convert::From<BaseError>,
+	BaseError: std::convert::From<C::Error>,
+	MessageBus: TMessageBus<C::Response, C::Error, C>,
+{
+	type Response = Response<C::Response>;
+	type Error = Status;
+	type Future = BoxFuture<Self::Response, Self::Error>;
+
+	fn poll_ready(&mut self, _: &mut TaskContext<'_>) -> Poll<Result<(), Self::Error>> {
+		Poll::Ready(Ok(()))
+	}
+
+	fn call(&mut self, request: Request<C>) -> Self::Future {
+		let conn = self.conn;
+		Box::pin(async move { MessageBus.dispatch(request.into_inner(), conn).await.map(Response::new).map_err(|err| grpc_status(&err)) })
+	}
+}
+
+/// Answer gRPC request of `C`. Called by the service `grpc_command_service!` generates.
+pub async fn serve_command<C, B>(conn: &'static dyn TConnection, request: http::Request<B>) -> http::Response<Body>
+where
+	C: TCommandSpec + DeserializeOwned,
+	C::Response: Serialize,
+	C::Error: THttpStatus + std::convert::From<BaseError>,
+	BaseError: std::convert::From<C::Error>,
+	MessageBus: TMessageBus<C::Response, C::Error, C>,
+	B: tonic::codegen::Body + Send + 'static,
+	B::Error: Into<StdError> + Send + 'static,
+{
+	Grpc::new(JsonCodec::<C::Response, C>::default()).unary(CommandRpc::<C>::new(conn), request).await
+}
+
+/// Define tonic service named `$service_name` with unary RPC of each command, dispatched on `MessageBus` and JSON encoded.
+#[macro_export]
+macro_rules! grpc_command_service {
+	($vis:vis $name:ident = $service_name:literal { $($command:ty),* $(,)? }) => {
+		#[derive(Clone)]
+		$vis struct $name {
+			conn: &'static dyn ::ruva::TConnection,
+		}
+
+		impl $name {
+			$vis fn new(conn: &'static dyn ::ruva::TConnection) -> Self {
+				Self { conn }
+			}
+		}
+
+		impl ::ruva::tonic::server::NamedService for $name {
+			const NAME: &'static str = $service_name;
+		}
+
+		impl<B> ::ruva::tonic::codegen::Service<::ruva::tonic::codegen::http::Request<B>> for $name
+		where
+			B: ::ruva::tonic::codegen::Body + Send + 'static,
+			B::Error: Into<::ruva::tonic::codegen::StdError> + Send + 'static,
+		{
+			type Response = ::ruva::tonic::codegen::http::Response<::ruva::tonic::body::Body>;
+			type Error = ::std::convert::Infallible;
+			type Future = ::ruva::tonic::codegen::BoxFuture<Self::Response, Self::Error>;
+
+			fn poll_ready(&mut self, _: &mut ::ruva::tonic::codegen::Context<'_>) -> ::ruva::tonic::codegen::Poll<Result<(), Self::Error>> {
+				::ruva::tonic::codegen::Poll::Ready(Ok(()))
+			}
+
+			fn call(&mut self, request: ::ruva::tonic::codegen::http::Request<B>) -> Self::Future {
+				let conn = self.conn;
+				let method = request.uri().path().rsplit('/').next().unwrap_or_default().to_string();
+				$(
+					if method == ::ruva::rpc_method_name::<$command>() {
+						return Box::pin(async move { Ok(::ruva::serve_command::<$command, B>(conn, request).await) });
+					}
+				)*
+				Box::pin(async move { Ok(::ruva::tonic::Status::unimplemented(method).into_http()) })
+			}
+		}
+	};
+}
+
+#[cfg(test)]
+mod test {
+	use super::*;
+
+	#[test]
+	fn test_grpc_status_follows_http_status() {
+		assert_eq!(grpc_status(&BaseError::NotFound).code(), Code::NotFound);
+		assert_eq!(grpc_status(&BaseError::TransactionConflict("deadlock".into())).code(), Code::Aborted);
+		assert_eq!(grpc_status(&BaseError::ServiceError).code(), Code::Internal);
+	}
+
+	#[test]
+	fn test_rpc_method_name() {
+		struct MakeOrder;
+		assert_eq!(rpc_method_name::<MakeOrder>(), "MakeOrder");
+	}
+}
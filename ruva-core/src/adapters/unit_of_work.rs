@@ -0,0 +1,210 @@
+//! [TUnitOfWork] of [Context] on the database its connection points to - `PgPool` with `sqlx-postgres`, `SqlitePool` with `sqlx-sqlite`
+//! and `mongodb::Database` with `mongodb`, or on [FakeOutbox] in tests.
+use crate::bus_components::contexts::Context;
+use crate::prelude::{record_side_effect, BaseError, CommitStage, FakeOutbox, OutBox, TUnitOfWork};
+use crate::testing::FakeTransaction;
+
+enum Transaction {
+	#[cfg(feature = "sqlx-postgres")]
+	Postgres(sqlx::Transaction<'static, sqlx::Postgres>),
+	#[cfg(feature = "sqlx-sqlite")]
+	Sqlite(sqlx::Transaction<'static, sqlx::Sqlite>),
+	#[cfg(feature = "mongodb")]
+	Mongo(Box<mongodb::ClientSession>),
+	Fake(FakeTransaction),
+}
+
+impl Transaction {
+	async fn commit(self) -> Result<(), BaseError> {
+		match self {
+			#[cfg(feature = "sqlx-postgres")]
+			Self::Postgres(trx) => Ok(trx.commit().await?),
+			#[cfg(feature = "sqlx-sqlite")]
+			Self::Sqlite(trx) => Ok(trx.commit().await?),
+			#[cfg(feature = "mongodb")]
+			Self::Mongo(mut session) => Ok(session.commit_transaction().await?),
+			Self::Fake(trx) => {
+				trx.commit();
+				Ok(())
+			}
+		}
+	}
+
+	async fn rollback(self) -> Result<(), BaseError> {
+		match self {
+			#[cfg(feature = "sqlx-postgres")]
+			Self::Postgres(trx) => Ok(trx.rollback().await?),
+			#[cfg(feature = "sqlx-sqlite")]
+			Self::Sqlite(trx) => Ok(trx.rollback().await?),
+			#[cfg(feature = "mongodb")]
+			Self::Mongo(mut session) => Ok(session.abort_transaction().await?),
+			Self::Fake(_) => Ok(()),
+		}
+	}
+}
+
+impl Context {
+	fn take_transaction(&mut self) -> Option<Transaction> {
+		#[cfg(feature = "sqlx-postgres")]
+		if let Some(trx) = self.pg_transaction.take() {
+			return Some(Transaction::Postgres(trx));
+		}
+		#[cfg(feature = "sqlx-sqlite")]
+		if let Some(trx) = self.sqlite_transaction.take() {
+			return Some(Transaction::Sqlite(trx));
+		}
+		#[cfg(feature = "mongodb")]
+		if let Some(session) = self.mongo_session.take() {
+			return Some(Transaction::Mongo(Box::new(session)));
+		}
+		self.fake_transaction.take().map(Transaction::Fake)
+	}
+
+	fn has_transaction(&self) -> bool {
+		#[cfg(feature = "sqlx-postgres")]
+		if self.pg_transaction.is_some() {
+			return true;
+		}
+		#[cfg(feature = "sqlx-sqlite")]
+		if self.sqlite_transaction.is_some() {
+			return true;
+		}
+		#[cfg(feature = "mongodb")]
+		if self.mongo_session.is_some() {
+			return true;
+		}
+		self.fake_transaction.is_some()
+	}
+
+	/// Externally notifiable events of the unit of work as outbox rows
+	pub(crate) fn pending_outboxes(&mut self) -> Result<Vec<OutBox>, BaseError> {
+		let now = self.now();
+		let outboxes = self
+			.curr_events
+			.iter()
+			.filter(|e| e.externally_notifiable())
+			.map(|e| Ok(OutBox { create_dt: now, ..self.outbox(e)? }))
+			.collect::<Result<Vec<_>, BaseError>>()?;
+		// * Record-only handler reports the events instead of publishing them. See `handler_sandbox`.
+		if crate::bus_components::sandbox::is_sandboxed() {
+			for outbox in outboxes {
+				record_side_effect("outbox", format!("publish {} of {} {}", outbox.topic, outbox.aggregate_name, outbox.aggregate_id));
+			}
+			return Ok(vec![]);
+		}
+		self.uncommitted_token.merge(outboxes.iter().map(|outbox| outbox.id).collect());
+		Ok(outboxes)
+	}
+}
+
+impl TUnitOfWork for Context {
+	async fn begin(&mut self) -> Result<(), BaseError> {
+		if self.has_transaction() {
+			tracing::warn!("Transaction Begun Already!");
+			return Err(BaseError::TransactionError);
+		}
+		let conn = self.super_ctx.conn;
+
+		#[cfg(feature = "sqlx-postgres")]
+		if let Some(pool) = conn.downcast_ref::<&sqlx::PgPool>().copied().or(conn.downcast_ref::<sqlx::PgPool>()) {
+			self.pg_transaction = Some(pool.begin().await?);
+			return Ok(());
+		}
+		#[cfg(feature = "sqlx-sqlite")]
+		if let Some(pool) = conn.downcast_ref::<&sqlx::SqlitePool>().copied().or(conn.downcast_ref::<sqlx::SqlitePool>()) {
+			self.sqlite_transaction = Some(pool.begin().await?);
+			return Ok(());
+		}
+		#[cfg(feature = "mongodb")]
+		if let Some(database) = conn.downcast_ref::<&mongodb::Database>().copied().or(conn.downcast_ref::<mongodb::Database>()) {
+			let mut session = database.client().start_session().await?;
+			session.start_transaction().await?;
+			self.mongo_session = Some(session);
+			return Ok(());
+		}
+		if let Some(outbox) = conn.downcast_ref::<FakeOutbox>() {
+			self.fake_transaction = Some(FakeTransaction::new(outbox));
+			return Ok(());
+		}
+		tracing::error!("Transaction Error!");
+		Err(BaseError::TransactionError)
+	}
+
+	async fn _commit(&mut self) -> Result<(), BaseError> {
+		match self.take_transaction() {
+			None => panic!("Tranasction Has Not Begun!"),
+			Some(trx) if self.is_dry_run() => {
+				tracing::info!("Dry run. Transaction is rolled back instead of committed.");
+				trx.rollback().await
+			}
+			Some(trx) => {
+				let started = std::time::Instant::now();
+				trx.commit().await?;
+				self.record_commit_duration(started.elapsed());
+				self.super_ctx.record_consistency_token(std::mem::take(&mut self.uncommitted_token));
+				Ok(())
+			}
+		}
+	}
+
+	async fn rollback(&mut self) -> Result<(), BaseError> {
+		self.curr_events.clear();
+		self.uncommitted_token = Default::default();
+		self.discard_stage_hooks();
+		match self.take_transaction() {
+			None => panic!("Tranasction Has Not Begun!"),
+			Some(trx) => trx.rollback().await,
+		}
+	}
+
+	async fn close(&mut self) {
+		if let Some(trx) = self.take_transaction() {
+			let _ = trx.rollback().await;
+		}
+	}
+
+	async fn process_internal_events(&mut self) -> Result<(), BaseError> {
+		self.send_internally_notifiable_messages().await;
+		Ok(())
+	}
+
+	async fn process_external_events(&mut self) -> Result<(), BaseError> {
+		// Checked here as this is the last step before commit
+		self.check_event_limit()?;
+		if self.fake_transaction.is_some() {
+			let outboxes = self.pending_outboxes()?;
+			self.fake_transaction.as_mut().unwrap().write(outboxes);
+			// * Not kept by the fake
+			self.super_ctx.take_idempotency_claim();
+			self.super_ctx.take_journal_entry();
+			return Ok(());
+		}
+		#[cfg(feature = "sqlx-sqlite")]
+		if self.sqlite_transaction.is_some() {
+			self.save_sqlite_outbox().await?;
+			self.save_sqlite_idempotency_claim().await?;
+			return self.save_sqlite_journal_entry().await;
+		}
+		#[cfg(feature = "mongodb")]
+		if self.mongo_session.is_some() {
+			self.save_mongo_outbox().await?;
+			self.save_mongo_idempotency_claim().await?;
+			return self.save_mongo_journal_entry().await;
+		}
+		#[cfg(feature = "sqlx-postgres")]
+		{
+			self.save_pg_outbox().await?;
+			self.save_pg_idempotency_claim().await?;
+			self.save_pg_journal_entry().await?;
+		}
+		Ok(())
+	}
+
+	async fn run_stage(&mut self, stage: CommitStage) -> Result<(), BaseError> {
+		self.run_stage_hooks(stage).await
+	}
+
+	fn discard_stages(&mut self) {
+		self.discard_stage_hooks();
+	}
+}
@@ -14,4 +14,75 @@ pub trait TAggregate: Send + Sync + Default {
 
 	fn take_events(&mut self) -> std::collections::VecDeque<std::sync::Arc<dyn TEvent>>;
 	fn raise_event(&mut self, event: std::sync::Arc<dyn TEvent>);
+
+	/// Fields changed through setters. Repository can use it to update only changed columns.
+	fn dirty_fields(&self) -> Vec<&'static str> {
+		vec![]
+	}
+
+	/// Events about the mutation of the aggregate itself, such as `OrderUpdated` with changed fields.
+	/// Taken by `#[event_hook]` before the events raised with [TAggregate::raise_event].
+	fn mutation_events(&mut self) -> VecDeque<std::sync::Arc<dyn TEvent>> {
+		VecDeque::new()
+	}
+}
+
+/// Field of aggregate changed through setter, carried by the event of `#[auto_event(..)]`
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct FieldChange {
+	pub field: String,
+	pub old: serde_json::Value,
+	pub new: serde_json::Value,
+}
+
+/// Loads aggregate `A` by its id. Implement it on the unit of work so that `load_*` helpers generated for `#[reference(A)]` fields can be used.
+pub trait TLoadAggregate<A, Id: ?Sized>: Send {
+	fn load_aggregate(&mut self, id: &Id) -> impl std::future::Future<Output = Result<A, crate::prelude::BaseError>> + Send;
+}
+
+/// Aggregate guarded by optimistic concurrency. Implemented by `#[aggregate]` for the field marked with `#[version]`.
+pub trait TVersioned {
+	/// Version the aggregate was loaded at
+	fn version(&self) -> i64;
+	/// Called once the update at the current version is written
+	fn bump_version(&mut self);
+}
+
+/// Build `SET` clause of `UPDATE` statement only with given columns. Placeholders start from `first_placeholder`.
+/// ## Example
+/// ```rust
+/// # use ruva_core::prelude::*;
+/// # struct Order;
+/// # impl Order { fn dirty_fields(&self) -> Vec<&'static str> { vec!["status", "version"] } }
+/// # let aggregate = Order;
+/// let set_clause = update_set_clause(&aggregate.dirty_fields(), 2);
+/// let query = format!("UPDATE orders SET {set_clause} WHERE id = $1");
+/// ```
+pub fn update_set_clause(columns: &[&str], first_placeholder: usize) -> String {
+	columns
+		.iter()
+		.enumerate()
+		.map(|(i, column)| format!("{} = ${}", column, first_placeholder + i))
+		.collect::<Vec<_>>()
+		.join(", ")
+}
+
+/// Same as [update_set_clause] but also bumps `version_column`, for `UPDATE` of [TVersioned] aggregate.
+/// `version_column` is left out of `columns` as it is bumped by the database, so don't bind value for it.
+/// ## Example
+/// ```rust
+/// # use ruva_core::prelude::*;
+/// # struct Order;
+/// # impl Order { fn dirty_fields(&self) -> Vec<&'static str> { vec!["status", "version"] } }
+/// # let aggregate = Order;
+/// let set_clause = versioned_set_clause(&aggregate.dirty_fields(), "version", 3);
+/// let query = format!("UPDATE orders SET {set_clause} WHERE id = $1 AND version = $2");
+/// ```
+pub fn versioned_set_clause(columns: &[&str], version_column: &str, first_placeholder: usize) -> String {
+	let columns = columns.iter().copied().filter(|column| *column != version_column).collect::<Vec<_>>();
+	let set_clause = update_set_clause(&columns, first_placeholder);
+	match set_clause.is_empty() {
+		true => format!("{} = {} + 1", version_column, version_column),
+		false => format!("{}, {} = {} + 1", set_clause, version_column, version_column),
+	}
 }
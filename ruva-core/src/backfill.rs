@@ -0,0 +1,243 @@
+//! ### Backfill
+//! Managed backfill of a new projection. Declare where items come from and how they are projected, then run it
+//! from a command handler so that it goes through the bus like any other command.
+//!
+//! ```rust,ignore
+//! struct OrderSummaryBackfill(PgPool);
+//!
+//! #[async_trait]
+//! impl TBackfillJob<Context> for OrderSummaryBackfill {
+//!     type Item = Order;
+//!     fn spec(&self) -> BackfillSpec {
+//!         BackfillSpec::new("order_summary").batch_size(500).rate_limit(Duration::from_millis(100))
+//!     }
+//!     async fn fetch(&self, after: Option<i64>, limit: usize) -> Result<Vec<(i64, Order)>, BaseError> {
+//!         // SELECT * FROM orders WHERE id > $1 ORDER BY id LIMIT $2
+//!     }
+//!     async fn project(&self, items: Vec<Order>, ctx: &mut Context) -> Result<(), BaseError> {
+//!         // INSERT INTO order_summary ... on `ctx.transaction()`
+//!     }
+//! }
+//!
+//! pub async fn run_order_summary_backfill(_cmd: RunOrderSummaryBackfill, ctx: &mut Context) -> Result<ServiceResponse, ServiceError> {
+//!     let done = run_backfill(&OrderSummaryBackfill(pool()), ctx).await?;
+//!     Ok(done.processed.into())
+//! }
+//! ```
+//! Cursor of the last projected item is checkpointed after every batch, so running the same job again resumes where it stopped.
+//! [BackfillProgressed] is raised on the context for every batch, which is handed over to event handlers on commit.
+//! Checkpoints are saved through [TBackfillCheckpoint] of the context, that is, in the transaction of the command, so they are
+//! committed together with the progress events and with the projection written on the context. As the whole run is committed
+//! with the command, give large backfills a source that ends early, and run the command again to continue.
+use std::{collections::VecDeque, future::Future, sync::Arc, time::Duration};
+
+use async_trait::async_trait;
+use serde::Serialize;
+
+use crate::prelude::{BaseError, TEvent, TSetCurrentEvents};
+
+pub struct BackfillSpec {
+	pub name: String,
+	pub batch_size: usize,
+	/// Delay between batches
+	pub rate_limit: Option<Duration>,
+}
+
+impl BackfillSpec {
+	pub fn new(name: impl Into<String>) -> Self {
+		Self {
+			name: name.into(),
+			batch_size: 100,
+			rate_limit: None,
+		}
+	}
+	pub fn batch_size(mut self, batch_size: usize) -> Self {
+		assert!(batch_size > 0, "Batch size must be greater than 0");
+		self.batch_size = batch_size;
+		self
+	}
+	pub fn rate_limit(mut self, delay: Duration) -> Self {
+		self.rate_limit = Some(delay);
+		self
+	}
+}
+
+/// Backfill run on context `C`. Job that doesn't write through the context implements it for any `C: Send`.
+#[async_trait]
+pub trait TBackfillJob<C: Send>: Send + Sync {
+	type Item: Send;
+
+	fn spec(&self) -> BackfillSpec;
+
+	/// Source of the backfill - source query or event range.
+	/// Return at most `limit` items whose cursor is greater than `after`, ordered by cursor.
+	async fn fetch(&self, after: Option<i64>, limit: usize) -> Result<Vec<(i64, Self::Item)>, BaseError>;
+
+	/// Target projection. Written on `ctx`, it is committed if and only if the checkpoint is.
+	async fn project(&self, items: Vec<Self::Item>, ctx: &mut C) -> Result<(), BaseError>;
+}
+
+/// Checkpoints of [run_backfill]. Implemented on the unit of work so that the checkpoint is saved in the transaction of the command.
+pub trait TBackfillCheckpoint: Send {
+	fn load_checkpoint(&mut self, name: &str) -> impl Future<Output = Result<Option<i64>, BaseError>> + Send;
+	fn save_checkpoint(&mut self, name: &str, cursor: i64) -> impl Future<Output = Result<(), BaseError>> + Send;
+}
+
+/// Storage of checkpoints outside of the transaction, such as those of [ImportOptions::checkpoint](crate::prelude::ImportOptions::checkpoint)
+#[async_trait]
+pub trait TCheckpointStore: Send + Sync {
+	async fn load(&self, name: &str) -> Result<Option<i64>, BaseError>;
+	async fn save(&self, name: &str, cursor: i64) -> Result<(), BaseError>;
+}
+
+#[async_trait]
+impl<T: TCheckpointStore + ?Sized> TCheckpointStore for Arc<T> {
+	async fn load(&self, name: &str) -> Result<Option<i64>, BaseError> {
+		self.as_ref().load(name).await
+	}
+	async fn save(&self, name: &str, cursor: i64) -> Result<(), BaseError> {
+		self.as_ref().save(name, cursor).await
+	}
+}
+
+#[derive(Default)]
+pub struct InMemoryCheckpointStore(std::sync::Mutex<std::collections::HashMap<String, i64>>);
+
+#[async_trait]
+impl TCheckpointStore for InMemoryCheckpointStore {
+	async fn load(&self, name: &str) -> Result<Option<i64>, BaseError> {
+		Ok(self.0.lock().unwrap().get(name).copied())
+	}
+	async fn save(&self, name: &str, cursor: i64) -> Result<(), BaseError> {
+		self.0.lock().unwrap().insert(name.to_string(), cursor);
+		Ok(())
+	}
+}
+
+/// Raised for every batch projected and once more when the backfill is finished.
+#[derive(Debug, Clone, Serialize)]
+pub struct BackfillProgressed {
+	pub name: String,
+	pub cursor: Option<i64>,
+	/// Number of items processed in this run
+	pub processed: usize,
+	pub finished: bool,
+}
+
+impl TEvent for BackfillProgressed {
+	fn internally_notifiable(&self) -> bool {
+		true
+	}
+	fn state(&self) -> String {
+		serde_json::to_string(self).expect("Failed to serialize")
+	}
+}
+
+/// Run backfill job from the last checkpoint until source is exhausted.
+pub async fn run_backfill<C, J>(job: &J, ctx: &mut C) -> Result<BackfillProgressed, BaseError>
+where
+	C: TBackfillCheckpoint + TSetCurrentEvents,
+	J: TBackfillJob<C>,
+{
+	let spec = job.spec();
+	let mut progress = BackfillProgressed {
+		name: spec.name.clone(),
+		cursor: ctx.load_checkpoint(&spec.name).await?,
+		processed: 0,
+		finished: false,
+	};
+	tracing::info!("Backfill {} started from {:?}", spec.name, progress.cursor);
+
+	loop {
+		let items = job.fetch(progress.cursor, spec.batch_size).await?;
+		let Some(last) = items.last().map(|(cursor, _)| *cursor) else {
+			break;
+		};
+		let count = items.len();
+		job.project(items.into_iter().map(|(_, item)| item).collect(), ctx).await?;
+		ctx.save_checkpoint(&spec.name, last).await?;
+
+		progress.cursor = Some(last);
+		progress.processed += count;
+		ctx.set_current_events(VecDeque::from([Arc::new(progress.clone()) as Arc<dyn TEvent>]));
+
+		if count < spec.batch_size {
+			break;
+		}
+		if let Some(delay) = spec.rate_limit {
+			tokio::time::sleep(delay).await;
+		}
+	}
+
+	progress.finished = true;
+	ctx.set_current_events(VecDeque::from([Arc::new(progress.clone()) as Arc<dyn TEvent>]));
+	tracing::info!("Backfill {} finished. {} items processed", spec.name, progress.processed);
+	Ok(progress)
+}
+
+#[cfg(test)]
+mod test {
+	use super::*;
+
+	struct Numbers;
+
+	#[async_trait]
+	impl TBackfillJob<Context> for Numbers {
+		type Item = i64;
+		fn spec(&self) -> BackfillSpec {
+			BackfillSpec::new("numbers").batch_size(2)
+		}
+		async fn fetch(&self, after: Option<i64>, limit: usize) -> Result<Vec<(i64, i64)>, BaseError> {
+			Ok((1..=5).filter(|n| Some(*n) > after).take(limit).map(|n| (n, n)).collect())
+		}
+		async fn project(&self, items: Vec<i64>, ctx: &mut Context) -> Result<(), BaseError> {
+			ctx.projected.extend(items);
+			Ok(())
+		}
+	}
+
+	/// Unit of work whose checkpoints, events and projection are committed together
+	#[derive(Default)]
+	struct Context {
+		checkpoints: std::collections::HashMap<String, i64>,
+		events: VecDeque<Arc<dyn TEvent>>,
+		projected: Vec<i64>,
+	}
+	impl TSetCurrentEvents for Context {
+		fn set_current_events(&mut self, events: VecDeque<Arc<dyn TEvent>>) {
+			self.events.extend(events)
+		}
+	}
+	impl TBackfillCheckpoint for Context {
+		async fn load_checkpoint(&mut self, name: &str) -> Result<Option<i64>, BaseError> {
+			Ok(self.checkpoints.get(name).copied())
+		}
+		async fn save_checkpoint(&mut self, name: &str, cursor: i64) -> Result<(), BaseError> {
+			self.checkpoints.insert(name.to_string(), cursor);
+			Ok(())
+		}
+	}
+
+	#[tokio::test]
+	async fn test_backfill_resumes_from_checkpoint() {
+		let mut ctx = Context::default();
+		ctx.save_checkpoint("numbers", 2).await.unwrap();
+
+		let done = run_backfill(&Numbers, &mut ctx).await.unwrap();
+		assert_eq!(ctx.projected, vec![3, 4, 5]);
+		assert_eq!(done.processed, 3);
+		assert_eq!(ctx.load_checkpoint("numbers").await.unwrap(), Some(5));
+
+		let progress = ctx
+			.events
+			.iter()
+			.map(|e| e.downcast_ref::<BackfillProgressed>().unwrap())
+			.map(|p| (p.cursor, p.finished))
+			.collect::<Vec<_>>();
+		assert_eq!(progress, vec![(Some(4), false), (Some(5), false), (Some(5), true)]);
+
+		// Nothing left to backfill
+		let done = run_backfill(&Numbers, &mut ctx).await.unwrap();
+		assert_eq!(done.processed, 0);
+	}
+}
@@ -0,0 +1,89 @@
+//! ### Actor
+//! Who is executing the command. It is carried by [ContextManager] so that handlers and aspects(authorization, audit)
+//! see background jobs and admin impersonation in the same way.
+//!
+//! ```rust
+//! # use ruva_core::prelude::*;
+//! # async fn example<C>(cmd: C, conn: &'static dyn TConnection) -> Result<(), C::Error>
+//! # where C: TCommandRoute, C::Error: From<BaseError>, BaseError: From<C::Error>, MessageBus: TEventBus<C::Error> {
+//! let context_manager = ContextManager::new(conn).with_actor(Actor::Impersonated {
+//!     admin: "admin-1".into(),
+//!     as_user: "user-42".into(),
+//! });
+//! let res = MessageBus.execute_and_wait_with(cmd, context_manager).await?;
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! [ContextManager]: crate::prelude::ContextManager
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash, serde::Serialize, serde::Deserialize)]
+pub enum Actor {
+	/// Actor is not identified
+	#[default]
+	Anonymous,
+	/// Authenticated user
+	User(String),
+	/// Background job, scheduler or any other process acting on its own. The value is the name of the process.
+	System(String),
+	/// Admin acting on behalf of a user
+	Impersonated { admin: String, as_user: String },
+}
+
+impl Actor {
+	/// The user on whose behalf the command is executed. Authorization should be checked against it.
+	pub fn effective_user(&self) -> Option<&str> {
+		match self {
+			Self::User(user) => Some(user),
+			Self::Impersonated { as_user, .. } => Some(as_user),
+			Self::Anonymous | Self::System(_) => None,
+		}
+	}
+
+	/// The one who actually performs the command. Audit should record it.
+	pub fn principal(&self) -> Option<&str> {
+		match self {
+			Self::User(user) => Some(user),
+			Self::Impersonated { admin, .. } => Some(admin),
+			Self::System(name) => Some(name),
+			Self::Anonymous => None,
+		}
+	}
+
+	pub fn is_system(&self) -> bool {
+		matches!(self, Self::System(_))
+	}
+
+	pub fn is_impersonated(&self) -> bool {
+		matches!(self, Self::Impersonated { .. })
+	}
+}
+
+impl std::fmt::Display for Actor {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		match self {
+			Self::Anonymous => write!(f, "anonymous"),
+			Self::User(user) => write!(f, "user:{}", user),
+			Self::System(name) => write!(f, "system:{}", name),
+			Self::Impersonated { admin, as_user } => write!(f, "user:{} (impersonated by {})", as_user, admin),
+		}
+	}
+}
+
+#[test]
+fn test_actor_identities() {
+	let actor = Actor::Impersonated {
+		admin: "admin".into(),
+		as_user: "migo".into(),
+	};
+	assert_eq!(actor.effective_user(), Some("migo"));
+	assert_eq!(actor.principal(), Some("admin"));
+	assert!(actor.is_impersonated());
+
+	let actor = Actor::System("scheduler".into());
+	assert_eq!(actor.effective_user(), None);
+	assert_eq!(actor.principal(), Some("scheduler"));
+	assert_eq!(actor.to_string(), "system:scheduler");
+
+	assert_eq!(Actor::default(), Actor::Anonymous);
+}
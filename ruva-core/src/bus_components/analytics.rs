@@ -0,0 +1,197 @@
+//! ### Usage analytics
+//! Product teams want to know which features are used, not the payloads. [Analytics] turns the dispatches of selected
+//! commands and the handling of selected events into [AnalyticsRecord]s and sends them to [TAnalyticsSink].
+//! Nothing is recorded unless [set_analytics] is called.
+//!
+//! ```rust,ignore
+//! // On boot. `KafkaAnalyticsSink` is available with `ruva-kafka` feature.
+//! set_analytics(
+//!     Analytics::new(KafkaAnalyticsSink::from_brokers("localhost:9092", "analytics.usage")?)
+//!         .command::<MakeOrder>()
+//!         .command::<CancelOrder>()
+//!         .event::<OrderDelivered>()
+//!         .with_tenant_salt("2024-q3"),
+//! );
+//! ```
+//! Record carries only the name, tenant, duration and outcome. Tenant is replaced by its HMAC-SHA256 keyed by the salt so that
+//! records of a tenant can be grouped without revealing who it is. Keep the salt secret, or tenant ids can be guessed by hashing them. Records are sent off the bus and failure to send is only logged.
+//!
+//! For HTTP collectors such as Segment, implement [TAnalyticsSink] with the HTTP client of the service.
+use std::sync::{Arc, OnceLock};
+use std::time::Duration;
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use hmac::{Hmac, Mac};
+use serde::Serialize;
+use sha2::Sha256;
+
+use crate::prelude::{clock, BaseError, TCommand, TTopic};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AnalyticsKind {
+	Command,
+	Event,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct AnalyticsRecord {
+	pub kind: AnalyticsKind,
+	/// Type name of the command without path, or topic of the event
+	pub name: String,
+	/// HMAC-SHA256 of the tenant keyed by the salt, hex encoded
+	pub tenant: Option<String>,
+	pub duration_ms: u64,
+	pub succeeded: bool,
+	pub recorded_at: DateTime<Utc>,
+}
+
+/// Where analytics records are sent to
+#[async_trait]
+pub trait TAnalyticsSink: Send + Sync {
+	async fn send(&self, record: &AnalyticsRecord) -> Result<(), BaseError>;
+}
+
+#[async_trait]
+impl<T: TAnalyticsSink + ?Sized> TAnalyticsSink for Arc<T> {
+	async fn send(&self, record: &AnalyticsRecord) -> Result<(), BaseError> {
+		self.as_ref().send(record).await
+	}
+}
+
+/// Which commands and events are recorded, and where they are sent
+pub struct Analytics {
+	sink: Arc<dyn TAnalyticsSink>,
+	commands: hashbrown::HashSet<&'static str>,
+	topics: hashbrown::HashSet<String>,
+	tenant_salt: String,
+}
+
+impl Analytics {
+	pub fn new(sink: impl TAnalyticsSink + 'static) -> Self {
+		Self {
+			sink: Arc::new(sink),
+			commands: Default::default(),
+			topics: Default::default(),
+			tenant_salt: String::new(),
+		}
+	}
+
+	pub fn command<C: TCommand>(mut self) -> Self {
+		self.commands.insert(std::any::type_name::<C>());
+		self
+	}
+
+	pub fn event<T: TTopic>(self) -> Self {
+		self.topic(T::TOPIC)
+	}
+
+	/// Event by its topic, for events declared elsewhere
+	pub fn topic(mut self, topic: impl Into<String>) -> Self {
+		self.topics.insert(topic.into());
+		self
+	}
+
+	/// Key of the hash of tenant. Changing it breaks grouping with the records sent before.
+	pub fn with_tenant_salt(mut self, salt: impl Into<String>) -> Self {
+		self.tenant_salt = salt.into();
+		self
+	}
+
+	fn anonymize(&self, tenant: &str) -> String {
+		let mut mac = Hmac::<Sha256>::new_from_slice(self.tenant_salt.as_bytes()).expect("HMAC takes key of any length");
+		mac.update(tenant.as_bytes());
+		mac.finalize().into_bytes().iter().map(|byte| format!("{:02x}", byte)).collect()
+	}
+
+	fn record(&self, kind: AnalyticsKind, name: &str, tenant: Option<&str>, elapsed: Duration, succeeded: bool) {
+		let record = AnalyticsRecord {
+			kind,
+			name: name.rsplit("::").next().unwrap_or(name).to_string(),
+			tenant: tenant.map(|tenant| self.anonymize(tenant)),
+			duration_ms: elapsed.as_millis() as u64,
+			succeeded,
+			recorded_at: clock().now(),
+		};
+		let Ok(runtime) = tokio::runtime::Handle::try_current() else {
+			tracing::warn!(name = record.name, "Analytics record is dropped as there is no runtime to send it on.");
+			return;
+		};
+		let sink = self.sink.clone();
+		runtime.spawn(async move {
+			if let Err(err) = sink.send(&record).await {
+				tracing::warn!(name = record.name, "Failed to send analytics record! {:?}", err);
+			}
+		});
+	}
+}
+
+static ANALYTICS: OnceLock<Analytics> = OnceLock::new();
+
+/// ## Panics
+/// If analytics is already set.
+pub fn set_analytics(analytics: Analytics) {
+	if ANALYTICS.set(analytics).is_err() {
+		panic!("Analytics Is Already Set!");
+	}
+}
+
+/// Called by the bus when `command` is handled and committed
+pub(crate) fn record_command(command: &'static str, tenant: Option<&str>, elapsed: Duration, succeeded: bool) {
+	if let Some(analytics) = ANALYTICS.get().filter(|analytics| analytics.commands.contains(command)) {
+		analytics.record(AnalyticsKind::Command, command, tenant, elapsed, succeeded);
+	}
+}
+
+/// Called by the bus when every handler of event of `topic` is run
+pub(crate) fn record_event(topic: &str, tenant: Option<&str>, elapsed: Duration, succeeded: bool) {
+	if let Some(analytics) = ANALYTICS.get().filter(|analytics| analytics.topics.contains(topic)) {
+		analytics.record(AnalyticsKind::Event, topic, tenant, elapsed, succeeded);
+	}
+}
+
+#[tokio::test]
+async fn test_analytics_records_selected_commands() {
+	use std::sync::Mutex;
+
+	#[derive(Default)]
+	struct Sink(Mutex<Vec<AnalyticsRecord>>);
+	#[async_trait]
+	impl TAnalyticsSink for Sink {
+		async fn send(&self, record: &AnalyticsRecord) -> Result<(), BaseError> {
+			self.0.lock().unwrap().push(record.clone());
+			Ok(())
+		}
+	}
+	#[derive(Debug)]
+	struct MakeOrder;
+	impl TCommand for MakeOrder {}
+	#[derive(Debug)]
+	struct Login;
+	impl TCommand for Login {}
+
+	let sink = Arc::new(Sink::default());
+	set_analytics(Analytics::new(sink.clone()).command::<MakeOrder>().topic("OrderDelivered").with_tenant_salt("salt"));
+
+	record_command(std::any::type_name::<MakeOrder>(), Some("acme"), Duration::from_millis(12), true);
+	record_command(std::any::type_name::<Login>(), Some("acme"), Duration::from_millis(3), true);
+	record_event("OrderDelivered", None, Duration::from_millis(5), false);
+	tokio::task::yield_now().await;
+
+	let mut records = sink.0.lock().unwrap().clone();
+	records.sort_by_key(|record| record.duration_ms);
+	assert_eq!(records.len(), 2);
+	assert_eq!(
+		(records[0].kind, records[0].name.as_str(), records[0].tenant.as_deref(), records[0].succeeded),
+		(AnalyticsKind::Event, "OrderDelivered", None, false)
+	);
+	assert_eq!((records[1].kind, records[1].name.as_str(), records[1].duration_ms), (AnalyticsKind::Command, "MakeOrder", 12));
+
+	// Tenant is grouped but not revealed
+	let tenant = records[1].tenant.clone().unwrap();
+	assert_ne!(tenant, "acme");
+	assert_eq!(Some(tenant), Some(ANALYTICS.get().unwrap().anonymize("acme")));
+	// Stable across builds, as records sent before and after an upgrade are grouped together
+	assert_eq!(ANALYTICS.get().unwrap().anonymize("acme"), "cd7a99c0743566a350fb04d1e99cd5de3989f0a07c16510dce0f858ed276cebe");
+}
@@ -0,0 +1,364 @@
+//! ### Aspect ordering
+//! Which aspect wraps which matters - audit should see the command rejected by authorization, cache shouldn't answer
+//! before authorization, retry shouldn't run inside replay protection, and so on - but layers stacked by hand get it wrong silently.
+//! Each aspect declares where it goes with [TAspectMetadata], and [ServiceBuilder::try_service](super::layer::ServiceBuilder::try_service)
+//! checks the layers added with [aspect_fn] against it, failing with [AspectChainError] when they conflict.
+//!
+//! ```rust,ignore
+//! impl TCommandRoute for MakeOrder {
+//!     fn command_handler(context_manager: AtomicContextManager, cmd: Self) -> impl TCommandService<Self::Response, Self::Error> {
+//!         ServiceBuilder::new()
+//!             .layer(aspect_fn(|inner| AuditAspect::new(&context_manager, &cmd, inner)))
+//!             .layer(aspect_fn(|inner| AuthorizationAspect::new(&context_manager, &cmd, inner)))
+//!             .layer(aspect_fn(|inner| ValidationAspect::new(&cmd, inner)))
+//!             .layer(retry_layer().with_max_attempts(5))
+//!             .try_service(move || CommandHandler((cmd.clone(), Context::new(context_manager.clone()))))
+//!             .expect("Invalid aspects of MakeOrder")
+//!     }
+//! }
+//! ```
+//! Layers of a route are the same for every command, so the conflict is found by the first command dispatched, in its test.
+//!
+//! Aspect with lower [TAspectMetadata::order] must be the outer one. Built-in aspects are spaced by 100, so that your own
+//! aspects can go in between. Each of them declares the aspects it must be wrapped by with [TAspectMetadata::wrapped_by] -
+//! idempotency outside replay protection, for instance - so that your own aspect taking one of the built-in ids is checked against them.
+use std::fmt;
+
+use super::audit::AuditAspect;
+use super::authorization::AuthorizationAspect;
+use super::cache::CacheAspect;
+use super::idempotency::IdempotencyAspect;
+use super::journal::CommandJournalAspect;
+use super::layer::TLayer;
+use super::replay::ReplayProtectionAspect;
+use super::retry::RetryHandler;
+use super::validation::ValidationAspect;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct AspectId(pub &'static str);
+
+impl AspectId {
+	pub const AUDIT: AspectId = AspectId("audit");
+	pub const AUTHORIZATION: AspectId = AspectId("authorization");
+	pub const VALIDATION: AspectId = AspectId("validation");
+	pub const REPLAY_PROTECTION: AspectId = AspectId("replay_protection");
+	pub const IDEMPOTENCY: AspectId = AspectId("idempotency");
+	pub const CACHE: AspectId = AspectId("cache");
+	pub const JOURNAL: AspectId = AspectId("journal");
+	pub const RETRY: AspectId = AspectId("retry");
+}
+
+impl fmt::Display for AspectId {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		f.write_str(self.0)
+	}
+}
+
+/// Where the aspect goes among the layers of [ServiceBuilder](super::layer::ServiceBuilder)
+pub trait TAspectMetadata {
+	fn id() -> AspectId;
+	/// Lower one wraps higher one
+	fn order() -> i32 {
+		0
+	}
+	/// Aspects that must be in the chain, outside of this one
+	fn requires() -> &'static [AspectId] {
+		&[]
+	}
+	/// Aspects that must be outside of this one, if they are in the chain
+	fn wrapped_by() -> &'static [AspectId] {
+		&[]
+	}
+}
+
+macro_rules! aspect_metadata {
+	($($aspect:ident => $id:ident, $order:expr, wrapped_by [$($wrapped_by:ident),*];)*) => {
+		$(
+			impl<S> TAspectMetadata for $aspect<S> {
+				fn id() -> AspectId {
+					AspectId::$id
+				}
+				fn order() -> i32 {
+					$order
+				}
+				fn wrapped_by() -> &'static [AspectId] {
+					&[$(AspectId::$wrapped_by),*]
+				}
+			}
+		)*
+	};
+}
+
+aspect_metadata! {
+	AuditAspect => AUDIT, -400, wrapped_by [];
+	// Rejected commands are audited too
+	AuthorizationAspect => AUTHORIZATION, -300, wrapped_by [AUDIT];
+	ValidationAspect => VALIDATION, -200, wrapped_by [AUDIT, AUTHORIZATION];
+	// Retry of the client, sent with the same envelope id, gets the recorded response rather than being rejected as replay
+	IdempotencyAspect => IDEMPOTENCY, -100, wrapped_by [AUDIT, AUTHORIZATION, VALIDATION];
+	ReplayProtectionAspect => REPLAY_PROTECTION, 0, wrapped_by [AUDIT, AUTHORIZATION, VALIDATION, IDEMPOTENCY];
+	// Cached response is served only to the ones authorized
+	CacheAspect => CACHE, 100, wrapped_by [AUDIT, AUTHORIZATION];
+	// Only the commands actually run are journaled
+	CommandJournalAspect => JOURNAL, 200, wrapped_by [AUTHORIZATION, VALIDATION, REPLAY_PROTECTION, IDEMPOTENCY, CACHE];
+	// Attempt after the first one would be rejected as replay, or claim the idempotency key again
+	RetryHandler => RETRY, 300, wrapped_by [AUTHORIZATION, VALIDATION, REPLAY_PROTECTION, IDEMPOTENCY, CACHE, JOURNAL];
+}
+
+/// [TAspectMetadata] of an aspect, as reported by [TLayer::aspects]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AspectMetadata {
+	pub id: AspectId,
+	pub order: i32,
+	pub requires: &'static [AspectId],
+	pub wrapped_by: &'static [AspectId],
+}
+
+impl AspectMetadata {
+	pub fn of<A: TAspectMetadata>() -> Self {
+		Self {
+			id: A::id(),
+			order: A::order(),
+			requires: A::requires(),
+			wrapped_by: A::wrapped_by(),
+		}
+	}
+}
+
+/// Layer from closure making an aspect, which reports the metadata of the aspect unlike [layer_fn](super::layer::layer_fn)
+#[derive(Clone, Copy)]
+pub struct AspectFn<F>(F);
+
+pub fn aspect_fn<F>(f: F) -> AspectFn<F> {
+	AspectFn(f)
+}
+
+impl<S, F, Out> TLayer<S> for AspectFn<F>
+where
+	F: Fn(S) -> Out,
+	Out: TAspectMetadata,
+{
+	type Service = Out;
+	fn layer(&self, inner: S) -> Self::Service {
+		(self.0)(inner)
+	}
+	fn aspects(&self) -> Vec<AspectMetadata> {
+		vec![AspectMetadata::of::<Out>()]
+	}
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AspectChainError {
+	Duplicated(AspectId),
+	Missing {
+		aspect: AspectId,
+		requires: AspectId,
+	},
+	/// Required aspect would run inside the one requiring it
+	Misordered {
+		aspect: AspectId,
+		requires: AspectId,
+	},
+	/// Aspect of higher order wraps the one of lower order
+	Unsorted {
+		outer: AspectId,
+		inner: AspectId,
+	},
+}
+
+impl fmt::Display for AspectChainError {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self {
+			AspectChainError::Duplicated(aspect) => write!(f, "Aspect {} is added more than once", aspect),
+			AspectChainError::Missing { aspect, requires } => write!(f, "Aspect {} requires {}, which is not in the chain", aspect, requires),
+			AspectChainError::Misordered { aspect, requires } => write!(f, "Aspect {} requires {} outside of it, but {} is added inside", aspect, requires, requires),
+			AspectChainError::Unsorted { outer, inner } => write!(f, "Aspect {} is ordered inside {}, but is added outside of it", outer, inner),
+		}
+	}
+}
+
+impl std::error::Error for AspectChainError {}
+
+/// Check that each of `aspects`, from the outermost one, is added once, in order, and has the aspects it requires, or is
+/// wrapped by, outside of it.
+pub(crate) fn check_aspects(aspects: &[AspectMetadata]) -> Result<(), AspectChainError> {
+	let position = |id: AspectId| aspects.iter().position(|aspect| aspect.id == id);
+	for (index, aspect) in aspects.iter().enumerate() {
+		if position(aspect.id) != Some(index) {
+			return Err(AspectChainError::Duplicated(aspect.id));
+		}
+		for &requires in aspect.requires {
+			match position(requires) {
+				None => return Err(AspectChainError::Missing { aspect: aspect.id, requires }),
+				Some(required) if required > index => return Err(AspectChainError::Misordered { aspect: aspect.id, requires }),
+				Some(_) => {}
+			}
+		}
+		for &requires in aspect.wrapped_by {
+			if position(requires).is_some_and(|wrapping| wrapping > index) {
+				return Err(AspectChainError::Misordered { aspect: aspect.id, requires });
+			}
+		}
+		if let Some(outer) = index.checked_sub(1).map(|outer| &aspects[outer]).filter(|outer| outer.order > aspect.order) {
+			return Err(AspectChainError::Unsorted { outer: outer.id, inner: aspect.id });
+		}
+	}
+	Ok(())
+}
+
+#[tokio::test]
+async fn test_aspects_checked_on_service_builder() {
+	use super::layer::{layer_fn, ServiceBuilder};
+	use super::messagebus::TCommandService;
+	use super::retry::retry_layer;
+	use crate::prelude::{AtomicContextManager, BaseError, ContextManager, TCommand, TConnection};
+	use std::sync::{Arc, Mutex};
+
+	#[derive(Debug)]
+	struct MakeOrder;
+	impl TCommand for MakeOrder {}
+
+	static TRACE: Mutex<Vec<AspectId>> = Mutex::new(vec![]);
+	macro_rules! traced_aspect {
+		($aspect:ident, $id:expr, $order:expr, $requires:expr) => {
+			struct $aspect<S>(S);
+			impl<S> TAspectMetadata for $aspect<S> {
+				fn id() -> AspectId {
+					$id
+				}
+				fn order() -> i32 {
+					$order
+				}
+				fn requires() -> &'static [AspectId] {
+					$requires
+				}
+			}
+			impl<S: TCommandService<(), BaseError>> TCommandService<(), BaseError> for $aspect<S> {
+				async fn execute(self) -> Result<(), BaseError> {
+					TRACE.lock().unwrap().push($id);
+					self.0.execute().await
+				}
+			}
+		};
+	}
+	traced_aspect!(Metrics, AspectId("metrics"), -500, &[]);
+	traced_aspect!(Authorization, AspectId::AUTHORIZATION, -300, &[]);
+	traced_aspect!(LateAuthorization, AspectId::AUTHORIZATION, 0, &[]);
+	traced_aspect!(TenantScope, AspectId("tenant_scope"), -250, &[AspectId::AUTHORIZATION]);
+	traced_aspect!(LateAudit, AspectId::AUDIT, -200, &[]);
+
+	struct Handler;
+	impl TCommandService<(), BaseError> for Handler {
+		async fn execute(self) -> Result<(), BaseError> {
+			TRACE.lock().unwrap().push(AspectId("handler"));
+			Ok(())
+		}
+	}
+	struct TestConnection;
+	impl TConnection for TestConnection {}
+
+	// Layers without metadata are not checked
+	let service = ServiceBuilder::new()
+		.layer(aspect_fn(Metrics))
+		.layer(layer_fn(|inner| inner))
+		.layer(aspect_fn(Authorization))
+		.layer(aspect_fn(TenantScope))
+		.try_service(Handler)
+		.unwrap();
+	service.execute().await.unwrap();
+	assert_eq!(
+		*TRACE.lock().unwrap(),
+		vec![AspectId("metrics"), AspectId::AUTHORIZATION, AspectId("tenant_scope"), AspectId("handler")]
+	);
+
+	// Conflicts
+	let err = ServiceBuilder::new().layer(aspect_fn(TenantScope)).try_service(Handler).err().unwrap();
+	assert_eq!(
+		err,
+		AspectChainError::Missing {
+			aspect: AspectId("tenant_scope"),
+			requires: AspectId::AUTHORIZATION
+		}
+	);
+	assert_eq!(err.to_string(), "Aspect tenant_scope requires authorization, which is not in the chain");
+
+	let err = ServiceBuilder::new().layer(aspect_fn(Metrics)).layer(aspect_fn(Metrics)).try_service(Handler).err().unwrap();
+	assert_eq!(err, AspectChainError::Duplicated(AspectId("metrics")));
+
+	let err = ServiceBuilder::new()
+		.layer(aspect_fn(TenantScope))
+		.layer(aspect_fn(LateAuthorization))
+		.try_service(Handler)
+		.err()
+		.unwrap();
+	assert_eq!(
+		err,
+		AspectChainError::Misordered {
+			aspect: AspectId("tenant_scope"),
+			requires: AspectId::AUTHORIZATION
+		}
+	);
+
+	let err = ServiceBuilder::new().layer(aspect_fn(Authorization)).layer(aspect_fn(Metrics)).try_service(Handler).err().unwrap();
+	assert_eq!(
+		err,
+		AspectChainError::Unsorted {
+			outer: AspectId::AUTHORIZATION,
+			inner: AspectId("metrics")
+		}
+	);
+
+	// Aspect that built-in one is wrapped by is optional, but must be outside of it once added
+	let context_manager: AtomicContextManager = Arc::new(ContextManager::new(&TestConnection));
+	macro_rules! authorization {
+		() => {
+			aspect_fn(|inner| AuthorizationAspect::new(&context_manager, &MakeOrder, inner))
+		};
+	}
+	assert!(ServiceBuilder::new().layer(authorization!()).try_service(Handler).is_ok());
+	let err = ServiceBuilder::new().layer(authorization!()).layer(aspect_fn(LateAudit)).try_service(Handler).err().unwrap();
+	assert_eq!(
+		err,
+		AspectChainError::Misordered {
+			aspect: AspectId::AUTHORIZATION,
+			requires: AspectId::AUDIT
+		}
+	);
+
+	// Retry is checked as the other aspects
+	assert!(ServiceBuilder::new().layer(authorization!()).layer(retry_layer()).try_service(|| Handler).is_ok());
+	let err = ServiceBuilder::new().layer(retry_layer()).layer(authorization!()).try_service(|| Handler).err().unwrap();
+	assert_eq!(
+		err,
+		AspectChainError::Misordered {
+			aspect: AspectId::RETRY,
+			requires: AspectId::AUTHORIZATION
+		}
+	);
+}
+
+#[test]
+fn test_builtin_aspects_wrapped_in_order() {
+	let builtins = [
+		AspectMetadata::of::<AuditAspect<()>>(),
+		AspectMetadata::of::<AuthorizationAspect<()>>(),
+		AspectMetadata::of::<ValidationAspect<()>>(),
+		AspectMetadata::of::<IdempotencyAspect<()>>(),
+		AspectMetadata::of::<ReplayProtectionAspect<()>>(),
+		AspectMetadata::of::<CacheAspect<()>>(),
+		AspectMetadata::of::<CommandJournalAspect<()>>(),
+		AspectMetadata::of::<RetryHandler<()>>(),
+	];
+	let order = |id: AspectId| builtins.iter().find(|builtin| builtin.id == id).unwrap().order;
+	for aspect in builtins {
+		for &wrapping in aspect.wrapped_by {
+			assert!(order(wrapping) < aspect.order, "{} must be wrapped by {}", aspect.id, wrapping);
+		}
+	}
+	// Client retry with the same envelope id gets the recorded response
+	assert!(order(AspectId::IDEMPOTENCY) < order(AspectId::REPLAY_PROTECTION));
+	// Built-in aspects sorted by their order pass the check
+	let mut sorted = builtins.to_vec();
+	sorted.sort_by_key(|aspect| aspect.order);
+	assert_eq!(check_aspects(&sorted), Ok(()));
+}
@@ -0,0 +1,284 @@
+//! ### Audit log
+//! [AuditAspect] records who ran which command, with what and when, in [TAuditSink] set by [set_audit_sink].
+//! Who is [Actor](super::actor::Actor) of the context, so admin impersonating a user is recorded as the admin.
+//!
+//! ```rust,ignore
+//! // On boot. Implement `TAuditSink` to keep records in a table instead.
+//! set_audit_sink(TracingAuditSink);
+//!
+//! impl TCommandRoute for ChangePassword {
+//!     fn command_handler(context_manager: AtomicContextManager, cmd: Self) -> impl TCommandService<Self::Response, Self::Error> {
+//!         AuditAspect::new(&context_manager, &cmd, CommandHandler((cmd, Context::new(context_manager.clone())))).redact(["old_password", "new_password"])
+//!     }
+//! }
+//! ```
+//! Unlike [CommandJournalAspect](super::journal::CommandJournalAspect), which is for rerunning commands, audit record is meant to be read by people,
+//! so secrets are redacted from the payload. Failure of the sink is logged and doesn't fail the command.
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::{Duration, Instant};
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+
+use super::actor::Actor;
+use super::contexts::AtomicContextManager;
+use super::messagebus::TCommandService;
+use crate::prelude::{ApplicationError, ApplicationResponse, BaseError, SnowFlake, TCommand};
+
+/// Replaces value of redacted field
+pub const REDACTED: &str = "[REDACTED]";
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub enum AuditOutcome {
+	Succeeded,
+	Failed(String),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct AuditRecord {
+	pub id: i64,
+	/// Type name of the command
+	pub command: String,
+	/// Serialized command with redacted fields
+	pub payload: String,
+	pub actor: Actor,
+	pub tenant: Option<String>,
+	/// Trace id the error response of the request is answered with, so that failed outcome reported by user can be found.
+	/// See [ContextManager::trace_id](super::contexts::ContextManager::trace_id).
+	pub trace_id: Option<String>,
+	pub outcome: AuditOutcome,
+	pub elapsed: Duration,
+	pub recorded_at: DateTime<Utc>,
+}
+
+#[async_trait]
+pub trait TAuditSink: Send + Sync {
+	async fn record(&self, record: &AuditRecord) -> Result<(), BaseError>;
+}
+
+#[async_trait]
+impl<T: TAuditSink + ?Sized> TAuditSink for Arc<T> {
+	async fn record(&self, record: &AuditRecord) -> Result<(), BaseError> {
+		self.as_ref().record(record).await
+	}
+}
+
+/// Logs each record as `audit` target event, so that log pipeline ships it wherever it is kept.
+pub struct TracingAuditSink;
+
+#[async_trait]
+impl TAuditSink for TracingAuditSink {
+	async fn record(&self, record: &AuditRecord) -> Result<(), BaseError> {
+		tracing::info!(
+			target: "audit",
+			id = record.id,
+			command = %record.command,
+			payload = %record.payload,
+			principal = record.actor.principal(),
+			effective_user = record.actor.effective_user(),
+			tenant = record.tenant.as_deref(),
+			trace_id = record.trace_id.as_deref(),
+			outcome = ?record.outcome,
+			elapsed_ms = record.elapsed.as_millis() as u64,
+			"{} is run.",
+			record.command
+		);
+		Ok(())
+	}
+}
+
+/// For tests. Records are kept in memory.
+#[derive(Default)]
+pub struct InMemoryAuditSink(Mutex<Vec<AuditRecord>>);
+
+impl InMemoryAuditSink {
+	pub fn records(&self) -> Vec<AuditRecord> {
+		self.0.lock().unwrap().clone()
+	}
+}
+
+#[async_trait]
+impl TAuditSink for InMemoryAuditSink {
+	async fn record(&self, record: &AuditRecord) -> Result<(), BaseError> {
+		self.0.lock().unwrap().push(record.clone());
+		Ok(())
+	}
+}
+
+static AUDIT_SINK: RwLock<Option<Arc<dyn TAuditSink>>> = RwLock::new(None);
+
+/// Not set by default, in which case [AuditAspect] records nothing.
+pub fn set_audit_sink(sink: impl TAuditSink + 'static) {
+	*AUDIT_SINK.write().unwrap() = Some(Arc::new(sink));
+}
+
+pub fn audit_sink() -> Option<Arc<dyn TAuditSink>> {
+	AUDIT_SINK.read().unwrap().clone()
+}
+
+/// Record the command `inner` runs, once it is done. Dry run is not recorded.
+pub struct AuditAspect<S> {
+	context_manager: AtomicContextManager,
+	command: &'static str,
+	/// Command that fails to serialize is not run, as it could not be recorded.
+	payload: Result<serde_json::Value, BaseError>,
+	sink: Option<Arc<dyn TAuditSink>>,
+	inner: S,
+}
+
+impl<S> AuditAspect<S> {
+	pub fn new<C: TCommand + Serialize>(context_manager: &AtomicContextManager, command: &C, inner: S) -> Self {
+		Self {
+			context_manager: context_manager.clone(),
+			command: std::any::type_name::<C>(),
+			payload: serde_json::to_value(command).map_err(|err| BaseError::DatabaseError(err.to_string())),
+			sink: audit_sink(),
+			inner,
+		}
+	}
+
+	/// Record in `sink` instead of the one set by [set_audit_sink]
+	pub fn with_sink(mut self, sink: impl TAuditSink + 'static) -> Self {
+		self.sink = Some(Arc::new(sink));
+		self
+	}
+
+	/// Replace fields of the payload with [REDACTED]. Nested field is given as dotted path such as `card.number`,
+	/// which is applied to every element of arrays along the path.
+	pub fn redact<'a>(mut self, fields: impl IntoIterator<Item = &'a str>) -> Self {
+		if let Ok(payload) = self.payload.as_mut() {
+			for field in fields {
+				redact_path(payload, &field.split('.').collect::<Vec<_>>());
+			}
+		}
+		self
+	}
+
+	/// Redact the payload by hand, for example masking all but the last digits of an account number.
+	pub fn redact_with(mut self, redact: impl FnOnce(&mut serde_json::Value)) -> Self {
+		if let Ok(payload) = self.payload.as_mut() {
+			redact(payload);
+		}
+		self
+	}
+}
+
+fn redact_path(value: &mut serde_json::Value, path: &[&str]) {
+	let Some((field, rest)) = path.split_first() else {
+		return;
+	};
+	match value {
+		serde_json::Value::Object(fields) => match (fields.get_mut(*field), rest.is_empty()) {
+			(Some(value), true) => *value = serde_json::Value::String(REDACTED.to_string()),
+			(Some(value), false) => redact_path(value, rest),
+			(None, _) => {}
+		},
+		serde_json::Value::Array(elements) => elements.iter_mut().for_each(|element| redact_path(element, path)),
+		_ => {}
+	}
+}
+
+impl<R, E, S> TCommandService<R, E> for AuditAspect<S>
+where
+	R: ApplicationResponse,
+	E: ApplicationError + std::convert::From<BaseError>,
+	S: TCommandService<R, E>,
+{
+	async fn execute(self) -> Result<R, E> {
+		let Some(sink) = self.sink.filter(|_| !self.context_manager.dry_run) else {
+			return self.inner.execute().await;
+		};
+		let payload = self.payload?;
+		let started = Instant::now();
+		let res = self.inner.execute().await;
+		let record = AuditRecord {
+			id: *SnowFlake::generate(),
+			command: self.command.to_string(),
+			payload: payload.to_string(),
+			actor: self.context_manager.actor.clone(),
+			tenant: self.context_manager.tenant.clone(),
+			trace_id: self.context_manager.trace_id.clone(),
+			outcome: match res.as_ref() {
+				Ok(_) => AuditOutcome::Succeeded,
+				Err(err) => AuditOutcome::Failed(format!("{:?}", err)),
+			},
+			elapsed: started.elapsed(),
+			recorded_at: self.context_manager.clock.now(),
+		};
+		if let Err(err) = sink.record(&record).await {
+			tracing::error!(command = %record.command, "Failed to record audit! {:?}", err);
+		}
+		res
+	}
+}
+
+#[tokio::test]
+async fn test_audit_records_redacted_payload() {
+	use super::contexts::ContextManager;
+	use super::executor::TConnection;
+
+	struct Connection;
+	impl TConnection for Connection {}
+	#[derive(Debug, Serialize)]
+	struct Card {
+		number: String,
+	}
+	#[derive(Debug, Serialize)]
+	struct Pay {
+		amount: i64,
+		password: String,
+		cards: Vec<Card>,
+	}
+	impl TCommand for Pay {}
+	struct Handler(bool);
+	impl TCommandService<(), BaseError> for Handler {
+		async fn execute(self) -> Result<(), BaseError> {
+			match self.0 {
+				true => Ok(()),
+				false => Err(BaseError::ServiceError),
+			}
+		}
+	}
+
+	let sink = Arc::new(InMemoryAuditSink::default());
+	let command = Pay {
+		amount: 10,
+		password: "secret".into(),
+		cards: vec![Card { number: "4111".into() }],
+	};
+	let mut context_manager = ContextManager::new(&Connection).with_actor(Actor::Impersonated {
+		admin: "admin-1".into(),
+		as_user: "user-42".into(),
+	});
+	context_manager.trace_id = Some("4bf92f3577b34da6a3ce929d0e0e4736".into());
+	let context_manager = Arc::new(context_manager);
+	AuditAspect::new(&context_manager, &command, Handler(true))
+		.with_sink(sink.clone())
+		.redact(["password", "cards.number"])
+		.execute()
+		.await
+		.unwrap();
+	AuditAspect::new(&context_manager, &command, Handler(false))
+		.with_sink(sink.clone())
+		.redact_with(|payload| payload["amount"] = 0.into())
+		.execute()
+		.await
+		.unwrap_err();
+
+	let records = sink.records();
+	// Parsed, as key order differs when `serde_json/preserve_order` is enabled by another dependency
+	let payload = |record: &AuditRecord| serde_json::from_str::<serde_json::Value>(&record.payload).unwrap();
+	assert_eq!(records.len(), 2);
+	assert_eq!(payload(&records[0]), serde_json::json!({"amount": 10, "cards": [{"number": "[REDACTED]"}], "password": "[REDACTED]"}));
+	assert_eq!((records[0].actor.principal(), &records[0].outcome), (Some("admin-1"), &AuditOutcome::Succeeded));
+	assert_eq!(payload(&records[1]), serde_json::json!({"amount": 0, "cards": [{"number": "4111"}], "password": "secret"}));
+	assert_eq!(records[1].outcome, AuditOutcome::Failed("ServiceError".into()));
+	// Failed outcome is recorded with the trace id of the request, not the one of the span the command is run in
+	assert_eq!(records[1].trace_id.as_deref(), Some("4bf92f3577b34da6a3ce929d0e0e4736"));
+
+	// Dry run is not recorded
+	let dry_run = Arc::new(ContextManager::new(&Connection).with_dry_run());
+	AuditAspect::new(&dry_run, &command, Handler(true)).with_sink(sink.clone()).execute().await.unwrap();
+	assert_eq!(sink.records().len(), 2);
+}
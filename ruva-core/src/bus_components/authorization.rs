@@ -0,0 +1,219 @@
+//! ### Authorization
+//! [AuthorizationAspect] asks [TAuthorizer] of the context manager, the one set by [set_authorizer] by default, whether the actor of the context may run the command,
+//! and fails it with `BaseError::Forbidden` before the inner service runs if not, answered with 403 by web integrations.
+//!
+//! ```rust,ignore
+//! // On boot
+//! set_authorizer(
+//!     RoleAuthorizer::default()
+//!         .require::<CancelOrder>(["admin", "support"])
+//!         .require::<RefundOrder>(["admin"])
+//!         // Expired orders are cancelled by the scheduler too
+//!         .allow_system::<CancelOrder>(["scheduler"])
+//!         .allow_all::<ViewProduct>(),
+//! );
+//!
+//! impl TCommandRoute for CancelOrder {
+//!     fn command_handler(context_manager: AtomicContextManager, cmd: Self) -> impl TCommandService<Self::Response, Self::Error> {
+//!         AuthorizationAspect::new(&context_manager, &cmd, CommandHandler((cmd, Context::new(context_manager.clone()))))
+//!     }
+//! }
+//! ```
+//! Rules that depend on the content of the command, such as ownership of the order, belong to the handler.
+//! The aspect fails closed - command is forbidden when no authorizer is set. Reason of denial is logged, not returned to the caller.
+use std::sync::{Arc, RwLock};
+
+use async_trait::async_trait;
+
+use super::actor::Actor;
+use super::contexts::AtomicContextManager;
+use super::current_user::CurrentUser;
+use super::messagebus::TCommandService;
+use crate::prelude::{ApplicationError, ApplicationResponse, BaseError, TCommand};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Authorization {
+	Allow,
+	/// Reason is logged. Caller gets `BaseError::Forbidden` without it.
+	Deny(String),
+}
+
+#[async_trait]
+pub trait TAuthorizer: Send + Sync {
+	/// `command` is the type name of the command. `current_user` is given when the dispatch is made for an authenticated user.
+	async fn authorize(&self, command: &str, actor: &Actor, current_user: Option<&CurrentUser>) -> Authorization;
+}
+
+#[async_trait]
+impl<T: TAuthorizer + ?Sized> TAuthorizer for Arc<T> {
+	async fn authorize(&self, command: &str, actor: &Actor, current_user: Option<&CurrentUser>) -> Authorization {
+		self.as_ref().authorize(command, actor, current_user).await
+	}
+}
+
+#[derive(Default)]
+struct Rule {
+	roles: Vec<String>,
+	systems: Vec<String>,
+	public: bool,
+}
+
+/// Allows command to the users with any of the roles required for it, and to the system actors named for it.
+/// Commands without any rule are denied, so that a command added without thinking of its access is not open to everyone.
+#[derive(Default)]
+pub struct RoleAuthorizer {
+	rules: hashbrown::HashMap<&'static str, Rule>,
+}
+
+impl RoleAuthorizer {
+	/// Require any of `roles` for `C`. Called again for the same command, the roles are added.
+	pub fn require<C: TCommand>(mut self, roles: impl IntoIterator<Item = impl Into<String>>) -> Self {
+		self.rule::<C>().roles.extend(roles.into_iter().map(Into::into));
+		self
+	}
+
+	/// Allow `C` to the system actors of `names`, such as `scheduler` or `inbox:OrderPaid`.
+	pub fn allow_system<C: TCommand>(mut self, names: impl IntoIterator<Item = impl Into<String>>) -> Self {
+		self.rule::<C>().systems.extend(names.into_iter().map(Into::into));
+		self
+	}
+
+	/// Allow `C` to anyone, including anonymous actor
+	pub fn allow_all<C: TCommand>(mut self) -> Self {
+		self.rule::<C>().public = true;
+		self
+	}
+
+	fn rule<C: TCommand>(&mut self) -> &mut Rule {
+		self.rules.entry(std::any::type_name::<C>()).or_default()
+	}
+}
+
+#[async_trait]
+impl TAuthorizer for RoleAuthorizer {
+	async fn authorize(&self, command: &str, actor: &Actor, current_user: Option<&CurrentUser>) -> Authorization {
+		let Some(rule) = self.rules.get(command) else {
+			return Authorization::Deny(format!("{} has no authorization rule", command));
+		};
+		if rule.public {
+			return Authorization::Allow;
+		}
+		if let Actor::System(name) = actor {
+			return match rule.systems.contains(name) {
+				true => Authorization::Allow,
+				false => Authorization::Deny(format!("{} is not allowed to system actor {}", command, name)),
+			};
+		}
+		if current_user.is_some_and(|user| rule.roles.iter().any(|role| user.has_role(role))) {
+			return Authorization::Allow;
+		}
+		Authorization::Deny(format!("{} requires any of {:?}", command, rule.roles))
+	}
+}
+
+static AUTHORIZER: RwLock<Option<Arc<dyn TAuthorizer>>> = RwLock::new(None);
+
+/// Authorizer of dispatches created from now on. Dispatch can be given its own with [ContextManager::with_authorizer](super::contexts::ContextManager::with_authorizer).
+pub fn set_authorizer(authorizer: impl TAuthorizer + 'static) {
+	*AUTHORIZER.write().unwrap() = Some(Arc::new(authorizer));
+}
+
+pub fn authorizer() -> Option<Arc<dyn TAuthorizer>> {
+	AUTHORIZER.read().unwrap().clone()
+}
+
+/// Authorize the command before running `inner` service.
+pub struct AuthorizationAspect<S> {
+	context_manager: AtomicContextManager,
+	command: &'static str,
+	inner: S,
+}
+
+impl<S> AuthorizationAspect<S> {
+	pub fn new<C: TCommand>(context_manager: &AtomicContextManager, _command: &C, inner: S) -> Self {
+		Self {
+			context_manager: context_manager.clone(),
+			command: std::any::type_name::<C>(),
+			inner,
+		}
+	}
+}
+
+impl<R, E, S> TCommandService<R, E> for AuthorizationAspect<S>
+where
+	R: ApplicationResponse,
+	E: ApplicationError + std::convert::From<BaseError>,
+	S: TCommandService<R, E>,
+{
+	async fn execute(self) -> Result<R, E> {
+		let decision = match self.context_manager.authorizer.as_ref() {
+			Some(authorizer) => authorizer.authorize(self.command, &self.context_manager.actor, self.context_manager.current_user.as_ref()).await,
+			None => {
+				tracing::error!(command = self.command, "No authorizer is set!");
+				Authorization::Deny(format!("{} is not authorized", self.command))
+			}
+		};
+		if let Authorization::Deny(reason) = decision {
+			tracing::warn!(command = self.command, actor = %self.context_manager.actor, "Command is forbidden! {}", reason);
+			return Err(BaseError::Forbidden("Not allowed to run the command".to_string()).into());
+		}
+		self.inner.execute().await
+	}
+}
+
+#[tokio::test]
+async fn test_authorization_aspect() {
+	use super::contexts::ContextManager;
+	use super::executor::TConnection;
+	use crate::prelude::THttpStatus;
+
+	struct Connection;
+	impl TConnection for Connection {}
+	#[derive(Debug)]
+	struct RefundOrder;
+	impl TCommand for RefundOrder {}
+	#[derive(Debug)]
+	struct ViewOrder;
+	impl TCommand for ViewOrder {}
+	#[derive(Debug)]
+	struct DeleteOrder;
+	impl TCommand for DeleteOrder {}
+	struct Handler;
+	impl TCommandService<(), BaseError> for Handler {
+		async fn execute(self) -> Result<(), BaseError> {
+			Ok(())
+		}
+	}
+
+	// Fails closed without authorizer
+	let anonymous = Arc::new(ContextManager::new(&Connection));
+	assert!(matches!(AuthorizationAspect::new(&anonymous, &ViewOrder, Handler).execute().await, Err(BaseError::Forbidden(_))));
+
+	let authorizer = || {
+		RoleAuthorizer::default()
+			.require::<RefundOrder>(["admin"])
+			.allow_system::<RefundOrder>(["scheduler"])
+			.allow_all::<ViewOrder>()
+	};
+	let context_manager = || ContextManager::new(&Connection).with_authorizer(authorizer());
+	let admin = Arc::new(context_manager().with_current_user(CurrentUser::new("kim").with_role("admin")));
+	let user = Arc::new(context_manager().with_current_user(CurrentUser::new("lee").with_scope("admin")));
+	let scheduler = Arc::new(context_manager().with_actor(Actor::System("scheduler".into())));
+	let inbox = Arc::new(context_manager().with_actor(Actor::System("inbox:OrderPaid".into())));
+	let anonymous = Arc::new(context_manager());
+
+	assert!(AuthorizationAspect::new(&admin, &RefundOrder, Handler).execute().await.is_ok());
+	assert!(AuthorizationAspect::new(&scheduler, &RefundOrder, Handler).execute().await.is_ok());
+	assert!(AuthorizationAspect::new(&anonymous, &ViewOrder, Handler).execute().await.is_ok());
+	// System actor is allowed only the commands given to it by name
+	assert!(AuthorizationAspect::new(&inbox, &RefundOrder, Handler).execute().await.is_err());
+	// Command without rule is denied
+	assert!(AuthorizationAspect::new(&admin, &DeleteOrder, Handler).execute().await.is_err());
+
+	let Err(BaseError::Forbidden(reason)) = AuthorizationAspect::new(&user, &RefundOrder, Handler).execute().await else {
+		panic!("RefundOrder must be forbidden to user without admin role");
+	};
+	// Required roles are not told to the caller
+	assert!(!reason.contains("admin"));
+	assert_eq!(BaseError::Forbidden(reason).http_status(), 403);
+}
@@ -0,0 +1,197 @@
+//! ### Backlog metrics
+//! Autoscaling and alerts are better driven by how far the bus is behind than by CPU.
+//! [OutboxRelay](crate::prelude::OutboxRelay) and [Inbox](super::inbox::Inbox) sample the backlog of their source
+//! and count the messages they process, which are reported to [TBusObserver](super::observer::TBusObserver)
+//! and kept in [backlog_metrics] for introspection.
+//!
+//! ```rust,ignore
+//! struct Metrics;
+//! impl TBusObserver for Metrics {
+//!     fn backlog_sampled(&self, source: MessageSource, backlog: &Backlog) {
+//!         gauge!("bus_backlog", "source" => source.as_str()).set(backlog.count as f64);
+//!     }
+//! }
+//!
+//! // From admin endpoint
+//! let report = backlog_metrics().report();
+//! ```
+//! Backlog of the outbox comes from [TOutboxStore::backlog](crate::prelude::TOutboxStore::backlog)
+//! and that of the inbox from [TEventConsumer::backlog](super::inbox::TEventConsumer::backlog), for example consumer lag of Kafka.
+//! Nothing is sampled if they return `None`, which is the default.
+use std::collections::VecDeque;
+use std::sync::{LazyLock, Mutex};
+use std::time::{Duration, Instant};
+
+use super::observer::notify;
+
+/// Processing rate is averaged over this window
+const RATE_WINDOW: Duration = Duration::from_secs(60);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum MessageSource {
+	Outbox,
+	Inbox,
+}
+
+impl MessageSource {
+	pub fn as_str(&self) -> &'static str {
+		match self {
+			MessageSource::Outbox => "outbox",
+			MessageSource::Inbox => "inbox",
+		}
+	}
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Backlog {
+	/// Messages waiting to be processed
+	pub count: u64,
+	/// Age of the oldest waiting message. `None` if nothing is waiting or the source doesn't know.
+	pub oldest_age: Option<Duration>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct TopicThroughput {
+	pub source: MessageSource,
+	pub topic: String,
+	/// Messages processed since the process started
+	pub processed: u64,
+	/// Messages processed per second over the last minute
+	pub per_second: f64,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct BacklogReport {
+	/// Latest sample of each source
+	pub backlogs: Vec<(MessageSource, Backlog)>,
+	/// Sorted by source and topic
+	pub topics: Vec<TopicThroughput>,
+}
+
+#[derive(Default)]
+struct Throughput {
+	processed: u64,
+	/// Count per second since `started`, within the window
+	buckets: VecDeque<(u64, u64)>,
+}
+
+pub struct BacklogMetrics {
+	started: Instant,
+	backlogs: Mutex<hashbrown::HashMap<MessageSource, Backlog>>,
+	topics: Mutex<hashbrown::HashMap<(MessageSource, String), Throughput>>,
+}
+
+static BACKLOG_METRICS: LazyLock<BacklogMetrics> = LazyLock::new(BacklogMetrics::default);
+
+/// Process-wide backlog and throughput of the configured transports
+pub fn backlog_metrics() -> &'static BacklogMetrics {
+	&BACKLOG_METRICS
+}
+
+impl Default for BacklogMetrics {
+	fn default() -> Self {
+		Self {
+			started: Instant::now(),
+			backlogs: Default::default(),
+			topics: Default::default(),
+		}
+	}
+}
+
+impl BacklogMetrics {
+	pub fn backlog(&self, source: MessageSource) -> Option<Backlog> {
+		self.backlogs.lock().unwrap().get(&source).copied()
+	}
+
+	pub fn report(&self) -> BacklogReport {
+		let mut backlogs = self.backlogs.lock().unwrap().iter().map(|(source, backlog)| (*source, *backlog)).collect::<Vec<_>>();
+		backlogs.sort_by_key(|(source, _)| *source);
+
+		let now = self.second(Instant::now());
+		let mut topics = self.topics.lock().unwrap();
+		let mut topics = topics
+			.iter_mut()
+			.map(|((source, topic), throughput)| {
+				throughput.evict(now);
+				TopicThroughput {
+					source: *source,
+					topic: topic.clone(),
+					processed: throughput.processed,
+					per_second: throughput.buckets.iter().map(|(_, count)| count).sum::<u64>() as f64 / RATE_WINDOW.as_secs_f64(),
+				}
+			})
+			.collect::<Vec<_>>();
+		topics.sort_by(|a, b| (a.source, &a.topic).cmp(&(b.source, &b.topic)));
+		BacklogReport { backlogs, topics }
+	}
+
+	/// Called by the transport when it learns the backlog of `source`
+	pub(crate) fn sample(&self, source: MessageSource, backlog: Backlog) {
+		self.backlogs.lock().unwrap().insert(source, backlog);
+		notify(|o| o.backlog_sampled(source, &backlog));
+	}
+
+	/// Called by the transport for each message of `topic` it has processed
+	pub(crate) fn processed(&self, source: MessageSource, topic: &str) {
+		self.record(source, topic, Instant::now());
+		notify(|o| o.message_processed(source, topic));
+	}
+
+	fn record(&self, source: MessageSource, topic: &str, at: Instant) {
+		let second = self.second(at);
+		let mut topics = self.topics.lock().unwrap();
+		let throughput = topics.entry((source, topic.to_string())).or_default();
+		throughput.processed += 1;
+		match throughput.buckets.back_mut() {
+			Some((last, count)) if *last == second => *count += 1,
+			_ => throughput.buckets.push_back((second, 1)),
+		}
+		throughput.evict(second);
+	}
+
+	fn second(&self, at: Instant) -> u64 {
+		at.saturating_duration_since(self.started).as_secs()
+	}
+}
+
+impl Throughput {
+	fn evict(&mut self, now: u64) {
+		while self.buckets.front().is_some_and(|(second, _)| second + RATE_WINDOW.as_secs() <= now) {
+			self.buckets.pop_front();
+		}
+	}
+}
+
+#[test]
+fn test_backlog_metrics() {
+	let metrics = BacklogMetrics::default();
+	metrics.sample(
+		MessageSource::Outbox,
+		Backlog {
+			count: 3,
+			oldest_age: Some(Duration::from_secs(5)),
+		},
+	);
+	assert_eq!(metrics.backlog(MessageSource::Outbox).unwrap().count, 3);
+	assert!(metrics.backlog(MessageSource::Inbox).is_none());
+
+	// Two minutes ago, out of the window
+	metrics.record(MessageSource::Inbox, "OrderPlaced", metrics.started);
+	let now = metrics.started + Duration::from_secs(120);
+	(0..30).for_each(|_| metrics.record(MessageSource::Inbox, "OrderPlaced", now));
+	metrics.record(MessageSource::Outbox, "OrderPlaced", now);
+
+	// `report` evicts against the real clock, so check the buckets directly
+	let mut topics = metrics.topics.lock().unwrap();
+	let inbox = topics.get_mut(&(MessageSource::Inbox, "OrderPlaced".to_string())).unwrap();
+	assert_eq!(inbox.processed, 31);
+	assert_eq!(inbox.buckets, VecDeque::from([(120, 30)]));
+	drop(topics);
+
+	let report = metrics.report();
+	assert_eq!(report.backlogs, vec![(MessageSource::Outbox, metrics.backlog(MessageSource::Outbox).unwrap())]);
+	assert_eq!(
+		report.topics.iter().map(|t| (t.source, t.topic.as_str(), t.processed)).collect::<Vec<_>>(),
+		vec![(MessageSource::Outbox, "OrderPlaced", 1), (MessageSource::Inbox, "OrderPlaced", 31)]
+	);
+}
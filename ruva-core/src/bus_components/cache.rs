@@ -0,0 +1,372 @@
+//! ### Response cache
+//! Query-style command whose response doesn't change until some event happens - product detail, dashboard summary and so on -
+//! is answered from [TCacheStore] set by [set_cache_store] by [CacheAspect], for the TTL of [TCachedCommand].
+//!
+//! ```rust,ignore
+//! // On boot
+//! set_cache_store(InMemoryCacheStore::new(10_000));
+//!
+//! impl TCachedCommand for GetProduct {
+//!     fn ttl() -> Duration {
+//!         Duration::from_secs(60)
+//!     }
+//!     fn invalidated_by() -> &'static [&'static str] {
+//!         &[ProductUpdated::TOPIC, ProductDiscontinued::TOPIC]
+//!     }
+//! }
+//!
+//! impl TCommandRoute for GetProduct {
+//!     fn command_handler(context_manager: AtomicContextManager, cmd: Self) -> impl TCommandService<Self::Response, Self::Error> {
+//!         CacheAspect::new(&context_manager, &cmd, CommandHandler((cmd, ReadContext::new(context_manager.clone()))))
+//!     }
+//! }
+//! ```
+//! Response is cached by the command type, its serialized fields, the tenant of the context and - unless
+//! [TCachedCommand::shared_across_users] - the user it is run for, so that one user's response is never served to another.
+//! Cached responses are invalidated once an event of the topics given by [TCachedCommand::invalidated_by] is handled on the bus -
+//! raised in this process or received by [Inbox](super::inbox::Inbox) - after its handlers run, so that a response cached while
+//! the handlers are still updating read models doesn't outlive them. Errors are not cached. Without cache store, the command is run every time.
+//!
+//! [InMemoryCacheStore] serves a single instance. With `redis` feature, [RedisCacheStore](crate::adapters::redis::RedisCacheStore)
+//! shares cached responses and their invalidation across instances.
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::Duration;
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use serde::{de::DeserializeOwned, Serialize};
+
+use super::contexts::AtomicContextManager;
+use super::messagebus::TCommandService;
+use crate::prelude::{clock, ApplicationError, ApplicationResponse, BaseError, TCommand};
+
+pub trait TCachedCommand: TCommand + Serialize {
+	/// How long the response is served from cache
+	fn ttl() -> Duration;
+	/// Topics of the events that make the cached responses stale
+	fn invalidated_by() -> &'static [&'static str] {
+		&[]
+	}
+	/// Whether the response is the same whoever runs the command, such as public catalog.
+	/// Otherwise, it is cached per user.
+	fn shared_across_users() -> bool {
+		false
+	}
+}
+
+#[async_trait]
+pub trait TCacheStore: Send + Sync {
+	/// `None` if missing or expired
+	async fn get(&self, key: &str) -> Result<Option<String>, BaseError>;
+	/// Cache `value` for `ttl`, to be invalidated with any of `tags`
+	async fn set(&self, key: &str, value: String, ttl: Duration, tags: &[&str]) -> Result<(), BaseError>;
+	/// Remove every value cached with `tag`
+	async fn invalidate(&self, tag: &str) -> Result<(), BaseError>;
+}
+
+#[async_trait]
+impl<T: TCacheStore + ?Sized> TCacheStore for Arc<T> {
+	async fn get(&self, key: &str) -> Result<Option<String>, BaseError> {
+		self.as_ref().get(key).await
+	}
+	async fn set(&self, key: &str, value: String, ttl: Duration, tags: &[&str]) -> Result<(), BaseError> {
+		self.as_ref().set(key, value, ttl, tags).await
+	}
+	async fn invalidate(&self, tag: &str) -> Result<(), BaseError> {
+		self.as_ref().invalidate(tag).await
+	}
+}
+
+struct Cached {
+	value: String,
+	expires_at: DateTime<Utc>,
+	tags: Vec<String>,
+	used_at: u64,
+}
+
+#[derive(Default)]
+struct Lru {
+	entries: hashbrown::HashMap<String, Cached>,
+	/// Keys by the tick they were used last
+	recency: BTreeMap<u64, String>,
+	tagged: hashbrown::HashMap<String, hashbrown::HashSet<String>>,
+	tick: u64,
+}
+
+impl Lru {
+	fn touch(&mut self, key: &str) {
+		self.tick += 1;
+		if let Some(cached) = self.entries.get_mut(key) {
+			self.recency.remove(&cached.used_at);
+			cached.used_at = self.tick;
+			self.recency.insert(self.tick, key.to_string());
+		}
+	}
+
+	fn remove(&mut self, key: &str) {
+		let Some(cached) = self.entries.remove(key) else {
+			return;
+		};
+		self.recency.remove(&cached.used_at);
+		for tag in cached.tags {
+			if let Some(keys) = self.tagged.get_mut(&tag) {
+				keys.remove(key);
+				if keys.is_empty() {
+					self.tagged.remove(&tag);
+				}
+			}
+		}
+	}
+}
+
+/// Least recently used responses are evicted beyond `capacity`. For tests and single instance deployments.
+pub struct InMemoryCacheStore {
+	capacity: usize,
+	lru: Mutex<Lru>,
+}
+
+impl InMemoryCacheStore {
+	pub fn new(capacity: usize) -> Self {
+		Self { capacity, lru: Default::default() }
+	}
+
+	pub fn len(&self) -> usize {
+		self.lru.lock().unwrap().entries.len()
+	}
+
+	pub fn is_empty(&self) -> bool {
+		self.len() == 0
+	}
+}
+
+#[async_trait]
+impl TCacheStore for InMemoryCacheStore {
+	async fn get(&self, key: &str) -> Result<Option<String>, BaseError> {
+		let mut lru = self.lru.lock().unwrap();
+		let Some(cached) = lru.entries.get(key) else {
+			return Ok(None);
+		};
+		if cached.expires_at <= clock().now() {
+			lru.remove(key);
+			return Ok(None);
+		}
+		let value = cached.value.clone();
+		lru.touch(key);
+		Ok(Some(value))
+	}
+
+	async fn set(&self, key: &str, value: String, ttl: Duration, tags: &[&str]) -> Result<(), BaseError> {
+		let mut lru = self.lru.lock().unwrap();
+		lru.remove(key);
+		let cached = Cached {
+			value,
+			expires_at: clock().now() + ttl,
+			tags: tags.iter().map(ToString::to_string).collect(),
+			used_at: 0,
+		};
+		for tag in tags {
+			lru.tagged.entry(tag.to_string()).or_default().insert(key.to_string());
+		}
+		lru.entries.insert(key.to_string(), cached);
+		lru.touch(key);
+		while lru.entries.len() > self.capacity {
+			let Some((_, least_recent)) = lru.recency.pop_first() else {
+				break;
+			};
+			lru.remove(&least_recent);
+		}
+		Ok(())
+	}
+
+	async fn invalidate(&self, tag: &str) -> Result<(), BaseError> {
+		let mut lru = self.lru.lock().unwrap();
+		for key in lru.tagged.remove(tag).unwrap_or_default() {
+			lru.remove(&key);
+		}
+		Ok(())
+	}
+}
+
+static CACHE_STORE: RwLock<Option<Arc<dyn TCacheStore>>> = RwLock::new(None);
+
+pub fn set_cache_store(store: impl TCacheStore + 'static) {
+	*CACHE_STORE.write().unwrap() = Some(Arc::new(store));
+}
+
+pub fn cache_store() -> Option<Arc<dyn TCacheStore>> {
+	CACHE_STORE.read().unwrap().clone()
+}
+
+/// Called by the bus after the handlers of event on `topic` run
+pub(crate) async fn invalidate_cached_responses(topic: &str) {
+	let Some(store) = cache_store() else {
+		return;
+	};
+	if let Err(err) = store.invalidate(topic).await {
+		tracing::error!(topic, "Failed to invalidate cached responses! {:?}", err);
+	}
+}
+
+/// Answer the command with the cached response, or run `inner` and cache its response.
+pub struct CacheAspect<S> {
+	command: &'static str,
+	key: Result<String, BaseError>,
+	ttl: Duration,
+	tags: &'static [&'static str],
+	inner: S,
+}
+
+impl<S> CacheAspect<S> {
+	pub fn new<C: TCachedCommand>(context_manager: &AtomicContextManager, command: &C, inner: S) -> Self {
+		let name = std::any::type_name::<C>();
+		let tenant = context_manager.tenant.as_deref().unwrap_or_default();
+		let user = match C::shared_across_users() {
+			true => "",
+			false => context_manager
+				.current_user
+				.as_ref()
+				.map(|user| user.id.as_str())
+				.or(context_manager.actor.effective_user())
+				.unwrap_or_default(),
+		};
+		Self {
+			command: name,
+			// Serialized so that `:` in tenant or user id can't make keys of different contexts collide
+			key: serde_json::to_string(&(name, tenant, user, command)).map_err(|err| BaseError::DatabaseError(err.to_string())),
+			ttl: C::ttl(),
+			tags: C::invalidated_by(),
+			inner,
+		}
+	}
+}
+
+impl<R, E, S> TCommandService<R, E> for CacheAspect<S>
+where
+	R: ApplicationResponse + Serialize + DeserializeOwned,
+	E: ApplicationError + std::convert::From<BaseError>,
+	S: TCommandService<R, E>,
+{
+	async fn execute(self) -> Result<R, E> {
+		let Some(store) = cache_store() else {
+			return self.inner.execute().await;
+		};
+		let key = self.key?;
+		match store.get(&key).await {
+			Ok(Some(cached)) => match serde_json::from_str(&cached) {
+				Ok(response) => return Ok(response),
+				// Response type has changed since it was cached
+				Err(err) => tracing::warn!(command = self.command, "Cached response is discarded. {}", err),
+			},
+			Ok(None) => {}
+			Err(err) => tracing::error!(command = self.command, "Failed to read cached response! {:?}", err),
+		}
+
+		let res = self.inner.execute().await;
+		if let Ok(response) = res.as_ref() {
+			let response = serde_json::to_string(response).map_err(|err| BaseError::DatabaseError(err.to_string()))?;
+			if let Err(err) = store.set(&key, response, self.ttl, self.tags).await {
+				tracing::error!(command = self.command, "Failed to cache response! {:?}", err);
+			}
+		}
+		res
+	}
+}
+
+#[tokio::test]
+async fn test_in_memory_cache_store() {
+	let store = InMemoryCacheStore::new(2);
+	store.set("a", "1".into(), Duration::from_secs(60), &["ProductUpdated"]).await.unwrap();
+	store.set("b", "2".into(), Duration::from_secs(60), &["ProductUpdated", "PriceChanged"]).await.unwrap();
+	assert_eq!(store.get("a").await.unwrap().as_deref(), Some("1"));
+
+	// `b` is the least recently used
+	store.set("c", "3".into(), Duration::from_secs(60), &["PriceChanged"]).await.unwrap();
+	assert_eq!(store.get("b").await.unwrap(), None);
+	assert_eq!(store.len(), 2);
+
+	store.invalidate("PriceChanged").await.unwrap();
+	assert_eq!(store.get("c").await.unwrap(), None);
+	assert_eq!(store.get("a").await.unwrap().as_deref(), Some("1"));
+
+	store.set("d", "4".into(), Duration::ZERO, &[]).await.unwrap();
+	assert_eq!(store.get("d").await.unwrap(), None);
+	assert!(!store.is_empty());
+}
+
+#[tokio::test]
+async fn test_cache_aspect() {
+	use std::sync::atomic::{AtomicUsize, Ordering};
+
+	#[derive(Debug, Serialize)]
+	struct GetProduct(i64);
+	impl TCommand for GetProduct {}
+	impl TCachedCommand for GetProduct {
+		fn ttl() -> Duration {
+			Duration::from_secs(60)
+		}
+		fn invalidated_by() -> &'static [&'static str] {
+			&["CacheTestProductUpdated"]
+		}
+	}
+	#[derive(Debug, PartialEq, Serialize, serde::Deserialize)]
+	struct Product(i64, usize);
+	impl ApplicationResponse for Product {}
+	static RUN: AtomicUsize = AtomicUsize::new(0);
+	struct Handler(i64);
+	impl TCommandService<Product, BaseError> for Handler {
+		async fn execute(self) -> Result<Product, BaseError> {
+			Ok(Product(self.0, RUN.fetch_add(1, Ordering::SeqCst) + 1))
+		}
+	}
+	struct Connection;
+	impl crate::prelude::TConnection for Connection {}
+	let context_manager = |tenant: &str, user: &str| {
+		Arc::new(
+			crate::prelude::ContextManager::new(&Connection)
+				.with_tenant(tenant)
+				.with_actor(crate::prelude::Actor::User(user.into())),
+		)
+	};
+	let get_product = |context_manager: &AtomicContextManager, id| CacheAspect::new(context_manager, &GetProduct(id), Handler(id));
+
+	set_cache_store(InMemoryCacheStore::new(100));
+	let alice = context_manager("acme", "alice");
+	assert_eq!(get_product(&alice, 1).execute().await.unwrap(), Product(1, 1));
+	assert_eq!(get_product(&alice, 1).execute().await.unwrap(), Product(1, 1));
+	assert_eq!(get_product(&alice, 2).execute().await.unwrap(), Product(2, 2));
+
+	// Neither another user nor the same user of another tenant is answered with alice's response
+	assert_eq!(get_product(&context_manager("acme", "bob"), 1).execute().await.unwrap(), Product(1, 3));
+	assert_eq!(get_product(&context_manager("globex", "alice"), 1).execute().await.unwrap(), Product(1, 4));
+
+	invalidate_cached_responses("CacheTestProductUpdated").await;
+	assert_eq!(get_product(&alice, 1).execute().await.unwrap(), Product(1, 5));
+}
+
+#[tokio::test]
+async fn test_command_failing_to_serialize_is_not_run() {
+	// Map with non-string keys can't be serialized to JSON
+	#[derive(Debug, Serialize)]
+	struct GetPrices(std::collections::HashMap<(i64, i64), i64>);
+	impl TCommand for GetPrices {}
+	impl TCachedCommand for GetPrices {
+		fn ttl() -> Duration {
+			Duration::from_secs(60)
+		}
+	}
+	struct Handler;
+	impl TCommandService<(), BaseError> for Handler {
+		async fn execute(self) -> Result<(), BaseError> {
+			panic!("Command failing to serialize must not be run");
+		}
+	}
+	struct Connection;
+	impl crate::prelude::TConnection for Connection {}
+
+	set_cache_store(InMemoryCacheStore::new(100));
+	let context_manager = Arc::new(crate::prelude::ContextManager::new(&Connection));
+	let command = GetPrices([((1, 2), 3)].into());
+	let res = CacheAspect::new(&context_manager, &command, Handler).execute().await;
+	assert!(matches!(res, Err(BaseError::DatabaseError(_))));
+}
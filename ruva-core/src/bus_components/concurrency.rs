@@ -0,0 +1,92 @@
+//! ### Concurrency limits
+//! Commands share the database pool, so a burst of one heavy command type can starve all the others.
+//! Limits cap the number of commands in flight - for the whole bus and for each command type.
+//!
+//! ```rust
+//! # use ruva_core::prelude::*;
+//! # #[derive(Debug)]
+//! # struct GenerateReport;
+//! # impl TCommand for GenerateReport {}
+//! // On boot
+//! set_global_concurrency_limit(200);
+//! set_command_concurrency_limit::<GenerateReport>(4);
+//! ```
+//! Command over the limit is not queued but fails right away with `BaseError::Overloaded`, answered with 503 by web integrations.
+//! Permit is held while the command is handled and committed, and released before its events are handled.
+use std::sync::{Arc, RwLock};
+
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+
+use crate::prelude::BaseError;
+
+struct Limit {
+	semaphore: Arc<Semaphore>,
+	max: usize,
+}
+
+impl Limit {
+	fn new(max: usize) -> Self {
+		Self {
+			semaphore: Arc::new(Semaphore::new(max)),
+			max,
+		}
+	}
+
+	fn try_acquire(&self, command: &str) -> Result<OwnedSemaphorePermit, BaseError> {
+		self.semaphore.clone().try_acquire_owned().map_err(|_| {
+			tracing::warn!(command, limit = self.max, "Command is rejected by concurrency limit.");
+			BaseError::Overloaded {
+				command: command.to_string(),
+				limit: self.max,
+			}
+		})
+	}
+}
+
+static GLOBAL_LIMIT: RwLock<Option<Arc<Limit>>> = RwLock::new(None);
+static COMMAND_LIMITS: RwLock<Option<hashbrown::HashMap<&'static str, Arc<Limit>>>> = RwLock::new(None);
+
+/// Number of commands of any type in flight at once. Setting it again replaces the limit for commands dispatched from then on.
+pub fn set_global_concurrency_limit(max: usize) {
+	*GLOBAL_LIMIT.write().unwrap() = Some(Arc::new(Limit::new(max)));
+}
+
+/// Number of commands of `C` in flight at once. Setting it again replaces the limit for commands dispatched from then on.
+pub fn set_command_concurrency_limit<C>(max: usize) {
+	COMMAND_LIMITS
+		.write()
+		.unwrap()
+		.get_or_insert_with(Default::default)
+		.insert(std::any::type_name::<C>(), Arc::new(Limit::new(max)));
+}
+
+/// Permits of a command in flight. Dropping it releases them.
+pub(crate) struct ConcurrencyPermit {
+	_global: Option<OwnedSemaphorePermit>,
+	_command: Option<OwnedSemaphorePermit>,
+}
+
+/// Take permits for `command`, failing with `BaseError::Overloaded` if either limit is reached
+pub(crate) fn acquire_concurrency_permit(command: &'static str) -> Result<ConcurrencyPermit, BaseError> {
+	let command_limit = COMMAND_LIMITS.read().unwrap().as_ref().and_then(|limits| limits.get(command).cloned());
+	let _command = command_limit.map(|limit| limit.try_acquire(command)).transpose()?;
+	let global_limit = GLOBAL_LIMIT.read().unwrap().clone();
+	let _global = global_limit.map(|limit| limit.try_acquire(command)).transpose()?;
+	Ok(ConcurrencyPermit { _global, _command })
+}
+
+#[test]
+fn test_command_concurrency_limit() {
+	struct GenerateReport;
+	struct MakeOrder;
+	set_command_concurrency_limit::<GenerateReport>(1);
+
+	let report = std::any::type_name::<GenerateReport>();
+	let permit = acquire_concurrency_permit(report).unwrap();
+	assert!(matches!(acquire_concurrency_permit(report), Err(BaseError::Overloaded { limit: 1, .. })));
+	// Other commands are not affected
+	let _order = acquire_concurrency_permit(std::any::type_name::<MakeOrder>()).unwrap();
+
+	drop(permit);
+	assert!(acquire_concurrency_permit(report).is_ok());
+}
@@ -0,0 +1,193 @@
+//! ### Read-your-writes
+//! Projection catches up on a command some time after it commits, so UI reading right after writing may not see its own change.
+//! [ConsistencyToken] of the dispatch - ids of the outbox rows it committed - is handed to the client, which passes it
+//! back with the next query. The query waits until the projection has consumed every one of them before reading.
+//!
+//! ```rust,ignore
+//! // Command side
+//! let (res, token) = MessageBus.dispatch_with_consistency(cmd, ContextManager::new(conn)).await?;
+//! // Respond with `X-Consistency-Token: {token}`
+//!
+//! // Query side. Projection records the id of every outbox row it consumed.
+//! struct OrderSummary(PgPool);
+//! #[async_trait]
+//! impl TProjectionCheckpoint for OrderSummary {
+//!     async fn consumed(&self, ids: &[i64]) -> Result<bool, BaseError> {
+//!         // SELECT count(*) FROM projection_consumed WHERE name = 'order_summary' AND outbox_id = ANY($1), compared with ids.len()
+//!     }
+//! }
+//! let context_manager = ContextManager::new(conn).with_consistency_token(header.parse()?);
+//! let ctx = ReadContext::new(Arc::new(context_manager));
+//! ctx.wait_for_projection(&OrderSummary(pool), Duration::from_secs(2)).await?;
+//! ```
+//! Consumption is checked per row, not against the largest id consumed, because outbox ids are generated before commit
+//! on many nodes and rows are not published in id order - a row with smaller id may be committed and consumed later.
+//! Token of a dispatch that committed no outbox row is empty, and a query given an empty token doesn't wait.
+use std::collections::BTreeSet;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use async_trait::async_trait;
+
+use super::contexts::{ContextManager, ReadContext};
+use crate::prelude::BaseError;
+
+/// Ids of outbox rows committed by a dispatch. Formatted as comma separated ids.
+#[derive(Debug, Clone, Default, PartialEq, Eq, PartialOrd, Ord, Hash, serde::Serialize, serde::Deserialize)]
+#[serde(transparent)]
+pub struct ConsistencyToken(pub BTreeSet<i64>);
+
+impl ConsistencyToken {
+	pub fn is_empty(&self) -> bool {
+		self.0.is_empty()
+	}
+
+	pub fn merge(&mut self, other: ConsistencyToken) {
+		self.0.extend(other.0);
+	}
+}
+
+impl FromIterator<i64> for ConsistencyToken {
+	fn from_iter<T: IntoIterator<Item = i64>>(iter: T) -> Self {
+		Self(iter.into_iter().collect())
+	}
+}
+
+impl std::fmt::Display for ConsistencyToken {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		for (i, id) in self.0.iter().enumerate() {
+			if i > 0 {
+				f.write_str(",")?;
+			}
+			write!(f, "{}", id)?;
+		}
+		Ok(())
+	}
+}
+
+impl std::str::FromStr for ConsistencyToken {
+	type Err = BaseError;
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		s.split(',')
+			.map(str::trim)
+			.filter(|id| !id.is_empty())
+			.map(|id| id.parse().map_err(|_| BaseError::Rejected(format!("Invalid consistency token {}", s))))
+			.collect()
+	}
+}
+
+/// How far a projection has consumed the outbox
+#[async_trait]
+pub trait TProjectionCheckpoint: Send + Sync {
+	/// Whether every outbox row of `ids` is consumed
+	async fn consumed(&self, ids: &[i64]) -> Result<bool, BaseError>;
+}
+
+/// Outbox rows consumed by a projection kept in memory, for the one updated in process such as by an event handler.
+/// Ids are kept for the lifetime of the process, so it suits tests and small projections.
+#[derive(Debug, Default)]
+pub struct InMemoryProjectionCheckpoint(Mutex<hashbrown::HashSet<i64>>);
+
+impl InMemoryProjectionCheckpoint {
+	/// Record outbox row of `id` as consumed
+	pub fn consume(&self, id: i64) {
+		self.0.lock().unwrap().insert(id);
+	}
+}
+
+#[async_trait]
+impl TProjectionCheckpoint for InMemoryProjectionCheckpoint {
+	async fn consumed(&self, ids: &[i64]) -> Result<bool, BaseError> {
+		let consumed = self.0.lock().unwrap();
+		Ok(ids.iter().all(|id| consumed.contains(id)))
+	}
+}
+
+const MIN_POLL_INTERVAL: Duration = Duration::from_millis(10);
+const MAX_POLL_INTERVAL: Duration = Duration::from_millis(200);
+
+/// Wait until `projection` has consumed every row of `token`, polling at growing interval.
+/// `false` if it doesn't within `timeout`, leaving it to the caller whether to answer with stale data or fail.
+pub async fn wait_for_consistency(projection: &impl TProjectionCheckpoint, token: &ConsistencyToken, timeout: Duration) -> Result<bool, BaseError> {
+	if token.is_empty() {
+		return Ok(true);
+	}
+	let ids = token.0.iter().copied().collect::<Vec<_>>();
+	let deadline = tokio::time::Instant::now() + timeout;
+	let mut interval = MIN_POLL_INTERVAL;
+	loop {
+		if projection.consumed(&ids).await? {
+			return Ok(true);
+		}
+		let now = tokio::time::Instant::now();
+		if now >= deadline {
+			return Ok(false);
+		}
+		tokio::time::sleep(interval.min(deadline - now)).await;
+		interval = (interval * 2).min(MAX_POLL_INTERVAL);
+	}
+}
+
+impl ContextManager {
+	/// Token the client got from the command, for the queries of this context to wait for. See [ReadContext::wait_for_projection].
+	pub fn with_consistency_token(mut self, token: ConsistencyToken) -> Self {
+		self.consistency_token = Some(token);
+		self
+	}
+
+	/// Report outbox rows committed by unit of work, adding them to [UowStats::consistency_token](super::stats::UowStats::consistency_token).
+	/// Done by the unit of work of ruva. Unit of work implemented elsewhere calls it after its commit.
+	pub fn record_consistency_token(&self, token: ConsistencyToken) {
+		self.record(|stats| stats.consistency_token.merge(token));
+	}
+}
+
+impl ReadContext {
+	/// Wait for `projection` to catch up on the token given by [ContextManager::with_consistency_token]. `true` right away if no token is given.
+	pub async fn wait_for_projection(&self, projection: &impl TProjectionCheckpoint, timeout: Duration) -> Result<bool, BaseError> {
+		match self.consistency_token() {
+			Some(token) => wait_for_consistency(projection, &token, timeout).await,
+			None => Ok(true),
+		}
+	}
+}
+
+#[tokio::test]
+async fn test_wait_for_projection() {
+	use crate::prelude::TConnection;
+	use std::sync::Arc;
+
+	struct Connection;
+	impl TConnection for Connection {}
+
+	let context_manager = ContextManager::new(&Connection);
+	context_manager.record_consistency_token(ConsistencyToken::from_iter([7]));
+	context_manager.record_consistency_token(ConsistencyToken::from_iter([5]));
+	assert_eq!(context_manager.stats().consistency_token.to_string(), "5,7");
+
+	let projection = Arc::new(InMemoryProjectionCheckpoint::default());
+	assert!(ReadContext::new(Arc::new(ContextManager::new(&Connection)))
+		.wait_for_projection(projection.as_ref(), Duration::ZERO)
+		.await
+		.unwrap());
+	// Dispatch without outbox rows has nothing to wait for
+	let empty: ConsistencyToken = "".parse().unwrap();
+	assert!(wait_for_consistency(projection.as_ref(), &empty, Duration::ZERO).await.unwrap());
+
+	let token: ConsistencyToken = "5,7".parse().unwrap();
+	let ctx = ReadContext::new(Arc::new(ContextManager::new(&Connection).with_consistency_token(token)));
+	// Row of larger id consumed first doesn't cover the smaller one
+	projection.consume(7);
+	assert!(!ctx.wait_for_projection(projection.as_ref(), Duration::from_millis(30)).await.unwrap());
+
+	let consumer = tokio::spawn({
+		let projection = projection.clone();
+		async move {
+			tokio::time::sleep(Duration::from_millis(20)).await;
+			projection.consume(5);
+		}
+	});
+	assert!(ctx.wait_for_projection(projection.as_ref(), Duration::from_secs(1)).await.unwrap());
+	consumer.await.unwrap();
+	assert!("x".parse::<ConsistencyToken>().is_err());
+}
@@ -1,13 +1,73 @@
+use super::actor::Actor;
 use super::executor::TConnection;
-use crate::{make_smart_pointer, prelude::TEvent};
+use super::messagebus::EventProgress;
+use crate::{
+	make_smart_pointer,
+	prelude::{FlushMode, TEvent},
+};
 use std::{collections::VecDeque, sync::Arc};
 
 /// Request Context Manager
 /// it lives as long as the request lives
-
 pub struct ContextManager {
 	pub event_queue: VecDeque<Arc<dyn TEvent>>,
 	pub conn: &'static dyn TConnection,
+	/// Connection for read-only access. See [ReadContext].
+	pub replica: Option<&'static dyn TConnection>,
+	pub actor: Actor,
+	/// Authenticated user of the request. See [with_current_user](ContextManager::with_current_user).
+	pub current_user: Option<super::current_user::CurrentUser>,
+	/// Receives progress of event processing. See `TMessageBus::execute_and_forget_with_progress`.
+	pub progress: Option<tokio::sync::broadcast::Sender<EventProgress>>,
+	/// Dependencies memoized for this dispatch. See [ContextManager::memoized].
+	pub(crate) memo: super::memo::Memo,
+	/// Dependencies given for this dispatch. See [ContextManager::resolve].
+	pub(crate) dependencies: super::dependency::Dependencies,
+	/// Cap on internally notifiable events of this dispatch. See [EventLimit](super::limit::EventLimit).
+	pub event_limit: Option<super::limit::EventLimit>,
+	pub(crate) enqueued: std::sync::atomic::AtomicUsize,
+	/// Type name of the command that started this dispatch
+	pub(crate) command: &'static str,
+	/// Time source of this dispatch. See [TClock](crate::prelude::TClock).
+	pub clock: Arc<dyn crate::prelude::TClock>,
+	/// See [UowStats](super::stats::UowStats).
+	pub(crate) stats: super::stats::StatsRecorder,
+	/// Selects tenant-specific event handlers. See [tenant_handlers](super::tenant::tenant_handlers).
+	pub tenant: Option<String>,
+	/// Transaction is rolled back instead of committed and events are not handled. See [with_dry_run](ContextManager::with_dry_run).
+	pub dry_run: bool,
+	/// See [CommandJournalAspect](super::journal::CommandJournalAspect).
+	pub(crate) journal_entry: std::sync::Mutex<Option<super::journal::JournalEntry>>,
+	/// See [on_stage](ContextManager::on_stage).
+	pub(crate) stage_hooks: super::pipeline::StageHooks,
+	/// Request metadata stashed by middleware. See [with_extension](ContextManager::with_extension).
+	pub extensions: super::extensions::Extensions,
+	/// Queries of this context wait for projections to catch up on it. See [with_consistency_token](ContextManager::with_consistency_token).
+	pub consistency_token: Option<super::consistency::ConsistencyToken>,
+	/// Shared by every message of the dispatch. See [with_correlation_id](ContextManager::with_correlation_id).
+	pub correlation_id: Option<String>,
+	/// Trace id of where the context manager is created, the same one [ErrorResponse](crate::prelude::ErrorResponse) of the request carries.
+	/// Recorded by [AuditAspect](super::audit::AuditAspect) instead of the one of the command span.
+	pub trace_id: Option<String>,
+	/// See [message_id](ContextManager::message_id).
+	pub(crate) message_id: Option<String>,
+	pub(crate) raised: super::correlation::RaisedEvents,
+	/// See [IdempotencyAspect](super::idempotency::IdempotencyAspect).
+	pub(crate) idempotency_claim: std::sync::Mutex<Option<super::idempotency::IdempotencyClaim>>,
+	/// `(event, handler)` to run, the others skipped. See [ImportOptions](super::import::ImportOptions).
+	pub(crate) selected_handlers: Option<Arc<hashbrown::HashSet<(String, String)>>>,
+	/// See [propagating_handler_errors](ContextManager::propagating_handler_errors).
+	pub(crate) propagate_handler_errors: bool,
+	/// Process-wide one by default. See [set_missing_handler_policy](super::messagebus::set_missing_handler_policy).
+	pub missing_handler_policy: super::messagebus::MissingHandlerPolicy,
+	/// See [with_async_failure_policy](ContextManager::with_async_failure_policy).
+	pub async_failure_policy: super::handler::AsyncFailurePolicy,
+	/// Process-wide one by default. See [set_dead_letter_store](super::dead_letter::set_dead_letter_store).
+	pub(crate) dead_letter_store: Option<Arc<dyn super::dead_letter::TDeadLetterStore>>,
+	/// Process-wide one by default. See [set_authorizer](super::authorization::set_authorizer).
+	pub(crate) authorizer: Option<Arc<dyn super::authorization::TAuthorizer>>,
+	/// Process-wide one by default. See [set_idempotency_store](super::idempotency::set_idempotency_store).
+	pub(crate) idempotency_store: Option<Arc<dyn super::idempotency::TIdempotencyStore>>,
 }
 
 pub type AtomicContextManager = Arc<ContextManager>;
@@ -15,7 +75,95 @@ pub type AtomicContextManager = Arc<ContextManager>;
 impl ContextManager {
 	/// Creation of context manager returns context manager AND event receiver
 	pub fn new(conn: &'static dyn TConnection) -> Self {
-		Self { event_queue: VecDeque::new(), conn }
+		Self {
+			event_queue: VecDeque::new(),
+			conn,
+			replica: None,
+			actor: Actor::default(),
+			current_user: None,
+			progress: None,
+			memo: Default::default(),
+			dependencies: Default::default(),
+			event_limit: super::limit::default_event_limit(),
+			enqueued: Default::default(),
+			command: "",
+			clock: crate::prelude::clock(),
+			stats: Default::default(),
+			tenant: None,
+			dry_run: false,
+			journal_entry: Default::default(),
+			stage_hooks: Default::default(),
+			extensions: Default::default(),
+			consistency_token: None,
+			correlation_id: None,
+			trace_id: crate::prelude::current_trace_id(),
+			message_id: None,
+			raised: Default::default(),
+			idempotency_claim: Default::default(),
+			selected_handlers: None,
+			propagate_handler_errors: false,
+			missing_handler_policy: super::messagebus::missing_handler_policy(),
+			async_failure_policy: Default::default(),
+			dead_letter_store: super::dead_letter::dead_letter_store(),
+			authorizer: super::authorization::authorizer(),
+			idempotency_store: super::idempotency::idempotency_store(),
+		}
+	}
+
+	pub fn with_progress(mut self, progress: tokio::sync::broadcast::Sender<EventProgress>) -> Self {
+		self.progress = Some(progress);
+		self
+	}
+
+	pub fn with_replica(mut self, replica: &'static dyn TConnection) -> Self {
+		self.replica = Some(replica);
+		self
+	}
+
+	pub fn with_clock(mut self, clock: Arc<dyn crate::prelude::TClock>) -> Self {
+		self.clock = clock;
+		self
+	}
+
+	pub fn with_actor(mut self, actor: Actor) -> Self {
+		self.actor = actor;
+		self
+	}
+
+	pub fn with_missing_handler_policy(mut self, policy: super::messagebus::MissingHandlerPolicy) -> Self {
+		self.missing_handler_policy = policy;
+		self
+	}
+
+	/// What to do with the other handlers of async handler group when one of them fails in this dispatch
+	pub fn with_async_failure_policy(mut self, policy: super::handler::AsyncFailurePolicy) -> Self {
+		self.async_failure_policy = policy;
+		self
+	}
+
+	/// Keep dead letters of this dispatch in `store`, instead of the process-wide one
+	pub fn with_dead_letter_store(mut self, store: impl super::dead_letter::TDeadLetterStore + 'static) -> Self {
+		self.dead_letter_store = Some(Arc::new(store));
+		self
+	}
+
+	/// Authorize commands of this dispatch with `authorizer`, instead of the process-wide one
+	pub fn with_authorizer(mut self, authorizer: impl super::authorization::TAuthorizer + 'static) -> Self {
+		self.authorizer = Some(Arc::new(authorizer));
+		self
+	}
+
+	/// Claim idempotency keys of this dispatch in `store`, instead of the process-wide one
+	pub fn with_idempotency_store(mut self, store: impl super::idempotency::TIdempotencyStore + 'static) -> Self {
+		self.idempotency_store = Some(Arc::new(store));
+		self
+	}
+
+	/// Fail event handling with the first handler error, leaving the rest of the queue unprocessed, instead of dead-lettering it
+	/// and going on. For callers that take the failure on themselves, such as [Inbox](super::inbox::Inbox) letting the event be redelivered.
+	pub fn propagating_handler_errors(mut self) -> Self {
+		self.propagate_handler_errors = true;
+		self
 	}
 
 	/// SAFETY: This is safe because we are sure this method is used only in the context of command and event handling
@@ -34,6 +182,13 @@ pub struct Context {
 
 	#[cfg(feature = "sqlx-postgres")]
 	pub(crate) pg_transaction: Option<sqlx::Transaction<'static, sqlx::Postgres>>,
+	#[cfg(feature = "sqlx-sqlite")]
+	pub(crate) sqlite_transaction: Option<sqlx::Transaction<'static, sqlx::Sqlite>>,
+	#[cfg(feature = "mongodb")]
+	pub(crate) mongo_session: Option<mongodb::ClientSession>,
+	pub(crate) fake_transaction: Option<crate::testing::FakeTransaction>,
+	/// Outbox rows written in the transaction, reported once it is committed
+	pub(crate) uncommitted_token: super::consistency::ConsistencyToken,
 }
 
 impl Context {
@@ -43,10 +198,70 @@ impl Context {
 			super_ctx,
 			#[cfg(feature = "sqlx-postgres")]
 			pg_transaction: None,
+			#[cfg(feature = "sqlx-sqlite")]
+			sqlite_transaction: None,
+			#[cfg(feature = "mongodb")]
+			mongo_session: None,
+			fake_transaction: None,
+			uncommitted_token: Default::default(),
 		}
 	}
 
+	/// Unit of work should roll back instead of committing when it is true
+	pub fn is_dry_run(&self) -> bool {
+		self.super_ctx.dry_run
+	}
+
+	/// Current time by the clock of the dispatch
+	pub fn now(&self) -> chrono::DateTime<chrono::Utc> {
+		self.super_ctx.clock.now()
+	}
+
+	/// Raise event that is not from aggregate.
+	pub fn raise(&mut self, event: impl TEvent + 'static) {
+		self.buffer([Arc::new(event) as Arc<dyn TEvent>]);
+	}
+
+	/// Raise several events at once, keeping their relative order. Nothing else is put between them.
+	/// ## Example
+	/// ```rust
+	/// # use std::sync::Arc;
+	/// # use ruva_core::prelude::*;
+	/// # struct Connection;
+	/// # impl TConnection for Connection {}
+	/// # struct OrderPlaced;
+	/// # impl TEvent for OrderPlaced { fn state(&self) -> String { "{}".into() } }
+	/// # struct StockReserved;
+	/// # impl TEvent for StockReserved { fn state(&self) -> String { "{}".into() } }
+	/// # struct PaymentRequested;
+	/// # impl TEvent for PaymentRequested { fn state(&self) -> String { "{}".into() } }
+	/// # let mut ctx = Context::new(Arc::new(ContextManager::new(&Connection)));
+	/// ctx.raise_all([Arc::new(OrderPlaced) as Arc<dyn TEvent>, Arc::new(StockReserved), Arc::new(PaymentRequested)]);
+	/// ```
+	pub fn raise_all(&mut self, events: impl IntoIterator<Item = Arc<dyn TEvent>>) {
+		self.buffer(events);
+	}
+
+	/// Keep events until commit. Internally notifiable event with [FlushMode::Immediate] is also put on the event queue right away.
+	fn buffer(&mut self, events: impl IntoIterator<Item = Arc<dyn TEvent>>) {
+		for event in events {
+			self.super_ctx.record(|stats| stats.events_raised += 1);
+			self.super_ctx.assign_message_id(&event);
+			if event.internally_notifiable() && event.flush_mode() == FlushMode::Immediate {
+				self.enqueue(event.clone());
+			}
+			self.curr_events.push_back(event);
+		}
+	}
+
+	fn enqueue(&self, event: Arc<dyn TEvent>) {
+		super::observer::notify(|o| o.event_enqueued(&event.metadata().topic));
+		self.super_ctx.enqueued.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+		self.super_ctx.get_mut().push_back(event)
+	}
+
 	pub fn event_hook(&mut self, aggregate: &mut impl crate::prelude::TAggregate) {
+		self.set_current_events(aggregate.mutation_events());
 		self.set_current_events(aggregate.take_events());
 	}
 
@@ -55,8 +270,66 @@ impl Context {
 
 		self.curr_events
 			.iter()
-			.filter(|e| e.internally_notifiable())
-			.for_each(|e| self.super_ctx.get_mut().push_back(e.clone()));
+			.filter(|e| e.internally_notifiable() && e.flush_mode() == FlushMode::AfterCommit)
+			.for_each(|e| self.enqueue(e.clone()));
+	}
+}
+
+/// Read-only context for event handlers that only read data.
+/// It is bound to the replica connection if [ContextManager] has one, otherwise to the primary one.
+/// There is no transaction, no commit hooks and no way to raise events.
+///
+/// ```rust,ignore
+/// init_event_handler!(
+///     ServiceError,
+///     |ctx| OrderReadHandler(ReadContext::new(ctx)),
+///     OrderSucceeded: [send_receipt],
+/// );
+///
+/// impl TOrderReader for ReadContext {
+///     async fn get(&self, id: i64) -> Result<Order, BaseError> {
+///         sqlx::query_as("SELECT * FROM orders WHERE id = $1").bind(id).fetch_one(self.pg_pool()).await
+///     }
+/// }
+/// ```
+pub struct ReadContext {
+	conn: &'static dyn TConnection,
+	pub(crate) super_ctx: AtomicContextManager,
+}
+
+impl ReadContext {
+	pub fn new(super_ctx: AtomicContextManager) -> Self {
+		Self {
+			conn: super_ctx.replica.unwrap_or(super_ctx.conn),
+			super_ctx,
+		}
+	}
+
+	pub fn actor(&self) -> &Actor {
+		&self.super_ctx.actor
+	}
+
+	pub fn current_user(&self) -> Option<&super::current_user::CurrentUser> {
+		self.super_ctx.current_user.as_ref()
+	}
+
+	pub fn tenant(&self) -> Option<&str> {
+		self.super_ctx.tenant.as_deref()
+	}
+
+	pub fn consistency_token(&self) -> Option<super::consistency::ConsistencyToken> {
+		self.super_ctx.consistency_token.clone()
+	}
+}
+
+/// Repository that only reads. Implemented by [ReadContext].
+pub trait TReadRepository: Send + Sync {
+	fn connection(&self) -> &'static dyn TConnection;
+}
+
+impl TReadRepository for ReadContext {
+	fn connection(&self) -> &'static dyn TConnection {
+		self.conn
 	}
 }
 
@@ -66,8 +339,62 @@ pub trait TSetCurrentEvents: Send + Sync {
 
 impl TSetCurrentEvents for Context {
 	fn set_current_events(&mut self, events: VecDeque<std::sync::Arc<dyn TEvent>>) {
-		self.curr_events.extend(events)
+		self.buffer(events)
+	}
+}
+
+#[test]
+fn test_raise_all_keeps_order() {
+	struct CustomConnection;
+	impl TConnection for CustomConnection {}
+	#[derive(Debug)]
+	struct Raised(usize);
+	impl TEvent for Raised {
+		fn state(&self) -> String {
+			self.0.to_string()
+		}
+	}
+
+	let mut ctx = Context::new(Arc::new(ContextManager::new(&CustomConnection)));
+	ctx.raise(Raised(0));
+	ctx.raise_all((1..4).map(|i| Arc::new(Raised(i)) as Arc<dyn TEvent>));
+	assert_eq!(ctx.curr_events.iter().map(|e| e.state()).collect::<Vec<_>>(), vec!["0", "1", "2", "3"]);
+}
+
+#[tokio::test]
+async fn test_flush_mode() {
+	struct CustomConnection;
+	impl TConnection for CustomConnection {}
+	struct Audited;
+	impl TEvent for Audited {
+		fn internally_notifiable(&self) -> bool {
+			true
+		}
+		fn flush_mode(&self) -> FlushMode {
+			FlushMode::Immediate
+		}
+		fn state(&self) -> String {
+			"{}".into()
+		}
 	}
+	struct Committed;
+	impl TEvent for Committed {
+		fn internally_notifiable(&self) -> bool {
+			true
+		}
+		fn state(&self) -> String {
+			"{}".into()
+		}
+	}
+
+	let context_manager = Arc::new(ContextManager::new(&CustomConnection));
+	let mut ctx = Context::new(Arc::clone(&context_manager));
+	ctx.raise(Committed);
+	ctx.raise(Audited);
+	assert_eq!(context_manager.iter().map(|e| e.metadata().topic).collect::<Vec<_>>(), vec!["Audited"]);
+
+	ctx.send_internally_notifiable_messages().await;
+	assert_eq!(context_manager.iter().map(|e| e.metadata().topic).collect::<Vec<_>>(), vec!["Audited", "Committed"]);
 }
 
 #[tokio::test]
@@ -102,3 +429,17 @@ async fn test_context_managers() {
 	let events = context_manager.iter().map(|e| e.downcast_ref::<CustomEvent>().unwrap().0).collect::<Vec<_>>();
 	assert_eq!(events, (0..count).collect::<Vec<_>>());
 }
+
+#[test]
+fn test_read_context_prefers_replica() {
+	struct Primary;
+	impl TConnection for Primary {}
+	struct Replica;
+	impl TConnection for Replica {}
+
+	let ctx = ReadContext::new(Arc::new(ContextManager::new(&Primary)));
+	assert!(ctx.connection().downcast_ref::<Primary>().is_some());
+
+	let ctx = ReadContext::new(Arc::new(ContextManager::new(&Primary).with_replica(&Replica)));
+	assert!(ctx.connection().downcast_ref::<Replica>().is_some());
+}
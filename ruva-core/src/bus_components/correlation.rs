@@ -0,0 +1,214 @@
+//! ### Correlation and causation
+//! Every dispatch has a correlation id, given with [ContextManager::with_correlation_id] - from `X-Correlation-Id` header, for instance -
+//! or generated otherwise. Every message handled in it has its own id: the command gets one at dispatch, and event gets one when it is raised,
+//! which is also the id of its outbox row. Events raised while handling a message carry the correlation id and the id of that message
+//! as causation id, in [EventMetadata] given by [Context::metadata] and in [OutBox].
+//!
+//! ```rust
+//! # use ruva_core::prelude::*;
+//! # async fn example<C>(cmd: C, conn: &'static dyn TConnection) -> Result<(), C::Error>
+//! # where C: TCommandRoute, C::Error: From<BaseError>, BaseError: From<C::Error>, MessageBus: TEventBus<C::Error> {
+//! # let request_id = "9f2c1d";
+//! let context_manager = ContextManager::new(conn).with_correlation_id(request_id);
+//! MessageBus.dispatch_with(cmd, context_manager).await?;
+//!
+//! // In command or event handler
+//! # let ctx = Context::new(std::sync::Arc::new(ContextManager::new(conn)));
+//! tracing::info!(correlation_id = ctx.correlation_id(), causation_id = ctx.message_id(), "Reserving stock");
+//!
+//! // On boot, to store them in the outbox
+//! enable_outbox_correlation();
+//! # Ok(())
+//! # }
+//! ```
+//! Publisher sends them along with the event (`correlation_id` and `causation_id` headers of Kafka), and [Inbox](super::inbox::Inbox)
+//! continues the chain - events raised by the handlers of inbound event carry its correlation id and have its id as causation id.
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+
+use super::contexts::{Context, ContextManager, ReadContext};
+use crate::prelude::{BaseError, EventMetadata, OutBox, SnowFlake, TEvent};
+
+/// Ids of the events raised in the dispatch, kept until the dispatch ends.
+/// Event is held so that its address, which is the key, is not reused by another event in the meantime.
+#[derive(Default)]
+pub(crate) struct RaisedEvents(Mutex<hashbrown::HashMap<usize, Raised>>);
+
+struct Raised {
+	_event: Arc<dyn TEvent>,
+	id: String,
+	causation_id: Option<String>,
+}
+
+impl RaisedEvents {
+	fn key(event: &Arc<dyn TEvent>) -> usize {
+		Arc::as_ptr(event) as *const () as usize
+	}
+
+	pub(crate) fn assign(&self, event: &Arc<dyn TEvent>, id: String, causation_id: Option<String>) {
+		let raised = Raised {
+			_event: event.clone(),
+			id,
+			causation_id,
+		};
+		self.0.lock().unwrap().insert(Self::key(event), raised);
+	}
+
+	/// Id and causation id of `event`, if it is raised in the dispatch
+	fn get(&self, event: &Arc<dyn TEvent>) -> Option<(String, Option<String>)> {
+		self.0.lock().unwrap().get(&Self::key(event)).map(|raised| (raised.id.clone(), raised.causation_id.clone()))
+	}
+}
+
+fn generate_id() -> String {
+	SnowFlake::generate().to_string()
+}
+
+impl ContextManager {
+	/// Correlation id inherited from the caller. Generated at dispatch if not given.
+	pub fn with_correlation_id(mut self, correlation_id: impl Into<String>) -> Self {
+		self.correlation_id = Some(correlation_id.into());
+		self
+	}
+
+	pub fn correlation_id(&self) -> Option<&str> {
+		self.correlation_id.as_deref()
+	}
+
+	/// Id of the message being handled - the command, or the event whose handlers are running.
+	/// It is the causation id of the events raised now.
+	pub fn message_id(&self) -> Option<&str> {
+		self.message_id.as_deref()
+	}
+
+	/// Give the command of the dispatch its id.
+	pub(crate) fn start_command(mut self) -> Self {
+		self.correlation_id.get_or_insert_with(generate_id);
+		self.message_id = Some(generate_id());
+		self
+	}
+
+	/// Make `event` the message being handled. Event that is not raised in the dispatch, such as the one given to `handle_events`, gets a new id.
+	pub(crate) fn start_event(self: &Arc<Self>, event: &Arc<dyn TEvent>) {
+		let message_id = self.raised.get(event).map_or_else(generate_id, |(id, _)| id);
+		let context_manager = self.get_mut();
+		context_manager.correlation_id.get_or_insert_with(generate_id);
+		context_manager.message_id = Some(message_id);
+	}
+
+	/// Id `event` is given when it is raised now
+	pub(crate) fn assign_message_id(&self, event: &Arc<dyn TEvent>) {
+		self.raised.assign(event, generate_id(), self.message_id.clone());
+	}
+}
+
+impl Context {
+	pub fn correlation_id(&self) -> Option<&str> {
+		self.super_ctx.correlation_id()
+	}
+
+	/// See [ContextManager::message_id]
+	pub fn message_id(&self) -> Option<&str> {
+		self.super_ctx.message_id()
+	}
+
+	/// Metadata of `event` raised in the dispatch, with correlation id and causation id filled.
+	pub fn metadata(&self, event: &Arc<dyn TEvent>) -> EventMetadata {
+		let causation_id = self.super_ctx.raised.get(event).and_then(|(_, causation_id)| causation_id);
+		EventMetadata {
+			correlation_id: self.super_ctx.correlation_id.clone(),
+			causation_id,
+			..event.metadata()
+		}
+	}
+
+	/// Outbox row of `event` raised in the dispatch. Its id is the id of the event.
+	pub fn outbox(&self, event: &Arc<dyn TEvent>) -> Result<OutBox, BaseError> {
+		let metadata = self.metadata(event);
+		let outbox = event.outbox()?;
+		Ok(OutBox {
+			id: self.super_ctx.raised.get(event).and_then(|(id, _)| id.parse().ok()).unwrap_or(outbox.id),
+			correlation_id: metadata.correlation_id,
+			causation_id: metadata.causation_id,
+			..outbox
+		})
+	}
+}
+
+impl ReadContext {
+	pub fn correlation_id(&self) -> Option<&str> {
+		self.super_ctx.correlation_id()
+	}
+
+	/// See [ContextManager::message_id]
+	pub fn message_id(&self) -> Option<&str> {
+		self.super_ctx.message_id()
+	}
+}
+
+static OUTBOX_CORRELATION: AtomicBool = AtomicBool::new(false);
+
+/// Store `OutBox::correlation_id` and `OutBox::causation_id` of rows written from now on. Requires their columns of `service_outbox`.
+/// ```sql
+/// ALTER TABLE service_outbox ADD COLUMN correlation_id TEXT, ADD COLUMN causation_id TEXT;
+/// ```
+pub fn enable_outbox_correlation() {
+	OUTBOX_CORRELATION.store(true, Ordering::Relaxed);
+}
+
+pub fn outbox_correlation_enabled() -> bool {
+	OUTBOX_CORRELATION.load(Ordering::Relaxed)
+}
+
+#[test]
+fn test_correlation_and_causation() {
+	use super::executor::TConnection;
+
+	struct Connection;
+	impl TConnection for Connection {}
+	struct OrderPlaced;
+	impl TEvent for OrderPlaced {
+		fn externally_notifiable(&self) -> bool {
+			true
+		}
+		fn internally_notifiable(&self) -> bool {
+			true
+		}
+		fn state(&self) -> String {
+			"{}".into()
+		}
+	}
+	struct StockReserved;
+	impl TEvent for StockReserved {
+		fn externally_notifiable(&self) -> bool {
+			true
+		}
+		fn state(&self) -> String {
+			"{}".into()
+		}
+	}
+
+	// Correlation id is generated unless given
+	assert!(ContextManager::new(&Connection).start_command().correlation_id().is_some());
+
+	let context_manager = Arc::new(ContextManager::new(&Connection).with_correlation_id("request-1").start_command());
+	let command_id = context_manager.message_id().map(ToString::to_string);
+	let mut ctx = Context::new(context_manager.clone());
+	ctx.raise(OrderPlaced);
+	let order_placed = ctx.curr_events[0].clone();
+	let metadata = ctx.metadata(&order_placed);
+	assert_eq!((metadata.topic.as_str(), metadata.correlation_id.as_deref()), ("OrderPlaced", Some("request-1")));
+	assert_eq!(metadata.causation_id, command_id);
+	let outbox = ctx.outbox(&order_placed).unwrap();
+	assert_eq!((outbox.correlation_id.as_deref(), outbox.causation_id), (Some("request-1"), command_id));
+
+	// Handler of OrderPlaced raises StockReserved, caused by it
+	context_manager.start_event(&order_placed);
+	assert_eq!(context_manager.message_id(), Some(outbox.id.to_string().as_str()));
+	let mut ctx = Context::new(context_manager.clone());
+	ctx.raise(StockReserved);
+	let stock_reserved = ctx.outbox(&ctx.curr_events[0].clone()).unwrap();
+	assert_eq!(stock_reserved.correlation_id.as_deref(), Some("request-1"));
+	assert_eq!(stock_reserved.causation_id, Some(outbox.id.to_string()));
+	assert_ne!(stock_reserved.id, outbox.id);
+}
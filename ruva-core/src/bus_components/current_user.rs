@@ -0,0 +1,148 @@
+//! ### Current user
+//! Authenticated user of the request with roles and claims, so that services share one type instead of each defining their own.
+//! Web adapter builds it from the claims of validated JWT and hands it to the dispatch, and handlers and aspects read it from context.
+//!
+//! ```rust,ignore
+//! // In auth middleware, after the token is validated with `jsonwebtoken` or alike
+//! let user = CurrentUser::from_jwt_claims(token_data.claims)?;
+//! req.extensions_mut().insert(user);
+//!
+//! // In axum handler
+//! async fn make_order(bus: BusState, user: CurrentUser, CommandExtractor(cmd): CommandExtractor<MakeOrder>) -> Result<Json<ServiceResponse>, HttpError<ServiceError>> {
+//!     Ok(Json(bus.dispatch_as(user, cmd).await?))
+//! }
+//!
+//! // In command handler
+//! let tier: Option<String> = ctx.current_user().and_then(|user| user.claim("tier"));
+//! ```
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+use super::actor::Actor;
+use super::contexts::{Context, ContextManager};
+use crate::prelude::BaseError;
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CurrentUser {
+	pub id: String,
+	#[serde(default)]
+	pub roles: Vec<String>,
+	/// OAuth scopes the token is granted. Kept apart from `roles` as they are chosen by the client, not given to the user.
+	#[serde(default)]
+	pub scopes: Vec<String>,
+	/// Every claim of the token, including the ones taken as `id` and `roles`
+	#[serde(default)]
+	pub claims: serde_json::Map<String, serde_json::Value>,
+}
+
+impl CurrentUser {
+	pub fn new(id: impl Into<String>) -> Self {
+		Self { id: id.into(), ..Default::default() }
+	}
+
+	pub fn with_role(mut self, role: impl Into<String>) -> Self {
+		self.roles.push(role.into());
+		self
+	}
+
+	pub fn with_scope(mut self, scope: impl Into<String>) -> Self {
+		self.scopes.push(scope.into());
+		self
+	}
+
+	pub fn with_claim(mut self, name: impl Into<String>, value: impl Into<serde_json::Value>) -> Self {
+		self.claims.insert(name.into(), value.into());
+		self
+	}
+
+	/// `id` is taken from `sub` claim, `roles` from `roles` array and `scopes` from space separated `scope`.
+	/// ## Errors
+	/// [BaseError::Rejected] if `claims` is not an object or has no `sub`.
+	pub fn from_jwt_claims(claims: serde_json::Value) -> Result<Self, BaseError> {
+		let serde_json::Value::Object(claims) = claims else {
+			return Err(BaseError::Rejected("JWT claims must be an object".to_string()));
+		};
+		let Some(id) = claims.get("sub").and_then(|sub| sub.as_str()) else {
+			return Err(BaseError::Rejected("JWT claims have no sub".to_string()));
+		};
+		let roles = claims.get("roles").and_then(|roles| roles.as_array()).into_iter().flatten().filter_map(|role| role.as_str());
+		let scopes = claims.get("scope").and_then(|scope| scope.as_str()).into_iter().flat_map(str::split_whitespace);
+		Ok(Self {
+			id: id.to_string(),
+			roles: roles.map(ToString::to_string).collect(),
+			scopes: scopes.map(ToString::to_string).collect(),
+			claims: claims.clone(),
+		})
+	}
+
+	pub fn has_role(&self, role: &str) -> bool {
+		self.roles.iter().any(|r| r == role)
+	}
+
+	pub fn has_scope(&self, scope: &str) -> bool {
+		self.scopes.iter().any(|s| s == scope)
+	}
+
+	/// Claim of `name` as `T`. `None` if it is absent or of another type.
+	pub fn claim<T: DeserializeOwned>(&self, name: &str) -> Option<T> {
+		self.claims.get(name).and_then(|value| T::deserialize(value).ok())
+	}
+}
+
+impl ContextManager {
+	/// Actor becomes the user, unless it is given already, for example as impersonation.
+	pub fn with_current_user(mut self, user: CurrentUser) -> Self {
+		if self.actor == Actor::Anonymous {
+			self.actor = Actor::User(user.id.clone());
+		}
+		self.current_user = Some(user);
+		self
+	}
+}
+
+impl Context {
+	pub fn current_user(&self) -> Option<&CurrentUser> {
+		self.super_ctx.current_user.as_ref()
+	}
+}
+
+#[test]
+fn test_current_user_from_jwt_claims() {
+	let user = CurrentUser::from_jwt_claims(serde_json::json!({
+		"sub": "migo",
+		"roles": ["admin"],
+		"scope": "orders:read orders:write",
+		"tier": "gold",
+	}))
+	.unwrap();
+	assert_eq!(user.id, "migo");
+	assert_eq!(user.roles, vec!["admin"]);
+	assert_eq!(user.scopes, vec!["orders:read", "orders:write"]);
+	assert!(user.has_scope("orders:write"));
+	assert!(!user.has_role("orders:write"));
+
+	assert_eq!(user.claim::<String>("tier").as_deref(), Some("gold"));
+	assert_eq!(user.claim::<i64>("tier"), None);
+
+	// Scope named after a role doesn't grant the role
+	let scoped = CurrentUser::from_jwt_claims(serde_json::json!({"sub": "migo", "scope": "admin"})).unwrap();
+	assert!(!scoped.has_role("admin"));
+
+	assert!(matches!(CurrentUser::from_jwt_claims(serde_json::json!({"roles": []})), Err(BaseError::Rejected(_))));
+}
+
+#[test]
+fn test_current_user_sets_actor() {
+	struct Connection;
+	impl super::executor::TConnection for Connection {}
+
+	let context_manager = ContextManager::new(&Connection).with_current_user(CurrentUser::new("migo").with_role("admin"));
+	assert_eq!(context_manager.actor, Actor::User("migo".into()));
+
+	let impersonated = Actor::Impersonated {
+		admin: "admin".into(),
+		as_user: "migo".into(),
+	};
+	let context_manager = ContextManager::new(&Connection).with_actor(impersonated.clone()).with_current_user(CurrentUser::new("migo"));
+	assert_eq!(context_manager.actor, impersonated);
+	assert!(context_manager.current_user.unwrap().roles.is_empty());
+}
@@ -0,0 +1,273 @@
+//! ### Dead letters of event handlers
+//! Event handler that fails is logged and the bus goes on with the next one. With [set_dead_letter_store],
+//! the event and the error are also kept in [TDeadLetterStore] so that they can be replayed once the cause is fixed.
+//!
+//! ```rust,ignore
+//! // On boot. `PgPool` implements `TDeadLetterStore` with `sqlx-postgres` feature.
+//! set_dead_letter_store(pool.clone());
+//!
+//! // Later, from admin endpoint
+//! let report = DeadLetterReplay::new(conn, pool.clone()).register::<OrderSucceeded>().replay(&MessageBus, 100).await?;
+//! ```
+//! Only the handler that failed is run again on replay, not the whole handler list of the topic.
+//! Dead letter is removed once its handler succeeds.
+//!
+//! The same store keeps inbound events that [EventPolicies](super::policy::EventPolicies) failed to translate, without handler.
+//! They are skipped on replay.
+use std::sync::{Arc, Mutex, RwLock};
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use serde::de::DeserializeOwned;
+
+use super::actor::Actor;
+use super::contexts::ContextManager;
+use super::executor::TConnection;
+use super::handler::EventHandlers;
+use super::messagebus::{handle_next_event, TEventBus};
+use crate::prelude::{json, upcast_payload, ApplicationError, BaseError, SnowFlake, TEvent, TTopic};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DeadLetter {
+	pub id: i64,
+	pub topic: String,
+	/// Position of the failed handler in the list registered for `topic`. `None` if the event failed before reaching handler.
+	pub handler_index: Option<usize>,
+	/// `TEvent::state()` of the event
+	pub payload: String,
+	/// `EventMetadata::version` of the payload. Payload is upcast on replay if the event has moved on.
+	pub version: u32,
+	pub error: String,
+	pub failed_at: DateTime<Utc>,
+}
+
+#[async_trait]
+pub trait TDeadLetterStore: Send + Sync {
+	async fn push(&self, dead_letter: &DeadLetter) -> Result<(), BaseError>;
+	/// The oldest ones first
+	async fn fetch(&self, limit: usize) -> Result<Vec<DeadLetter>, BaseError>;
+	async fn remove(&self, id: i64) -> Result<(), BaseError>;
+}
+
+#[async_trait]
+impl<T: TDeadLetterStore + ?Sized> TDeadLetterStore for Arc<T> {
+	async fn push(&self, dead_letter: &DeadLetter) -> Result<(), BaseError> {
+		self.as_ref().push(dead_letter).await
+	}
+	async fn fetch(&self, limit: usize) -> Result<Vec<DeadLetter>, BaseError> {
+		self.as_ref().fetch(limit).await
+	}
+	async fn remove(&self, id: i64) -> Result<(), BaseError> {
+		self.as_ref().remove(id).await
+	}
+}
+
+/// For tests and single instance deployments. Dead letters are lost on restart.
+#[derive(Default)]
+pub struct InMemoryDeadLetterStore(Mutex<Vec<DeadLetter>>);
+
+#[async_trait]
+impl TDeadLetterStore for InMemoryDeadLetterStore {
+	async fn push(&self, dead_letter: &DeadLetter) -> Result<(), BaseError> {
+		self.0.lock().unwrap().push(dead_letter.clone());
+		Ok(())
+	}
+	async fn fetch(&self, limit: usize) -> Result<Vec<DeadLetter>, BaseError> {
+		Ok(self.0.lock().unwrap().iter().take(limit).cloned().collect())
+	}
+	async fn remove(&self, id: i64) -> Result<(), BaseError> {
+		self.0.lock().unwrap().retain(|dead_letter| dead_letter.id != id);
+		Ok(())
+	}
+}
+
+static DEAD_LETTER_STORE: RwLock<Option<Arc<dyn TDeadLetterStore>>> = RwLock::new(None);
+
+/// Keep events whose handler failed in `store`. Not set by default, in which case the failure is only logged.
+/// Dispatch can be given its own with [ContextManager::with_dead_letter_store].
+pub fn set_dead_letter_store(store: impl TDeadLetterStore + 'static) {
+	*DEAD_LETTER_STORE.write().unwrap() = Some(Arc::new(store));
+}
+
+pub(crate) fn dead_letter_store() -> Option<Arc<dyn TDeadLetterStore>> {
+	DEAD_LETTER_STORE.read().unwrap().clone()
+}
+
+/// Called by the bus when `index`th handler of `topic` fails. Failure to keep the dead letter is only logged.
+pub(crate) async fn dead_letter(context_manager: &ContextManager, topic: &str, index: usize, event: &dyn TEvent, error: &BaseError) {
+	let Some(store) = context_manager.dead_letter_store.as_ref() else {
+		return;
+	};
+	let dead_letter = DeadLetter {
+		id: *SnowFlake::generate(),
+		topic: topic.to_string(),
+		handler_index: Some(index),
+		payload: event.state(),
+		version: event.metadata().version,
+		error: format!("{:?}", error),
+		failed_at: context_manager.clock.now(),
+	};
+	if let Err(err) = store.push(&dead_letter).await {
+		tracing::error!(topic, index, "Failed to keep dead letter! {:?}", err);
+	}
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DeadLetterReplayReport {
+	pub replayed: usize,
+	/// Ids of dead letters whose handler failed again. They are kept in the store.
+	pub failed: Vec<i64>,
+	/// Ids of dead letters whose topic is not registered or whose handler no longer exists
+	pub skipped: Vec<i64>,
+}
+
+type Deserialize = fn(&str) -> Result<Arc<dyn TEvent>, json::Error>;
+
+pub struct DeadLetterReplay<S> {
+	conn: &'static dyn TConnection,
+	store: S,
+	routes: hashbrown::HashMap<String, Deserialize>,
+}
+
+impl<S: TDeadLetterStore> DeadLetterReplay<S> {
+	pub fn new(conn: &'static dyn TConnection, store: S) -> Self {
+		Self {
+			conn,
+			store,
+			routes: Default::default(),
+		}
+	}
+
+	/// Replay dead letters of `T`
+	pub fn register<T: TEvent + TTopic + DeserializeOwned + 'static>(mut self) -> Self {
+		self.routes
+			.insert(T::TOPIC.to_string(), |payload| json::from_str::<T>(payload).map(|event| Arc::new(event) as Arc<dyn TEvent>));
+		self
+	}
+
+	/// Run the failed handler of up to `limit` dead letters again, along with the events they raise.
+	pub async fn replay<E>(&self, bus: &(impl TEventBus<E> + Sync), limit: usize) -> Result<DeadLetterReplayReport, BaseError>
+	where
+		E: ApplicationError + std::convert::From<BaseError> + 'static,
+		BaseError: std::convert::From<E>,
+	{
+		let mut report = DeadLetterReplayReport::default();
+		for dead_letter in self.store.fetch(limit).await? {
+			let deserialized = self.routes.get(&dead_letter.topic).map(|deserialize| {
+				let payload = upcast_payload(&dead_letter.topic, dead_letter.version, &dead_letter.payload)?;
+				deserialize(&payload).map_err(|err| BaseError::DatabaseError(err.to_string()))
+			});
+			let event = match deserialized {
+				Some(Ok(event)) => event,
+				Some(Err(err)) => {
+					tracing::error!(id = dead_letter.id, topic = %dead_letter.topic, "Failed to deserialize dead letter! {:?}", err);
+					report.skipped.push(dead_letter.id);
+					continue;
+				}
+				None => {
+					report.skipped.push(dead_letter.id);
+					continue;
+				}
+			};
+			let context_manager = Arc::new(ContextManager::new(self.conn).with_actor(Actor::System("dead_letter_replay".into())));
+			let Some(index) = dead_letter.handler_index else {
+				report.skipped.push(dead_letter.id);
+				continue;
+			};
+			let res = match bus.event_handler().get(&dead_letter.topic) {
+				Some(EventHandlers::Sync(handlers) | EventHandlers::Async(handlers)) if index < handlers.len() => handlers[index](event, context_manager.clone()).await,
+				Some(EventHandlers::Batch { handlers, .. }) if index < handlers.len() => handlers[index](vec![event], context_manager.clone()).await,
+				_ => {
+					report.skipped.push(dead_letter.id);
+					continue;
+				}
+			};
+			match res {
+				Ok(()) => {
+					self.store.remove(dead_letter.id).await?;
+					report.replayed += 1;
+					handle_next_event(context_manager, bus.event_handler()).await.map_err(BaseError::from)?;
+				}
+				Err(err) => {
+					tracing::error!(id = dead_letter.id, topic = %dead_letter.topic, "Replayed dead letter failed again! {:?}", BaseError::from(err));
+					report.failed.push(dead_letter.id);
+				}
+			}
+		}
+		Ok(report)
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use super::*;
+	use crate::prelude::TEventHandler;
+	use std::sync::atomic::{AtomicBool, Ordering};
+
+	struct Connection;
+	impl TConnection for Connection {}
+
+	#[derive(serde::Serialize, serde::Deserialize)]
+	struct InvoiceRequested {
+		order_id: i64,
+	}
+	impl TEvent for InvoiceRequested {
+		fn internally_notifiable(&self) -> bool {
+			true
+		}
+		fn state(&self) -> String {
+			serde_json::to_string(self).unwrap()
+		}
+	}
+	impl TTopic for InvoiceRequested {
+		const TOPIC: &'static str = "InvoiceRequested";
+	}
+
+	static FIXED: AtomicBool = AtomicBool::new(false);
+	struct Bus;
+	impl TEventBus<BaseError> for Bus {
+		fn event_handler(&self) -> &'static TEventHandler<BaseError> {
+			static EVENT_HANDLER: std::sync::LazyLock<TEventHandler<BaseError>> = std::sync::LazyLock::new(|| {
+				let mut map = TEventHandler::default();
+				map.insert(
+					"InvoiceRequested".to_string(),
+					EventHandlers::Sync(vec![
+						Box::new(|_, _| Box::pin(async { Ok(()) })),
+						Box::new(|_, _| {
+							Box::pin(async {
+								match FIXED.load(Ordering::SeqCst) {
+									true => Ok(()),
+									false => Err(BaseError::ServiceError),
+								}
+							})
+						}),
+					]),
+				);
+				map
+			});
+			&EVENT_HANDLER
+		}
+	}
+
+	#[tokio::test]
+	async fn test_failed_handler_is_dead_lettered_and_replayed() {
+		let store = Arc::new(InMemoryDeadLetterStore::default());
+		Bus.handle_events(vec![Arc::new(InvoiceRequested { order_id: 1 })], ContextManager::new(&Connection).with_dead_letter_store(store.clone()))
+			.await
+			.unwrap();
+
+		let dead_letters = store.fetch(100).await.unwrap();
+		assert_eq!(dead_letters.len(), 1);
+		assert_eq!((dead_letters[0].topic.as_str(), dead_letters[0].handler_index), ("InvoiceRequested", Some(1)));
+		assert_eq!(dead_letters[0].payload, r#"{"order_id":1}"#);
+
+		let replay = DeadLetterReplay::new(&Connection, store.clone()).register::<InvoiceRequested>();
+		let report = replay.replay(&Bus, 100).await.unwrap();
+		assert_eq!(report.failed, vec![dead_letters[0].id]);
+
+		FIXED.store(true, Ordering::SeqCst);
+		let report = replay.replay(&Bus, 100).await.unwrap();
+		assert_eq!(report.replayed, 1);
+		assert!(store.fetch(100).await.unwrap().is_empty());
+	}
+}
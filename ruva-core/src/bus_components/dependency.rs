@@ -0,0 +1,95 @@
+//! ### Domain service dependencies
+//! Collaborators such as payment gateway are declared as trait with `declare_dependency!`,
+//! registered on boot and resolved from [ContextManager] in handlers. Test replaces it with fake or mock per dispatch.
+//!
+//! ```rust,ignore
+//! ruva::declare_dependency! {
+//!     #[async_trait]
+//!     pub trait TPaymentGateway {
+//!         async fn charge(&self, user_id: i64, amount: i64) -> Result<(), BaseError>;
+//!     }
+//! }
+//!
+//! // On boot
+//! <dyn TPaymentGateway>::register(StripeGateway::new(key));
+//!
+//! // In handler
+//! <dyn TPaymentGateway>::resolve(&ctx).charge(cmd.user_id, cmd.amount).await?;
+//!
+//! // In test, with `mock` feature and `mockall` in dev-dependencies
+//! let mut gateway = MockTPaymentGateway::new();
+//! gateway.expect_charge().returning(|_, _| Ok(()));
+//! let context_manager = ContextManager::new(conn).with_dependency::<dyn TPaymentGateway>(Arc::new(gateway));
+//! MessageBus.dispatch_with(MakeOrder { .. }, context_manager).await?;
+//! ```
+use std::any::{Any, TypeId};
+use std::sync::{Arc, LazyLock, RwLock};
+
+use super::contexts::ContextManager;
+
+pub(crate) type Dependencies = hashbrown::HashMap<TypeId, Box<dyn Any + Send + Sync>>;
+
+static DEPENDENCIES: LazyLock<RwLock<Dependencies>> = LazyLock::new(Default::default);
+
+/// Register process-wide implementation of `T`, usually `dyn Trait`. Registering again replaces the previous one.
+pub fn register_dependency<T: ?Sized + Send + Sync + 'static>(dependency: Arc<T>) {
+	DEPENDENCIES.write().unwrap().insert(TypeId::of::<T>(), Box::new(dependency));
+}
+
+pub fn resolve_dependency<T: ?Sized + Send + Sync + 'static>() -> Option<Arc<T>> {
+	DEPENDENCIES
+		.read()
+		.unwrap()
+		.get(&TypeId::of::<T>())
+		.map(|dependency| dependency.downcast_ref::<Arc<T>>().expect("Type Mismatch!").clone())
+}
+
+impl ContextManager {
+	/// Use `dependency` for `T` in this dispatch only, instead of the registered one.
+	pub fn with_dependency<T: ?Sized + Send + Sync + 'static>(mut self, dependency: Arc<T>) -> Self {
+		self.dependencies.insert(TypeId::of::<T>(), Box::new(dependency));
+		self
+	}
+
+	/// In record-only handler, the recorder of `T` is returned instead. See [handler_sandbox](super::sandbox::handler_sandbox).
+	/// ## Panics
+	/// If `T` is neither given to this context manager nor registered.
+	pub fn resolve<T: ?Sized + Send + Sync + 'static>(&self) -> Arc<T> {
+		if let Some(recorder) = super::sandbox::handler_sandbox().recorder::<T>() {
+			return recorder;
+		}
+		match self.dependencies.get(&TypeId::of::<T>()) {
+			Some(dependency) => dependency.downcast_ref::<Arc<T>>().expect("Type Mismatch!").clone(),
+			None => resolve_dependency::<T>().unwrap_or_else(|| panic!("Dependency {} Is Not Registered!", std::any::type_name::<T>())),
+		}
+	}
+}
+
+#[test]
+fn test_context_manager_dependency_overrides_registered_one() {
+	use super::executor::TConnection;
+
+	struct Connection;
+	impl TConnection for Connection {}
+	trait TGreeter: Send + Sync {
+		fn greet(&self) -> &'static str;
+	}
+	struct Real;
+	impl TGreeter for Real {
+		fn greet(&self) -> &'static str {
+			"real"
+		}
+	}
+	struct Fake;
+	impl TGreeter for Fake {
+		fn greet(&self) -> &'static str {
+			"fake"
+		}
+	}
+
+	register_dependency::<dyn TGreeter>(Arc::new(Real));
+	assert_eq!(ContextManager::new(&Connection).resolve::<dyn TGreeter>().greet(), "real");
+
+	let context_manager = ContextManager::new(&Connection).with_dependency::<dyn TGreeter>(Arc::new(Fake));
+	assert_eq!(context_manager.resolve::<dyn TGreeter>().greet(), "fake");
+}
@@ -0,0 +1,103 @@
+//! ### Type-erased dispatch
+//! `MessageBus` is bound to each command type at compile time, so the caller has to know which command it dispatches.
+//! [DynMessageBus] is for the layer that doesn't - generic API gateway, RPC endpoint, admin console - and gets
+//! the response serialized.
+//!
+//! ```rust,ignore
+//! // On boot
+//! let bus = DynMessageBus::new().register::<MakeOrder>().register::<CancelOrder>();
+//!
+//! // Somewhere command is decided at runtime
+//! let response: serde_json::Value = bus.dispatch(AnyCommand::new(cmd), conn).await?;
+//! ```
+//! Errors of the command are converted to [BaseError], as the error type is erased along with the command.
+use std::any::{Any, TypeId};
+use std::future::Future;
+use std::pin::Pin;
+
+use serde::Serialize;
+
+use super::contexts::ContextManager;
+use super::executor::TConnection;
+use super::messagebus::{MessageBus, TMessageBus};
+use crate::prelude::{BaseError, TCommand, TCommandSpec};
+
+type DynFuture = Pin<Box<dyn Future<Output = Result<serde_json::Value, BaseError>> + Send>>;
+type DynHandler = Box<dyn Fn(Box<dyn Any + Send>, ContextManager) -> DynFuture + Send + Sync>;
+
+/// Command whose type is erased. Dispatched by [DynMessageBus].
+pub struct AnyCommand {
+	type_id: TypeId,
+	name: &'static str,
+	command: Box<dyn Any + Send>,
+}
+
+impl AnyCommand {
+	pub fn new<C: TCommand>(command: C) -> Self {
+		Self {
+			type_id: TypeId::of::<C>(),
+			name: std::any::type_name::<C>(),
+			command: Box::new(command),
+		}
+	}
+
+	/// Type name of the command
+	pub fn name(&self) -> &'static str {
+		self.name
+	}
+}
+
+impl std::fmt::Debug for AnyCommand {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		f.debug_tuple("AnyCommand").field(&self.name).finish()
+	}
+}
+
+#[derive(Default)]
+pub struct DynMessageBus {
+	handlers: hashbrown::HashMap<TypeId, DynHandler>,
+}
+
+impl DynMessageBus {
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	/// Route `C` to `MessageBus`. Registering the same command again replaces the route.
+	pub fn register<C>(mut self) -> Self
+	where
+		C: TCommandSpec,
+		C::Response: Serialize,
+		C::Error: std::convert::From<BaseError>,
+		BaseError: std::convert::From<C::Error>,
+		MessageBus: TMessageBus<C::Response, C::Error, C>,
+	{
+		self.handlers.insert(
+			TypeId::of::<C>(),
+			Box::new(|command, context_manager| {
+				Box::pin(async move {
+					let command = *command.downcast::<C>().expect("Not Convertible!");
+					let res = MessageBus.dispatch_with(command, context_manager).await.map_err(BaseError::from)?;
+					serde_json::to_value(res).map_err(|err| BaseError::DatabaseError(err.to_string()))
+				})
+			}),
+		);
+		self
+	}
+
+	pub fn is_registered<C: TCommand>(&self) -> bool {
+		self.handlers.contains_key(&TypeId::of::<C>())
+	}
+
+	pub async fn dispatch(&self, command: AnyCommand, conn: &'static dyn TConnection) -> Result<serde_json::Value, BaseError> {
+		self.dispatch_with(command, ContextManager::new(conn)).await
+	}
+
+	/// Same as `dispatch` but with context manager prepared by caller.
+	/// ## Errors
+	/// [BaseError::HandlerNotFound] with the type name of the command if it is not registered.
+	pub async fn dispatch_with(&self, command: AnyCommand, context_manager: ContextManager) -> Result<serde_json::Value, BaseError> {
+		let handler = self.handlers.get(&command.type_id).ok_or_else(|| BaseError::HandlerNotFound(command.name.to_string()))?;
+		handler(command.command, context_manager).await
+	}
+}
@@ -0,0 +1,150 @@
+//! ### Command enrichment
+//! Filling defaults, resolving external references or normalizing strings of a command tends to be duplicated in every web layer
+//! and test that builds it. Register [TCommandEnricher] for the command type instead, and the bus runs it before the handler.
+//!
+//! ```rust
+//! # use ruva_core::prelude::*;
+//! # #[derive(Debug)]
+//! # struct RegisterUser { email: String }
+//! # impl TCommand for RegisterUser {}
+//! # struct ResolveReferrer;
+//! # impl ResolveReferrer { fn new(_: ()) -> Self { Self } }
+//! # #[async_trait]
+//! # impl TCommandEnricher<RegisterUser> for ResolveReferrer {
+//! #     async fn enrich(&self, command: RegisterUser, _: &ContextManager) -> Result<RegisterUser, BaseError> { Ok(command) }
+//! # }
+//! # let client = ();
+//! struct NormalizeEmail;
+//! #[async_trait]
+//! impl TCommandEnricher<RegisterUser> for NormalizeEmail {
+//!     async fn enrich(&self, mut command: RegisterUser, _: &ContextManager) -> Result<RegisterUser, BaseError> {
+//!         command.email = command.email.trim().to_lowercase();
+//!         Ok(command)
+//!     }
+//! }
+//!
+//! // On boot
+//! register_command_enricher::<RegisterUser>(NormalizeEmail);
+//! register_command_enricher::<RegisterUser>(ResolveReferrer::new(client));
+//! ```
+//! Enrichers of a command run in the order they are registered, each given the output of the previous one.
+//! Error of an enricher fails the command before its handler is resolved, counted as failure of the command.
+use std::any::{Any, TypeId};
+use std::sync::{Arc, RwLock};
+
+use async_trait::async_trait;
+
+use super::contexts::ContextManager;
+use crate::prelude::{BaseError, TCommand};
+
+#[async_trait]
+pub trait TCommandEnricher<C: TCommand>: Send + Sync {
+	/// `context_manager` has actor, tenant and current user of the dispatch
+	async fn enrich(&self, command: C, context_manager: &ContextManager) -> Result<C, BaseError>;
+}
+
+/// `Arc<dyn TCommandEnricher<C>>` of each command type
+type Enrichers = hashbrown::HashMap<TypeId, Vec<Arc<dyn Any + Send + Sync>>>;
+
+static COMMAND_ENRICHERS: RwLock<Option<Enrichers>> = RwLock::new(None);
+
+/// Add `enricher` at the end of the chain of `C`
+pub fn register_command_enricher<C: TCommand>(enricher: impl TCommandEnricher<C> + 'static) {
+	let enricher: Arc<dyn TCommandEnricher<C>> = Arc::new(enricher);
+	COMMAND_ENRICHERS
+		.write()
+		.unwrap()
+		.get_or_insert_with(Default::default)
+		.entry(TypeId::of::<C>())
+		.or_default()
+		.push(Arc::new(enricher));
+}
+
+/// Run the chain of `C` on `command`. Returned as is if nothing is registered.
+pub(crate) async fn enrich_command<C: TCommand>(command: C, context_manager: &ContextManager) -> Result<C, BaseError> {
+	let enrichers = match COMMAND_ENRICHERS.read().unwrap().as_ref().and_then(|enrichers| enrichers.get(&TypeId::of::<C>())) {
+		Some(enrichers) => enrichers
+			.iter()
+			.filter_map(|enricher| enricher.downcast_ref::<Arc<dyn TCommandEnricher<C>>>().cloned())
+			.collect::<Vec<_>>(),
+		None => return Ok(command),
+	};
+	let mut command = command;
+	for enricher in enrichers {
+		command = enricher.enrich(command, context_manager).await?;
+	}
+	Ok(command)
+}
+
+#[tokio::test]
+async fn test_command_enrichers_run_in_order() {
+	use crate::prelude::TConnection;
+
+	struct Connection;
+	impl TConnection for Connection {}
+	#[derive(Debug)]
+	struct RegisterUser {
+		email: String,
+		locale: Option<String>,
+	}
+	impl TCommand for RegisterUser {}
+
+	struct NormalizeEmail;
+	#[async_trait]
+	impl TCommandEnricher<RegisterUser> for NormalizeEmail {
+		async fn enrich(&self, command: RegisterUser, _: &ContextManager) -> Result<RegisterUser, BaseError> {
+			Ok(RegisterUser {
+				email: command.email.trim().to_lowercase(),
+				..command
+			})
+		}
+	}
+	struct DefaultLocale;
+	#[async_trait]
+	impl TCommandEnricher<RegisterUser> for DefaultLocale {
+		async fn enrich(&self, command: RegisterUser, _: &ContextManager) -> Result<RegisterUser, BaseError> {
+			if command.email.ends_with(".kr") {
+				return Ok(RegisterUser {
+					locale: command.locale.or(Some("ko".into())),
+					..command
+				});
+			}
+			Ok(command)
+		}
+	}
+	struct RejectBlocked;
+	#[async_trait]
+	impl TCommandEnricher<RegisterUser> for RejectBlocked {
+		async fn enrich(&self, command: RegisterUser, _: &ContextManager) -> Result<RegisterUser, BaseError> {
+			match command.email.starts_with("blocked@") {
+				true => Err(BaseError::Rejected(command.email)),
+				false => Ok(command),
+			}
+		}
+	}
+	register_command_enricher::<RegisterUser>(NormalizeEmail);
+	register_command_enricher::<RegisterUser>(DefaultLocale);
+	register_command_enricher::<RegisterUser>(RejectBlocked);
+
+	let context_manager = ContextManager::new(&Connection);
+	let enriched = enrich_command(
+		RegisterUser {
+			email: " Kim@Example.KR ".into(),
+			locale: None,
+		},
+		&context_manager,
+	)
+	.await
+	.unwrap();
+	assert_eq!((enriched.email.as_str(), enriched.locale.as_deref()), ("kim@example.kr", Some("ko")));
+
+	let rejected = enrich_command(
+		RegisterUser {
+			email: "BLOCKED@example.com".into(),
+			locale: None,
+		},
+		&context_manager,
+	)
+	.await;
+	assert!(matches!(rejected, Err(BaseError::Rejected(email)) if email == "blocked@example.com"));
+}
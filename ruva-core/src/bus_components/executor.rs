@@ -17,6 +17,16 @@ impl TConnection for &'static mut sqlx::PgConnection {}
 #[cfg(feature = "sqlx-postgres")]
 impl TConnection for Box<&'static mut sqlx::PgConnection> {}
 
+#[cfg(feature = "sqlx-sqlite")]
+impl TConnection for &'static sqlx::sqlite::SqlitePool {}
+#[cfg(feature = "sqlx-sqlite")]
+impl TConnection for sqlx::sqlite::SqlitePool {}
+
+#[cfg(feature = "mongodb")]
+impl TConnection for &'static mongodb::Database {}
+#[cfg(feature = "mongodb")]
+impl TConnection for mongodb::Database {}
+
 // Design TConnection so each different connection can be implemented and return itself
 
 impl_downcast!(TConnection);
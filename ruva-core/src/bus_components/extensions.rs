@@ -0,0 +1,126 @@
+//! ### Request extensions
+//! Request metadata that only some handlers need - locale, request id, client ip and so on - is stashed in [Extensions]
+//! of [ContextManager] by middleware, so that it reaches command and event handlers without being threaded through every signature.
+//!
+//! ```rust
+//! # use ruva_core::prelude::*;
+//! # async fn example<C>(cmd: C, conn: &'static dyn TConnection) -> Result<(), C::Error>
+//! # where C: TCommandRoute, C::Error: From<BaseError>, BaseError: From<C::Error>, MessageBus: TEventBus<C::Error> {
+//! # #[derive(Clone)]
+//! # pub struct RequestId(pub String);
+//! # let (accept_language, id) = ("ko".to_string(), "9f2c1d".to_string());
+//! #[derive(Clone)]
+//! pub struct Locale(pub String);
+//!
+//! // In middleware
+//! let context_manager = ContextManager::new(conn).with_extension(Locale(accept_language)).with_extension(RequestId(id));
+//! MessageBus.dispatch_with(cmd, context_manager).await?;
+//!
+//! // In command handler
+//! # let context_manager = std::sync::Arc::new(ContextManager::new(conn));
+//! # let ctx = Context::new(context_manager.clone());
+//! let locale = ctx.extension::<Locale>().map_or("en", |locale| locale.0.as_str());
+//!
+//! // In event handler
+//! let request_id = context_manager.extension::<RequestId>().cloned();
+//! # Ok(())
+//! # }
+//! ```
+//! Extensions are keyed by type, so wrap values in a newtype. Actor, tenant and current user have their own fields,
+//! which unlike extensions are kept in [ContextSnapshot](super::snapshot::ContextSnapshot).
+use std::any::{Any, TypeId};
+
+use super::contexts::{Context, ContextManager, ReadContext};
+
+/// Values keyed by their type, at most one per type
+#[derive(Default)]
+pub struct Extensions(hashbrown::HashMap<TypeId, Box<dyn Any + Send + Sync>>);
+
+impl Extensions {
+	/// Returns the value of the same type given before, if any.
+	pub fn insert<T: Send + Sync + 'static>(&mut self, value: T) -> Option<T> {
+		self.0
+			.insert(TypeId::of::<T>(), Box::new(value))
+			.and_then(|previous| previous.downcast().ok().map(|previous| *previous))
+	}
+
+	pub fn get<T: Send + Sync + 'static>(&self) -> Option<&T> {
+		self.0.get(&TypeId::of::<T>()).and_then(|value| value.downcast_ref())
+	}
+
+	pub fn get_mut<T: Send + Sync + 'static>(&mut self) -> Option<&mut T> {
+		self.0.get_mut(&TypeId::of::<T>()).and_then(|value| value.downcast_mut())
+	}
+
+	pub fn remove<T: Send + Sync + 'static>(&mut self) -> Option<T> {
+		self.0.remove(&TypeId::of::<T>()).and_then(|value| value.downcast().ok().map(|value| *value))
+	}
+
+	pub fn contains<T: Send + Sync + 'static>(&self) -> bool {
+		self.0.contains_key(&TypeId::of::<T>())
+	}
+
+	pub fn len(&self) -> usize {
+		self.0.len()
+	}
+
+	pub fn is_empty(&self) -> bool {
+		self.0.is_empty()
+	}
+}
+
+impl std::fmt::Debug for Extensions {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		f.debug_struct("Extensions").field("len", &self.0.len()).finish()
+	}
+}
+
+impl ContextManager {
+	/// Replaces the value of the same type given before.
+	pub fn with_extension<T: Send + Sync + 'static>(mut self, value: T) -> Self {
+		self.extensions.insert(value);
+		self
+	}
+
+	pub fn extension<T: Send + Sync + 'static>(&self) -> Option<&T> {
+		self.extensions.get()
+	}
+}
+
+impl Context {
+	pub fn extension<T: Send + Sync + 'static>(&self) -> Option<&T> {
+		self.super_ctx.extension()
+	}
+}
+
+impl ReadContext {
+	pub fn extension<T: Send + Sync + 'static>(&self) -> Option<&T> {
+		self.super_ctx.extension()
+	}
+}
+
+#[test]
+fn test_extensions() {
+	use super::executor::TConnection;
+	use std::sync::Arc;
+
+	struct Connection;
+	impl TConnection for Connection {}
+	#[derive(Debug, PartialEq)]
+	struct Locale(&'static str);
+	#[derive(Debug, PartialEq)]
+	struct RequestId(u64);
+
+	let mut extensions = Extensions::default();
+	assert_eq!(extensions.insert(Locale("ko")), None);
+	assert_eq!(extensions.insert(Locale("en")), Some(Locale("ko")));
+	extensions.get_mut::<Locale>().unwrap().0 = "ja";
+	assert_eq!(extensions.remove::<Locale>(), Some(Locale("ja")));
+	assert!(extensions.is_empty());
+
+	let context_manager = Arc::new(ContextManager::new(&Connection).with_extension(Locale("ko")).with_extension(RequestId(7)));
+	let ctx = Context::new(context_manager.clone());
+	assert_eq!(ctx.extension::<Locale>(), Some(&Locale("ko")));
+	assert_eq!(ReadContext::new(context_manager.clone()).extension::<RequestId>(), Some(&RequestId(7)));
+	assert_eq!(context_manager.extension::<String>(), None);
+}
@@ -1,5 +1,5 @@
 //! ### Example - simple command handler
-//! ```rust,no_run
+//! ```rust,ignore
 //! impl<C,R> TCommandService<(), ()> for CommandHandler<(C, R)>
 //! where
 //!     C: crate::prelude::TCommand + for<'a> TGetHandler<&'a mut R, Result<(), ()>>,
@@ -35,6 +35,7 @@ where
 			Ok(val) => {
 				dep.commit().await?;
 				dep.close().await;
+				crate::bus_components::observer::notify(|o| o.commit());
 
 				Ok(val)
 			}
@@ -42,6 +43,7 @@ where
 			Err(err) => {
 				dep.rollback().await?;
 				dep.close().await;
+				crate::bus_components::observer::notify(|o| o.rollback());
 
 				if let BaseError::StopSentinelWithEvent(event) = err.clone().into() {
 					dep.set_current_events(vec![event.clone()].into());
@@ -68,6 +70,9 @@ macro_rules! __register_uow_services_internal {
             $command:ty => $handler:expr
         ),*
     ) => {
+        // * Without this, duplicate only shows up as conflicting implementations of the traits below.
+        ruva::__assert_unique_registrations!([$($command),*] "is registered more than once in `register_uow_services!`");
+
         use ruva::TUnitOfWorkCommandHandler;
         type ApplicationResult = std::result::Result<$response,$error>;
 
@@ -78,9 +83,13 @@ macro_rules! __register_uow_services_internal {
                 }
             }
 
-            impl ::ruva::TMessageBus<$response,$error,$command> for ::ruva::MessageBus{
+            impl ::ruva::TCommandSpec for $command {
+                type Response = $response;
+                type Error = $error;
+            }
+
+            impl ::ruva::TCommandRoute for $command {
                 fn command_handler(
-                    &self,
                     context_manager: ruva::AtomicContextManager,
                     cmd: $command,
                 ) -> impl ::ruva::TCommandService<$response, $error> {
@@ -1,4 +1,7 @@
-use crate::{bus_components::contexts::AtomicContextManager, prelude::TEvent};
+use crate::{
+	bus_components::{contexts::AtomicContextManager, shutdown::ShutdownToken},
+	prelude::TEvent,
+};
 
 use std::pin::Pin;
 
@@ -7,15 +10,138 @@ pub type FutureResult<E> = Result<Future<E>, E>;
 
 pub type Handlers<E> = Vec<Box<dyn Fn(std::sync::Arc<dyn TEvent>, AtomicContextManager) -> Future<E> + Send + Sync>>;
 
+/// Handlers that take consecutive events of the same topic at once
+pub type BatchHandlers<E> = Vec<Box<dyn Fn(Vec<std::sync::Arc<dyn TEvent>>, AtomicContextManager) -> Future<E> + Send + Sync>>;
+
 pub enum EventHandlers<E> {
 	Sync(Handlers<E>),
 	Async(Handlers<E>),
+	Batch { handlers: BatchHandlers<E>, max_batch_size: usize },
 }
 impl<E> EventHandlers<E> {
 	pub fn extend(&mut self, handlers: Handlers<E>) {
 		match self {
 			Self::Sync(h) => h.extend(handlers),
 			Self::Async(h) => h.extend(handlers),
+			Self::Batch { .. } => panic!("Single event handler can't be added to batch handlers!"),
+		}
+	}
+}
+
+/// What to do with the other handlers of async handler group when one of them fails.
+/// See [with_async_failure_policy](crate::prelude::ContextManager::with_async_failure_policy).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum AsyncFailurePolicy {
+	/// Cancel the siblings still running
+	#[default]
+	CancelSiblings,
+	/// Let the siblings run to completion
+	RunAll,
+}
+
+/// Run async handler group concurrently on the dispatching task, so that handlers never touch the event queue in parallel.
+/// Result of each handler is returned at its index - `None` if it was cancelled or panicked.
+pub(crate) async fn run_handler_group<E>(futures: Vec<Future<E>>, policy: AsyncFailurePolicy, shutdown: &ShutdownToken) -> Vec<Option<Result<(), E>>> {
+	use futures::{stream::FuturesUnordered, FutureExt, StreamExt};
+
+	let mut results = futures.iter().map(|_| None).collect::<Vec<_>>();
+	let mut group = futures
+		.into_iter()
+		.enumerate()
+		.map(|(i, future)| std::panic::AssertUnwindSafe(future).catch_unwind().map(move |res| (i, res)))
+		.collect::<FuturesUnordered<_>>();
+
+	// * Siblings still running are cancelled when the group is dropped
+	loop {
+		tokio::select! {
+			next = group.next() => match next {
+				None => break,
+				Some((i, Ok(res))) => {
+					let failed = res.is_err();
+					results[i] = Some(res);
+					if failed && policy == AsyncFailurePolicy::CancelSiblings {
+						break;
+					}
+				}
+				Some((i, Err(_panic))) => {
+					tracing::error!("{i}th Async Event Handler Panicked!");
+					if policy == AsyncFailurePolicy::CancelSiblings {
+						break;
+					}
+				}
+			},
+			_ = shutdown.cancelled() => {
+				tracing::warn!("Shutdown requested. Cancelling async event handlers.");
+				break;
+			}
+		}
+	}
+	results
+}
+
+/// Sort handlers by explicit `#[order = n]` given in `init_event_handler!`.
+/// Handlers without order come after the ordered ones, keeping declaration order.
+/// ## Panics
+/// If the same order is given more than once, which `init_event_handler!` already rejects at compile time.
+pub fn sort_by_order<H>(topic: &str, handlers: Vec<(Option<usize>, H)>) -> Vec<H> {
+	let mut orders = std::collections::HashSet::new();
+	for order in handlers.iter().filter_map(|(order, _)| *order) {
+		if !orders.insert(order) {
+			panic!("Handler order {} is given more than once for {}!", order, topic);
 		}
 	}
+	let mut handlers = handlers;
+	handlers.sort_by_key(|(order, _)| order.unwrap_or(usize::MAX));
+	handlers.into_iter().map(|(_, handler)| handler).collect()
+}
+
+#[test]
+fn test_sort_by_order() {
+	let sorted = sort_by_order("OrderSucceeded", vec![(None, "a"), (Some(2), "b"), (None, "c"), (Some(1), "d")]);
+	assert_eq!(sorted, vec!["d", "b", "a", "c"]);
+}
+
+#[test]
+#[should_panic(expected = "Handler order 1 is given more than once for OrderSucceeded!")]
+fn test_sort_by_order_rejects_duplicate() {
+	sort_by_order("OrderSucceeded", vec![(Some(1), "a"), (Some(1), "b")]);
+}
+
+#[tokio::test]
+async fn test_handler_group_cancels_siblings_on_failure() {
+	let slow = || -> Future<()> {
+		Box::pin(async {
+			tokio::time::sleep(std::time::Duration::from_millis(200)).await;
+			Ok(())
+		})
+	};
+	let failing = || -> Future<()> { Box::pin(async { Err(()) }) };
+
+	let results = run_handler_group(vec![slow(), failing()], AsyncFailurePolicy::CancelSiblings, &ShutdownToken::new()).await;
+	assert_eq!(results, vec![None, Some(Err(()))]);
+
+	let results = run_handler_group(vec![slow(), failing()], AsyncFailurePolicy::RunAll, &ShutdownToken::new()).await;
+	assert_eq!(results, vec![Some(Ok(())), Some(Err(()))]);
+
+	let shutdown = ShutdownToken::new();
+	shutdown.shutdown();
+	let results = run_handler_group(vec![slow()], AsyncFailurePolicy::RunAll, &shutdown).await;
+	assert_eq!(results, vec![None]);
+}
+
+#[tokio::test]
+async fn test_handler_group_runs_on_dispatching_task() {
+	let task = || -> Future<()> {
+		let dispatching = tokio::task::try_id();
+		Box::pin(async move {
+			tokio::task::yield_now().await;
+			assert_eq!(tokio::task::try_id(), dispatching);
+			Ok(())
+		})
+	};
+
+	let results = tokio::spawn(async move { run_handler_group(vec![task(), task(), task()], AsyncFailurePolicy::RunAll, &ShutdownToken::new()).await })
+		.await
+		.unwrap();
+	assert_eq!(results, vec![Some(Ok(())); 3]);
 }
@@ -1,7 +1,7 @@
 //!
 //!
 //! ### example - transaction unit of work
-//! ```rust,no_run
+//! ```rust,ignore
 //! impl<C, R> TCommandService<ServiceResponse, ServiceError> for CommandHandler<(C, R)>
 //! where
 //!     C: TCommand + for<'a> TGetHandler<&'a mut R, Result<ServiceResponse>>,
@@ -39,5 +39,7 @@
 pub mod command;
 
 pub mod event;
+pub mod registry;
 pub use command::*;
 pub use event::*;
+pub use registry::*;
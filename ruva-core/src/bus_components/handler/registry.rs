@@ -0,0 +1,138 @@
+//! ### Typed registration
+//! [EventHandlerRegistry] builds [TEventHandler] keyed by `TTopic::TOPIC` of the event type, so renamed or mistyped event
+//! is caught at compile time rather than at runtime as missing handler.
+//!
+//! ```rust,ignore
+//! impl TEventBus<ServiceError> for MessageBus {
+//!     fn event_handler(&self) -> &'static TEventHandler<ServiceError> {
+//!         static EVENT_HANDLERS: LazyLock<TEventHandler<ServiceError>> = LazyLock::new(|| {
+//!             EventHandlerRegistry::new()
+//!                 .on(|event: OrderSucceeded, ctx| NotificationHandler::new(ctx).send_mail(event))
+//!                 .on_async(|event: OrderSucceeded, ctx| AnalyticsHandler::new(ctx).track(event))
+//!                 .on_batch(100, |events: Vec<ItemImported>, ctx| ItemHandler::new(ctx).upsert(events))
+//!                 .build()
+//!         });
+//!         &EVENT_HANDLERS
+//!     }
+//! }
+//! ```
+use std::future::Future as StdFuture;
+use std::sync::Arc;
+
+use super::event::{EventHandlers, Future};
+use crate::bus_components::contexts::AtomicContextManager;
+use crate::bus_components::messagebus::TEventHandler;
+use crate::prelude::{TEvent, TTopic};
+
+pub struct EventHandlerRegistry<E> {
+	handlers: TEventHandler<E>,
+}
+
+impl<E> Default for EventHandlerRegistry<E> {
+	fn default() -> Self {
+		Self { handlers: Default::default() }
+	}
+}
+
+impl<E: 'static> EventHandlerRegistry<E> {
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	/// Add handler of `T`, run one after another with the other handlers of `T`.
+	/// ## Panics
+	/// If `T` has async or batch handlers.
+	pub fn on<T, F, Fut>(self, handler: F) -> Self
+	where
+		T: TEvent + TTopic + Clone + 'static,
+		F: Fn(T, AtomicContextManager) -> Fut + Send + Sync + 'static,
+		Fut: StdFuture<Output = Result<(), E>> + Send + 'static,
+	{
+		self.add::<T, _, _>(|| EventHandlers::Sync(vec![]), handler)
+	}
+
+	/// Add handler of `T`, run concurrently with the other handlers of `T`.
+	/// ## Panics
+	/// If `T` has sync or batch handlers.
+	pub fn on_async<T, F, Fut>(self, handler: F) -> Self
+	where
+		T: TEvent + TTopic + Clone + 'static,
+		F: Fn(T, AtomicContextManager) -> Fut + Send + Sync + 'static,
+		Fut: StdFuture<Output = Result<(), E>> + Send + 'static,
+	{
+		self.add::<T, _, _>(|| EventHandlers::Async(vec![]), handler)
+	}
+
+	/// Add handler that takes consecutive events of `T` at once, at most `max_batch_size`.
+	/// ## Panics
+	/// If `T` has sync or async handlers, or batch handlers with different `max_batch_size`.
+	pub fn on_batch<T, F, Fut>(mut self, max_batch_size: usize, handler: F) -> Self
+	where
+		T: TEvent + TTopic + Clone + 'static,
+		F: Fn(Vec<T>, AtomicContextManager) -> Fut + Send + Sync + 'static,
+		Fut: StdFuture<Output = Result<(), E>> + Send + 'static,
+	{
+		let handlers = self.handlers.entry(T::TOPIC.to_string()).or_insert_with(|| EventHandlers::Batch { handlers: vec![], max_batch_size });
+		match handlers {
+			EventHandlers::Batch { handlers, max_batch_size: size } if *size == max_batch_size => handlers.push(Box::new(move |events: Vec<Arc<dyn TEvent>>, context_manager| {
+				let events = events.iter().map(|e| e.downcast_ref::<T>().expect("Not Convertible!").clone()).collect();
+				Box::pin(handler(events, context_manager)) as Future<E>
+			})),
+			_ => panic!("Handlers of {} can't be mixed with batch handlers of size {}!", T::TOPIC, max_batch_size),
+		}
+		self
+	}
+
+	fn add<T, F, Fut>(mut self, empty: impl FnOnce() -> EventHandlers<E>, handler: F) -> Self
+	where
+		T: TEvent + TTopic + Clone + 'static,
+		F: Fn(T, AtomicContextManager) -> Fut + Send + Sync + 'static,
+		Fut: StdFuture<Output = Result<(), E>> + Send + 'static,
+	{
+		let empty = empty();
+		let handlers = self.handlers.entry(T::TOPIC.to_string()).or_insert_with(|| match &empty {
+			EventHandlers::Async(_) => EventHandlers::Async(vec![]),
+			_ => EventHandlers::Sync(vec![]),
+		});
+		if std::mem::discriminant(handlers) != std::mem::discriminant(&empty) {
+			panic!("Sync, async and batch handlers of {} can't be mixed!", T::TOPIC);
+		}
+		handlers.extend(vec![Box::new(move |event: Arc<dyn TEvent>, context_manager| {
+			Box::pin(handler(event.downcast_ref::<T>().expect("Not Convertible!").clone(), context_manager)) as Future<E>
+		})]);
+		self
+	}
+
+	pub fn build(self) -> TEventHandler<E> {
+		self.handlers
+	}
+}
+
+#[test]
+fn test_registry_keys_handlers_by_topic() {
+	use crate::prelude::BaseError;
+
+	#[derive(Clone)]
+	struct OrderSucceeded;
+	impl TEvent for OrderSucceeded {
+		fn state(&self) -> String {
+			"{}".into()
+		}
+	}
+	impl TTopic for OrderSucceeded {
+		const TOPIC: &'static str = "OrderSucceeded";
+	}
+
+	let handlers = EventHandlerRegistry::<BaseError>::new()
+		.on(|_: OrderSucceeded, _| async { Ok(()) })
+		.on(|_: OrderSucceeded, _| async { Ok(()) })
+		.build();
+	assert!(matches!(handlers.get("OrderSucceeded"), Some(EventHandlers::Sync(h)) if h.len() == 2));
+
+	let mixed = std::panic::catch_unwind(|| {
+		EventHandlerRegistry::<BaseError>::new()
+			.on(|_: OrderSucceeded, _| async { Ok(()) })
+			.on_async(|_: OrderSucceeded, _| async { Ok(()) })
+	});
+	assert!(mixed.is_err());
+}
@@ -0,0 +1,339 @@
+//! ### Idempotency
+//! Client retrying a request it got no answer for - HTTP retry, at-least-once delivery of a queue and so on - shouldn't run the command twice.
+//! [IdempotencyAspect] looks up the key of [TIdempotentCommand] in [TIdempotencyStore] of the context manager, the one set by [set_idempotency_store] by default,
+//! and answers the duplicate with the response recorded for the key, instead of running the command again.
+//!
+//! ```rust,ignore
+//! // On boot. `PgPool` implements `TIdempotencyStore` with `sqlx-postgres` feature.
+//! set_idempotency_store(pool.clone());
+//!
+//! impl TIdempotentCommand for PlaceOrder {
+//!     fn idempotency_key(&self) -> Option<&str> {
+//!         self.request_id.as_deref()
+//!     }
+//! }
+//!
+//! impl TCommandRoute for PlaceOrder {
+//!     fn command_handler(context_manager: AtomicContextManager, cmd: Self) -> impl TCommandService<Self::Response, Self::Error> {
+//!         IdempotencyAspect::new(&context_manager, &cmd, CommandHandler((cmd, Context::new(context_manager.clone()))))
+//!     }
+//! }
+//! ```
+//! Key is claimed before the command runs, so that concurrent duplicate is rejected with `BaseError::ConcurrencyConflict` instead of running along.
+//! Keys are scoped by the tenant and the effective user of the context, so that key reused by another user is not answered with response of someone else.
+//!
+//! The unit of work of ruva marks the claim committed in the transaction of the command, and the response is recorded right after the command.
+//! Claim is released when the command fails, unless it is committed. Claim left without response - as the process crashed
+//! or failed to record it after commit - is never released, and its duplicate keeps failing with `BaseError::ConcurrencyConflict`,
+//! as running it again is exactly what idempotency is to prevent. Such claim is to be looked into and removed by hand.
+use std::sync::{Arc, Mutex, RwLock};
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use serde::{de::DeserializeOwned, Serialize};
+
+use super::contexts::{AtomicContextManager, ContextManager};
+use super::messagebus::TCommandService;
+use crate::prelude::{ApplicationError, ApplicationResponse, BaseError, TCommand};
+
+pub trait TIdempotentCommand: TCommand {
+	/// Key given by the client, such as `Idempotency-Key` header. Command without key is run every time.
+	fn idempotency_key(&self) -> Option<&str>;
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IdempotencyRecord {
+	pub key: String,
+	/// Type name of the command
+	pub command: String,
+	/// Serialized response. `None` until the command is done.
+	pub response: Option<String>,
+	/// Changes of the command are committed along with the claim
+	pub committed: bool,
+	pub recorded_at: DateTime<Utc>,
+}
+
+#[async_trait]
+pub trait TIdempotencyStore: Send + Sync {
+	async fn get(&self, key: &str) -> Result<Option<IdempotencyRecord>, BaseError>;
+	/// Claim `key` for `command` before it runs. Returns `false` if it is claimed already.
+	async fn claim(&self, key: &str, command: &str) -> Result<bool, BaseError>;
+	/// Record the response of the command that claimed `key`
+	async fn complete(&self, key: &str, response: &str) -> Result<(), BaseError>;
+	/// Remove the claim of `key` that is neither committed nor completed, so that the command that failed can be retried.
+	/// Returns `false` if there is no such claim.
+	async fn release(&self, key: &str) -> Result<bool, BaseError>;
+}
+
+#[async_trait]
+impl<T: TIdempotencyStore + ?Sized> TIdempotencyStore for Arc<T> {
+	async fn get(&self, key: &str) -> Result<Option<IdempotencyRecord>, BaseError> {
+		self.as_ref().get(key).await
+	}
+	async fn claim(&self, key: &str, command: &str) -> Result<bool, BaseError> {
+		self.as_ref().claim(key, command).await
+	}
+	async fn complete(&self, key: &str, response: &str) -> Result<(), BaseError> {
+		self.as_ref().complete(key, response).await
+	}
+	async fn release(&self, key: &str) -> Result<bool, BaseError> {
+		self.as_ref().release(key).await
+	}
+}
+
+/// For tests and single instance deployments. Records are lost on restart.
+#[derive(Default)]
+pub struct InMemoryIdempotencyStore(Mutex<hashbrown::HashMap<String, IdempotencyRecord>>);
+
+#[async_trait]
+impl TIdempotencyStore for InMemoryIdempotencyStore {
+	async fn get(&self, key: &str) -> Result<Option<IdempotencyRecord>, BaseError> {
+		Ok(self.0.lock().unwrap().get(key).cloned())
+	}
+	async fn claim(&self, key: &str, command: &str) -> Result<bool, BaseError> {
+		let mut records = self.0.lock().unwrap();
+		if records.contains_key(key) {
+			return Ok(false);
+		}
+		let record = IdempotencyRecord {
+			key: key.to_string(),
+			command: command.to_string(),
+			response: None,
+			committed: false,
+			recorded_at: crate::prelude::clock().now(),
+		};
+		records.insert(key.to_string(), record);
+		Ok(true)
+	}
+	async fn complete(&self, key: &str, response: &str) -> Result<(), BaseError> {
+		match self.0.lock().unwrap().get_mut(key) {
+			Some(record) => record.response = Some(response.to_string()),
+			None => return Err(BaseError::NotFound),
+		}
+		Ok(())
+	}
+	async fn release(&self, key: &str) -> Result<bool, BaseError> {
+		let mut records = self.0.lock().unwrap();
+		match records.get(key) {
+			Some(record) if record.response.is_none() && !record.committed => {
+				records.remove(key);
+				Ok(true)
+			}
+			_ => Ok(false),
+		}
+	}
+}
+
+static IDEMPOTENCY_STORE: RwLock<Option<Arc<dyn TIdempotencyStore>>> = RwLock::new(None);
+
+/// Store of dispatches created from now on. Dispatch can be given its own with [ContextManager::with_idempotency_store].
+pub fn set_idempotency_store(store: impl TIdempotencyStore + 'static) {
+	*IDEMPOTENCY_STORE.write().unwrap() = Some(Arc::new(store));
+}
+
+pub fn idempotency_store() -> Option<Arc<dyn TIdempotencyStore>> {
+	IDEMPOTENCY_STORE.read().unwrap().clone()
+}
+
+/// Key claimed by the command. Taken by the unit of work to mark it committed in its transaction.
+#[derive(Debug, Clone)]
+pub(crate) struct IdempotencyClaim {
+	pub(crate) key: String,
+	pub(crate) command: &'static str,
+}
+
+impl ContextManager {
+	pub(crate) fn take_idempotency_claim(&self) -> Option<IdempotencyClaim> {
+		self.idempotency_claim.lock().unwrap().take()
+	}
+}
+
+/// Answer duplicate of the command with the recorded response, or run `inner` and record its response.
+pub struct IdempotencyAspect<S> {
+	context_manager: AtomicContextManager,
+	command: &'static str,
+	key: Option<Result<String, BaseError>>,
+	inner: S,
+}
+
+impl<S> IdempotencyAspect<S> {
+	pub fn new<C: TIdempotentCommand>(context_manager: &AtomicContextManager, command: &C, inner: S) -> Self {
+		let tenant = context_manager.tenant.as_deref().unwrap_or_default();
+		let user = context_manager
+			.current_user
+			.as_ref()
+			.map(|user| user.id.as_str())
+			.or(context_manager.actor.effective_user())
+			.unwrap_or_default();
+		Self {
+			context_manager: context_manager.clone(),
+			command: std::any::type_name::<C>(),
+			// Serialized so that `:` in tenant or user id can't make keys of different contexts collide
+			key: command
+				.idempotency_key()
+				.map(|key| serde_json::to_string(&(tenant, user, key)).map_err(|err| BaseError::DatabaseError(err.to_string()))),
+			inner,
+		}
+	}
+}
+
+impl<R, E, S> TCommandService<R, E> for IdempotencyAspect<S>
+where
+	R: ApplicationResponse + Serialize + DeserializeOwned,
+	E: ApplicationError + std::convert::From<BaseError>,
+	S: TCommandService<R, E>,
+{
+	async fn execute(self) -> Result<R, E> {
+		// Dry run neither claims the key nor answers with recorded response
+		let Some(key) = self.key.filter(|_| !self.context_manager.dry_run) else {
+			return self.inner.execute().await;
+		};
+		let key = key?;
+		let Some(store) = self.context_manager.idempotency_store.clone() else {
+			tracing::error!(command = self.command, "No idempotency store is set!");
+			return Err(BaseError::ServiceError.into());
+		};
+		if !store.claim(&key, self.command).await? {
+			return match store.get(&key).await? {
+				Some(record) if record.command != self.command => Err(BaseError::Rejected(format!("Idempotency key {} is used for {}", key, record.command)).into()),
+				Some(IdempotencyRecord { response: Some(response), .. }) => {
+					tracing::info!(command = self.command, key, "Duplicate command is answered with recorded response.");
+					serde_json::from_str(&response).map_err(|err| BaseError::DatabaseError(err.to_string()).into())
+				}
+				// * Still running, or committed without response recorded. Either way, it must not run again.
+				_ => Err(BaseError::ConcurrencyConflict(format!("Response of idempotency key {} is not recorded", key)).into()),
+			};
+		}
+
+		*self.context_manager.idempotency_claim.lock().unwrap() = Some(IdempotencyClaim {
+			key: key.clone(),
+			command: self.command,
+		});
+		let res = self.inner.execute().await;
+		// Not taken by the unit of work, as the command failed before commit or doesn't commit through it
+		if let Some(claim) = self.context_manager.take_idempotency_claim() {
+			tracing::debug!(command = claim.command, key = claim.key, "Idempotency claim is not marked committed.");
+		}
+		let response = match res.as_ref() {
+			Ok(response) => serde_json::to_string(response).map_err(|err| BaseError::DatabaseError(err.to_string())),
+			Err(_) => {
+				// * Claim committed by the unit of work is kept, as the changes are
+				if let Err(err) = store.release(&key).await {
+					tracing::error!(command = self.command, key, "Failed to release claim of failed command! {:?}", err);
+				}
+				return res;
+			}
+		};
+		// Command is done anyway. Duplicate is rejected as long as the response is not recorded.
+		if let Err(err) = async { store.complete(&key, &response?).await }.await {
+			tracing::error!(command = self.command, key, "Failed to record response! {:?}", err);
+		}
+		res
+	}
+}
+
+#[tokio::test]
+async fn test_idempotency_aspect() {
+	use super::executor::TConnection;
+	use std::sync::atomic::{AtomicUsize, Ordering};
+
+	struct Connection;
+	impl TConnection for Connection {}
+	#[derive(Debug)]
+	struct PlaceOrder(Option<&'static str>);
+	impl TCommand for PlaceOrder {}
+	impl TIdempotentCommand for PlaceOrder {
+		fn idempotency_key(&self) -> Option<&str> {
+			self.0
+		}
+	}
+	#[derive(Debug)]
+	struct CancelOrder;
+	impl TCommand for CancelOrder {}
+	impl TIdempotentCommand for CancelOrder {
+		fn idempotency_key(&self) -> Option<&str> {
+			Some("request-1")
+		}
+	}
+	#[derive(Debug, PartialEq, Serialize, serde::Deserialize)]
+	struct OrderPlaced(usize);
+	impl ApplicationResponse for OrderPlaced {}
+	static RUN: AtomicUsize = AtomicUsize::new(0);
+	struct Handler;
+	impl TCommandService<OrderPlaced, BaseError> for Handler {
+		async fn execute(self) -> Result<OrderPlaced, BaseError> {
+			Ok(OrderPlaced(RUN.fetch_add(1, Ordering::SeqCst) + 1))
+		}
+	}
+
+	struct FailingHandler;
+	impl TCommandService<OrderPlaced, BaseError> for FailingHandler {
+		async fn execute(self) -> Result<OrderPlaced, BaseError> {
+			RUN.fetch_add(1, Ordering::SeqCst);
+			Err(BaseError::ServiceError)
+		}
+	}
+
+	let store = Arc::new(InMemoryIdempotencyStore::default());
+	let context_manager = || Arc::new(ContextManager::new(&Connection).with_idempotency_store(store.clone()));
+	let key = |key: &str| serde_json::to_string(&("", "", key)).unwrap();
+
+	let first = IdempotencyAspect::new(&context_manager(), &PlaceOrder(Some("request-1")), Handler).execute().await.unwrap();
+	let duplicate = IdempotencyAspect::new(&context_manager(), &PlaceOrder(Some("request-1")), Handler).execute().await.unwrap();
+	assert_eq!((first, duplicate), (OrderPlaced(1), OrderPlaced(1)));
+	assert_eq!(RUN.load(Ordering::SeqCst), 1);
+
+	// Without key, the command is run every time
+	assert_eq!(IdempotencyAspect::new(&context_manager(), &PlaceOrder(None), Handler).execute().await.unwrap(), OrderPlaced(2));
+
+	// Key of another user is not answered with the response of the first one
+	let other_user = Arc::new(
+		ContextManager::new(&Connection)
+			.with_idempotency_store(store.clone())
+			.with_actor(super::actor::Actor::User("lee".into())),
+	);
+	assert_eq!(IdempotencyAspect::new(&other_user, &PlaceOrder(Some("request-1")), Handler).execute().await.unwrap(), OrderPlaced(3));
+
+	// Key of another command
+	let Err(BaseError::Rejected(reason)) = IdempotencyAspect::new(&context_manager(), &CancelOrder, Handler).execute().await else {
+		panic!("Key used for PlaceOrder must be rejected for CancelOrder");
+	};
+	assert!(reason.ends_with("PlaceOrder"));
+
+	// Claimed but response is not recorded - still running, or crashed after commit - is never run again
+	store.claim(&key("request-2"), std::any::type_name::<PlaceOrder>()).await.unwrap();
+	let res = IdempotencyAspect::new(&context_manager(), &PlaceOrder(Some("request-2")), Handler).execute().await;
+	assert!(matches!(res, Err(BaseError::ConcurrencyConflict(_))));
+	assert_eq!(RUN.load(Ordering::SeqCst), 3);
+
+	// Claim of the command that failed is released, so that it can be retried
+	let res = IdempotencyAspect::new(&context_manager(), &PlaceOrder(Some("request-3")), FailingHandler).execute().await;
+	assert!(matches!(res, Err(BaseError::ServiceError)));
+	assert!(store.get(&key("request-3")).await.unwrap().is_none());
+	assert_eq!(
+		IdempotencyAspect::new(&context_manager(), &PlaceOrder(Some("request-3")), Handler).execute().await.unwrap(),
+		OrderPlaced(5)
+	);
+	assert_eq!(store.get(&key("request-3")).await.unwrap().unwrap().response.as_deref(), Some("5"));
+
+	// Concurrent duplicate is rejected without running, as the key is claimed before the command runs
+	struct SlowHandler(Arc<tokio::sync::Notify>);
+	impl TCommandService<OrderPlaced, BaseError> for SlowHandler {
+		async fn execute(self) -> Result<OrderPlaced, BaseError> {
+			self.0.notified().await;
+			Handler.execute().await
+		}
+	}
+	let notify = Arc::new(tokio::sync::Notify::new());
+	let (original, duplicate) = tokio::join!(
+		IdempotencyAspect::new(&context_manager(), &PlaceOrder(Some("request-4")), SlowHandler(notify.clone())).execute(),
+		async {
+			let res = IdempotencyAspect::new(&context_manager(), &PlaceOrder(Some("request-4")), Handler).execute().await;
+			notify.notify_one();
+			res
+		}
+	);
+	assert_eq!(original.unwrap(), OrderPlaced(6));
+	assert!(matches!(duplicate, Err(BaseError::ConcurrencyConflict(_))));
+	assert_eq!(RUN.load(Ordering::SeqCst), 6);
+}
@@ -0,0 +1,120 @@
+//! ### Event import
+//! One-time migration into a system built on ruva projections - events exported from the legacy system, or rebuilt from its rows -
+//! is fed through the event handlers with [TEventBus::import_events](super::messagebus::TEventBus::import_events),
+//! batch by batch, as if the events were raised on the bus.
+//!
+//! ```rust,ignore
+//! let events: Vec<(i64, Arc<dyn TEvent>)> = legacy_orders.into_iter().map(|order| (order.id, OrderPlaced::from(order).to_message())).collect();
+//!
+//! let options = ImportOptions::new("legacy-orders", conn)
+//!     .batch_size(500)
+//!     .rate_limit(Duration::from_millis(200))
+//!     // Only projections - no mail for orders placed years ago
+//!     .handler("OrderPlaced", "project_order_summary")
+//!     .handler("OrderPlaced", "project_sales_report")
+//!     .checkpoint(InMemoryCheckpointStore::default())
+//!     .on_progress(|progress| tracing::info!("{}/{} imported", progress.imported, progress.total));
+//! let done = MessageBus.import_events(events, options).await?;
+//! ```
+//! Handlers are selected by the event and handler names of `init_event_handler!`, as in [handler_toggles](super::toggles::handler_toggles),
+//! and the selection holds for the events cascaded from them too. Without selection, every handler runs.
+//! Events are imported in the order of their ids, which must be unique and stable across exports - id of the legacy row, for example.
+//! The largest id imported is checkpointed after every batch, so importing the events again, even if exported anew, resumes where it stopped.
+//! Import stops with the first handler that fails, and the batch it is in is imported again on resume, so handlers should be idempotent.
+//! Events are handled regardless of `internally_notifiable`.
+use std::sync::Arc;
+use std::time::Duration;
+
+use super::actor::Actor;
+use super::contexts::ContextManager;
+use super::executor::TConnection;
+use crate::prelude::TCheckpointStore;
+
+type ProgressCallback = Box<dyn Fn(&ImportProgress) + Send + Sync>;
+
+pub struct ImportOptions {
+	pub(crate) name: String,
+	pub(crate) conn: &'static dyn TConnection,
+	pub(crate) batch_size: usize,
+	/// Delay between batches
+	pub(crate) rate_limit: Option<Duration>,
+	pub(crate) handlers: Option<hashbrown::HashSet<(String, String)>>,
+	pub(crate) checkpoint: Option<Arc<dyn TCheckpointStore>>,
+	pub(crate) on_progress: Option<ProgressCallback>,
+}
+
+impl ImportOptions {
+	/// `name` is the key of the checkpoint, which is the largest id of the events imported. Handlers run on `conn` as `Actor::System("import:{name}")`.
+	pub fn new(name: impl Into<String>, conn: &'static dyn TConnection) -> Self {
+		Self {
+			name: name.into(),
+			conn,
+			batch_size: 100,
+			rate_limit: None,
+			handlers: None,
+			checkpoint: None,
+			on_progress: None,
+		}
+	}
+
+	pub fn batch_size(mut self, batch_size: usize) -> Self {
+		assert!(batch_size > 0, "Batch size must be greater than 0");
+		self.batch_size = batch_size;
+		self
+	}
+
+	pub fn rate_limit(mut self, delay: Duration) -> Self {
+		self.rate_limit = Some(delay);
+		self
+	}
+
+	/// Run `handler` of `event`. Once any handler is given, the others are skipped as if they succeeded.
+	pub fn handler(mut self, event: impl Into<String>, handler: impl Into<String>) -> Self {
+		self.handlers.get_or_insert_with(Default::default).insert((event.into(), handler.into()));
+		self
+	}
+
+	pub fn checkpoint(mut self, store: impl TCheckpointStore + 'static) -> Self {
+		self.checkpoint = Some(Arc::new(store));
+		self
+	}
+
+	/// Called after every batch
+	pub fn on_progress(mut self, on_progress: impl Fn(&ImportProgress) + Send + Sync + 'static) -> Self {
+		self.on_progress = Some(Box::new(on_progress));
+		self
+	}
+
+	pub(crate) fn context_manager(&self) -> ContextManager {
+		let mut context_manager = ContextManager::new(self.conn).with_actor(Actor::System(format!("import:{}", self.name))).propagating_handler_errors();
+		context_manager.selected_handlers = self.handlers.clone().map(Arc::new);
+		context_manager
+	}
+
+	pub(crate) fn report(&self, progress: &ImportProgress) {
+		tracing::info!("Import {}: {}/{} events imported", progress.name, progress.imported, progress.total);
+		if let Some(on_progress) = self.on_progress.as_ref() {
+			on_progress(progress);
+		}
+	}
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ImportProgress {
+	pub name: String,
+	/// Events imported so far, including the ones imported by previous runs
+	pub imported: usize,
+	pub total: usize,
+	pub finished: bool,
+}
+
+impl ContextManager {
+	/// Called by `init_event_handler!` before running each handler. See [ImportOptions::handler].
+	#[doc(hidden)]
+	pub fn is_handler_selected(&self, event: &str, handler: &str) -> bool {
+		match self.selected_handlers.as_ref() {
+			Some(selected) => selected.contains(&(event.to_string(), handler.to_string())),
+			None => true,
+		}
+	}
+}
@@ -0,0 +1,383 @@
+//! ### Inbox
+//! Consume events published by other services and run the local event handlers registered with `init_event_handler!`.
+//! Each event is recorded in [TInboxStore] by its id first, so redelivery by the broker is handled only once.
+//!
+//! ```rust,ignore
+//! // Events of other services, declared locally with `#[internally_notifiable]` so that local handlers can be registered.
+//! init_event_handler!(ServiceError, |ctx| Context::new(ctx), ExternalOrderPlaced: [reserve_stock]);
+//!
+//! let inbox = Inbox::new(conn, pool.clone()).register::<ExternalOrderPlaced>().register_topic::<ExternalPaymentDone>("payment.done");
+//!
+//! // On boot
+//! inbox.consume(&KafkaConsumer::new(..), &MessageBus, bus_shutdown_token()).await?;
+//! ```
+//! Events whose model differs from the one of this context are received through [Translation] with [Inbox::translate].
+//!
+//! Event is acknowledged to the consumer only after its handlers are run. If they fail, the record is removed
+//! so that redelivered event is handled again.
+use async_trait::async_trait;
+use serde::de::DeserializeOwned;
+use std::sync::{Arc, Mutex};
+use tracing::Instrument;
+
+use super::actor::Actor;
+use super::backlog::{backlog_metrics, Backlog, MessageSource};
+use super::contexts::ContextManager;
+use super::executor::TConnection;
+use super::messagebus::TEventBus;
+use super::preflight::{check_inbox_routes, PreflightReport};
+use super::propagation::{extract_trace_context, extract_trace_context_with, inbound_span, TTracePropagator};
+use super::shutdown::ShutdownToken;
+use super::translation::Translation;
+use crate::prelude::{json, strip_topic_namespace, upcast_payload, ApplicationError, BaseError, TEvent, TTopic};
+
+/// Event as received from the broker
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InboundEvent {
+	/// Unique id given by the producer, for example `OutBox::id`. Used for deduplication.
+	pub id: String,
+	pub topic: String,
+	pub payload: String,
+	/// `OutBox::version` given by the producer. Payload of older version is upcast before deserialization.
+	pub version: u32,
+	/// `OutBox::trace_context` given by the producer, such as `traceparent` header of Kafka. See [TTracePropagator](crate::prelude::TTracePropagator).
+	pub trace_context: Option<String>,
+	/// `OutBox::correlation_id` given by the producer. Events raised by the handlers carry it, and have `id` as their causation id.
+	pub correlation_id: Option<String>,
+}
+
+/// Source of inbound events - Kafka consumer, RabbitMQ queue and so on.
+#[async_trait]
+pub trait TEventConsumer: Send + Sync {
+	/// `None` when the source is closed
+	async fn next(&self) -> Option<Result<InboundEvent, BaseError>>;
+	async fn ack(&self, event: &InboundEvent) -> Result<(), BaseError>;
+	/// Events published but not yet received, such as consumer lag of Kafka. Sampled after each event, so it should be cheap.
+	/// `None` if not supported.
+	async fn backlog(&self) -> Option<Backlog> {
+		None
+	}
+}
+
+/// Deduplication storage keyed by event id
+#[async_trait]
+pub trait TInboxStore: Send + Sync {
+	/// Record `event_id`. Returns `false` if it has been recorded already.
+	async fn try_record(&self, event_id: &str, topic: &str) -> Result<bool, BaseError>;
+	/// Remove the record so that the event can be handled again
+	async fn forget(&self, event_id: &str) -> Result<(), BaseError>;
+}
+
+/// For tests and single instance deployments. Records are lost on restart.
+#[derive(Default)]
+pub struct InMemoryInboxStore(Mutex<hashbrown::HashSet<String>>);
+
+#[async_trait]
+impl TInboxStore for InMemoryInboxStore {
+	async fn try_record(&self, event_id: &str, _topic: &str) -> Result<bool, BaseError> {
+		Ok(self.0.lock().unwrap().insert(event_id.to_string()))
+	}
+	async fn forget(&self, event_id: &str) -> Result<(), BaseError> {
+		self.0.lock().unwrap().remove(event_id);
+		Ok(())
+	}
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum InboxOutcome {
+	Handled,
+	/// Recorded already
+	Duplicate,
+	/// No event type is registered for the topic
+	Ignored,
+	/// Payload couldn't be deserialized. It is acknowledged anyway as redelivery won't help.
+	Malformed(String),
+}
+
+type Deserialize = Box<dyn Fn(&str) -> Result<Arc<dyn TEvent>, String> + Send + Sync>;
+
+struct Route {
+	/// Topic of the event deserialized, which its handlers are registered on
+	event_topic: &'static str,
+	deserialize: Deserialize,
+}
+
+pub struct Inbox<S> {
+	conn: &'static dyn TConnection,
+	store: S,
+	routes: hashbrown::HashMap<String, Route>,
+	propagator: Option<Box<dyn TTracePropagator>>,
+}
+
+impl<S: TInboxStore> Inbox<S> {
+	pub fn new(conn: &'static dyn TConnection, store: S) -> Self {
+		Self {
+			conn,
+			store,
+			routes: Default::default(),
+			propagator: None,
+		}
+	}
+
+	/// Extract trace context of events by `propagator` instead of the one set by [set_trace_propagator](crate::prelude::set_trace_propagator)
+	pub fn with_trace_propagator(mut self, propagator: impl TTracePropagator + 'static) -> Self {
+		self.propagator = Some(Box::new(propagator));
+		self
+	}
+
+	/// Receive events of `T` on its topic
+	pub fn register<T: TEvent + TTopic + DeserializeOwned + 'static>(self) -> Self {
+		self.register_topic::<T>(T::TOPIC)
+	}
+
+	/// Receive events of `T` on `topic`, when it differs from the type name. `topic` is without namespace.
+	/// ## Panics
+	/// If event type for the same topic is already registered.
+	pub fn register_topic<T: TEvent + TTopic + DeserializeOwned + 'static>(self, topic: impl Into<String>) -> Self {
+		self.route::<T>(
+			topic.into(),
+			Box::new(|payload| json::from_str::<T>(payload).map(|event| Arc::new(event) as Arc<dyn TEvent>).map_err(|err| err.to_string())),
+		)
+	}
+
+	/// Receive events of another bounded context on the topic of `translation`, translated into `T`. See [Translation].
+	/// ## Panics
+	/// If event type for the same topic is already registered.
+	pub fn translate<T: TEvent + TTopic + DeserializeOwned + 'static>(self, translation: Translation<T>) -> Self {
+		self.route::<T>(translation.topic().to_string(), Box::new(translation.into_deserializer()))
+	}
+
+	fn route<T: TTopic>(mut self, topic: String, deserialize: Deserialize) -> Self {
+		if self.routes.contains_key(&topic) {
+			panic!("Inbox route for {} is already registered!", topic);
+		}
+		self.routes.insert(topic, Route { event_topic: T::TOPIC, deserialize });
+		self
+	}
+
+	/// Report inbound `pending_topics`, e.g. the ones with consumer lag on the broker, that have no route,
+	/// and routes whose event has no handler registered on `bus`.
+	pub fn preflight<E: 'static>(&self, bus: &impl TEventBus<E>, pending_topics: impl IntoIterator<Item = String>) -> PreflightReport {
+		// Events of other environments sharing the broker are not for this inbox
+		let pending_topics = pending_topics.into_iter().filter_map(|topic| strip_topic_namespace(&topic).map(str::to_string));
+		check_inbox_routes(bus.event_handler(), self.routes.iter().map(|(topic, route)| (topic.as_str(), route.event_topic)), pending_topics)
+	}
+
+	/// Deduplicate `event` and run the handlers of it on `bus`.
+	pub async fn receive<E>(&self, bus: &(impl TEventBus<E> + Sync), event: &InboundEvent) -> Result<InboxOutcome, E>
+	where
+		E: ApplicationError + std::convert::From<BaseError>,
+		BaseError: std::convert::From<E>,
+	{
+		// Events of other environments sharing the broker. See `topic_namespace`.
+		let Some((topic, deserialize)) = strip_topic_namespace(&event.topic).and_then(|topic| self.routes.get(topic).map(|route| (topic, &route.deserialize))) else {
+			return Ok(InboxOutcome::Ignored);
+		};
+		let upcasted = upcast_payload(topic, event.version, &event.payload).map_err(|err| format!("{:?}", err));
+		let deserialized = match upcasted.and_then(|payload| deserialize(&payload)) {
+			Ok(deserialized) => deserialized,
+			Err(err) => {
+				tracing::error!(topic = %event.topic, id = %event.id, "Failed to deserialize inbound event! {}", err);
+				return Ok(InboxOutcome::Malformed(err));
+			}
+		};
+		if !self.store.try_record(&event.id, &event.topic).await? {
+			tracing::info!(topic = %event.topic, id = %event.id, "Duplicate inbound event skipped.");
+			return Ok(InboxOutcome::Duplicate);
+		}
+
+		let span = inbound_span(topic);
+		match &self.propagator {
+			Some(propagator) => extract_trace_context_with(propagator.as_ref(), &span, event.trace_context.as_deref()),
+			None => extract_trace_context(&span, event.trace_context.as_deref()),
+		}
+		// Failed event is forgotten and left unacknowledged, to be redelivered
+		let mut context_manager = ContextManager::new(self.conn).with_actor(Actor::System(format!("inbox:{}", event.topic))).propagating_handler_errors();
+		context_manager.correlation_id = event.correlation_id.clone();
+		context_manager.raised.assign(&deserialized, event.id.clone(), None);
+		if let Err(err) = bus.handle_events(vec![deserialized], context_manager).instrument(span).await {
+			self.store.forget(&event.id).await?;
+			return Err(err);
+		}
+		backlog_metrics().processed(MessageSource::Inbox, topic);
+		Ok(InboxOutcome::Handled)
+	}
+
+	/// Receive events from `consumer` until it is closed or `shutdown` is signalled. Event is acknowledged once received successfully.
+	pub async fn consume<E>(&self, consumer: &impl TEventConsumer, bus: &(impl TEventBus<E> + Sync), shutdown: &ShutdownToken) -> Result<(), BaseError>
+	where
+		E: ApplicationError + std::convert::From<BaseError>,
+		BaseError: std::convert::From<E>,
+	{
+		while !shutdown.is_shutdown() {
+			let event = tokio::select! {
+				event = consumer.next() => event,
+				_ = shutdown.cancelled() => break,
+			};
+			let Some(event) = event else {
+				break;
+			};
+			let event = event?;
+			match self.receive(bus, &event).await {
+				Ok(_) => consumer.ack(&event).await?,
+				Err(err) => tracing::error!(topic = %event.topic, id = %event.id, "Failed to handle inbound event! {:?}", err),
+			}
+			if let Some(backlog) = consumer.backlog().await {
+				backlog_metrics().sample(MessageSource::Inbox, backlog);
+			}
+		}
+		Ok(())
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use super::*;
+	use crate::prelude::{EventHandlers, TEventHandler, INITIAL_EVENT_VERSION};
+	use std::sync::atomic::{AtomicUsize, Ordering};
+
+	struct Connection;
+	impl TConnection for Connection {}
+
+	#[derive(serde::Deserialize)]
+	struct PaymentDone {}
+	impl TEvent for PaymentDone {
+		fn internally_notifiable(&self) -> bool {
+			true
+		}
+		fn state(&self) -> String {
+			"{}".into()
+		}
+	}
+	impl TTopic for PaymentDone {
+		const TOPIC: &'static str = "PaymentDone";
+	}
+
+	#[derive(serde::Deserialize)]
+	struct RefundDone {}
+	impl TEvent for RefundDone {
+		fn internally_notifiable(&self) -> bool {
+			true
+		}
+		fn state(&self) -> String {
+			"{}".into()
+		}
+	}
+	impl TTopic for RefundDone {
+		const TOPIC: &'static str = "RefundDone";
+	}
+
+	static HANDLED: AtomicUsize = AtomicUsize::new(0);
+	/// Refund handler fails on the first attempt
+	static REFUND_ATTEMPTS: AtomicUsize = AtomicUsize::new(0);
+	struct Bus;
+	impl TEventBus<BaseError> for Bus {
+		fn event_handler(&self) -> &'static TEventHandler<BaseError> {
+			static EVENT_HANDLER: std::sync::LazyLock<TEventHandler<BaseError>> = std::sync::LazyLock::new(|| {
+				let mut map = TEventHandler::default();
+				map.insert(
+					"PaymentDone".to_string(),
+					EventHandlers::Sync(vec![Box::new(|_, _| {
+						HANDLED.fetch_add(1, Ordering::SeqCst);
+						Box::pin(async { Ok(()) })
+					})]),
+				);
+				map.insert(
+					"RefundDone".to_string(),
+					EventHandlers::Sync(vec![Box::new(|_, _| {
+						let attempt = REFUND_ATTEMPTS.fetch_add(1, Ordering::SeqCst);
+						Box::pin(async move {
+							match attempt {
+								0 => Err(BaseError::DatabaseError("Connection reset".into())),
+								_ => Ok(()),
+							}
+						})
+					})]),
+				);
+				map
+			});
+			&EVENT_HANDLER
+		}
+	}
+
+	#[tokio::test]
+	async fn test_inbox_deduplicates() {
+		let inbox = Inbox::new(&Connection, InMemoryInboxStore::default()).register::<PaymentDone>();
+		let event = InboundEvent {
+			id: "1".into(),
+			topic: "PaymentDone".into(),
+			payload: "{}".into(),
+			version: INITIAL_EVENT_VERSION,
+			trace_context: None,
+			correlation_id: None,
+		};
+
+		assert_eq!(inbox.receive(&Bus, &event).await.unwrap(), InboxOutcome::Handled);
+		assert_eq!(inbox.receive(&Bus, &event).await.unwrap(), InboxOutcome::Duplicate);
+		assert_eq!(HANDLED.load(Ordering::SeqCst), 1);
+
+		let malformed = InboundEvent {
+			id: "2".into(),
+			payload: "[".into(),
+			..event.clone()
+		};
+		assert!(matches!(inbox.receive(&Bus, &malformed).await.unwrap(), InboxOutcome::Malformed(_)));
+
+		let unknown = InboundEvent { topic: "Unknown".into(), ..event };
+		assert_eq!(inbox.receive(&Bus, &unknown).await.unwrap(), InboxOutcome::Ignored);
+	}
+
+	#[tokio::test]
+	async fn test_failed_event_is_redelivered() {
+		let inbox = Inbox::new(&Connection, InMemoryInboxStore::default()).register::<RefundDone>();
+		let event = InboundEvent {
+			id: "1".into(),
+			topic: "RefundDone".into(),
+			payload: "{}".into(),
+			version: INITIAL_EVENT_VERSION,
+			trace_context: None,
+			correlation_id: None,
+		};
+
+		assert!(matches!(inbox.receive(&Bus, &event).await, Err(BaseError::DatabaseError(_))));
+		// Not recorded as handled
+		assert_eq!(inbox.receive(&Bus, &event).await.unwrap(), InboxOutcome::Handled);
+		assert_eq!(REFUND_ATTEMPTS.load(Ordering::SeqCst), 2);
+		assert_eq!(inbox.receive(&Bus, &event).await.unwrap(), InboxOutcome::Duplicate);
+	}
+
+	#[test]
+	fn test_inbox_preflight() {
+		#[derive(serde::Deserialize)]
+		struct OrderShipped {}
+		impl TEvent for OrderShipped {
+			fn state(&self) -> String {
+				"{}".into()
+			}
+		}
+		impl TTopic for OrderShipped {
+			const TOPIC: &'static str = "OrderShipped";
+		}
+		// Handlers are looked up by its topic, not by its type name
+		#[derive(serde::Deserialize)]
+		struct PaymentSettled {}
+		impl TEvent for PaymentSettled {
+			fn state(&self) -> String {
+				"{}".into()
+			}
+		}
+		impl TTopic for PaymentSettled {
+			const TOPIC: &'static str = "PaymentDone";
+		}
+
+		let inbox = Inbox::new(&Connection, InMemoryInboxStore::default())
+			.register_topic::<PaymentDone>("payment.done")
+			.register_topic::<PaymentSettled>("payment.settled")
+			.register_topic::<OrderShipped>("order.shipped");
+
+		let report = inbox.preflight(&Bus, vec!["payment.done".to_string(), "refund.done".to_string()]);
+		assert_eq!(report.unrouted_topics, vec!["refund.done".to_string()]);
+		assert_eq!(report.unhandled_routes, vec!["order.shipped".to_string()]);
+		assert!(!report.is_ok());
+	}
+}
@@ -0,0 +1,82 @@
+//! ### Background jobs
+//! [JobDispatcher] dispatches commands from job runners the same way as from HTTP handlers,
+//! with context manager whose actor is `Actor::System` and a tracing span named after the job.
+//!
+//! ```rust,ignore
+//! let dispatcher = JobDispatcher::new("expire-coupons", conn);
+//!
+//! // Simple cron loop
+//! let _handle = dispatcher.clone().spawn_every(Duration::from_secs(60), || ExpireCoupons {});
+//!
+//! // apalis or any other job runner
+//! async fn send_reminder(job: Reminder, dispatcher: Data<JobDispatcher>) -> Result<(), ServiceError> {
+//!     dispatcher.dispatch(SendReminder { user_id: job.user_id }).await?;
+//!     Ok(())
+//! }
+//! ```
+use tracing::Instrument;
+
+use super::actor::Actor;
+use super::contexts::ContextManager;
+use super::executor::TConnection;
+use super::messagebus::{MessageBus, TMessageBus};
+use crate::prelude::{BaseError, TCommandSpec};
+
+#[derive(Clone)]
+pub struct JobDispatcher {
+	name: String,
+	conn: &'static dyn TConnection,
+}
+
+impl JobDispatcher {
+	pub fn new(name: impl Into<String>, conn: &'static dyn TConnection) -> Self {
+		Self { name: name.into(), conn }
+	}
+
+	pub fn name(&self) -> &str {
+		&self.name
+	}
+
+	/// Context manager that the command of this job runs with
+	pub fn context_manager(&self) -> ContextManager {
+		ContextManager::new(self.conn).with_actor(Actor::System(self.name.clone()))
+	}
+
+	pub async fn dispatch<C>(&self, message: C) -> Result<C::Response, C::Error>
+	where
+		C: TCommandSpec,
+		C::Error: std::convert::From<BaseError>,
+		BaseError: std::convert::From<C::Error>,
+		MessageBus: TMessageBus<C::Response, C::Error, C>,
+	{
+		let span = tracing::info_span!("job", job = %self.name, command = std::any::type_name::<C>());
+		MessageBus.dispatch_with(message, self.context_manager()).instrument(span).await
+	}
+
+	/// Dispatch command made by `make_command` every `interval`. Failure is logged and the loop goes on.
+	pub fn spawn_every<C>(self, interval: std::time::Duration, make_command: impl Fn() -> C + Send + Sync + 'static) -> tokio::task::JoinHandle<()>
+	where
+		C: TCommandSpec,
+		C::Error: std::convert::From<BaseError>,
+		BaseError: std::convert::From<C::Error>,
+		MessageBus: TMessageBus<C::Response, C::Error, C>,
+	{
+		tokio::spawn(async move {
+			loop {
+				if let Err(err) = self.dispatch(make_command()).await {
+					tracing::error!("Job {} failed! {:?}", self.name, err);
+				}
+				tokio::time::sleep(interval).await;
+			}
+		})
+	}
+}
+
+#[test]
+fn test_job_context_manager_acts_as_system() {
+	struct Connection;
+	impl TConnection for Connection {}
+
+	let dispatcher = JobDispatcher::new("expire-coupons", &Connection);
+	assert_eq!(dispatcher.context_manager().actor, Actor::System("expire-coupons".into()));
+}
@@ -0,0 +1,224 @@
+//! ### Command journal
+//! [CommandJournalAspect] records the command it wraps - payload, actor, correlation id and outcome - in [TCommandJournal]
+//! set by [set_command_journal]. With `sqlx-postgres`, entry of successful command is written to `command_log`
+//! in the transaction of the command, so journal and state never disagree.
+//!
+//! ```rust,ignore
+//! // On boot. `PgPool` implements `TCommandJournal` with `sqlx-postgres` feature.
+//! set_command_journal(pool.clone());
+//!
+//! impl TCommandRoute for TransferMoney {
+//!     fn command_handler(context_manager: AtomicContextManager, cmd: Self) -> impl TCommandService<Self::Response, Self::Error> {
+//!         CommandJournalAspect::new(&context_manager.clone(), &cmd, CommandHandler((cmd, Context::new(context_manager))))
+//!     }
+//! }
+//!
+//! // Debugging - run the journaled command again as the same actor, without committing or handling events.
+//! let res = MessageBus.rerun_journaled::<TransferMoney>(journal_id, conn).await?;
+//! ```
+use std::sync::{Arc, Mutex, RwLock};
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use serde::{de::DeserializeOwned, Serialize};
+
+use super::actor::Actor;
+use super::contexts::{AtomicContextManager, ContextManager};
+use super::executor::TConnection;
+use super::messagebus::{MessageBus, TCommandService, TMessageBus};
+use crate::prelude::{current_trace_id, ApplicationError, ApplicationResponse, BaseError, SnowFlake, TCommand, TCommandSpec};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum JournalOutcome {
+	Succeeded,
+	Failed(String),
+}
+
+impl JournalOutcome {
+	pub fn as_str(&self) -> &'static str {
+		match self {
+			Self::Succeeded => "succeeded",
+			Self::Failed(_) => "failed",
+		}
+	}
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct JournalEntry {
+	pub id: i64,
+	/// Type name of the command
+	pub command: String,
+	/// Serialized command
+	pub payload: String,
+	pub actor: Actor,
+	pub correlation_id: Option<String>,
+	pub outcome: JournalOutcome,
+	pub recorded_at: DateTime<Utc>,
+}
+
+#[async_trait]
+pub trait TCommandJournal: Send + Sync {
+	async fn record(&self, entry: &JournalEntry) -> Result<(), BaseError>;
+	async fn get(&self, id: i64) -> Result<Option<JournalEntry>, BaseError>;
+}
+
+#[async_trait]
+impl<T: TCommandJournal + ?Sized> TCommandJournal for Arc<T> {
+	async fn record(&self, entry: &JournalEntry) -> Result<(), BaseError> {
+		self.as_ref().record(entry).await
+	}
+	async fn get(&self, id: i64) -> Result<Option<JournalEntry>, BaseError> {
+		self.as_ref().get(id).await
+	}
+}
+
+/// For tests and single instance deployments. Entries are lost on restart.
+#[derive(Default)]
+pub struct InMemoryCommandJournal(Mutex<Vec<JournalEntry>>);
+
+#[async_trait]
+impl TCommandJournal for InMemoryCommandJournal {
+	async fn record(&self, entry: &JournalEntry) -> Result<(), BaseError> {
+		self.0.lock().unwrap().push(entry.clone());
+		Ok(())
+	}
+	async fn get(&self, id: i64) -> Result<Option<JournalEntry>, BaseError> {
+		Ok(self.0.lock().unwrap().iter().find(|entry| entry.id == id).cloned())
+	}
+}
+
+static COMMAND_JOURNAL: RwLock<Option<Arc<dyn TCommandJournal>>> = RwLock::new(None);
+
+/// Not set by default, in which case [CommandJournalAspect] records nothing.
+pub fn set_command_journal(journal: impl TCommandJournal + 'static) {
+	*COMMAND_JOURNAL.write().unwrap() = Some(Arc::new(journal));
+}
+
+pub fn command_journal() -> Option<Arc<dyn TCommandJournal>> {
+	COMMAND_JOURNAL.read().unwrap().clone()
+}
+
+impl ContextManager {
+	/// Run the command without committing its transaction or handling the events it raises. See [MessageBus::rerun_journaled].
+	pub fn with_dry_run(mut self) -> Self {
+		self.dry_run = true;
+		self
+	}
+
+	/// Journal entry of the command that is not written yet. Taken by the unit of work to write it in its transaction.
+	pub(crate) fn take_journal_entry(&self) -> Option<JournalEntry> {
+		self.journal_entry.lock().unwrap().take()
+	}
+}
+
+/// Journal the command `inner` runs. Dry run is not journaled.
+pub struct CommandJournalAspect<S> {
+	context_manager: AtomicContextManager,
+	/// Command that fails to serialize is not run, as it could never be rerun.
+	journaled: Result<(), BaseError>,
+	inner: S,
+}
+
+impl<S> CommandJournalAspect<S> {
+	pub fn new<C: TCommand + Serialize>(context_manager: &AtomicContextManager, command: &C, inner: S) -> Self {
+		let mut journaled = Ok(());
+		if !context_manager.dry_run && COMMAND_JOURNAL.read().unwrap().is_some() {
+			journaled = serde_json::to_string(command).map_err(|err| BaseError::DatabaseError(err.to_string())).map(|payload| {
+				let entry = JournalEntry {
+					id: *SnowFlake::generate(),
+					command: std::any::type_name::<C>().to_string(),
+					payload,
+					actor: context_manager.actor.clone(),
+					correlation_id: context_manager.correlation_id.clone().or_else(current_trace_id),
+					outcome: JournalOutcome::Succeeded,
+					recorded_at: context_manager.clock.now(),
+				};
+				*context_manager.journal_entry.lock().unwrap() = Some(entry);
+			});
+		}
+		Self {
+			context_manager: context_manager.clone(),
+			journaled,
+			inner,
+		}
+	}
+}
+
+impl<R, E, S> TCommandService<R, E> for CommandJournalAspect<S>
+where
+	R: ApplicationResponse,
+	E: ApplicationError + std::convert::From<BaseError>,
+	S: TCommandService<R, E>,
+{
+	async fn execute(self) -> Result<R, E> {
+		self.journaled?;
+		let res = self.inner.execute().await;
+		// Not taken by the unit of work, either because the command failed or the unit of work doesn't journal
+		if let (Some(mut entry), Some(journal)) = (self.context_manager.take_journal_entry(), command_journal()) {
+			if let Err(err) = res.as_ref() {
+				entry.outcome = JournalOutcome::Failed(format!("{:?}", err));
+			}
+			if let Err(err) = journal.record(&entry).await {
+				tracing::error!(command = %entry.command, "Failed to journal command! {:?}", err);
+			}
+		}
+		res
+	}
+}
+
+impl MessageBus {
+	/// Run journaled command again as the actor who ran it, in dry run.
+	pub async fn rerun_journaled<C>(&self, id: i64, conn: &'static dyn TConnection) -> Result<C::Response, C::Error>
+	where
+		C: TCommandSpec + DeserializeOwned,
+		C::Error: std::convert::From<BaseError>,
+		BaseError: std::convert::From<C::Error>,
+		Self: TMessageBus<C::Response, C::Error, C>,
+	{
+		let journal = command_journal().ok_or_else(|| BaseError::ServiceError)?;
+		let entry = journal.get(id).await?.ok_or_else(|| BaseError::NotFound)?;
+		if entry.command != std::any::type_name::<C>() {
+			return Err(BaseError::Rejected(format!("Journal entry {} is {}", id, entry.command)).into());
+		}
+		let command = serde_json::from_str::<C>(&entry.payload).map_err(|err| BaseError::DatabaseError(err.to_string()))?;
+		tracing::info!(id, command = %entry.command, "Rerunning journaled command in dry run.");
+		self.dispatch_with(command, ContextManager::new(conn).with_actor(entry.actor).with_dry_run()).await
+	}
+}
+
+#[tokio::test]
+async fn test_journal_records_outcome() {
+	struct Connection;
+	impl TConnection for Connection {}
+	#[derive(Debug, Serialize)]
+	struct Transfer {
+		amount: i64,
+	}
+	impl TCommand for Transfer {}
+	struct Handler(bool);
+	impl TCommandService<(), BaseError> for Handler {
+		async fn execute(self) -> Result<(), BaseError> {
+			match self.0 {
+				true => Ok(()),
+				false => Err(BaseError::ServiceError),
+			}
+		}
+	}
+
+	let journal = Arc::new(InMemoryCommandJournal::default());
+	set_command_journal(journal.clone());
+	let context_manager = Arc::new(ContextManager::new(&Connection).with_actor(Actor::User("migo".into())));
+	CommandJournalAspect::new(&context_manager, &Transfer { amount: 10 }, Handler(true)).execute().await.unwrap();
+	CommandJournalAspect::new(&context_manager, &Transfer { amount: 20 }, Handler(false)).execute().await.unwrap_err();
+
+	let entries = journal.0.lock().unwrap().clone();
+	assert_eq!(entries.len(), 2);
+	assert_eq!((entries[0].payload.as_str(), &entries[0].outcome), (r#"{"amount":10}"#, &JournalOutcome::Succeeded));
+	assert_eq!(entries[0].actor, Actor::User("migo".into()));
+	assert_eq!(entries[1].outcome, JournalOutcome::Failed("ServiceError".into()));
+
+	// Dry run is not journaled
+	let dry_run = Arc::new(ContextManager::new(&Connection).with_dry_run());
+	CommandJournalAspect::new(&dry_run, &Transfer { amount: 30 }, Handler(true)).execute().await.unwrap();
+	assert_eq!(journal.0.lock().unwrap().len(), 2);
+}
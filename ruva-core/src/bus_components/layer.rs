@@ -0,0 +1,188 @@
+//! ### Layer
+//! Compose aspects around command service without writing nested wrappers by hand, as with tower.
+//! Layer added first is the outermost one, so it sees the command first and the result last.
+//! To have aspects checked against the order they declare, add them with [aspect_fn](super::aspect::aspect_fn) and see
+//! [ServiceBuilder::try_service].
+//!
+//! ```rust,ignore
+//! struct Logging;
+//! impl<S> TLayer<S> for Logging {
+//!     type Service = LoggingAspect<S>;
+//!     fn layer(&self, inner: S) -> Self::Service {
+//!         LoggingAspect(inner)
+//!     }
+//! }
+//!
+//! impl TCommandRoute for MakeOrder {
+//!     fn command_handler(context_manager: AtomicContextManager, cmd: Self) -> impl TCommandService<Self::Response, Self::Error> {
+//!         ServiceBuilder::new()
+//!             .layer(Logging)
+//!             .layer(layer_fn(|inner| ReplayProtectionAspect::new(&GUARD, cmd.request_id, inner)))
+//!             .service(CommandHandler((cmd, Context::new(context_manager))))
+//!     }
+//! }
+//! ```
+
+use super::aspect::{check_aspects, AspectChainError, AspectMetadata};
+
+/// Decorate `S` with another service
+pub trait TLayer<S> {
+	type Service;
+	fn layer(&self, inner: S) -> Self::Service;
+	/// Aspects this layer adds, from the outermost one. Layer that is not an aspect adds none.
+	fn aspects(&self) -> Vec<AspectMetadata> {
+		vec![]
+	}
+}
+
+/// Layer that does nothing
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Identity;
+
+impl<S> TLayer<S> for Identity {
+	type Service = S;
+	fn layer(&self, inner: S) -> Self::Service {
+		inner
+	}
+}
+
+/// `Outer` wrapping `Inner`
+#[derive(Debug, Clone, Copy)]
+pub struct Stack<Inner, Outer> {
+	inner: Inner,
+	outer: Outer,
+}
+
+impl<S, Inner, Outer> TLayer<S> for Stack<Inner, Outer>
+where
+	Inner: TLayer<S>,
+	Outer: TLayer<Inner::Service>,
+{
+	type Service = Outer::Service;
+	fn layer(&self, inner: S) -> Self::Service {
+		self.outer.layer(self.inner.layer(inner))
+	}
+	fn aspects(&self) -> Vec<AspectMetadata> {
+		[self.outer.aspects(), self.inner.aspects()].concat()
+	}
+}
+
+/// Layer from closure
+#[derive(Clone, Copy)]
+pub struct LayerFn<F>(F);
+
+pub fn layer_fn<F>(f: F) -> LayerFn<F> {
+	LayerFn(f)
+}
+
+impl<S, F, Out> TLayer<S> for LayerFn<F>
+where
+	F: Fn(S) -> Out,
+{
+	type Service = Out;
+	fn layer(&self, inner: S) -> Self::Service {
+		(self.0)(inner)
+	}
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct ServiceBuilder<L> {
+	layer: L,
+}
+
+impl Default for ServiceBuilder<Identity> {
+	fn default() -> Self {
+		Self { layer: Identity }
+	}
+}
+
+impl ServiceBuilder<Identity> {
+	pub fn new() -> Self {
+		Self::default()
+	}
+}
+
+impl<L> ServiceBuilder<L> {
+	/// Add `layer` inside the ones added so far
+	pub fn layer<T>(self, layer: T) -> ServiceBuilder<Stack<T, L>> {
+		ServiceBuilder {
+			layer: Stack { inner: layer, outer: self.layer },
+		}
+	}
+
+	/// Wrap `service` with every layer
+	pub fn service<S>(&self, service: S) -> L::Service
+	where
+		L: TLayer<S>,
+	{
+		self.layer.layer(service)
+	}
+
+	/// Wrap `service` with every layer, failing if the aspects among them conflict with what they declare with
+	/// [TAspectMetadata](super::aspect::TAspectMetadata)
+	pub fn try_service<S>(&self, service: S) -> Result<L::Service, AspectChainError>
+	where
+		L: TLayer<S>,
+	{
+		check_aspects(&self.layer.aspects())?;
+		Ok(self.layer.layer(service))
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use super::*;
+	use crate::prelude::{BaseError, TCommandService};
+	use std::sync::{Arc, Mutex};
+
+	type Calls = Arc<Mutex<Vec<&'static str>>>;
+
+	struct Handler(Calls);
+	impl TCommandService<(), BaseError> for Handler {
+		async fn execute(self) -> Result<(), BaseError> {
+			self.0.lock().unwrap().push("handler");
+			Ok(())
+		}
+	}
+
+	struct Recording<S> {
+		name: &'static str,
+		calls: Calls,
+		inner: S,
+	}
+	impl<S: TCommandService<(), BaseError>> TCommandService<(), BaseError> for Recording<S> {
+		async fn execute(self) -> Result<(), BaseError> {
+			self.calls.lock().unwrap().push(self.name);
+			self.inner.execute().await
+		}
+	}
+
+	struct RecordingLayer(&'static str, Calls);
+	impl<S> TLayer<S> for RecordingLayer {
+		type Service = Recording<S>;
+		fn layer(&self, inner: S) -> Self::Service {
+			Recording {
+				name: self.0,
+				calls: self.1.clone(),
+				inner,
+			}
+		}
+	}
+
+	#[tokio::test]
+	async fn test_layers_are_applied_in_order() {
+		let calls = Calls::default();
+		let inner_calls = calls.clone();
+		let service = ServiceBuilder::new()
+			.layer(RecordingLayer("outer", calls.clone()))
+			.layer(layer_fn(move |inner| Recording {
+				name: "inner",
+				calls: inner_calls.clone(),
+				inner,
+			}))
+			.service(Handler(calls.clone()));
+
+		service.execute().await.unwrap();
+		assert_eq!(*calls.lock().unwrap(), vec!["outer", "inner", "handler"]);
+	}
+}
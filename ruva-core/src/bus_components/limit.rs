@@ -0,0 +1,126 @@
+//! ### Event limit
+//! A bug in handlers can make a single command cascade into an event storm. [EventLimit] caps the number of internally notifiable
+//! events one dispatch may enqueue, counting both the ones raised by the command and the ones cascaded from event handlers.
+//!
+//! ```rust
+//! # use ruva_core::prelude::*;
+//! # struct Connection;
+//! # impl TConnection for Connection {}
+//! # let conn = &Connection;
+//! // On boot, for every dispatch
+//! set_default_event_limit(EventLimit::warn(1_000));
+//!
+//! // Or for a dispatch
+//! let context_manager = ContextManager::new(conn).with_event_limit(EventLimit::reject(10_000));
+//! ```
+//! On exceeding, the originating command and the topic of the event are logged. [EventLimit::reject] also fails the commit
+//! that would go over the limit with `BaseError::EventLimitExceeded`.
+use std::sync::OnceLock;
+
+use super::contexts::{Context, ContextManager};
+use crate::prelude::BaseError;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct EventLimit {
+	pub max: usize,
+	pub reject: bool,
+}
+
+impl EventLimit {
+	/// Only log warning on exceeding
+	pub fn warn(max: usize) -> Self {
+		Self { max, reject: false }
+	}
+
+	/// Fail the commit that would exceed
+	pub fn reject(max: usize) -> Self {
+		Self { max, reject: true }
+	}
+}
+
+static DEFAULT_EVENT_LIMIT: OnceLock<EventLimit> = OnceLock::new();
+
+/// ## Panics
+/// If default event limit is already set.
+pub fn set_default_event_limit(limit: EventLimit) {
+	if DEFAULT_EVENT_LIMIT.set(limit).is_err() {
+		panic!("Default Event Limit Is Already Set!");
+	}
+}
+
+pub(crate) fn default_event_limit() -> Option<EventLimit> {
+	DEFAULT_EVENT_LIMIT.get().copied()
+}
+
+impl ContextManager {
+	pub fn with_event_limit(mut self, limit: EventLimit) -> Self {
+		self.event_limit = Some(limit);
+		self
+	}
+
+	/// Number of internally notifiable events enqueued in this dispatch so far
+	pub fn enqueued_events(&self) -> usize {
+		self.enqueued.load(std::sync::atomic::Ordering::Relaxed)
+	}
+}
+
+impl Context {
+	/// Check whether enqueuing internally notifiable events of this context goes over the limit of the dispatch.
+	pub fn check_event_limit(&self) -> Result<(), BaseError> {
+		let Some(limit) = self.super_ctx.event_limit else {
+			return Ok(());
+		};
+		let raised = self.curr_events.iter().filter(|e| e.internally_notifiable()).collect::<Vec<_>>();
+		let total = self.super_ctx.enqueued_events() + raised.len();
+		if total <= limit.max {
+			return Ok(());
+		}
+
+		let command = self.super_ctx.command;
+		let topic = raised.first().map(|e| e.metadata().topic).unwrap_or_default();
+		if limit.reject {
+			tracing::error!(command, topic, total, limit = limit.max, "Event Limit Exceeded! Commit is rejected.");
+			return Err(BaseError::EventLimitExceeded {
+				command: command.to_string(),
+				limit: limit.max,
+			});
+		}
+		tracing::warn!(command, topic, total, limit = limit.max, "Event Limit Exceeded!");
+		Ok(())
+	}
+}
+
+#[tokio::test]
+async fn test_event_limit() {
+	use super::executor::TConnection;
+	use crate::prelude::TEvent;
+	use std::sync::Arc;
+
+	struct Connection;
+	impl TConnection for Connection {}
+	struct Raised;
+	impl TEvent for Raised {
+		fn internally_notifiable(&self) -> bool {
+			true
+		}
+		fn state(&self) -> String {
+			"{}".into()
+		}
+	}
+
+	let context_manager = Arc::new(ContextManager::new(&Connection).with_event_limit(EventLimit::reject(2)));
+	let mut ctx = Context::new(Arc::clone(&context_manager));
+	ctx.raise_all((0..2).map(|_| Arc::new(Raised) as Arc<dyn TEvent>));
+	assert!(ctx.check_event_limit().is_ok());
+	ctx.send_internally_notifiable_messages().await;
+	assert_eq!(context_manager.enqueued_events(), 2);
+
+	let mut cascaded = Context::new(Arc::clone(&context_manager));
+	cascaded.raise(Raised);
+	assert!(matches!(cascaded.check_event_limit(), Err(BaseError::EventLimitExceeded { limit: 2, .. })));
+
+	let warned = Arc::new(ContextManager::new(&Connection).with_event_limit(EventLimit::warn(0)));
+	let mut ctx = Context::new(warned);
+	ctx.raise(Raised);
+	assert!(ctx.check_event_limit().is_ok());
+}
@@ -0,0 +1,201 @@
+//! ### Single-threaded bus
+//! [LocalMessageBus] is for short-lived, single-threaded environments such as CLI tools and AWS Lambda.
+//! Nothing is spawned and nothing is shared across threads, so command services, event handlers and dependencies
+//! don't need to be `Send + Sync` - `Rc`-based cache or `!Send` SDK client can be used as they are.
+//!
+//! ```rust,ignore
+//! let bus = LocalMessageBus::<ServiceError>::new().on(|event: OrderPlaced, ctx: Rc<LocalContextManager>| async move {
+//!     ctx.resolve::<RefCell<LruCache<i64, Order>>>().borrow_mut().pop(&event.order_id);
+//!     Ok(())
+//! });
+//!
+//! let context_manager = LocalContextManager::new(conn).with_dependency(Rc::new(RefCell::new(LruCache::new(100))));
+//! let res = bus.execute(context_manager, |ctx| PlaceOrderService(cmd, ctx)).await?;
+//! ```
+//! Events raised with [LocalContextManager::raise] are handled one by one, in order, after the command service returns.
+//! There is no outbox, so externally notifiable events are not published - publish them from the command service if needed.
+//! Runs on any executor, including `tokio` current-thread runtime.
+use std::any::{Any, TypeId};
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::future::Future;
+use std::pin::Pin;
+use std::rc::Rc;
+use std::sync::Arc;
+
+use super::actor::Actor;
+use super::executor::TConnection;
+use super::messagebus::{missing_handler_policy, MissingHandlerPolicy};
+use crate::prelude::{ApplicationError, BaseError, TEvent, TTopic};
+
+pub type LocalFuture<T> = Pin<Box<dyn Future<Output = T>>>;
+
+type LocalHandler<E> = Box<dyn Fn(Arc<dyn TEvent>, Rc<LocalContextManager>) -> LocalFuture<Result<(), E>>>;
+
+/// Context of dispatch on [LocalMessageBus]
+pub struct LocalContextManager {
+	pub conn: &'static dyn TConnection,
+	pub actor: Actor,
+	event_queue: RefCell<VecDeque<Arc<dyn TEvent>>>,
+	dependencies: RefCell<hashbrown::HashMap<TypeId, Box<dyn Any>>>,
+}
+
+impl LocalContextManager {
+	pub fn new(conn: &'static dyn TConnection) -> Self {
+		Self {
+			conn,
+			actor: Actor::default(),
+			event_queue: Default::default(),
+			dependencies: Default::default(),
+		}
+	}
+
+	pub fn with_actor(mut self, actor: Actor) -> Self {
+		self.actor = actor;
+		self
+	}
+
+	pub fn with_dependency<T: ?Sized + 'static>(self, dependency: Rc<T>) -> Self {
+		self.dependencies.borrow_mut().insert(TypeId::of::<T>(), Box::new(dependency));
+		self
+	}
+
+	/// ## Panics
+	/// If `T` is not given to this context manager. Process-wide dependencies are not looked up as they are `Send + Sync`.
+	pub fn resolve<T: ?Sized + 'static>(&self) -> Rc<T> {
+		match self.dependencies.borrow().get(&TypeId::of::<T>()) {
+			Some(dependency) => dependency.downcast_ref::<Rc<T>>().expect("Type Mismatch!").clone(),
+			None => panic!("Dependency {} Is Not Given!", std::any::type_name::<T>()),
+		}
+	}
+
+	/// Put internally notifiable event on the queue. Other events are ignored, and externally notifiable one is logged
+	/// as error since it is not published.
+	pub fn raise(&self, event: impl TEvent + 'static) {
+		if event.externally_notifiable() {
+			tracing::error!("{} is externally notifiable, but LocalMessageBus has no outbox. It is not published!", event.metadata().topic);
+		}
+		if event.internally_notifiable() {
+			self.event_queue.borrow_mut().push_back(Arc::new(event));
+		}
+	}
+
+	/// Events waiting in the queue
+	pub fn len(&self) -> usize {
+		self.event_queue.borrow().len()
+	}
+
+	pub fn is_empty(&self) -> bool {
+		self.event_queue.borrow().is_empty()
+	}
+}
+
+/// Command service of [LocalMessageBus]. Same as `TCommandService` but without `Send + Sync`.
+pub trait TLocalCommandService<R, E> {
+	fn execute(self) -> impl Future<Output = Result<R, E>>;
+}
+
+pub struct LocalMessageBus<E> {
+	handlers: hashbrown::HashMap<String, Vec<LocalHandler<E>>>,
+}
+
+impl<E> Default for LocalMessageBus<E> {
+	fn default() -> Self {
+		Self { handlers: Default::default() }
+	}
+}
+
+impl<E: ApplicationError + std::convert::From<BaseError>> LocalMessageBus<E> {
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	/// Add handler of `T`. Handlers of the same event run in the order they are added.
+	pub fn on<T, F, Fut>(mut self, handler: F) -> Self
+	where
+		T: TEvent + TTopic + Clone + 'static,
+		F: Fn(T, Rc<LocalContextManager>) -> Fut + 'static,
+		Fut: Future<Output = Result<(), E>> + 'static,
+	{
+		self.handlers.entry(T::TOPIC.to_string()).or_default().push(Box::new(move |event, context_manager| {
+			Box::pin(handler(event.downcast_ref::<T>().expect("Not Convertible!").clone(), context_manager))
+		}));
+		self
+	}
+
+	/// Run the command service made by `make_service`, then handle the events it raised.
+	pub async fn execute<R, S>(&self, context_manager: LocalContextManager, make_service: impl FnOnce(Rc<LocalContextManager>) -> S) -> Result<R, E>
+	where
+		S: TLocalCommandService<R, E>,
+	{
+		let context_manager = Rc::new(context_manager);
+		let res = make_service(Rc::clone(&context_manager)).execute().await?;
+		self.handle_events(&context_manager).await?;
+		Ok(res)
+	}
+
+	/// Handle events in the queue, including the ones raised by handlers, until it is empty.
+	/// Failure of handler is logged and the rest go on, as on `MessageBus`.
+	pub async fn handle_events(&self, context_manager: &Rc<LocalContextManager>) -> Result<(), E> {
+		loop {
+			// Borrow must not be held while handlers run, as they may raise events.
+			let Some(event) = context_manager.event_queue.borrow_mut().pop_front() else {
+				return Ok(());
+			};
+			let topic = event.metadata().topic;
+			let Some(handlers) = self.handlers.get(&topic) else {
+				if missing_handler_policy() == MissingHandlerPolicy::Strict {
+					return Err(BaseError::HandlerNotFound(topic).into());
+				}
+				tracing::warn!("No Handler Registered For {}! Skipped.", topic);
+				continue;
+			};
+			for (i, handler) in handlers.iter().enumerate() {
+				if let Err(err) = handler(event.clone(), Rc::clone(context_manager)).await {
+					tracing::error!("Error Occurred While Handling {} In {i}th Handler! Error:{:?}", topic, err);
+				}
+			}
+		}
+	}
+}
+
+#[tokio::test(flavor = "current_thread")]
+async fn test_local_bus_runs_non_send_handlers() {
+	struct Connection;
+	impl TConnection for Connection {}
+
+	#[derive(Clone)]
+	struct ItemImported(i64);
+	impl TEvent for ItemImported {
+		fn internally_notifiable(&self) -> bool {
+			true
+		}
+		fn state(&self) -> String {
+			self.0.to_string()
+		}
+	}
+	impl TTopic for ItemImported {
+		const TOPIC: &'static str = "ItemImported";
+	}
+
+	// `Rc<RefCell<_>>` is neither `Send` nor `Sync`
+	type Cache = RefCell<Vec<i64>>;
+	struct ImportService(Vec<i64>, Rc<LocalContextManager>);
+	impl TLocalCommandService<usize, BaseError> for ImportService {
+		async fn execute(self) -> Result<usize, BaseError> {
+			self.0.iter().for_each(|id| self.1.raise(ItemImported(*id)));
+			Ok(self.0.len())
+		}
+	}
+
+	let bus = LocalMessageBus::<BaseError>::new().on(|event: ItemImported, ctx: Rc<LocalContextManager>| async move {
+		ctx.resolve::<Cache>().borrow_mut().push(event.0);
+		Ok(())
+	});
+	let cache = Rc::new(Cache::default());
+	let context_manager = LocalContextManager::new(&Connection).with_dependency(cache.clone());
+
+	let res = bus.execute(context_manager, |ctx| ImportService(vec![1, 2, 3], ctx)).await.unwrap();
+	assert_eq!(res, 3);
+	assert_eq!(*cache.borrow(), vec![1, 2, 3]);
+}
@@ -0,0 +1,72 @@
+//! ### Memoized dependencies
+//! Event handler struct is constructed by `init_event_handler!` on every event. Dependencies that are expensive to build,
+//! such as HTTP clients or template engines, can be memoized instead of being rebuilt each time.
+//!
+//! ```rust,ignore
+//! init_event_handler!(
+//!     ServiceError,
+//!     |ctx: AtomicContextManager| NotificationHandler {
+//!         // Built once per process. Only for stateless dependencies.
+//!         mailer: process_shared(|| Mailer::new(&CONFIG.smtp_url)),
+//!         // Built once per dispatch and shared by handlers of all events the command raised.
+//!         templates: ctx.memoized(|| Templates::load(&ctx.actor)),
+//!         ctx,
+//!     },
+//!     OrderPlaced: [send_receipt],
+//! );
+//! ```
+//! Dependencies are keyed by type, so wrap them in a newtype if two instances of the same type are needed.
+use std::any::{Any, TypeId};
+use std::sync::{Arc, LazyLock, RwLock};
+
+use super::contexts::ContextManager;
+
+static PROCESS_SHARED: LazyLock<RwLock<hashbrown::HashMap<TypeId, &'static (dyn Any + Send + Sync)>>> = LazyLock::new(Default::default);
+
+/// Get `T` built once per process, building it with `init` on first call.
+pub fn process_shared<T: Send + Sync + 'static>(init: impl FnOnce() -> T) -> &'static T {
+	if let Some(shared) = PROCESS_SHARED.read().unwrap().get(&TypeId::of::<T>()) {
+		return shared.downcast_ref::<T>().expect("Type Mismatch!");
+	}
+	let mut map = PROCESS_SHARED.write().unwrap();
+	let shared = *map.entry(TypeId::of::<T>()).or_insert_with(|| Box::leak(Box::new(init())));
+	shared.downcast_ref::<T>().expect("Type Mismatch!")
+}
+
+pub(crate) type Memo = std::sync::Mutex<hashbrown::HashMap<TypeId, Arc<dyn Any + Send + Sync>>>;
+
+impl ContextManager {
+	/// Get `T` built once per dispatch, building it with `init` on first call.
+	/// In record-only handler, the recorder of `T` is returned instead. See [handler_sandbox](super::sandbox::handler_sandbox).
+	pub fn memoized<T: Send + Sync + 'static>(&self, init: impl FnOnce() -> T) -> Arc<T> {
+		if let Some(recorder) = super::sandbox::handler_sandbox().recorder::<T>() {
+			return recorder;
+		}
+		let mut memo = self.memo.lock().unwrap();
+		let memoized = memo.entry(TypeId::of::<T>()).or_insert_with(|| Arc::new(init())).clone();
+		memoized.downcast::<T>().expect("Type Mismatch!")
+	}
+}
+
+#[test]
+fn test_memoized_dependencies() {
+	use super::executor::TConnection;
+	use std::sync::atomic::{AtomicUsize, Ordering};
+
+	struct Connection;
+	impl TConnection for Connection {}
+	struct Client(usize);
+	static BUILT: AtomicUsize = AtomicUsize::new(0);
+	let build = || Client(BUILT.fetch_add(1, Ordering::SeqCst));
+
+	let first = process_shared(build);
+	let second = process_shared(build);
+	assert!(std::ptr::eq(first, second));
+
+	let context_manager = ContextManager::new(&Connection);
+	let first = context_manager.memoized(build);
+	let second = context_manager.memoized(build);
+	assert!(Arc::ptr_eq(&first, &second));
+	assert_ne!(ContextManager::new(&Connection).memoized(build).0, first.0);
+	assert_eq!(BUILT.load(Ordering::SeqCst), 3);
+}
@@ -1,38 +1,137 @@
 //! # Message Bus
 //! ### example
-//! ```rust,no_run
-//! impl ruva::TMessageBus<YourResponse,YourErrorError,YourCommand> for MessageBus{
-//!     fn command_handler(
-//!         &self,
-//!         context_manager: ruva::AtomicContextManager,
-//!         cmd: YourCommand,
-//!
-//!     ) -> impl ruva::TCommandService<YourResponse, YourError> {
-//!             LoggingAspect(
-//!                 UnitOfWorkHandler::new(
-//!                 ::ruva::SqlRepository::new(context_manager),
-//!                 cmd
-//!             )
-//!       )
+//! ```rust
+//! # use ruva_core::prelude::*;
+//! # #[derive(Debug)]
+//! # struct MakeOrder;
+//! # impl TCommand for MakeOrder {}
+//! # impl TCommandSpec for MakeOrder {
+//! #     type Response = ();
+//! #     type Error = BaseError;
+//! # }
+//! # struct LoggingAspect<S>(S);
+//! # impl<S: TCommandService<(), BaseError>> TCommandService<(), BaseError> for LoggingAspect<S> {
+//! #     async fn execute(self) -> Result<(), BaseError> { self.0.execute().await }
+//! # }
+//! # struct MakeOrderHandler(MakeOrder, Context);
+//! # impl TCommandService<(), BaseError> for MakeOrderHandler {
+//! #     async fn execute(self) -> Result<(), BaseError> { Ok(()) }
+//! # }
+//! impl TCommandRoute for MakeOrder {
+//!     fn command_handler(context_manager: AtomicContextManager, cmd: Self) -> impl TCommandService<Self::Response, Self::Error> {
+//!         LoggingAspect(MakeOrderHandler(cmd, Context::new(context_manager)))
 //!     }
 //! }
 //! ```
+//! `MessageBus` serves every command implementing [TCommandRoute] through the blanket implementation of [TMessageBus].
+//! `register_uow_services!` implements it for you.
+//! Aspects can also be composed with [ServiceBuilder](super::layer::ServiceBuilder) instead of nesting them by hand.
 
+use super::analytics::{record_command, record_event};
+use super::cache::invalidate_cached_responses;
+use super::concurrency::acquire_concurrency_permit;
+use super::consistency::ConsistencyToken;
 use super::contexts::*;
+use super::dead_letter::dead_letter;
+use super::enrich::enrich_command;
 use super::executor::TConnection;
-use super::handler::EventHandlers;
-use crate::prelude::{TCommand, TEvent};
+use super::handler::{run_handler_group, EventHandlers};
+use super::import::{ImportOptions, ImportProgress};
+use super::observer::notify;
+use super::preflight::{check_pending_topics, PreflightReport};
+use super::propagation::{command_span, handler_span};
+use super::shutdown::bus_shutdown_token;
+use super::stats::UowStats;
+use crate::prelude::{TCommand, TCommandSpec, TEvent};
 use crate::responses::{self, ApplicationError, ApplicationResponse, BaseError};
 use async_recursion::async_recursion;
 use async_trait::async_trait;
 use std::sync::Arc;
+use tracing::Instrument;
+
+/// Hasher of the event handler map, looked up on every event. aHash by default, foldhash with `foldhash` feature.
+#[cfg(not(feature = "foldhash"))]
+pub type HandlerHasher = hashbrown::hash_map::DefaultHashBuilder;
+#[cfg(feature = "foldhash")]
+pub type HandlerHasher = foldhash::fast::RandomState;
 
 /// Event handlers `TEventBus` work on
-pub type TEventHandler<E> = hashbrown::HashMap<String, EventHandlers<E>>;
+pub type TEventHandler<E> = hashbrown::HashMap<String, EventHandlers<E>, HandlerHasher>;
 
 #[async_trait]
 pub trait TEventBus<E> {
 	fn event_handler(&self) -> &'static TEventHandler<E>;
+
+	/// Report topics of in-flight messages that have no registered handler.
+	fn preflight(&self, pending_topics: Vec<String>) -> PreflightReport
+	where
+		E: 'static,
+	{
+		check_pending_topics(self.event_handler(), pending_topics)
+	}
+
+	/// Run handlers of internally notifiable `events` outside command dispatch, for example, from background job.
+	async fn handle_events(&self, events: Vec<Arc<dyn TEvent>>, context_manager: ContextManager) -> Result<(), E>
+	where
+		E: ApplicationError + std::convert::From<crate::responses::BaseError>,
+		crate::responses::BaseError: std::convert::From<E>,
+	{
+		let mut context_manager = context_manager;
+		context_manager.extend(events.into_iter().filter(|e| e.internally_notifiable()));
+		let context_manager = Arc::new(context_manager);
+		if let Some(event) = context_manager.get_mut().pop_front() {
+			handle_event(event, context_manager, self.event_handler()).await?;
+		}
+		Ok(())
+	}
+
+	/// Feed historical `events`, each with its id, through the handlers batch by batch in the order of the ids. See [ImportOptions].
+	async fn import_events(&self, events: Vec<(i64, Arc<dyn TEvent>)>, options: ImportOptions) -> Result<ImportProgress, E>
+	where
+		E: ApplicationError + std::convert::From<crate::responses::BaseError>,
+		crate::responses::BaseError: std::convert::From<E>,
+	{
+		let mut events = events;
+		events.sort_by_key(|(id, _)| *id);
+		let mut progress = ImportProgress {
+			name: options.name.clone(),
+			imported: 0,
+			total: events.len(),
+			finished: false,
+		};
+		if let Some(store) = options.checkpoint.as_ref() {
+			if let Some(watermark) = store.load(&options.name).await? {
+				progress.imported = events.partition_point(|(id, _)| *id <= watermark);
+			}
+		}
+		tracing::info!("Import {} started from {}", options.name, progress.imported);
+
+		let mut batches = events[progress.imported..].chunks(options.batch_size).peekable();
+		while let Some(batch) = batches.next() {
+			let mut context_manager = options.context_manager();
+			context_manager.extend(batch.iter().map(|(_, event)| event.clone()));
+			let context_manager = Arc::new(context_manager);
+			if let Some(event) = context_manager.get_mut().pop_front() {
+				// Batch that fails is not checkpointed, to be imported again
+				if let Err(err) = handle_event(event, context_manager, self.event_handler()).await {
+					tracing::error!("Import {} failed after {} events! {:?}", options.name, progress.imported, err);
+					return Err(err);
+				}
+			}
+
+			progress.imported += batch.len();
+			if let (Some(store), Some((watermark, _))) = (options.checkpoint.as_ref(), batch.last()) {
+				store.save(&options.name, *watermark).await?;
+			}
+			progress.finished = batches.peek().is_none();
+			options.report(&progress);
+			if let (Some(delay), false) = (options.rate_limit, progress.finished) {
+				tokio::time::sleep(delay).await;
+			}
+		}
+		progress.finished = true;
+		Ok(progress)
+	}
 }
 
 /// This function is used to handle event. It is called recursively until there is no event left in the queue.
@@ -48,15 +147,30 @@ where
 		tracing::info!("Processing {}...", msg.metadata().topic);
 	}
 
-	let handlers = event_handler.get(&msg.metadata().topic).ok_or_else(|| {
-		tracing::error!("Unprocessable Event Given! {:?}", msg);
-		BaseError::NotFound
-	})?;
+	let topic = msg.metadata().topic;
+	context_manager.start_event(&msg);
+	notify(|o| o.event_dequeued(&topic, context_manager.len()));
+	let Some(handlers) = context_manager.resolve_handlers(&topic, event_handler) else {
+		if context_manager.missing_handler_policy == MissingHandlerPolicy::Strict {
+			tracing::error!("Unprocessable Event Given! {:?}", msg);
+			return Err(BaseError::HandlerNotFound(topic).into());
+		}
+		tracing::warn!("No Handler Registered For {}! Skipped.", topic);
+		invalidate_cached_responses(&topic).await;
+		return handle_next_event(context_manager, event_handler).await;
+	};
 
+	let handling_started = std::time::Instant::now();
+	let mut succeeded = true;
 	match handlers {
 		EventHandlers::Sync(h) => {
 			for (i, handler) in h.iter().enumerate() {
-				if let Err(err) = handler(msg.clone(), Arc::clone(&context_manager)).await {
+				let started = std::time::Instant::now();
+				let res = handler(msg.clone(), Arc::clone(&context_manager)).instrument(handler_span(&topic, i)).await;
+				notify(|o| o.handler_finished(&topic, i, started.elapsed(), res.is_ok()));
+				report_progress(&context_manager, &topic, i, res.is_ok());
+				succeeded &= res.is_ok();
+				if let Err(err) = res {
 					// ! Safety:: BaseError Must Be Enforced To Be Accepted As Variant On ServiceError
 					match err.into() {
 						BaseError::StopSentinel => {
@@ -67,31 +181,111 @@ where
 						BaseError::StopSentinelWithEvent(event) => {
 							let error_msg = format!("Stop Sentinel With Event Arrived In {i}th Event!");
 							crate::backtrace_error!("{}", error_msg);
+							notify(|o| o.event_enqueued(&event.metadata().topic));
 							context_manager.get_mut().push_back(event);
 							break;
 						}
 						err => {
 							let error_msg = format!("Error Occurred While Handling Event In {i}th Event! Error:{:?}", err);
 							crate::backtrace_error!("{}", error_msg);
+							if context_manager.propagate_handler_errors {
+								return Err(err.into());
+							}
+							dead_letter(&context_manager, &topic, i, msg.as_ref(), &err).await;
 						}
 					}
 				}
 			}
 		}
+		EventHandlers::Batch { handlers, max_batch_size } => {
+			// * Micro batching - consecutive events of the same topic are taken from the queue up to `max_batch_size`.
+			// * Events raised by the handlers have the first event of the batch as their cause.
+			let mut events = vec![msg.clone()];
+			while events.len() < *max_batch_size {
+				match context_manager.front() {
+					Some(next) if next.metadata().topic == topic => events.push(context_manager.get_mut().pop_front().unwrap()),
+					_ => break,
+				}
+			}
+			for (i, handler) in handlers.iter().enumerate() {
+				let started = std::time::Instant::now();
+				let res = handler(events.clone(), Arc::clone(&context_manager)).instrument(handler_span(&topic, i)).await;
+				notify(|o| o.handler_finished(&topic, i, started.elapsed(), res.is_ok()));
+				report_progress(&context_manager, &topic, i, res.is_ok());
+				succeeded &= res.is_ok();
+				if let Err(err) = res {
+					let err = Into::<BaseError>::into(err);
+					let error_msg = format!("Error Occurred While Handling Event Batch In {i}th Handler! Error:{:?}", err);
+					crate::backtrace_error!("{}", error_msg);
+					if context_manager.propagate_handler_errors {
+						return Err(err.into());
+					}
+					for event in events.iter() {
+						dead_letter(&context_manager, &topic, i, event.as_ref(), &err).await;
+					}
+				}
+			}
+		}
 		EventHandlers::Async(h) => {
-			let futures = h.iter().map(|handler| handler(msg.clone(), Arc::clone(&context_manager)));
-			if let Err(err) = futures::future::try_join_all(futures).await {
-				let error_msg = format!("Error Occurred While Handling Event! Error:{:?}", err);
-				crate::backtrace_error!("{}", error_msg);
+			let futures = h
+				.iter()
+				.enumerate()
+				.map(|(i, handler)| {
+					let started = std::time::Instant::now();
+					let future = handler(msg.clone(), Arc::clone(&context_manager)).instrument(handler_span(&topic, i));
+					let topic = topic.clone();
+					let context_manager = Arc::clone(&context_manager);
+					Box::pin(async move {
+						let res = future.await;
+						notify(|o| o.handler_finished(&topic, i, started.elapsed(), res.is_ok()));
+						report_progress(&context_manager, &topic, i, res.is_ok());
+						res
+					}) as crate::prelude::Future<E>
+				})
+				.collect();
+			for (i, res) in run_handler_group(futures, context_manager.async_failure_policy, bus_shutdown_token()).await.into_iter().enumerate() {
+				succeeded &= matches!(res, Some(Ok(())));
+				match res {
+					Some(Ok(())) => {}
+					Some(Err(err)) => {
+						let err = Into::<BaseError>::into(err);
+						let error_msg = format!("Error Occurred While Handling Event In {i}th Async Handler! Error:{:?}", err);
+						crate::backtrace_error!("{}", error_msg);
+						if context_manager.propagate_handler_errors {
+							return Err(err.into());
+						}
+						dead_letter(&context_manager, &topic, i, msg.as_ref(), &err).await;
+					}
+					None => tracing::warn!("{i}th Async Handler Of {} Didn't Finish", topic),
+				}
 			}
 		}
 	}
+	// * After the handlers, so that response cached while they update read models is not left stale
+	invalidate_cached_responses(&topic).await;
+	record_event(&topic, context_manager.tenant.as_deref(), handling_started.elapsed(), succeeded);
+
+	handle_next_event(context_manager, event_handler).await
+}
+
+pub(crate) async fn handle_next_event<E>(context_manager: AtomicContextManager, event_handler: &'static TEventHandler<E>) -> Result<AtomicContextManager, E>
+where
+	E: ApplicationError + std::convert::From<crate::responses::BaseError> + std::convert::From<E>,
+	crate::responses::BaseError: std::convert::From<E>,
+{
+	if bus_shutdown_token().is_shutdown() {
+		tracing::warn!("Shutdown requested. {} events left in the queue are not processed.", context_manager.len());
+		return Ok(context_manager);
+	}
 
 	// Resursive case
 	let incoming_event = context_manager.get_mut().pop_front();
 
 	if let Some(event) = incoming_event {
 		if let Err(err) = handle_event(event, Arc::clone(&context_manager), event_handler).await {
+			if context_manager.propagate_handler_errors {
+				return Err(err);
+			}
 			// ! Safety:: BaseError Must Be Enforced To Be Accepted As Variant On ServiceError
 			tracing::error!("{:?}", err);
 		}
@@ -99,6 +293,41 @@ where
 	Ok(context_manager)
 }
 
+/// What to do with internally notifiable event that has no handler registered
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum MissingHandlerPolicy {
+	/// Log warning and go on with the next event in the queue
+	#[default]
+	WarnAndSkip,
+	/// Fail with `BaseError::HandlerNotFound`, leaving the rest of the queue unprocessed
+	Strict,
+}
+
+static STRICT_MISSING_HANDLER: std::sync::atomic::AtomicBool = std::sync::atomic::AtomicBool::new(false);
+
+pub fn set_missing_handler_policy(policy: MissingHandlerPolicy) {
+	STRICT_MISSING_HANDLER.store(policy == MissingHandlerPolicy::Strict, std::sync::atomic::Ordering::Relaxed);
+}
+
+pub fn missing_handler_policy() -> MissingHandlerPolicy {
+	match STRICT_MISSING_HANDLER.load(std::sync::atomic::Ordering::Relaxed) {
+		true => MissingHandlerPolicy::Strict,
+		false => MissingHandlerPolicy::WarnAndSkip,
+	}
+}
+
+fn report_progress(context_manager: &AtomicContextManager, topic: &str, index: usize, succeeded: bool) {
+	if let Some(progress) = context_manager.progress.as_ref() {
+		// Error only means that there is no receiver at the moment
+		let _ = progress.send(EventProgress::HandlerFinished {
+			topic: topic.to_string(),
+			index,
+			succeeded,
+			queued: context_manager.len(),
+		});
+	}
+}
+
 /// Interface for messagebus to work on
 pub trait TCommandService<R, E>: Send + Sync {
 	fn execute(self) -> impl std::future::Future<Output = Result<R, E>> + Send;
@@ -116,52 +345,198 @@ where
 
 	/// This method is used to handle command and return result.
 	/// ## Example
-	/// ```rust,no_run
-	/// let res = service.execute_and_wait(message).await?;
+	/// ```rust
+	/// # use ruva_core::prelude::*;
+	/// # async fn example<R, E, C>(service: impl TMessageBus<R, E, C> + Sync, message: C, conn: &'static dyn TConnection) -> Result<(), E>
+	/// # where BaseError: From<E>, R: ApplicationResponse, E: ApplicationError + From<BaseError>, C: TCommand {
+	/// let res = service.execute_and_wait(message, conn).await?;
+	/// # Ok(())
+	/// # }
 	/// ```
-
 	async fn execute_and_wait(&self, message: C, conn: &'static dyn TConnection) -> Result<R, E> {
+		self.execute_and_wait_with(message, ContextManager::new(conn)).await
+	}
+
+	/// Same as `execute_and_wait` but with context manager prepared by caller, for example, with [Actor](crate::prelude::Actor) set.
+	/// ## Example
+	/// ```rust
+	/// # use ruva_core::prelude::*;
+	/// # async fn example<R, E, C>(service: impl TMessageBus<R, E, C> + Sync, message: C, conn: &'static dyn TConnection) -> Result<(), E>
+	/// # where BaseError: From<E>, R: ApplicationResponse, E: ApplicationError + From<BaseError>, C: TCommand {
+	/// let context_manager = ContextManager::new(conn).with_actor(Actor::System("scheduler".into()));
+	/// let res = service.execute_and_wait_with(message, context_manager).await?;
+	/// # Ok(())
+	/// # }
+	/// ```
+	async fn execute_and_wait_with(&self, message: C, context_manager: ContextManager) -> Result<R, E> {
 		#[cfg(feature = "tracing")]
 		{
 			tracing::info!("{}", std::any::type_name::<C>());
 		}
 
-		let context_manager = Arc::new(ContextManager::new(conn));
-		let res = self.command_handler(Arc::clone(&context_manager), message).execute().await?;
+		let command = std::any::type_name::<C>();
+		let permit = acquire_concurrency_permit(command)?;
+		let started = std::time::Instant::now();
+		notify(|o| o.command_started(command));
+		let context_manager = Arc::new(ContextManager { command, ..context_manager }.start_command());
+		let span = command_span(command);
+		let res = async {
+			let message = enrich_command(message, &context_manager).await?;
+			self.command_handler(Arc::clone(&context_manager), message).execute().await
+		}
+		.instrument(span.clone())
+		.await;
+		drop(permit);
+		notify(|o| o.command_finished(command, started.elapsed(), res.is_ok()));
+		record_command(command, context_manager.tenant.as_deref(), started.elapsed(), res.is_ok());
+		let stats = context_manager.stats();
+		notify(|o| o.dispatch_stats(command, &stats));
+		let res = res?;
+		if context_manager.dry_run {
+			tracing::info!("Dry run of {} is done. {} events are not handled.", command, context_manager.len());
+			return Ok(res);
+		}
 
 		// Trigger event handler
 		if !context_manager.event_queue.is_empty() {
 			let event = context_manager.get_mut().pop_front();
-			handle_event(event.unwrap(), Arc::clone(&context_manager), self.event_handler()).await?;
+			handle_event(event.unwrap(), Arc::clone(&context_manager), self.event_handler()).instrument(span).await?;
 		}
 		Ok(res)
 	}
 
 	/// This method is used to handle command and return result proxy which holds the result and join handler.
 	/// ## Example
-	/// ```rust,no_run
-	/// let res = service.execute_and_forget(message).await?;
+	/// ```rust
+	/// # use ruva_core::prelude::*;
+	/// # async fn example<R, E, C>(service: impl TMessageBus<R, E, C> + Sync, message: C, conn: &'static dyn TConnection) -> Result<(), E>
+	/// # where BaseError: From<E>, R: ApplicationResponse, E: ApplicationError + From<BaseError>, C: TCommand {
+	/// let res = service.execute_and_forget(message, conn).await?;
 	/// let res = res.wait_until_event_processing_done().await?;
 	/// let res = res.result();
+	/// # Ok(())
+	/// # }
 	/// ```
 	async fn execute_and_forget(&self, message: C, conn: &'static dyn TConnection) -> Result<CommandResponseWithEventFutures<R, E>, E> {
+		self.execute_and_forget_with(message, ContextManager::new(conn)).await
+	}
+
+	/// Same as `execute_and_forget` but with context manager prepared by caller.
+	async fn execute_and_forget_with(&self, message: C, context_manager: ContextManager) -> Result<CommandResponseWithEventFutures<R, E>, E> {
 		#[cfg(feature = "tracing")]
 		{
 			tracing::info!("{}", std::any::type_name::<C>());
 		}
 
-		let context_manager = Arc::new(ContextManager::new(conn));
-		let res = self.command_handler(Arc::clone(&context_manager), message).execute().await?;
+		let command = std::any::type_name::<C>();
+		let permit = acquire_concurrency_permit(command)?;
+		let started = std::time::Instant::now();
+		notify(|o| o.command_started(command));
+		let context_manager = Arc::new(ContextManager { command, ..context_manager }.start_command());
+		let span = command_span(command);
+		let res = async {
+			let message = enrich_command(message, &context_manager).await?;
+			self.command_handler(Arc::clone(&context_manager), message).execute().await
+		}
+		.instrument(span.clone())
+		.await;
+		drop(permit);
+		notify(|o| o.command_finished(command, started.elapsed(), res.is_ok()));
+		record_command(command, context_manager.tenant.as_deref(), started.elapsed(), res.is_ok());
+		let stats = context_manager.stats();
+		notify(|o| o.dispatch_stats(command, &stats));
+		let res = res?;
 		let mut res = CommandResponseWithEventFutures { result: res, join_handler: None };
+		if context_manager.dry_run {
+			tracing::info!("Dry run of {} is done. {} events are not handled.", command, context_manager.len());
+			return Ok(res);
+		}
 
 		// Trigger event handler
+		let progress = context_manager.progress.clone();
 		if !context_manager.event_queue.is_empty() {
 			let event = context_manager.get_mut().pop_front().unwrap();
+			let event_handler = self.event_handler();
 
-			res.join_handler = Some(tokio::spawn(handle_event(event, context_manager, self.event_handler())));
+			res.join_handler = Some(tokio::spawn(
+				async move {
+					let res = handle_event(event, context_manager, event_handler).await;
+					if let Some(progress) = progress {
+						let _ = progress.send(EventProgress::Done);
+					}
+					res
+				}
+				.instrument(span),
+			));
+		} else if let Some(progress) = progress {
+			let _ = progress.send(EventProgress::Done);
 		}
 		Ok(res)
 	}
+
+	/// Same as `execute_and_forget` but with receiver that reports completion of each event handler,
+	/// so that web layer can stream progress of long-running cascade to clients(SSE, WebSocket).
+	/// ## Example
+	/// ```rust
+	/// # use ruva_core::prelude::*;
+	/// # async fn example<R, E, C>(service: impl TMessageBus<R, E, C> + Sync, message: C, conn: &'static dyn TConnection) -> Result<(), E>
+	/// # where BaseError: From<E>, R: ApplicationResponse, E: ApplicationError + From<BaseError>, C: TCommand {
+	/// # let sse = tokio::sync::mpsc::unbounded_channel().0;
+	/// let (res, mut progress) = service.execute_and_forget_with_progress(message, conn).await?;
+	/// while let Ok(progress) = progress.recv().await {
+	///     if let EventProgress::Done = progress {
+	///         break;
+	///     }
+	///     sse.send(progress);
+	/// }
+	/// # Ok(())
+	/// # }
+	/// ```
+	async fn execute_and_forget_with_progress(
+		&self,
+		message: C,
+		conn: &'static dyn TConnection,
+	) -> Result<(CommandResponseWithEventFutures<R, E>, tokio::sync::broadcast::Receiver<EventProgress>), E> {
+		let (sender, receiver) = tokio::sync::broadcast::channel(PROGRESS_CHANNEL_CAPACITY);
+		let res = self.execute_and_forget_with(message, ContextManager::new(conn).with_progress(sender)).await?;
+		Ok((res, receiver))
+	}
+}
+
+/// Route from command to its handler. `register_uow_services!` implements it for every registered command
+/// so that a single `MessageBus` serves all of them. Dispatching a command that is not registered is a compile error.
+#[diagnostic::on_unimplemented(message = "`{Self}` is not registered to the message bus", label = "register it with `register_uow_services!` or implement `TCommandRoute`")]
+pub trait TCommandRoute: TCommandSpec {
+	fn command_handler(context_manager: AtomicContextManager, cmd: Self) -> impl TCommandService<Self::Response, Self::Error>;
+}
+
+impl<C> TMessageBus<C::Response, C::Error, C> for MessageBus
+where
+	C: TCommandRoute,
+	C::Error: std::convert::From<BaseError>,
+	BaseError: std::convert::From<C::Error>,
+	MessageBus: TEventBus<C::Error>,
+{
+	fn command_handler(&self, context_manager: AtomicContextManager, cmd: C) -> impl TCommandService<C::Response, C::Error> {
+		C::command_handler(context_manager, cmd)
+	}
+}
+
+const PROGRESS_CHANNEL_CAPACITY: usize = 1024;
+
+/// Progress of event processing reported through the channel set by `ContextManager::with_progress`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum EventProgress {
+	HandlerFinished {
+		topic: String,
+		/// Position of the handler in the list registered for `topic`
+		index: usize,
+		succeeded: bool,
+		/// Number of events waiting in the queue
+		queued: usize,
+	},
+	/// Every event is processed
+	Done,
 }
 
 pub struct CommandResponseWithEventFutures<T, E> {
@@ -190,7 +565,7 @@ where
 
 /// This macro is used to create event handler for each event.
 /// ## Example
-/// ```rust,no_run
+/// ```rust,ignore
 ///
 /// init_event_handler!(
 ///     YourServiceError,
@@ -198,50 +573,48 @@ where
 ///     #[async]
 ///     YourEvent:[handler1, handler2],
 ///     YourEvent2:[handler3, handler4],
+///     // Consecutive `YourEvent3`s in the queue are handed over as `Vec<YourEvent3>`, at most 100 at a time.
+///     #[batch(100)]
+///     YourEvent3:[batch_handler],
+///     // Runs handler6 first regardless of declaration order. Orders must be constants unique within the list, or it fails to compile.
+///     // Handlers without order run after the ordered ones, in declaration order.
+///     YourEvent4:[handler5, #[order = 1] handler6],
 /// );
 /// ```
-///
-
+/// Listing an event, or a handler of an event, more than once fails to compile, naming the duplicate.
+/// Each handler can be disabled at runtime with `handler_toggles().disable("YourEvent", "handler1")`.
+/// It can also be switched to record-only mode with `handler_sandbox().record_only("YourEvent", "handler1")`,
+/// or verified against the handler it replaces with `handler_migrations().verify("YourEvent", "handler1", "handler2")`.
 #[macro_export]
 macro_rules! init_event_handler {
     (
 		$E:ty,
 		$event_handler :expr,
 			$(
-				$(#[$asynchrony:ident])?
-				$event:ty:[$($handler:ident $(=>($($injectable:ident $(( $($arg:ident),* ))? ),*))?),* $(,)? ]
+				$(#[$asynchrony:ident $(($batch_size:expr))?])?
+				$event:ty:[$($(#[order = $order:expr])? $handler:ident $(=>($($injectable:ident $(( $($arg:ident),* ))? ),*))?),* $(,)? ]
 			),*
 			$(,)?
 
     ) =>{
+		ruva::__assert_unique_registrations!(topic [$($event),*] "is listed more than once in `init_event_handler!`");
+		$(
+			ruva::__assert_unique_registrations!([$($handler),*] concat!("is listed more than once for `", stringify!($event), "` in `init_event_handler!`"));
+			ruva::__assert_unique_orders!([$(($($order)?)),*] concat!("Handler order is given more than once for `", stringify!($event), "` in `init_event_handler!`"));
+		)*
+
 		pub(crate) static EVENT_HANDLERS: std::sync::LazyLock<ruva::TEventHandler<$E>> = std::sync::LazyLock::new(
 			||{
-				let mut _map : ::ruva::TEventHandler<$E> = ::ruva::HandlerMapper::new();
+				// Topics are known at this point, so the map never grows.
+				let mut _map : ::ruva::TEventHandler<$E> = ::ruva::TEventHandler::with_capacity_and_hasher(
+					[$(stringify!($event)),*].len(),
+					::ruva::HandlerHasher::default()
+				);
 				$(
-
-				let mut handlers = if stringify!($($asynchrony)?) == "async" {
-					::ruva::EventHandlers::Async(vec![])
-				} else {
-					::ruva::EventHandlers::Sync(vec![])
-				};
-				handlers.extend(vec![
-					$(
-						Box::new(
-							|e: ::std::sync::Arc<dyn ::ruva::TEvent>, context_manager: ruva::AtomicContextManager | -> ::ruva::Future<$E> {
-								let event_handler = $event_handler(context_manager);
-								Box::pin(event_handler.$handler(
-									// * Convert event so event handler accepts not Arc<dyn TEvent> but `event_happend` type of message.
-									// Safety:: client should access this vector of handlers by providing the corresponding event name
-									// So, when it is followed, it logically doesn't make sense to cause an error.
-									e.downcast_ref::<$event>().expect("Not Convertible!").clone(),
-								))
-							}
-						),
-					)*
-				]);
                 _map.insert(
-                    stringify!($event).into(),
-					handlers
+                    // * Keyed by the topic constant so that renamed event fails to compile instead of going unhandled.
+                    <$event as ::ruva::TTopic>::TOPIC.into(),
+					ruva::__event_handlers_internal!($($asynchrony $(($batch_size))?)?; $E, $event_handler, $event, [$(($($order)?) $handler),*])
                 );
             )*
             _map
@@ -258,4 +631,304 @@ macro_rules! init_event_handler {
 
 }
 
+#[macro_export]
+#[doc(hidden)]
+macro_rules! __event_handlers_internal {
+	(batch($batch_size:expr); $E:ty, $event_handler:expr, $event:ty, [$(($($order:expr)?) $handler:ident),*]) => {{
+		$(::ruva::handler_toggles().register(stringify!($event), stringify!($handler));)*
+		::ruva::EventHandlers::Batch {
+			max_batch_size: $batch_size,
+			handlers: ::ruva::sort_by_order(stringify!($event), vec![
+				$(
+					(ruva::__handler_order!($($order)?), Box::new(
+						|events: ::std::vec::Vec<::std::sync::Arc<dyn ::ruva::TEvent>>, context_manager: ruva::AtomicContextManager| -> ::ruva::Future<$E> {
+							if !::ruva::handler_toggles().is_enabled(stringify!($event), stringify!($handler)) || !context_manager.is_handler_selected(stringify!($event), stringify!($handler)) {
+								return Box::pin(async { Ok(()) });
+							}
+							// * Message id of the first event of the batch
+							let event_key = context_manager.message_id().map(ToString::to_string);
+							// * Handler is built on first poll, so that dependencies it resolves on construction are recorded in the sandbox too.
+							::ruva::migrated(stringify!($event), stringify!($handler), event_key.as_deref(), ::ruva::sandboxed(stringify!($event), stringify!($handler), Box::pin(async move {
+								let event_handler = $event_handler(context_manager);
+								event_handler.$handler(
+									events.iter().map(|e| e.downcast_ref::<$event>().expect("Not Convertible!").clone()).collect::<::std::vec::Vec<$event>>(),
+								).await
+							})))
+						}
+					) as Box<dyn Fn(::std::vec::Vec<::std::sync::Arc<dyn ::ruva::TEvent>>, ruva::AtomicContextManager) -> ::ruva::Future<$E> + Send + Sync>),
+				)*
+			]),
+		}
+	}};
+	($($asynchrony:ident)?; $E:ty, $event_handler:expr, $event:ty, [$(($($order:expr)?) $handler:ident),*]) => {{
+		let mut handlers = if stringify!($($asynchrony)?) == "async" {
+			::ruva::EventHandlers::Async(vec![])
+		} else {
+			::ruva::EventHandlers::Sync(vec![])
+		};
+		$(::ruva::handler_toggles().register(stringify!($event), stringify!($handler));)*
+		handlers.extend(::ruva::sort_by_order(stringify!($event), vec![
+			$(
+				(ruva::__handler_order!($($order)?), Box::new(
+					|e: ::std::sync::Arc<dyn ::ruva::TEvent>, context_manager: ruva::AtomicContextManager | -> ::ruva::Future<$E> {
+						// * Disabled handler, or the one not selected for import, is skipped as if it succeeded. See `handler_toggles` and `ImportOptions`.
+						if !::ruva::handler_toggles().is_enabled(stringify!($event), stringify!($handler)) || !context_manager.is_handler_selected(stringify!($event), stringify!($handler)) {
+							return Box::pin(async { Ok(()) });
+						}
+						// * Handler being replaced is compared with its counterpart by the message id of the event. See `handler_migrations`.
+						let event_key = context_manager.message_id().map(ToString::to_string);
+						// * Record-only handler runs in the sandbox. See `handler_sandbox`.
+						// Handler is built on first poll, so that dependencies it resolves on construction are recorded in the sandbox too.
+						::ruva::migrated(stringify!($event), stringify!($handler), event_key.as_deref(), ::ruva::sandboxed(stringify!($event), stringify!($handler), Box::pin(async move {
+							let event_handler = $event_handler(context_manager);
+							event_handler.$handler(
+								// * Convert event so event handler accepts not Arc<dyn TEvent> but `event_happend` type of message.
+								// Safety:: client should access this vector of handlers by providing the corresponding event name
+								// So, when it is followed, it logically doesn't make sense to cause an error.
+								e.downcast_ref::<$event>().expect("Not Convertible!").clone(),
+							).await
+						})))
+					}
+				) as Box<dyn Fn(::std::sync::Arc<dyn ::ruva::TEvent>, ruva::AtomicContextManager) -> ::ruva::Future<$E> + Send + Sync>),
+			)*
+		]));
+		handlers
+	}};
+}
+
+#[macro_export]
+#[doc(hidden)]
+macro_rules! __handler_order {
+	() => {
+		None
+	};
+	($order:expr) => {
+		Some($order)
+	};
+}
+
+/// Fail to compile with `message` if any of the registrations is given more than once.
+/// Events are compared by their topic, so the same event is caught however its path is written. Others are compared as
+/// written apart from leading `self::`.
+#[macro_export]
+#[doc(hidden)]
+macro_rules! __assert_unique_registrations {
+	(topic [$(,)?] $message:expr) => {};
+	(topic [$head:ty $(, $tail:ty)* $(,)?] $message:expr) => {
+		$(
+			const _: () = ::std::assert!(
+				!::ruva::__same_registration(<$head as ::ruva::TTopic>::TOPIC, <$tail as ::ruva::TTopic>::TOPIC),
+				concat!("`", stringify!($tail), "` ", $message)
+			);
+		)*
+		ruva::__assert_unique_registrations!(topic [$($tail),*] $message);
+	};
+	([$(,)?] $message:expr) => {};
+	([$head:ty $(, $tail:ty)* $(,)?] $message:expr) => {
+		$(
+			const _: () = ::std::assert!(
+				!::ruva::__same_registration(stringify!($head), stringify!($tail)),
+				concat!("`", stringify!($tail), "` ", $message)
+			);
+		)*
+		ruva::__assert_unique_registrations!([$($tail),*] $message);
+	};
+}
+
+/// Fail to compile with `message` if any of the `#[order = n]` is given more than once.
+#[macro_export]
+#[doc(hidden)]
+macro_rules! __assert_unique_orders {
+	([$(($($order:expr)?)),*] $message:expr) => {
+		const _: () = ::std::assert!(::ruva::__unique_orders(&[$(ruva::__handler_order!($($order)?)),*]), $message);
+	};
+}
+
+#[doc(hidden)]
+pub const fn __unique_orders(orders: &[Option<usize>]) -> bool {
+	let mut i = 0;
+	while i < orders.len() {
+		let mut j = i + 1;
+		while j < orders.len() {
+			if let (Some(a), Some(b)) = (orders[i], orders[j]) {
+				if a == b {
+					return false;
+				}
+			}
+			j += 1;
+		}
+		i += 1;
+	}
+	true
+}
+
+/// Whether `a` and `b`, given by `stringify!`, are the same registration.
+/// Spaces depend on how the path is captured, and leading `self::` doesn't change what it names, so both are ignored.
+#[doc(hidden)]
+pub const fn __same_registration(a: &str, b: &str) -> bool {
+	const fn skip_spaces(path: &[u8], mut i: usize) -> usize {
+		while i < path.len() && path[i] == b' ' {
+			i += 1;
+		}
+		i
+	}
+	const fn skip_self(path: &[u8]) -> usize {
+		let prefix = b"self::";
+		let (mut i, mut j) = (0, 0);
+		while j < prefix.len() {
+			i = skip_spaces(path, i);
+			if i == path.len() || path[i] != prefix[j] {
+				return 0;
+			}
+			i += 1;
+			j += 1;
+		}
+		i
+	}
+	let (a, b) = (a.as_bytes(), b.as_bytes());
+	let (mut i, mut j) = (skip_self(a), skip_self(b));
+	loop {
+		i = skip_spaces(a, i);
+		j = skip_spaces(b, j);
+		match (i == a.len(), j == b.len()) {
+			(true, true) => return true,
+			(false, false) if a[i] == b[j] => {
+				i += 1;
+				j += 1;
+			}
+			_ => return false,
+		}
+	}
+}
+
 pub struct MessageBus;
+
+impl MessageBus {
+	/// Same as `execute_and_wait` but response and error type are inferred from [TCommandSpec] of the command.
+	/// ## Example
+	/// ```rust
+	/// # use ruva_core::prelude::*;
+	/// # async fn example<C>(cmd: C, conn: &'static dyn TConnection) -> Result<(), C::Error>
+	/// # where C: TCommandRoute, C::Error: From<BaseError>, BaseError: From<C::Error>, MessageBus: TEventBus<C::Error> {
+	/// let res = MessageBus.dispatch(cmd, conn).await?;
+	/// # Ok(())
+	/// # }
+	/// ```
+	pub async fn dispatch<C>(&self, message: C, conn: &'static dyn TConnection) -> Result<C::Response, C::Error>
+	where
+		C: TCommandSpec,
+		C::Error: std::convert::From<BaseError>,
+		BaseError: std::convert::From<C::Error>,
+		Self: TMessageBus<C::Response, C::Error, C>,
+	{
+		self.execute_and_wait(message, conn).await
+	}
+
+	/// Same as `dispatch` but with context manager prepared by caller.
+	pub async fn dispatch_with<C>(&self, message: C, context_manager: ContextManager) -> Result<C::Response, C::Error>
+	where
+		C: TCommandSpec,
+		C::Error: std::convert::From<BaseError>,
+		BaseError: std::convert::From<C::Error>,
+		Self: TMessageBus<C::Response, C::Error, C>,
+	{
+		self.execute_and_wait_with(message, context_manager).await
+	}
+
+	/// Same as `dispatch_with` but also returns [UowStats] of the whole dispatch, including events cascaded from event handlers.
+	/// ## Example
+	/// ```rust
+	/// # use std::time::Duration;
+	/// # use ruva_core::prelude::*;
+	/// # async fn example<C>(cmd: C, conn: &'static dyn TConnection) -> Result<(), C::Error>
+	/// # where C: TCommandRoute, C::Error: From<BaseError>, BaseError: From<C::Error>, MessageBus: TEventBus<C::Error> {
+	/// let (res, stats) = MessageBus.dispatch_with_stats(cmd, ContextManager::new(conn)).await;
+	/// assert!(stats.commit_duration < Some(Duration::from_millis(50)));
+	/// # Ok(())
+	/// # }
+	/// ```
+	pub async fn dispatch_with_stats<C>(&self, message: C, context_manager: ContextManager) -> (Result<C::Response, C::Error>, UowStats)
+	where
+		C: TCommandSpec,
+		C::Error: std::convert::From<BaseError>,
+		BaseError: std::convert::From<C::Error>,
+		Self: TMessageBus<C::Response, C::Error, C>,
+	{
+		let stats = Arc::clone(&context_manager.stats);
+		let res = self.execute_and_wait_with(message, context_manager).await;
+		let stats = stats.lock().unwrap().clone();
+		(res, stats)
+	}
+
+	/// Same as `dispatch_with` but also returns [ConsistencyToken] for the client to read its own writes with.
+	/// Empty if the dispatch committed no outbox row.
+	pub async fn dispatch_with_consistency<C>(&self, message: C, context_manager: ContextManager) -> Result<(C::Response, ConsistencyToken), C::Error>
+	where
+		C: TCommandSpec,
+		C::Error: std::convert::From<BaseError>,
+		BaseError: std::convert::From<C::Error>,
+		Self: TMessageBus<C::Response, C::Error, C>,
+	{
+		let (res, stats) = self.dispatch_with_stats(message, context_manager).await;
+		Ok((res?, stats.consistency_token))
+	}
+}
+
+#[tokio::test]
+async fn test_missing_handler_policy() {
+	use std::sync::atomic::{AtomicUsize, Ordering};
+
+	struct Connection;
+	impl TConnection for Connection {}
+	struct Unhandled;
+	impl TEvent for Unhandled {
+		fn state(&self) -> String {
+			"{}".into()
+		}
+	}
+	struct Handled;
+	impl TEvent for Handled {
+		fn state(&self) -> String {
+			"{}".into()
+		}
+	}
+
+	static HANDLED: AtomicUsize = AtomicUsize::new(0);
+	static EVENT_HANDLER: std::sync::LazyLock<TEventHandler<BaseError>> = std::sync::LazyLock::new(|| {
+		let mut map = TEventHandler::default();
+		map.insert(
+			"Handled".to_string(),
+			EventHandlers::Sync(vec![Box::new(|_, _| {
+				HANDLED.fetch_add(1, Ordering::SeqCst);
+				Box::pin(async { Ok(()) })
+			})]),
+		);
+		map
+	});
+	let context_manager = |policy| {
+		let mut context_manager = ContextManager::new(&Connection).with_missing_handler_policy(policy);
+		context_manager.push_back(Arc::new(Handled));
+		Arc::new(context_manager)
+	};
+
+	// The rest of the queue is processed
+	assert!(handle_event(Arc::new(Unhandled), context_manager(MissingHandlerPolicy::WarnAndSkip), &EVENT_HANDLER).await.is_ok());
+	assert_eq!(HANDLED.load(Ordering::SeqCst), 1);
+
+	let res = handle_event(Arc::new(Unhandled), context_manager(MissingHandlerPolicy::Strict), &EVENT_HANDLER).await;
+	assert!(matches!(res, Err(BaseError::HandlerNotFound(topic)) if topic == "Unhandled"));
+	assert_eq!(HANDLED.load(Ordering::SeqCst), 1);
+}
+
+#[test]
+fn test_same_registration() {
+	assert!(__same_registration("OrderPlaced", "OrderPlaced"));
+	assert!(!__same_registration("OrderPlaced", "OrderPlace"));
+	assert!(!__same_registration("OrderPlaced", "events :: OrderPlaced"));
+	assert!(__same_registration("self :: OrderPlaced", "OrderPlaced"));
+	assert!(__same_registration("self::events::OrderPlaced", "events :: OrderPlaced"));
+	assert!(!__same_registration("selfish :: OrderPlaced", "OrderPlaced"));
+	const _: () = assert!(__same_registration("Vec < i32 >", "Vec < i32 >"));
+	assert!(__unique_orders(&[None, Some(1), None, Some(2)]));
+	assert!(!__unique_orders(&[Some(1), None, Some(1)]));
+}
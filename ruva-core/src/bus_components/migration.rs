@@ -0,0 +1,266 @@
+//! ### Handler migration
+//! To replace event handler, for example projection, register the new one next to the old one in `init_event_handler!`
+//! and verify them against each other on live traffic before cutting over.
+//!
+//! ```rust,ignore
+//! init_event_handler!(ServiceError, |ctx| Projection::new(ctx), OrderPlaced: [project_order, project_order_v2]);
+//!
+//! // Both handlers report what they write
+//! pub async fn project_order_v2(&mut self, event: OrderPlaced) -> Result<(), ServiceError> {
+//!     let row = OrderView::from(&event);
+//!     record_handler_output(&row);
+//!     self.insert_order_view_v2(row).await
+//! }
+//!
+//! // On boot
+//! handler_migrations().verify("OrderPlaced", "project_order", "project_order_v2");
+//!
+//! // From admin endpoint, once enough events agree
+//! if handler_migrations().status("OrderPlaced").is_some_and(|status| status.consecutive_matches >= 10_000) {
+//!     handler_migrations().promote("OrderPlaced");
+//! }
+//! ```
+//! While verifying, the new handler runs in record-only mode of [handler_sandbox](super::sandbox::handler_sandbox),
+//! so the outside world sees the effects of the old one only. For each event, outcome and outputs recorded with
+//! [record_handler_output] are compared and divergences are logged and kept in the report.
+//! `promote` disables the old handler with [handler_toggles](super::toggles::handler_toggles) and lets the new one
+//! perform its effects, while `abort` disables the new one.
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex, RwLock};
+
+use serde::Serialize;
+
+use super::handler::Future;
+use super::sandbox::handler_sandbox;
+use super::toggles::handler_toggles;
+
+/// Divergences kept in the report. The oldest ones are dropped beyond it.
+const REPORT_CAPACITY: usize = 1_000;
+
+/// Runs waiting for the counterpart. Counterpart that never comes, for example of disabled handler, is dropped beyond it.
+const PENDING_CAPACITY: usize = 10_000;
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct MigrationStatus {
+	pub old: String,
+	pub new: String,
+	/// Events both handlers have run for
+	pub compared: u64,
+	pub diverged: u64,
+	/// Events compared since the last divergence
+	pub consecutive_matches: u64,
+}
+
+/// Outcome of a handler for an event
+#[derive(Debug, Clone, PartialEq)]
+pub struct HandlerRun {
+	pub succeeded: bool,
+	pub outputs: Vec<serde_json::Value>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Divergence {
+	pub topic: String,
+	pub old: HandlerRun,
+	pub new: HandlerRun,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Role {
+	Old,
+	New,
+}
+
+struct Migration {
+	status: MigrationStatus,
+	/// Run of one side keyed by the event, waiting for the other side
+	pending: VecDeque<(String, Role, HandlerRun)>,
+}
+
+#[derive(Default)]
+pub struct HandlerMigrations {
+	migrations: RwLock<hashbrown::HashMap<String, Arc<Mutex<Migration>>>>,
+	report: Mutex<VecDeque<Divergence>>,
+}
+
+static HANDLER_MIGRATIONS: std::sync::LazyLock<HandlerMigrations> = std::sync::LazyLock::new(Default::default);
+
+/// Process-wide migrations that `init_event_handler!` consults before running each handler.
+pub fn handler_migrations() -> &'static HandlerMigrations {
+	&HANDLER_MIGRATIONS
+}
+
+tokio::task_local! {
+	static OUTPUTS: std::cell::RefCell<Vec<serde_json::Value>>;
+}
+
+/// Record what the handler produced, such as the row it writes, to be compared with the counterpart.
+/// No-op unless the handler is under migration.
+pub fn record_handler_output(output: impl Serialize) {
+	let _ = OUTPUTS.try_with(|outputs| match serde_json::to_value(output) {
+		Ok(value) => outputs.borrow_mut().push(value),
+		Err(err) => tracing::warn!("Failed to serialize handler output! {}", err),
+	});
+}
+
+impl HandlerMigrations {
+	/// Run `new` handler of `topic` alongside `old` and compare them. `new` is put in record-only mode.
+	pub fn verify(&self, topic: &str, old: &str, new: &str) {
+		tracing::warn!("Event handler {}::{} is verified against {}", topic, new, old);
+		handler_sandbox().record_only(topic, new);
+		let migration = Migration {
+			status: MigrationStatus {
+				old: old.to_string(),
+				new: new.to_string(),
+				..Default::default()
+			},
+			pending: VecDeque::new(),
+		};
+		self.migrations.write().unwrap().insert(topic.to_string(), Arc::new(Mutex::new(migration)));
+	}
+
+	pub fn status(&self, topic: &str) -> Option<MigrationStatus> {
+		let migration = self.migrations.read().unwrap().get(topic).cloned()?;
+		let status = migration.lock().unwrap().status.clone();
+		Some(status)
+	}
+
+	/// Cut over to the new handler. Returns `false` if no migration of `topic` is being verified.
+	pub fn promote(&self, topic: &str) -> bool {
+		let Some(migration) = self.migrations.write().unwrap().remove(topic) else {
+			return false;
+		};
+		let status = &migration.lock().unwrap().status;
+		tracing::warn!("Event handler {}::{} is promoted over {}", topic, status.new, status.old);
+		handler_sandbox().execute(topic, &status.new);
+		handler_toggles().disable(topic, &status.old);
+		true
+	}
+
+	/// Give up the new handler. Returns `false` if no migration of `topic` is being verified.
+	pub fn abort(&self, topic: &str) -> bool {
+		let Some(migration) = self.migrations.write().unwrap().remove(topic) else {
+			return false;
+		};
+		let status = &migration.lock().unwrap().status;
+		tracing::warn!("Migration of event handler {}::{} is aborted", topic, status.new);
+		handler_sandbox().execute(topic, &status.new);
+		handler_toggles().disable(topic, &status.new);
+		true
+	}
+
+	/// Divergences found so far, removing them from the report
+	pub fn take_report(&self) -> Vec<Divergence> {
+		self.report.lock().unwrap().drain(..).collect()
+	}
+
+	fn role(&self, topic: &str, handler: &str) -> Option<(Arc<Mutex<Migration>>, Role)> {
+		let migrations = self.migrations.read().unwrap();
+		// Avoid lookup on hot path when nothing is migrated
+		if migrations.is_empty() {
+			return None;
+		}
+		let migration = migrations.get(topic)?;
+		let role = {
+			let status = &migration.lock().unwrap().status;
+			match handler {
+				h if h == status.old => Role::Old,
+				h if h == status.new => Role::New,
+				_ => return None,
+			}
+		};
+		Some((migration.clone(), role))
+	}
+
+	fn compare(&self, topic: &str, migration: &Mutex<Migration>, event_key: String, role: Role, run: HandlerRun) {
+		let mut migration = migration.lock().unwrap();
+		let Some(idx) = migration.pending.iter().position(|(key, r, _)| *key == event_key && *r != role) else {
+			if migration.pending.len() == PENDING_CAPACITY {
+				migration.pending.pop_front();
+			}
+			migration.pending.push_back((event_key, role, run));
+			return;
+		};
+		let (_, _, counterpart) = migration.pending.remove(idx).unwrap();
+		let (old, new) = match role {
+			Role::Old => (run, counterpart),
+			Role::New => (counterpart, run),
+		};
+		migration.status.compared += 1;
+		if old == new {
+			migration.status.consecutive_matches += 1;
+			return;
+		}
+		migration.status.diverged += 1;
+		migration.status.consecutive_matches = 0;
+		tracing::warn!(topic, "Event handler {} diverged from {}! old: {:?}, new: {:?}", migration.status.new, migration.status.old, old, new);
+		let mut report = self.report.lock().unwrap();
+		if report.len() == REPORT_CAPACITY {
+			report.pop_front();
+		}
+		report.push_back(Divergence { topic: topic.to_string(), old, new });
+	}
+}
+
+/// Capture outcome and outputs of `future` if the handler is under migration. Called by `init_event_handler!`.
+/// `event_key` identifies the event both handlers are given - its message id, see [ContextManager::message_id](crate::prelude::ContextManager::message_id).
+pub fn migrated<E: 'static>(topic: &'static str, handler: &'static str, event_key: Option<&str>, future: Future<E>) -> Future<E> {
+	let Some((migration, role)) = handler_migrations().role(topic, handler) else {
+		return future;
+	};
+	let Some(event_key) = event_key.map(ToString::to_string) else {
+		tracing::warn!(topic, handler, "Event without message id is not compared");
+		return future;
+	};
+	Box::pin(async move {
+		let (res, outputs) = OUTPUTS
+			.scope(Default::default(), async move {
+				let res = future.await;
+				(res, OUTPUTS.with(|outputs| outputs.take()))
+			})
+			.await;
+		let run = HandlerRun { succeeded: res.is_ok(), outputs };
+		handler_migrations().compare(topic, &migration, event_key, role, run);
+		res
+	})
+}
+
+#[cfg(test)]
+mod test {
+	use super::*;
+	use crate::prelude::BaseError;
+
+	fn handler(amount: i64) -> Future<BaseError> {
+		Box::pin(async move {
+			record_handler_output(serde_json::json!({ "amount": amount }));
+			Ok(())
+		})
+	}
+
+	#[tokio::test]
+	async fn test_handler_migration() {
+		handler_migrations().verify("MigrationTestEvent", "project", "project_v2");
+		assert!(handler_sandbox().is_record_only("MigrationTestEvent", "project_v2"));
+
+		// Same output for event 1, different for event 2
+		migrated("MigrationTestEvent", "project", Some("1"), handler(10)).await.unwrap();
+		migrated("MigrationTestEvent", "project_v2", Some("1"), handler(10)).await.unwrap();
+		migrated("MigrationTestEvent", "project_v2", Some("2"), handler(21)).await.unwrap();
+		migrated("MigrationTestEvent", "project", Some("2"), handler(20)).await.unwrap();
+		migrated("MigrationTestEvent", "project", Some("3"), handler(30)).await.unwrap();
+		migrated("MigrationTestEvent", "project_v2", Some("3"), handler(30)).await.unwrap();
+
+		let status = handler_migrations().status("MigrationTestEvent").unwrap();
+		assert_eq!((status.compared, status.diverged, status.consecutive_matches), (3, 1, 1));
+		let report = handler_migrations().take_report();
+		assert_eq!(report.len(), 1);
+		assert_eq!(report[0].old.outputs, vec![serde_json::json!({ "amount": 20 })]);
+		assert_eq!(report[0].new.outputs, vec![serde_json::json!({ "amount": 21 })]);
+
+		assert!(handler_migrations().promote("MigrationTestEvent"));
+		assert!(handler_migrations().status("MigrationTestEvent").is_none());
+		assert!(!handler_sandbox().is_record_only("MigrationTestEvent", "project_v2"));
+		assert!(!handler_toggles().is_enabled("MigrationTestEvent", "project"));
+		assert!(handler_toggles().is_enabled("MigrationTestEvent", "project_v2"));
+	}
+}
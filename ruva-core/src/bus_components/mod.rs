@@ -1,4 +1,47 @@
+pub mod actor;
+pub mod analytics;
+pub mod aspect;
+pub mod audit;
+pub mod authorization;
+pub mod backlog;
+pub mod cache;
+pub mod concurrency;
+pub mod consistency;
 pub mod contexts;
+pub mod correlation;
+pub mod current_user;
+pub mod dead_letter;
+pub mod dependency;
+pub mod dynamic;
+pub mod enrich;
 pub mod executor;
+pub mod extensions;
 pub mod handler;
+pub mod idempotency;
+pub mod import;
+pub mod inbox;
+pub mod job;
+pub mod journal;
+pub mod layer;
+pub mod limit;
+pub mod local;
+pub mod memo;
 pub mod messagebus;
+pub mod migration;
+pub mod observer;
+pub mod pipeline;
+pub mod policy;
+pub mod preflight;
+pub mod propagation;
+pub mod queue;
+pub mod replay;
+pub mod retry;
+pub mod saga;
+pub mod sandbox;
+pub mod shutdown;
+pub mod snapshot;
+pub mod stats;
+pub mod tenant;
+pub mod toggles;
+pub mod translation;
+pub mod validation;
@@ -0,0 +1,76 @@
+//! ### Bus observer
+//! Observation-only concerns such as logging, metrics or test spies don't need a full aspect.
+//! Implement [TBusObserver] once and register it on boot. Every callback has no-op default.
+//!
+//! ```rust,ignore
+//! struct Metrics;
+//! impl TBusObserver for Metrics {
+//!     fn command_finished(&self, command: &str, elapsed: Duration, succeeded: bool) {
+//!         histogram!("command_duration", "command" => command.to_string()).record(elapsed);
+//!     }
+//! }
+//!
+//! register_bus_observer(Metrics);
+//! ```
+//! Callbacks are called inline on the bus, so they should return quickly.
+use std::sync::{Arc, LazyLock, RwLock};
+use std::time::Duration;
+
+use super::backlog::{Backlog, MessageSource};
+use super::stats::UowStats;
+
+pub trait TBusObserver: Send + Sync {
+	/// `command` is the type name of the command
+	fn command_started(&self, _command: &str) {}
+	fn command_finished(&self, _command: &str, _elapsed: Duration, _succeeded: bool) {}
+	/// Internally notifiable event is put on the event queue
+	fn event_enqueued(&self, _topic: &str) {}
+	/// Event is taken off the event queue to be handled. `depth` is the number of events left in the queue.
+	fn event_dequeued(&self, _topic: &str, _depth: usize) {}
+	/// `index` is the position of the handler in the list registered for `topic`
+	fn handler_finished(&self, _topic: &str, _index: usize, _elapsed: Duration, _succeeded: bool) {}
+	/// Stats of the unit of work, called right after `command_finished`. Events cascaded from event handlers are not counted yet.
+	fn dispatch_stats(&self, _command: &str, _stats: &UowStats) {}
+	fn commit(&self) {}
+	fn rollback(&self) {}
+	/// Backlog of `source` is sampled. See [backlog_metrics](super::backlog::backlog_metrics).
+	fn backlog_sampled(&self, _source: MessageSource, _backlog: &Backlog) {}
+	/// Message of `topic` is published from the outbox or handled from the inbox
+	fn message_processed(&self, _source: MessageSource, _topic: &str) {}
+}
+
+static BUS_OBSERVERS: LazyLock<RwLock<Vec<Arc<dyn TBusObserver>>>> = LazyLock::new(Default::default);
+
+pub fn register_bus_observer(observer: impl TBusObserver + 'static) {
+	BUS_OBSERVERS.write().unwrap().push(Arc::new(observer));
+}
+
+/// Notify every registered observer.
+pub(crate) fn notify(f: impl Fn(&dyn TBusObserver)) {
+	let observers = BUS_OBSERVERS.read().unwrap();
+	observers.iter().for_each(|observer| f(observer.as_ref()));
+}
+
+#[test]
+fn test_bus_observer() {
+	use std::sync::Mutex;
+
+	struct Spy(Arc<Mutex<Vec<String>>>);
+	impl TBusObserver for Spy {
+		fn command_started(&self, command: &str) {
+			self.0.lock().unwrap().push(format!("started:{}", command));
+		}
+		fn commit(&self) {
+			self.0.lock().unwrap().push("commit".into());
+		}
+	}
+
+	let calls = Arc::new(Mutex::new(vec![]));
+	register_bus_observer(Spy(calls.clone()));
+	notify(|o| o.command_started("ObserverTestCommand"));
+	notify(|o| o.commit());
+
+	let calls = calls.lock().unwrap();
+	assert!(calls.contains(&"started:ObserverTestCommand".to_string()));
+	assert!(calls.contains(&"commit".to_string()));
+}
@@ -0,0 +1,172 @@
+//! ### Commit pipeline
+//! Commit of unit of work goes through [CommitStage]s in order. Aspect can hook into a stage of the dispatch it wraps
+//! instead of wrapping the whole `execute()`, which runs before events are even published.
+//!
+//! ```rust
+//! # use ruva_core::prelude::*;
+//! # struct OrderCache;
+//! # impl OrderCache { fn invalidate(&self, _: &i64) {} }
+//! # static ORDER_CACHE: OrderCache = OrderCache;
+//! # pub struct CacheInvalidationAspect<S>(S);
+//! impl<S> CacheInvalidationAspect<S> {
+//!     pub fn new(context_manager: &AtomicContextManager, order_id: i64, inner: S) -> Self {
+//!         // Readers must not refill the cache with stale data before commit
+//!         context_manager.on_stage(CommitStage::Publish, move |_| async move {
+//!             ORDER_CACHE.invalidate(&order_id);
+//!             Ok(())
+//!         });
+//!         Self(inner)
+//!     }
+//! }
+//! ```
+//! Hooks of a stage run in the order they are registered. Failure of `Validate` or `Persist` hook fails the commit,
+//! while that of `Publish` hook is only logged as the transaction is already committed.
+//! Hooks not run yet are dropped when the unit of work is rolled back or its commit fails, so they never fire for another attempt.
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Mutex;
+
+use super::contexts::{AtomicContextManager, Context, ContextManager};
+use crate::prelude::BaseError;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum CommitStage {
+	/// Command handler succeeded but nothing is written yet
+	Validate,
+	/// Outbox is written in the transaction, which is about to be committed
+	Persist,
+	/// Transaction is committed and internally notifiable events are put on the event queue
+	Publish,
+}
+
+type StageFuture = Pin<Box<dyn Future<Output = Result<(), BaseError>> + Send>>;
+type StageHook = Box<dyn FnOnce(AtomicContextManager) -> StageFuture + Send + Sync>;
+
+#[derive(Default)]
+pub(crate) struct StageHooks(Mutex<Vec<(CommitStage, StageHook)>>);
+
+impl ContextManager {
+	/// Run `hook` when commit of this dispatch reaches `stage`. Hook runs at most once.
+	pub fn on_stage<F, Fut>(&self, stage: CommitStage, hook: F)
+	where
+		F: FnOnce(AtomicContextManager) -> Fut + Send + Sync + 'static,
+		Fut: Future<Output = Result<(), BaseError>> + Send + 'static,
+	{
+		self.stage_hooks.0.lock().unwrap().push((stage, Box::new(move |context_manager| Box::pin(hook(context_manager)))));
+	}
+
+	fn take_stage_hooks(&self, stage: CommitStage) -> Vec<StageHook> {
+		let mut hooks = self.stage_hooks.0.lock().unwrap();
+		let (taken, rest) = std::mem::take(&mut *hooks).into_iter().partition::<Vec<_>, _>(|(s, _)| *s == stage);
+		*hooks = rest;
+		taken.into_iter().map(|(_, hook)| hook).collect()
+	}
+}
+
+impl Context {
+	/// Run hooks registered for `stage`. Meant to be called from `TUnitOfWork::run_stage`.
+	/// Nothing is published in dry run, so `Publish` hooks are dropped.
+	pub async fn run_stage_hooks(&mut self, stage: CommitStage) -> Result<(), BaseError> {
+		let hooks = self.super_ctx.take_stage_hooks(stage);
+		if stage == CommitStage::Publish && self.is_dry_run() {
+			return Ok(());
+		}
+		for hook in hooks {
+			match hook(self.super_ctx.clone()).await {
+				Err(err) if stage == CommitStage::Publish => tracing::error!(command = self.super_ctx.command, "Hook of publish stage failed! {:?}", err),
+				res => res?,
+			}
+		}
+		Ok(())
+	}
+
+	/// Drop hooks of every stage. Meant to be called from `TUnitOfWork::rollback` and `TUnitOfWork::discard_stages`.
+	pub fn discard_stage_hooks(&self) {
+		self.super_ctx.stage_hooks.0.lock().unwrap().clear();
+	}
+}
+
+#[tokio::test]
+async fn test_stage_hooks_run_once_in_order() {
+	use super::executor::TConnection;
+	use std::sync::Arc;
+
+	struct Connection;
+	impl TConnection for Connection {}
+
+	let recorded = Arc::new(Mutex::new(vec![]));
+	let context_manager = Arc::new(ContextManager::new(&Connection));
+	for (stage, name) in [(CommitStage::Publish, "invalidate"), (CommitStage::Validate, "check"), (CommitStage::Publish, "notify")] {
+		let recorded = recorded.clone();
+		context_manager.on_stage(stage, move |_| async move {
+			recorded.lock().unwrap().push(name);
+			Ok(())
+		});
+	}
+	context_manager.on_stage(CommitStage::Persist, |_| async { Err(BaseError::Rejected("audit".into())) });
+
+	let mut context = Context::new(context_manager);
+	context.run_stage_hooks(CommitStage::Validate).await.unwrap();
+	assert!(context.run_stage_hooks(CommitStage::Persist).await.is_err());
+	context.run_stage_hooks(CommitStage::Publish).await.unwrap();
+	context.run_stage_hooks(CommitStage::Publish).await.unwrap();
+
+	assert_eq!(*recorded.lock().unwrap(), vec!["check", "invalidate", "notify"]);
+}
+
+#[tokio::test]
+async fn test_stage_hooks_are_discarded_on_failure() {
+	use super::executor::TConnection;
+	use crate::prelude::TUnitOfWork;
+	use std::sync::atomic::{AtomicUsize, Ordering};
+	use std::sync::Arc;
+
+	struct Connection;
+	impl TConnection for Connection {}
+	struct UnitOfWork(Context);
+	impl TUnitOfWork for UnitOfWork {
+		async fn begin(&mut self) -> Result<(), BaseError> {
+			Ok(())
+		}
+		async fn _commit(&mut self) -> Result<(), BaseError> {
+			Ok(())
+		}
+		async fn rollback(&mut self) -> Result<(), BaseError> {
+			self.0.discard_stage_hooks();
+			Ok(())
+		}
+		async fn close(&mut self) {}
+		async fn run_stage(&mut self, stage: CommitStage) -> Result<(), BaseError> {
+			self.0.run_stage_hooks(stage).await
+		}
+		fn discard_stages(&mut self) {
+			self.0.discard_stage_hooks();
+		}
+	}
+
+	static PUBLISHED: AtomicUsize = AtomicUsize::new(0);
+	let context_manager = Arc::new(ContextManager::new(&Connection));
+	let publish = |context_manager: &ContextManager| {
+		context_manager.on_stage(CommitStage::Publish, |_| async {
+			PUBLISHED.fetch_add(1, Ordering::SeqCst);
+			Ok(())
+		})
+	};
+
+	// Commit fails at validate stage, leaving publish hook behind unless discarded
+	publish(&context_manager);
+	context_manager.on_stage(CommitStage::Validate, |_| async { Err(BaseError::Rejected("invalid".into())) });
+	let mut uow = UnitOfWork(Context::new(context_manager.clone()));
+	assert!(uow.commit().await.is_err());
+
+	// Retry registers its own hook, which runs once
+	publish(&context_manager);
+	uow.commit().await.unwrap();
+	assert_eq!(PUBLISHED.load(Ordering::SeqCst), 1);
+
+	// Rolled back
+	publish(&context_manager);
+	uow.rollback().await.unwrap();
+	uow.commit().await.unwrap();
+	assert_eq!(PUBLISHED.load(Ordering::SeqCst), 1);
+}
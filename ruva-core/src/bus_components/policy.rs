@@ -0,0 +1,153 @@
+//! ### Inbound event policies
+//! Declarative translation of consumed external events into local commands.
+//!
+//! ```rust,ignore
+//! let policies = EventPolicies::new(conn)
+//!     .register(on_event::<ExternalOrderPlaced>().dispatch(|e| CreateLocalOrder { external_id: e.id, amount: e.amount }))
+//!     .register(on_event::<ExternalOrderCancelled>().dispatch(|e| CancelLocalOrder { external_id: e.id }))
+//!     // With topic constant from producer's contract crate. See `topics!`.
+//!     .register(order_contract::topics::ORDER_REFUNDED.on_event().dispatch(|e| RefundLocalOrder { external_id: e.id }))
+//!     .dead_letter(pool.clone());
+//!
+//! // In the consumer loop
+//! policies.handle(&message.topic, &message.payload).await?;
+//! ```
+//! Event that fails to deserialize or whose command fails is kept in [TDeadLetterStore], if any, without handler index.
+use std::future::Future;
+use std::pin::Pin;
+
+use serde::de::DeserializeOwned;
+
+use super::actor::Actor;
+use super::contexts::ContextManager;
+use super::dead_letter::{DeadLetter, TDeadLetterStore};
+use super::executor::TConnection;
+use super::messagebus::{MessageBus, TMessageBus};
+use crate::prelude::{clock, strip_topic_namespace, BaseError, SnowFlake, TCommandSpec, INITIAL_EVENT_VERSION};
+
+type PolicyFuture = Pin<Box<dyn Future<Output = Result<(), BaseError>> + Send>>;
+type Translate = Box<dyn Fn(&str, ContextManager) -> PolicyFuture + Send + Sync>;
+
+pub struct Policy {
+	topic: String,
+	translate: Translate,
+}
+
+pub struct PolicyBuilder<T> {
+	topic: String,
+	_event: std::marker::PhantomData<fn() -> T>,
+}
+
+/// Start declaring policy for external event `T`. Topic is the type name of `T`, as with `TEvent`.
+pub fn on_event<T: DeserializeOwned + 'static>() -> PolicyBuilder<T> {
+	PolicyBuilder {
+		topic: std::any::type_name::<T>().split("::").last().unwrap().to_string(),
+		_event: std::marker::PhantomData,
+	}
+}
+
+impl<T: DeserializeOwned + 'static> PolicyBuilder<T> {
+	/// Override topic when it differs from the type name.
+	pub fn topic(mut self, topic: impl Into<String>) -> Self {
+		self.topic = topic.into();
+		self
+	}
+
+	pub fn dispatch<C>(self, to_command: impl Fn(T) -> C + Send + Sync + 'static) -> Policy
+	where
+		C: TCommandSpec,
+		C::Error: std::convert::From<BaseError>,
+		BaseError: std::convert::From<C::Error>,
+		MessageBus: TMessageBus<C::Response, C::Error, C>,
+	{
+		Policy {
+			topic: self.topic,
+			translate: Box::new(move |payload, context_manager| {
+				let command = serde_json::from_str::<T>(payload).map(&to_command);
+				Box::pin(async move {
+					let command = command.map_err(|err| BaseError::DatabaseError(format!("Failed to deserialize: {}", err)))?;
+					MessageBus.dispatch_with(command, context_manager).await.map(|_| ()).map_err(BaseError::from)
+				})
+			}),
+		}
+	}
+}
+
+impl<T: DeserializeOwned + 'static> crate::prelude::Topic<T> {
+	/// Start declaring policy for the event of this topic.
+	pub fn on_event(&self) -> PolicyBuilder<T> {
+		on_event::<T>().topic(self.name())
+	}
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PolicyOutcome {
+	Dispatched,
+	/// No policy is declared for the topic
+	Ignored,
+	DeadLettered(String),
+}
+
+pub struct EventPolicies {
+	conn: &'static dyn TConnection,
+	policies: hashbrown::HashMap<String, Policy>,
+	dead_letter: Option<Box<dyn TDeadLetterStore>>,
+}
+
+impl EventPolicies {
+	pub fn new(conn: &'static dyn TConnection) -> Self {
+		Self {
+			conn,
+			policies: Default::default(),
+			dead_letter: None,
+		}
+	}
+
+	/// ## Panics
+	/// If policy for the same topic is already added.
+	pub fn register(mut self, policy: Policy) -> Self {
+		if self.policies.contains_key(&policy.topic) {
+			panic!("Policy for {} is already added!", policy.topic);
+		}
+		self.policies.insert(policy.topic.clone(), policy);
+		self
+	}
+
+	pub fn dead_letter(mut self, store: impl TDeadLetterStore + 'static) -> Self {
+		self.dead_letter = Some(Box::new(store));
+		self
+	}
+
+	pub fn topics(&self) -> impl Iterator<Item = &str> {
+		self.policies.keys().map(String::as_str)
+	}
+
+	/// Translate consumed event into command and dispatch it. Event of another namespace is ignored. See `topic_namespace`.
+	/// Error is returned only when dead letter store is not set or fails, so that consumer doesn't acknowledge the message.
+	pub async fn handle(&self, topic: &str, payload: &str) -> Result<PolicyOutcome, BaseError> {
+		let Some(policy) = strip_topic_namespace(topic).and_then(|topic| self.policies.get(topic)) else {
+			return Ok(PolicyOutcome::Ignored);
+		};
+		let context_manager = ContextManager::new(self.conn).with_actor(Actor::System(format!("policy:{}", topic)));
+		match (policy.translate)(payload, context_manager).await {
+			Ok(()) => Ok(PolicyOutcome::Dispatched),
+			Err(err) => {
+				tracing::error!("Policy for {} failed! {:?}", topic, err);
+				let Some(store) = self.dead_letter.as_ref() else {
+					return Err(err);
+				};
+				let dead_letter = DeadLetter {
+					id: *SnowFlake::generate(),
+					topic: topic.to_string(),
+					handler_index: None,
+					payload: payload.to_string(),
+					version: INITIAL_EVENT_VERSION,
+					error: format!("{:?}", err),
+					failed_at: clock().now(),
+				};
+				store.push(&dead_letter).await?;
+				Ok(PolicyOutcome::DeadLettered(dead_letter.error))
+			}
+		}
+	}
+}
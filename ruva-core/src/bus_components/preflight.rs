@@ -0,0 +1,120 @@
+//! ### Preflight check
+//! Deployment may remove event handlers while messages for them are still in flight.
+//! Before the bus starts consuming, topics found in unprocessed rows can be compared against the registered handlers,
+//! and topics waiting on the broker against the routes of [Inbox](super::inbox::Inbox).
+//!
+//! ```rust,ignore
+//! let pending = OutBox::pending_topics(&pool).await?;
+//! let report = MessageBus.preflight(pending).merge(inbox.preflight(&MessageBus, consumer_lagging_topics));
+//! if !report.is_ok() {
+//!     tracing::warn!("Preflight check failed: {:?}", report);
+//! }
+//! ```
+use super::messagebus::TEventHandler;
+
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct PreflightReport {
+	/// Topics found in unprocessed messages for which no handler is registered. Sorted and deduplicated.
+	pub unknown_topics: Vec<String>,
+	/// Inbound topics for which no inbox route is registered, so that their events would be ignored. Sorted and deduplicated.
+	pub unrouted_topics: Vec<String>,
+	/// Inbox routes whose event has no registered handler. Sorted.
+	pub unhandled_routes: Vec<String>,
+}
+
+impl PreflightReport {
+	pub fn is_ok(&self) -> bool {
+		self.unknown_topics.is_empty() && self.unrouted_topics.is_empty() && self.unhandled_routes.is_empty()
+	}
+
+	/// Combine with the report of another check, e.g. outbox check with inbox check.
+	pub fn merge(mut self, other: PreflightReport) -> Self {
+		for (topics, other) in [
+			(&mut self.unknown_topics, other.unknown_topics),
+			(&mut self.unrouted_topics, other.unrouted_topics),
+			(&mut self.unhandled_routes, other.unhandled_routes),
+		] {
+			topics.extend(other);
+			topics.sort();
+			topics.dedup();
+		}
+		self
+	}
+}
+
+/// Compare `pending_topics` with the keys of `event_handler` and report the ones that are not registered.
+pub fn check_pending_topics<E>(event_handler: &TEventHandler<E>, pending_topics: impl IntoIterator<Item = String>) -> PreflightReport {
+	let mut unknown_topics = pending_topics.into_iter().filter(|topic| !event_handler.contains_key(topic)).collect::<Vec<_>>();
+	unknown_topics.sort();
+	unknown_topics.dedup();
+
+	if !unknown_topics.is_empty() {
+		tracing::warn!("Topics without registered handler found: {:?}", unknown_topics);
+	}
+	PreflightReport { unknown_topics, ..Default::default() }
+}
+
+/// Compare inbound `pending_topics`, without namespace, with `routes` of inbox, and `routes` with the keys of `event_handler`.
+/// `routes` are pairs of inbound topic and the topic of the event it is deserialized into.
+pub(crate) fn check_inbox_routes<'a, E>(event_handler: &TEventHandler<E>, routes: impl IntoIterator<Item = (&'a str, &'a str)>, pending_topics: impl IntoIterator<Item = String>) -> PreflightReport {
+	let routes = routes.into_iter().collect::<Vec<_>>();
+
+	let mut unrouted_topics = pending_topics.into_iter().filter(|topic| !routes.iter().any(|(route, _)| route == topic)).collect::<Vec<_>>();
+	unrouted_topics.sort();
+	unrouted_topics.dedup();
+
+	let mut unhandled_routes = routes
+		.iter()
+		.filter(|(_, event_topic)| !event_handler.contains_key(*event_topic))
+		.map(|(route, _)| route.to_string())
+		.collect::<Vec<_>>();
+	unhandled_routes.sort();
+
+	if !unrouted_topics.is_empty() {
+		tracing::warn!("Inbound topics without inbox route found: {:?}", unrouted_topics);
+	}
+	if !unhandled_routes.is_empty() {
+		tracing::warn!("Inbox routes without registered handler found: {:?}", unhandled_routes);
+	}
+	PreflightReport {
+		unrouted_topics,
+		unhandled_routes,
+		..Default::default()
+	}
+}
+
+#[test]
+fn test_check_pending_topics() {
+	use super::handler::EventHandlers;
+
+	let mut event_handler: TEventHandler<()> = Default::default();
+	event_handler.insert("OrderSucceeded".into(), EventHandlers::Sync(vec![]));
+
+	let report = check_pending_topics(&event_handler, vec!["OrderSucceeded".to_string(), "OrderFailed".to_string(), "OrderFailed".to_string()]);
+	assert!(!report.is_ok());
+	assert_eq!(report.unknown_topics, vec!["OrderFailed".to_string()]);
+
+	let report = check_pending_topics(&event_handler, vec!["OrderSucceeded".to_string()]);
+	assert!(report.is_ok());
+}
+
+#[test]
+fn test_check_inbox_routes() {
+	use super::handler::EventHandlers;
+
+	let mut event_handler: TEventHandler<()> = Default::default();
+	event_handler.insert("PaymentDone".into(), EventHandlers::Sync(vec![]));
+
+	let routes = [("payment.done", "PaymentDone"), ("refund.done", "RefundDone")];
+	let report = check_inbox_routes(&event_handler, routes, vec!["payment.done".to_string(), "order.placed".to_string(), "order.placed".to_string()]);
+	assert!(!report.is_ok());
+	assert_eq!(report.unrouted_topics, vec!["order.placed".to_string()]);
+	assert_eq!(report.unhandled_routes, vec!["refund.done".to_string()]);
+
+	let report = check_inbox_routes(&event_handler, [("payment.done", "PaymentDone")], vec!["payment.done".to_string()]);
+	assert!(report.is_ok());
+
+	let merged = check_pending_topics(&event_handler, vec!["OrderFailed".to_string()]).merge(report);
+	assert_eq!(merged.unknown_topics, vec!["OrderFailed".to_string()]);
+	assert!(!merged.is_ok());
+}
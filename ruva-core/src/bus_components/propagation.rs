@@ -0,0 +1,246 @@
+//! ### Trace propagation
+//! Dispatch of a command runs in `command` span with `command.name` attribute, and each event handler cascaded from it
+//! in `event_handler` span under it, with `event.topic` and `handler.index`. Export them with `tracing-opentelemetry` or alike.
+//!
+//! For the trace to go on across services, set [TTracePropagator]. Context of the span that raised externally notifiable event
+//! is injected into `OutBox::trace_context`, sent along with the event by the publisher (`traceparent` header of Kafka)
+//! and extracted by [Inbox](super::inbox::Inbox) as the parent of `inbound_event` span.
+//!
+//! ```rust,ignore
+//! struct W3CPropagator;
+//! impl TTracePropagator for W3CPropagator {
+//!     fn inject(&self) -> Option<String> {
+//!         let mut carrier = HashMap::new();
+//!         TraceContextPropagator::new().inject_context(&Span::current().context(), &mut carrier);
+//!         carrier.remove("traceparent")
+//!     }
+//!     fn extract(&self, span: &Span, carrier: &str) {
+//!         let carrier = HashMap::from([("traceparent".to_string(), carrier.to_string())]);
+//!         span.set_parent(TraceContextPropagator::new().extract(&carrier));
+//!     }
+//! }
+//!
+//! // On boot
+//! set_trace_propagator(W3CPropagator);
+//! enable_outbox_trace_context();
+//! ```
+//! With `opentelemetry` feature, [OtelPropagator](crate::prelude::OtelPropagator) does the above for `tracing-opentelemetry` layer.
+//! [Inbox](super::inbox::Inbox) can be given its own propagator with `with_trace_propagator`, instead of the process-wide one.
+//!
+//! Capture never fails the command or the publication of its events. Nothing is captured when no propagator is set or the current span
+//! has no trace context, and a propagator that panics is logged and treated as such.
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::OnceLock;
+
+use tracing::Span;
+
+/// Carries trace context in and out of the process as text
+pub trait TTracePropagator: Send + Sync {
+	/// Context of the current span, for example W3C `traceparent`
+	fn inject(&self) -> Option<String>;
+	/// Make `span` continue the trace of `carrier`
+	fn extract(&self, span: &Span, carrier: &str);
+}
+
+static TRACE_PROPAGATOR: OnceLock<Box<dyn TTracePropagator>> = OnceLock::new();
+
+/// ## Panics
+/// If trace propagator is already set.
+pub fn set_trace_propagator(propagator: impl TTracePropagator + 'static) {
+	if TRACE_PROPAGATOR.set(Box::new(propagator)).is_err() {
+		panic!("Trace Propagator Is Already Set!");
+	}
+}
+
+/// Context of the current span by the propagator. `None` if it is not set, it has nothing to inject or it panics.
+pub fn inject_trace_context() -> Option<String> {
+	inject_trace_context_with(TRACE_PROPAGATOR.get()?.as_ref())
+}
+
+/// Context of the current span by `propagator`. `None` if it has nothing to inject or it panics.
+pub fn inject_trace_context_with(propagator: &dyn TTracePropagator) -> Option<String> {
+	match catch_unwind(AssertUnwindSafe(|| propagator.inject())) {
+		Ok(carrier) => carrier.filter(|carrier| !carrier.is_empty()),
+		Err(_) => {
+			tracing::warn!("Trace propagator panicked on inject! Trace context is not captured.");
+			None
+		}
+	}
+}
+
+/// Make `span` continue the trace of `carrier`, if any, by the propagator. `span` is left as it is if the propagator panics.
+pub fn extract_trace_context(span: &Span, carrier: Option<&str>) {
+	if let Some(propagator) = TRACE_PROPAGATOR.get() {
+		extract_trace_context_with(propagator.as_ref(), span, carrier);
+	}
+}
+
+/// Make `span` continue the trace of `carrier`, if any, by `propagator`. `span` is left as it is if the propagator panics.
+pub fn extract_trace_context_with(propagator: &dyn TTracePropagator, span: &Span, carrier: Option<&str>) {
+	if let Some(carrier) = carrier {
+		if catch_unwind(AssertUnwindSafe(|| propagator.extract(span, carrier))).is_err() {
+			tracing::warn!("Trace propagator panicked on extract! {} is not continued.", carrier);
+		}
+	}
+}
+
+static OUTBOX_TRACE_CONTEXT: AtomicBool = AtomicBool::new(false);
+
+/// Store `OutBox::trace_context` of rows written from now on. Requires `trace_context` column of `service_outbox`.
+/// ```sql
+/// ALTER TABLE service_outbox ADD COLUMN trace_context TEXT;
+/// ```
+pub fn enable_outbox_trace_context() {
+	OUTBOX_TRACE_CONTEXT.store(true, Ordering::Relaxed);
+}
+
+pub fn outbox_trace_context_enabled() -> bool {
+	OUTBOX_TRACE_CONTEXT.load(Ordering::Relaxed)
+}
+
+pub(crate) fn command_span(command: &str) -> Span {
+	tracing::info_span!("command", command.name = command)
+}
+
+pub(crate) fn handler_span(topic: &str, index: usize) -> Span {
+	tracing::info_span!("event_handler", event.topic = topic, handler.index = index)
+}
+
+pub(crate) fn inbound_span(topic: &str) -> Span {
+	tracing::info_span!("inbound_event", event.topic = topic)
+}
+
+#[cfg(test)]
+mod test {
+	use std::sync::atomic::AtomicU64;
+	use std::sync::{Arc, Mutex};
+	use tracing::span::{Attributes, Id, Record};
+	use tracing::{Event, Instrument, Metadata, Subscriber};
+
+	use crate::prelude::{
+		BaseError, ContextManager, EventHandlers, InMemoryInboxStore, InboundEvent, Inbox, InboxOutcome, TConnection, TEvent, TEventBus, TEventHandler, TTopic, INITIAL_EVENT_VERSION,
+	};
+
+	/// Records name and parent of every span
+	#[derive(Default)]
+	struct SpanRecorder {
+		next_id: AtomicU64,
+		spans: Mutex<Vec<(&'static str, Option<u64>)>>,
+		stack: Mutex<Vec<u64>>,
+	}
+
+	impl Subscriber for SpanRecorder {
+		fn enabled(&self, _: &Metadata<'_>) -> bool {
+			true
+		}
+		fn new_span(&self, span: &Attributes<'_>) -> Id {
+			let id = self.next_id.fetch_add(1, std::sync::atomic::Ordering::SeqCst) + 1;
+			let parent = match span.parent() {
+				Some(parent) => Some(parent.into_u64()),
+				None if span.is_contextual() => self.stack.lock().unwrap().last().copied(),
+				None => None,
+			};
+			self.spans.lock().unwrap().push((span.metadata().name(), parent));
+			Id::from_u64(id)
+		}
+		fn record(&self, _: &Id, _: &Record<'_>) {}
+		fn record_follows_from(&self, _: &Id, _: &Id) {}
+		fn event(&self, _: &Event<'_>) {}
+		fn enter(&self, span: &Id) {
+			self.stack.lock().unwrap().push(span.into_u64());
+		}
+		fn exit(&self, _: &Id) {
+			self.stack.lock().unwrap().pop();
+		}
+	}
+
+	struct Connection;
+	impl TConnection for Connection {}
+
+	#[derive(serde::Deserialize)]
+	struct StockReserved {}
+	impl TEvent for StockReserved {
+		fn internally_notifiable(&self) -> bool {
+			true
+		}
+		fn state(&self) -> String {
+			"{}".into()
+		}
+	}
+	impl TTopic for StockReserved {
+		const TOPIC: &'static str = "StockReserved";
+	}
+
+	struct Bus;
+	impl TEventBus<BaseError> for Bus {
+		fn event_handler(&self) -> &'static TEventHandler<BaseError> {
+			static EVENT_HANDLER: std::sync::LazyLock<TEventHandler<BaseError>> = std::sync::LazyLock::new(|| {
+				let mut map = TEventHandler::default();
+				map.insert(
+					"StockReserved".to_string(),
+					EventHandlers::Sync(vec![Box::new(|_, _| Box::pin(async { Ok(()) })), Box::new(|_, _| Box::pin(async { Ok(()) }))]),
+				);
+				map
+			});
+			&EVENT_HANDLER
+		}
+	}
+
+	#[tokio::test]
+	async fn test_event_handler_spans() {
+		let recorder = Arc::new(SpanRecorder::default());
+		let _guard = tracing::subscriber::set_default(Arc::clone(&recorder));
+
+		Bus.handle_events(vec![Arc::new(StockReserved {})], ContextManager::new(&Connection))
+			.instrument(super::command_span("ReserveStock"))
+			.await
+			.unwrap();
+		assert_eq!(*recorder.spans.lock().unwrap(), vec![("command", None), ("event_handler", Some(1)), ("event_handler", Some(1))]);
+	}
+
+	#[tokio::test]
+	async fn test_inbound_event_span() {
+		let recorder = Arc::new(SpanRecorder::default());
+		let _guard = tracing::subscriber::set_default(Arc::clone(&recorder));
+
+		let inbox = Inbox::new(&Connection, InMemoryInboxStore::default()).register::<StockReserved>();
+		let event = InboundEvent {
+			id: "1".into(),
+			topic: "StockReserved".into(),
+			payload: "{}".into(),
+			version: INITIAL_EVENT_VERSION,
+			trace_context: Some("00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01".into()),
+			correlation_id: None,
+		};
+		inbox.receive(&Bus, &event).await.unwrap();
+		assert_eq!(*recorder.spans.lock().unwrap(), vec![("inbound_event", None), ("event_handler", Some(1)), ("event_handler", Some(1))]);
+	}
+
+	#[tokio::test]
+	async fn test_panicking_propagator_is_tolerated() {
+		struct Panicking;
+		impl super::TTracePropagator for Panicking {
+			fn inject(&self) -> Option<String> {
+				panic!("No tracer provider installed")
+			}
+			fn extract(&self, _: &tracing::Span, _: &str) {
+				panic!("No tracer provider installed")
+			}
+		}
+
+		assert_eq!(super::inject_trace_context_with(&Panicking), None);
+		super::extract_trace_context_with(&Panicking, &tracing::Span::none(), Some("00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01"));
+
+		let inbox = Inbox::new(&Connection, InMemoryInboxStore::default()).register::<StockReserved>().with_trace_propagator(Panicking);
+		let event = InboundEvent {
+			id: "1".into(),
+			topic: "StockReserved".into(),
+			payload: "{}".into(),
+			version: INITIAL_EVENT_VERSION,
+			trace_context: Some("00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01".into()),
+			correlation_id: None,
+		};
+		assert_eq!(inbox.receive(&Bus, &event).await.unwrap(), InboxOutcome::Handled);
+	}
+}
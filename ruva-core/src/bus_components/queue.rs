@@ -0,0 +1,341 @@
+//! ### Queued execution
+//! Slow command - report generation, bulk export and so on - shouldn't hold the API request until it is done.
+//! [CommandQueue] runs each registered command either inline or through a durable queue in [TCommandQueueStore],
+//! as its [ExecutionStrategy] says, which can be changed while the process is running.
+//! Queued command is answered with a ticket id right away, and the status of the ticket is queried later.
+//!
+//! ```rust,ignore
+//! // On boot. `PgPool` and `SqlitePool` implement `TCommandQueueStore` with `sqlx-postgres` and `sqlx-sqlite` features.
+//! let queue = Arc::new(CommandQueue::new(conn, pool.clone()).register::<GenerateReport>(ExecutionStrategy::Queued).register::<RenameReport>(ExecutionStrategy::Inline));
+//! tokio::spawn({
+//!     let queue = queue.clone();
+//!     async move { queue.run(Duration::from_secs(1), bus_shutdown_token()).await }
+//! });
+//!
+//! // In your API
+//! match queue.dispatch(GenerateReport { .. }, ContextManager::new(conn).with_actor(actor)).await? {
+//!     Execution::Done(response) => Json(response).into_response(),
+//!     Execution::Queued(ticket_id) => (StatusCode::ACCEPTED, Json(ticket_id)).into_response(),
+//! }
+//!
+//! // Later, by the one who queued it
+//! let ticket = queue.status(ticket_id, &actor).await?;
+//! ```
+//! Queued command is run by the worker with the context it was queued in - see [Deferred] - and its response is kept serialized in the ticket.
+//! Worker holds the ticket for [CommandQueue::with_lease], which should be longer than the command can take. Ticket of a worker
+//! that crashed is claimed again by another once it expires, so queued command may run more than once.
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::Duration;
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+use super::actor::Actor;
+use super::contexts::ContextManager;
+use super::executor::TConnection;
+use super::messagebus::{MessageBus, TMessageBus};
+use super::shutdown::ShutdownToken;
+use super::snapshot::Deferred;
+use crate::prelude::{clock, BaseError, SnowFlake, TCommandSpec};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ExecutionStrategy {
+	/// Run in the dispatching task and answer with the response
+	#[default]
+	Inline,
+	/// Put on the queue and answer with the ticket id
+	Queued,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum TicketStatus {
+	Queued,
+	Running,
+	Succeeded,
+	Failed,
+}
+
+impl TicketStatus {
+	pub fn as_str(&self) -> &'static str {
+		match self {
+			TicketStatus::Queued => "queued",
+			TicketStatus::Running => "running",
+			TicketStatus::Succeeded => "succeeded",
+			TicketStatus::Failed => "failed",
+		}
+	}
+
+	pub fn parse(status: &str) -> Option<Self> {
+		match status {
+			"queued" => Some(TicketStatus::Queued),
+			"running" => Some(TicketStatus::Running),
+			"succeeded" => Some(TicketStatus::Succeeded),
+			"failed" => Some(TicketStatus::Failed),
+			_ => None,
+		}
+	}
+}
+
+/// Queued command and its result
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Ticket {
+	pub id: i64,
+	/// Type name of the command
+	pub command: String,
+	/// Command serialized together with the context it was queued in
+	pub payload: String,
+	pub status: TicketStatus,
+	/// Actor the command is queued by, who can query the ticket
+	pub owner: Actor,
+	/// Until when the worker running the ticket holds it
+	pub claimed_until: Option<DateTime<Utc>>,
+	/// Serialized response, once succeeded
+	pub response: Option<String>,
+	/// Once failed
+	pub error: Option<String>,
+	pub queued_at: DateTime<Utc>,
+	pub updated_at: DateTime<Utc>,
+}
+
+#[async_trait]
+pub trait TCommandQueueStore: Send + Sync {
+	async fn enqueue(&self, ticket: &Ticket) -> Result<(), BaseError>;
+	/// Mark the oldest ticket that is queued, or running with its claim expired, running until `claimed_until` and return it.
+	/// Ticket is taken by only one of the workers.
+	async fn claim_next(&self, claimed_until: DateTime<Utc>) -> Result<Option<Ticket>, BaseError>;
+	/// Record the result of the ticket claimed until `claimed_until`, as [claim_next](TCommandQueueStore::claim_next) returned it.
+	/// `result` is the serialized response, or the error. Returns `false` if the claim has expired and the ticket is claimed again by another worker.
+	async fn finish(&self, id: i64, claimed_until: DateTime<Utc>, result: Result<String, String>) -> Result<bool, BaseError>;
+	async fn get_ticket(&self, id: i64) -> Result<Option<Ticket>, BaseError>;
+}
+
+#[async_trait]
+impl<T: TCommandQueueStore + ?Sized> TCommandQueueStore for Arc<T> {
+	async fn enqueue(&self, ticket: &Ticket) -> Result<(), BaseError> {
+		self.as_ref().enqueue(ticket).await
+	}
+	async fn claim_next(&self, claimed_until: DateTime<Utc>) -> Result<Option<Ticket>, BaseError> {
+		self.as_ref().claim_next(claimed_until).await
+	}
+	async fn finish(&self, id: i64, claimed_until: DateTime<Utc>, result: Result<String, String>) -> Result<bool, BaseError> {
+		self.as_ref().finish(id, claimed_until, result).await
+	}
+	async fn get_ticket(&self, id: i64) -> Result<Option<Ticket>, BaseError> {
+		self.as_ref().get_ticket(id).await
+	}
+}
+
+/// For tests and single instance deployments. Tickets are lost on restart.
+#[derive(Default)]
+pub struct InMemoryCommandQueueStore(Mutex<std::collections::BTreeMap<i64, Ticket>>);
+
+#[async_trait]
+impl TCommandQueueStore for InMemoryCommandQueueStore {
+	async fn enqueue(&self, ticket: &Ticket) -> Result<(), BaseError> {
+		self.0.lock().unwrap().insert(ticket.id, ticket.clone());
+		Ok(())
+	}
+	async fn claim_next(&self, claimed_until: DateTime<Utc>) -> Result<Option<Ticket>, BaseError> {
+		let now = clock().now();
+		let mut tickets = self.0.lock().unwrap();
+		let Some(ticket) = tickets.values_mut().find(|ticket| match ticket.status {
+			TicketStatus::Queued => true,
+			TicketStatus::Running => ticket.claimed_until.is_some_and(|until| until < now),
+			TicketStatus::Succeeded | TicketStatus::Failed => false,
+		}) else {
+			return Ok(None);
+		};
+		ticket.status = TicketStatus::Running;
+		ticket.claimed_until = Some(claimed_until);
+		ticket.updated_at = now;
+		Ok(Some(ticket.clone()))
+	}
+	async fn finish(&self, id: i64, claimed_until: DateTime<Utc>, result: Result<String, String>) -> Result<bool, BaseError> {
+		let mut tickets = self.0.lock().unwrap();
+		let ticket = tickets.get_mut(&id).ok_or(BaseError::NotFound)?;
+		if ticket.status != TicketStatus::Running || ticket.claimed_until != Some(claimed_until) {
+			return Ok(false);
+		}
+		ticket.updated_at = clock().now();
+		ticket.claimed_until = None;
+		match result {
+			Ok(response) => (ticket.status, ticket.response) = (TicketStatus::Succeeded, Some(response)),
+			Err(error) => (ticket.status, ticket.error) = (TicketStatus::Failed, Some(error)),
+		}
+		Ok(true)
+	}
+	async fn get_ticket(&self, id: i64) -> Result<Option<Ticket>, BaseError> {
+		Ok(self.0.lock().unwrap().get(&id).cloned())
+	}
+}
+
+/// Result of [CommandQueue::dispatch]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Execution<R> {
+	Done(R),
+	/// Id of the ticket
+	Queued(i64),
+}
+
+type QueuedFuture = Pin<Box<dyn Future<Output = Result<String, BaseError>> + Send>>;
+type QueuedHandler = Box<dyn Fn(String, &'static dyn TConnection) -> QueuedFuture + Send + Sync>;
+
+pub struct CommandQueue<S> {
+	conn: &'static dyn TConnection,
+	store: S,
+	handlers: hashbrown::HashMap<&'static str, QueuedHandler>,
+	strategies: RwLock<hashbrown::HashMap<&'static str, ExecutionStrategy>>,
+	lease: Duration,
+}
+
+impl<S: TCommandQueueStore> CommandQueue<S> {
+	/// Queued commands are run on `conn`
+	pub fn new(conn: &'static dyn TConnection, store: S) -> Self {
+		Self {
+			conn,
+			store,
+			handlers: Default::default(),
+			strategies: Default::default(),
+			lease: Duration::from_secs(300),
+		}
+	}
+
+	/// How long a worker holds the ticket it claimed, 5 minutes by default. Should be longer than the command can take,
+	/// as the ticket is claimed again by another worker once it expires.
+	pub fn with_lease(mut self, lease: Duration) -> Self {
+		self.lease = lease;
+		self
+	}
+
+	/// Let `C` be run through the queue, starting with `strategy`
+	pub fn register<C>(mut self, strategy: ExecutionStrategy) -> Self
+	where
+		C: TCommandSpec + Serialize + DeserializeOwned,
+		C::Response: Serialize,
+		C::Error: std::convert::From<BaseError>,
+		BaseError: std::convert::From<C::Error>,
+		MessageBus: TMessageBus<C::Response, C::Error, C>,
+	{
+		let name = std::any::type_name::<C>();
+		self.handlers.insert(
+			name,
+			Box::new(|payload, conn| {
+				Box::pin(async move {
+					let deferred: Deferred<C> = serde_json::from_str(&payload).map_err(|err| BaseError::DatabaseError(err.to_string()))?;
+					let (context_manager, cmd) = deferred.restore(conn);
+					let res = MessageBus.dispatch_with(cmd, context_manager).await.map_err(BaseError::from)?;
+					serde_json::to_string(&res).map_err(|err| BaseError::DatabaseError(err.to_string()))
+				})
+			}),
+		);
+		self.strategies.get_mut().unwrap().insert(name, strategy);
+		self
+	}
+
+	/// Change how `C` is run from now on. Unregistered command is always run inline.
+	pub fn set_strategy<C: TCommandSpec>(&self, strategy: ExecutionStrategy) {
+		let name = std::any::type_name::<C>();
+		if !self.handlers.contains_key(name) {
+			tracing::warn!(command = name, "Strategy of unregistered command is ignored.");
+			return;
+		}
+		tracing::info!(command = name, "Execution strategy changed to {:?}", strategy);
+		self.strategies.write().unwrap().insert(name, strategy);
+	}
+
+	pub fn strategy<C: TCommandSpec>(&self) -> ExecutionStrategy {
+		self.strategies.read().unwrap().get(std::any::type_name::<C>()).copied().unwrap_or_default()
+	}
+
+	/// Run `cmd` inline or put it on the queue, as its strategy says.
+	pub async fn dispatch<C>(&self, cmd: C, context_manager: ContextManager) -> Result<Execution<C::Response>, C::Error>
+	where
+		C: TCommandSpec + Serialize,
+		C::Error: std::convert::From<BaseError>,
+		BaseError: std::convert::From<C::Error>,
+		MessageBus: TMessageBus<C::Response, C::Error, C>,
+	{
+		match self.strategy::<C>() {
+			ExecutionStrategy::Inline => MessageBus.dispatch_with(cmd, context_manager).await.map(Execution::Done),
+			ExecutionStrategy::Queued => Ok(Execution::Queued(self.enqueue(cmd, &context_manager).await?)),
+		}
+	}
+
+	/// Put `cmd` on the queue regardless of its strategy. Returns the id of the ticket.
+	pub async fn enqueue<C: TCommandSpec + Serialize>(&self, cmd: C, context_manager: &ContextManager) -> Result<i64, BaseError> {
+		let command = std::any::type_name::<C>();
+		if !self.handlers.contains_key(command) {
+			return Err(BaseError::HandlerNotFound(command.to_string()));
+		}
+		let now = clock().now();
+		let ticket = Ticket {
+			id: *SnowFlake::generate(),
+			command: command.to_string(),
+			payload: serde_json::to_string(&Deferred::new(context_manager, cmd)).map_err(|err| BaseError::DatabaseError(err.to_string()))?,
+			status: TicketStatus::Queued,
+			owner: context_manager.actor.clone(),
+			claimed_until: None,
+			response: None,
+			error: None,
+			queued_at: now,
+			updated_at: now,
+		};
+		self.store.enqueue(&ticket).await?;
+		Ok(ticket.id)
+	}
+
+	/// Ticket queued by `actor`. Ticket of someone else is answered with `BaseError::Forbidden`, unless `actor` is a system actor.
+	pub async fn status(&self, ticket_id: i64, actor: &Actor) -> Result<Option<Ticket>, BaseError> {
+		let Some(ticket) = self.store.get_ticket(ticket_id).await? else {
+			return Ok(None);
+		};
+		let owned = actor.effective_user().is_some_and(|user| ticket.owner.effective_user() == Some(user));
+		if !owned && !actor.is_system() {
+			return Err(BaseError::Forbidden(format!("Ticket {} is not queued by {}", ticket_id, actor)));
+		}
+		Ok(Some(ticket))
+	}
+
+	/// Run the oldest queued command. Returns its id, or `None` if the queue is empty.
+	pub async fn process_next(&self) -> Result<Option<i64>, BaseError> {
+		let claimed_until = clock().now() + self.lease;
+		let Some(ticket) = self.store.claim_next(claimed_until).await? else {
+			return Ok(None);
+		};
+		// * As stored, which may be less precise than the one given
+		let claimed_until = ticket.claimed_until.unwrap_or(claimed_until);
+		let result = match self.handlers.get(ticket.command.as_str()) {
+			Some(handler) => handler(ticket.payload, self.conn).await.map_err(|err| format!("{:?}", err)),
+			None => Err(format!("{} is not registered", ticket.command)),
+		};
+		if let Err(err) = result.as_ref() {
+			tracing::error!(command = ticket.command, ticket = ticket.id, "Queued command failed! {}", err);
+		}
+		if !self.store.finish(ticket.id, claimed_until, result).await? {
+			tracing::warn!(
+				command = ticket.command,
+				ticket = ticket.id,
+				"Lease expired and ticket is claimed again by another worker. Result is discarded."
+			);
+		}
+		Ok(Some(ticket.id))
+	}
+
+	/// Run queued commands one by one until `shutdown` is signalled, polling every `poll_interval` while the queue is empty.
+	pub async fn run(&self, poll_interval: Duration, shutdown: &ShutdownToken) {
+		while !shutdown.is_shutdown() {
+			match self.process_next().await {
+				Ok(Some(_)) => continue,
+				Ok(None) => {}
+				Err(err) => tracing::error!("Failed to process command queue! {:?}", err),
+			}
+			tokio::select! {
+				_ = tokio::time::sleep(poll_interval) => {},
+				_ = shutdown.cancelled() => break,
+			}
+		}
+	}
+}
@@ -0,0 +1,123 @@
+//! ### Replay protection
+//! Security-sensitive commands such as password reset or payout carry envelope id issued by [SnowFlake].
+//! [ReplayProtectionAspect] rejects the command whose id is older than the window, ahead of the clock by more than the
+//! allowed skew, or already seen.
+//! As the timestamp is part of snowflake, the check needs no lookup other than ids seen within the window.
+//!
+//! ```rust,ignore
+//! static PAYOUT_REPLAY_GUARD: LazyLock<ReplayGuard> = LazyLock::new(|| ReplayGuard::new(Duration::from_secs(300)));
+//!
+//! impl TReplayProtected for RequestPayout {
+//!     fn envelope_id(&self) -> SnowFlake {
+//!         self.request_id
+//!     }
+//! }
+//!
+//! impl TCommandRoute for RequestPayout {
+//!     fn command_handler(context_manager: AtomicContextManager, cmd: Self) -> impl TCommandService<Self::Response, Self::Error> {
+//!         ReplayProtectionAspect::new(&PAYOUT_REPLAY_GUARD, cmd.envelope_id(), CommandHandler((cmd, SqlRepository::new(context_manager))))
+//!     }
+//! }
+//! ```
+//! Seen ids are kept in memory, so the guard protects single instance. Within the window, replays across instances must be caught by the handler.
+use std::collections::BTreeSet;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use super::messagebus::TCommandService;
+use crate::prelude::{ApplicationError, ApplicationResponse, BaseError, SnowFlake, TCommand};
+
+pub trait TReplayProtected: TCommand {
+	/// Id issued when the command was first made, not when it is received.
+	fn envelope_id(&self) -> SnowFlake;
+}
+
+pub struct ReplayGuard {
+	window: Duration,
+	allowed_skew: Duration,
+	seen: Mutex<BTreeSet<i64>>,
+}
+
+impl ReplayGuard {
+	/// Clocks of the issuers are allowed to be ahead by 5 seconds.
+	pub fn new(window: Duration) -> Self {
+		Self {
+			window,
+			allowed_skew: Duration::from_secs(5),
+			seen: Default::default(),
+		}
+	}
+
+	/// How far ahead of this instance the clock of the issuer may be. Id from further in the future is rejected,
+	/// as it would otherwise stay valid for that long on top of the window.
+	pub fn with_allowed_skew(mut self, allowed_skew: Duration) -> Self {
+		self.allowed_skew = allowed_skew;
+		self
+	}
+
+	/// Accept `id` and remember it, or reject it if it is out of window or seen before.
+	pub fn check(&self, id: SnowFlake) -> Result<(), BaseError> {
+		self.check_at(id, crate::prelude::clock().now().timestamp_millis())
+	}
+
+	fn check_at(&self, id: SnowFlake, now_millis: i64) -> Result<(), BaseError> {
+		let oldest = now_millis - self.window.as_millis() as i64;
+		if id.timestamp_millis() < oldest {
+			return Err(BaseError::Rejected(format!("{} is out of replay window", id)));
+		}
+		if id.timestamp_millis() > now_millis + self.allowed_skew.as_millis() as i64 {
+			return Err(BaseError::Rejected(format!("{} is issued in the future", id)));
+		}
+
+		let mut seen = self.seen.lock().unwrap();
+		// Ids are ordered by time, so ids that went out of window are the ones below the smallest id of `oldest`.
+		*seen = seen.split_off(&(oldest << 22));
+		if !seen.insert(*id) {
+			return Err(BaseError::Rejected(format!("{} is replayed", id)));
+		}
+		Ok(())
+	}
+}
+
+/// Check envelope id before running `inner` service.
+pub struct ReplayProtectionAspect<S> {
+	guard: &'static ReplayGuard,
+	id: SnowFlake,
+	inner: S,
+}
+
+impl<S> ReplayProtectionAspect<S> {
+	pub fn new(guard: &'static ReplayGuard, id: SnowFlake, inner: S) -> Self {
+		Self { guard, id, inner }
+	}
+}
+
+impl<R, E, S> TCommandService<R, E> for ReplayProtectionAspect<S>
+where
+	R: ApplicationResponse,
+	E: ApplicationError + std::convert::From<BaseError>,
+	S: TCommandService<R, E>,
+{
+	async fn execute(self) -> Result<R, E> {
+		self.guard.check(self.id)?;
+		self.inner.execute().await
+	}
+}
+
+#[test]
+fn test_replay_guard() {
+	let guard = ReplayGuard::new(Duration::from_secs(60));
+	let now = chrono::Utc::now().timestamp_millis();
+	let id_at = |millis: i64| SnowFlake(millis << 22);
+
+	assert!(guard.check_at(id_at(now - 1_000), now).is_ok());
+	assert!(matches!(guard.check_at(id_at(now - 1_000), now), Err(BaseError::Rejected(_))));
+	assert!(matches!(guard.check_at(id_at(now - 61_000), now), Err(BaseError::Rejected(_))));
+	// Within the skew, but not beyond
+	assert!(guard.check_at(id_at(now + 4_000), now).is_ok());
+	assert!(matches!(guard.check_at(id_at(now + 6_000), now), Err(BaseError::Rejected(_))));
+
+	// Seen id that went out of window is forgotten
+	assert!(guard.check_at(id_at(now), now + 59_500).is_ok());
+	assert_eq!(guard.seen.lock().unwrap().len(), 2);
+}
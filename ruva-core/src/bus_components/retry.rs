@@ -0,0 +1,158 @@
+//! ### Retry
+//! [RetryHandler] runs the command service again when it fails with retryable error, by default
+//! `BaseError::TransactionConflict` which serialization failure and deadlock are converted into.
+//! As [TCommandService] is consumed on execution, the handler is given a closure that makes a fresh service for every attempt.
+//!
+//! ```rust,ignore
+//! impl TCommandRoute for TransferMoney {
+//!     fn command_handler(context_manager: AtomicContextManager, cmd: Self) -> impl TCommandService<Self::Response, Self::Error> {
+//!         RetryHandler::new(move || CommandHandler((cmd.clone(), Context::new(context_manager.clone()))))
+//!             .with_max_attempts(5)
+//!             .with_backoff(Backoff { initial: Duration::from_millis(20), max: Duration::from_secs(1) })
+//!     }
+//! }
+//! ```
+//! Each attempt runs in its own transaction, so events of failed attempts are rolled back with it.
+//!
+//! Among other aspects, add it as the innermost layer with [retry_layer], given the closure that makes the service.
+//! ```rust,ignore
+//! ServiceBuilder::new()
+//!     .layer(aspect_fn(|inner| IdempotencyAspect::new(&context_manager, &cmd, inner)))
+//!     .layer(retry_layer().with_max_attempts(5))
+//!     .try_service(move || CommandHandler((cmd.clone(), Context::new(context_manager.clone()))))
+//! ```
+use std::hash::{BuildHasher, Hasher};
+
+use super::aspect::AspectMetadata;
+use super::layer::TLayer;
+use super::messagebus::TCommandService;
+use crate::prelude::{ApplicationError, ApplicationResponse, Backoff, BaseError};
+
+pub struct RetryHandler<F> {
+	make_service: F,
+	max_attempts: u32,
+	backoff: Backoff,
+	jitter: f64,
+	retry_if: fn(&BaseError) -> bool,
+}
+
+impl<F> RetryHandler<F> {
+	/// Up to 3 attempts with backoff from 50ms, jittered by half, on [BaseError::is_retryable] errors.
+	pub fn new(make_service: F) -> Self {
+		Self {
+			make_service,
+			max_attempts: 3,
+			backoff: Backoff {
+				initial: std::time::Duration::from_millis(50),
+				max: std::time::Duration::from_secs(2),
+			},
+			jitter: 0.5,
+			retry_if: BaseError::is_retryable,
+		}
+	}
+
+	/// Including the first attempt
+	pub fn with_max_attempts(mut self, max_attempts: u32) -> Self {
+		self.max_attempts = max_attempts.max(1);
+		self
+	}
+
+	pub fn with_backoff(mut self, backoff: Backoff) -> Self {
+		self.backoff = backoff;
+		self
+	}
+
+	/// Portion of the delay randomly cut off, from 0.0 to 1.0, so that conflicting commands don't retry in lockstep.
+	pub fn with_jitter(mut self, jitter: f64) -> Self {
+		self.jitter = jitter.clamp(0.0, 1.0);
+		self
+	}
+
+	pub fn with_retry_if(mut self, retry_if: fn(&BaseError) -> bool) -> Self {
+		self.retry_if = retry_if;
+		self
+	}
+
+	fn delay(&self, failures: u32) -> std::time::Duration {
+		let random = std::collections::hash_map::RandomState::new().build_hasher().finish() as f64 / u64::MAX as f64;
+		self.backoff.delay(failures).mul_f64(1.0 - self.jitter * random)
+	}
+}
+
+/// [RetryHandler] with default settings, as layer wrapping the closure that makes the service
+pub fn retry_layer() -> RetryHandler<()> {
+	RetryHandler::new(())
+}
+
+impl<F> TLayer<F> for RetryHandler<()> {
+	type Service = RetryHandler<F>;
+	fn layer(&self, make_service: F) -> Self::Service {
+		RetryHandler {
+			make_service,
+			max_attempts: self.max_attempts,
+			backoff: self.backoff,
+			jitter: self.jitter,
+			retry_if: self.retry_if,
+		}
+	}
+	fn aspects(&self) -> Vec<AspectMetadata> {
+		vec![AspectMetadata::of::<Self>()]
+	}
+}
+
+impl<R, E, S, F> TCommandService<R, E> for RetryHandler<F>
+where
+	R: ApplicationResponse,
+	E: ApplicationError + Clone,
+	BaseError: std::convert::From<E>,
+	S: TCommandService<R, E>,
+	F: Fn() -> S + Send + Sync,
+{
+	async fn execute(self) -> Result<R, E> {
+		let mut attempt = 1;
+		loop {
+			match (self.make_service)().execute().await {
+				Err(err) if attempt < self.max_attempts && (self.retry_if)(&err.clone().into()) => {
+					let delay = self.delay(attempt);
+					tracing::warn!(attempt, "Retrying command in {:?}. {:?}", delay, err);
+					tokio::time::sleep(delay).await;
+					attempt += 1;
+				}
+				res => return res,
+			}
+		}
+	}
+}
+
+#[tokio::test]
+async fn test_retry_handler() {
+	use std::sync::atomic::{AtomicU32, Ordering};
+
+	struct Flaky(&'static AtomicU32, u32);
+	impl TCommandService<(), BaseError> for Flaky {
+		async fn execute(self) -> Result<(), BaseError> {
+			match self.0.fetch_add(1, Ordering::SeqCst) + 1 < self.1 {
+				true => Err(BaseError::TransactionConflict("could not serialize access".into())),
+				false => Ok(()),
+			}
+		}
+	}
+	let no_delay = Backoff {
+		initial: std::time::Duration::ZERO,
+		max: std::time::Duration::ZERO,
+	};
+
+	static SUCCEEDS_AT_THIRD: AtomicU32 = AtomicU32::new(0);
+	assert!(RetryHandler::new(|| Flaky(&SUCCEEDS_AT_THIRD, 3)).with_backoff(no_delay).execute().await.is_ok());
+	assert_eq!(SUCCEEDS_AT_THIRD.load(Ordering::SeqCst), 3);
+
+	static GIVES_UP: AtomicU32 = AtomicU32::new(0);
+	let res = RetryHandler::new(|| Flaky(&GIVES_UP, 10)).with_backoff(no_delay).with_max_attempts(2).execute().await;
+	assert!(matches!(res, Err(BaseError::TransactionConflict(_))));
+	assert_eq!(GIVES_UP.load(Ordering::SeqCst), 2);
+
+	static NOT_RETRYABLE: AtomicU32 = AtomicU32::new(0);
+	let res = RetryHandler::new(|| Flaky(&NOT_RETRYABLE, 10)).with_retry_if(|_| false).execute().await;
+	assert!(res.is_err());
+	assert_eq!(NOT_RETRYABLE.load(Ordering::SeqCst), 1);
+}
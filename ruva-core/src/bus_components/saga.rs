@@ -0,0 +1,370 @@
+//! ### Saga
+//! Process manager for long-running workflow that spans several aggregates.
+//! Saga keeps its state between events, keyed by correlation id, and each step it takes is registered as event handler.
+//! Steps register compensations as they go, and when a step fails, the compensations are run in reverse order.
+//!
+//! ```rust,ignore
+//! #[derive(Default, Serialize, Deserialize)]
+//! struct OrderSaga {
+//!     payment_id: Option<i64>,
+//! }
+//!
+//! #[derive(Serialize, Deserialize)]
+//! enum OrderCompensation {
+//!     ReleaseStock { order_id: i64 },
+//!     Refund { payment_id: i64 },
+//! }
+//!
+//! impl TSaga for OrderSaga {
+//!     const NAME: &'static str = "order";
+//!     type Compensation = OrderCompensation;
+//!     async fn compensate(compensation: OrderCompensation, context_manager: AtomicContextManager) -> Result<(), BaseError> {
+//!         match compensation {
+//!             OrderCompensation::ReleaseStock { order_id } => MessageBus.dispatch_with(ReleaseStock { order_id }, ContextManager::new(context_manager.conn)).await,
+//!             OrderCompensation::Refund { payment_id } => MessageBus.dispatch_with(Refund { payment_id }, ContextManager::new(context_manager.conn)).await,
+//!         }
+//!         .map(|_| ())
+//!         .map_err(BaseError::from)
+//!     }
+//! }
+//!
+//! impl TSagaStep<StockReserved> for OrderSaga {
+//!     fn saga_id(event: &StockReserved) -> String {
+//!         event.order_id.to_string()
+//!     }
+//!     async fn react(saga: &mut SagaInstance<Self>, event: StockReserved, context_manager: AtomicContextManager) -> Result<(), BaseError> {
+//!         saga.on_failure(OrderCompensation::ReleaseStock { order_id: event.order_id });
+//!         MessageBus.dispatch_with(RequestPayment { order_id: event.order_id }, ContextManager::new(context_manager.conn)).await?;
+//!         Ok(())
+//!     }
+//! }
+//!
+//! init_event_handler!(
+//!     ServiceError,
+//!     |ctx: AtomicContextManager| SagaHandler::<OrderSaga, _>::new(ctx.clone(), Context::new(ctx)),
+//!     StockReserved: [react],
+//!     PaymentCompleted: [react],
+//!     ShipmentFailed: [react],
+//! );
+//! ```
+//! Saga that is completed or compensated ignores further events.
+use std::future::Future;
+
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+use super::contexts::AtomicContextManager;
+use crate::prelude::{BaseError, TUnitOfWork};
+
+pub trait TSaga: Serialize + DeserializeOwned + Default + Send + Sync + 'static {
+	/// Distinguishes persisted state of this saga from the others
+	const NAME: &'static str;
+
+	/// What to undo when the saga fails, usually enum of commands
+	type Compensation: Serialize + DeserializeOwned + Send + Sync;
+
+	fn compensate(compensation: Self::Compensation, context_manager: AtomicContextManager) -> impl Future<Output = Result<(), BaseError>> + Send;
+}
+
+/// Reaction of saga to event `E`
+pub trait TSagaStep<E>: TSaga {
+	/// Correlation id of the saga instance the event belongs to
+	fn saga_id(event: &E) -> String;
+
+	fn react(saga: &mut SagaInstance<Self>, event: E, context_manager: AtomicContextManager) -> impl Future<Output = Result<(), BaseError>> + Send;
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum SagaStatus {
+	Running,
+	Completed,
+	/// Failed and every compensation is run
+	Compensated,
+	/// Compensation failed as well. Needs manual intervention.
+	Failed(String),
+}
+
+impl SagaStatus {
+	pub fn as_str(&self) -> &'static str {
+		match self {
+			Self::Running => "running",
+			Self::Completed => "completed",
+			Self::Compensated => "compensated",
+			Self::Failed(_) => "failed",
+		}
+	}
+
+	pub fn is_finished(&self) -> bool {
+		!matches!(self, Self::Running)
+	}
+}
+
+/// Saga state with its progress
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(bound = "")]
+pub struct SagaInstance<S: TSaga> {
+	pub id: String,
+	pub status: SagaStatus,
+	pub state: S,
+	compensations: Vec<S::Compensation>,
+}
+
+impl<S: TSaga> SagaInstance<S> {
+	pub fn new(id: impl Into<String>) -> Self {
+		Self {
+			id: id.into(),
+			status: SagaStatus::Running,
+			state: S::default(),
+			compensations: vec![],
+		}
+	}
+
+	/// Register what to undo if any later step fails
+	pub fn on_failure(&mut self, compensation: S::Compensation) {
+		self.compensations.push(compensation);
+	}
+
+	pub fn complete(&mut self) {
+		self.status = SagaStatus::Completed;
+	}
+
+	pub fn compensations(&self) -> &[S::Compensation] {
+		&self.compensations
+	}
+
+	/// Run compensations in reverse order of registration. Stops at the first failure.
+	async fn compensate(&mut self, context_manager: &AtomicContextManager) {
+		while let Some(compensation) = self.compensations.pop() {
+			if let Err(err) = S::compensate(compensation, context_manager.clone()).await {
+				tracing::error!(saga = S::NAME, id = %self.id, "Failed to compensate saga! {:?}", err);
+				self.status = SagaStatus::Failed(format!("{:?}", err));
+				return;
+			}
+		}
+		self.status = SagaStatus::Compensated;
+	}
+
+	pub fn to_record(&self) -> Result<SagaRecord, BaseError> {
+		Ok(SagaRecord {
+			name: S::NAME.to_string(),
+			id: self.id.clone(),
+			status: self.status.as_str().to_string(),
+			state: serde_json::to_string(self).map_err(|err| BaseError::DatabaseError(err.to_string()))?,
+		})
+	}
+
+	pub fn from_record(record: &SagaRecord) -> Result<Self, BaseError> {
+		serde_json::from_str(&record.state).map_err(|err| BaseError::DatabaseError(format!("Failed to deserialize saga {}: {}", record.name, err)))
+	}
+}
+
+/// Persisted form of [SagaInstance]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SagaRecord {
+	pub name: String,
+	pub id: String,
+	/// [SagaStatus::as_str], for querying. The status itself is kept in `state`.
+	pub status: String,
+	/// Serialized [SagaInstance]
+	pub state: String,
+}
+
+/// Where saga instances are kept. Implemented on the unit of work so that the saga is saved in the transaction of its step.
+pub trait TSagaRepository: Send {
+	/// Lock the record for the rest of the transaction so that events of the same saga are not handled concurrently.
+	fn load_saga(&mut self, name: &str, id: &str) -> impl Future<Output = Result<Option<SagaRecord>, BaseError>> + Send;
+	fn save_saga(&mut self, record: &SagaRecord) -> impl Future<Output = Result<(), BaseError>> + Send;
+}
+
+/// Event handler that runs steps of saga `S` on repository `R`
+pub struct SagaHandler<S, R> {
+	context_manager: AtomicContextManager,
+	repository: R,
+	_saga: std::marker::PhantomData<fn() -> S>,
+}
+
+impl<S: TSaga, R: TUnitOfWork + TSagaRepository> SagaHandler<S, R> {
+	pub fn new(context_manager: AtomicContextManager, repository: R) -> Self {
+		Self {
+			context_manager,
+			repository,
+			_saga: std::marker::PhantomData,
+		}
+	}
+
+	/// Load the saga `event` belongs to, or start new one, and run its step for the event.
+	/// When the step fails, its transaction is rolled back and the saga is compensated.
+	pub async fn react<E, Err>(mut self, event: E) -> Result<(), Err>
+	where
+		S: TSagaStep<E>,
+		E: Send,
+		Err: std::convert::From<BaseError>,
+	{
+		let res = self.run(event).await;
+		self.repository.close().await;
+		res.map_err(Err::from)
+	}
+
+	async fn run<E: Send>(&mut self, event: E) -> Result<(), BaseError>
+	where
+		S: TSagaStep<E>,
+	{
+		let id = S::saga_id(&event);
+		self.repository.begin().await?;
+		let mut saga = match self.repository.load_saga(S::NAME, &id).await? {
+			Some(record) => SagaInstance::<S>::from_record(&record)?,
+			None => SagaInstance::new(id),
+		};
+		if saga.status.is_finished() {
+			tracing::info!(saga = S::NAME, id = %saga.id, "Event for finished saga is ignored. {:?}", saga.status);
+			return Ok(());
+		}
+		let registered = saga.compensations.len();
+
+		let err = match S::react(&mut saga, event, self.context_manager.clone()).await {
+			Ok(()) => {
+				self.repository.save_saga(&saga.to_record()?).await?;
+				return self.repository.commit().await;
+			}
+			Err(err) => err,
+		};
+		tracing::error!(saga = S::NAME, id = %saga.id, "Saga step failed! Compensating. {:?}", err);
+		self.repository.rollback().await?;
+
+		// * Loaded again under lock, as another event of the saga may have been handled once the rollback released it
+		self.repository.begin().await?;
+		let mut current = match self.repository.load_saga(S::NAME, &saga.id).await? {
+			Some(record) => SagaInstance::<S>::from_record(&record)?,
+			None => SagaInstance::new(saga.id.clone()),
+		};
+		if current.status.is_finished() {
+			tracing::warn!(saga = S::NAME, id = %current.id, "Saga is finished while its step failed. It is not compensated. {:?}", current.status);
+			return Err(err);
+		}
+		// Side effects of the failed step may be done already
+		current.compensations.extend(saga.compensations.drain(registered..));
+		current.compensate(&self.context_manager).await;
+		self.repository.save_saga(&current.to_record()?).await?;
+		self.repository.commit().await?;
+		Err(err)
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use super::*;
+	use crate::prelude::{ContextManager, TConnection};
+	use std::sync::{Arc, Mutex};
+
+	struct Connection;
+	impl TConnection for Connection {}
+
+	static STORE: std::sync::LazyLock<Mutex<hashbrown::HashMap<String, SagaRecord>>> = std::sync::LazyLock::new(Default::default);
+	static COMPENSATED: Mutex<Vec<i64>> = Mutex::new(vec![]);
+
+	#[derive(Default)]
+	struct Repository(Vec<SagaRecord>);
+	impl TUnitOfWork for Repository {
+		async fn begin(&mut self) -> Result<(), BaseError> {
+			Ok(())
+		}
+		async fn _commit(&mut self) -> Result<(), BaseError> {
+			let mut store = STORE.lock().unwrap();
+			self.0.drain(..).for_each(|record| {
+				store.insert(record.id.clone(), record);
+			});
+			Ok(())
+		}
+		async fn rollback(&mut self) -> Result<(), BaseError> {
+			self.0.clear();
+			Ok(())
+		}
+		async fn close(&mut self) {}
+	}
+	impl TSagaRepository for Repository {
+		async fn load_saga(&mut self, _name: &str, id: &str) -> Result<Option<SagaRecord>, BaseError> {
+			Ok(STORE.lock().unwrap().get(id).cloned())
+		}
+		async fn save_saga(&mut self, record: &SagaRecord) -> Result<(), BaseError> {
+			self.0.push(record.clone());
+			Ok(())
+		}
+	}
+
+	#[derive(Default, Serialize, Deserialize)]
+	struct OrderSaga {
+		reserved: Vec<i64>,
+	}
+	impl TSaga for OrderSaga {
+		const NAME: &'static str = "order";
+		type Compensation = i64;
+		async fn compensate(compensation: i64, _context_manager: AtomicContextManager) -> Result<(), BaseError> {
+			COMPENSATED.lock().unwrap().push(compensation);
+			Ok(())
+		}
+	}
+
+	struct StockReserved(i64);
+	struct PaymentFailed;
+	impl TSagaStep<StockReserved> for OrderSaga {
+		fn saga_id(_event: &StockReserved) -> String {
+			"order-1".into()
+		}
+		async fn react(saga: &mut SagaInstance<Self>, event: StockReserved, _context_manager: AtomicContextManager) -> Result<(), BaseError> {
+			saga.state.reserved.push(event.0);
+			saga.on_failure(event.0);
+			Ok(())
+		}
+	}
+	impl TSagaStep<PaymentFailed> for OrderSaga {
+		fn saga_id(_event: &PaymentFailed) -> String {
+			"order-1".into()
+		}
+		async fn react(_saga: &mut SagaInstance<Self>, _event: PaymentFailed, _context_manager: AtomicContextManager) -> Result<(), BaseError> {
+			Err(BaseError::ServiceError)
+		}
+	}
+
+	/// Another event of the saga completes it right after this step fails
+	struct ShipmentFailed;
+	impl TSagaStep<ShipmentFailed> for OrderSaga {
+		fn saga_id(_event: &ShipmentFailed) -> String {
+			"order-2".into()
+		}
+		async fn react(saga: &mut SagaInstance<Self>, _event: ShipmentFailed, _context_manager: AtomicContextManager) -> Result<(), BaseError> {
+			saga.on_failure(3);
+			let mut completed = SagaInstance::<OrderSaga>::new("order-2");
+			completed.complete();
+			STORE.lock().unwrap().insert("order-2".into(), completed.to_record()?);
+			Err(BaseError::ServiceError)
+		}
+	}
+
+	fn handler() -> SagaHandler<OrderSaga, Repository> {
+		SagaHandler::new(Arc::new(ContextManager::new(&Connection)), Repository::default())
+	}
+
+	#[tokio::test]
+	async fn test_saga_compensates_in_reverse_order() {
+		handler().react::<_, BaseError>(StockReserved(1)).await.unwrap();
+		handler().react::<_, BaseError>(StockReserved(2)).await.unwrap();
+		let saga = SagaInstance::<OrderSaga>::from_record(STORE.lock().unwrap().get("order-1").unwrap()).unwrap();
+		assert_eq!(saga.state.reserved, vec![1, 2]);
+		assert_eq!(saga.status, SagaStatus::Running);
+
+		assert!(handler().react::<_, BaseError>(PaymentFailed).await.is_err());
+		assert_eq!(*COMPENSATED.lock().unwrap(), vec![2, 1]);
+		assert_eq!(STORE.lock().unwrap().get("order-1").unwrap().status, "compensated");
+
+		// Finished saga ignores further events
+		handler().react::<_, BaseError>(StockReserved(3)).await.unwrap();
+		let saga = SagaInstance::<OrderSaga>::from_record(STORE.lock().unwrap().get("order-1").unwrap()).unwrap();
+		assert_eq!(saga.state.reserved, vec![1, 2]);
+	}
+
+	#[tokio::test]
+	async fn test_saga_finished_meanwhile_is_not_overwritten() {
+		assert!(handler().react::<_, BaseError>(ShipmentFailed).await.is_err());
+		assert_eq!(STORE.lock().unwrap().get("order-2").unwrap().status, "completed");
+		assert!(!COMPENSATED.lock().unwrap().contains(&3));
+	}
+}
@@ -0,0 +1,227 @@
+//! ### Sandbox
+//! Handlers registered through `init_event_handler!` can be switched to record-only mode, for example, to verify
+//! new handler chain in staging against production-like data. Record-only handler still runs with its context,
+//! so database projections are applied, but dependencies it resolves from [ContextManager] are replaced with recorders
+//! that put [SideEffect]s on the report instead of calling the outside world.
+//!
+//! ```rust,ignore
+//! struct RecordingNotifier;
+//! #[async_trait]
+//! impl TNotifier for RecordingNotifier {
+//!     async fn send(&self, to: &str, message: &str) -> Result<(), BaseError> {
+//!         record_side_effect("notifier", format!("send {message:?} to {to}"));
+//!         Ok(())
+//!     }
+//! }
+//!
+//! // On boot
+//! <dyn TNotifier>::register_recorder(RecordingNotifier);
+//! handler_sandbox().record_only("OrderSucceeded", "notify_customer");
+//!
+//! // Later
+//! for effect in handler_sandbox().take_report() {
+//!     println!("{}::{} {} - {}", effect.topic, effect.handler, effect.client, effect.description);
+//! }
+//! ```
+//! Record-only handler panics when it resolves dependency without recorder, rather than performing the effect for real.
+//! The same goes for the ones it gets from [ContextManager::memoized], whose recorder is registered by their type with
+//! [HandlerSandbox::register_recorder]. Outbox rows of the events it raises are not written but put on the report with
+//! client `outbox`, so that the events are not published to other services.
+//! [process_shared](crate::prelude::process_shared) is not sandboxed, so keep it for the clients without side effects.
+use std::any::TypeId;
+use std::collections::{BTreeSet, VecDeque};
+use std::sync::{Arc, Mutex, RwLock};
+
+use super::dependency::Dependencies;
+use super::handler::Future;
+
+/// Side effects kept in the report. The oldest ones are dropped beyond it.
+const REPORT_CAPACITY: usize = 10_000;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SideEffect {
+	pub topic: String,
+	pub handler: String,
+	/// Name of the client given by the recorder, such as `notifier`
+	pub client: String,
+	pub description: String,
+}
+
+#[derive(Default)]
+pub struct HandlerSandbox {
+	record_only: RwLock<BTreeSet<(String, String)>>,
+	recorders: RwLock<Dependencies>,
+	report: Mutex<VecDeque<SideEffect>>,
+}
+
+static HANDLER_SANDBOX: std::sync::LazyLock<HandlerSandbox> = std::sync::LazyLock::new(Default::default);
+
+/// Process-wide sandbox that `init_event_handler!` consults before running each handler.
+pub fn handler_sandbox() -> &'static HandlerSandbox {
+	&HANDLER_SANDBOX
+}
+
+tokio::task_local! {
+	/// (topic, handler) of record-only handler being run
+	static SANDBOXED: (&'static str, &'static str);
+}
+
+impl HandlerSandbox {
+	pub fn record_only(&self, topic: &str, handler: &str) {
+		tracing::warn!("Event handler set to record-only: {}::{}", topic, handler);
+		self.record_only.write().unwrap().insert((topic.to_string(), handler.to_string()));
+	}
+
+	/// Let the handler perform side effects again
+	pub fn execute(&self, topic: &str, handler: &str) {
+		tracing::info!("Event handler set to execute: {}::{}", topic, handler);
+		self.record_only.write().unwrap().remove(&(topic.to_string(), handler.to_string()));
+	}
+
+	pub fn is_record_only(&self, topic: &str, handler: &str) -> bool {
+		let record_only = self.record_only.read().unwrap();
+		// Avoid allocation on hot path when sandbox is not used
+		!record_only.is_empty() && record_only.contains(&(topic.to_string(), handler.to_string()))
+	}
+
+	/// Use `recorder` for `T` in record-only handlers. Usually called through `<dyn Trait>::register_recorder` of `declare_dependency!`.
+	pub fn register_recorder<T: ?Sized + Send + Sync + 'static>(&self, recorder: Arc<T>) {
+		self.recorders.write().unwrap().insert(TypeId::of::<T>(), Box::new(recorder));
+	}
+
+	/// Side effects recorded so far, removing them from the report
+	pub fn take_report(&self) -> Vec<SideEffect> {
+		self.report.lock().unwrap().drain(..).collect()
+	}
+
+	fn record(&self, effect: SideEffect) {
+		tracing::info!(topic = %effect.topic, handler = %effect.handler, "Side effect recorded on {}: {}", effect.client, effect.description);
+		let mut report = self.report.lock().unwrap();
+		if report.len() == REPORT_CAPACITY {
+			report.pop_front();
+		}
+		report.push_back(effect);
+	}
+
+	/// Recorder of `T` if called from record-only handler
+	pub(crate) fn recorder<T: ?Sized + Send + Sync + 'static>(&self) -> Option<Arc<T>> {
+		if !is_sandboxed() {
+			return None;
+		}
+		match self.recorders.read().unwrap().get(&TypeId::of::<T>()) {
+			Some(recorder) => Some(recorder.downcast_ref::<Arc<T>>().expect("Type Mismatch!").clone()),
+			None => panic!("Dependency {} Has No Sandbox Recorder!", std::any::type_name::<T>()),
+		}
+	}
+}
+
+/// Whether called from record-only handler
+pub(crate) fn is_sandboxed() -> bool {
+	SANDBOXED.try_with(|_| ()).is_ok()
+}
+
+/// Put side effect on the report of the sandbox. Called by recorders.
+pub fn record_side_effect(client: &str, description: impl Into<String>) {
+	let (topic, handler) = SANDBOXED.try_with(|sandboxed| *sandboxed).unwrap_or_default();
+	handler_sandbox().record(SideEffect {
+		topic: topic.to_string(),
+		handler: handler.to_string(),
+		client: client.to_string(),
+		description: description.into(),
+	});
+}
+
+/// Run `future` of the handler in the sandbox if it is record-only. Called by `init_event_handler!`.
+pub fn sandboxed<E: 'static>(topic: &'static str, handler: &'static str, future: Future<E>) -> Future<E> {
+	if !handler_sandbox().is_record_only(topic, handler) {
+		return future;
+	}
+	Box::pin(SANDBOXED.scope((topic, handler), future))
+}
+
+#[cfg(test)]
+mod test {
+	use super::*;
+	use crate::prelude::{BaseError, ContextManager, TConnection};
+	use std::sync::atomic::{AtomicUsize, Ordering};
+
+	struct Connection;
+	impl TConnection for Connection {}
+
+	trait TNotifier: Send + Sync {
+		fn send(&self, to: &str);
+	}
+	static SENT: AtomicUsize = AtomicUsize::new(0);
+	struct Notifier;
+	impl TNotifier for Notifier {
+		fn send(&self, _to: &str) {
+			SENT.fetch_add(1, Ordering::SeqCst);
+		}
+	}
+	struct RecordingNotifier;
+	impl TNotifier for RecordingNotifier {
+		fn send(&self, to: &str) {
+			record_side_effect("notifier", format!("send to {to}"));
+		}
+	}
+
+	fn handler(context_manager: Arc<ContextManager>) -> Future<BaseError> {
+		Box::pin(async move {
+			context_manager.resolve::<dyn TNotifier>().send("migo");
+			Ok(())
+		})
+	}
+
+	struct Mailer {
+		recording: bool,
+	}
+	impl Mailer {
+		fn send(&self, to: &str) {
+			match self.recording {
+				true => record_side_effect("mailer", format!("mail to {to}")),
+				false => {
+					SENT.fetch_add(1, Ordering::SeqCst);
+				}
+			}
+		}
+	}
+
+	#[tokio::test]
+	async fn test_record_only_handler_records_side_effects() {
+		crate::prelude::register_dependency::<dyn TNotifier>(Arc::new(Notifier));
+		handler_sandbox().register_recorder::<dyn TNotifier>(Arc::new(RecordingNotifier));
+		let context_manager = Arc::new(ContextManager::new(&Connection));
+
+		sandboxed("OrderSucceeded", "notify", handler(context_manager.clone())).await.unwrap();
+		assert_eq!(SENT.load(Ordering::SeqCst), 1);
+		assert!(handler_sandbox().take_report().is_empty());
+
+		handler_sandbox().record_only("OrderSucceeded", "notify");
+		sandboxed("OrderSucceeded", "notify", handler(context_manager.clone())).await.unwrap();
+		handler_sandbox().execute("OrderSucceeded", "notify");
+		assert_eq!(SENT.load(Ordering::SeqCst), 1);
+		assert_eq!(
+			handler_sandbox().take_report(),
+			vec![SideEffect {
+				topic: "OrderSucceeded".into(),
+				handler: "notify".into(),
+				client: "notifier".into(),
+				description: "send to migo".into(),
+			}]
+		);
+
+		// * Memoized dependency, even the one built by handler run earlier in the dispatch
+		handler_sandbox().register_recorder(Arc::new(Mailer { recording: true }));
+		context_manager.memoized(|| Mailer { recording: false }).send("migo");
+		assert_eq!(SENT.load(Ordering::SeqCst), 2);
+		handler_sandbox().record_only("OrderSucceeded", "mail");
+		let mail = Box::pin(async move {
+			context_manager.memoized(|| Mailer { recording: false }).send("migo");
+			Ok::<_, BaseError>(())
+		});
+		sandboxed("OrderSucceeded", "mail", mail).await.unwrap();
+		handler_sandbox().execute("OrderSucceeded", "mail");
+		assert_eq!(SENT.load(Ordering::SeqCst), 2);
+		assert_eq!(handler_sandbox().take_report().into_iter().map(|effect| effect.description).collect::<Vec<_>>(), vec!["mail to migo"]);
+	}
+}
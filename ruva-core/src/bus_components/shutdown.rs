@@ -0,0 +1,47 @@
+//! ### Shutdown
+//! Signal the bus to stop. Running async handler groups are cancelled and events left in the queue are not processed.
+//!
+//! ```rust,ignore
+//! tokio::signal::ctrl_c().await?;
+//! bus_shutdown_token().shutdown();
+//! ```
+use std::sync::{Arc, LazyLock};
+
+use tokio::sync::watch;
+
+#[derive(Clone)]
+pub struct ShutdownToken(Arc<watch::Sender<bool>>);
+
+impl Default for ShutdownToken {
+	fn default() -> Self {
+		Self(Arc::new(watch::channel(false).0))
+	}
+}
+
+impl ShutdownToken {
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	pub fn shutdown(&self) {
+		self.0.send_replace(true);
+	}
+
+	pub fn is_shutdown(&self) -> bool {
+		*self.0.borrow()
+	}
+
+	/// Resolves once `shutdown` is called.
+	pub async fn cancelled(&self) {
+		let mut receiver = self.0.subscribe();
+		// Sender lives as long as self, so error is not possible.
+		let _ = receiver.wait_for(|shutdown| *shutdown).await;
+	}
+}
+
+static BUS_SHUTDOWN: LazyLock<ShutdownToken> = LazyLock::new(ShutdownToken::new);
+
+/// Token the message bus honors
+pub fn bus_shutdown_token() -> &'static ShutdownToken {
+	&BUS_SHUTDOWN
+}
@@ -0,0 +1,143 @@
+//! ### Context snapshot
+//! Work deferred to scheduler or background worker loses who requested it unless the context goes along with it.
+//! Persist [ContextSnapshot] with the payload and restore it at processing time so that deferred handlers behave like inline ones.
+//!
+//! ```rust,ignore
+//! // When deferring
+//! let deferred = Deferred::new(&context_manager, SendReport { user_id });
+//! queue.push(serde_json::to_string(&deferred)?).await?;
+//!
+//! // When processing
+//! let deferred: Deferred<SendReport> = serde_json::from_str(&message)?;
+//! let (context_manager, cmd) = deferred.restore(conn);
+//! MessageBus.dispatch_with(cmd, context_manager).await?;
+//! ```
+//! Snapshot sits in queues and tables for as long as the work is deferred, so the current user is kept without the raw claims of its token
+//! but the ones allowed by [set_snapshot_claims].
+use std::sync::RwLock;
+
+use serde::{Deserialize, Serialize};
+
+use super::actor::Actor;
+use super::contexts::ContextManager;
+use super::current_user::CurrentUser;
+use super::executor::TConnection;
+
+/// Serializable part of [ContextManager]. Fields are defaulted on deserialization so that snapshots taken by older versions can be restored.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ContextSnapshot {
+	#[serde(default)]
+	pub actor: Actor,
+	#[serde(default)]
+	pub tenant: Option<String>,
+	/// Only the claims allowed by [set_snapshot_claims] are kept
+	#[serde(default)]
+	pub current_user: Option<CurrentUser>,
+	/// Deferred work continues the correlation of the dispatch it was deferred from
+	#[serde(default)]
+	pub correlation_id: Option<String>,
+}
+
+static SNAPSHOT_CLAIMS: RwLock<Vec<&'static str>> = RwLock::new(Vec::new());
+
+/// Claims of [CurrentUser] kept in [ContextSnapshot], for deferred handlers reading them. None is kept by default.
+pub fn set_snapshot_claims(claims: &[&'static str]) {
+	*SNAPSHOT_CLAIMS.write().unwrap() = claims.to_vec();
+}
+
+fn snapshot_user(user: &CurrentUser) -> CurrentUser {
+	let allowed = SNAPSHOT_CLAIMS.read().unwrap();
+	CurrentUser {
+		id: user.id.clone(),
+		roles: user.roles.clone(),
+		scopes: user.scopes.clone(),
+		claims: user
+			.claims
+			.iter()
+			.filter(|(name, _)| allowed.contains(&name.as_str()))
+			.map(|(name, value)| (name.clone(), value.clone()))
+			.collect(),
+	}
+}
+
+impl ContextManager {
+	pub fn snapshot(&self) -> ContextSnapshot {
+		ContextSnapshot {
+			actor: self.actor.clone(),
+			tenant: self.tenant.clone(),
+			current_user: self.current_user.as_ref().map(snapshot_user),
+			correlation_id: self.correlation_id.clone(),
+		}
+	}
+
+	/// Context manager on `conn` with the state of `snapshot`. Event queue starts empty.
+	pub fn restore(conn: &'static dyn TConnection, snapshot: ContextSnapshot) -> Self {
+		let mut context_manager = ContextManager::new(conn).with_actor(snapshot.actor);
+		context_manager.current_user = snapshot.current_user;
+		context_manager.correlation_id = snapshot.correlation_id;
+		match snapshot.tenant {
+			Some(tenant) => context_manager.with_tenant(tenant),
+			None => context_manager,
+		}
+	}
+}
+
+/// Payload deferred together with the context it was created in
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Deferred<T> {
+	pub context: ContextSnapshot,
+	pub payload: T,
+}
+
+impl<T> Deferred<T> {
+	pub fn new(context_manager: &ContextManager, payload: T) -> Self {
+		Self {
+			context: context_manager.snapshot(),
+			payload,
+		}
+	}
+
+	pub fn restore(self, conn: &'static dyn TConnection) -> (ContextManager, T) {
+		(ContextManager::restore(conn, self.context), self.payload)
+	}
+}
+
+#[test]
+fn test_deferred_restores_context() {
+	struct Connection;
+	impl TConnection for Connection {}
+
+	set_snapshot_claims(&["tier"]);
+	let context_manager = ContextManager::new(&Connection)
+		.with_actor(Actor::Impersonated {
+			admin: "admin".into(),
+			as_user: "migo".into(),
+		})
+		.with_tenant("acme")
+		.with_current_user(
+			CurrentUser::new("migo")
+				.with_role("admin")
+				.with_scope("orders:read")
+				.with_claim("tier", "gold")
+				.with_claim("email", "migo@acme.com"),
+		)
+		.with_correlation_id("request-1");
+	let serialized = serde_json::to_string(&Deferred::new(&context_manager, 42)).unwrap();
+
+	let deferred: Deferred<i32> = serde_json::from_str(&serialized).unwrap();
+	let (restored, payload) = deferred.restore(&Connection);
+	assert_eq!(payload, 42);
+	assert_eq!(restored.actor, context_manager.actor);
+	assert_eq!(restored.tenant.as_deref(), Some("acme"));
+	// Claims not allowed are dropped
+	assert!(!serialized.contains("migo@acme.com"));
+	let user = restored.current_user.as_ref().unwrap();
+	assert_eq!((user.id.as_str(), user.has_role("admin"), user.has_scope("orders:read")), ("migo", true, true));
+	assert_eq!(user.claim::<String>("tier").as_deref(), Some("gold"));
+	assert_eq!(user.claim::<String>("email"), None);
+	assert_eq!(restored.correlation_id(), Some("request-1"));
+
+	// Snapshot without fields is restored with defaults
+	let deferred: Deferred<i32> = serde_json::from_str(r#"{"context":{},"payload":1}"#).unwrap();
+	assert_eq!(deferred.context, ContextSnapshot::default());
+}
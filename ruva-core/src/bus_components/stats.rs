@@ -0,0 +1,95 @@
+//! ### Dispatch statistics
+//! [UowStats] is collected for every dispatch so that performance regressions can be caught by integration tests.
+//! Rows read and written are reported by repositories as only they know what the executor returned.
+//!
+//! ```rust,ignore
+//! impl TOrderRepository for Context {
+//!     async fn update(&mut self, order: &Order) -> Result<(), BaseError> {
+//!         let res = sqlx::query("UPDATE orders SET ...").execute(self.transaction()).await?;
+//!         self.record_rows_written(res.rows_affected());
+//!         Ok(())
+//!     }
+//! }
+//!
+//! // In test
+//! let (res, stats) = MessageBus.dispatch_with_stats(MakeOrder { user_id: 1 }, ContextManager::new(conn)).await;
+//! assert!(stats.rows_written <= Some(3));
+//! assert_eq!(stats.events_raised, 2);
+//! ```
+//! Stats are also given to [TBusObserver::dispatch_stats](super::observer::TBusObserver::dispatch_stats) once the command is done.
+use std::{
+	sync::{Arc, Mutex},
+	time::Duration,
+};
+
+use super::contexts::{Context, ContextManager};
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct UowStats {
+	/// `None` if no repository reported it
+	pub rows_read: Option<u64>,
+	/// `None` if no repository reported it
+	pub rows_written: Option<u64>,
+	/// Every event raised in the dispatch, whether or not it is internally notifiable
+	pub events_raised: usize,
+	/// Sum of time spent on committing transactions. `None` if unit of work doesn't measure it.
+	pub commit_duration: Option<Duration>,
+	/// Outbox rows committed in the dispatch. See [ConsistencyToken](super::consistency::ConsistencyToken).
+	pub consistency_token: super::consistency::ConsistencyToken,
+}
+
+pub(crate) type StatsRecorder = Arc<Mutex<UowStats>>;
+
+impl ContextManager {
+	/// Stats collected so far in this dispatch
+	pub fn stats(&self) -> UowStats {
+		self.stats.lock().unwrap().clone()
+	}
+
+	pub(crate) fn record(&self, f: impl FnOnce(&mut UowStats)) {
+		f(&mut self.stats.lock().unwrap())
+	}
+}
+
+impl Context {
+	pub fn record_rows_read(&self, rows: u64) {
+		self.super_ctx.record(|stats| *stats.rows_read.get_or_insert(0) += rows);
+	}
+
+	pub fn record_rows_written(&self, rows: u64) {
+		self.super_ctx.record(|stats| *stats.rows_written.get_or_insert(0) += rows);
+	}
+
+	pub(crate) fn record_commit_duration(&self, elapsed: Duration) {
+		self.super_ctx.record(|stats| *stats.commit_duration.get_or_insert(Duration::ZERO) += elapsed);
+	}
+}
+
+#[test]
+fn test_uow_stats() {
+	use crate::prelude::{TConnection, TEvent};
+
+	struct Connection;
+	impl TConnection for Connection {}
+	struct Raised;
+	impl TEvent for Raised {
+		fn state(&self) -> String {
+			"{}".into()
+		}
+	}
+
+	let context_manager = Arc::new(ContextManager::new(&Connection));
+	let mut ctx = Context::new(Arc::clone(&context_manager));
+	assert_eq!(context_manager.stats(), UowStats::default());
+
+	ctx.record_rows_read(3);
+	ctx.record_rows_read(2);
+	ctx.raise(Raised);
+	ctx.record_commit_duration(Duration::from_millis(5));
+
+	let stats = context_manager.stats();
+	assert_eq!(stats.rows_read, Some(5));
+	assert_eq!(stats.rows_written, None);
+	assert_eq!(stats.events_raised, 1);
+	assert_eq!(stats.commit_duration, Some(Duration::from_millis(5)));
+}
@@ -0,0 +1,143 @@
+//! ### Per-tenant event handlers
+//! Tenant may have its own handlers for some topics, for example, white-label customer with custom invoicing.
+//! When [ContextManager] carries tenant id, handlers registered for the tenant are used for the topics they cover,
+//! and the ones of `init_event_handler!` for the rest.
+//!
+//! ```rust,ignore
+//! // On boot
+//! init_tenant_event_handler!(
+//!     "acme",
+//!     ServiceError,
+//!     |ctx| AcmeInvoicing(Context::new(ctx)),
+//!     OrderSucceeded: [issue_invoice],
+//! );
+//!
+//! let context_manager = ContextManager::new(conn).with_tenant("acme");
+//! MessageBus.dispatch_with(cmd, context_manager).await?;
+//! ```
+//! Overrides replace the whole handler list of the topic. Handlers the tenant keeps have to be listed again.
+use std::any::{Any, TypeId};
+use std::sync::RwLock;
+
+use super::contexts::ContextManager;
+use super::messagebus::TEventHandler;
+
+type Overrides = &'static (dyn Any + Send + Sync);
+
+#[derive(Default)]
+pub struct TenantHandlers {
+	// tenant -> error type of the handlers -> `&'static TEventHandler<E>`
+	overrides: RwLock<hashbrown::HashMap<String, hashbrown::HashMap<TypeId, Overrides>>>,
+}
+
+static TENANT_HANDLERS: std::sync::LazyLock<TenantHandlers> = std::sync::LazyLock::new(Default::default);
+
+/// Process-wide tenant overrides that event handling consults before the default handlers.
+pub fn tenant_handlers() -> &'static TenantHandlers {
+	&TENANT_HANDLERS
+}
+
+impl TenantHandlers {
+	/// Set handlers of `tenant`, replacing the ones registered before. Meant to be called on boot, as the replaced ones are never freed.
+	pub fn register<E: 'static>(&self, tenant: &str, handlers: TEventHandler<E>) {
+		let handlers: &'static TEventHandler<E> = Box::leak(Box::new(handlers));
+		tracing::info!("Tenant event handlers registered for {}: {:?}", tenant, handlers.keys().collect::<Vec<_>>());
+		self.overrides.write().unwrap().entry(tenant.to_string()).or_default().insert(TypeId::of::<E>(), handlers);
+	}
+
+	/// Handlers of `topic` that `tenant` overrides
+	pub fn get<E: 'static>(&self, tenant: &str, topic: &str) -> Option<&'static super::handler::EventHandlers<E>> {
+		let overrides = self.overrides.read().unwrap();
+		let handlers = overrides.get(tenant)?.get(&TypeId::of::<E>())?;
+		handlers.downcast_ref::<TEventHandler<E>>()?.get(topic)
+	}
+
+	/// Tenants with overrides
+	pub fn tenants(&self) -> Vec<String> {
+		self.overrides.read().unwrap().keys().cloned().collect()
+	}
+}
+
+impl ContextManager {
+	pub fn with_tenant(mut self, tenant: impl Into<String>) -> Self {
+		self.tenant = Some(tenant.into());
+		self
+	}
+
+	/// Handlers of `topic` for the tenant of this dispatch, falling back to `default`.
+	pub(crate) fn resolve_handlers<E: 'static>(&self, topic: &str, default: &'static TEventHandler<E>) -> Option<&'static super::handler::EventHandlers<E>> {
+		self.tenant.as_deref().and_then(|tenant| tenant_handlers().get::<E>(tenant, topic)).or_else(|| default.get(topic))
+	}
+}
+
+/// Register tenant-specific handlers. Takes the same form as `init_event_handler!` with the tenant id in front.
+/// Unlike `init_event_handler!`, it is a statement to run on boot.
+#[macro_export]
+macro_rules! init_tenant_event_handler {
+    (
+		$tenant:expr,
+		$E:ty,
+		$event_handler :expr,
+			$(
+				$(#[$asynchrony:ident $(($batch_size:expr))?])?
+				$event:ty:[$($(#[order = $order:expr])? $handler:ident),* $(,)? ]
+			),*
+			$(,)?
+    ) => {{
+		ruva::__assert_unique_registrations!(topic [$($event),*] "is listed more than once in `init_tenant_event_handler!`");
+		$(
+			ruva::__assert_unique_registrations!([$($handler),*] concat!("is listed more than once for `", stringify!($event), "` in `init_tenant_event_handler!`"));
+			ruva::__assert_unique_orders!([$(($($order)?)),*] concat!("Handler order is given more than once for `", stringify!($event), "` in `init_tenant_event_handler!`"));
+		)*
+		let mut _map: ::ruva::TEventHandler<$E> = ::ruva::TEventHandler::default();
+		$(
+			_map.insert(
+				<$event as ::ruva::TTopic>::TOPIC.into(),
+				ruva::__event_handlers_internal!($($asynchrony $(($batch_size))?)?; $E, $event_handler, $event, [$(($($order)?) $handler),*])
+			);
+		)*
+		::ruva::tenant_handlers().register::<$E>($tenant, _map);
+	}};
+}
+
+#[cfg(test)]
+mod test {
+	use super::*;
+	use crate::prelude::{BaseError, EventHandlers, TConnection};
+
+	struct Connection;
+	impl TConnection for Connection {}
+
+	fn handlers(name: &'static str) -> EventHandlers<BaseError> {
+		EventHandlers::Sync(vec![Box::new(move |_, _| {
+			Box::pin(async move {
+				tracing::info!("{}", name);
+				Ok(())
+			})
+		})])
+	}
+
+	#[test]
+	fn test_tenant_overrides_fall_back_to_default() {
+		static DEFAULT: std::sync::LazyLock<TEventHandler<BaseError>> = std::sync::LazyLock::new(|| {
+			let mut map = TEventHandler::default();
+			map.insert("InvoiceRequested".to_string(), handlers("default"));
+			map.insert("OrderPlaced".to_string(), handlers("default"));
+			map
+		});
+		let mut overrides = TEventHandler::default();
+		overrides.insert("InvoiceRequested".to_string(), handlers("acme"));
+		tenant_handlers().register::<BaseError>("acme", overrides);
+
+		let is_default = |context_manager: &ContextManager, topic: &str| std::ptr::eq(context_manager.resolve_handlers(topic, &DEFAULT).unwrap(), DEFAULT.get(topic).unwrap());
+
+		let acme = ContextManager::new(&Connection).with_tenant("acme");
+		assert!(!is_default(&acme, "InvoiceRequested"));
+		assert!(is_default(&acme, "OrderPlaced"));
+
+		let other = ContextManager::new(&Connection).with_tenant("other");
+		assert!(is_default(&other, "InvoiceRequested"));
+		assert!(is_default(&ContextManager::new(&Connection), "InvoiceRequested"));
+		assert!(acme.resolve_handlers("Unknown", &DEFAULT).is_none());
+	}
+}
@@ -0,0 +1,154 @@
+//! ### Runtime toggling of event handlers
+//! Handlers registered through `init_event_handler!` can be disabled and enabled while the process is running,
+//! for example, to pause the email handler during an incident. Disabled handler is skipped as if it succeeded.
+//!
+//! ```rust,no_run
+//! # use ruva_core::prelude::*;
+//! # async fn example() -> Result<(), BaseError> {
+//! let toggles = handler_toggles();
+//! toggles.restore(&FileToggleStore::new("handler_toggles.json")).await?; // on boot
+//!
+//! // in your admin endpoint
+//! toggles.disable("OrderSucceeded", "send_mail");
+//! toggles.persist(&FileToggleStore::new("handler_toggles.json")).await?;
+//!
+//! // introspection
+//! let registered = toggles.registered(); // topic -> handler names
+//! # Ok(())
+//! # }
+//! ```
+use std::collections::{BTreeMap, BTreeSet};
+use std::sync::RwLock;
+
+use async_trait::async_trait;
+
+use crate::prelude::BaseError;
+
+#[derive(Default)]
+pub struct HandlerToggles {
+	registered: RwLock<BTreeMap<String, Vec<String>>>,
+	disabled: RwLock<BTreeSet<(String, String)>>,
+}
+
+static HANDLER_TOGGLES: std::sync::LazyLock<HandlerToggles> = std::sync::LazyLock::new(Default::default);
+
+/// Process-wide toggles that `init_event_handler!` consults before running each handler.
+pub fn handler_toggles() -> &'static HandlerToggles {
+	&HANDLER_TOGGLES
+}
+
+impl HandlerToggles {
+	/// Called by `init_event_handler!` so that registered handlers can be listed.
+	pub fn register(&self, topic: &str, handler: &str) {
+		let mut registered = self.registered.write().unwrap();
+		let handlers = registered.entry(topic.to_string()).or_default();
+		if !handlers.iter().any(|h| h == handler) {
+			handlers.push(handler.to_string());
+		}
+	}
+
+	/// Registered handler names by topic
+	pub fn registered(&self) -> BTreeMap<String, Vec<String>> {
+		self.registered.read().unwrap().clone()
+	}
+
+	pub fn disable(&self, topic: &str, handler: &str) {
+		tracing::warn!("Event handler disabled: {}::{}", topic, handler);
+		self.disabled.write().unwrap().insert((topic.to_string(), handler.to_string()));
+	}
+
+	pub fn enable(&self, topic: &str, handler: &str) {
+		tracing::info!("Event handler enabled: {}::{}", topic, handler);
+		self.disabled.write().unwrap().remove(&(topic.to_string(), handler.to_string()));
+	}
+
+	pub fn is_enabled(&self, topic: &str, handler: &str) -> bool {
+		let disabled = self.disabled.read().unwrap();
+		// Avoid allocation on hot path when nothing is disabled
+		disabled.is_empty() || !disabled.contains(&(topic.to_string(), handler.to_string()))
+	}
+
+	/// Disabled (topic, handler) pairs
+	pub fn disabled(&self) -> Vec<(String, String)> {
+		self.disabled.read().unwrap().iter().cloned().collect()
+	}
+
+	/// Save disabled handlers so that restart respects the toggle.
+	pub async fn persist(&self, store: &impl TToggleStore) -> Result<(), BaseError> {
+		store.save(self.disabled()).await
+	}
+
+	/// Replace disabled handlers with the ones saved in store.
+	pub async fn restore(&self, store: &impl TToggleStore) -> Result<(), BaseError> {
+		let disabled = store.load().await?;
+		*self.disabled.write().unwrap() = disabled.into_iter().collect();
+		Ok(())
+	}
+}
+
+/// Storage of disabled handlers
+#[async_trait]
+pub trait TToggleStore: Send + Sync {
+	async fn load(&self) -> Result<Vec<(String, String)>, BaseError>;
+	async fn save(&self, disabled: Vec<(String, String)>) -> Result<(), BaseError>;
+}
+
+/// Store that keeps disabled handlers in JSON file
+pub struct FileToggleStore {
+	path: std::path::PathBuf,
+}
+
+impl FileToggleStore {
+	pub fn new(path: impl Into<std::path::PathBuf>) -> Self {
+		Self { path: path.into() }
+	}
+}
+
+#[async_trait]
+impl TToggleStore for FileToggleStore {
+	async fn load(&self) -> Result<Vec<(String, String)>, BaseError> {
+		match std::fs::read_to_string(&self.path) {
+			Ok(content) => serde_json::from_str(&content).map_err(|err| {
+				tracing::error!("Failed to parse handler toggles! {}", err);
+				BaseError::ServiceError
+			}),
+			Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(vec![]),
+			Err(err) => {
+				tracing::error!("Failed to read handler toggles! {}", err);
+				Err(BaseError::ServiceError)
+			}
+		}
+	}
+
+	async fn save(&self, disabled: Vec<(String, String)>) -> Result<(), BaseError> {
+		let content = serde_json::to_string(&disabled).expect("Failed to serialize handler toggles");
+		std::fs::write(&self.path, content).map_err(|err| {
+			tracing::error!("Failed to write handler toggles! {}", err);
+			BaseError::ServiceError
+		})
+	}
+}
+
+#[tokio::test]
+async fn test_handler_toggles_persisted() {
+	let toggles = HandlerToggles::default();
+	toggles.register("OrderSucceeded", "send_mail");
+	toggles.register("OrderSucceeded", "change_inventory_count");
+	assert_eq!(toggles.registered()["OrderSucceeded"], vec!["send_mail".to_string(), "change_inventory_count".to_string()]);
+
+	toggles.disable("OrderSucceeded", "send_mail");
+	assert!(!toggles.is_enabled("OrderSucceeded", "send_mail"));
+	assert!(toggles.is_enabled("OrderSucceeded", "change_inventory_count"));
+
+	let path = std::env::temp_dir().join(format!("ruva_toggles_{}.json", std::process::id()));
+	let store = FileToggleStore::new(&path);
+	toggles.persist(&store).await.unwrap();
+
+	let restarted = HandlerToggles::default();
+	restarted.restore(&store).await.unwrap();
+	assert!(!restarted.is_enabled("OrderSucceeded", "send_mail"));
+
+	restarted.enable("OrderSucceeded", "send_mail");
+	assert!(restarted.is_enabled("OrderSucceeded", "send_mail"));
+	let _ = std::fs::remove_file(path);
+}
@@ -0,0 +1,190 @@
+//! ### Anti-corruption layer
+//! Events of another bounded context are shaped by its model - field names, nesting and optional fields of its own.
+//! [Translation] declares how such an event maps onto an event of this context, and [Inbox](super::inbox::Inbox) applies it
+//! before the handlers run, so that the foreign model doesn't leak into the domain.
+//!
+//! ```rust,ignore
+//! // Event of this context
+//! #[derive(Debug, Clone, Serialize, Deserialize, TEvent)]
+//! #[internally_notifiable]
+//! pub struct StockRequested {
+//!     pub order_id: i64,
+//!     pub customer_id: i64,
+//!     pub quantity: i64,
+//!     pub priority: String,
+//! }
+//!
+//! // `{"orderNo": 1, "customer": {"id": 7}, "qty": "3", "memo": ".."}` published by order service
+//! let translation = Translation::<StockRequested>::from_topic("orders.OrderPlaced")
+//!     .rename("orderNo", "order_id")
+//!     .rename("customer.id", "customer_id")
+//!     .map("qty", "quantity", |qty| qty.as_str().and_then(|qty| qty.parse::<i64>().ok()).map(Into::into).unwrap_or_default())
+//!     .default("priority", "normal")
+//!     .drop("memo");
+//!
+//! let inbox = Inbox::new(conn, pool.clone()).translate(translation);
+//! ```
+//! Rules are applied in the order they are given. Fields not mentioned are passed as they are, and source path
+//! may point into nested objects with `.`. Payload that fails to translate is [InboxOutcome::Malformed](super::inbox::InboxOutcome::Malformed).
+use std::marker::PhantomData;
+use std::sync::Arc;
+
+use serde::de::DeserializeOwned;
+use serde_json::{Map, Value};
+
+use crate::prelude::{json, TEvent};
+
+type Mapper = Arc<dyn Fn(Value) -> Value + Send + Sync>;
+
+enum Rule {
+	Rename { from: String, to: String },
+	Map { from: String, to: String, mapper: Mapper },
+	Default { field: String, value: Value },
+	Drop(String),
+}
+
+/// Declarative mapping of foreign event on `topic` into `T`
+pub struct Translation<T> {
+	topic: String,
+	rules: Vec<Rule>,
+	_target: PhantomData<fn() -> T>,
+}
+
+impl<T: TEvent + DeserializeOwned + 'static> Translation<T> {
+	/// `topic` is the one the other context publishes to, without namespace.
+	pub fn from_topic(topic: impl Into<String>) -> Self {
+		Self {
+			topic: topic.into(),
+			rules: vec![],
+			_target: PhantomData,
+		}
+	}
+
+	/// Move the value at `from` to the field `to`. Nothing happens if it is missing.
+	pub fn rename(mut self, from: impl Into<String>, to: impl Into<String>) -> Self {
+		self.rules.push(Rule::Rename { from: from.into(), to: to.into() });
+		self
+	}
+
+	/// Move the value at `from` to the field `to` through `mapper` - unit conversion, enum of the other context and so on.
+	/// Nothing happens if it is missing.
+	pub fn map(mut self, from: impl Into<String>, to: impl Into<String>, mapper: impl Fn(Value) -> Value + Send + Sync + 'static) -> Self {
+		self.rules.push(Rule::Map {
+			from: from.into(),
+			to: to.into(),
+			mapper: Arc::new(mapper),
+		});
+		self
+	}
+
+	/// Fill `field` when the other context doesn't give it, or gives `null`.
+	pub fn default(mut self, field: impl Into<String>, value: impl Into<Value>) -> Self {
+		self.rules.push(Rule::Default {
+			field: field.into(),
+			value: value.into(),
+		});
+		self
+	}
+
+	/// Remove the value at `field`
+	pub fn drop(mut self, field: impl Into<String>) -> Self {
+		self.rules.push(Rule::Drop(field.into()));
+		self
+	}
+
+	pub fn topic(&self) -> &str {
+		&self.topic
+	}
+
+	/// Translate `payload` of the foreign event into `T`
+	pub fn translate(&self, payload: &str) -> Result<T, String> {
+		let Value::Object(mut fields) = json::from_str::<Value>(payload).map_err(|err| err.to_string())? else {
+			return Err(format!("Payload of {} is not an object", self.topic));
+		};
+		for rule in self.rules.iter() {
+			match rule {
+				Rule::Rename { from, to } => {
+					if let Some(value) = take(&mut fields, from) {
+						fields.insert(to.clone(), value);
+					}
+				}
+				Rule::Map { from, to, mapper } => {
+					if let Some(value) = take(&mut fields, from) {
+						fields.insert(to.clone(), mapper(value));
+					}
+				}
+				Rule::Default { field, value } => {
+					let current = fields.entry(field.clone()).or_insert(Value::Null);
+					if current.is_null() {
+						*current = value.clone();
+					}
+				}
+				Rule::Drop(field) => {
+					take(&mut fields, field);
+				}
+			}
+		}
+		serde_json::from_value(Value::Object(fields)).map_err(|err| format!("Failed to translate {} into {}: {}", self.topic, std::any::type_name::<T>(), err))
+	}
+
+	/// Translation as the inbox takes it
+	pub(crate) fn into_deserializer(self) -> impl Fn(&str) -> Result<Arc<dyn TEvent>, String> + Send + Sync {
+		move |payload| self.translate(payload).map(|event| Arc::new(event) as Arc<dyn TEvent>)
+	}
+}
+
+/// Remove the value at `path`, whose segments are separated by `.`
+fn take(fields: &mut Map<String, Value>, path: &str) -> Option<Value> {
+	match path.split_once('.') {
+		None => fields.remove(path),
+		Some((head, rest)) => match fields.get_mut(head)? {
+			Value::Object(nested) => take(nested, rest),
+			_ => None,
+		},
+	}
+}
+
+#[test]
+fn test_translation() {
+	#[derive(Debug, PartialEq, serde::Deserialize)]
+	struct StockRequested {
+		order_id: i64,
+		customer_id: i64,
+		quantity: i64,
+		priority: String,
+	}
+	impl TEvent for StockRequested {
+		fn internally_notifiable(&self) -> bool {
+			true
+		}
+		fn state(&self) -> String {
+			"{}".into()
+		}
+	}
+
+	let translation = Translation::<StockRequested>::from_topic("orders.OrderPlaced")
+		.rename("orderNo", "order_id")
+		.rename("customer.id", "customer_id")
+		.map("qty", "quantity", |qty| qty.as_str().and_then(|qty| qty.parse::<i64>().ok()).map(Into::into).unwrap_or_default())
+		.default("priority", "normal")
+		.drop("customer");
+
+	let translated = translation
+		.translate(r#"{"orderNo": 1, "customer": {"id": 7, "grade": "gold"}, "qty": "3", "priority": null}"#)
+		.unwrap();
+	let expected = StockRequested {
+		order_id: 1,
+		customer_id: 7,
+		quantity: 3,
+		priority: "normal".into(),
+	};
+	assert_eq!(translated, expected);
+
+	// Given value is kept
+	let translated = translation.translate(r#"{"orderNo": 1, "customer": {"id": 7}, "qty": "3", "priority": "high"}"#).unwrap();
+	assert_eq!(translated.priority, "high");
+
+	let err = translation.translate(r#"{"orderNo": 1, "qty": "3"}"#).unwrap_err();
+	assert!(err.contains("customer_id"));
+	assert!(translation.translate("[]").is_err());
+}
@@ -0,0 +1,237 @@
+//! ### Command validation
+//! [ValidationAspect] checks the command with [TValidate] before the inner service runs, failing with `BaseError::ValidationFailed`
+//! that lists every violated rule, answered with 422 by web integrations. Rules are declared on fields with `#[derive(TValidate)]`.
+//!
+//! ```rust,ignore
+//! #[derive(Debug, TValidate)]
+//! pub struct RegisterUser {
+//!     #[validate(length(min = 1, max = 30), regex = "^[a-z0-9_]+$")]
+//!     pub username: String,
+//!     #[validate(range(min = 14))]
+//!     pub age: u32,
+//!     // Checked only when given
+//!     #[validate(length(max = 200))]
+//!     pub bio: Option<String>,
+//! }
+//!
+//! impl TCommandRoute for RegisterUser {
+//!     fn command_handler(context_manager: AtomicContextManager, cmd: Self) -> impl TCommandService<Self::Response, Self::Error> {
+//!         ValidationAspect::new(&cmd, CommandHandler((cmd, Context::new(context_manager))))
+//!     }
+//! }
+//! ```
+//! Bounds of `range` are of the type of the field, such as `range(min = 0.5)` for `f64` and `range(min = Decimal::ONE)`
+//! for `Decimal`. `regex` rule requires `regex` feature.
+//!
+//! Rules that can't be declared, such as the ones across fields, go in a hand-written [TValidate] using [ValidationErrors::add].
+use super::messagebus::TCommandService;
+use crate::prelude::{ApplicationError, ApplicationResponse, BaseError};
+
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize)]
+pub struct ValidationError {
+	pub field: String,
+	/// Rule that is violated - `length`, `range`, `regex` or what hand-written validation gives
+	pub code: String,
+	pub message: String,
+}
+
+/// Every violation found in a command
+#[derive(Debug, Clone, Default, PartialEq, Eq, serde::Serialize)]
+pub struct ValidationErrors(pub Vec<ValidationError>);
+
+impl ValidationErrors {
+	pub fn add(&mut self, field: impl Into<String>, code: impl Into<String>, message: impl Into<String>) {
+		self.0.push(ValidationError {
+			field: field.into(),
+			code: code.into(),
+			message: message.into(),
+		});
+	}
+
+	pub fn is_empty(&self) -> bool {
+		self.0.is_empty()
+	}
+
+	/// Violations of `field`
+	pub fn field<'a>(&'a self, field: &'a str) -> impl Iterator<Item = &'a ValidationError> {
+		self.0.iter().filter(move |error| error.field == field)
+	}
+
+	pub fn into_result(self) -> Result<(), Self> {
+		match self.is_empty() {
+			true => Ok(()),
+			false => Err(self),
+		}
+	}
+
+	/// `length(min, max)` rule. Length of string is counted in characters.
+	pub fn check_length(&mut self, field: &str, length: usize, min: Option<usize>, max: Option<usize>) {
+		if let Some(min) = min.filter(|min| length < *min) {
+			self.add(field, "length", format!("length must be at least {}", min));
+		}
+		if let Some(max) = max.filter(|max| length > *max) {
+			self.add(field, "length", format!("length must be at most {}", max));
+		}
+	}
+
+	/// `range(min, max)` rule, both inclusive. Bounds are compared in the type of the field, so that large integers and
+	/// decimals are not rounded.
+	pub fn check_range<T: PartialOrd + std::fmt::Display>(&mut self, field: &str, value: &T, min: Option<T>, max: Option<T>) {
+		if let Some(min) = min.filter(|min| value < min) {
+			self.add(field, "range", format!("must be at least {}", min));
+		}
+		if let Some(max) = max.filter(|max| value > max) {
+			self.add(field, "range", format!("must be at most {}", max));
+		}
+	}
+
+	/// `regex` rule, enabled by `regex` feature. `pattern` is compiled once and kept for later checks.
+	/// ## Panics
+	/// If `pattern` is not a valid regex. Patterns of `#[derive(TValidate)]` are checked at compile time.
+	#[cfg(feature = "regex")]
+	pub fn check_regex(&mut self, field: &str, value: &str, pattern: &'static str) {
+		use std::sync::{LazyLock, RwLock};
+		static COMPILED: LazyLock<RwLock<hashbrown::HashMap<&'static str, regex::Regex>>> = LazyLock::new(Default::default);
+		let compiled = COMPILED.read().unwrap().get(pattern).map(|regex| regex.is_match(value));
+		let matched = match compiled {
+			Some(matched) => matched,
+			None => {
+				let regex = regex::Regex::new(pattern).expect("Invalid validation pattern!");
+				let matched = regex.is_match(value);
+				COMPILED.write().unwrap().insert(pattern, regex);
+				matched
+			}
+		};
+		if !matched {
+			self.add(field, "regex", format!("must match {}", pattern));
+		}
+	}
+}
+
+impl std::fmt::Display for ValidationErrors {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		let errors = self.0.iter().map(|error| format!("{}: {}", error.field, error.message)).collect::<Vec<_>>();
+		write!(f, "{}", errors.join(", "))
+	}
+}
+
+pub trait TValidate {
+	fn validate(&self) -> Result<(), ValidationErrors>;
+}
+
+/// Length that `length` rule of `#[derive(TValidate)]` checks
+pub trait TValidateLength {
+	fn validation_length(&self) -> usize;
+}
+
+impl TValidateLength for str {
+	fn validation_length(&self) -> usize {
+		self.chars().count()
+	}
+}
+
+impl TValidateLength for String {
+	fn validation_length(&self) -> usize {
+		self.as_str().validation_length()
+	}
+}
+
+impl<T> TValidateLength for Vec<T> {
+	fn validation_length(&self) -> usize {
+		self.len()
+	}
+}
+
+impl<T> TValidateLength for [T] {
+	fn validation_length(&self) -> usize {
+		self.len()
+	}
+}
+
+impl<K, V, S> TValidateLength for std::collections::HashMap<K, V, S> {
+	fn validation_length(&self) -> usize {
+		self.len()
+	}
+}
+
+/// Validate the command before running `inner` service.
+pub struct ValidationAspect<S> {
+	validated: Result<(), ValidationErrors>,
+	inner: S,
+}
+
+impl<S> ValidationAspect<S> {
+	/// `command` is validated right away, as it is usually moved into `inner`.
+	pub fn new<C: TValidate>(command: &C, inner: S) -> Self {
+		Self { validated: command.validate(), inner }
+	}
+}
+
+impl<R, E, S> TCommandService<R, E> for ValidationAspect<S>
+where
+	R: ApplicationResponse,
+	E: ApplicationError + std::convert::From<BaseError>,
+	S: TCommandService<R, E>,
+{
+	async fn execute(self) -> Result<R, E> {
+		self.validated.map_err(BaseError::ValidationFailed)?;
+		self.inner.execute().await
+	}
+}
+
+#[tokio::test]
+async fn test_validation_aspect() {
+	struct Transfer {
+		memo: String,
+		amount: i64,
+	}
+	impl TValidate for Transfer {
+		fn validate(&self) -> Result<(), ValidationErrors> {
+			let mut errors = ValidationErrors::default();
+			errors.check_length("memo", self.memo.validation_length(), None, Some(5));
+			#[cfg(feature = "regex")]
+			errors.check_regex("memo", &self.memo, "^[a-z]*$");
+			errors.check_range("amount", &self.amount, Some(1), None);
+			errors.into_result()
+		}
+	}
+	struct Handler;
+	impl TCommandService<(), BaseError> for Handler {
+		async fn execute(self) -> Result<(), BaseError> {
+			Ok(())
+		}
+	}
+
+	assert!(ValidationAspect::new(&Transfer { memo: "rent".into(), amount: 10 }, Handler).execute().await.is_ok());
+
+	let Err(BaseError::ValidationFailed(errors)) = ValidationAspect::new(
+		&Transfer {
+			memo: "Rent 한달".into(), amount: 0
+		},
+		Handler,
+	)
+	.execute()
+	.await
+	else {
+		panic!("Transfer must be invalid");
+	};
+	#[cfg(feature = "regex")]
+	{
+		assert_eq!(errors.field("memo").map(|error| error.code.as_str()).collect::<Vec<_>>(), vec!["length", "regex"]);
+		assert_eq!(errors.to_string(), "memo: length must be at most 5, memo: must match ^[a-z]*$, amount: must be at least 1");
+	}
+	#[cfg(not(feature = "regex"))]
+	assert_eq!(errors.to_string(), "memo: length must be at most 5, amount: must be at least 1");
+	assert_eq!(errors.field("amount").next().unwrap().message, "must be at least 1");
+}
+
+#[test]
+fn test_check_range_in_field_type() {
+	// Both are the same as f64
+	let (limit, value) = (9_007_199_254_740_992_i64, 9_007_199_254_740_993_i64);
+	let mut errors = ValidationErrors::default();
+	errors.check_range("quota", &value, None, Some(limit));
+	errors.check_range("quota", &limit, None, Some(limit));
+	assert_eq!(errors.0.len(), 1);
+	assert_eq!(errors.0[0].message, "must be at most 9007199254740992");
+}
@@ -0,0 +1,89 @@
+//! ### Clock
+//! Time source of timestamps ruva records - outbox `create_dt`, timeout due time, reservation expiry and replay window.
+//! Defaults to system time. Replace it process-wide with [set_clock] or per dispatch with `ContextManager::with_clock`
+//! to make timestamps deterministic in tests or to replay with historical time.
+//!
+//! ```rust
+//! # use std::sync::Arc;
+//! # use ruva_core::prelude::*;
+//! # struct Connection;
+//! # impl TConnection for Connection {}
+//! # fn main() -> Result<(), Box<dyn std::error::Error>> {
+//! # let conn = &Connection;
+//! let clock = Arc::new(FixedClock::new("2024-01-01T00:00:00Z".parse()?));
+//! let context_manager = ContextManager::new(conn).with_clock(clock.clone());
+//! clock.advance(chrono::Duration::hours(2));
+//! # Ok(())
+//! # }
+//! ```
+use std::sync::{atomic::AtomicI64, atomic::Ordering, Arc, OnceLock};
+
+use chrono::{DateTime, Utc};
+
+pub trait TClock: Send + Sync {
+	fn now(&self) -> DateTime<Utc>;
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemClock;
+
+impl TClock for SystemClock {
+	fn now(&self) -> DateTime<Utc> {
+		Utc::now()
+	}
+}
+
+/// Clock that moves only when told to. Precision is millisecond.
+#[derive(Debug)]
+pub struct FixedClock(AtomicI64);
+
+impl FixedClock {
+	pub fn new(now: DateTime<Utc>) -> Self {
+		Self(AtomicI64::new(now.timestamp_millis()))
+	}
+
+	pub fn set(&self, now: DateTime<Utc>) {
+		self.0.store(now.timestamp_millis(), Ordering::Relaxed);
+	}
+
+	pub fn advance(&self, duration: chrono::Duration) {
+		self.0.fetch_add(duration.num_milliseconds(), Ordering::Relaxed);
+	}
+}
+
+impl TClock for FixedClock {
+	fn now(&self) -> DateTime<Utc> {
+		DateTime::from_timestamp_millis(self.0.load(Ordering::Relaxed)).expect("Timestamp out of range")
+	}
+}
+
+impl<C: TClock + ?Sized> TClock for Arc<C> {
+	fn now(&self) -> DateTime<Utc> {
+		self.as_ref().now()
+	}
+}
+
+static CLOCK: OnceLock<Arc<dyn TClock>> = OnceLock::new();
+
+/// ## Panics
+/// If clock is already set.
+pub fn set_clock(clock: impl TClock + 'static) {
+	if CLOCK.set(Arc::new(clock)).is_err() {
+		panic!("Clock Is Already Set!");
+	}
+}
+
+/// Process-wide clock. [SystemClock] unless set with [set_clock].
+pub fn clock() -> Arc<dyn TClock> {
+	CLOCK.get_or_init(|| Arc::new(SystemClock)).clone()
+}
+
+#[test]
+fn test_fixed_clock() {
+	let start = DateTime::from_timestamp_millis(1_700_000_000_000).unwrap();
+	let clock = Arc::new(FixedClock::new(start));
+	assert_eq!(clock.now(), start);
+
+	clock.advance(chrono::Duration::seconds(90));
+	assert_eq!(TClock::now(&clock), start + chrono::Duration::seconds(90));
+}
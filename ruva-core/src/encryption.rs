@@ -0,0 +1,158 @@
+//! ### Encryption at rest
+//! Fields of aggregate marked with `#[encrypted_column]` are encrypted at the persistence boundary,
+//! that is, when aggregate is converted to its adapter, and decrypted on the way back.
+//! Encryption itself is delegated to [TKeyProvider] so that KMS or any other key management can be plugged in.
+//!
+//! Ciphertext is bound to the cell it is written to - aggregate, column and id of the aggregate - as associated data,
+//! so that ciphertext copied to another row or column, one customer's SSN into another's record for example, fails to decrypt.
+//!
+//! ```rust,ignore
+//! // On boot, before any aggregate is converted
+//! ruva::set_key_provider(RingKeyProvider::new(2, key)); // with `encryption-ring` feature
+//!
+//! // After rotation, ciphertext of the retired key is still decrypted
+//! ruva::set_key_provider(RingKeyProvider::new(3, new_key).with_retired_key(2, key));
+//! ```
+use std::sync::OnceLock;
+
+use base64::Engine;
+
+use crate::prelude::BaseError;
+
+pub trait TKeyProvider: Send + Sync {
+	/// Encrypt `plaintext`, authenticating `aad` along with it
+	fn encrypt(&self, plaintext: &[u8], aad: &[u8]) -> Result<Vec<u8>, BaseError>;
+	/// Fails unless `aad` is the one `ciphertext` is encrypted with
+	fn decrypt(&self, ciphertext: &[u8], aad: &[u8]) -> Result<Vec<u8>, BaseError>;
+}
+
+static KEY_PROVIDER: OnceLock<Box<dyn TKeyProvider>> = OnceLock::new();
+
+/// ## Panics
+/// If key provider is already set.
+pub fn set_key_provider(provider: impl TKeyProvider + 'static) {
+	if KEY_PROVIDER.set(Box::new(provider)).is_err() {
+		panic!("Key Provider Is Already Set!");
+	}
+}
+
+fn key_provider() -> Result<&'static dyn TKeyProvider, BaseError> {
+	match KEY_PROVIDER.get() {
+		Some(provider) => Ok(provider.as_ref()),
+		None => {
+			tracing::error!("Key provider is not set! Call `set_key_provider` before converting aggregate with encrypted column.");
+			Err(BaseError::ServiceError)
+		}
+	}
+}
+
+/// Associated data of `column` of `aggregate` identified by `id`
+fn column_aad(aggregate: &str, column: &str, id: &dyn std::fmt::Display) -> Vec<u8> {
+	format!("{}.{}:{}", aggregate, column, id).into_bytes()
+}
+
+/// Used by adapter generated by `#[aggregate]`. Returns base64 encoded ciphertext bound to `column` of `aggregate` identified by `id`.
+pub fn encrypt_column(plaintext: &str, aggregate: &str, column: &str, id: &dyn std::fmt::Display) -> Result<String, BaseError> {
+	let ciphertext = key_provider()?.encrypt(plaintext.as_bytes(), &column_aad(aggregate, column, id))?;
+	Ok(base64::engine::general_purpose::STANDARD.encode(ciphertext))
+}
+
+/// Used by adapter generated by `#[aggregate]`.
+/// ## Errors
+/// If `ciphertext` is not the one encrypted for `column` of `aggregate` identified by `id`.
+pub fn decrypt_column(ciphertext: &str, aggregate: &str, column: &str, id: &dyn std::fmt::Display) -> Result<String, BaseError> {
+	// Empty string is what `Default` gives, which is never a result of encryption.
+	if ciphertext.is_empty() {
+		return Ok(String::new());
+	}
+	let ciphertext = base64::engine::general_purpose::STANDARD
+		.decode(ciphertext)
+		.map_err(|err| BaseError::DatabaseError(format!("Encrypted column is not base64 encoded! {}", err)))?;
+	let plaintext = key_provider()?.decrypt(&ciphertext, &column_aad(aggregate, column, id))?;
+	String::from_utf8(plaintext).map_err(|_| BaseError::DatabaseError("Decrypted column is not utf-8!".to_string()))
+}
+
+/// AES-256-GCM with id of the key and random nonce prepended to ciphertext.
+/// Keys are rotated by giving new key with new id, keeping the retired ones with [with_retired_key](RingKeyProvider::with_retired_key).
+#[cfg(feature = "encryption-ring")]
+pub struct RingKeyProvider {
+	key_id: u32,
+	keys: hashbrown::HashMap<u32, ring::aead::LessSafeKey>,
+	rng: ring::rand::SystemRandom,
+}
+
+#[cfg(feature = "encryption-ring")]
+impl RingKeyProvider {
+	const KEY_ID_LEN: usize = 4;
+
+	/// Encrypt with `key` identified by `key_id`
+	pub fn new(key_id: u32, key: [u8; 32]) -> Self {
+		Self {
+			key_id,
+			keys: hashbrown::HashMap::from([(key_id, Self::aead_key(key))]),
+			rng: ring::rand::SystemRandom::new(),
+		}
+	}
+
+	/// Decrypt ciphertext encrypted with `key` before it is rotated
+	pub fn with_retired_key(mut self, key_id: u32, key: [u8; 32]) -> Self {
+		self.keys.entry(key_id).or_insert_with(|| Self::aead_key(key));
+		self
+	}
+
+	fn aead_key(key: [u8; 32]) -> ring::aead::LessSafeKey {
+		ring::aead::LessSafeKey::new(ring::aead::UnboundKey::new(&ring::aead::AES_256_GCM, &key).expect("Key length is fixed"))
+	}
+}
+
+#[cfg(feature = "encryption-ring")]
+impl TKeyProvider for RingKeyProvider {
+	fn encrypt(&self, plaintext: &[u8], aad: &[u8]) -> Result<Vec<u8>, BaseError> {
+		use ring::rand::SecureRandom;
+
+		let mut nonce = [0u8; ring::aead::NONCE_LEN];
+		self.rng.fill(&mut nonce).map_err(|_| BaseError::ServiceError)?;
+		let mut in_out = plaintext.to_vec();
+		self.keys[&self.key_id]
+			.seal_in_place_append_tag(ring::aead::Nonce::assume_unique_for_key(nonce), ring::aead::Aad::from(aad), &mut in_out)
+			.map_err(|_| BaseError::ServiceError)?;
+		Ok([self.key_id.to_be_bytes().to_vec(), nonce.to_vec(), in_out].concat())
+	}
+
+	fn decrypt(&self, ciphertext: &[u8], aad: &[u8]) -> Result<Vec<u8>, BaseError> {
+		if ciphertext.len() < Self::KEY_ID_LEN + ring::aead::NONCE_LEN {
+			return Err(BaseError::ServiceError);
+		}
+		let (key_id, ciphertext) = ciphertext.split_at(Self::KEY_ID_LEN);
+		let key_id = u32::from_be_bytes(key_id.try_into().expect("Length is checked"));
+		let Some(key) = self.keys.get(&key_id) else {
+			tracing::error!(key_id, "Ciphertext is encrypted with unknown key!");
+			return Err(BaseError::ServiceError);
+		};
+		let (nonce, sealed) = ciphertext.split_at(ring::aead::NONCE_LEN);
+		let nonce = ring::aead::Nonce::try_assume_unique_for_key(nonce).map_err(|_| BaseError::ServiceError)?;
+		let mut in_out = sealed.to_vec();
+		let plaintext = key.open_in_place(nonce, ring::aead::Aad::from(aad), &mut in_out).map_err(|_| BaseError::ServiceError)?;
+		Ok(plaintext.to_vec())
+	}
+}
+
+#[cfg(feature = "encryption-ring")]
+#[test]
+fn test_ring_key_provider_round_trip() {
+	let provider = RingKeyProvider::new(1, [7; 32]);
+	let ciphertext = provider.encrypt(b"secret", b"Customer.ssn:1").unwrap();
+	assert_ne!(&ciphertext[RingKeyProvider::KEY_ID_LEN + ring::aead::NONCE_LEN..], b"secret");
+	assert_eq!(provider.decrypt(&ciphertext, b"Customer.ssn:1").unwrap(), b"secret");
+	assert!(RingKeyProvider::new(1, [8; 32]).decrypt(&ciphertext, b"Customer.ssn:1").is_err());
+
+	// Copied to another row
+	assert!(provider.decrypt(&ciphertext, b"Customer.ssn:2").is_err());
+
+	// Rotated
+	let rotated = RingKeyProvider::new(2, [8; 32]).with_retired_key(1, [7; 32]);
+	assert_eq!(rotated.decrypt(&ciphertext, b"Customer.ssn:1").unwrap(), b"secret");
+	let ciphertext = rotated.encrypt(b"secret", b"Customer.ssn:1").unwrap();
+	assert_eq!(ciphertext[..RingKeyProvider::KEY_ID_LEN], 2u32.to_be_bytes());
+	assert!(provider.decrypt(&ciphertext, b"Customer.ssn:1").is_err());
+}
@@ -0,0 +1,362 @@
+//! ### Event sourcing
+//! Instead of its latest state, event sourced aggregate is kept as append-only stream of the events it went through.
+//! [EventSourcedRepository] rebuilds the aggregate by applying the events of its stream in order, and appends new ones
+//! only if nothing else has been appended since the aggregate was loaded.
+//!
+//! ```rust
+//! # use std::collections::VecDeque;
+//! # use std::sync::Arc;
+//! # use ruva_core::prelude::*;
+//! # #[derive(Default)]
+//! # pub struct Account { id: String, balance: i64, version: i64, events: VecDeque<Arc<dyn TEvent>> }
+//! # impl TAggregate for Account {
+//! #     fn events(&self) -> &VecDeque<Arc<dyn TEvent>> { &self.events }
+//! #     fn take_events(&mut self) -> VecDeque<Arc<dyn TEvent>> { std::mem::take(&mut self.events) }
+//! #     fn raise_event(&mut self, event: Arc<dyn TEvent>) { self.events.push_back(event) }
+//! # }
+//! # pub struct Deposit { account_id: String, amount: i64 }
+//! #[derive(Serialize, Deserialize)]
+//! pub enum AccountEvent {
+//!     Opened { id: String },
+//!     Deposited { amount: i64 },
+//! }
+//!
+//! impl TEventSourced for Account {
+//!     type Event = AccountEvent;
+//!     const EVENT_TYPE: &'static str = "AccountEvent";
+//!     fn aggregate_id(&self) -> String { self.id.clone() }
+//!     fn version(&self) -> i64 { self.version }
+//!     fn set_version(&mut self, version: i64) { self.version = version }
+//!     fn apply(&mut self, event: &AccountEvent) {
+//!         match event {
+//!             AccountEvent::Opened { id } => self.id = id.clone(),
+//!             AccountEvent::Deposited { amount } => self.balance += amount,
+//!         }
+//!     }
+//! }
+//!
+//! # async fn example(mut ctx: impl TEventStore, cmd: Deposit) -> Result<(), BaseError> {
+//! // In command handler. `Context` implements `TEventStore` with `sqlx-postgres` feature.
+//! let mut repository = EventSourcedRepository::<Account, _>::new(&mut ctx);
+//! let mut account = repository.get(&cmd.account_id).await?;
+//! repository.save(&mut account, vec![AccountEvent::Deposited { amount: cmd.amount }]).await?;
+//! # Ok(())
+//! # }
+//! ```
+//! Append on stale aggregate fails with `BaseError::ConcurrencyConflict`, so the command can be run again with `RetryHandler`.
+//!
+//! Each event is stored with [TEventSourced::EVENT_VERSION] of the time. Once the event type changes, bump it and register
+//! [TEventUpcaster](crate::prelude::TEventUpcaster) for [TEventSourced::EVENT_TYPE], such as `AccountEvent`, so that
+//! older events are upcast when the stream is loaded.
+//!
+//! When early events of long streams are moved to cold storage, wrap the store with [ArchivedEventStore] so that the
+//! aggregate is still rebuilt from the first event.
+use std::{borrow::Cow, future::Future};
+
+use serde::{de::DeserializeOwned, Serialize};
+
+use crate::prelude::{json, upcast_payload, BaseError, TAggregate, TLoadAggregate, INITIAL_EVENT_VERSION};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StoredEvent {
+	pub aggregate_id: String,
+	/// Position in the stream, starting from 1
+	pub seq: i64,
+	pub payload: String,
+	/// [TEventSourced::EVENT_VERSION] the payload was serialized at
+	pub version: u32,
+}
+
+/// Where event streams are kept. Implemented on the unit of work so that events are appended in the transaction of the command.
+pub trait TEventStore: Send {
+	/// Events of the aggregate in order of `seq`
+	fn load_stream(&mut self, aggregate_id: &str) -> impl Future<Output = Result<Vec<StoredEvent>, BaseError>> + Send;
+	/// Append `payloads` serialized at `version` right after `expected_seq`.
+	/// ## Errors
+	/// [BaseError::ConcurrencyConflict] if the stream has moved past `expected_seq`.
+	fn append_stream(&mut self, aggregate_id: &str, expected_seq: i64, version: u32, payloads: Vec<String>) -> impl Future<Output = Result<(), BaseError>> + Send;
+}
+
+impl<S: TEventStore> TEventStore for &mut S {
+	fn load_stream(&mut self, aggregate_id: &str) -> impl Future<Output = Result<Vec<StoredEvent>, BaseError>> + Send {
+		(**self).load_stream(aggregate_id)
+	}
+	fn append_stream(&mut self, aggregate_id: &str, expected_seq: i64, version: u32, payloads: Vec<String>) -> impl Future<Output = Result<(), BaseError>> + Send {
+		(**self).append_stream(aggregate_id, expected_seq, version, payloads)
+	}
+}
+
+/// Aggregate whose state is derived from its events
+pub trait TEventSourced: TAggregate + 'static {
+	type Event: Serialize + DeserializeOwned + Send + Sync;
+	/// Topic upcasters of [Event](TEventSourced::Event) are registered for. Must be unique among event sourced aggregates.
+	const EVENT_TYPE: &'static str;
+	/// Version of the events appended from now on
+	const EVENT_VERSION: u32 = INITIAL_EVENT_VERSION;
+
+	fn aggregate_id(&self) -> String;
+	/// Change the state by `event`. It must not fail, as the event has happened already.
+	fn apply(&mut self, event: &Self::Event);
+	/// Sequence of the last event applied. `0` for new aggregate.
+	fn version(&self) -> i64;
+	fn set_version(&mut self, version: i64);
+}
+
+pub struct EventSourcedRepository<A, S> {
+	store: S,
+	_aggregate: std::marker::PhantomData<fn() -> A>,
+}
+
+impl<A: TEventSourced, S: TEventStore> EventSourcedRepository<A, S> {
+	pub fn new(store: S) -> Self {
+		Self {
+			store,
+			_aggregate: std::marker::PhantomData,
+		}
+	}
+
+	/// Rebuild the aggregate from its stream
+	/// ## Errors
+	/// [BaseError::NotFound] if the stream is empty.
+	pub async fn get(&mut self, aggregate_id: &str) -> Result<A, BaseError> {
+		let stream = self.store.load_stream(aggregate_id).await?;
+		if stream.is_empty() {
+			return Err(BaseError::NotFound);
+		}
+		let mut aggregate = A::default();
+		for stored in stream {
+			// Parsed in place, either upcast or as it is stored
+			let upcasted = match upcast_payload(A::EVENT_TYPE, stored.version, &stored.payload)? {
+				Cow::Owned(upcasted) => Some(upcasted),
+				Cow::Borrowed(_) => None,
+			};
+			let payload = upcasted.unwrap_or(stored.payload);
+			let event = json::from_string::<A::Event>(payload).map_err(|err| BaseError::DatabaseError(format!("Failed to deserialize event {} of {}: {}", stored.seq, aggregate_id, err)))?;
+			aggregate.apply(&event);
+			aggregate.set_version(stored.seq);
+		}
+		Ok(aggregate)
+	}
+
+	/// Apply `events` to `aggregate` and append them to its stream, expecting the stream to be at `aggregate.version()`.
+	/// Events are applied first so that the creating event can give the aggregate its id. Discard the aggregate if it fails.
+	pub async fn save(&mut self, aggregate: &mut A, events: Vec<A::Event>) -> Result<(), BaseError> {
+		if events.is_empty() {
+			return Ok(());
+		}
+		let payloads = events
+			.iter()
+			.map(|event| json::to_string(event).map_err(|err| BaseError::DatabaseError(err.to_string())))
+			.collect::<Result<Vec<_>, _>>()?;
+		let expected_seq = aggregate.version();
+		events.iter().for_each(|event| aggregate.apply(event));
+		aggregate.set_version(expected_seq + payloads.len() as i64);
+		self.store.append_stream(&aggregate.aggregate_id(), expected_seq, A::EVENT_VERSION, payloads).await
+	}
+}
+
+impl<A: TEventSourced, S: TEventStore> TLoadAggregate<A, str> for EventSourcedRepository<A, S> {
+	fn load_aggregate(&mut self, id: &str) -> impl Future<Output = Result<A, BaseError>> + Send {
+		self.get(id)
+	}
+}
+
+/// Cold storage of events moved out of the event store
+pub trait TEventArchive: Send + Sync {
+	/// Archived events of the stream whose `seq` is below `before`, in order of `seq`
+	fn load_archived(&self, aggregate_id: &str, before: i64) -> impl Future<Output = Result<Vec<StoredEvent>, BaseError>> + Send;
+}
+
+/// [TEventStore] that loads the beginning of the stream from `archive` when it is no longer in `store`.
+/// Archival must leave at least the last event of every stream in `store`, as append is checked against it.
+pub struct ArchivedEventStore<S, A> {
+	store: S,
+	archive: A,
+}
+
+impl<S: TEventStore, A: TEventArchive> ArchivedEventStore<S, A> {
+	pub fn new(store: S, archive: A) -> Self {
+		Self { store, archive }
+	}
+}
+
+impl<S: TEventStore, A: TEventArchive> TEventStore for ArchivedEventStore<S, A> {
+	async fn load_stream(&mut self, aggregate_id: &str) -> Result<Vec<StoredEvent>, BaseError> {
+		let stream = self.store.load_stream(aggregate_id).await?;
+		let Some(first) = stream.first().map(|stored| stored.seq).filter(|seq| *seq > 1) else {
+			return Ok(stream);
+		};
+		let mut archived = self.archive.load_archived(aggregate_id, first).await?;
+		if archived.len() as i64 != first - 1 {
+			return Err(BaseError::DatabaseError(format!("Archive of {} has {} events before {}", aggregate_id, archived.len(), first)));
+		}
+		archived.extend(stream);
+		Ok(archived)
+	}
+
+	fn append_stream(&mut self, aggregate_id: &str, expected_seq: i64, version: u32, payloads: Vec<String>) -> impl Future<Output = Result<(), BaseError>> + Send {
+		self.store.append_stream(aggregate_id, expected_seq, version, payloads)
+	}
+}
+
+/// For tests. Streams are lost on drop.
+#[derive(Default)]
+pub struct InMemoryEventStore(hashbrown::HashMap<String, Vec<StoredEvent>>);
+
+impl TEventStore for InMemoryEventStore {
+	async fn load_stream(&mut self, aggregate_id: &str) -> Result<Vec<StoredEvent>, BaseError> {
+		Ok(self.0.get(aggregate_id).cloned().unwrap_or_default())
+	}
+
+	async fn append_stream(&mut self, aggregate_id: &str, expected_seq: i64, version: u32, payloads: Vec<String>) -> Result<(), BaseError> {
+		let stream = self.0.entry(aggregate_id.to_string()).or_default();
+		let last_seq = stream.last().map(|stored| stored.seq).unwrap_or_default();
+		if last_seq != expected_seq {
+			return Err(BaseError::ConcurrencyConflict(format!("Stream of {} is at {}, not {}", aggregate_id, last_seq, expected_seq)));
+		}
+		stream.extend(payloads.into_iter().zip(expected_seq + 1..).map(|(payload, seq)| StoredEvent {
+			aggregate_id: aggregate_id.to_string(),
+			seq,
+			payload,
+			version,
+		}));
+		Ok(())
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use super::*;
+	use crate::prelude::TEvent;
+	use std::collections::VecDeque;
+	use std::sync::Arc;
+
+	#[derive(Default)]
+	struct Account {
+		id: String,
+		balance: i64,
+		version: i64,
+		events: VecDeque<Arc<dyn TEvent>>,
+	}
+
+	impl TAggregate for Account {
+		fn events(&self) -> &VecDeque<Arc<dyn TEvent>> {
+			&self.events
+		}
+		fn take_events(&mut self) -> VecDeque<Arc<dyn TEvent>> {
+			std::mem::take(&mut self.events)
+		}
+		fn raise_event(&mut self, event: Arc<dyn TEvent>) {
+			self.events.push_back(event)
+		}
+	}
+
+	#[derive(Serialize, serde::Deserialize)]
+	enum AccountEvent {
+		Opened { id: String },
+		Deposited { amount: i64 },
+	}
+
+	impl TEventSourced for Account {
+		type Event = AccountEvent;
+		const EVENT_TYPE: &'static str = "AccountEvent";
+		// * `Deposited` was `Credited` at version 1
+		const EVENT_VERSION: u32 = 2;
+		fn aggregate_id(&self) -> String {
+			self.id.clone()
+		}
+		fn apply(&mut self, event: &AccountEvent) {
+			match event {
+				AccountEvent::Opened { id } => self.id = id.clone(),
+				AccountEvent::Deposited { amount } => self.balance += amount,
+			}
+		}
+		fn version(&self) -> i64 {
+			self.version
+		}
+		fn set_version(&mut self, version: i64) {
+			self.version = version
+		}
+	}
+
+	#[tokio::test]
+	async fn test_event_sourced_repository() {
+		let mut store = InMemoryEventStore::default();
+		let mut repository = EventSourcedRepository::<Account, _>::new(&mut store);
+		assert!(matches!(repository.get("1").await, Err(BaseError::NotFound)));
+
+		let mut account = Account::default();
+		repository
+			.save(&mut account, vec![AccountEvent::Opened { id: "1".into() }, AccountEvent::Deposited { amount: 100 }])
+			.await
+			.unwrap();
+		assert_eq!((account.balance, account.version), (100, 2));
+
+		let mut loaded = repository.load_aggregate("1").await.unwrap();
+		assert_eq!((loaded.id.as_str(), loaded.balance, loaded.version), ("1", 100, 2));
+
+		// Stream moved on after `stale` was loaded
+		let mut stale = repository.get("1").await.unwrap();
+		repository.save(&mut loaded, vec![AccountEvent::Deposited { amount: 50 }]).await.unwrap();
+		let res = repository.save(&mut stale, vec![AccountEvent::Deposited { amount: 10 }]).await;
+		assert!(matches!(res, Err(BaseError::ConcurrencyConflict(_))));
+
+		assert_eq!(repository.get("1").await.unwrap().balance, 150);
+	}
+
+	#[tokio::test]
+	async fn test_events_of_older_version_are_upcast_on_load() {
+		struct CreditedToDeposited;
+		impl crate::prelude::TEventUpcaster for CreditedToDeposited {
+			fn topic(&self) -> &str {
+				"AccountEvent"
+			}
+			fn source_version(&self) -> u32 {
+				1
+			}
+			fn upcast(&self, payload: serde_json::Value) -> Result<serde_json::Value, BaseError> {
+				Ok(serde_json::json!({ "Deposited": payload["Credited"] }))
+			}
+		}
+		crate::prelude::register_upcaster(CreditedToDeposited);
+
+		let mut store = InMemoryEventStore::default();
+		store.append_stream("1", 0, 2, vec![r#"{"Opened":{"id":"1"}}"#.into()]).await.unwrap();
+		store.append_stream("1", 1, 1, vec![r#"{"Credited":{"amount":30}}"#.into()]).await.unwrap();
+		let mut repository = EventSourcedRepository::<Account, _>::new(&mut store);
+		let mut account = repository.get("1").await.unwrap();
+		assert_eq!((account.balance, account.version), (30, 2));
+
+		repository.save(&mut account, vec![AccountEvent::Deposited { amount: 5 }]).await.unwrap();
+		assert_eq!(store.load_stream("1").await.unwrap().iter().map(|stored| stored.version).collect::<Vec<_>>(), vec![2, 1, 2]);
+	}
+
+	#[tokio::test]
+	async fn test_archived_event_store() {
+		struct Archive(Vec<StoredEvent>);
+		impl TEventArchive for Archive {
+			async fn load_archived(&self, aggregate_id: &str, before: i64) -> Result<Vec<StoredEvent>, BaseError> {
+				Ok(self.0.iter().filter(|stored| stored.aggregate_id == aggregate_id && stored.seq < before).cloned().collect())
+			}
+		}
+
+		let mut store = InMemoryEventStore::default();
+		let mut repository = EventSourcedRepository::<Account, _>::new(&mut store);
+		let mut account = Account::default();
+		let events = vec![AccountEvent::Opened { id: "1".into() }, AccountEvent::Deposited { amount: 100 }, AccountEvent::Deposited { amount: 20 }];
+		repository.save(&mut account, events).await.unwrap();
+
+		// First two events are moved to the archive
+		let archived = store.0.get_mut("1").unwrap().drain(..2).collect::<Vec<_>>();
+		let mut store = ArchivedEventStore::new(store, Archive(archived));
+		let mut repository = EventSourcedRepository::<Account, _>::new(&mut store);
+		let mut loaded = repository.get("1").await.unwrap();
+		assert_eq!((loaded.id.as_str(), loaded.balance, loaded.version), ("1", 120, 3));
+		repository.save(&mut loaded, vec![AccountEvent::Deposited { amount: 5 }]).await.unwrap();
+		assert_eq!(repository.get("1").await.unwrap().balance, 125);
+
+		// Archive missing events fails rather than rebuilding a wrong state
+		let mut store = ArchivedEventStore::new(store.store, Archive(vec![]));
+		assert!(matches!(EventSourcedRepository::<Account, _>::new(&mut store).get("1").await, Err(BaseError::DatabaseError(_))));
+	}
+}
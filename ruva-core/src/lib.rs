@@ -1,37 +1,142 @@
 mod adapters;
 mod aggregate;
+mod backfill;
 mod backtrace;
 mod bus_components;
+mod clock;
+mod encryption;
+mod event_store;
+pub mod json;
 mod macros;
 mod message;
 mod outbox;
 mod responses;
 mod snowflake;
+pub mod spi;
+mod testing;
+#[cfg(feature = "typescript")]
+mod typescript;
 mod unit_of_work;
 
 pub mod prelude {
 	pub use crate::aggregate::*;
+	pub use crate::backfill::{run_backfill, BackfillProgressed, BackfillSpec, InMemoryCheckpointStore, TBackfillCheckpoint, TBackfillJob, TCheckpointStore};
+	pub use crate::bus_components::actor::Actor;
+	pub use crate::bus_components::analytics::{set_analytics, Analytics, AnalyticsKind, AnalyticsRecord, TAnalyticsSink};
+	pub use crate::bus_components::aspect::{aspect_fn, AspectChainError, AspectFn, AspectId, AspectMetadata, TAspectMetadata};
+	pub use crate::bus_components::audit::{audit_sink, set_audit_sink, AuditAspect, AuditOutcome, AuditRecord, InMemoryAuditSink, TAuditSink, TracingAuditSink, REDACTED};
+	pub use crate::bus_components::authorization::{authorizer, set_authorizer, Authorization, AuthorizationAspect, RoleAuthorizer, TAuthorizer};
+	pub use crate::bus_components::backlog::{backlog_metrics, Backlog, BacklogMetrics, BacklogReport, MessageSource, TopicThroughput};
+	pub use crate::bus_components::cache::{cache_store, set_cache_store, CacheAspect, InMemoryCacheStore, TCacheStore, TCachedCommand};
+	pub use crate::bus_components::concurrency::{set_command_concurrency_limit, set_global_concurrency_limit};
+	pub use crate::bus_components::consistency::{wait_for_consistency, ConsistencyToken, InMemoryProjectionCheckpoint, TProjectionCheckpoint};
 	pub use crate::bus_components::contexts::AtomicContextManager;
 	pub use crate::bus_components::contexts::Context;
 	pub use crate::bus_components::contexts::ContextManager;
+	pub use crate::bus_components::contexts::ReadContext;
+	pub use crate::bus_components::contexts::TReadRepository;
 	pub use crate::bus_components::contexts::TSetCurrentEvents;
+	pub use crate::bus_components::correlation::{enable_outbox_correlation, outbox_correlation_enabled};
+	pub use crate::bus_components::current_user::CurrentUser;
+	pub use crate::bus_components::dead_letter::{set_dead_letter_store, DeadLetter, DeadLetterReplay, DeadLetterReplayReport, InMemoryDeadLetterStore, TDeadLetterStore};
+	pub use crate::bus_components::dependency::{register_dependency, resolve_dependency};
+	pub use crate::bus_components::dynamic::{AnyCommand, DynMessageBus};
+	pub use crate::bus_components::enrich::{register_command_enricher, TCommandEnricher};
 	pub use crate::bus_components::executor::TConnection;
+	pub use crate::bus_components::extensions::Extensions;
 	pub use crate::bus_components::handler::*;
+	pub use crate::bus_components::idempotency::{idempotency_store, set_idempotency_store, IdempotencyAspect, IdempotencyRecord, InMemoryIdempotencyStore, TIdempotencyStore, TIdempotentCommand};
+	pub use crate::bus_components::import::{ImportOptions, ImportProgress};
+	pub use crate::bus_components::inbox::{InMemoryInboxStore, InboundEvent, Inbox, InboxOutcome, TEventConsumer, TInboxStore};
+	pub use crate::bus_components::job::JobDispatcher;
+	pub use crate::bus_components::journal::{command_journal, set_command_journal, CommandJournalAspect, InMemoryCommandJournal, JournalEntry, JournalOutcome, TCommandJournal};
+	pub use crate::bus_components::layer::{layer_fn, Identity, LayerFn, ServiceBuilder, Stack, TLayer};
+	pub use crate::bus_components::limit::{set_default_event_limit, EventLimit};
+	pub use crate::bus_components::local::{LocalContextManager, LocalFuture, LocalMessageBus, TLocalCommandService};
+	pub use crate::bus_components::memo::process_shared;
 	pub use crate::bus_components::messagebus::*;
+	pub use crate::bus_components::migration::{handler_migrations, migrated, record_handler_output, Divergence, HandlerMigrations, HandlerRun, MigrationStatus};
+	pub use crate::bus_components::observer::{register_bus_observer, TBusObserver};
+	pub use crate::bus_components::pipeline::CommitStage;
+	pub use crate::bus_components::policy::{on_event, EventPolicies, Policy, PolicyOutcome};
+	pub use crate::bus_components::preflight::PreflightReport;
+	pub use crate::bus_components::propagation::{
+		enable_outbox_trace_context, extract_trace_context, extract_trace_context_with, inject_trace_context, inject_trace_context_with, outbox_trace_context_enabled, set_trace_propagator,
+		TTracePropagator,
+	};
+	pub use crate::bus_components::queue::{CommandQueue, Execution, ExecutionStrategy, InMemoryCommandQueueStore, TCommandQueueStore, Ticket, TicketStatus};
+	pub use crate::bus_components::replay::{ReplayGuard, ReplayProtectionAspect, TReplayProtected};
+	pub use crate::bus_components::retry::{retry_layer, RetryHandler};
+	pub use crate::bus_components::saga::{SagaHandler, SagaInstance, SagaRecord, SagaStatus, TSaga, TSagaRepository, TSagaStep};
+	pub use crate::bus_components::sandbox::{handler_sandbox, record_side_effect, sandboxed, HandlerSandbox, SideEffect};
+	pub use crate::bus_components::shutdown::{bus_shutdown_token, ShutdownToken};
+	pub use crate::bus_components::snapshot::{set_snapshot_claims, ContextSnapshot, Deferred};
+	pub use crate::bus_components::stats::UowStats;
+	pub use crate::bus_components::tenant::{tenant_handlers, TenantHandlers};
+	pub use crate::bus_components::toggles::{handler_toggles, FileToggleStore, HandlerToggles, TToggleStore};
+	pub use crate::bus_components::translation::Translation;
+	pub use crate::bus_components::validation::{TValidate, TValidateLength, ValidationAspect, ValidationError, ValidationErrors};
 
+	#[cfg(feature = "ruva-axum")]
+	pub use crate::adapters::axum::{BusState, CommandExtractor, HttpError};
+	#[cfg(feature = "ruva-kafka")]
+	pub use crate::adapters::kafka::{KafkaAnalyticsSink, KafkaEventPublisher, PartitionKey};
+	#[cfg(feature = "metrics")]
+	pub use crate::adapters::metrics::MetricsObserver;
+	#[cfg(feature = "mongodb")]
+	pub use crate::adapters::mongo::MongoRepository;
+	#[cfg(feature = "opentelemetry")]
+	pub use crate::adapters::otel::{otel_trace_id, OtelPropagator};
+	#[cfg(feature = "redis")]
+	pub use crate::adapters::redis::RedisCacheStore;
+	#[cfg(feature = "sqlx-postgres")]
+	pub use crate::adapters::sqlx::emitter::EventEmitter;
+	#[cfg(feature = "sqlx-postgres")]
+	pub use crate::adapters::sqlx::partition;
+	#[cfg(feature = "sqlx-postgres")]
+	pub use crate::adapters::sqlx::postgres::{create_pg_outbox_schema, PG_OUTBOX_SCHEMA};
+	#[cfg(feature = "sqlx-postgres")]
+	pub use crate::adapters::sqlx::reservation::{expire_due_reservations, Reservation, ReservationExpired, ReservationHandler, ReservationStatus};
+	#[cfg(feature = "sqlx-sqlite")]
+	pub use crate::adapters::sqlx::sqlite::{create_sqlite_schema, SQLITE_SCHEMA};
+	#[cfg(feature = "sqlx-postgres")]
+	pub use crate::adapters::sqlx::timeout::{fire_due_timeouts, spawn_timeout_firing, ScheduledTimeout};
+	#[cfg(feature = "ruva-tonic")]
+	pub use crate::adapters::tonic::{grpc_status, rpc_method_name, serve_command, CommandRpc, JsonCodec};
+	pub use crate::clock::{clock, set_clock, FixedClock, SystemClock, TClock};
+	#[cfg(feature = "encryption-ring")]
+	pub use crate::encryption::RingKeyProvider;
+	pub use crate::encryption::{decrypt_column, encrypt_column, set_key_provider, TKeyProvider};
+	pub use crate::event_store::{ArchivedEventStore, EventSourcedRepository, InMemoryEventStore, StoredEvent, TEventArchive, TEventSourced, TEventStore};
+	pub use crate::json;
 	pub use crate::message::*;
-	pub use crate::outbox::OutBox;
-	pub use crate::responses::{ApplicationError, ApplicationResponse, BaseError};
-	pub use crate::snowflake::SnowFlake;
+	#[cfg(feature = "msgpack")]
+	pub use crate::outbox::MessagePackSerializer;
+	pub use crate::outbox::{
+		decode_payload, enable_outbox_publish_class, enable_outbox_sequence, enable_outbox_version, encode_payload, event_serializer, namespaced_topic, outbox_publish_class_enabled,
+		outbox_sequence_enabled, outbox_version_enabled, register_upcaster, set_event_serializer, set_topic_namespace, strip_topic_namespace, subscriptions, topic_namespace, upcast_payload,
+		AggregateRemap, AggregateRemapJob, ArchivedOutboxReader, Backoff, DeliveryStatus, EventPayload, FileSubscriptionStore, JsonSerializer, OutBox, OutboxRelay, PublishClass, ReconciliationReport,
+		RedeliveryFilter, SequenceCheck, SequenceTracker, Subscription, Subscriptions, TDeliveryHook, TDeliveryLedger, TEventSerializer, TEventUpcaster, TOutboxArchive, TOutboxHistory,
+		TOutboxPublisher, TOutboxStore, TRemapStore, TRewriteOutbox, TSubscriptionStore, INITIAL_EVENT_VERSION, JSON_CONTENT_TYPE,
+	};
+	pub use crate::responses::{current_trace_id, reason_phrase, set_trace_id_provider, ApplicationError, ApplicationResponse, BaseError, ErrorResponse, THttpStatus};
+	pub use crate::snowflake::{NumericalUniqueIdBucket, NumericalUniqueIdGenerator, SnowFlake};
+	pub use crate::testing::{DispatchSnapshot, EventAssertions, FakeOutbox};
+	#[cfg(feature = "typescript")]
+	pub use crate::typescript::{render_typescript, write_typescript, TTypeScript};
 	pub use crate::unit_of_work::*;
 	pub use async_trait::async_trait;
 	pub use hashbrown::HashMap as HandlerMapper;
+	#[cfg(feature = "mongodb")]
+	pub use mongodb;
 	pub use serde;
 	pub use serde::{Deserialize, Serialize};
 	pub use serde_json;
-	#[cfg(feature = "sqlx-postgres")]
+	#[cfg(any(feature = "sqlx-postgres", feature = "sqlx-sqlite"))]
 	pub use sqlx;
 	pub use tokio;
+	#[cfg(feature = "ruva-tonic")]
+	pub use tonic;
 	pub use tracing;
 }
 
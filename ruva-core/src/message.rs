@@ -1,13 +1,12 @@
 //! ### TEvent
 //! [TEvent] is a trait to manage application-specific events.
 //! Using ruva framework, you can simply annotate struct as follows:
-//! ```rust,no_run
+//! ```rust,ignore
 //! #[derive(Serialize, Deserialize, Clone, TEvent)]
-//! #[aggregate(CustomAggregate)]
 //! #[internally_notifiable]
-//! #[externally_notifiable]
+//! #[externally_notifiable(CustomAggregate)]
 //! pub struct CustomEvent {
-//!     
+//!     #[identifier]
 //!     pub id: i64,
 //!     pub custom_field: String,
 //! }
@@ -15,7 +14,15 @@
 //! Here, `internally_notifiable` indicates that the event will be handled internally by `MessageBus`
 //! And the `externally_notifiable` means that the event will be stored in the form of `OutBox` and
 //! will be handled in the separate process (or thread)
-use crate::prelude::OutBox;
+//!
+//! Externally notifiable event must derive `Serialize`, and internally notifiable one `Clone` as each of its handlers takes its own copy.
+//! The derive fails with `externally_notifiable_event_must_derive_serialize` or `internally_notifiable_event_must_derive_clone` otherwise.
+//!
+//! Internally notifiable event is handled only after the transaction that raised it is committed.
+//! Add `#[flush_immediately]` to put it on the event queue as soon as it is raised. See [FlushMode].
+//!
+//! Add `#[publish_class(bulk)]` or `#[publish_class(low)]` to let the outbox relay publish it after realtime events. See [PublishClass].
+use crate::prelude::{inject_trace_context, BaseError, OutBox, PublishClass, INITIAL_EVENT_VERSION};
 use downcast_rs::{impl_downcast, Downcast};
 use std::fmt::Debug;
 
@@ -27,22 +34,50 @@ pub trait TEvent: Sync + Send + Downcast {
 		false
 	}
 
+	/// When internally notifiable event becomes visible to the event loop. See [FlushMode].
+	fn flush_mode(&self) -> FlushMode {
+		FlushMode::AfterCommit
+	}
+
 	fn metadata(&self) -> EventMetadata {
 		let event_name = std::any::type_name::<Self>().split("::").last().unwrap();
 		EventMetadata {
 			aggregate_id: Default::default(),
 			aggregate_name: Default::default(),
 			topic: event_name.to_string(),
+			version: INITIAL_EVENT_VERSION,
+			publish_class: PublishClass::Realtime,
+			correlation_id: None,
+			causation_id: None,
 		}
 	}
-	fn outbox(&self) -> OutBox {
+	/// Outbox row of the event, whose state is encoded with [event_serializer](crate::prelude::event_serializer)
+	fn outbox(&self) -> Result<OutBox, BaseError> {
 		let metadata = self.metadata();
-		OutBox::new(metadata.aggregate_id, metadata.aggregate_name, metadata.topic, self.state())
+		Ok(OutBox {
+			version: metadata.version,
+			publish_class: metadata.publish_class,
+			trace_context: inject_trace_context(),
+			correlation_id: metadata.correlation_id,
+			causation_id: metadata.causation_id,
+			..OutBox::encoded(metadata.aggregate_id, metadata.aggregate_name, metadata.topic, &self.state())?
+		})
 	}
 
 	fn state(&self) -> String;
 }
 
+/// When internally notifiable event is put on the event queue of the dispatch
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum FlushMode {
+	/// After the transaction that raised the event is committed. Event of rolled back transaction is never handled.
+	#[default]
+	AfterCommit,
+	/// As soon as the event is raised, even if the transaction is rolled back later.
+	/// For events whose handlers must run regardless of the outcome, such as audit or notification of attempt.
+	Immediate,
+}
+
 impl_downcast!(TEvent);
 impl Debug for dyn TEvent {
 	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
@@ -55,6 +90,70 @@ pub struct EventMetadata {
 	pub aggregate_id: String,
 	pub aggregate_name: String,
 	pub topic: String,
+	/// Version of the payload schema, given with `#[event_version(N)]`. See [TEventUpcaster](crate::prelude::TEventUpcaster).
+	pub version: u32,
+	/// Given with `#[publish_class(..)]`. See [PublishClass].
+	pub publish_class: PublishClass,
+	/// Correlation id of the dispatch that raised the event. Filled by the bus, see [Context::metadata](crate::prelude::Context::metadata).
+	pub correlation_id: Option<String>,
+	/// Id of the message whose handling raised the event. Filled by the bus as well.
+	pub causation_id: Option<String>,
+}
+
+/// Topic of event known at compile time. Implemented by `#[derive(TEvent)]`.
+pub trait TTopic {
+	const TOPIC: &'static str;
+}
+
+/// Topic constant bound to its payload type. Generated by `topics!` so that consumer refers to topic of producer's contract crate
+/// instead of hand-typed string.
+pub struct Topic<T> {
+	name: &'static str,
+	_payload: std::marker::PhantomData<fn() -> T>,
+}
+
+impl<T> Topic<T> {
+	pub const fn new(name: &'static str) -> Self {
+		Self {
+			name,
+			_payload: std::marker::PhantomData,
+		}
+	}
+
+	pub const fn name(&self) -> &'static str {
+		self.name
+	}
+}
+
+impl<T> Clone for Topic<T> {
+	fn clone(&self) -> Self {
+		*self
+	}
+}
+impl<T> Copy for Topic<T> {}
+
+impl<T> Debug for Topic<T> {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		write!(f, "Topic({})", self.name)
+	}
+}
+
+impl<T> std::fmt::Display for Topic<T> {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		f.write_str(self.name)
+	}
+}
+
+impl<T> PartialEq<str> for Topic<T> {
+	fn eq(&self, other: &str) -> bool {
+		self.name == other
+	}
 }
 
 pub trait TCommand: 'static + Send + Sync + Debug {}
+
+/// Response and error type the command results in. With this, `MessageBus::dispatch` infers them from the command.
+pub trait TCommandSpec: TCommand {
+	type Response: crate::prelude::ApplicationResponse;
+	type Error: crate::prelude::ApplicationError;
+}
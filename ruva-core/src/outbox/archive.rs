@@ -0,0 +1,145 @@
+//! ### Archived outbox
+//! Outbox table is kept short by moving old rows to cold storage - S3 or parquet exports, for instance - which replay and rebuild
+//! still need to read from the beginning. [ArchivedOutboxReader] reads the rows up to the archive watermark from [TOutboxArchive]
+//! and the rest from the hot table, so that tooling reading [TOutboxHistory] doesn't tell one from the other.
+//!
+//! ```rust,ignore
+//! struct S3Archive(aws_sdk_s3::Client);
+//!
+//! #[async_trait]
+//! impl TOutboxArchive for S3Archive {
+//!     async fn archived_until(&self) -> Result<Option<i64>, BaseError> {
+//!         // Largest id of the exported rows, kept in the manifest of the export
+//!     }
+//!     async fn fetch_archived(&self, after: Option<i64>, limit: usize) -> Result<Vec<OutBox>, BaseError> {
+//!         // Read the parquet files covering ids after `after`
+//!     }
+//! }
+//!
+//! // `PgPool` and `SqlitePool` implement `TOutboxHistory` with `sqlx-postgres` and `sqlx-sqlite` features.
+//! let history = ArchivedOutboxReader::new(pool(), S3Archive(client));
+//! let rows = history.fetch_history(None, 500).await?;
+//! ```
+//! Export rows in id order and raise the watermark only after the export is complete. Rows may be deleted from the hot table
+//! any time after that, as rows at or below the watermark are never read from it.
+use std::sync::Arc;
+
+use async_trait::async_trait;
+
+use super::OutBox;
+use crate::prelude::BaseError;
+
+/// Every outbox row ever written, processed or not, for replay and rebuild
+#[async_trait]
+pub trait TOutboxHistory: Send + Sync {
+	/// Rows whose id is greater than `after`, ordered by id
+	async fn fetch_history(&self, after: Option<i64>, limit: usize) -> Result<Vec<OutBox>, BaseError>;
+}
+
+#[async_trait]
+impl<T: TOutboxHistory + ?Sized> TOutboxHistory for Arc<T> {
+	async fn fetch_history(&self, after: Option<i64>, limit: usize) -> Result<Vec<OutBox>, BaseError> {
+		self.as_ref().fetch_history(after, limit).await
+	}
+}
+
+/// Cold storage of outbox rows moved out of the hot table
+#[async_trait]
+pub trait TOutboxArchive: Send + Sync {
+	/// Largest id archived. Every row up to it is in the archive. `None` if nothing is archived yet.
+	async fn archived_until(&self) -> Result<Option<i64>, BaseError>;
+	/// Archived rows whose id is greater than `after`, ordered by id
+	async fn fetch_archived(&self, after: Option<i64>, limit: usize) -> Result<Vec<OutBox>, BaseError>;
+}
+
+#[async_trait]
+impl<T: TOutboxArchive + ?Sized> TOutboxArchive for Arc<T> {
+	async fn archived_until(&self) -> Result<Option<i64>, BaseError> {
+		self.as_ref().archived_until().await
+	}
+	async fn fetch_archived(&self, after: Option<i64>, limit: usize) -> Result<Vec<OutBox>, BaseError> {
+		self.as_ref().fetch_archived(after, limit).await
+	}
+}
+
+/// [TOutboxHistory] over the hot table `H` and its archive `A`
+pub struct ArchivedOutboxReader<H, A> {
+	hot: H,
+	archive: A,
+}
+
+impl<H: TOutboxHistory, A: TOutboxArchive> ArchivedOutboxReader<H, A> {
+	pub fn new(hot: H, archive: A) -> Self {
+		Self { hot, archive }
+	}
+}
+
+#[async_trait]
+impl<H: TOutboxHistory, A: TOutboxArchive> TOutboxHistory for ArchivedOutboxReader<H, A> {
+	async fn fetch_history(&self, after: Option<i64>, limit: usize) -> Result<Vec<OutBox>, BaseError> {
+		let Some(watermark) = self.archive.archived_until().await? else {
+			return self.hot.fetch_history(after, limit).await;
+		};
+		let mut rows = vec![];
+		if after.is_none_or(|after| after < watermark) {
+			rows = self.archive.fetch_archived(after, limit).await?;
+			rows.retain(|row| row.id <= watermark);
+			// * Archive has more up to the watermark. The next call continues from the last row.
+			if rows.len() == limit {
+				return Ok(rows);
+			}
+		}
+		// * Rows at or below the watermark may still be in the hot table until they are deleted.
+		let after = after.max(Some(watermark));
+		rows.extend(self.hot.fetch_history(after, limit - rows.len()).await?);
+		Ok(rows)
+	}
+}
+
+#[tokio::test]
+async fn test_archived_outbox_reader() {
+	struct Rows(Vec<i64>, Option<i64>);
+	impl Rows {
+		fn after(&self, after: Option<i64>, limit: usize) -> Vec<OutBox> {
+			let rows = self.0.iter().filter(|id| after.is_none_or(|after| **id > after)).take(limit);
+			rows.map(|id| OutBox {
+				id: *id,
+				..OutBox::new("1".into(), "Order".into(), "OrderPlaced".into(), "{}".into())
+			})
+			.collect()
+		}
+	}
+	#[async_trait]
+	impl TOutboxHistory for Rows {
+		async fn fetch_history(&self, after: Option<i64>, limit: usize) -> Result<Vec<OutBox>, BaseError> {
+			Ok(self.after(after, limit))
+		}
+	}
+	#[async_trait]
+	impl TOutboxArchive for Rows {
+		async fn archived_until(&self) -> Result<Option<i64>, BaseError> {
+			Ok(self.1)
+		}
+		async fn fetch_archived(&self, after: Option<i64>, limit: usize) -> Result<Vec<OutBox>, BaseError> {
+			Ok(self.after(after, limit))
+		}
+	}
+
+	// 1 to 4 are archived, and 3 and 4 are not deleted from the hot table yet
+	let reader = ArchivedOutboxReader::new(Rows(vec![3, 4, 5, 6, 7], None), Rows(vec![1, 2, 3, 4], Some(4)));
+	let mut read = vec![];
+	let mut after = None;
+	loop {
+		let rows = reader.fetch_history(after, 3).await.unwrap();
+		let Some(last) = rows.last() else {
+			break;
+		};
+		after = Some(last.id);
+		read.push(rows.iter().map(|row| row.id).collect::<Vec<_>>());
+	}
+	assert_eq!(read, vec![vec![1, 2, 3], vec![4, 5, 6], vec![7]]);
+
+	// Nothing archived
+	let reader = ArchivedOutboxReader::new(Rows(vec![1, 2], None), Rows(vec![], None));
+	assert_eq!(reader.fetch_history(None, 10).await.unwrap().len(), 2);
+}
@@ -0,0 +1,81 @@
+//! ### Delivery receipt
+//! Relay publishes `OutBox` rows to the broker. Once the broker confirms the publish,
+//! the relay marks the row as processed and notifies [TDeliveryHook] so that applications can
+//! react to the actual delivery, for example marking an invoice as "sent".
+//!
+//! ```rust
+//! # use ruva_core::prelude::*;
+//! struct InvoiceSentHook;
+//!
+//! #[async_trait]
+//! impl TDeliveryHook for InvoiceSentHook {
+//!     async fn on_delivered(&self, topic: &str, event_id: i64) {
+//!         if topic == "InvoiceIssued" {
+//!             // update business state here
+//!         }
+//!     }
+//! }
+//! ```
+use async_trait::async_trait;
+
+use super::OutBox;
+
+/// Hook invoked after the broker confirms that the outbox row has been published.
+#[async_trait]
+pub trait TDeliveryHook: Send + Sync {
+	async fn on_delivered(&self, topic: &str, event_id: i64);
+}
+
+/// No-op hook for relays that don't need delivery receipts.
+#[async_trait]
+impl TDeliveryHook for () {
+	async fn on_delivered(&self, _topic: &str, _event_id: i64) {}
+}
+
+#[async_trait]
+impl<T: TDeliveryHook> TDeliveryHook for Vec<T> {
+	async fn on_delivered(&self, topic: &str, event_id: i64) {
+		for hook in self.iter() {
+			hook.on_delivered(topic, event_id).await;
+		}
+	}
+}
+
+#[async_trait]
+impl TDeliveryHook for Box<dyn TDeliveryHook> {
+	async fn on_delivered(&self, topic: &str, event_id: i64) {
+		self.as_ref().on_delivered(topic, event_id).await
+	}
+}
+
+impl OutBox {
+	/// Mark outbox as processed and notify the hook. It must be called only after the broker confirms the publish.
+	pub async fn confirm_delivery(&mut self, hook: &dyn TDeliveryHook) {
+		self.processed = true;
+		hook.on_delivered(&self.topic, self.id).await;
+	}
+}
+
+#[tokio::test]
+async fn test_confirm_delivery_invokes_hook() {
+	use std::sync::Mutex;
+
+	#[derive(Default)]
+	struct RecordingHook(Mutex<Vec<(String, i64)>>);
+
+	#[async_trait]
+	impl TDeliveryHook for RecordingHook {
+		async fn on_delivered(&self, topic: &str, event_id: i64) {
+			self.0.lock().unwrap().push((topic.to_string(), event_id));
+		}
+	}
+
+	let hook = RecordingHook::default();
+	let mut outbox = OutBox::new("1".into(), "Invoice".into(), "InvoiceIssued".into(), "{}".into());
+	assert!(!outbox.processed);
+
+	outbox.confirm_delivery(&hook).await;
+
+	assert!(outbox.processed);
+	assert_eq!(*hook.0.lock().unwrap(), vec![("InvoiceIssued".to_string(), outbox.id)]);
+}
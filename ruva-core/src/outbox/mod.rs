@@ -0,0 +1,76 @@
+mod archive;
+mod delivery;
+mod namespace;
+mod publish_class;
+mod reconciliation;
+mod redelivery;
+mod relay;
+mod remap;
+mod sequence;
+mod serializer;
+mod subscription;
+mod upcast;
+
+pub use archive::*;
+use chrono::{DateTime, Utc};
+pub use delivery::*;
+pub use namespace::*;
+pub use publish_class::*;
+pub use reconciliation::*;
+pub use redelivery::*;
+pub use relay::*;
+pub use remap::*;
+pub use sequence::*;
+pub use serializer::*;
+pub use subscription::*;
+pub use upcast::*;
+
+use crate::prelude::{SnowFlake, TClock};
+
+#[derive(Debug, Clone)]
+pub struct OutBox {
+	pub id: i64,
+	pub aggregate_id: String,
+	pub aggregate_name: String,
+	pub topic: String,
+	/// State of the event encoded with [event_serializer] at the time it is raised. See [OutBox::json_state].
+	pub state: Vec<u8>,
+	/// Content type of `state`, sent along with it by publishers
+	pub content_type: String,
+	pub processed: bool,
+	pub create_dt: DateTime<Utc>,
+	/// Per-aggregate sequence. Assigned on insert if [enable_outbox_sequence] is called.
+	pub sequence: Option<i64>,
+	/// `EventMetadata::version` of the event. Stored if [enable_outbox_version] is called.
+	pub version: u32,
+	/// `EventMetadata::publish_class` of the event. Stored if [enable_outbox_publish_class] is called.
+	pub publish_class: PublishClass,
+	/// Trace context of the span that raised the event. Stored if [enable_outbox_trace_context](crate::prelude::enable_outbox_trace_context) is called.
+	pub trace_context: Option<String>,
+	/// Correlation id of the dispatch that raised the event. Stored if [enable_outbox_correlation](crate::prelude::enable_outbox_correlation) is called.
+	pub correlation_id: Option<String>,
+	/// Id of the message whose handling raised the event. Stored along with `correlation_id`.
+	pub causation_id: Option<String>,
+}
+
+impl OutBox {
+	/// Outbox whose `state` is kept as JSON. See [OutBox::encoded] for the one encoded with [event_serializer].
+	pub fn new(aggregate_id: String, aggregate_name: String, topic: String, state: String) -> Self {
+		Self {
+			id: *SnowFlake::generate(),
+			aggregate_id,
+			aggregate_name,
+			topic,
+			state: state.into_bytes(),
+			content_type: JSON_CONTENT_TYPE.to_string(),
+			processed: false,
+			create_dt: crate::prelude::clock().now(),
+			sequence: None,
+			version: INITIAL_EVENT_VERSION,
+			publish_class: PublishClass::Realtime,
+			trace_context: None,
+			correlation_id: None,
+			causation_id: None,
+		}
+	}
+}
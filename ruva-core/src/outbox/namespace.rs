@@ -0,0 +1,61 @@
+//! ### Topic namespace
+//! Environments sharing a broker are kept apart by prefixing external topics with namespace, for example `staging.orders`.
+//! Namespace is read from `TOPIC_NAMESPACE` environment variable, or set with [set_topic_namespace] on boot.
+//! [OutboxRelay](super::OutboxRelay) publishes to namespaced topics, and `Inbox` and `EventPolicies` only take events
+//! of their namespace, matching them by the topic without it.
+//!
+//! ```rust
+//! # use ruva_core::prelude::*;
+//! // TOPIC_NAMESPACE=staging.orders
+//! # set_topic_namespace("staging.orders");
+//! assert_eq!(namespaced_topic("OrderSucceeded"), "staging.orders.OrderSucceeded");
+//! assert_eq!(strip_topic_namespace("staging.orders.OrderSucceeded"), Some("OrderSucceeded"));
+//! assert_eq!(strip_topic_namespace("production.orders.OrderSucceeded"), None);
+//! ```
+use std::sync::{LazyLock, RwLock};
+
+static TOPIC_NAMESPACE: LazyLock<RwLock<String>> = LazyLock::new(|| RwLock::new(std::env::var("TOPIC_NAMESPACE").unwrap_or_default()));
+
+/// Override the namespace read from environment. Empty namespace leaves topics as they are.
+pub fn set_topic_namespace(namespace: impl Into<String>) {
+	*TOPIC_NAMESPACE.write().unwrap() = namespace.into();
+}
+
+pub fn topic_namespace() -> String {
+	TOPIC_NAMESPACE.read().unwrap().clone()
+}
+
+/// `topic` as published to the broker
+pub fn namespaced_topic(topic: &str) -> String {
+	prefix(&TOPIC_NAMESPACE.read().unwrap(), topic)
+}
+
+/// Topic without namespace, or `None` if `topic` is of another namespace.
+pub fn strip_topic_namespace(topic: &str) -> Option<&str> {
+	strip(&TOPIC_NAMESPACE.read().unwrap(), topic)
+}
+
+fn prefix(namespace: &str, topic: &str) -> String {
+	match namespace.is_empty() {
+		true => topic.to_string(),
+		false => format!("{}.{}", namespace, topic),
+	}
+}
+
+fn strip<'a>(namespace: &str, topic: &'a str) -> Option<&'a str> {
+	if namespace.is_empty() {
+		return Some(topic);
+	}
+	topic.strip_prefix(namespace)?.strip_prefix('.')
+}
+
+#[test]
+fn test_topic_namespace() {
+	assert_eq!(prefix("", "OrderSucceeded"), "OrderSucceeded");
+	assert_eq!(strip("", "OrderSucceeded"), Some("OrderSucceeded"));
+
+	assert_eq!(prefix("staging.orders", "OrderSucceeded"), "staging.orders.OrderSucceeded");
+	assert_eq!(strip("staging.orders", "staging.orders.OrderSucceeded"), Some("OrderSucceeded"));
+	assert_eq!(strip("staging.orders", "production.orders.OrderSucceeded"), None);
+	assert_eq!(strip("staging.orders", "staging.ordersOrderSucceeded"), None);
+}
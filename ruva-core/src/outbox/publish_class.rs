@@ -0,0 +1,128 @@
+//! ### Publish class
+//! Huge backfill shouldn't hold back time-sensitive notifications queued behind it. Event declares its class with
+//! `#[publish_class(realtime | bulk | low)]` on `#[derive(TEvent)]`, and [OutboxRelay](super::OutboxRelay) publishes
+//! higher classes first and throttles those given a rate limit.
+//!
+//! ```rust,ignore
+//! #[derive(Serialize, Deserialize, Clone, TEvent)]
+//! #[externally_notifiable(Catalog)]
+//! #[publish_class(bulk)]
+//! pub struct ProductReindexed {
+//!     #[identifier]
+//!     pub id: i64,
+//! }
+//!
+//! // On boot
+//! enable_outbox_publish_class();
+//! let _handle = OutboxRelay::new(pool.clone(), publisher)
+//!     .with_rate_limit(PublishClass::Bulk, 200)
+//!     .with_rate_limit(PublishClass::Low, 20)
+//!     .spawn(bus_shutdown_token().clone());
+//! ```
+//! Events without the attribute are [PublishClass::Realtime]. Order of events is kept within a class, not across classes.
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Instant;
+
+/// Ordered from the most urgent
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Default)]
+pub enum PublishClass {
+	#[default]
+	Realtime,
+	Bulk,
+	Low,
+}
+
+impl PublishClass {
+	pub fn as_str(&self) -> &'static str {
+		match self {
+			PublishClass::Realtime => "realtime",
+			PublishClass::Bulk => "bulk",
+			PublishClass::Low => "low",
+		}
+	}
+
+	/// Value of `publish_class` column. Lower is published first.
+	pub fn priority(&self) -> i16 {
+		*self as i16
+	}
+
+	/// Unknown priority falls back to [PublishClass::Low], so that it never overtakes known classes.
+	pub fn from_priority(priority: i16) -> Self {
+		match priority {
+			0 => PublishClass::Realtime,
+			1 => PublishClass::Bulk,
+			_ => PublishClass::Low,
+		}
+	}
+}
+
+static OUTBOX_PUBLISH_CLASS: AtomicBool = AtomicBool::new(false);
+
+/// Store `OutBox::publish_class` of rows written from now on and fetch them by class. Requires `publish_class` column of `service_outbox`.
+/// ```sql
+/// ALTER TABLE service_outbox ADD COLUMN publish_class SMALLINT NOT NULL DEFAULT 0;
+/// CREATE INDEX service_outbox_unprocessed_by_class ON service_outbox (publish_class, create_dt) WHERE processed = false;
+/// ```
+pub fn enable_outbox_publish_class() {
+	OUTBOX_PUBLISH_CLASS.store(true, Ordering::Relaxed);
+}
+
+pub fn outbox_publish_class_enabled() -> bool {
+	OUTBOX_PUBLISH_CLASS.load(Ordering::Relaxed)
+}
+
+/// Token bucket holding up to one second worth of publishes
+#[derive(Debug)]
+pub(crate) struct RateLimiter {
+	per_second: f64,
+	tokens: f64,
+	refilled: Instant,
+}
+
+impl RateLimiter {
+	pub(crate) fn new(per_second: u32) -> Self {
+		Self {
+			per_second: per_second as f64,
+			tokens: per_second as f64,
+			refilled: Instant::now(),
+		}
+	}
+
+	pub(crate) fn try_acquire(&mut self, now: Instant) -> bool {
+		let elapsed = now.saturating_duration_since(self.refilled).as_secs_f64();
+		self.tokens = (self.tokens + elapsed * self.per_second).min(self.per_second);
+		self.refilled = now;
+		if self.tokens < 1.0 {
+			return false;
+		}
+		self.tokens -= 1.0;
+		true
+	}
+}
+
+#[test]
+fn test_rate_limiter() {
+	let mut limiter = RateLimiter::new(2);
+	let now = limiter.refilled;
+	assert!(limiter.try_acquire(now));
+	assert!(limiter.try_acquire(now));
+	assert!(!limiter.try_acquire(now));
+
+	// Half a second refills one
+	let later = now + std::time::Duration::from_millis(500);
+	assert!(limiter.try_acquire(later));
+	assert!(!limiter.try_acquire(later));
+
+	// Never more than a second worth
+	let much_later = later + std::time::Duration::from_secs(10);
+	assert_eq!((0..5).filter(|_| limiter.try_acquire(much_later)).count(), 2);
+}
+
+#[test]
+fn test_publish_class_priority() {
+	for class in [PublishClass::Realtime, PublishClass::Bulk, PublishClass::Low] {
+		assert_eq!(PublishClass::from_priority(class.priority()), class);
+	}
+	assert_eq!(PublishClass::from_priority(9), PublishClass::Low);
+	assert!(PublishClass::Realtime < PublishClass::Bulk);
+}
@@ -0,0 +1,83 @@
+//! ### Reconciliation
+//! Cross-check outbox rows against what the broker or inbox actually received over a time window,
+//! and report missing or duplicate deliveries for audits.
+//!
+//! ```rust,ignore
+//! struct KafkaLedger(/* consumer of the audit topic */);
+//!
+//! #[async_trait]
+//! impl TDeliveryLedger for KafkaLedger {
+//!     async fn delivered(&self, from: DateTime<Utc>, to: DateTime<Utc>) -> Result<Vec<i64>, BaseError> {
+//!         // ids of events received within the window. Received twice, listed twice.
+//!     }
+//! }
+//!
+//! let report = OutBox::reconcile(&pool, &KafkaLedger(..), from, to).await?;
+//! if !report.is_clean() {
+//!     tracing::warn!("{}", serde_json::to_string(&report)?);
+//! }
+//! ```
+use std::collections::{BTreeMap, BTreeSet};
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+
+use crate::prelude::BaseError;
+
+/// Receiving side of the outbox - broker, inbox or anything that can tell which events arrived.
+#[async_trait]
+pub trait TDeliveryLedger: Send + Sync {
+	/// Ids of events received within `[from, to)`. An event received more than once must be listed as many times.
+	async fn delivered(&self, from: DateTime<Utc>, to: DateTime<Utc>) -> Result<Vec<i64>, BaseError>;
+}
+
+#[derive(Debug, Clone, Default, Serialize, PartialEq, Eq)]
+pub struct ReconciliationReport {
+	pub from: DateTime<Utc>,
+	pub to: DateTime<Utc>,
+	/// Number of outbox rows marked as processed within the window
+	pub published: usize,
+	/// Marked as processed but never received
+	pub missing: Vec<i64>,
+	/// Received more than once, with the number of receptions
+	pub duplicated: BTreeMap<i64, usize>,
+	/// Received but no corresponding outbox row within the window
+	pub unknown: Vec<i64>,
+}
+
+impl ReconciliationReport {
+	/// Compare processed outbox ids with delivered ids.
+	pub fn new(from: DateTime<Utc>, to: DateTime<Utc>, published: impl IntoIterator<Item = i64>, delivered: impl IntoIterator<Item = i64>) -> Self {
+		let published = published.into_iter().collect::<BTreeSet<_>>();
+		let mut receptions = BTreeMap::<i64, usize>::new();
+		for id in delivered {
+			*receptions.entry(id).or_default() += 1;
+		}
+
+		Self {
+			from,
+			to,
+			published: published.len(),
+			missing: published.iter().filter(|id| !receptions.contains_key(id)).copied().collect(),
+			duplicated: receptions.iter().filter(|(_, count)| **count > 1).map(|(id, count)| (*id, *count)).collect(),
+			unknown: receptions.keys().filter(|id| !published.contains(id)).copied().collect(),
+		}
+	}
+
+	pub fn is_clean(&self) -> bool {
+		self.missing.is_empty() && self.duplicated.is_empty() && self.unknown.is_empty()
+	}
+}
+
+#[test]
+fn test_reconciliation_report() {
+	let report = ReconciliationReport::new(Default::default(), Default::default(), [1, 2, 3], [1, 3, 3, 4]);
+	assert_eq!(report.published, 3);
+	assert_eq!(report.missing, vec![2]);
+	assert_eq!(report.duplicated, BTreeMap::from([(3, 2)]));
+	assert_eq!(report.unknown, vec![4]);
+	assert!(!report.is_clean());
+
+	assert!(ReconciliationReport::new(Default::default(), Default::default(), [1, 2], [2, 1]).is_clean());
+}
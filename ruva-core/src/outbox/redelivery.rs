@@ -0,0 +1,63 @@
+//! ### Redelivery
+//! Re-queue outbox rows for the relay without SQL surgery, for example, after fixing a consumer bug.
+//!
+//! ```rust,ignore
+//! let filter = RedeliveryFilter::default()
+//!     .topic("OrderSucceeded")
+//!     .created_between(incident_started_at, incident_resolved_at)
+//!     .status(DeliveryStatus::Processed);
+//! let requeued = OutBox::redeliver(&pool, &filter).await?;
+//! ```
+use chrono::{DateTime, Utc};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DeliveryStatus {
+	/// Published by the relay
+	Processed,
+	/// Not published yet, including the rows the relay failed to publish
+	Unprocessed,
+}
+
+/// Criteria to select outbox rows. Criteria that are not set match every row.
+#[derive(Debug, Clone, Default)]
+pub struct RedeliveryFilter {
+	pub topic: Option<String>,
+	pub aggregate_id: Option<String>,
+	pub created_from: Option<DateTime<Utc>>,
+	pub created_to: Option<DateTime<Utc>>,
+	pub status: Option<DeliveryStatus>,
+}
+
+impl RedeliveryFilter {
+	pub fn topic(mut self, topic: impl Into<String>) -> Self {
+		self.topic = Some(topic.into());
+		self
+	}
+	pub fn aggregate_id(mut self, aggregate_id: impl Into<String>) -> Self {
+		self.aggregate_id = Some(aggregate_id.into());
+		self
+	}
+	/// `from` is inclusive and `to` is exclusive.
+	pub fn created_between(mut self, from: DateTime<Utc>, to: DateTime<Utc>) -> Self {
+		self.created_from = Some(from);
+		self.created_to = Some(to);
+		self
+	}
+	pub fn status(mut self, status: DeliveryStatus) -> Self {
+		self.status = Some(status);
+		self
+	}
+
+	pub fn is_empty(&self) -> bool {
+		self.topic.is_none() && self.aggregate_id.is_none() && self.created_from.is_none() && self.created_to.is_none() && self.status.is_none()
+	}
+}
+
+#[test]
+fn test_redelivery_filter() {
+	assert!(RedeliveryFilter::default().is_empty());
+	let filter = RedeliveryFilter::default().topic("OrderSucceeded").status(DeliveryStatus::Processed);
+	assert!(!filter.is_empty());
+	assert_eq!(filter.topic.as_deref(), Some("OrderSucceeded"));
+	assert_eq!(filter.aggregate_id, None);
+}
@@ -0,0 +1,398 @@
+//! ### Outbox relay
+//! [OutboxRelay] is the other half of `externally_notifiable` - it reads unprocessed `OutBox` rows from [TOutboxStore],
+//! publishes them through [TOutboxPublisher] and marks them processed once the broker confirms.
+//!
+//! ```rust,ignore
+//! struct HttpPublisher(reqwest::Client);
+//!
+//! #[async_trait]
+//! impl TOutboxPublisher for HttpPublisher {
+//!     async fn publish(&self, outbox: &OutBox) -> Result<(), BaseError> {
+//!         self.0.post(format!("https://events.example.com/{}", outbox.topic)).body(outbox.state.clone()).send().await
+//!             .map_err(|err| BaseError::ServiceError)?;
+//!         Ok(())
+//!     }
+//! }
+//!
+//! // On boot. `PgPool` implements `TOutboxStore` with `sqlx-postgres` feature.
+//! let _handle = OutboxRelay::new(pool.clone(), HttpPublisher(client)).with_hook(InvoiceSentHook).spawn(bus_shutdown_token().clone());
+//! ```
+//! Delivery is at-least-once: a row published right before a crash, or by two relay instances at the same time, is published again.
+//! Consumers are expected to deduplicate by `OutBox::id`.
+//!
+//! Topic is prefixed with [topic_namespace](super::topic_namespace) when publishing, if any.
+//! Row is published to each destination [subscriptions](super::subscriptions) route its topic to, instead, if there is any.
+//!
+//! On failure, the rest of the batch is not published so that the order of events is kept,
+//! and the relay waits with exponential backoff before trying again.
+//!
+//! Rows of a batch are published by [PublishClass](super::PublishClass), realtime first. Rows of a class over its
+//! [rate limit](OutboxRelay::with_rate_limit) are left for the following batches.
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use async_trait::async_trait;
+
+use super::{namespaced_topic, subscriptions, OutBox, PublishClass, RateLimiter, Subscriptions, TDeliveryHook};
+use crate::prelude::{backlog_metrics, Backlog, BacklogMetrics, BaseError, MessageSource, ShutdownToken};
+
+/// Delivers outbox row to the broker - Kafka, RabbitMQ, HTTP and so on.
+#[async_trait]
+pub trait TOutboxPublisher: Send + Sync {
+	/// Resolve only after the broker confirms the publish.
+	async fn publish(&self, outbox: &OutBox) -> Result<(), BaseError>;
+}
+
+/// Where the relay reads unprocessed rows from
+#[async_trait]
+pub trait TOutboxStore: Send + Sync {
+	/// Unprocessed rows in the order they should be published
+	async fn fetch_unprocessed(&self, limit: usize) -> Result<Vec<OutBox>, BaseError>;
+	async fn mark_processed(&self, id: i64) -> Result<(), BaseError>;
+	/// Number of unprocessed rows and age of the oldest one, sampled before a batch once per [backlog interval](OutboxRelay::with_backlog_interval).
+	/// `None` if not supported.
+	async fn backlog(&self) -> Result<Option<Backlog>, BaseError> {
+		Ok(None)
+	}
+}
+
+#[async_trait]
+impl<T: TOutboxStore + ?Sized> TOutboxStore for std::sync::Arc<T> {
+	async fn fetch_unprocessed(&self, limit: usize) -> Result<Vec<OutBox>, BaseError> {
+		self.as_ref().fetch_unprocessed(limit).await
+	}
+	async fn mark_processed(&self, id: i64) -> Result<(), BaseError> {
+		self.as_ref().mark_processed(id).await
+	}
+	async fn backlog(&self) -> Result<Option<Backlog>, BaseError> {
+		self.as_ref().backlog().await
+	}
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Backoff {
+	pub initial: Duration,
+	pub max: Duration,
+}
+
+impl Default for Backoff {
+	fn default() -> Self {
+		Self {
+			initial: Duration::from_millis(500),
+			max: Duration::from_secs(60),
+		}
+	}
+}
+
+impl Backoff {
+	/// Delay after `failures` consecutive failures. Doubles every time up to `max`.
+	pub fn delay(&self, failures: u32) -> Duration {
+		let factor = 2u32.saturating_pow(failures.saturating_sub(1));
+		self.initial.saturating_mul(factor).min(self.max)
+	}
+}
+
+pub struct OutboxRelay<S, P> {
+	store: S,
+	publisher: P,
+	hook: Box<dyn TDeliveryHook>,
+	batch_size: usize,
+	interval: Duration,
+	backoff: Backoff,
+	rate_limits: Mutex<hashbrown::HashMap<PublishClass, RateLimiter>>,
+	/// Process-wide ones if not given
+	subscriptions: Option<Arc<Subscriptions>>,
+	metrics: Option<Arc<BacklogMetrics>>,
+	backlog_interval: Duration,
+	backlog_sampled_at: Mutex<Option<Instant>>,
+}
+
+impl<S: TOutboxStore + 'static, P: TOutboxPublisher + 'static> OutboxRelay<S, P> {
+	pub fn new(store: S, publisher: P) -> Self {
+		Self {
+			store,
+			publisher,
+			hook: Box::new(()),
+			batch_size: 100,
+			interval: Duration::from_secs(1),
+			backoff: Backoff::default(),
+			rate_limits: Default::default(),
+			subscriptions: None,
+			metrics: None,
+			backlog_interval: Duration::from_secs(30),
+			backlog_sampled_at: Default::default(),
+		}
+	}
+
+	pub fn with_hook(mut self, hook: impl TDeliveryHook + 'static) -> Self {
+		self.hook = Box::new(hook);
+		self
+	}
+
+	pub fn with_batch_size(mut self, batch_size: usize) -> Self {
+		self.batch_size = batch_size;
+		self
+	}
+
+	/// Polling interval when there is nothing left to publish
+	pub fn with_interval(mut self, interval: Duration) -> Self {
+		self.interval = interval;
+		self
+	}
+
+	pub fn with_backoff(mut self, backoff: Backoff) -> Self {
+		self.backoff = backoff;
+		self
+	}
+
+	/// Publish at most `per_second` rows of `class` per second, with burst of up to one second worth.
+	pub fn with_rate_limit(self, class: PublishClass, per_second: u32) -> Self {
+		self.rate_limits.lock().unwrap().insert(class, RateLimiter::new(per_second));
+		self
+	}
+
+	/// Route rows by `subscriptions` instead of the process-wide [subscriptions]
+	pub fn with_subscriptions(mut self, subscriptions: Arc<Subscriptions>) -> Self {
+		self.subscriptions = Some(subscriptions);
+		self
+	}
+
+	/// Report backlog and throughput to `metrics` instead of the process-wide [backlog_metrics]
+	pub fn with_backlog_metrics(mut self, metrics: Arc<BacklogMetrics>) -> Self {
+		self.metrics = Some(metrics);
+		self
+	}
+
+	/// How often backlog is sampled from the store, as counting unprocessed rows may scan large outbox. 30 seconds by default.
+	pub fn with_backlog_interval(mut self, interval: Duration) -> Self {
+		self.backlog_interval = interval;
+		self
+	}
+
+	fn subscriptions(&self) -> &Subscriptions {
+		self.subscriptions.as_deref().unwrap_or(subscriptions())
+	}
+
+	fn metrics(&self) -> &BacklogMetrics {
+		self.metrics.as_deref().unwrap_or(backlog_metrics())
+	}
+
+	/// Publish one batch. Returns the number of published rows.
+	/// On failure, rows published before the failing one stay processed.
+	pub async fn relay_once(&self) -> Result<usize, BaseError> {
+		self.sample_backlog().await;
+		let mut batch = self.store.fetch_unprocessed(self.batch_size).await?;
+		// Stable, so that the order within a class is kept
+		batch.sort_by_key(|outbox| outbox.publish_class);
+		let mut published = 0;
+		// Once throttled, the rest of the class waits so that its order is kept
+		let mut throttled = hashbrown::HashSet::new();
+		for mut outbox in batch {
+			if throttled.contains(&outbox.publish_class) || !self.acquire(outbox.publish_class) {
+				throttled.insert(outbox.publish_class);
+				continue;
+			}
+			for destination in self.subscriptions().route(&outbox.topic) {
+				let namespaced = OutBox {
+					topic: namespaced_topic(&destination),
+					..outbox.clone()
+				};
+				if let Err(err) = self.publisher.publish(&namespaced).await {
+					tracing::error!(topic = %outbox.topic, destination = %destination, id = outbox.id, "Failed to publish outbox! {:?}", err);
+					return Err(err);
+				}
+			}
+			self.store.mark_processed(outbox.id).await?;
+			self.metrics().processed(MessageSource::Outbox, &outbox.topic);
+			outbox.confirm_delivery(self.hook.as_ref()).await;
+			published += 1;
+		}
+		Ok(published)
+	}
+
+	async fn sample_backlog(&self) {
+		{
+			let mut sampled_at = self.backlog_sampled_at.lock().unwrap();
+			let now = Instant::now();
+			if sampled_at.is_some_and(|sampled_at| now.duration_since(sampled_at) < self.backlog_interval) {
+				return;
+			}
+			*sampled_at = Some(now);
+		}
+		match self.store.backlog().await {
+			Ok(Some(backlog)) => self.metrics().sample(MessageSource::Outbox, backlog),
+			Ok(None) => {}
+			Err(err) => tracing::warn!("Failed to sample outbox backlog! {:?}", err),
+		}
+	}
+
+	fn acquire(&self, class: PublishClass) -> bool {
+		match self.rate_limits.lock().unwrap().get_mut(&class) {
+			Some(limiter) => limiter.try_acquire(Instant::now()),
+			None => true,
+		}
+	}
+
+	/// Run the relay until `shutdown` is signalled.
+	pub fn spawn(self, shutdown: ShutdownToken) -> tokio::task::JoinHandle<()> {
+		tokio::spawn(async move {
+			let mut failures = 0;
+			while !shutdown.is_shutdown() {
+				let wait = match self.relay_once().await {
+					Ok(published) => {
+						failures = 0;
+						// There may be more
+						if published == self.batch_size {
+							continue;
+						}
+						self.interval
+					}
+					Err(_) => {
+						failures += 1;
+						self.backoff.delay(failures)
+					}
+				};
+				tokio::select! {
+					_ = tokio::time::sleep(wait) => {}
+					_ = shutdown.cancelled() => {}
+				}
+			}
+		})
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use super::*;
+	use std::sync::{
+		atomic::{AtomicUsize, Ordering},
+		Mutex,
+	};
+
+	#[derive(Default)]
+	struct InMemoryStore(Mutex<Vec<OutBox>>);
+
+	#[async_trait]
+	impl TOutboxStore for InMemoryStore {
+		async fn fetch_unprocessed(&self, limit: usize) -> Result<Vec<OutBox>, BaseError> {
+			Ok(self.0.lock().unwrap().iter().filter(|o| !o.processed).take(limit).cloned().collect())
+		}
+		async fn mark_processed(&self, id: i64) -> Result<(), BaseError> {
+			self.0.lock().unwrap().iter_mut().filter(|o| o.id == id).for_each(|o| o.processed = true);
+			Ok(())
+		}
+		async fn backlog(&self) -> Result<Option<Backlog>, BaseError> {
+			let count = self.0.lock().unwrap().iter().filter(|o| !o.processed).count();
+			Ok(Some(Backlog {
+				count: count as u64,
+				oldest_age: None,
+			}))
+		}
+	}
+
+	/// Fails on the `fail_at`th publish
+	struct FlakyPublisher {
+		published: Mutex<Vec<String>>,
+		attempts: AtomicUsize,
+		fail_at: usize,
+	}
+
+	#[async_trait]
+	impl TOutboxPublisher for FlakyPublisher {
+		async fn publish(&self, outbox: &OutBox) -> Result<(), BaseError> {
+			if self.attempts.fetch_add(1, Ordering::SeqCst) + 1 == self.fail_at {
+				return Err(BaseError::ServiceError);
+			}
+			self.published.lock().unwrap().push(outbox.topic.clone());
+			Ok(())
+		}
+	}
+
+	#[tokio::test]
+	async fn test_relay_keeps_order_on_failure() {
+		let store = std::sync::Arc::new(InMemoryStore::default());
+		store
+			.0
+			.lock()
+			.unwrap()
+			.extend(["First", "Second", "Third"].map(|topic| OutBox::new("1".into(), "Order".into(), topic.into(), "{}".into())));
+		let publisher = FlakyPublisher {
+			published: Default::default(),
+			attempts: Default::default(),
+			fail_at: 2,
+		};
+		let metrics = Arc::new(BacklogMetrics::default());
+		let relay = OutboxRelay::new(store.clone(), publisher).with_backlog_metrics(metrics.clone());
+
+		assert!(relay.relay_once().await.is_err());
+		assert_eq!(store.0.lock().unwrap().iter().filter(|o| o.processed).count(), 1);
+		assert_eq!(metrics.backlog(MessageSource::Outbox).unwrap().count, 3);
+
+		assert_eq!(relay.relay_once().await.unwrap(), 2);
+		assert_eq!(*relay.publisher.published.lock().unwrap(), vec!["First", "Second", "Third"]);
+		assert!(store.0.lock().unwrap().iter().all(|o| o.processed));
+		// Not sampled again within the interval
+		assert_eq!(metrics.backlog(MessageSource::Outbox).unwrap().count, 3);
+	}
+
+	#[tokio::test]
+	async fn test_relay_publishes_realtime_first_and_throttles_bulk() {
+		let store = std::sync::Arc::new(InMemoryStore::default());
+		let row = |topic: &str, publish_class| OutBox {
+			publish_class,
+			..OutBox::new("1".into(), "Catalog".into(), topic.into(), "{}".into())
+		};
+		store.0.lock().unwrap().extend([
+			row("Reindexed1", PublishClass::Bulk),
+			row("Reindexed2", PublishClass::Bulk),
+			row("Archived", PublishClass::Low),
+			row("PriceChanged", PublishClass::Realtime),
+		]);
+		let publisher = FlakyPublisher {
+			published: Default::default(),
+			attempts: Default::default(),
+			fail_at: 0,
+		};
+		let relay = OutboxRelay::new(store.clone(), publisher).with_rate_limit(PublishClass::Bulk, 1);
+
+		assert_eq!(relay.relay_once().await.unwrap(), 3);
+		assert_eq!(*relay.publisher.published.lock().unwrap(), vec!["PriceChanged", "Reindexed1", "Archived"]);
+		assert_eq!(
+			store.0.lock().unwrap().iter().filter(|o| !o.processed).map(|o| o.topic.as_str()).collect::<Vec<_>>(),
+			vec!["Reindexed2"]
+		);
+	}
+
+	#[tokio::test]
+	async fn test_relay_publishes_to_subscriptions() {
+		let store = std::sync::Arc::new(InMemoryStore::default());
+		store
+			.0
+			.lock()
+			.unwrap()
+			.extend(["InvoiceIssued", "InvoiceVoided"].map(|topic| OutBox::new("1".into(), "Invoice".into(), topic.into(), "{}".into())));
+		let subscriptions = Arc::new(Subscriptions::default());
+		subscriptions.subscribe("InvoiceIssued", "InvoiceIssued");
+		subscriptions.subscribe("InvoiceIssued", "billing.invoices");
+		let publisher = FlakyPublisher {
+			published: Default::default(),
+			attempts: Default::default(),
+			fail_at: 0,
+		};
+		let relay = OutboxRelay::new(store.clone(), publisher).with_subscriptions(subscriptions);
+
+		assert_eq!(relay.relay_once().await.unwrap(), 2);
+		assert_eq!(*relay.publisher.published.lock().unwrap(), vec!["InvoiceIssued", "billing.invoices", "InvoiceVoided"]);
+	}
+
+	#[test]
+	fn test_backoff() {
+		let backoff = Backoff {
+			initial: Duration::from_secs(1),
+			max: Duration::from_secs(5),
+		};
+		assert_eq!(backoff.delay(1), Duration::from_secs(1));
+		assert_eq!(backoff.delay(3), Duration::from_secs(4));
+		assert_eq!(backoff.delay(10), Duration::from_secs(5));
+	}
+}
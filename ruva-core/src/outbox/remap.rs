@@ -0,0 +1,282 @@
+//! ### Aggregate remap
+//! When an aggregate is split into two or merged with another, its events kept in the outbox still carry the old
+//! `aggregate_name` and `aggregate_id`. [AggregateRemap] declares where the rows of the old aggregates go and how their
+//! events change, and [AggregateRemapJob] rewrites the rows as a [backfill](crate::prelude::run_backfill).
+//!
+//! ```rust
+//! # use ruva_core::prelude::*;
+//! # async fn example<S: TRemapStore>(pool: impl Fn() -> S, ctx: &mut (impl TRewriteOutbox + TBackfillCheckpoint + TSetCurrentEvents)) -> Result<(), BaseError> {
+//! // `Order` is split into `Order` and `Shipment`, which takes over the shipping events with the same id.
+//! let remap = AggregateRemap::split("Order", |row| match row.topic == "OrderShipped" {
+//!     true => ("Shipment".to_string(), row.aggregate_id.clone()),
+//!     false => ("Order".to_string(), row.aggregate_id.clone()),
+//! })
+//! .event("OrderShipped", "ShipmentDispatched", |mut state| {
+//!     state["shipment_id"] = state["order_id"].take();
+//!     Ok(state)
+//! });
+//!
+//! // `Cart` and `Checkout` are merged into `Order` keyed by the cart id
+//! let remap = AggregateRemap::merge(["Cart", "Checkout"], "Order", |row| row.aggregate_id.clone());
+//!
+//! // In command handler. `PgPool` implements `TRemapStore` and `Context` implements `TRewriteOutbox` with `sqlx-postgres` feature.
+//! let job = AggregateRemapJob::new("split_order_shipment", remap, pool());
+//! let done = run_backfill(&job, ctx).await?;
+//! # Ok(())
+//! # }
+//! ```
+//! As with any backfill, cursor is checkpointed after every batch so the job resumes where it stopped. Rows are rewritten
+//! on the context, in the transaction that saves the checkpoint, so that routes and upcasts are never applied twice to the same row.
+//! Rows are rewritten in place, keeping their id, order and processed flag. With [enable_outbox_sequence](crate::prelude::enable_outbox_sequence),
+//! rows moved to another aggregate are given the next sequences of that aggregate.
+use std::future::Future;
+use std::sync::Arc;
+
+use async_trait::async_trait;
+
+use super::{encode_payload, OutBox};
+use crate::prelude::{json, BackfillSpec, BaseError, TBackfillJob};
+
+/// Outbox rows of the aggregates to be remapped
+#[async_trait]
+pub trait TRemapStore: Send + Sync {
+	/// Rows of `aggregate_names` whose id is greater than `after`, ordered by id
+	async fn fetch_aggregate_rows(&self, aggregate_names: &[String], after: Option<i64>, limit: usize) -> Result<Vec<OutBox>, BaseError>;
+}
+
+#[async_trait]
+impl<T: TRemapStore + ?Sized> TRemapStore for Arc<T> {
+	async fn fetch_aggregate_rows(&self, aggregate_names: &[String], after: Option<i64>, limit: usize) -> Result<Vec<OutBox>, BaseError> {
+		self.as_ref().fetch_aggregate_rows(aggregate_names, after, limit).await
+	}
+}
+
+/// Implemented on the unit of work so that rows are rewritten in the transaction that saves the checkpoint of the remap.
+pub trait TRewriteOutbox: Send {
+	/// Overwrite `aggregate_name`, `aggregate_id`, `topic` and `state` of the rows by their id.
+	/// Rows without `sequence` are given the next sequences of their aggregate if sequencing is enabled.
+	fn rewrite_rows(&mut self, rows: Vec<OutBox>) -> impl Future<Output = Result<(), BaseError>> + Send;
+}
+
+type Route = Box<dyn Fn(&OutBox) -> (String, String) + Send + Sync>;
+type Upcast = Box<dyn Fn(serde_json::Value) -> Result<serde_json::Value, BaseError> + Send + Sync>;
+
+/// Where rows of the old aggregates go
+pub struct AggregateRemap {
+	sources: Vec<String>,
+	route: Route,
+	/// New topic and payload transform by old topic
+	events: hashbrown::HashMap<String, (String, Upcast)>,
+}
+
+impl AggregateRemap {
+	/// Route each row of `source` to the new aggregate, given as `(aggregate_name, aggregate_id)`.
+	pub fn split(source: impl Into<String>, route: impl Fn(&OutBox) -> (String, String) + Send + Sync + 'static) -> Self {
+		Self {
+			sources: vec![source.into()],
+			route: Box::new(route),
+			events: Default::default(),
+		}
+	}
+
+	/// Move rows of `sources` to `target`, with aggregate id given by `id`.
+	pub fn merge(sources: impl IntoIterator<Item = impl Into<String>>, target: impl Into<String>, id: impl Fn(&OutBox) -> String + Send + Sync + 'static) -> Self {
+		let target = target.into();
+		Self {
+			sources: sources.into_iter().map(Into::into).collect(),
+			route: Box::new(move |row| (target.clone(), id(row))),
+			events: Default::default(),
+		}
+	}
+
+	/// Rename event of `topic` to `new_topic`, transforming its payload with `upcast`.
+	/// ## Panics
+	/// If `topic` is already declared.
+	pub fn event(mut self, topic: impl Into<String>, new_topic: impl Into<String>, upcast: impl Fn(serde_json::Value) -> Result<serde_json::Value, BaseError> + Send + Sync + 'static) -> Self {
+		let topic = topic.into();
+		if self.events.contains_key(&topic) {
+			panic!("Remap of event {} is already declared!", topic);
+		}
+		self.events.insert(topic, (new_topic.into(), Box::new(upcast)));
+		self
+	}
+
+	pub fn sources(&self) -> &[String] {
+		&self.sources
+	}
+
+	/// Row as it should be after the remap. Sequence of the row moved to another aggregate is taken off, to be reassigned by the store.
+	pub fn remap(&self, row: OutBox) -> Result<OutBox, BaseError> {
+		let (aggregate_name, aggregate_id) = (self.route)(&row);
+		let sequence = match aggregate_name == row.aggregate_name && aggregate_id == row.aggregate_id {
+			true => row.sequence,
+			false => None,
+		};
+		let Some((new_topic, upcast)) = self.events.get(&row.topic) else {
+			return Ok(OutBox {
+				aggregate_name,
+				aggregate_id,
+				sequence,
+				..row
+			});
+		};
+		let state = json::from_str(&row.json_state()?).map_err(|err| BaseError::DatabaseError(format!("Failed to deserialize outbox {}: {}", row.id, err)))?;
+		// Re-encoded with the serializer of now, as the one of the row may be gone
+		let payload = encode_payload(new_topic, &upcast(state)?.to_string())?;
+		Ok(OutBox {
+			aggregate_name,
+			aggregate_id,
+			topic: new_topic.clone(),
+			state: payload.bytes,
+			content_type: payload.content_type.to_string(),
+			sequence,
+			..row
+		})
+	}
+}
+
+/// [AggregateRemap] run as backfill job, with outbox id as cursor
+pub struct AggregateRemapJob<S> {
+	name: String,
+	remap: AggregateRemap,
+	store: S,
+	batch_size: usize,
+}
+
+impl<S: TRemapStore> AggregateRemapJob<S> {
+	/// `name` identifies the checkpoint, so give each remap its own.
+	pub fn new(name: impl Into<String>, remap: AggregateRemap, store: S) -> Self {
+		Self {
+			name: name.into(),
+			remap,
+			store,
+			batch_size: 500,
+		}
+	}
+
+	pub fn with_batch_size(mut self, batch_size: usize) -> Self {
+		self.batch_size = batch_size;
+		self
+	}
+}
+
+#[async_trait]
+impl<S: TRemapStore, C: TRewriteOutbox> TBackfillJob<C> for AggregateRemapJob<S> {
+	type Item = OutBox;
+
+	fn spec(&self) -> BackfillSpec {
+		BackfillSpec::new(self.name.clone()).batch_size(self.batch_size)
+	}
+
+	async fn fetch(&self, after: Option<i64>, limit: usize) -> Result<Vec<(i64, OutBox)>, BaseError> {
+		let rows = self.store.fetch_aggregate_rows(self.remap.sources(), after, limit).await?;
+		Ok(rows.into_iter().map(|row| (row.id, row)).collect())
+	}
+
+	async fn project(&self, items: Vec<OutBox>, ctx: &mut C) -> Result<(), BaseError> {
+		let rows = items.into_iter().map(|row| self.remap.remap(row)).collect::<Result<Vec<_>, _>>()?;
+		ctx.rewrite_rows(rows).await
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use super::*;
+	use crate::prelude::{run_backfill, TBackfillCheckpoint, TEvent, TSetCurrentEvents};
+	use std::collections::VecDeque;
+	use std::sync::Mutex;
+
+	#[derive(Default)]
+	struct InMemoryStore(Mutex<Vec<OutBox>>);
+
+	#[async_trait]
+	impl TRemapStore for InMemoryStore {
+		async fn fetch_aggregate_rows(&self, aggregate_names: &[String], after: Option<i64>, limit: usize) -> Result<Vec<OutBox>, BaseError> {
+			let rows = self.0.lock().unwrap();
+			Ok(rows
+				.iter()
+				.filter(|row| aggregate_names.contains(&row.aggregate_name) && Some(row.id) > after)
+				.take(limit)
+				.cloned()
+				.collect())
+		}
+	}
+
+	struct Context(Arc<InMemoryStore>);
+	impl TRewriteOutbox for Context {
+		async fn rewrite_rows(&mut self, rewritten: Vec<OutBox>) -> Result<(), BaseError> {
+			let mut rows = self.0 .0.lock().unwrap();
+			for new in rewritten {
+				if let Some(row) = rows.iter_mut().find(|row| row.id == new.id) {
+					*row = new;
+				}
+			}
+			Ok(())
+		}
+	}
+	impl TBackfillCheckpoint for Context {
+		async fn load_checkpoint(&mut self, _name: &str) -> Result<Option<i64>, BaseError> {
+			Ok(None)
+		}
+		async fn save_checkpoint(&mut self, _name: &str, _cursor: i64) -> Result<(), BaseError> {
+			Ok(())
+		}
+	}
+	impl TSetCurrentEvents for Context {
+		fn set_current_events(&mut self, _events: VecDeque<Arc<dyn TEvent>>) {}
+	}
+
+	fn row(id: i64, aggregate_name: &str, aggregate_id: &str, topic: &str, state: &str) -> OutBox {
+		OutBox {
+			id,
+			..OutBox::new(aggregate_id.into(), aggregate_name.into(), topic.into(), state.into())
+		}
+	}
+
+	#[tokio::test]
+	async fn test_split_aggregate() {
+		let store = Arc::new(InMemoryStore::default());
+		store.0.lock().unwrap().extend([
+			row(1, "Order", "1", "OrderPlaced", "{}"),
+			row(2, "Order", "1", "OrderShipped", r#"{"order_id":1}"#),
+			row(3, "Cart", "7", "CartCreated", "{}"),
+		]);
+		let remap = AggregateRemap::split("Order", |row| match row.topic == "OrderShipped" {
+			true => ("Shipment".to_string(), format!("shipment-{}", row.aggregate_id)),
+			false => ("Order".to_string(), row.aggregate_id.clone()),
+		})
+		.event("OrderShipped", "ShipmentDispatched", |mut state| {
+			state["shipment_id"] = state["order_id"].take();
+			Ok(state)
+		});
+		let job = AggregateRemapJob::new("split_order", remap, store.clone()).with_batch_size(1);
+
+		let done = run_backfill(&job, &mut Context(store.clone())).await.unwrap();
+		assert_eq!(done.processed, 2);
+
+		let rows = store.0.lock().unwrap();
+		assert_eq!(
+			rows.iter().map(|row| (row.aggregate_name.as_str(), row.aggregate_id.as_str(), row.topic.as_str())).collect::<Vec<_>>(),
+			vec![("Order", "1", "OrderPlaced"), ("Shipment", "shipment-1", "ShipmentDispatched"), ("Cart", "7", "CartCreated")]
+		);
+		let state: serde_json::Value = serde_json::from_str(&rows[1].json_state().unwrap()).unwrap();
+		assert_eq!(state, serde_json::json!({"order_id": null, "shipment_id": 1}));
+	}
+
+	#[test]
+	fn test_merge_aggregates() {
+		let remap = AggregateRemap::merge(["Cart", "Checkout"], "Order", |row| format!("order-{}", row.aggregate_id));
+		assert_eq!(remap.sources(), ["Cart".to_string(), "Checkout".to_string()]);
+
+		let merged = remap
+			.remap(OutBox {
+				sequence: Some(3),
+				..row(1, "Checkout", "7", "CheckoutStarted", "{}")
+			})
+			.unwrap();
+		assert_eq!((merged.id, merged.aggregate_name.as_str(), merged.aggregate_id.as_str()), (1, "Order", "order-7"));
+		assert_eq!(merged.topic, "CheckoutStarted");
+		// Sequence of `Checkout` means nothing in `Order`
+		assert_eq!(merged.sequence, None);
+	}
+}
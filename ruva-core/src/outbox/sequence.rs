@@ -0,0 +1,93 @@
+//! ### Per-aggregate sequence
+//! Event id and creation time don't tell consumer whether it missed an event or received them out of order.
+//! When enabled, outbox row is given sequence number that increases by one per aggregate, assigned in the transaction writing the row.
+//! Consumer tracks the last sequence per aggregate with [SequenceTracker] and requests replay on gap.
+//!
+//! ```rust
+//! # use ruva_core::prelude::*;
+//! # struct Message { aggregate_name: String, aggregate_id: String, sequence: i64 }
+//! # async fn handle(_: Message) -> Result<(), BaseError> { Ok(()) }
+//! # async fn request_replay(_: &str, _: i64) -> Result<(), BaseError> { Ok(()) }
+//! # async fn example(mut tracker: SequenceTracker, message: Message) -> Result<(), BaseError> {
+//! // Producer, on boot
+//! enable_outbox_sequence();
+//!
+//! // Consumer
+//! match tracker.observe(&message.aggregate_name, &message.aggregate_id, message.sequence) {
+//!     SequenceCheck::InOrder => handle(message).await?,
+//!     SequenceCheck::Duplicate => {}
+//!     SequenceCheck::Gap { expected, .. } => request_replay(&message.aggregate_id, expected).await?,
+//! }
+//! # Ok(())
+//! # }
+//! ```
+use std::sync::atomic::{AtomicBool, Ordering};
+
+static OUTBOX_SEQUENCE: AtomicBool = AtomicBool::new(false);
+
+/// Assign per-aggregate sequence to outbox rows written from now on. Requires `sequence` column and `service_outbox_sequence` table.
+pub fn enable_outbox_sequence() {
+	OUTBOX_SEQUENCE.store(true, Ordering::Relaxed);
+}
+
+pub fn outbox_sequence_enabled() -> bool {
+	OUTBOX_SEQUENCE.load(Ordering::Relaxed)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SequenceCheck {
+	InOrder,
+	/// Sequence at or below the last one observed
+	Duplicate,
+	/// Sequences between `expected` and `received` are missing
+	Gap {
+		expected: i64,
+		received: i64,
+	},
+}
+
+/// Consumer side record of the last sequence per aggregate. Sequence starts from 1.
+#[derive(Debug, Default)]
+pub struct SequenceTracker {
+	last: hashbrown::HashMap<(String, String), i64>,
+}
+
+impl SequenceTracker {
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	/// Check `sequence` against the last one. Only sequence in order advances the tracker.
+	pub fn observe(&mut self, aggregate_name: &str, aggregate_id: &str, sequence: i64) -> SequenceCheck {
+		let key = (aggregate_name.to_string(), aggregate_id.to_string());
+		let last = self.last.get(&key).copied().unwrap_or(0);
+		match sequence - last {
+			1 => {
+				self.last.insert(key, sequence);
+				SequenceCheck::InOrder
+			}
+			diff if diff <= 0 => SequenceCheck::Duplicate,
+			_ => SequenceCheck::Gap {
+				expected: last + 1,
+				received: sequence,
+			},
+		}
+	}
+
+	/// Restore the last sequence, for example from consumer's own storage on boot.
+	pub fn resume(&mut self, aggregate_name: &str, aggregate_id: &str, last_sequence: i64) {
+		self.last.insert((aggregate_name.to_string(), aggregate_id.to_string()), last_sequence);
+	}
+}
+
+#[test]
+fn test_sequence_tracker() {
+	let mut tracker = SequenceTracker::new();
+	assert_eq!(tracker.observe("Order", "1", 1), SequenceCheck::InOrder);
+	assert_eq!(tracker.observe("Order", "1", 1), SequenceCheck::Duplicate);
+	assert_eq!(tracker.observe("Order", "1", 4), SequenceCheck::Gap { expected: 2, received: 4 });
+	assert_eq!(tracker.observe("Order", "2", 1), SequenceCheck::InOrder);
+
+	tracker.resume("Order", "1", 3);
+	assert_eq!(tracker.observe("Order", "1", 4), SequenceCheck::InOrder);
+}
@@ -0,0 +1,210 @@
+//! ### Event serialization
+//! State of event is encoded by [TEventSerializer] set with [set_event_serializer] - MessagePack for smaller payloads, Protobuf
+//! for schema evolution and so on - and JSON if none is set. Outbox stores the encoded bytes along with their content type,
+//! so that publishers send them as they are and rows written before the serializer is switched are still read.
+//!
+//! ```rust,ignore
+//! // On boot of producer. `MessagePackSerializer` requires `msgpack` feature.
+//! set_event_serializer(MessagePackSerializer);
+//!
+//! // In publisher
+//! record.payload(&outbox.state).header("content-type", &outbox.content_type);
+//!
+//! // In consumer, before handing the event to the inbox
+//! let payload = decode_payload(&topic, content_type, &bytes)?;
+//! ```
+//! Upcasters and [AggregateRemap](super::AggregateRemap) work on JSON, decoding the state with [OutBox::json_state].
+//! Protobuf needs schema of each message, which is why serializer is given the topic:
+//! ```rust,ignore
+//! struct ProtobufSerializer;
+//! impl TEventSerializer for ProtobufSerializer {
+//!     fn content_type(&self) -> &'static str { "application/x-protobuf" }
+//!     fn serialize(&self, topic: &str, state: &serde_json::Value) -> Result<Vec<u8>, BaseError> {
+//!         match topic {
+//!             "OrderPlaced" => Ok(serde_json::from_value::<proto::OrderPlaced>(state.clone())?.encode_to_vec()),
+//!             _ => Err(BaseError::DeliveryError(format!("No schema for {}", topic))),
+//!         }
+//!     }
+//!     fn deserialize(&self, topic: &str, bytes: &[u8]) -> Result<serde_json::Value, BaseError> { .. }
+//! }
+//! ```
+use std::sync::OnceLock;
+
+use super::OutBox;
+use crate::prelude::{json, BaseError, TEvent};
+
+pub const JSON_CONTENT_TYPE: &str = "application/json";
+
+/// Encode state of event for the outbox and the wire, and back. State is given as JSON value, as [TEvent::state] returns it.
+pub trait TEventSerializer: Send + Sync {
+	/// MIME type stored and sent along with the payload, for example as Kafka header
+	fn content_type(&self) -> &'static str;
+	fn serialize(&self, topic: &str, state: &serde_json::Value) -> Result<Vec<u8>, BaseError>;
+	fn deserialize(&self, topic: &str, bytes: &[u8]) -> Result<serde_json::Value, BaseError>;
+}
+
+/// Default serializer. State is stored as [TEvent::state] returns it, and parsed by [json] backend.
+pub struct JsonSerializer;
+
+impl TEventSerializer for JsonSerializer {
+	fn content_type(&self) -> &'static str {
+		JSON_CONTENT_TYPE
+	}
+
+	fn serialize(&self, _topic: &str, state: &serde_json::Value) -> Result<Vec<u8>, BaseError> {
+		json::to_vec(state).map_err(|err| BaseError::DeliveryError(err.to_string()))
+	}
+
+	fn deserialize(&self, _topic: &str, bytes: &[u8]) -> Result<serde_json::Value, BaseError> {
+		json::from_slice(bytes).map_err(|err| BaseError::DeliveryError(err.to_string()))
+	}
+}
+
+#[cfg(feature = "msgpack")]
+pub struct MessagePackSerializer;
+
+#[cfg(feature = "msgpack")]
+impl TEventSerializer for MessagePackSerializer {
+	fn content_type(&self) -> &'static str {
+		"application/msgpack"
+	}
+
+	fn serialize(&self, _topic: &str, state: &serde_json::Value) -> Result<Vec<u8>, BaseError> {
+		rmp_serde::to_vec_named(state).map_err(|err| BaseError::DeliveryError(err.to_string()))
+	}
+
+	fn deserialize(&self, _topic: &str, bytes: &[u8]) -> Result<serde_json::Value, BaseError> {
+		rmp_serde::from_slice(bytes).map_err(|err| BaseError::DeliveryError(err.to_string()))
+	}
+}
+
+static EVENT_SERIALIZER: OnceLock<Box<dyn TEventSerializer>> = OnceLock::new();
+
+/// ## Panics
+/// If event serializer is already set.
+pub fn set_event_serializer(serializer: impl TEventSerializer + 'static) {
+	if EVENT_SERIALIZER.set(Box::new(serializer)).is_err() {
+		panic!("Event Serializer Is Already Set!");
+	}
+}
+
+pub fn event_serializer() -> &'static dyn TEventSerializer {
+	match EVENT_SERIALIZER.get() {
+		Some(serializer) => serializer.as_ref(),
+		None => &JsonSerializer,
+	}
+}
+
+/// State of event encoded by [TEventSerializer]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EventPayload {
+	pub content_type: &'static str,
+	pub bytes: Vec<u8>,
+}
+
+/// Encode JSON `state` of `topic` with [event_serializer]. JSON state is passed through without parsing.
+pub fn encode_payload(topic: &str, state: &str) -> Result<EventPayload, BaseError> {
+	encode_with(event_serializer(), topic, state)
+}
+
+fn encode_with(serializer: &dyn TEventSerializer, topic: &str, state: &str) -> Result<EventPayload, BaseError> {
+	let content_type = serializer.content_type();
+	if content_type == JSON_CONTENT_TYPE {
+		return Ok(EventPayload {
+			content_type,
+			bytes: state.as_bytes().to_vec(),
+		});
+	}
+	let state = json::from_str(state).map_err(|err| BaseError::DeliveryError(format!("Failed to parse state of {}: {}", topic, err)))?;
+	Ok(EventPayload {
+		content_type,
+		bytes: serializer.serialize(topic, &state)?,
+	})
+}
+
+/// JSON state of payload received with `content_type`, as [InboundEvent](crate::prelude::InboundEvent) expects.
+/// ## Errors
+/// [BaseError::DeliveryError] if `content_type` is neither JSON nor that of [event_serializer], or the payload is malformed.
+pub fn decode_payload(topic: &str, content_type: &str, bytes: &[u8]) -> Result<String, BaseError> {
+	decode_with(event_serializer(), topic, content_type, bytes)
+}
+
+fn decode_with(serializer: &dyn TEventSerializer, topic: &str, content_type: &str, bytes: &[u8]) -> Result<String, BaseError> {
+	// Parameters such as `charset` are not told apart
+	let media_type = content_type.split(';').next().unwrap_or_default().trim();
+	if media_type.eq_ignore_ascii_case(JSON_CONTENT_TYPE) {
+		return String::from_utf8(bytes.to_vec()).map_err(|err| BaseError::DeliveryError(err.to_string()));
+	}
+	if !media_type.eq_ignore_ascii_case(serializer.content_type()) {
+		return Err(BaseError::DeliveryError(format!("Unsupported content type {} of {}", content_type, topic)));
+	}
+	Ok(serializer.deserialize(topic, bytes)?.to_string())
+}
+
+impl OutBox {
+	/// Outbox of `topic` whose JSON `state` is encoded with [event_serializer]
+	pub fn encoded(aggregate_id: String, aggregate_name: String, topic: String, state: &str) -> Result<Self, BaseError> {
+		let payload = encode_payload(&topic, state)?;
+		Ok(OutBox {
+			state: payload.bytes,
+			content_type: payload.content_type.to_string(),
+			..OutBox::new(aggregate_id, aggregate_name, topic, String::new())
+		})
+	}
+
+	/// State decoded into JSON, whatever it is encoded with
+	pub fn json_state(&self) -> Result<String, BaseError> {
+		decode_payload(&self.topic, &self.content_type, &self.state)
+	}
+}
+
+impl dyn TEvent {
+	/// State encoded with [event_serializer]
+	pub fn payload(&self) -> Result<EventPayload, BaseError> {
+		encode_payload(&self.metadata().topic, &self.state())
+	}
+}
+
+#[test]
+fn test_json_payload_is_passed_through() {
+	let payload = encode_with(&JsonSerializer, "OrderPlaced", r#"{"id":1}"#).unwrap();
+	assert_eq!(
+		payload,
+		EventPayload {
+			content_type: JSON_CONTENT_TYPE,
+			bytes: br#"{"id":1}"#.to_vec()
+		}
+	);
+	assert_eq!(decode_with(&JsonSerializer, "OrderPlaced", JSON_CONTENT_TYPE, &payload.bytes).unwrap(), r#"{"id":1}"#);
+	assert!(decode_with(&JsonSerializer, "OrderPlaced", "application/msgpack", &payload.bytes).is_err());
+	// Media type is compared without parameters
+	assert_eq!(decode_with(&JsonSerializer, "OrderPlaced", "application/json; charset=utf-8", &payload.bytes).unwrap(), r#"{"id":1}"#);
+	assert_eq!(decode_with(&JsonSerializer, "OrderPlaced", "Application/JSON", &payload.bytes).unwrap(), r#"{"id":1}"#);
+}
+
+#[test]
+fn test_outbox_keeps_encoded_state() {
+	let outbox = OutBox::encoded("1".into(), "Order".into(), "OrderPlaced".into(), r#"{"id":1}"#).unwrap();
+	assert_eq!((outbox.state.as_slice(), outbox.content_type.as_str()), (br#"{"id":1}"#.as_slice(), JSON_CONTENT_TYPE));
+	assert_eq!(outbox.json_state().unwrap(), r#"{"id":1}"#);
+
+	let outbox = OutBox {
+		content_type: "application/x-protobuf".into(),
+		..outbox
+	};
+	assert!(outbox.json_state().is_err());
+}
+
+#[cfg(feature = "msgpack")]
+#[test]
+fn test_msgpack_payload_roundtrip() {
+	let payload = encode_with(&MessagePackSerializer, "OrderPlaced", r#"{"id":1,"items":["a","b"]}"#).unwrap();
+	assert_eq!(payload.content_type, "application/msgpack");
+	assert!(payload.bytes.len() < r#"{"id":1,"items":["a","b"]}"#.len());
+
+	let decoded = decode_with(&MessagePackSerializer, "OrderPlaced", "application/msgpack", &payload.bytes).unwrap();
+	assert_eq!(serde_json::from_str::<serde_json::Value>(&decoded).unwrap(), serde_json::json!({"id": 1, "items": ["a", "b"]}));
+	// JSON is accepted whatever serializer is set, for producers not migrated yet
+	assert_eq!(decode_with(&MessagePackSerializer, "OrderPlaced", JSON_CONTENT_TYPE, b"{}").unwrap(), "{}");
+	assert!(decode_with(&MessagePackSerializer, "OrderPlaced", "application/msgpack; v=1", &payload.bytes).is_ok());
+}
@@ -0,0 +1,231 @@
+//! ### Subscriptions
+//! [OutboxRelay](super::OutboxRelay) publishes outbox row to the topic of its event, unless [Subscriptions] route the topic elsewhere.
+//! Routes are changed while the process is running and persisted in [TSubscriptionStore], so that a new downstream consumer
+//! can get its own destination without redeploying the producing service.
+//!
+//! ```rust,no_run
+//! # use ruva_core::prelude::*;
+//! # async fn example() -> Result<(), BaseError> {
+//! let store = FileSubscriptionStore::new("subscriptions.json");
+//! let subscriptions = subscriptions();
+//! subscriptions.restore(&store).await?; // on boot
+//!
+//! // in your admin endpoint - keep publishing to `OrderPlaced` and also to the queue of billing service
+//! subscriptions.subscribe_in(&store, "OrderPlaced", "OrderPlaced").await?;
+//! subscriptions.subscribe_in(&store, "OrderPlaced", "billing.orders").await?;
+//! # Ok(())
+//! # }
+//! ```
+//! [FileSubscriptionStore] is for a single instance. Instances sharing subscriptions keep them in a database - `PgPool` and
+//! `SqlitePool` implement [TSubscriptionStore] with `sqlx-postgres` and `sqlx-sqlite` features - and each of them calls
+//! [Subscriptions::restore] periodically to pick up the changes made through the others.
+//! Destination is what the publisher takes as topic - Kafka topic, queue name and so on - and is namespaced as topic is.
+//! Once a topic has any subscription, rows are published only to its subscriptions, so subscribe the topic to itself to keep the default route.
+//! If publishing to one of the destinations fails, the row is published again to all of them.
+use std::collections::{BTreeMap, BTreeSet};
+use std::sync::RwLock;
+
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+
+use crate::prelude::BaseError;
+
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct Subscription {
+	pub topic: String,
+	pub destination: String,
+}
+
+impl Subscription {
+	pub fn new(topic: &str, destination: &str) -> Self {
+		Self {
+			topic: topic.to_string(),
+			destination: destination.to_string(),
+		}
+	}
+}
+
+/// Destinations of topics
+#[derive(Default)]
+pub struct Subscriptions {
+	routes: RwLock<BTreeMap<String, BTreeSet<String>>>,
+}
+
+static SUBSCRIPTIONS: std::sync::LazyLock<Subscriptions> = std::sync::LazyLock::new(Default::default);
+
+/// Process-wide subscriptions that the outbox relay consults before publishing each row.
+pub fn subscriptions() -> &'static Subscriptions {
+	&SUBSCRIPTIONS
+}
+
+impl Subscriptions {
+	/// Publish rows of `topic` to `destination`. Returns `false` if it is subscribed already.
+	pub fn subscribe(&self, topic: &str, destination: &str) -> bool {
+		tracing::info!("Subscription added: {} -> {}", topic, destination);
+		self.routes.write().unwrap().entry(topic.to_string()).or_default().insert(destination.to_string())
+	}
+
+	/// Returns `false` if it is not subscribed.
+	pub fn unsubscribe(&self, topic: &str, destination: &str) -> bool {
+		let mut routes = self.routes.write().unwrap();
+		let Some(destinations) = routes.get_mut(topic) else {
+			return false;
+		};
+		let removed = destinations.remove(destination);
+		if destinations.is_empty() {
+			routes.remove(topic);
+		}
+		if removed {
+			tracing::warn!("Subscription removed: {} -> {}", topic, destination);
+		}
+		removed
+	}
+
+	/// Where rows of `topic` are published - its subscriptions, or `topic` itself if there is none.
+	pub fn route(&self, topic: &str) -> Vec<String> {
+		match self.routes.read().unwrap().get(topic) {
+			Some(destinations) => destinations.iter().cloned().collect(),
+			None => vec![topic.to_string()],
+		}
+	}
+
+	pub fn list(&self) -> Vec<Subscription> {
+		let routes = self.routes.read().unwrap();
+		routes
+			.iter()
+			.flat_map(|(topic, destinations)| {
+				destinations.iter().map(|destination| Subscription {
+					topic: topic.clone(),
+					destination: destination.clone(),
+				})
+			})
+			.collect()
+	}
+
+	/// [Subscriptions::subscribe] and add the subscription to `store`, leaving the other subscriptions in it as they are.
+	pub async fn subscribe_in(&self, store: &impl TSubscriptionStore, topic: &str, destination: &str) -> Result<bool, BaseError> {
+		store.add(&Subscription::new(topic, destination)).await?;
+		Ok(self.subscribe(topic, destination))
+	}
+
+	/// [Subscriptions::unsubscribe] and remove the subscription from `store`, leaving the other subscriptions in it as they are.
+	pub async fn unsubscribe_in(&self, store: &impl TSubscriptionStore, topic: &str, destination: &str) -> Result<bool, BaseError> {
+		store.remove(&Subscription::new(topic, destination)).await?;
+		Ok(self.unsubscribe(topic, destination))
+	}
+
+	/// Replace subscriptions in `store` with the ones of this process, dropping what others saved meanwhile.
+	/// Use [Subscriptions::subscribe_in] and [Subscriptions::unsubscribe_in] when the store is shared.
+	pub async fn persist(&self, store: &impl TSubscriptionStore) -> Result<(), BaseError> {
+		store.save(self.list()).await
+	}
+
+	/// Replace subscriptions with the ones saved in `store`. With a store shared between instances, call it periodically
+	/// to pick up the changes made through the others.
+	pub async fn restore(&self, store: &impl TSubscriptionStore) -> Result<(), BaseError> {
+		let mut routes = BTreeMap::<String, BTreeSet<String>>::new();
+		for subscription in store.load().await? {
+			routes.entry(subscription.topic).or_default().insert(subscription.destination);
+		}
+		*self.routes.write().unwrap() = routes;
+		Ok(())
+	}
+}
+
+/// Storage of subscriptions
+#[async_trait]
+pub trait TSubscriptionStore: Send + Sync {
+	async fn load(&self) -> Result<Vec<Subscription>, BaseError>;
+	/// Replace every subscription with `subscriptions`
+	async fn save(&self, subscriptions: Vec<Subscription>) -> Result<(), BaseError>;
+	/// Store that is shared should add it on its own, rather than loading and saving every subscription.
+	async fn add(&self, subscription: &Subscription) -> Result<(), BaseError> {
+		let mut subscriptions = self.load().await?;
+		if !subscriptions.contains(subscription) {
+			subscriptions.push(subscription.clone());
+		}
+		self.save(subscriptions).await
+	}
+	/// Store that is shared should remove it on its own, rather than loading and saving every subscription.
+	async fn remove(&self, subscription: &Subscription) -> Result<(), BaseError> {
+		let mut subscriptions = self.load().await?;
+		subscriptions.retain(|saved| saved != subscription);
+		self.save(subscriptions).await
+	}
+}
+
+/// Store that keeps subscriptions in JSON file, for a single instance
+pub struct FileSubscriptionStore {
+	path: std::path::PathBuf,
+}
+
+impl FileSubscriptionStore {
+	pub fn new(path: impl Into<std::path::PathBuf>) -> Self {
+		Self { path: path.into() }
+	}
+}
+
+#[async_trait]
+impl TSubscriptionStore for FileSubscriptionStore {
+	async fn load(&self) -> Result<Vec<Subscription>, BaseError> {
+		match tokio::fs::read_to_string(&self.path).await {
+			Ok(content) => serde_json::from_str(&content).map_err(|err| {
+				tracing::error!("Failed to parse subscriptions! {}", err);
+				BaseError::ServiceError
+			}),
+			Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(vec![]),
+			Err(err) => {
+				tracing::error!("Failed to read subscriptions! {}", err);
+				Err(BaseError::ServiceError)
+			}
+		}
+	}
+
+	async fn save(&self, subscriptions: Vec<Subscription>) -> Result<(), BaseError> {
+		let content = serde_json::to_string(&subscriptions).expect("Failed to serialize subscriptions");
+		// * Written aside and renamed over, so that the file is never left half written.
+		let mut temp_path = self.path.clone().into_os_string();
+		temp_path.push(".tmp");
+		let written = match tokio::fs::write(&temp_path, content).await {
+			Ok(()) => tokio::fs::rename(&temp_path, &self.path).await,
+			Err(err) => Err(err),
+		};
+		written.map_err(|err| {
+			tracing::error!("Failed to write subscriptions! {}", err);
+			BaseError::ServiceError
+		})
+	}
+}
+
+#[tokio::test]
+async fn test_subscriptions_persisted() {
+	let subscriptions = Subscriptions::default();
+	assert_eq!(subscriptions.route("OrderPlaced"), vec!["OrderPlaced"]);
+
+	assert!(subscriptions.subscribe("OrderPlaced", "OrderPlaced"));
+	assert!(subscriptions.subscribe("OrderPlaced", "billing.orders"));
+	assert!(!subscriptions.subscribe("OrderPlaced", "billing.orders"));
+	assert_eq!(subscriptions.route("OrderPlaced"), vec!["OrderPlaced", "billing.orders"]);
+
+	let path = std::env::temp_dir().join(format!("ruva_subscriptions_{}.json", std::process::id()));
+	let store = FileSubscriptionStore::new(&path);
+	subscriptions.persist(&store).await.unwrap();
+
+	let restarted = Subscriptions::default();
+	restarted.restore(&store).await.unwrap();
+	assert_eq!(restarted.list(), subscriptions.list());
+
+	assert!(restarted.unsubscribe("OrderPlaced", "OrderPlaced"));
+	assert!(!restarted.unsubscribe("OrderPlaced", "OrderPlaced"));
+	assert_eq!(restarted.route("OrderPlaced"), vec!["billing.orders"]);
+	restarted.unsubscribe("OrderPlaced", "billing.orders");
+	assert_eq!(restarted.route("OrderPlaced"), vec!["OrderPlaced"]);
+
+	// Subscription added through another instance is kept
+	assert!(restarted.subscribe_in(&store, "OrderCancelled", "billing.orders").await.unwrap());
+	subscriptions.unsubscribe_in(&store, "OrderPlaced", "OrderPlaced").await.unwrap();
+	subscriptions.restore(&store).await.unwrap();
+	assert_eq!(subscriptions.route("OrderCancelled"), vec!["billing.orders"]);
+	assert_eq!(subscriptions.route("OrderPlaced"), vec!["billing.orders"]);
+	let _ = std::fs::remove_file(path);
+}
@@ -0,0 +1,142 @@
+//! ### Event versioning
+//! Payload kept in the outbox, the broker or the dead letter store is serialized from the event struct of that time.
+//! Once the struct changes, bump its version with `#[event_version(N)]` and register [TEventUpcaster] that turns
+//! payload of the previous version into the current one. Inbox and dead letter replay upcast payloads before deserializing them.
+//!
+//! ```rust,ignore
+//! #[derive(Serialize, Deserialize, Clone, TEvent)]
+//! #[internally_notifiable]
+//! #[event_version(2)]
+//! pub struct OrderPlaced {
+//!     pub order_id: i64,
+//!     // `amount: i64` of version 1 is split into the two
+//!     pub price: i64,
+//!     pub quantity: i64,
+//! }
+//!
+//! struct OrderPlacedV1;
+//! impl TEventUpcaster for OrderPlacedV1 {
+//!     fn topic(&self) -> &str { "OrderPlaced" }
+//!     fn source_version(&self) -> u32 { 1 }
+//!     fn upcast(&self, mut payload: serde_json::Value) -> Result<serde_json::Value, BaseError> {
+//!         payload["price"] = payload["amount"].take();
+//!         payload["quantity"] = 1.into();
+//!         Ok(payload)
+//!     }
+//! }
+//!
+//! // On boot
+//! register_upcaster(OrderPlacedV1);
+//! ```
+//! Upcasters of a topic are chained, so payload of version 1 goes through `1 -> 2`, `2 -> 3` and so on.
+use std::borrow::Cow;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, RwLock};
+
+use crate::prelude::{json, BaseError};
+
+/// Version of event that doesn't declare one
+pub const INITIAL_EVENT_VERSION: u32 = 1;
+
+/// Transform payload of `topic` from `source_version()` to the next version
+pub trait TEventUpcaster: Send + Sync {
+	/// Topic without namespace
+	fn topic(&self) -> &str;
+	fn source_version(&self) -> u32;
+	fn upcast(&self, payload: serde_json::Value) -> Result<serde_json::Value, BaseError>;
+}
+
+type Upcasters = hashbrown::HashMap<(String, u32), Arc<dyn TEventUpcaster>>;
+
+static UPCASTERS: RwLock<Option<Upcasters>> = RwLock::new(None);
+
+/// ## Panics
+/// If upcaster for the same topic and version is already registered.
+pub fn register_upcaster(upcaster: impl TEventUpcaster + 'static) {
+	let mut upcasters = UPCASTERS.write().unwrap();
+	let key = (upcaster.topic().to_string(), upcaster.source_version());
+	let upcasters = upcasters.get_or_insert_with(Default::default);
+	if upcasters.contains_key(&key) {
+		panic!("Upcaster of {} from version {} is already registered!", key.0, key.1);
+	}
+	upcasters.insert(key, Arc::new(upcaster));
+}
+
+/// Bring `payload` of `topic` serialized at `version` up to the latest version upcasters reach.
+/// Payload is returned as is when no upcaster applies.
+pub fn upcast_payload<'a>(topic: &str, version: u32, payload: &'a str) -> Result<Cow<'a, str>, BaseError> {
+	let upcasters = UPCASTERS.read().unwrap();
+	let Some(upcasters) = upcasters.as_ref() else {
+		return Ok(Cow::Borrowed(payload));
+	};
+	let mut version = version;
+	let mut value: Option<serde_json::Value> = None;
+	while let Some(upcaster) = upcasters.get(&(topic.to_string(), version)) {
+		let current = match value.take() {
+			Some(current) => current,
+			None => json::from_str(payload).map_err(|err| BaseError::DatabaseError(err.to_string()))?,
+		};
+		value = Some(upcaster.upcast(current)?);
+		version += 1;
+	}
+	match value {
+		Some(value) => Ok(Cow::Owned(value.to_string())),
+		None => Ok(Cow::Borrowed(payload)),
+	}
+}
+
+static OUTBOX_VERSION: AtomicBool = AtomicBool::new(false);
+
+/// Store `OutBox::version` of rows written from now on. Requires `version` column of `service_outbox`.
+/// ```sql
+/// ALTER TABLE service_outbox ADD COLUMN version INTEGER NOT NULL DEFAULT 1;
+/// ```
+pub fn enable_outbox_version() {
+	OUTBOX_VERSION.store(true, Ordering::Relaxed);
+}
+
+pub fn outbox_version_enabled() -> bool {
+	OUTBOX_VERSION.load(Ordering::Relaxed)
+}
+
+#[test]
+fn test_upcasters_are_chained() {
+	struct Rename;
+	impl TEventUpcaster for Rename {
+		fn topic(&self) -> &str {
+			"UpcastTestEvent"
+		}
+		fn source_version(&self) -> u32 {
+			1
+		}
+		fn upcast(&self, mut payload: serde_json::Value) -> Result<serde_json::Value, BaseError> {
+			payload["price"] = payload["amount"].take();
+			Ok(payload)
+		}
+	}
+	struct AddQuantity;
+	impl TEventUpcaster for AddQuantity {
+		fn topic(&self) -> &str {
+			"UpcastTestEvent"
+		}
+		fn source_version(&self) -> u32 {
+			2
+		}
+		fn upcast(&self, mut payload: serde_json::Value) -> Result<serde_json::Value, BaseError> {
+			payload["quantity"] = 1.into();
+			Ok(payload)
+		}
+	}
+	register_upcaster(Rename);
+	register_upcaster(AddQuantity);
+
+	let upcasted: serde_json::Value = serde_json::from_str(&upcast_payload("UpcastTestEvent", 1, r#"{"amount":10}"#).unwrap()).unwrap();
+	assert_eq!(upcasted, serde_json::json!({"amount": null, "price": 10, "quantity": 1}));
+
+	let upcasted: serde_json::Value = serde_json::from_str(&upcast_payload("UpcastTestEvent", 2, r#"{"price":10}"#).unwrap()).unwrap();
+	assert_eq!(upcasted, serde_json::json!({"price": 10, "quantity": 1}));
+
+	// Current version and unknown topic are left untouched
+	assert!(matches!(upcast_payload("UpcastTestEvent", 3, "{}").unwrap(), Cow::Borrowed("{}")));
+	assert!(matches!(upcast_payload("Unknown", 1, "not json").unwrap(), Cow::Borrowed("not json")));
+}
@@ -7,7 +7,43 @@ pub enum BaseError {
 	TransactionError,
 	StopSentinelWithEvent(std::sync::Arc<dyn TEvent>),
 	DatabaseError(String),
+	/// Database constraint(unique, foreign key, check...) is violated. `constraint` is the name of the constraint.
+	ConstraintViolation {
+		constraint: String,
+		message: String,
+	},
 	ServiceError,
+	/// No handler is registered for internally notifiable event of the topic. See `MissingHandlerPolicy`.
+	HandlerNotFound(String),
+	/// Dispatch of `command` tried to enqueue more events than `limit`. See `EventLimit`.
+	EventLimitExceeded {
+		command: String,
+		limit: usize,
+	},
+	/// Command is rejected by an aspect before it reaches the handler, for example replay protection.
+	Rejected(String),
+	/// Broker didn't accept the event. See `TOutboxPublisher`.
+	DeliveryError(String),
+	/// `command` is not run as `limit` commands are in flight already. See `set_command_concurrency_limit`.
+	Overloaded {
+		command: String,
+		limit: usize,
+	},
+	/// Aggregate was changed by someone else since it was loaded. See `TVersioned`.
+	ConcurrencyConflict(String),
+	/// Transaction is aborted by serialization failure or deadlock. Retrying the whole command may succeed. See `RetryHandler`.
+	TransactionConflict(String),
+	/// Command breaks the rules of its `TValidate`. See `ValidationAspect`.
+	ValidationFailed(crate::prelude::ValidationErrors),
+	/// Actor is not allowed to run the command. See `AuthorizationAspect`.
+	Forbidden(String),
+}
+
+impl BaseError {
+	/// Whether running the command again may succeed
+	pub fn is_retryable(&self) -> bool {
+		matches!(self, Self::TransactionConflict(_) | Self::ConcurrencyConflict(_))
+	}
 }
 
 pub trait ApplicationResponse: Send + Sync {}
@@ -21,6 +57,153 @@ impl From<BaseError> for Box<dyn ApplicationError> {
 	}
 }
 
+/// HTTP status code the error is answered with by web integrations such as `ruva-axum`.
+/// `#[derive(ApplicationError)]` implements it, taking `#[http_status(..)]` of variants.
+/// Web integrations answer with [public_message](THttpStatus::public_message) and [public_details](THttpStatus::public_details)
+/// only, and log the `Debug` form of the error, which may carry SQL, reason of denial and so on.
+pub trait THttpStatus {
+	fn http_status(&self) -> u16;
+	/// Message the client is answered with. Defaults to the reason phrase of the status code.
+	fn public_message(&self) -> String {
+		reason_phrase(self.http_status()).to_string()
+	}
+	/// Structured detail for the client, such as violations of `BaseError::ValidationFailed`
+	fn public_details(&self) -> Option<serde_json::Value> {
+		None
+	}
+}
+
+pub fn reason_phrase(status: u16) -> &'static str {
+	match status {
+		400 => "Bad Request",
+		401 => "Unauthorized",
+		403 => "Forbidden",
+		404 => "Not Found",
+		409 => "Conflict",
+		412 => "Precondition Failed",
+		422 => "Unprocessable Entity",
+		429 => "Too Many Requests",
+		501 => "Not Implemented",
+		502 => "Bad Gateway",
+		503 => "Service Unavailable",
+		504 => "Gateway Timeout",
+		_ => "Internal Server Error",
+	}
+}
+
+impl THttpStatus for BaseError {
+	fn http_status(&self) -> u16 {
+		match self {
+			Self::Forbidden(_) => 403,
+			Self::NotFound => 404,
+			Self::ConstraintViolation { .. } | Self::TransactionConflict(_) | Self::ConcurrencyConflict(_) => 409,
+			Self::Rejected(_) | Self::ValidationFailed(_) => 422,
+			Self::DeliveryError(_) => 502,
+			Self::Overloaded { .. } => 503,
+			_ => 500,
+		}
+	}
+
+	fn public_message(&self) -> String {
+		match self {
+			Self::ValidationFailed(_) => "Validation Failed".to_string(),
+			_ => reason_phrase(self.http_status()).to_string(),
+		}
+	}
+
+	fn public_details(&self) -> Option<serde_json::Value> {
+		match self {
+			Self::ValidationFailed(errors) => serde_json::to_value(errors).ok(),
+			_ => None,
+		}
+	}
+}
+
 impl ApplicationResponse for () {}
 
 impl ApplicationError for () {}
+
+static TRACE_ID_PROVIDER: std::sync::OnceLock<fn() -> Option<String>> = std::sync::OnceLock::new();
+
+/// Replace how the current trace id is read, for example from OpenTelemetry span context.
+/// By default, id of the current `tracing` span is used.
+/// ## Panics
+/// If trace id provider is already set.
+pub fn set_trace_id_provider(provider: fn() -> Option<String>) {
+	if TRACE_ID_PROVIDER.set(provider).is_err() {
+		panic!("Trace Id Provider Is Already Set!");
+	}
+}
+
+/// `None` if the provider panics, so that a broken tracing setup doesn't take down error reporting.
+pub fn current_trace_id() -> Option<String> {
+	match TRACE_ID_PROVIDER.get() {
+		Some(provider) => std::panic::catch_unwind(provider).unwrap_or_else(|_| {
+			tracing::warn!("Trace id provider panicked!");
+			None
+		}),
+		None => tracing::Span::current().id().map(|id| format!("{:016x}", id.into_u64())),
+	}
+}
+
+/// Error response with trace id of where it occurred, so that error id reported by user can be correlated with traces.
+/// Failed outcome of the command is audited with the same trace id. See [ContextManager::trace_id](crate::prelude::ContextManager::trace_id).
+/// ## Example
+/// ```rust,ignore
+/// match MessageBus.dispatch(cmd, conn).await {
+///     Ok(res) => Json(res).into_response(),
+///     Err(err) => (StatusCode::BAD_REQUEST, Json(ErrorResponse::capture(err))).into_response(),
+/// }
+/// // {"error": "NotFound", "trace_id": "0000000000000001"}
+/// ```
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct ErrorResponse<E> {
+	pub error: E,
+	/// See [THttpStatus::public_details]
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub details: Option<serde_json::Value>,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub trace_id: Option<String>,
+}
+
+impl<E: ApplicationError> ErrorResponse<E> {
+	/// Attach trace id of the current span to `error`.
+	pub fn capture(error: E) -> Self {
+		Self {
+			error,
+			details: None,
+			trace_id: current_trace_id(),
+		}
+	}
+}
+
+impl<E: ApplicationError> From<E> for ErrorResponse<E> {
+	fn from(error: E) -> Self {
+		Self::capture(error)
+	}
+}
+
+#[test]
+fn test_error_response_without_span() {
+	#[derive(Debug, serde::Serialize)]
+	enum TestError {
+		NotFound,
+	}
+	impl ApplicationError for TestError {}
+
+	let response = ErrorResponse::capture(TestError::NotFound);
+	assert_eq!(serde_json::to_string(&response).unwrap(), r#"{"error":"NotFound"}"#);
+}
+
+#[test]
+fn test_public_message_of_base_error() {
+	let error = BaseError::DatabaseError("relation \"orders\" does not exist".into());
+	assert_eq!((error.http_status(), error.public_message()), (500, "Internal Server Error".to_string()));
+	assert_eq!(BaseError::Forbidden("MakeOrder requires admin".into()).public_message(), "Forbidden");
+
+	let mut errors = crate::prelude::ValidationErrors::default();
+	errors.add("quantity", "range", "must be positive");
+	let error = BaseError::ValidationFailed(errors);
+	assert_eq!(error.public_message(), "Validation Failed");
+	assert_eq!(error.public_details(), Some(serde_json::json!([{"field": "quantity", "code": "range", "message": "must be positive"}])));
+}
@@ -44,7 +44,7 @@ impl NumericalUniqueIdGenerator {
 	/// # Examples
 	///
 	/// ```
-	/// use snowflake::NumericalUniqueIdGenerator;
+	/// use ruva_core::prelude::NumericalUniqueIdGenerator;
 	///
 	/// let id_generator = NumericalUniqueIdGenerator::new(1, 1);
 	/// ```
@@ -58,7 +58,7 @@ impl NumericalUniqueIdGenerator {
 	///
 	/// ```
 	/// use std::time::{Duration, UNIX_EPOCH};
-	/// use snowflake::NumericalUniqueIdGenerator;
+	/// use ruva_core::prelude::NumericalUniqueIdGenerator;
 	///
 	/// // 1 January 2015 00:00:00
 	/// let discord_epoch = UNIX_EPOCH + Duration::from_millis(1420070400000);
@@ -89,14 +89,14 @@ impl NumericalUniqueIdGenerator {
 	/// The basic guarantee time punctuality.
 	///
 	/// Basic guarantee time punctuality.
-
+	///
 	/// When traffic peaks, 4096 in a millsec is simply not enough.
 	/// But setting time after every 4096 calls.
 	///
 	/// # Examples
 	///
 	/// ```
-	/// use snowflake::NumericalUniqueIdGenerator;
+	/// use ruva_core::prelude::NumericalUniqueIdGenerator;
 	///
 	/// let mut id_generator = NumericalUniqueIdGenerator::new(1, 1);
 	/// id_generator.generate();
@@ -132,7 +132,7 @@ impl NumericalUniqueIdBucket {
 	/// # Examples
 	///
 	/// ```
-	/// use snowflake::NumericalUniqueIdBucket;
+	/// use ruva_core::prelude::NumericalUniqueIdBucket;
 	///
 	/// let id_generator_bucket = NumericalUniqueIdBucket::new(1, 1);
 	/// ```
@@ -147,7 +147,7 @@ impl NumericalUniqueIdBucket {
 	///
 	/// ```
 	/// use std::time::{Duration, UNIX_EPOCH};
-	/// use snowflake::NumericalUniqueIdBucket;
+	/// use ruva_core::prelude::NumericalUniqueIdBucket;
 	///
 	/// // 1 January 2015 00:00:00
 	/// let beringlab = UNIX_EPOCH + Duration::from_millis(1570292856000);
@@ -163,7 +163,7 @@ impl NumericalUniqueIdBucket {
 	/// # Examples
 	///
 	/// ```
-	/// use snowflake::NumericalUniqueIdBucket;
+	/// use ruva_core::prelude::NumericalUniqueIdBucket;
 	///
 	/// let mut id_generator_bucket = NumericalUniqueIdBucket::new(1, 1);
 	/// let id = id_generator_bucket.get_id();
@@ -222,6 +222,11 @@ impl SnowFlake {
 	pub fn generate() -> Self {
 		ID_GENERATOR.generate().into()
 	}
+
+	/// Milliseconds since UNIX epoch when the id was generated.
+	pub fn timestamp_millis(&self) -> i64 {
+		self.0 >> 22
+	}
 }
 
 impl Deref for SnowFlake {
@@ -0,0 +1,68 @@
+//! ### Service provider interface
+//! Items for those who build their own bus, aspect or registration macro rather than using [MessageBus](crate::prelude::MessageBus)
+//! and `init_event_handler!` as they are. They are kept here regardless of where they are defined, and changed only with
+//! the major version, bumping [SPI_VERSION]. Items of `prelude` that are not re-exported here may move between minor releases.
+//!
+//! ```rust
+//! # use ruva_core as ruva;
+//! # use std::sync::{Arc, LazyLock};
+//! # type ServiceError = ruva::prelude::BaseError;
+//! # async fn audit(_: Arc<dyn TEvent>, _: AtomicContextManager) -> Result<(), ServiceError> { Ok(()) }
+//! use ruva::spi::*;
+//!
+//! struct AuditBus;
+//!
+//! impl TEventBus<ServiceError> for AuditBus {
+//!     fn event_handler(&self) -> &'static TEventHandler<ServiceError> {
+//!         static EVENT_HANDLERS: LazyLock<TEventHandler<ServiceError>> = LazyLock::new(|| {
+//!             let mut handlers = TEventHandler::default();
+//!             let handler: Handlers<ServiceError> = vec![Box::new(|event: Arc<dyn TEvent>, context_manager: AtomicContextManager| {
+//!                 sandboxed("OrderPlaced", "audit", Box::pin(async move { audit(event, context_manager).await }))
+//!             })];
+//!             handlers.insert("OrderPlaced".to_string(), EventHandlers::Sync(handler));
+//!             handlers
+//!         });
+//!         &EVENT_HANDLERS
+//!     }
+//! }
+//! ```
+//!
+//! #### Registries
+//! [TEventHandler] maps topic to [EventHandlers], which is what [TEventBus::event_handler] returns.
+//! Build it by hand, or with [EventHandlerRegistry] for registration keyed by event type.
+//! For the runtime controls to apply to handlers built by hand, do as `init_event_handler!` does - register them to [handler_toggles],
+//! skip disabled ones and wrap the rest with [sandboxed] and then [migrated].
+//!
+//! #### Context
+//! [ContextManager] is shared by the command and every event cascaded from it, while [Context] is the unit of work of one handler.
+//! Custom unit of work implements [TUnitOfWork] and [TSetCurrentEvents] so that command handlers can be run on it.
+//!
+//! #### Dispatch pipeline
+//! [TMessageBus] dispatches command to [TCommandService] built by [TMessageBus::command_handler], commits it through
+//! [TUnitOfWork] stage by stage - see [CommitStage] - and then runs handlers of the events raised.
+//! Cross-cutting concerns wrap the service with [TLayer]. [TBusObserver] is notified along the way.
+
+/// Bumped when an item of this module changes in a way that breaks implementors
+pub const SPI_VERSION: u32 = 1;
+
+// Registries
+pub use crate::bus_components::handler::{BatchHandlers, EventHandlerRegistry, EventHandlers, Future, FutureResult, Handlers};
+pub use crate::bus_components::messagebus::{HandlerHasher, TEventHandler};
+pub use crate::bus_components::migration::migrated;
+pub use crate::bus_components::sandbox::sandboxed;
+pub use crate::bus_components::toggles::{handler_toggles, HandlerToggles};
+
+// Context
+pub use crate::bus_components::contexts::{AtomicContextManager, Context, ContextManager, TSetCurrentEvents};
+pub use crate::bus_components::executor::TConnection;
+pub use crate::unit_of_work::TUnitOfWork;
+
+// Dispatch pipeline
+pub use crate::bus_components::layer::TLayer;
+pub use crate::bus_components::messagebus::{TCommandService, TEventBus, TMessageBus};
+pub use crate::bus_components::observer::{register_bus_observer, TBusObserver};
+pub use crate::bus_components::pipeline::CommitStage;
+
+// Messages and errors the above are written in
+pub use crate::message::{TCommand, TEvent};
+pub use crate::responses::{ApplicationError, ApplicationResponse, BaseError};
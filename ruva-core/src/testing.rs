@@ -0,0 +1,434 @@
+//! ### Test utilities
+//! [EventAssertions] replaces manual inspection of raised events in handler tests.
+//!
+//! ```rust,ignore
+//! EventAssertions::from_aggregate(&order)
+//!     .assert_contains::<OrderSucceeded>(|e| e.user_id == 1)
+//!     .assert_count::<OrderSucceeded>(1)
+//!     .assert_order::<OrderCreated, OrderSucceeded>();
+//! ```
+//! On failure, the message lists every captured event with its state so that difference from the expectation is visible at a glance.
+//!
+//! [DispatchSnapshot] serializes the whole outcome of a dispatch so that regression test of complex handler becomes one-liner.
+//! ```rust,ignore
+//! DispatchSnapshot::new(&response)
+//!     .events(&raised_events)
+//!     .aggregate(OrderAdapter::from(order))
+//!     .assert_golden("tests/snapshots/make_order.json");
+//!
+//! // or with insta
+//! insta::assert_snapshot!(DispatchSnapshot::new(&response).events(&raised_events).render());
+//! ```
+//! Set `RUVA_UPDATE_SNAPSHOTS=1` to (re)write golden files.
+//!
+//! [FakeOutbox] stands in for `service_outbox` so that external notification of handler is verified without database.
+//! It is a connection [Context](crate::prelude::Context) runs its unit of work on, adding the rows of the events on commit.
+//! ```rust
+//! # use ruva_core::prelude::*;
+//! # async fn example<C>(cmd: C) -> Result<(), C::Error>
+//! # where C: TCommandRoute, C::Error: From<BaseError>, BaseError: From<C::Error>, MessageBus: TEventBus<C::Error> {
+//! let outbox: &'static FakeOutbox = Box::leak(Box::new(FakeOutbox::new()));
+//! MessageBus.dispatch_with(cmd, ContextManager::new(outbox)).await?;
+//!
+//! outbox.assert_topic_emitted("OrderSucceeded", 1).assert_topic_not_emitted("OrderFailed");
+//! # Ok(())
+//! # }
+//! ```
+//! Only the outbox is kept. Repositories of the handlers are to be faked on their own.
+//! It is also [TOutboxStore] and [TOutboxPublisher], to be given to [OutboxRelay](crate::prelude::OutboxRelay) on either side.
+use std::{
+	collections::VecDeque,
+	sync::{Arc, Mutex},
+};
+
+use async_trait::async_trait;
+use serde::Serialize;
+
+use crate::prelude::{BaseError, OutBox, TAggregate, TConnection, TEvent, TOutboxPublisher, TOutboxStore};
+
+pub struct EventAssertions {
+	events: Vec<Arc<dyn TEvent>>,
+}
+
+impl EventAssertions {
+	pub fn new(events: impl IntoIterator<Item = Arc<dyn TEvent>>) -> Self {
+		Self { events: events.into_iter().collect() }
+	}
+
+	/// Capture events raised on aggregate without taking them out.
+	pub fn from_aggregate(aggregate: &impl TAggregate) -> Self {
+		Self::new(aggregate.events().iter().cloned())
+	}
+
+	pub fn events(&self) -> &[Arc<dyn TEvent>] {
+		&self.events
+	}
+
+	fn captured(&self) -> String {
+		if self.events.is_empty() {
+			return "  (no events captured)".to_string();
+		}
+		self.events
+			.iter()
+			.enumerate()
+			.map(|(i, e)| format!("  [{}] {} {}", i, e.metadata().topic, e.state()))
+			.collect::<Vec<_>>()
+			.join("\n")
+	}
+
+	fn topic<T: TEvent>() -> &'static str {
+		std::any::type_name::<T>().split("::").last().unwrap()
+	}
+
+	fn position<T: TEvent>(&self) -> Option<usize> {
+		self.events.iter().position(|e| e.downcast_ref::<T>().is_some())
+	}
+
+	/// Assert that at least one event of type `T` satisfies `predicate`.
+	#[track_caller]
+	pub fn assert_contains<T: TEvent>(&self, predicate: impl Fn(&T) -> bool) -> &Self {
+		if !self.events.iter().filter_map(|e| e.downcast_ref::<T>()).any(predicate) {
+			panic!("expected: {} matching predicate\ncaptured:\n{}", Self::topic::<T>(), self.captured());
+		}
+		self
+	}
+
+	/// Assert that no event of type `T` was raised.
+	#[track_caller]
+	pub fn assert_not_contains<T: TEvent>(&self) -> &Self {
+		if self.position::<T>().is_some() {
+			panic!("expected: no {}\ncaptured:\n{}", Self::topic::<T>(), self.captured());
+		}
+		self
+	}
+
+	/// Assert the number of events of type `T`.
+	#[track_caller]
+	pub fn assert_count<T: TEvent>(&self, expected: usize) -> &Self {
+		let count = self.events.iter().filter(|e| e.downcast_ref::<T>().is_some()).count();
+		if count != expected {
+			panic!(
+				"expected: {} x {}, actual: {} x {}\ncaptured:\n{}",
+				Self::topic::<T>(),
+				expected,
+				Self::topic::<T>(),
+				count,
+				self.captured()
+			);
+		}
+		self
+	}
+
+	/// Assert that the first `A` was raised before the first `B`.
+	#[track_caller]
+	pub fn assert_order<A: TEvent, B: TEvent>(&self) -> &Self {
+		match (self.position::<A>(), self.position::<B>()) {
+			(Some(a), Some(b)) if a < b => self,
+			_ => panic!("expected: {} before {}\ncaptured:\n{}", Self::topic::<A>(), Self::topic::<B>(), self.captured()),
+		}
+	}
+}
+
+impl From<&VecDeque<Arc<dyn TEvent>>> for EventAssertions {
+	fn from(value: &VecDeque<Arc<dyn TEvent>>) -> Self {
+		Self::new(value.iter().cloned())
+	}
+}
+
+#[derive(Debug, Serialize)]
+struct EventSnapshot {
+	topic: String,
+	aggregate_id: String,
+	aggregate_name: String,
+	state: serde_json::Value,
+}
+
+/// Serializable outcome of a dispatch - response, raised events and end state of aggregate
+#[derive(Debug, Serialize)]
+pub struct DispatchSnapshot {
+	response: serde_json::Value,
+	events: Vec<EventSnapshot>,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	aggregate: Option<serde_json::Value>,
+}
+
+impl DispatchSnapshot {
+	pub fn new(response: &impl Serialize) -> Self {
+		Self {
+			response: serde_json::to_value(response).expect("Failed to serialize response"),
+			events: vec![],
+			aggregate: None,
+		}
+	}
+
+	pub fn events<'a>(mut self, events: impl IntoIterator<Item = &'a Arc<dyn TEvent>>) -> Self {
+		self.events.extend(events.into_iter().map(|e| {
+			let metadata = e.metadata();
+			let state = e.state();
+			EventSnapshot {
+				topic: metadata.topic,
+				aggregate_id: metadata.aggregate_id,
+				aggregate_name: metadata.aggregate_name,
+				state: serde_json::from_str(&state).unwrap_or(serde_json::Value::String(state)),
+			}
+		}));
+		self
+	}
+
+	/// End state of aggregate. Pass adapter of the aggregate so that only persisted fields are recorded.
+	pub fn aggregate(mut self, adapter: impl Serialize) -> Self {
+		self.aggregate = Some(serde_json::to_value(adapter).expect("Failed to serialize aggregate"));
+		self
+	}
+
+	/// Pretty-printed JSON. Stable across runs as long as the outcome is the same.
+	pub fn render(&self) -> String {
+		serde_json::to_string_pretty(self).expect("Failed to serialize snapshot")
+	}
+
+	/// Compare with golden file at `path`. If the file doesn't exist or `RUVA_UPDATE_SNAPSHOTS` is set, the file is written instead.
+	#[track_caller]
+	pub fn assert_golden(&self, path: impl AsRef<std::path::Path>) {
+		let path = path.as_ref();
+		let rendered = self.render();
+		if std::env::var("RUVA_UPDATE_SNAPSHOTS").is_ok() || !path.exists() {
+			if let Some(parent) = path.parent() {
+				std::fs::create_dir_all(parent).expect("Failed to create snapshot directory");
+			}
+			std::fs::write(path, rendered).expect("Failed to write snapshot");
+			return;
+		}
+		let golden = std::fs::read_to_string(path).expect("Failed to read snapshot");
+		if golden.trim_end() != rendered.trim_end() {
+			panic!("snapshot mismatch: {}\n--- golden\n{}\n+++ actual\n{}", path.display(), golden, rendered);
+		}
+	}
+}
+
+/// In-memory outbox recording added rows
+#[derive(Debug, Default)]
+pub struct FakeOutbox {
+	rows: Mutex<Vec<OutBox>>,
+}
+
+impl FakeOutbox {
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	pub fn add(&self, rows: impl IntoIterator<Item = OutBox>) {
+		self.rows.lock().unwrap().extend(rows);
+	}
+
+	/// Add externally notifiable ones of `events`, as the unit of work does on commit.
+	pub fn record_events<'a>(&self, events: impl IntoIterator<Item = &'a Arc<dyn TEvent>>) {
+		self.add(events.into_iter().filter(|e| e.externally_notifiable()).map(|e| e.outbox().expect("Failed to encode event")));
+	}
+
+	/// Rows added so far, in the order they were added
+	pub fn rows(&self) -> Vec<OutBox> {
+		self.rows.lock().unwrap().clone()
+	}
+
+	pub fn clear(&self) {
+		self.rows.lock().unwrap().clear();
+	}
+
+	fn captured(&self) -> String {
+		let rows = self.rows.lock().unwrap();
+		if rows.is_empty() {
+			return "  (no rows added)".to_string();
+		}
+		rows.iter()
+			.enumerate()
+			.map(|(i, row)| format!("  [{}] {} {}", i, row.topic, row.json_state().unwrap_or_else(|err| format!("{:?}", err))))
+			.collect::<Vec<_>>()
+			.join("\n")
+	}
+
+	/// Assert the number of rows of `topic`.
+	#[track_caller]
+	pub fn assert_topic_emitted(&self, topic: &str, times: usize) -> &Self {
+		let count = self.rows.lock().unwrap().iter().filter(|row| row.topic == topic).count();
+		if count != times {
+			panic!("expected: {} x {}, actual: {} x {}\ncaptured:\n{}", topic, times, topic, count, self.captured());
+		}
+		self
+	}
+
+	#[track_caller]
+	pub fn assert_topic_not_emitted(&self, topic: &str) -> &Self {
+		self.assert_topic_emitted(topic, 0)
+	}
+
+	/// Assert that at least one row of `topic` has payload satisfying `predicate`.
+	#[track_caller]
+	pub fn assert_payload(&self, topic: &str, predicate: impl Fn(&serde_json::Value) -> bool) -> &Self {
+		let matched = self
+			.rows
+			.lock()
+			.unwrap()
+			.iter()
+			.filter(|row| row.topic == topic)
+			.any(|row| row.json_state().is_ok_and(|state| serde_json::from_str(&state).is_ok_and(|payload| predicate(&payload))));
+		if !matched {
+			panic!("expected: {} matching predicate\ncaptured:\n{}", topic, self.captured());
+		}
+		self
+	}
+}
+
+impl TConnection for FakeOutbox {}
+
+/// Transaction of [Context](crate::prelude::Context) on [FakeOutbox], adding the rows written in it on commit
+pub(crate) struct FakeTransaction {
+	outbox: &'static FakeOutbox,
+	rows: Vec<OutBox>,
+}
+
+impl FakeTransaction {
+	pub(crate) fn new(outbox: &'static FakeOutbox) -> Self {
+		Self { outbox, rows: vec![] }
+	}
+
+	pub(crate) fn write(&mut self, rows: Vec<OutBox>) {
+		self.rows.extend(rows);
+	}
+
+	pub(crate) fn commit(self) {
+		self.outbox.add(self.rows);
+	}
+}
+
+#[async_trait]
+impl TOutboxStore for FakeOutbox {
+	async fn fetch_unprocessed(&self, limit: usize) -> Result<Vec<OutBox>, BaseError> {
+		Ok(self.rows.lock().unwrap().iter().filter(|row| !row.processed).take(limit).cloned().collect())
+	}
+
+	async fn mark_processed(&self, id: i64) -> Result<(), BaseError> {
+		self.rows.lock().unwrap().iter_mut().filter(|row| row.id == id).for_each(|row| row.processed = true);
+		Ok(())
+	}
+}
+
+/// Published rows are added, as if the broker were an outbox.
+#[async_trait]
+impl TOutboxPublisher for FakeOutbox {
+	async fn publish(&self, outbox: &OutBox) -> Result<(), BaseError> {
+		self.add([outbox.clone()]);
+		Ok(())
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use super::{EventAssertions, FakeOutbox};
+	use crate::prelude::{OutboxRelay, TEvent, TOutboxStore};
+	use std::sync::Arc;
+
+	struct OrderCreated;
+	impl TEvent for OrderCreated {
+		fn state(&self) -> String {
+			"{}".into()
+		}
+	}
+	struct OrderSucceeded(i64);
+	impl TEvent for OrderSucceeded {
+		fn state(&self) -> String {
+			format!("{{\"user_id\":{}}}", self.0)
+		}
+	}
+
+	#[test]
+	fn test_event_assertions() {
+		let events: Vec<Arc<dyn TEvent>> = vec![Arc::new(OrderCreated), Arc::new(OrderSucceeded(1))];
+		EventAssertions::new(events)
+			.assert_contains::<OrderSucceeded>(|e| e.0 == 1)
+			.assert_count::<OrderCreated>(1)
+			.assert_order::<OrderCreated, OrderSucceeded>();
+	}
+
+	#[test]
+	fn test_dispatch_snapshot() {
+		let events: Vec<Arc<dyn TEvent>> = vec![Arc::new(OrderSucceeded(1))];
+		let snapshot = super::DispatchSnapshot::new(&"ok").events(&events).aggregate(serde_json::json!({"user_id": 1}));
+		assert_eq!(
+			snapshot.render(),
+			r#"{
+  "response": "ok",
+  "events": [
+    {
+      "topic": "OrderSucceeded",
+      "aggregate_id": "",
+      "aggregate_name": "",
+      "state": {
+        "user_id": 1
+      }
+    }
+  ],
+  "aggregate": {
+    "user_id": 1
+  }
+}"#
+		);
+	}
+
+	#[test]
+	#[should_panic(expected = "expected: OrderCreated before OrderSucceeded")]
+	fn test_event_assertions_order_failure() {
+		let events: Vec<Arc<dyn TEvent>> = vec![Arc::new(OrderSucceeded(1)), Arc::new(OrderCreated)];
+		EventAssertions::new(events).assert_order::<OrderCreated, OrderSucceeded>();
+	}
+
+	struct OrderShipped;
+	impl TEvent for OrderShipped {
+		fn externally_notifiable(&self) -> bool {
+			true
+		}
+		fn state(&self) -> String {
+			r#"{"order_id":7}"#.into()
+		}
+	}
+
+	#[tokio::test]
+	async fn test_fake_outbox() {
+		let outbox = Arc::new(FakeOutbox::new());
+		let events: Vec<Arc<dyn TEvent>> = vec![Arc::new(OrderCreated), Arc::new(OrderShipped), Arc::new(OrderShipped)];
+		outbox.record_events(&events);
+		outbox
+			.assert_topic_emitted("OrderShipped", 2)
+			.assert_topic_not_emitted("OrderCreated")
+			.assert_payload("OrderShipped", |payload| payload["order_id"] == 7);
+
+		let broker = FakeOutbox::new();
+		let relay = OutboxRelay::new(outbox.clone(), broker);
+		assert_eq!(relay.relay_once().await.unwrap(), 2);
+		assert!(outbox.fetch_unprocessed(10).await.unwrap().is_empty());
+	}
+
+	#[tokio::test]
+	async fn test_context_on_fake_outbox() {
+		use crate::prelude::{Context, ContextManager, TUnitOfWork};
+
+		let outbox: &'static FakeOutbox = Box::leak(Box::new(FakeOutbox::new()));
+		let mut ctx = Context::new(Arc::new(ContextManager::new(outbox)));
+		ctx.begin().await.unwrap();
+		ctx.raise(OrderShipped);
+		ctx.raise(OrderCreated);
+		ctx.commit().await.unwrap();
+
+		ctx.begin().await.unwrap();
+		ctx.raise(OrderShipped);
+		ctx.process_external_events().await.unwrap();
+		ctx.rollback().await.unwrap();
+
+		outbox.assert_topic_emitted("OrderShipped", 1).assert_topic_not_emitted("OrderCreated");
+	}
+
+	#[test]
+	#[should_panic(expected = "expected: OrderShipped x 1, actual: OrderShipped x 0")]
+	fn test_fake_outbox_failure() {
+		FakeOutbox::new().assert_topic_emitted("OrderShipped", 1);
+	}
+}
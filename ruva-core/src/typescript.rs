@@ -0,0 +1,186 @@
+//! TypeScript type definitions for the contracts exposed by the bus.
+//!
+//! Enabled by `typescript` feature. Derive `TTypeScript` on commands, responses and externally notifiable events,
+//! collect them with `typescript_bindings!` and write the result with [write_typescript], for example from a test:
+//!
+//! ```rust,no_run
+//! #[test]
+//! fn export_typescript() {
+//!     ruva::write_typescript("../web/src/bindings.ts", &ruva::typescript_bindings!(CreateOrderBody, OrderResponse, OrderCreated)).unwrap();
+//! }
+//! ```
+
+use std::{
+	collections::{BTreeMap, BTreeSet, HashMap, HashSet, VecDeque},
+	path::Path,
+	rc::Rc,
+	sync::Arc,
+};
+
+use crate::snowflake::SnowFlake;
+
+/// Type that has TypeScript counterpart.
+pub trait TTypeScript {
+	/// Type expression used where the type is referenced. e.g. `number`, `OrderCreated`
+	fn ts_type() -> String;
+
+	/// Standalone declaration such as `export interface OrderCreated { ... }`. `None` for built-in types.
+	fn ts_declaration() -> Option<String> {
+		None
+	}
+}
+
+/// Join declarations into a single module. Types without declaration are skipped.
+pub fn render_typescript(declarations: impl IntoIterator<Item = Option<String>>) -> String {
+	let mut rendered = String::from("// This file is generated by ruva. Do not edit manually.\n");
+	for declaration in declarations.into_iter().flatten() {
+		rendered.push('\n');
+		rendered.push_str(&declaration);
+		rendered.push('\n');
+	}
+	rendered
+}
+
+/// Write generated definitions to `path`. File is left untouched if contents haven't changed so that
+/// front-end watchers are not triggered needlessly.
+pub fn write_typescript(path: impl AsRef<Path>, contents: &str) -> std::io::Result<()> {
+	let path = path.as_ref();
+	if std::fs::read_to_string(path).is_ok_and(|existing| existing == contents) {
+		return Ok(());
+	}
+	if let Some(parent) = path.parent().filter(|parent| !parent.as_os_str().is_empty()) {
+		std::fs::create_dir_all(parent)?;
+	}
+	std::fs::write(path, contents)
+}
+
+macro_rules! impl_ts_primitive {
+	($ts:literal: $($ty:ty),*) => {
+		$(
+			impl TTypeScript for $ty {
+				fn ts_type() -> String {
+					$ts.to_string()
+				}
+			}
+		)*
+	};
+}
+
+impl_ts_primitive!("number": i8, i16, i32, i64, isize, u8, u16, u32, u64, usize, f32, f64);
+impl_ts_primitive!("string": String, str, char, SnowFlake, uuid::Uuid, chrono::NaiveDate, chrono::NaiveDateTime, chrono::NaiveTime);
+impl_ts_primitive!("boolean": bool);
+impl_ts_primitive!("null": ());
+impl_ts_primitive!("unknown": serde_json::Value);
+
+impl<Tz: chrono::TimeZone> TTypeScript for chrono::DateTime<Tz> {
+	fn ts_type() -> String {
+		"string".to_string()
+	}
+}
+
+impl<T: TTypeScript> TTypeScript for Option<T> {
+	fn ts_type() -> String {
+		format!("{} | null", T::ts_type())
+	}
+}
+
+macro_rules! impl_ts_transparent {
+	($($ty:ident),*) => {
+		$(
+			impl<T: TTypeScript + ?Sized> TTypeScript for $ty<T> {
+				fn ts_type() -> String {
+					T::ts_type()
+				}
+			}
+		)*
+	};
+}
+
+impl_ts_transparent!(Box, Arc, Rc);
+
+impl<T: TTypeScript + ?Sized> TTypeScript for &T {
+	fn ts_type() -> String {
+		T::ts_type()
+	}
+}
+
+macro_rules! impl_ts_array {
+	($($ty:ident),*) => {
+		$(
+			impl<T: TTypeScript> TTypeScript for $ty<T> {
+				fn ts_type() -> String {
+					array_of::<T>()
+				}
+			}
+		)*
+	};
+}
+
+impl_ts_array!(Vec, VecDeque, BTreeSet);
+
+impl<T: TTypeScript> TTypeScript for [T] {
+	fn ts_type() -> String {
+		array_of::<T>()
+	}
+}
+
+impl<T: TTypeScript, S> TTypeScript for HashSet<T, S> {
+	fn ts_type() -> String {
+		array_of::<T>()
+	}
+}
+
+impl<K: TTypeScript, V: TTypeScript, S> TTypeScript for HashMap<K, V, S> {
+	fn ts_type() -> String {
+		format!("Record<{}, {}>", K::ts_type(), V::ts_type())
+	}
+}
+
+impl<K: TTypeScript, V: TTypeScript, S> TTypeScript for hashbrown::HashMap<K, V, S> {
+	fn ts_type() -> String {
+		format!("Record<{}, {}>", K::ts_type(), V::ts_type())
+	}
+}
+
+impl<K: TTypeScript, V: TTypeScript> TTypeScript for BTreeMap<K, V> {
+	fn ts_type() -> String {
+		format!("Record<{}, {}>", K::ts_type(), V::ts_type())
+	}
+}
+
+macro_rules! impl_ts_tuple {
+	($($ty:ident),+) => {
+		impl<$($ty: TTypeScript),+> TTypeScript for ($($ty,)+) {
+			fn ts_type() -> String {
+				format!("[{}]", [$($ty::ts_type()),+].join(", "))
+			}
+		}
+	};
+}
+
+impl_ts_tuple!(A);
+impl_ts_tuple!(A, B);
+impl_ts_tuple!(A, B, C);
+impl_ts_tuple!(A, B, C, D);
+
+// `string | null` must be parenthesized before `[]` is attached
+fn array_of<T: TTypeScript>() -> String {
+	let inner = T::ts_type();
+	if inner.contains(['|', '&', ' ']) {
+		format!("({})[]", inner)
+	} else {
+		format!("{}[]", inner)
+	}
+}
+
+#[test]
+fn test_builtin_types() {
+	assert_eq!(<Vec<Option<i64>>>::ts_type(), "(number | null)[]");
+	assert_eq!(<HashMap<String, Vec<SnowFlake>>>::ts_type(), "Record<string, string[]>");
+	assert_eq!(<(bool, Arc<str>)>::ts_type(), "[boolean, string]");
+	assert_eq!(<chrono::DateTime<chrono::Utc>>::ts_type(), "string");
+	assert_eq!(
+		render_typescript([None, Some("export type Id = string;".to_string())]),
+		"// This file is generated by ruva. Do not edit manually.\n\nexport type Id = string;\n"
+	);
+}
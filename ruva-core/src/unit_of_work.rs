@@ -16,7 +16,19 @@
 //!
 //! #### Usage Pattern
 //!
-//! ```rust,no_run
+//! ```rust
+//! # use std::marker::PhantomData;
+//! # use ruva_core::prelude::*;
+//! # pub struct CreateCommand;
+//! # #[derive(Default)]
+//! # pub struct CustomAggregate { id: i64 }
+//! # impl CustomAggregate { fn new(_: CreateCommand) -> Self { Self::default() } }
+//! # pub struct CustomResponse(i64);
+//! # impl From<i64> for CustomResponse { fn from(id: i64) -> Self { Self(id) } }
+//! # type CustomError = BaseError;
+//! # pub trait TCustomRepository {
+//! #     async fn add(&mut self, aggregate: &mut CustomAggregate) -> Result<(), BaseError>;
+//! # }
 //! // Service Handler
 //! pub struct CustomHandler<R> {
 //!     _r: PhantomData<R>,
@@ -43,22 +55,32 @@
 //! ```
 //!
 
-use crate::prelude::BaseError;
+use crate::prelude::{BaseError, CommitStage};
 
 /// Template for Unit of Work
 /// Concrete implementation must implement `_commit` method
 /// If you want to add hooks on events, you can implement `process_internal_events` and `process_external_events`
-
 pub trait TUnitOfWork: Send + Sync {
 	fn begin(&mut self) -> impl std::future::Future<Output = Result<(), BaseError>> + Send;
 
 	// Template method
 	fn commit(&mut self) -> impl std::future::Future<Output = Result<(), BaseError>> + Send {
 		async {
-			self.process_internal_events().await?;
-			self.process_external_events().await?;
-			self._commit().await?;
-			Ok(())
+			let res = async {
+				self.run_stage(CommitStage::Validate).await?;
+				self.process_external_events().await?;
+				self.run_stage(CommitStage::Persist).await?;
+				self._commit().await?;
+				// * Internally notifiable events become visible to the event loop only after commit. See `FlushMode`.
+				self.process_internal_events().await?;
+				self.run_stage(CommitStage::Publish).await
+			}
+			.await;
+			// * Hooks of the stages not reached must not run in the next unit of work, such as retry of the command.
+			if res.is_err() {
+				self.discard_stages();
+			}
+			res
 		}
 	}
 	// Actual commit which concrete implementation must implement
@@ -76,4 +98,10 @@ pub trait TUnitOfWork: Send + Sync {
 	fn process_external_events(&mut self) -> impl std::future::Future<Output = Result<(), BaseError>> + Send {
 		async { Ok(()) }
 	}
+	// Hook. Extension point of each stage of `commit`. See `ContextManager::on_stage`.
+	fn run_stage(&mut self, _stage: CommitStage) -> impl std::future::Future<Output = Result<(), BaseError>> + Send {
+		async { Ok(()) }
+	}
+	// Hook. Called when `commit` fails, to drop what is registered for the stages. Rollback should do the same.
+	fn discard_stages(&mut self) {}
 }
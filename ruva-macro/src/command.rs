@@ -37,11 +37,10 @@ fn craete_into_statement_from_struct_command(original_name: &syn::Ident, body_de
 			named: named
 				.into_iter()
 				.cloned()
-				.map(|f| {
+				.inspect(|f| {
 					// Get type name and identifier for the type
 					idents_in_vec.push(f.ident.clone().unwrap().to_string());
 					types_in_vec.push(get_type_name(&f.ty));
-					f
 				})
 				.filter(|f| !input_required_values.iter().any(|required_f| required_f.ident == f.ident))
 				.map(|mut f| {
@@ -154,6 +153,36 @@ pub fn declare_command(ast: &mut DeriveInput) -> TokenStream {
 	)
 }
 
+pub fn render_command_spec(ast: &DeriveInput) -> syn::Result<TokenStream> {
+	let name = &ast.ident;
+	let (impl_generics, ty_generics, where_clause) = ast.generics.split_for_impl();
+
+	let mut response: Option<syn::Type> = None;
+	let mut error: Option<syn::Type> = None;
+	for attr in ast.attrs.iter().filter(|attr| attr.path().is_ident("command_spec")) {
+		attr.parse_nested_meta(|meta| {
+			if meta.path.is_ident("response") {
+				response = Some(meta.value()?.parse()?);
+			} else if meta.path.is_ident("error") {
+				error = Some(meta.value()?.parse()?);
+			} else {
+				return Err(meta.error("expected `response` or `error`"));
+			}
+			Ok(())
+		})?;
+	}
+	let (Some(response), Some(error)) = (response, error) else {
+		return Err(syn::Error::new_spanned(name, "#[command_spec(response = ..., error = ...)] is required"));
+	};
+
+	Ok(quote!(
+		impl #impl_generics ruva::TCommandSpec for #name #ty_generics #where_clause {
+			type Response = #response;
+			type Error = #error;
+		}
+	))
+}
+
 fn parse_attributes(attrs: &proc_macro::TokenStream) -> (Vec<String>, Vec<String>) {
 	let mut macros_to_inject_to_body = vec!["Debug".to_string(), "ruva::Deserialize".to_string()];
 	let normalized_body_macro = macros_to_inject_to_body.iter().map(|x| x.split("::").last().unwrap().to_string()).collect::<Vec<String>>();
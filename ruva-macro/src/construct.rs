@@ -4,7 +4,7 @@ use proc_macro2::TokenStream;
 /// construct function will take all the fields as arguments and return the struct instance
 /// with #[except] attribute, the field will be excluded from the construct function.
 /// With the use of #[except] attribute, the struct must derive Default to be able to construct the struct
-/// ```rust
+/// ```rust,ignore
 /// #[derive(Default,TConstruct)]
 /// struct TestStruct {
 /// value: i32,
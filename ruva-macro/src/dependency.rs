@@ -0,0 +1,36 @@
+use proc_macro::TokenStream;
+use syn::{parse_quote, ItemTrait};
+
+pub(crate) fn render_dependency(mut item: ItemTrait) -> TokenStream {
+	let name = item.ident.clone();
+	item.supertraits.push(parse_quote!(Send));
+	item.supertraits.push(parse_quote!(Sync));
+
+	// automock generates `self` in method bodies, so it must carry the span of the trait the user wrote.
+	let automock = if cfg!(feature = "mock") {
+		quote_spanned!(name.span()=> #[cfg_attr(test, mockall::automock)])
+	} else {
+		quote!()
+	};
+
+	quote!(
+		#automock
+		#item
+
+		impl dyn #name {
+			pub fn register(dependency: impl #name + 'static) {
+				::ruva::register_dependency::<dyn #name>(::std::sync::Arc::new(dependency));
+			}
+
+			/// Used instead of the registered one in record-only event handlers
+			pub fn register_recorder(recorder: impl #name + 'static) {
+				::ruva::handler_sandbox().register_recorder::<dyn #name>(::std::sync::Arc::new(recorder));
+			}
+
+			pub fn resolve(context_manager: &::ruva::ContextManager) -> ::std::sync::Arc<dyn #name> {
+				context_manager.resolve::<dyn #name>()
+			}
+		}
+	)
+	.into()
+}
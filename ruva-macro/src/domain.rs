@@ -5,7 +5,7 @@ use syn::{parse::Parser, parse_macro_input, punctuated::Punctuated, token::Comma
 
 use crate::{
 	helpers::{derive_helpers::add_derive_macros, generic_helpers::add_aggregate_generic_defaults},
-	utils::{check_if_field_has_attribute, extract_field_names, locate_crate_on_derive_macro, remove_fields_based_on_field_name, skip_over_attributes, sort_macros_to_inject},
+	utils::{check_if_field_has_attribute, derives_trait, extract_field_names, locate_crate_on_derive_macro, remove_fields_based_on_field_name, skip_over_attributes, sort_macros_to_inject},
 };
 
 pub(crate) fn render_aggregate(input: TokenStream, attrs: TokenStream) -> TokenStream {
@@ -15,6 +15,10 @@ pub(crate) fn render_aggregate(input: TokenStream, attrs: TokenStream) -> TokenS
 	let mut ast = parse_macro_input!(input as DeriveInput);
 
 	let name = ast.ident.clone();
+	let auto_event = match take_auto_event(&mut ast) {
+		Ok(auto_event) => auto_event,
+		Err(err) => return err.into_compile_error().into(),
+	};
 
 	add_aggregate_generic_defaults(&mut ast.generics);
 	add_derive_macros(&mut ast, &macros_to_inject);
@@ -23,12 +27,18 @@ pub(crate) fn render_aggregate(input: TokenStream, attrs: TokenStream) -> TokenS
 
 	let crates = locate_crate_on_derive_macro(&ast);
 
-	let adapter_quote = create_struct_adapter_quote(&ast, true);
+	let versioned = get_versioned_impl(&ast);
+	let adapter_quote = create_struct_adapter_quote(&ast, true, auto_event.is_some());
 
-	let setters = set_entity_fields(&mut ast.data, true);
+	let setters = set_entity_fields(&mut ast.data, true, auto_event.is_some());
+	let (mutation_events, auto_event_struct) = match auto_event {
+		Some(auto_event) => render_auto_event(&ast, &auto_event),
+		None => (quote!(), quote!()),
+	};
 
 	quote!(
 		#ast
+		#auto_event_struct
 		impl #impl_generics  #crates::TAggregate for #name #ty_generics #where_clause {
 			// type Identifier = #aggregate_identifier_type;
 
@@ -42,6 +52,10 @@ pub(crate) fn render_aggregate(input: TokenStream, attrs: TokenStream) -> TokenS
 				tracing::info!("event raised! {:?}", event.metadata());
 				self.events.push_back(event)
 			}
+			fn dirty_fields(&self) -> Vec<&'static str> {
+				self.dirty_fields.iter().copied().collect()
+			}
+			#mutation_events
 		}
 
 		impl #impl_generics #name #ty_generics #where_clause{
@@ -50,10 +64,39 @@ pub(crate) fn render_aggregate(input: TokenStream, attrs: TokenStream) -> TokenS
 
 
 		#adapter_quote
+		#versioned
 	)
 	.into()
 }
 
+// `#[version] version: i64` implements `TVersioned` so that update of the aggregate can be guarded by the version it was loaded at.
+fn get_versioned_impl(ast: &DeriveInput) -> proc_macro2::TokenStream {
+	let syn::Data::Struct(DataStruct {
+		fields: syn::Fields::Named(fields), ..
+	}) = &ast.data
+	else {
+		return quote!();
+	};
+	let versions = fields.named.iter().filter(|f| f.attrs.iter().any(|attr| attr.path().is_ident("version"))).collect::<Vec<_>>();
+	let field = match versions.as_slice() {
+		[] => return quote!(),
+		[field] => field.ident.as_ref().unwrap(),
+		[_, duplicated, ..] => return syn::Error::new_spanned(duplicated, "Only one field can be marked with #[version]").into_compile_error(),
+	};
+	let name = &ast.ident;
+	let (impl_generics, ty_generics, where_clause) = ast.generics.split_for_impl();
+	quote!(
+		impl #impl_generics ::ruva::TVersioned for #name #ty_generics #where_clause {
+			fn version(&self) -> i64 {
+				self.#field as i64
+			}
+			fn bump_version(&mut self) {
+				self.#field += 1;
+			}
+		}
+	)
+}
+
 pub(crate) fn render_entity_token(input: TokenStream, attrs: TokenStream) -> TokenStream {
 	let mut macros_to_inject = vec!["ruva::Serialize".to_string(), "Debug".to_string(), "Default".to_string()];
 	sort_macros_to_inject(&mut macros_to_inject, attrs);
@@ -63,9 +106,9 @@ pub(crate) fn render_entity_token(input: TokenStream, attrs: TokenStream) -> Tok
 	let name = &ast.ident;
 	let generics = &ast.generics;
 	let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
-	let adapter_quote = create_struct_adapter_quote(&ast, false);
+	let adapter_quote = create_struct_adapter_quote(&ast, false, false);
 
-	let setters = set_entity_fields(&mut ast.data, false);
+	let setters = set_entity_fields(&mut ast.data, false, false);
 
 	quote!(
 		#ast
@@ -80,14 +123,18 @@ pub(crate) fn render_entity_token(input: TokenStream, attrs: TokenStream) -> Tok
 	.into()
 }
 
-pub(crate) fn set_entity_fields(input_data: &mut syn::Data, for_aggregate: bool) -> proc_macro2::TokenStream {
+pub(crate) fn set_entity_fields(input_data: &mut syn::Data, for_aggregate: bool, track_changes: bool) -> proc_macro2::TokenStream {
 	if let syn::Data::Struct(DataStruct {
 		fields: syn::Fields::Named(ref mut fields),
 		..
 	}) = input_data
 	{
+		let reference_loaders = get_reference_loaders(fields);
 		fields.named.iter_mut().for_each(|f| {
 			skip_over_attributes(f, "adapter_ignore");
+			skip_over_attributes(f, "encrypted_column");
+			skip_over_attributes(f, "reference");
+			skip_over_attributes(f, "version");
 		});
 
 		if fields.named.iter().any(|x| x.ident.as_ref().unwrap() == "is_existing") {
@@ -96,6 +143,10 @@ pub(crate) fn set_entity_fields(input_data: &mut syn::Data, for_aggregate: bool)
 		if fields.named.iter().any(|x| x.ident.as_ref().unwrap() == "is_updated") {
 			panic!("is_updated field not injectable! Perhaps it's duplicated?");
 		}
+		if fields.named.iter().any(|x| x.ident.as_ref().unwrap() == "dirty_fields") {
+			panic!("dirty_fields field not injectable! Perhaps it's duplicated?");
+		}
+		let trackable_fields = fields.named.iter().map(|f| f.ident.as_ref().unwrap().to_string()).collect::<Vec<_>>();
 
 		fields.named.extend([
 			syn::Field::parse_named
@@ -110,8 +161,29 @@ pub(crate) fn set_entity_fields(input_data: &mut syn::Data, for_aggregate: bool)
 				   pub(crate) is_updated: bool
 				})
 				.unwrap(),
+			syn::Field::parse_named
+				.parse2(quote! {
+				   #[serde(skip_deserializing, skip_serializing)]
+				   pub(crate) dirty_fields: ::std::collections::BTreeSet<&'static str>
+				})
+				.unwrap(),
 		]);
 
+		if track_changes {
+			if fields.named.iter().any(|x| x.ident.as_ref().unwrap() == "field_changes") {
+				panic!("field_changes field not injectable! Perhaps it's duplicated?");
+			}
+			// Values of fields before the first change, keyed by field name
+			fields.named.push(
+				syn::Field::parse_named
+					.parse2(quote! {
+					   #[serde(skip_deserializing, skip_serializing)]
+					   pub(crate) field_changes: ::std::collections::BTreeMap<&'static str, ::ruva::serde_json::Value>
+					})
+					.unwrap(),
+			);
+		}
+
 		if for_aggregate {
 			if fields.named.iter().any(|x| x.ident.as_ref().unwrap() == "events") {
 				panic!("events field not injectable! Perhaps it's duplicated?");
@@ -126,17 +198,195 @@ pub(crate) fn set_entity_fields(input_data: &mut syn::Data, for_aggregate: bool)
 					.unwrap(),
 			)
 		}
+		let setters = get_setters(input_data, &trackable_fields, track_changes);
+		quote!(#setters #reference_loaders)
 	} else {
 		if for_aggregate {
 			panic!("[aggregate] can be attached only to struct")
 		}
 		panic!("[entity] can be attached only to struct")
 	}
+}
+
+pub(crate) struct AutoEvent {
+	suffix: Ident,
+	identifier: Option<Ident>,
+}
 
-	get_setters(input_data)
+// `#[auto_event(Updated)]` or `#[auto_event(Updated, identifier = id)]` put under `#[aggregate]`
+fn take_auto_event(ast: &mut DeriveInput) -> syn::Result<Option<AutoEvent>> {
+	let Some(idx) = ast.attrs.iter().position(|attr| attr.path().is_ident("auto_event")) else {
+		return Ok(None);
+	};
+	let attr = ast.attrs.remove(idx);
+	let args = attr.parse_args_with(Punctuated::<syn::Meta, Comma>::parse_terminated)?;
+	let mut args = args.into_iter();
+	let suffix = match args.next() {
+		Some(syn::Meta::Path(path)) if path.get_ident().is_some() => path.get_ident().unwrap().clone(),
+		_ => return Err(syn::Error::new_spanned(&attr, "#[auto_event(..)] expects event suffix first. Example: #[auto_event(Updated)]")),
+	};
+	let identifier = match args.next() {
+		Some(syn::Meta::NameValue(name_value)) if name_value.path.is_ident("identifier") => match name_value.value {
+			syn::Expr::Path(syn::ExprPath { path, .. }) if path.get_ident().is_some() => Some(path.get_ident().unwrap().clone()),
+			value => return Err(syn::Error::new_spanned(value, "identifier expects field name. Example: identifier = id")),
+		},
+		None => None,
+		Some(other) => return Err(syn::Error::new_spanned(other, "Unknown argument of #[auto_event(..)]")),
+	};
+	Ok(Some(AutoEvent { suffix, identifier }))
 }
 
-fn get_setters(data: &Data) -> proc_macro2::TokenStream {
+// Event carrying old and new values of the fields changed through setters, and `TAggregate::mutation_events` that raises it.
+// Externally notifiable only when identifier of the aggregate is given, as outbox needs aggregate id.
+fn render_auto_event(ast: &DeriveInput, auto_event: &AutoEvent) -> (proc_macro2::TokenStream, proc_macro2::TokenStream) {
+	let name = &ast.ident;
+	let vis = &ast.vis;
+	let event = Ident::new(&format!("{}{}", name, auto_event.suffix), auto_event.suffix.span());
+	let fields = match &ast.data {
+		Data::Struct(DataStruct {
+			fields: syn::Fields::Named(fields), ..
+		}) => fields,
+		_ => panic!("[aggregate] can be attached only to struct"),
+	};
+	let tracked = fields
+		.named
+		.iter()
+		.filter_map(|f| f.ident.as_ref())
+		.filter(|ident| !["is_existing", "is_updated", "dirty_fields", "field_changes", "events"].contains(&ident.to_string().as_str()))
+		.map(|ident| {
+			let field_name = ident.to_string();
+			quote!(#field_name => ::ruva::serde_json::to_value(&self.#ident).unwrap_or_default(),)
+		})
+		.collect::<Vec<_>>();
+
+	let (notifiability, identifier_field, identifier_init) = match &auto_event.identifier {
+		Some(identifier) => {
+			let Some(field) = fields.named.iter().find(|f| f.ident.as_ref() == Some(identifier)) else {
+				return (
+					quote!(),
+					syn::Error::new_spanned(identifier, format!("{} has no field named {}", name, identifier)).into_compile_error(),
+				);
+			};
+			let ty = &field.ty;
+			(
+				quote!(#[internally_notifiable] #[externally_notifiable(#name)]),
+				quote!(#[identifier] pub #identifier: #ty,),
+				quote!(#identifier: self.#identifier.clone(),),
+			)
+		}
+		None => (quote!(#[internally_notifiable]), quote!(), quote!()),
+	};
+	let doc = format!("Fields of `{}` changed through setters, raised by `#[event_hook]` on repository update", name);
+
+	let mutation_events = quote!(
+		fn mutation_events(&mut self) -> ::std::collections::VecDeque<::std::sync::Arc<dyn ::ruva::TEvent>> {
+			let changes = ::std::mem::take(&mut self.field_changes)
+				.into_iter()
+				.map(|(field, old)| {
+					let new = match field {
+						#(#tracked)*
+						_ => ::ruva::serde_json::Value::Null,
+					};
+					::ruva::FieldChange { field: field.to_string(), old, new }
+				})
+				// Set back to what it was
+				.filter(|change| change.old != change.new)
+				.collect::<Vec<_>>();
+			if changes.is_empty() {
+				return ::std::collections::VecDeque::new();
+			}
+			::std::collections::VecDeque::from([::std::sync::Arc::new(#event { #identifier_init changes }) as ::std::sync::Arc<dyn ::ruva::TEvent>])
+		}
+	);
+	let event_struct = quote!(
+		#[doc = #doc]
+		#[derive(Debug, Clone, ::ruva::Serialize, ::ruva::Deserialize, ::ruva::TEvent)]
+		#notifiability
+		#vis struct #event {
+			#identifier_field
+			pub changes: Vec<::ruva::FieldChange>,
+		}
+	);
+	(mutation_events, event_struct)
+}
+
+// `#[reference(Customer)] customer_id: i64` generates `load_customer(&self, uow)` that loads `Customer` through `TLoadAggregate`.
+// Field must hold the id, optionally wrapped in `Option`, never the referenced aggregate itself.
+fn get_reference_loaders(fields: &syn::FieldsNamed) -> proc_macro2::TokenStream {
+	let loaders = fields.named.iter().filter_map(|f| {
+		let attr = f.attrs.iter().find(|attr| attr.path().is_ident("reference"))?;
+		let ident = f.ident.as_ref().unwrap();
+		let referenced = match attr.parse_args::<Type>() {
+			Ok(referenced) => referenced,
+			Err(err) => return Some(err.into_compile_error()),
+		};
+		let referenced_name = referenced.to_token_stream().to_string();
+		let id_type = optional_inner_type(&f.ty);
+		if id_type.unwrap_or(&f.ty).to_token_stream().to_string() == referenced_name {
+			return Some(syn::Error::new_spanned(&f.ty, format!("#[reference({0})] field must hold the id of {0}, not {0} itself", referenced_name)).into_compile_error());
+		}
+
+		let name = ident.to_string();
+		let loader = Ident::new(&format!("load_{}", name.strip_suffix("_id").unwrap_or(&name)), ident.span());
+		let doc = format!("Load `{}` referenced by `{}`", referenced_name, name);
+		Some(match id_type {
+			Some(id_type) => quote!(
+				#[doc = #doc]
+				pub async fn #loader<U: ruva::TLoadAggregate<#referenced, #id_type>>(&self, uow: &mut U) -> Result<Option<#referenced>, ruva::BaseError> {
+					match self.#ident.as_ref() {
+						Some(id) => uow.load_aggregate(id).await.map(Some),
+						None => Ok(None),
+					}
+				}
+			),
+			None => {
+				let id_type = &f.ty;
+				quote!(
+					#[doc = #doc]
+					pub async fn #loader<U: ruva::TLoadAggregate<#referenced, #id_type>>(&self, uow: &mut U) -> Result<#referenced, ruva::BaseError> {
+						uow.load_aggregate(&self.#ident).await
+					}
+				)
+			}
+		})
+	});
+	quote!(#(#loaders)*)
+}
+
+/// Field given by `#[encrypted_column(id = field)]`, `id` if not given
+fn encrypted_column_id(field: &Field) -> syn::Result<String> {
+	let attr = field.attrs.iter().find(|attr| attr.path().is_ident("encrypted_column")).expect("Checked by the caller");
+	match &attr.meta {
+		syn::Meta::Path(_) => Ok("id".to_string()),
+		_ => {
+			let name_value = attr.parse_args::<syn::MetaNameValue>()?;
+			match (&name_value.value, name_value.path.is_ident("id")) {
+				(syn::Expr::Path(path), true) if path.path.get_ident().is_some() => Ok(path.path.get_ident().unwrap().to_string()),
+				_ => Err(syn::Error::new_spanned(
+					name_value,
+					"encrypted_column expects field identifying the aggregate. Example: #[encrypted_column(id = customer_no)]",
+				)),
+			}
+		}
+	}
+}
+
+/// `T` of `Option<T>`
+fn optional_inner_type(ty: &Type) -> Option<&Type> {
+	let Type::Path(type_path) = ty else { return None };
+	let segment = type_path.path.segments.last()?;
+	if segment.ident != "Option" {
+		return None;
+	}
+	let syn::PathArguments::AngleBracketed(args) = &segment.arguments else { return None };
+	match args.args.first()? {
+		syn::GenericArgument::Type(inner) => Some(inner),
+		_ => None,
+	}
+}
+
+// Setters of fields given in `trackable_fields` mark the field dirty so that repository can update only changed columns
+fn get_setters(data: &Data, trackable_fields: &[String], track_changes: bool) -> proc_macro2::TokenStream {
 	let field_idents: Vec<Field> = match data {
 		Data::Struct(data) => data.fields.clone().into_iter().filter_map(Some).collect(),
 		_ => panic!("Only Struct Is supported"),
@@ -145,17 +395,39 @@ fn get_setters(data: &Data) -> proc_macro2::TokenStream {
 	for f in field_idents {
 		let ident = f.ident.unwrap();
 		let ty = f.ty.to_token_stream().to_string();
+		let mark_dirty = if trackable_fields.contains(&ident.to_string()) {
+			format!("self.dirty_fields.insert(\"{}\");", ident)
+		} else {
+			String::new()
+		};
+		let record_change = if track_changes && trackable_fields.contains(&ident.to_string()) {
+			format!(
+				"self.field_changes.entry(\"{0}\").or_insert_with(|| ::ruva::serde_json::to_value(&self.{0}).unwrap_or_default());",
+				ident
+			)
+		} else {
+			String::new()
+		};
 		let code = format!(
-			"pub fn set_{}(&mut self, {}:impl core::convert::Into<{}>){{self.{}={}.into();self.is_updated=true}}",
-			ident, ident, ty, ident, ident
+			"pub fn set_{}(&mut self, {}:impl core::convert::Into<{}>){{{}self.{}={}.into();self.is_updated=true;{}}}",
+			ident, ident, ty, record_change, ident, ident, mark_dirty
 		);
 		quotes.push(code);
 	}
+	quotes.push(
+		"
+		/// Fields changed through setters since the last load or `clear_dirty_fields` call, in field name order.
+		pub fn dirty_fields(&self) -> Vec<&'static str> { self.dirty_fields.iter().copied().collect() }
+		pub fn is_dirty(&self, field: &str) -> bool { self.dirty_fields.contains(field) }
+		pub fn clear_dirty_fields(&mut self) { self.dirty_fields.clear() }
+		"
+		.to_string(),
+	);
 	let joined: proc_macro2::TokenStream = quotes.join(" ").parse().unwrap();
 	joined
 }
 
-pub fn create_struct_adapter_quote(input: &DeriveInput, for_aggregate: bool) -> proc_macro2::TokenStream {
+pub fn create_struct_adapter_quote(input: &DeriveInput, for_aggregate: bool, track_changes: bool) -> proc_macro2::TokenStream {
 	let aggregate_name = input.ident.clone();
 	let mut generics = input.generics.clone();
 	add_aggregate_generic_defaults(&mut generics);
@@ -166,6 +438,9 @@ pub fn create_struct_adapter_quote(input: &DeriveInput, for_aggregate: bool) ->
 	adapter_input.ident = adapter_name.clone();
 
 	let mut fields_to_ignore: Vec<String> = vec![];
+	// Encrypted field with the field identifying the aggregate, which its ciphertext is bound to
+	let mut fields_to_encrypt: Vec<(String, String)> = vec![];
+	let mut errors: Vec<syn::Error> = vec![];
 
 	if let syn::Data::Struct(DataStruct {
 		fields: syn::Fields::Named(ref mut fields),
@@ -173,6 +448,15 @@ pub fn create_struct_adapter_quote(input: &DeriveInput, for_aggregate: bool) ->
 	}) = &mut adapter_input.data
 	{
 		fields.named.iter_mut().for_each(|f: &mut Field| {
+			if let Some(encrypted_field) = check_if_field_has_attribute(f, "encrypted_column") {
+				match encrypted_column_id(f) {
+					Ok(id) => fields_to_encrypt.push((encrypted_field, id)),
+					Err(err) => errors.push(err),
+				}
+				skip_over_attributes(f, "encrypted_column");
+			}
+			skip_over_attributes(f, "reference");
+			skip_over_attributes(f, "version");
 			if let Some(ignorable_field) = check_if_field_has_attribute(f, "adapter_ignore") {
 				// if the field's type is generic, skip over
 
@@ -185,19 +469,48 @@ pub fn create_struct_adapter_quote(input: &DeriveInput, for_aggregate: bool) ->
 		remove_fields_based_on_field_name(fields, &fields_to_ignore);
 	}
 
+	let field_names = extract_field_names(input);
+	for (field_name, id) in fields_to_encrypt.iter() {
+		if !field_names.contains(id) || fields_to_ignore.contains(id) {
+			let message = format!(
+				"Encrypted column `{}` is bound to `{}` field identifying the aggregate, which the adapter doesn't have. Give another one with `#[encrypted_column(id = field)]`",
+				field_name, id
+			);
+			errors.push(syn::Error::new_spanned(&input.ident, message));
+		}
+	}
+	if !errors.is_empty() {
+		return errors.into_iter().map(syn::Error::into_compile_error).collect();
+	}
+
 	let mut aggregates_fields: Vec<String> = vec![];
 	let mut adapter_fields: Vec<String> = vec![];
-
-	extract_field_names(input).into_iter().for_each(|field_name| {
+	let mut adapter_fields_by_ref: Vec<String> = vec![];
+
+	// Encrypted fields come first, so that they borrow the id before it is moved
+	for (field_name, id) in fields_to_encrypt.iter() {
+		// Adapter holds ciphertext while aggregate holds plaintext
+		let args = format!("&value.{}, \"{}\", \"{}\", &value.{}", field_name, aggregate_name, field_name, id);
+		adapter_fields.push(format!("{}: ruva::encrypt_column({})?", field_name, args));
+		adapter_fields_by_ref.push(format!("{}: ruva::encrypt_column({})?", field_name, args));
+		aggregates_fields.push(format!("{}: ruva::decrypt_column({})?", field_name, args));
+	}
+	field_names.into_iter().for_each(|field_name| {
+		let is_encrypted = fields_to_encrypt.iter().any(|(encrypted, _)| *encrypted == field_name);
 		// ignorable field means that the field is not compatible with adapter
-		if !fields_to_ignore.contains(&field_name) {
+		if !is_encrypted && !fields_to_ignore.contains(&field_name) {
 			adapter_fields.push(format!("{}: value.{}", field_name, field_name));
+			adapter_fields_by_ref.push(format!("{}: value.{}.clone()", field_name, field_name));
 			aggregates_fields.push(format!("{}: value.{}", field_name, field_name));
 		}
 	});
 
 	aggregates_fields.push("is_existing: true".to_string());
 	aggregates_fields.push("is_updated: false".to_string());
+	aggregates_fields.push("dirty_fields: ::std::collections::BTreeSet::new()".to_string());
+	if track_changes {
+		aggregates_fields.push("field_changes: ::std::collections::BTreeMap::new()".to_string());
+	}
 
 	// ! Event field is only for aggregate
 	if for_aggregate {
@@ -215,29 +528,122 @@ pub fn create_struct_adapter_quote(input: &DeriveInput, for_aggregate: bool) ->
 
 	let adapter_fields: proc_macro2::TokenStream = adapter_fields.parse().unwrap();
 
+	// Encryption and decryption may fail, on a corrupt row or a rotated key for example, so conversions become `TryFrom`.
+	let conversion = |source: proc_macro2::TokenStream, target: proc_macro2::TokenStream, where_clause: Option<&syn::WhereClause>, fields: proc_macro2::TokenStream| match fields_to_encrypt.is_empty()
+	{
+		true => quote!(
+			From<#source> for #target #where_clause{
+				fn from(value: #source) -> #target{
+					Self{
+						#fields
+					}
+				}
+			}
+		),
+		false => quote!(
+			TryFrom<#source> for #target #where_clause{
+				type Error = ruva::BaseError;
+				fn try_from(value: #source) -> Result<#target, ruva::BaseError>{
+					Ok(Self{
+						#fields
+					})
+				}
+			}
+		),
+	};
+
+	// Non-consuming conversion needs every field to be cloned, so it is generated only when the struct derives `Clone`.
+	let from_ref_quote = if derives_trait(input, "Clone") {
+		let adapter_fields_by_ref: proc_macro2::TokenStream = adapter_fields_by_ref.join(",").parse().unwrap();
+		let (impl_aggregate_generics, ty_aggregate_generics, where_aggregate_clause) = input.generics.split_for_impl();
+		let (_, ty_adapter_generics, _) = generics.split_for_impl();
+		let conversion = conversion(
+			quote!(&#aggregate_name #ty_aggregate_generics),
+			quote!(#adapter_name #ty_adapter_generics),
+			where_aggregate_clause,
+			adapter_fields_by_ref,
+		);
+		quote!(impl #impl_aggregate_generics #conversion)
+	} else {
+		quote!()
+	};
+
+	let partial_adapter_quote = create_partial_adapter_quote(input, &adapter_input, &generics);
+
 	adapter_input.generics = generics.clone();
 
 	let (impl_adapter_generics, ty_adapter_generics, _where_adapter_clause) = generics.split_for_impl();
 
 	let (impl_aggregate_generics, ty_aggregate_generics, where_aggregate_clause) = input.generics.split_for_impl();
 
+	let to_aggregate = conversion(
+		quote!(#adapter_name #ty_adapter_generics),
+		quote!(#aggregate_name #impl_aggregate_generics),
+		where_aggregate_clause,
+		aggregates_fields,
+	);
+	let to_adapter = conversion(
+		quote!(#aggregate_name #ty_aggregate_generics),
+		quote!(#adapter_name #impl_adapter_generics),
+		where_aggregate_clause,
+		adapter_fields,
+	);
+
 	quote!(
 
 		#adapter_input
 
-		impl #impl_aggregate_generics From<#adapter_name #ty_adapter_generics> for #aggregate_name #impl_aggregate_generics #where_aggregate_clause{
-			fn from(value: #adapter_name #ty_adapter_generics) -> #aggregate_name #impl_aggregate_generics {
-				Self{
-					#aggregates_fields
-				}
-			}
+		impl #impl_aggregate_generics #to_aggregate
+
+		impl #impl_aggregate_generics #to_adapter
+
+		#from_ref_quote
+
+		#partial_adapter_quote
+	)
+}
+
+/// `{name}PartialAdapter` has every field of adapter as `Option` for PATCH-style update.
+/// `apply_to` sets only the given fields through setters, so that they are marked dirty.
+fn create_partial_adapter_quote(input: &DeriveInput, adapter_input: &DeriveInput, adapter_generics: &Generics) -> proc_macro2::TokenStream {
+	// Partial adapter can't bind generics ignored from adapter, so it is not generated in that case.
+	if adapter_generics.params.len() != input.generics.params.len() {
+		return quote!();
+	}
+	let syn::Data::Struct(DataStruct {
+		fields: syn::Fields::Named(fields), ..
+	}) = &adapter_input.data
+	else {
+		return quote!();
+	};
+
+	let aggregate_name = &input.ident;
+	let partial_name = Ident::new(&(input.ident.to_string() + "PartialAdapter"), proc_macro2::Span::call_site());
+	let vis = &input.vis;
+	let idents = fields.named.iter().map(|f| f.ident.clone().unwrap()).collect::<Vec<_>>();
+	let field_vis = fields.named.iter().map(|f| &f.vis);
+	let types = fields.named.iter().map(|f| &f.ty);
+	let setters = idents.iter().map(|ident| Ident::new(&format!("set_{}", ident), proc_macro2::Span::call_site()));
+
+	let (impl_generics, ty_generics, where_clause) = adapter_generics.split_for_impl();
+	let (_, ty_aggregate_generics, _) = input.generics.split_for_impl();
+
+	quote!(
+		#[derive(Debug, Default, ruva::Serialize, ruva::Deserialize)]
+		#vis struct #partial_name #impl_generics #where_clause {
+			#(
+				#[serde(default, skip_serializing_if = "Option::is_none")]
+				#field_vis #idents: ::std::option::Option<#types>,
+			)*
 		}
 
-		impl #impl_aggregate_generics From<#aggregate_name #ty_aggregate_generics> for #adapter_name #impl_adapter_generics #where_aggregate_clause{
-			fn from(value: #aggregate_name #ty_aggregate_generics) -> #adapter_name #ty_adapter_generics{
-				Self{
-					#adapter_fields
-				}
+		impl #impl_generics #partial_name #ty_generics #where_clause {
+			pub fn apply_to(self, value: &mut #aggregate_name #ty_aggregate_generics) {
+				#(
+					if let Some(field) = self.#idents {
+						value.#setters(field);
+					}
+				)*
 			}
 		}
 	)
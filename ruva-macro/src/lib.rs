@@ -1,32 +1,38 @@
-use message::{extract_externally_notifiable_event_req, render_event_visibility, render_message_token};
+use message::{extract_externally_notifiable_event_req, render_event_visibility, render_flush_mode, render_message_token, render_versioned_metadata};
 use proc_macro::TokenStream;
-use syn::{parse_macro_input, DeriveInput, ItemFn};
+use syn::{parse_macro_input, DeriveInput};
 
 #[macro_use]
 extern crate quote;
 
 mod command;
 mod construct;
+mod dependency;
 mod domain;
 mod handler;
 mod helpers;
 mod message;
 mod message_handler;
 mod result;
+mod topics;
+#[cfg(feature = "typescript")]
+mod typescript;
 mod utils;
+mod validate;
 
-#[proc_macro_derive(TEvent, attributes(internally_notifiable, externally_notifiable, identifier))]
+#[proc_macro_derive(TEvent, attributes(internally_notifiable, externally_notifiable, identifier, flush_immediately, event_version, publish_class))]
 pub fn message_derive(attr: TokenStream) -> TokenStream {
 	let mut ast: DeriveInput = syn::parse(attr.clone()).unwrap();
-	let externally_notifiable_event_req = extract_externally_notifiable_event_req(&mut ast);
-	let visibilities = render_event_visibility(&ast);
+	let externally_notifiable_event_req = extract_externally_notifiable_event_req(&mut ast).or_else(|| render_versioned_metadata(&ast).map(|metadata| (metadata, Default::default())));
+	let mut visibilities = render_event_visibility(&ast);
+	visibilities.push(render_flush_mode(&ast));
 
 	render_message_token(&ast, visibilities, externally_notifiable_event_req).into()
 }
 
 /// Define Aggregate root
 /// ## Example
-/// ```rust,no_run
+/// ```rust,ignore
 /// #[aggregate]
 /// pub struct TestAggregate {
 ///     pub(crate) age: i64,
@@ -41,7 +47,7 @@ pub fn message_derive(attr: TokenStream) -> TokenStream {
 /// }
 /// ```
 ///
-/// ```rust,no_run
+/// ```rust,ignore
 /// #[aggregate]
 /// pub struct TestAggregate {
 ///     pub(crate) age: i64,
@@ -50,7 +56,7 @@ pub fn message_derive(attr: TokenStream) -> TokenStream {
 /// ```
 ///
 /// Likewise, not specifying `identifier` will also error out
-/// ```rust,no_run
+/// ```rust,ignore
 /// #[aggregate]
 /// pub struct TestAggregate {
 ///     pub(crate) age: i64,
@@ -59,7 +65,7 @@ pub fn message_derive(attr: TokenStream) -> TokenStream {
 /// ```
 ///
 /// `{your aggregate name}Adapter` will be generated automatically so you can use it to adapt it to database
-/// ```rust,no_run
+/// ```rust,ignore
 /// #[aggregate]
 /// pub struct AggregateStruct {
 ///     #[adapter_ignore]
@@ -79,7 +85,7 @@ pub fn message_derive(attr: TokenStream) -> TokenStream {
 ///
 /// ## Automatic derive macro
 /// `#[derive(Default, Debug, Serialize, Deserialize)]` will be automatically added to the struct.
-/// ```rust,no_run
+/// ```rust,ignore
 /// #[aggregate]
 /// pub struct AggregateStruct {
 ///    #[adapter_ignore]
@@ -92,7 +98,7 @@ pub fn message_derive(attr: TokenStream) -> TokenStream {
 /// Even if you add `Default`, `Debug`, `Serialize`, `Deserialize` there won't be any conflict.
 ///
 /// Conversion is automatically done as follows:
-/// ```rust,no_run
+/// ```rust,ignore
 /// let aggregate = AggregateStruct {
 ///         name: "migo".into(),
 ///         some_other_field: 2,
@@ -108,7 +114,7 @@ pub fn message_derive(attr: TokenStream) -> TokenStream {
 /// ```
 ///
 /// Generic can also be used for aggregate:
-/// ```rust,no_run
+/// ```rust,ignore
 /// #[derive(Default, Debug, Serialize, Deserialize)]
 /// struct Unset;
 ///
@@ -151,6 +157,96 @@ pub fn message_derive(attr: TokenStream) -> TokenStream {
 /// assert_eq!(my_int32_struct.do_something_with_i32(), i32::default());
 ///
 /// ```
+///
+/// ## Dirty field tracking
+/// Generated setters record changed fields so that repository can update only changed columns.
+/// ```rust,ignore
+/// let mut aggregate = AggregateStruct::default();
+/// aggregate.set_name("migo");
+/// assert_eq!(aggregate.dirty_fields(), vec!["name"]);
+/// let set_clause = ruva::update_set_clause(&aggregate.dirty_fields(), 2); // "name = $2"
+/// aggregate.clear_dirty_fields();
+/// ```
+///
+/// ## Event on update
+/// `#[auto_event(Updated)]` generates `{your aggregate name}Updated` event with old and new values of the fields changed through setters.
+/// It is raised by `#[event_hook]` of the repository method the aggregate is given to. Fields set back to what they were are left out.
+/// With `identifier = ..`, the event carries the field as its identifier and is also externally notifiable.
+/// Every field must be `Serialize`.
+/// ```rust,ignore
+/// #[aggregate]
+/// #[auto_event(Updated, identifier = id)]
+/// pub struct Order {
+///     id: i64,
+///     amount: i64,
+/// }
+///
+/// order.set_amount(30);
+/// ctx.update(&mut order).await?; // OrderUpdated { id: 1, changes: [FieldChange { field: "amount", old: 10, new: 30 }] }
+/// ```
+///
+/// ## Encrypted column
+/// `String` field marked with `#[encrypted_column]` is encrypted when converted to adapter and decrypted when converted back,
+/// using key provider registered with `ruva::set_key_provider`. Domain code keeps dealing with plaintext.
+/// As encryption and decryption may fail, conversions of the aggregate with encrypted column are `TryFrom` with `BaseError`.
+/// Ciphertext is bound to the aggregate name, the column and the `id` field of the aggregate, so it can't be copied to another row.
+/// Give another field with `#[encrypted_column(id = field)]` when the aggregate is identified otherwise.
+/// ```rust,ignore
+/// ruva::set_key_provider(RingKeyProvider::new(1, key));
+///
+/// #[aggregate]
+/// pub struct Customer {
+///     id: i64,
+///     #[encrypted_column]
+///     ssn: String,
+/// }
+/// let adapter = CustomerAdapter::try_from(customer)?; // adapter.ssn is base64 encoded ciphertext
+/// ```
+///
+/// ## Conversion by reference and partial update
+/// When the struct derives `Clone`, `From<&Aggregate>` is also generated so the aggregate can still be used after conversion.
+/// `{your aggregate name}PartialAdapter` has every adapter field as `Option`. `apply_to` sets only given fields, marking them dirty.
+/// ```rust,ignore
+/// let adapter = ProfileAdapter::from(&profile);
+///
+/// // PATCH /profiles/1 {"bio": "rustacean"}
+/// let patch: ProfilePartialAdapter = serde_json::from_str(body)?;
+/// patch.apply_to(&mut profile);
+/// ```
+///
+/// ## Reference to other aggregate
+/// Field marked with `#[reference(OtherAggregate)]` holds only the id of the other aggregate.
+/// `load_{field name without _id}` is generated to load it through `TLoadAggregate` implemented on the unit of work.
+/// `Option` of id is also allowed, in which case `Option` of the aggregate is loaded.
+/// ```rust,ignore
+/// #[aggregate]
+/// pub struct Order {
+///     #[reference(Customer)]
+///     customer_id: i64,
+/// }
+///
+/// impl TLoadAggregate<Customer, i64> for Context { ... }
+///
+/// let customer: Customer = order.load_customer(ctx).await?;
+/// ```
+///
+/// ## Optimistic concurrency
+/// Field marked with `#[version]` implements `TVersioned`. Update of the aggregate matches the row by the version it was loaded at,
+/// so that concurrent update fails with `BaseError::ConcurrencyConflict` instead of overwriting the other.
+/// ```rust,ignore
+/// #[aggregate]
+/// pub struct Order {
+///     id: i64,
+///     name: String,
+///     #[version]
+///     version: i64,
+/// }
+///
+/// let set_clause = versioned_set_clause(&order.dirty_fields(), "version", 3); // "name = $3, version = version + 1"
+/// let query = format!("UPDATE orders SET {set_clause} WHERE id = $1 AND version = $2");
+/// let update = sqlx::query(&query).bind(order.id).bind(order.version).bind(order.name.clone());
+/// ctx.update_versioned(&mut order, update).await?;
+/// ```
 #[proc_macro_attribute]
 pub fn aggregate(attrs: TokenStream, input: TokenStream) -> TokenStream {
 	domain::render_aggregate(input, attrs)
@@ -159,7 +255,7 @@ pub fn aggregate(attrs: TokenStream, input: TokenStream) -> TokenStream {
 /// Define ApplicationResponse so that could be recognized by messagebus
 /// ## Example
 ///
-/// ```rust,no_run
+/// ```rust,ignore
 /// #[derive(Debug, ApplicationResponse)]
 /// enum ServiceResponse{
 ///     Response1
@@ -174,7 +270,7 @@ pub fn response_derive(attr: TokenStream) -> TokenStream {
 
 /// Attribute macro for marking repository methods that collect events
 /// ## Example
-/// ```rust,no_run
+/// ```rust,ignore
 ///
 /// #[aggregate]
 /// #[derive(Default, Serialize, Deserialize)]
@@ -198,9 +294,19 @@ pub fn response_derive(attr: TokenStream) -> TokenStream {
 ///         assert!(!events.is_empty())
 /// }
 /// ```
+///
+/// It can also be put on impl block, inherent or of trait, in which case every `&mut self` method
+/// that takes `&mut` argument is hooked. Events of all hooked methods pile up on the same context.
+/// ```rust,ignore
+/// #[event_hook]
+/// impl TOrderRepository for Context {
+///     async fn add(&mut self, order: &mut Order) -> Result<i64, BaseError> { .. }
+///     async fn update(&mut self, order: &mut Order) -> Result<(), BaseError> { .. }
+/// }
+/// ```
 #[proc_macro_attribute]
 pub fn event_hook(_: TokenStream, input: TokenStream) -> TokenStream {
-	let ast: ItemFn = syn::parse_macro_input!(input as ItemFn);
+	let ast: syn::Item = syn::parse_macro_input!(input as syn::Item);
 	message::event_hook(ast).into()
 }
 
@@ -216,9 +322,11 @@ pub fn event_hook(_: TokenStream, input: TokenStream) -> TokenStream {
 /// - `#[stop_sentinel]` - Specify the error matching for `BaseError::StopSentinel`.
 /// - `#[stop_sentinel_with_event]` - Specify the error matching for `BaseError::StopSentinelWithEvent`.
 /// - `#[database_error]` - Specify the error matching for `BaseError::DatabaseError`.
+/// - `#[constraint("...")]` - Specify the error matching for `BaseError::ConstraintViolation` of the given constraint name.
+///   Violation of constraint that is not mapped falls back to `#[database_error]`.
 ///
 /// ## Example
-/// ```rust,no_run
+/// ```rust,ignore
 /// #[derive(Debug, ApplicationError)]
 /// #[crates(crate::imports::ruva)]
 /// enum TestError {
@@ -228,9 +336,15 @@ pub fn event_hook(_: TokenStream, input: TokenStream) -> TokenStream {
 ///   StopWithEvent(Box<AnyError>),
 ///   #[database_error]
 ///   DatabaseError(Box<AnyError>),
+///   #[constraint("orders_email_key")]
+///   DuplicateEmail,
+///   #[http_status(404)]
+///   OrderNotFound,
 /// }
 /// ```
-#[proc_macro_derive(ApplicationError, attributes(stop_sentinel, stop_sentinel_with_event, database_error, crates))]
+/// `#[http_status(..)]` sets the status code of `THttpStatus`. Constraint variants default to 409, `BaseError` follows its own mapping and the rest are 500.
+/// Public message of variants with status or constraint is their name, and of the rest is the reason phrase of the status.
+#[proc_macro_derive(ApplicationError, attributes(stop_sentinel, stop_sentinel_with_event, database_error, constraint, http_status, crates))]
 pub fn error_derive(attr: TokenStream) -> TokenStream {
 	let ast: DeriveInput = syn::parse(attr).unwrap();
 
@@ -247,7 +361,7 @@ pub fn entity(attrs: TokenStream, input: TokenStream) -> TokenStream {
 /// which are Debug and Deserialize for body and Debug and Serialize for command
 /// ### Example
 ///
-/// ```rust,no_run
+/// ```rust,ignore
 /// #[into_command(body(Debug, Deserialize), command(Debug, Serialize))]
 /// pub struct X{}
 /// #[into_command(command(Debug, Serialize), body(Debug, Deserialize))]
@@ -264,6 +378,48 @@ pub fn into_command(attrs: TokenStream, input: TokenStream) -> TokenStream {
 	command::render_into_command(input, attrs)
 }
 
+/// Bind response and error type to the command so that it can be dispatched without specifying them.
+/// Commands registered with `register_uow_services!` get it implemented already.
+/// ### Example
+///
+/// ```rust,ignore
+/// #[derive(Debug, TCommandSpec)]
+/// #[command_spec(response = ServiceResponse, error = ServiceError)]
+/// pub struct MakeOrder {}
+///
+/// let res: ServiceResponse = MessageBus.dispatch(MakeOrder {}, conn).await?;
+/// ```
+#[proc_macro_derive(TCommandSpec, attributes(command_spec))]
+pub fn derive_command_spec(input: TokenStream) -> TokenStream {
+	let input = parse_macro_input!(input as DeriveInput);
+
+	command::render_command_spec(&input).unwrap_or_else(syn::Error::into_compile_error).into()
+}
+
+/// Implement `TValidate` with rules declared on fields, checked by `ValidationAspect` before the command is handled.
+/// `Option` field is checked only when it is `Some`. Bounds of `range` are of the type of the field, and `regex` requires
+/// `regex` feature.
+/// ### Example
+///
+/// ```rust,ignore
+/// #[derive(Debug, TValidate)]
+/// pub struct RegisterUser {
+///     // String is measured in characters, collections in elements
+///     #[validate(length(min = 1, max = 30), regex = "^[a-z0-9_]+$")]
+///     pub username: String,
+///     #[validate(range(min = 14, max = 150))]
+///     pub age: u32,
+///     #[validate(length(max = 200))]
+///     pub bio: Option<String>,
+/// }
+/// ```
+#[proc_macro_derive(TValidate, attributes(validate))]
+pub fn derive_validate(input: TokenStream) -> TokenStream {
+	let input = parse_macro_input!(input as DeriveInput);
+
+	validate::render_validate(&input).unwrap_or_else(syn::Error::into_compile_error).into()
+}
+
 // what if I want attribute to be #[ruva(except)]?
 #[proc_macro_derive(TConstruct, attributes(except))]
 pub fn derive_construct(input: TokenStream) -> TokenStream {
@@ -281,3 +437,86 @@ pub fn inject(attrs: TokenStream, input: TokenStream) -> TokenStream {
 pub fn message_handler(_: TokenStream, input: TokenStream) -> TokenStream {
 	message_handler::render_message_handler(input)
 }
+
+/// Declare trait as dependency of handlers. `Send + Sync` is added as supertraits and `register`/`register_recorder`/`resolve` are generated on `dyn Trait`.
+/// With `mock` feature, `mockall::automock` is applied in test build so `Mock{Trait}` can be given to `ContextManager::with_dependency`.
+///
+/// ## Example
+/// ```rust,ignore
+/// declare_dependency! {
+///     #[async_trait]
+///     pub trait TPaymentGateway {
+///         async fn charge(&self, user_id: i64, amount: i64) -> Result<(), BaseError>;
+///     }
+/// }
+///
+/// <dyn TPaymentGateway>::register(StripeGateway::new(key));
+/// <dyn TPaymentGateway>::resolve(&ctx).charge(cmd.user_id, cmd.amount).await?;
+/// ```
+#[proc_macro]
+pub fn declare_dependency(input: TokenStream) -> TokenStream {
+	let item = parse_macro_input!(input as syn::ItemTrait);
+	dependency::render_dependency(item)
+}
+
+/// Generate `topics` module of typed topic constants from events deriving `TEvent`.
+/// Put it in the crate shared between producer and consumers so that consumers bind to the constants instead of hand-typed strings.
+///
+/// ## Example
+/// ```rust,ignore
+/// // order-contract crate
+/// topics!(OrderPlaced, OrderCancelled);
+///
+/// // consumer crate
+/// assert_eq!(order_contract::topics::ORDER_PLACED.name(), "OrderPlaced");
+/// EventPolicies::new(conn).register(order_contract::topics::ORDER_PLACED.on_event().dispatch(|e| CreateLocalOrder { external_id: e.id }));
+/// ```
+#[proc_macro]
+pub fn topics(input: TokenStream) -> TokenStream {
+	topics::render_topics(input)
+}
+
+/// Implement `TTypeScript` so that the type can be exported as TypeScript definition. Requires `typescript` feature.
+/// `rename`, `rename_all`, `skip`, `default`, `tag` and `untagged` of serde are respected. Every field type must implement `TTypeScript`.
+///
+/// ## Example
+/// ```rust,ignore
+/// #[derive(Serialize, Deserialize, TTypeScript)]
+/// #[serde(rename_all = "camelCase")]
+/// pub struct OrderCreated {
+///     pub order_id: i64,
+///     pub memo: Option<String>,
+/// }
+///
+/// assert_eq!(
+///     OrderCreated::ts_declaration().unwrap(),
+///     "export interface OrderCreated {\n\torderId: number;\n\tmemo: string | null;\n}"
+/// );
+///
+/// // Commands get the input body exported through `into_command`
+/// #[into_command(body(ruva::TTypeScript))]
+/// pub struct CreateOrder { ... }
+/// ```
+#[cfg(feature = "typescript")]
+#[proc_macro_derive(TTypeScript, attributes(serde))]
+pub fn derive_typescript(input: TokenStream) -> TokenStream {
+	let input = parse_macro_input!(input as DeriveInput);
+
+	typescript::render_typescript(&input).unwrap_or_else(syn::Error::into_compile_error).into()
+}
+
+/// Render TypeScript definitions of the given types into a single module as `String`.
+/// Meant to be called from a test or a build step and written with `write_typescript` to keep front-end clients in sync.
+///
+/// ## Example
+/// ```rust,ignore
+/// #[test]
+/// fn export_typescript() {
+///     ruva::write_typescript("../web/src/bindings.ts", &typescript_bindings!(CreateOrderBody, OrderResponse, OrderCreated)).unwrap();
+/// }
+/// ```
+#[cfg(feature = "typescript")]
+#[proc_macro]
+pub fn typescript_bindings(input: TokenStream) -> TokenStream {
+	typescript::render_typescript_bindings(input).unwrap_or_else(syn::Error::into_compile_error).into()
+}
@@ -1,5 +1,7 @@
 use proc_macro2::TokenStream;
-use syn::{parse_quote, Data, DataStruct, DeriveInput, Fields, FieldsNamed, FnArg, ItemFn, Meta, MetaList, Pat, PatIdent, PatType, Path, Type};
+use syn::{
+	parse_quote, Block, Data, DataStruct, DeriveInput, Fields, FieldsNamed, FnArg, ImplItem, Item, Meta, MetaList, Pat, PatIdent, PatType, Path, ReturnType, Signature, Stmt, Type, TypeReference,
+};
 
 use crate::utils::{get_attributes, get_trait_checking_stmts, locate_crate_on_derive_macro};
 
@@ -8,6 +10,7 @@ pub(crate) fn render_message_token(ast: &DeriveInput, visibilities: Vec<TokenStr
 	let crates = locate_crate_on_derive_macro(ast);
 
 	let (metadata_generator, impl_assertion) = externally_notifiable_event_req.unwrap_or_else(|| (TokenStream::new(), TokenStream::new()));
+	let trait_assertion = render_trait_assertion(ast);
 
 	quote! {
 		impl #crates::TEvent for #name {
@@ -15,18 +18,50 @@ pub(crate) fn render_message_token(ast: &DeriveInput, visibilities: Vec<TokenStr
 			#metadata_generator
 
 			fn state(&self) -> ::std::string::String {
-				serde_json::to_string(&self).expect("Failed to serialize")
+				#crates::json::to_string(&self).expect("Failed to serialize")
 			}
 
 			#(#visibilities)*
 		}
+		impl #crates::TTopic for #name {
+			const TOPIC: &'static str = stringify!(#name);
+		}
 		impl #name{
 			pub(crate) fn to_message(self)->  ::std::sync::Arc<dyn #crates::TEvent> {
 				::std::sync::Arc::new(self)
 			}
 		}
 		#impl_assertion
+		#trait_assertion
+	}
+}
+
+/// Externally notifiable event is serialized into the outbox and internally notifiable one is cloned for each of its handlers.
+/// Missing `Serialize` or `Clone` is reported against the event with the requirement in its name, rather than deep in the expansion.
+fn render_trait_assertion(ast: &DeriveInput) -> TokenStream {
+	let name = &ast.ident;
+	let crates = locate_crate_on_derive_macro(ast);
+	let mut assertions = vec![];
+	if ast.attrs.iter().any(|attr| attr.path().is_ident("externally_notifiable")) {
+		assertions.push(quote_spanned!(name.span()=>
+			fn externally_notifiable_event_must_derive_serialize<T: #crates::Serialize>() {}
+			externally_notifiable_event_must_derive_serialize::<#name>();
+		));
 	}
+	if ast.attrs.iter().any(|attr| attr.path().is_ident("internally_notifiable")) {
+		assertions.push(quote_spanned!(name.span()=>
+			fn internally_notifiable_event_must_derive_clone<T: ::std::clone::Clone>() {}
+			internally_notifiable_event_must_derive_clone::<#name>();
+		));
+	}
+	if assertions.is_empty() {
+		return TokenStream::new();
+	}
+	quote!(
+		const _: fn() = || {
+			#(#assertions)*
+		};
+	)
 }
 
 pub(crate) fn render_event_visibility(ast: &DeriveInput) -> Vec<TokenStream> {
@@ -62,7 +97,8 @@ pub(crate) fn render_event_visibility(ast: &DeriveInput) -> Vec<TokenStream> {
 					vec![quote!()]
 				}
 			}
-			_ => panic!("Notifiability was not specified!"),
+			// Doc comments and the like
+			Meta::NameValue(_) => vec![],
 		})
 		.collect::<Vec<_>>();
 	if propagatability.is_empty() {
@@ -71,12 +107,29 @@ pub(crate) fn render_event_visibility(ast: &DeriveInput) -> Vec<TokenStream> {
 	propagatability
 }
 
+/// `#[flush_immediately]` makes the event visible to the event loop as soon as it is raised, not after commit.
+pub(crate) fn render_flush_mode(ast: &DeriveInput) -> TokenStream {
+	let crates = locate_crate_on_derive_macro(ast);
+	if ast.attrs.iter().any(|attr| attr.path().is_ident("flush_immediately")) {
+		quote!(
+			fn flush_mode(&self) -> #crates::FlushMode {
+				#crates::FlushMode::Immediate
+			}
+		)
+	} else {
+		quote!()
+	}
+}
+
 // first return token is for identifier, second return token is for aggregate assertion
 pub(crate) fn extract_externally_notifiable_event_req(ast: &mut DeriveInput) -> Option<(TokenStream, TokenStream)> {
 	let mut token: Option<(TokenStream, TokenStream)> = None;
 	for attr in ast.attrs.iter_mut() {
 		if let Meta::List(MetaList { path, tokens, .. }) = &mut attr.meta {
 			let ident = path.get_ident();
+			if ident.unwrap() == "event_version" || ident.unwrap() == "publish_class" {
+				continue;
+			}
 			if ident.unwrap() != "externally_notifiable" {
 				return None;
 			}
@@ -112,6 +165,8 @@ pub(crate) fn extract_externally_notifiable_event_req(ast: &mut DeriveInput) ->
 pub(crate) fn generate_event_metadata(ast: &DeriveInput, aggregate_metadata: String) -> TokenStream {
 	let name = &ast.ident;
 	let crates = locate_crate_on_derive_macro(ast);
+	let version = event_version(ast);
+	let publish_class = publish_class(ast);
 
 	match &ast.data {
 		Data::Struct(DataStruct {
@@ -130,7 +185,11 @@ pub(crate) fn generate_event_metadata(ast: &DeriveInput, aggregate_metadata: Str
 					#crates::EventMetadata{
 					aggregate_id: self.#ident.to_string(),
 					aggregate_name: #aggregate_metadata.into(),
-					topic: stringify!(#name).into()
+					topic: stringify!(#name).into(),
+					version: #version,
+					publish_class: #publish_class,
+					correlation_id: None,
+					causation_id: None,
 				}
 			}
 			)
@@ -139,14 +198,118 @@ pub(crate) fn generate_event_metadata(ast: &DeriveInput, aggregate_metadata: Str
 	}
 }
 
-pub(crate) fn event_hook(mut ast: ItemFn) -> TokenStream {
-	if ast.sig.inputs.is_empty() {
-		panic!("There must be message argument!");
+/// `#[event_version(N)]`, or the initial version if not given
+fn event_version(ast: &DeriveInput) -> TokenStream {
+	let crates = locate_crate_on_derive_macro(ast);
+	match ast.attrs.iter().find(|attr| attr.path().is_ident("event_version")) {
+		Some(attr) => {
+			let version: syn::LitInt = attr.parse_args().expect("Version must be given as integer!\rExample: #[event_version(2)]");
+			quote!(#version)
+		}
+		None => quote!(#crates::INITIAL_EVENT_VERSION),
+	}
+}
+
+/// `#[publish_class(realtime | bulk | low)]`, or realtime if not given
+fn publish_class(ast: &DeriveInput) -> TokenStream {
+	let crates = locate_crate_on_derive_macro(ast);
+	let class = match ast.attrs.iter().find(|attr| attr.path().is_ident("publish_class")) {
+		Some(attr) => {
+			let class: syn::Ident = attr.parse_args().expect("Publish class must be given as identifier!\rExample: #[publish_class(bulk)]");
+			match class.to_string().as_str() {
+				"realtime" => quote!(Realtime),
+				"bulk" => quote!(Bulk),
+				"low" => quote!(Low),
+				_ => panic!("Publish class must be one of realtime, bulk and low!"),
+			}
+		}
+		None => quote!(Realtime),
+	};
+	quote!(#crates::PublishClass::#class)
+}
+
+/// Metadata of event that is not externally notifiable but has `#[event_version(N)]` or `#[publish_class(..)]`
+pub(crate) fn render_versioned_metadata(ast: &DeriveInput) -> Option<TokenStream> {
+	if !ast.attrs.iter().any(|attr| attr.path().is_ident("event_version") || attr.path().is_ident("publish_class")) {
+		return None;
+	}
+	let name = &ast.ident;
+	let crates = locate_crate_on_derive_macro(ast);
+	let version = event_version(ast);
+	let publish_class = publish_class(ast);
+	Some(quote!(
+		fn metadata(&self) -> #crates::EventMetadata {
+			#crates::EventMetadata {
+				aggregate_id: Default::default(),
+				aggregate_name: Default::default(),
+				topic: stringify!(#name).into(),
+				version: #version,
+				publish_class: #publish_class,
+				correlation_id: None,
+				causation_id: None,
+			}
+		}
+	))
+}
+
+pub(crate) fn event_hook(item: Item) -> TokenStream {
+	match item {
+		Item::Fn(mut ast) => {
+			if ast.sig.inputs.is_empty() {
+				panic!("There must be message argument!");
+			};
+			hook_block(&ast.sig, &mut ast.block);
+			quote!(#ast)
+		}
+		// Every `&mut self` method that takes `&mut` argument is hooked, so that hooks spread across impl blocks
+		// and trait impls all put the events of the aggregate they are given on the same context.
+		Item::Impl(mut ast) => {
+			for item in ast.items.iter_mut() {
+				if let ImplItem::Fn(method) = item {
+					// Hooking twice would collect events of the aggregate once more, which is harmless but wasteful.
+					method.attrs.retain(|attr| attr.path().segments.last().is_none_or(|seg| seg.ident != "event_hook"));
+					if takes_mut_self(&method.sig) {
+						hook_block(&method.sig, &mut method.block);
+					}
+				}
+			}
+			quote!(#ast)
+		}
+		_ => panic!("event_hook can be used only on function or impl block!"),
+	}
+}
+
+fn takes_mut_self(sig: &Signature) -> bool {
+	let Some(FnArg::Receiver(receiver)) = sig.inputs.first() else {
+		return false;
 	};
+	receiver.mutability.is_some()
+		&& sig
+			.inputs
+			.iter()
+			.skip(1)
+			.any(|arg| matches!(arg, FnArg::Typed(PatType { ty, .. }) if matches!(**ty, Type::Reference(TypeReference { mutability: Some(_), .. }))))
+}
 
+// Body is run first so that the hook sees the events raised in it, including on early return.
+fn hook_block(sig: &Signature, block: &mut Block) {
 	let mut stmts = get_trait_checking_stmts("::ruva::TAggregate");
 
-	for aggregate in &ast.sig.inputs.iter().skip(1).collect::<Vec<_>>() {
+	let body = std::mem::take(&mut block.stmts);
+	let output = match &sig.output {
+		ReturnType::Default => quote!(: ()),
+		ReturnType::Type(_, ty) if matches!(**ty, Type::ImplTrait(_)) => quote!(),
+		ReturnType::Type(_, ty) => quote!(: #ty),
+	};
+	stmts.push(match sig.asyncness {
+		Some(_) => parse_quote!(let __result #output = async { #(#body)* }.await;),
+		None => parse_quote!(
+			#[allow(clippy::redundant_closure_call)]
+			let __result #output = (|| { #(#body)* })();
+		),
+	});
+
+	for aggregate in &sig.inputs.iter().skip(1).collect::<Vec<_>>() {
 		if let FnArg::Typed(PatType { pat, ty, .. }) = aggregate {
 			let ty: Box<Type> = match *ty.clone() {
 				Type::Reference(a) => a.elem,
@@ -162,9 +325,6 @@ pub(crate) fn event_hook(mut ast: ItemFn) -> TokenStream {
 			}
 		}
 	}
-	stmts.extend(std::mem::take(&mut ast.block.stmts));
-	ast.block.stmts = stmts;
-	quote!(
-		#ast
-	)
+	stmts.push(Stmt::Expr(parse_quote!(__result), None));
+	block.stmts = stmts;
 }
@@ -90,15 +90,86 @@ pub(crate) fn render_error_token(ast: &DeriveInput) -> TokenStream {
 		syn::Ident::new("DatabaseError", proc_macro2::Span::call_site())
 	};
 
+	/* \#\[constraint("...")\] */
+	let constraint_arms = data_enum
+		.variants
+		.iter()
+		.filter_map(|variant| {
+			let attr = variant.attrs.iter().find(|attr| attr.path().is_ident("constraint"))?;
+			if !matches!(variant.fields, syn::Fields::Unit) {
+				panic!("#[constraint(...)] expects unit.")
+			}
+			let constraint_name = attr
+				.parse_args::<syn::LitStr>()
+				.expect("#[constraint(...)] expects constraint name. Example: #[constraint(\"orders_email_key\")]");
+			let ident = &variant.ident;
+			Some(quote!(
+				#crates::BaseError::ConstraintViolation { constraint, .. } if constraint == #constraint_name => Self::#ident,
+			))
+		})
+		.collect::<Vec<_>>();
+
+	/* \#\[http_status(..)\] */
+	let http_status_arms = data_enum
+		.variants
+		.iter()
+		.filter_map(|variant| {
+			let ident = &variant.ident;
+			let status = match variant.attrs.iter().find(|attr| attr.path().is_ident("http_status")) {
+				Some(attr) => attr.parse_args::<syn::LitInt>().expect("#[http_status(..)] expects status code. Example: #[http_status(404)]"),
+				// Constraint is mostly violated by duplicate
+				None if variant.attrs.iter().any(|attr| attr.path().is_ident("constraint")) => syn::LitInt::new("409", proc_macro2::Span::call_site()),
+				None => return None,
+			};
+			Some(quote!(Self::#ident { .. } => #status,))
+		})
+		.collect::<Vec<_>>();
+	// Variants given status are meant for the client, so their names are their public messages
+	let public_message_arms = data_enum
+		.variants
+		.iter()
+		.filter(|variant| variant.attrs.iter().any(|attr| attr.path().is_ident("http_status") || attr.path().is_ident("constraint")))
+		.map(|variant| {
+			let ident = &variant.ident;
+			let message = ident.to_string();
+			quote!(Self::#ident { .. } => #message.to_string(),)
+		})
+		.collect::<Vec<_>>();
+
 	quote!(
 		impl #crates::ApplicationError for #name {}
 
+		impl #crates::THttpStatus for #name {
+			fn http_status(&self) -> u16 {
+				match self {
+					#(#http_status_arms)*
+					Self::BaseError(error) => #crates::THttpStatus::http_status(error),
+					_ => 500,
+				}
+			}
+			fn public_message(&self) -> String {
+				match self {
+					#(#public_message_arms)*
+					Self::BaseError(error) => #crates::THttpStatus::public_message(error),
+					_ => #crates::reason_phrase(#crates::THttpStatus::http_status(self)).to_string(),
+				}
+			}
+			fn public_details(&self) -> Option<#crates::serde_json::Value> {
+				match self {
+					Self::BaseError(error) => #crates::THttpStatus::public_details(error),
+					_ => None,
+				}
+			}
+		}
+
 		impl ::std::convert::From<#crates::BaseError> for #name {
 			fn from(value: #crates::BaseError) -> Self {
 				match value {
 					#crates::BaseError::StopSentinel => Self::#stop_sentinel,
 					#crates::BaseError::StopSentinelWithEvent(event) => Self::#stop_sentinel_with_event(event),
 					#crates::BaseError::DatabaseError(error) => Self::#database_error(error),
+					#(#constraint_arms)*
+					#crates::BaseError::ConstraintViolation { message, .. } => Self::#database_error(message),
 					err => Self::BaseError(err),
 				}
 			}
@@ -110,6 +181,7 @@ pub(crate) fn render_error_token(ast: &DeriveInput) -> TokenStream {
 					#name::#stop_sentinel => #crates::BaseError::StopSentinel,
 					#name::#stop_sentinel_with_event(event) => #crates::BaseError::StopSentinelWithEvent(event),
 					#name::#database_error(error) => #crates::BaseError::DatabaseError(error),
+					#name::BaseError(error) => error,
 					// _ => #crates::BaseError::ServiceError(::std::boxed::Box::new(value)),
 					_=> #crates::BaseError::ServiceError,
 				};
@@ -0,0 +1,46 @@
+use proc_macro::TokenStream;
+use syn::{parse::Parser, punctuated::Punctuated, Path, Token};
+
+/// `OrderPlaced` -> `ORDER_PLACED`
+fn to_screaming_snake_case(name: &str) -> String {
+	let mut screaming = String::with_capacity(name.len() + 4);
+	for (i, c) in name.char_indices() {
+		if c.is_uppercase() && i != 0 {
+			screaming.push('_');
+		}
+		screaming.push(c.to_ascii_uppercase());
+	}
+	screaming
+}
+
+pub(crate) fn render_topics(input: TokenStream) -> TokenStream {
+	let events = Punctuated::<Path, Token![,]>::parse_terminated.parse(input).expect("topics! expects comma separated event types.");
+
+	let constants = events.iter().map(|event| {
+		let name = event.segments.last().expect("Event type is empty").ident.to_string();
+		let constant = syn::Ident::new(&to_screaming_snake_case(&name), proc_macro2::Span::call_site());
+		quote!(
+			pub const #constant: ::ruva::Topic<#event> = ::ruva::Topic::new(<#event as ::ruva::TTopic>::TOPIC);
+		)
+	});
+	let events = events.iter();
+
+	quote!(
+		pub mod topics {
+			#[allow(unused_imports)]
+			use super::*;
+
+			#(#constants)*
+
+			/// Every topic declared in this module
+			pub const ALL: &[&str] = &[#(<#events as ::ruva::TTopic>::TOPIC),*];
+		}
+	)
+	.into()
+}
+
+#[test]
+fn test_to_screaming_snake_case() {
+	assert_eq!(to_screaming_snake_case("OrderPlaced"), "ORDER_PLACED");
+	assert_eq!(to_screaming_snake_case("Shipped"), "SHIPPED");
+}
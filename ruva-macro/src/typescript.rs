@@ -0,0 +1,253 @@
+use proc_macro2::TokenStream;
+use syn::{parse::Parser, punctuated::Punctuated, Data, DeriveInput, Fields, LitStr, Token, Type};
+
+/// Subset of serde attributes that changes the shape of serialized value
+#[derive(Default)]
+struct SerdeAttrs {
+	rename: Option<String>,
+	rename_all: Option<String>,
+	tag: Option<String>,
+	untagged: bool,
+	skip: bool,
+	optional: bool,
+	flatten: bool,
+}
+
+fn parse_serde_attrs(attrs: &[syn::Attribute]) -> syn::Result<SerdeAttrs> {
+	let mut parsed = SerdeAttrs::default();
+	for attr in attrs.iter().filter(|attr| attr.path().is_ident("serde")) {
+		attr.parse_nested_meta(|meta| {
+			let key = meta.path.get_ident().map(ToString::to_string).unwrap_or_default();
+			match key.as_str() {
+				"rename" => parsed.rename = Some(meta.value()?.parse::<LitStr>()?.value()),
+				"rename_all" => parsed.rename_all = Some(meta.value()?.parse::<LitStr>()?.value()),
+				"tag" => parsed.tag = Some(meta.value()?.parse::<LitStr>()?.value()),
+				"content" => return Err(meta.error("adjacently tagged enum is not supported by TTypeScript")),
+				"untagged" => parsed.untagged = true,
+				"skip" | "skip_serializing" => parsed.skip = true,
+				"default" | "skip_serializing_if" => {
+					parsed.optional = true;
+					skip_meta_value(&meta)?;
+				}
+				"flatten" => parsed.flatten = true,
+				_ => skip_meta_value(&meta)?,
+			}
+			Ok(())
+		})?;
+	}
+	Ok(parsed)
+}
+
+// Consume value of serde attributes irrelevant to TypeScript such as `with = "..."` or `rename(serialize = "...")`
+fn skip_meta_value(meta: &syn::meta::ParseNestedMeta) -> syn::Result<()> {
+	if meta.input.peek(Token![=]) {
+		meta.value()?.parse::<syn::Expr>()?;
+	} else if meta.input.peek(syn::token::Paren) {
+		meta.parse_nested_meta(|nested| skip_meta_value(&nested))?;
+	}
+	Ok(())
+}
+
+fn split_words(name: &str) -> Vec<String> {
+	let mut words: Vec<String> = vec![];
+	for part in name.split(['_', '-']).filter(|part| !part.is_empty()) {
+		let mut word = String::new();
+		for c in part.chars() {
+			if c.is_uppercase() && !word.is_empty() {
+				words.push(std::mem::take(&mut word));
+			}
+			word.push(c);
+		}
+		words.push(word);
+	}
+	words
+}
+
+fn capitalize(word: &str) -> String {
+	let mut chars = word.chars();
+	chars.next().map(|first| first.to_uppercase().chain(chars.flat_map(char::to_lowercase)).collect()).unwrap_or_default()
+}
+
+fn apply_rename_all(name: &str, rule: &str) -> syn::Result<String> {
+	let words = split_words(name);
+	let lower = || words.iter().map(|word| word.to_lowercase());
+	let upper = || words.iter().map(|word| word.to_uppercase());
+	Ok(match rule {
+		"lowercase" => name.to_lowercase(),
+		"UPPERCASE" => name.to_uppercase(),
+		"PascalCase" => words.iter().map(|word| capitalize(word)).collect(),
+		"camelCase" => lower().take(1).chain(words.iter().skip(1).map(|word| capitalize(word))).collect(),
+		"snake_case" => lower().collect::<Vec<_>>().join("_"),
+		"SCREAMING_SNAKE_CASE" => upper().collect::<Vec<_>>().join("_"),
+		"kebab-case" => lower().collect::<Vec<_>>().join("-"),
+		"SCREAMING-KEBAB-CASE" => upper().collect::<Vec<_>>().join("-"),
+		_ => return Err(syn::Error::new(proc_macro2::Span::call_site(), format!("unknown rename_all rule: {}", rule))),
+	})
+}
+
+fn serialized_name(ident: &syn::Ident, attrs: &SerdeAttrs, rename_all: Option<&String>) -> syn::Result<String> {
+	let ident = ident.to_string();
+	let ident = ident.trim_start_matches("r#");
+	match (&attrs.rename, rename_all) {
+		(Some(rename), _) => Ok(rename.clone()),
+		(None, Some(rule)) => apply_rename_all(ident, rule),
+		(None, None) => Ok(ident.to_string()),
+	}
+}
+
+fn property_key(name: &str) -> String {
+	let is_identifier = name.chars().next().is_some_and(|c| c.is_alphabetic() || c == '_' || c == '$') && name.chars().all(|c| c.is_alphanumeric() || c == '_' || c == '$');
+	if is_identifier {
+		name.to_string()
+	} else {
+		format!("{:?}", name)
+	}
+}
+
+fn ts_type_of(ty: &Type) -> TokenStream {
+	quote!(<#ty as ::ruva::TTypeScript>::ts_type())
+}
+
+/// Expression evaluating to `Vec<String>` of object members such as `name: string`
+fn render_members(fields: &syn::FieldsNamed, rename_all: Option<&String>) -> syn::Result<TokenStream> {
+	let mut members = vec![];
+	for field in fields.named.iter() {
+		let attrs = parse_serde_attrs(&field.attrs)?;
+		if attrs.skip {
+			continue;
+		}
+		if attrs.flatten {
+			return Err(syn::Error::new_spanned(field, "#[serde(flatten)] is not supported by TTypeScript"));
+		}
+		let key = property_key(&serialized_name(field.ident.as_ref().unwrap(), &attrs, rename_all)?);
+		let separator = if attrs.optional { "?: " } else { ": " };
+		let ty = ts_type_of(&field.ty);
+		members.push(quote!(format!("{}{}{}", #key, #separator, #ty)));
+	}
+	Ok(quote!(vec![#(#members),*] as Vec<String>))
+}
+
+fn inline_object(members: TokenStream) -> TokenStream {
+	quote!(format!("{{ {} }}", (#members).join("; ")))
+}
+
+fn render_tuple(fields: &syn::FieldsUnnamed) -> TokenStream {
+	let types = fields.unnamed.iter().map(|field| ts_type_of(&field.ty));
+	quote!(format!("[{}]", [#(#types),*].join(", ")))
+}
+
+/// Expression evaluating to declaration string
+fn render_declaration(ast: &DeriveInput) -> syn::Result<TokenStream> {
+	let name = ast.ident.to_string();
+	let container = parse_serde_attrs(&ast.attrs)?;
+
+	match &ast.data {
+		Data::Struct(data) => match &data.fields {
+			Fields::Named(fields) => {
+				let members = render_members(fields, container.rename_all.as_ref())?;
+				Ok(quote!(
+					format!("export interface {} {{\n{}}}", #name, (#members).into_iter().map(|member| format!("\t{};\n", member)).collect::<String>())
+				))
+			}
+			Fields::Unnamed(fields) if fields.unnamed.len() == 1 => {
+				let ty = ts_type_of(&fields.unnamed[0].ty);
+				Ok(quote!(format!("export type {} = {};", #name, #ty)))
+			}
+			Fields::Unnamed(fields) => {
+				let tuple = render_tuple(fields);
+				Ok(quote!(format!("export type {} = {};", #name, #tuple)))
+			}
+			Fields::Unit => Ok(quote!(format!("export type {} = null;", #name))),
+		},
+		Data::Enum(data) => {
+			let mut variants = vec![];
+			for variant in data.variants.iter() {
+				let attrs = parse_serde_attrs(&variant.attrs)?;
+				if attrs.skip {
+					continue;
+				}
+				let key = serialized_name(&variant.ident, &attrs, container.rename_all.as_ref())?;
+				let quoted = format!("{:?}", key);
+				let rendered = match (&container.tag, container.untagged, &variant.fields) {
+					(_, true, Fields::Unit) => quote!("null".to_string()),
+					(_, true, Fields::Unnamed(fields)) if fields.unnamed.len() == 1 => ts_type_of(&fields.unnamed[0].ty),
+					(_, true, Fields::Unnamed(fields)) => render_tuple(fields),
+					(_, true, Fields::Named(fields)) => inline_object(render_members(fields, attrs.rename_all.as_ref())?),
+
+					(Some(tag), _, Fields::Unit) => {
+						let member = format!("{}: {}", property_key(tag), quoted);
+						quote!(format!("{{ {} }}", #member))
+					}
+					(Some(tag), _, Fields::Named(fields)) => {
+						let member = format!("{}: {}", property_key(tag), quoted);
+						let members = render_members(fields, attrs.rename_all.as_ref())?;
+						inline_object(quote!(std::iter::once(#member.to_string()).chain(#members).collect::<Vec<_>>()))
+					}
+					(Some(tag), _, Fields::Unnamed(fields)) if fields.unnamed.len() == 1 => {
+						let member = format!("{}: {}", property_key(tag), quoted);
+						let ty = ts_type_of(&fields.unnamed[0].ty);
+						quote!(format!("({{ {} }} & {})", #member, #ty))
+					}
+					(Some(_), _, Fields::Unnamed(fields)) => return Err(syn::Error::new_spanned(fields, "internally tagged enum cannot contain tuple variant")),
+
+					(None, false, Fields::Unit) => quote!(#quoted.to_string()),
+					(None, false, Fields::Unnamed(fields)) if fields.unnamed.len() == 1 => {
+						let ty = ts_type_of(&fields.unnamed[0].ty);
+						quote!(format!("{{ {}: {} }}", #quoted, #ty))
+					}
+					(None, false, Fields::Unnamed(fields)) => {
+						let tuple = render_tuple(fields);
+						quote!(format!("{{ {}: {} }}", #quoted, #tuple))
+					}
+					(None, false, Fields::Named(fields)) => {
+						let object = inline_object(render_members(fields, attrs.rename_all.as_ref())?);
+						quote!(format!("{{ {}: {} }}", #quoted, #object))
+					}
+				};
+				variants.push(rendered);
+			}
+			if variants.is_empty() {
+				return Ok(quote!(format!("export type {} = never;", #name)));
+			}
+			Ok(quote!(format!("export type {} = {};", #name, [#(#variants),*].join(" | "))))
+		}
+		Data::Union(_) => Err(syn::Error::new_spanned(&ast.ident, "TTypeScript cannot be derived for union")),
+	}
+}
+
+pub(crate) fn render_typescript(ast: &DeriveInput) -> syn::Result<TokenStream> {
+	if !ast.generics.params.is_empty() {
+		return Err(syn::Error::new_spanned(&ast.generics, "TTypeScript cannot be derived for generic type"));
+	}
+	let name = &ast.ident;
+	let ts_name = name.to_string();
+	let declaration = render_declaration(ast)?;
+
+	Ok(quote!(
+		impl ::ruva::TTypeScript for #name {
+			fn ts_type() -> String {
+				#ts_name.to_string()
+			}
+			fn ts_declaration() -> Option<String> {
+				Some(#declaration)
+			}
+		}
+	))
+}
+
+pub(crate) fn render_typescript_bindings(input: proc_macro::TokenStream) -> syn::Result<TokenStream> {
+	let types = Punctuated::<Type, Token![,]>::parse_terminated.parse(input)?;
+	let types = types.iter();
+	Ok(quote!(
+		::ruva::render_typescript([#(<#types as ::ruva::TTypeScript>::ts_declaration()),*])
+	))
+}
+
+#[test]
+fn test_apply_rename_all() {
+	assert_eq!(apply_rename_all("order_id", "camelCase").unwrap(), "orderId");
+	assert_eq!(apply_rename_all("OrderPlaced", "snake_case").unwrap(), "order_placed");
+	assert_eq!(apply_rename_all("OrderPlaced", "SCREAMING-KEBAB-CASE").unwrap(), "ORDER-PLACED");
+	assert_eq!(apply_rename_all("order_id", "PascalCase").unwrap(), "OrderId");
+	assert_eq!(property_key("order-id"), "\"order-id\"");
+}
@@ -31,6 +31,15 @@ pub(crate) fn check_if_field_has_attribute(field: &Field, attribute_name: &str)
 	field.attrs.iter().find(|attr| attr.path().is_ident(attribute_name)).map(|_| field.ident.as_ref().unwrap().to_string())
 }
 
+/// Whether `trait_name` is derived with `#[derive(...)]` attribute. Path of the trait is ignored.
+pub(crate) fn derives_trait(ast: &DeriveInput, trait_name: &str) -> bool {
+	ast.attrs.iter().filter(|attr| attr.path().is_ident("derive")).any(|attr| {
+		attr.parse_args_with(syn::punctuated::Punctuated::<syn::Path, syn::Token![,]>::parse_terminated)
+			.map(|paths| paths.iter().any(|path| path.segments.last().is_some_and(|segment| segment.ident == trait_name)))
+			.unwrap_or(false)
+	})
+}
+
 pub(crate) fn extract_field_names(ast: &DeriveInput) -> Vec<String> {
 	match &ast.data {
 		syn::Data::Struct(syn::DataStruct {
@@ -45,7 +54,7 @@ pub(crate) fn remove_fields_based_on_field_name(given_fields: &mut syn::FieldsNa
 	let new_fields: Punctuated<Field, Comma> = given_fields
 		.named
 		.iter()
-		.filter(|f| f.ident.as_ref().map_or(true, |ident| !fields_to_remove.contains(&ident.to_string())))
+		.filter(|f| f.ident.as_ref().is_none_or(|ident| !fields_to_remove.contains(&ident.to_string())))
 		.cloned()
 		.collect();
 
@@ -0,0 +1,125 @@
+use proc_macro2::TokenStream;
+use syn::{Data, DeriveInput, Expr, ExprLit, Fields, Lit, LitStr, Type};
+
+use crate::utils::locate_crate_on_derive_macro;
+
+enum Rule {
+	Length { min: Option<Expr>, max: Option<Expr> },
+	Range { min: Option<Expr>, max: Option<Expr> },
+	Regex(LitStr),
+}
+
+/// `min = .., max = ..` of `length(..)` and `range(..)`
+fn parse_bounds(meta: &syn::meta::ParseNestedMeta) -> syn::Result<(Option<Expr>, Option<Expr>)> {
+	let (mut min, mut max) = (None, None);
+	meta.parse_nested_meta(|bound| {
+		if bound.path.is_ident("min") {
+			min = Some(bound.value()?.parse::<Expr>()?);
+		} else if bound.path.is_ident("max") {
+			max = Some(bound.value()?.parse::<Expr>()?);
+		} else {
+			return Err(bound.error("expected `min` or `max`"));
+		}
+		Ok(())
+	})?;
+	if min.is_none() && max.is_none() {
+		return Err(meta.error("at least one of `min` and `max` is required"));
+	}
+	Ok((min, max))
+}
+
+fn parse_rules(field: &syn::Field) -> syn::Result<Vec<Rule>> {
+	let mut rules = vec![];
+	for attr in field.attrs.iter().filter(|attr| attr.path().is_ident("validate")) {
+		attr.parse_nested_meta(|meta| {
+			if meta.path.is_ident("length") {
+				let (min, max) = parse_bounds(&meta)?;
+				rules.push(Rule::Length { min, max });
+			} else if meta.path.is_ident("range") {
+				let (min, max) = parse_bounds(&meta)?;
+				rules.push(Rule::Range { min, max });
+			} else if meta.path.is_ident("regex") {
+				let Expr::Lit(ExprLit { lit: Lit::Str(pattern), .. }) = meta.value()?.parse::<Expr>()? else {
+					return Err(meta.error("expected `regex = \"pattern\"`"));
+				};
+				// Invalid pattern fails the build rather than the first command
+				if let Err(err) = regex::Regex::new(&pattern.value()) {
+					return Err(syn::Error::new_spanned(&pattern, format!("invalid regex: {}", err)));
+				}
+				rules.push(Rule::Regex(pattern));
+			} else {
+				return Err(meta.error("expected `length`, `range` or `regex`"));
+			}
+			Ok(())
+		})?;
+	}
+	Ok(rules)
+}
+
+/// `Option<T>` is validated only when it is `Some`
+fn is_option(ty: &Type) -> bool {
+	matches!(ty, Type::Path(path) if path.path.segments.last().is_some_and(|segment| segment.ident == "Option"))
+}
+
+/// Bound of `range`, which is of the type of the field
+fn range_bound(bound: &Option<Expr>) -> TokenStream {
+	match bound {
+		Some(bound) => quote!(::std::option::Option::Some(#bound)),
+		None => quote!(::std::option::Option::None),
+	}
+}
+
+fn bound<T: quote::ToTokens>(bound: &Option<Expr>, cast: T) -> TokenStream {
+	match bound {
+		Some(bound) => quote!(::std::option::Option::Some((#bound) as #cast)),
+		None => quote!(::std::option::Option::None),
+	}
+}
+
+pub(crate) fn render_validate(input: &DeriveInput) -> syn::Result<TokenStream> {
+	let name = &input.ident;
+	let crates = locate_crate_on_derive_macro(input);
+	let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+
+	let Data::Struct(data) = &input.data else {
+		return Err(syn::Error::new_spanned(input, "TValidate can only be derived for structs"));
+	};
+	let Fields::Named(fields) = &data.fields else {
+		return Err(syn::Error::new_spanned(input, "TValidate can only be derived for structs with named fields"));
+	};
+
+	let mut checks = vec![];
+	for field in fields.named.iter() {
+		let ident = field.ident.as_ref().unwrap();
+		let field_name = ident.to_string();
+		let rules = parse_rules(field)?;
+		if rules.is_empty() {
+			continue;
+		}
+		let rules = rules.iter().map(|rule| match rule {
+			Rule::Length { min, max } => {
+				let (min, max) = (bound(min, quote!(usize)), bound(max, quote!(usize)));
+				quote!(errors.check_length(#field_name, #crates::TValidateLength::validation_length(value), #min, #max);)
+			}
+			Rule::Range { min, max } => {
+				let (min, max) = (range_bound(min), range_bound(max));
+				quote!(errors.check_range(#field_name, value, #min, #max);)
+			}
+			Rule::Regex(pattern) => quote!(errors.check_regex(#field_name, ::std::convert::AsRef::<str>::as_ref(value), #pattern);),
+		});
+		checks.push(match is_option(&field.ty) {
+			true => quote!(if let ::std::option::Option::Some(value) = &self.#ident { #(#rules)* }),
+			false => quote!({ let value = &self.#ident; #(#rules)* }),
+		});
+	}
+
+	Ok(quote! {
+		impl #impl_generics #crates::TValidate for #name #ty_generics #where_clause {
+			fn validate(&self) -> ::std::result::Result<(), #crates::ValidationErrors> {
+				let mut errors = #crates::ValidationErrors::default();
+				#(#checks)*
+				errors.into_result()
+			}
+		}
+	})
+}
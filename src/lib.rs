@@ -59,7 +59,7 @@
 //! Note that use of `internally_notifiable`(or `externally_notifiable`) and `identifier` are MUST.
 //!
 //! * `internally_notifiable` is marker to let the system know that the event should be handled
-//!    within the application
+//!   within the application
 //! * `externally_notifiable` is to leave `OutBox`.
 //! * `identifier` is to record aggregate id.
 //!
@@ -218,13 +218,23 @@
 
 pub extern crate static_assertions;
 
+pub use ruva_core::__assert_unique_orders;
+pub use ruva_core::__assert_unique_registrations;
+pub use ruva_core::__event_handlers_internal;
+pub use ruva_core::__handler_order;
 pub use ruva_core::__register_uow_services_internal;
 pub use ruva_core::error;
+#[cfg(feature = "ruva-tonic")]
+pub use ruva_core::grpc_command_service;
 pub use ruva_core::init_event_handler;
+pub use ruva_core::init_tenant_event_handler;
 pub use ruva_core::make_conversion;
 pub use ruva_core::make_smart_pointer;
 pub use ruva_core::prelude::*;
 pub use ruva_core::prepare_bulk_operation;
 pub use ruva_core::register_uow_services;
+pub use ruva_core::spi;
 
-pub use ruva_macro::{aggregate, entity, event_hook, into_command, ApplicationError, ApplicationResponse, TConstruct, TEvent};
+pub use ruva_macro::{aggregate, declare_dependency, entity, event_hook, into_command, topics, ApplicationError, ApplicationResponse, TCommandSpec, TConstruct, TEvent, TValidate};
+#[cfg(feature = "typescript")]
+pub use ruva_macro::{typescript_bindings, TTypeScript};
@@ -133,3 +133,304 @@ fn test_generic_aggregate() {
 	assert_eq!(my_struct.age, 2);
 	assert!(my_struct.sub_type.is_empty());
 }
+
+#[test]
+fn test_dirty_fields_tracked_by_setters() {
+	#[aggregate]
+	pub struct DirtyTest {
+		id: i32,
+		name: String,
+		age: i32,
+		address: String,
+	}
+
+	let mut aggregate = DirtyTest::default();
+	assert!(aggregate.dirty_fields().is_empty());
+
+	aggregate.set_name("migo");
+	aggregate.set_address("seoul");
+	assert!(aggregate.is_dirty("name"));
+	assert!(!aggregate.is_dirty("age"));
+	assert_eq!(TAggregate::dirty_fields(&aggregate), vec!["address", "name"]);
+	assert_eq!(update_set_clause(&aggregate.dirty_fields(), 2), "address = $2, name = $3");
+
+	aggregate.clear_dirty_fields();
+	assert!(aggregate.dirty_fields().is_empty());
+
+	// Loaded aggregate starts clean
+	let loaded = DirtyTest::from(DirtyTestAdapter::default());
+	assert!(loaded.dirty_fields().is_empty());
+}
+
+#[test]
+fn test_encrypted_column() {
+	// Appends additional authenticated data to the reversed plaintext and checks it on decryption
+	struct ReversingKeyProvider;
+	impl TKeyProvider for ReversingKeyProvider {
+		fn encrypt(&self, plaintext: &[u8], aad: &[u8]) -> Result<Vec<u8>, BaseError> {
+			Ok(plaintext.iter().rev().chain(aad).copied().collect())
+		}
+		fn decrypt(&self, ciphertext: &[u8], aad: &[u8]) -> Result<Vec<u8>, BaseError> {
+			let sealed = ciphertext.strip_suffix(aad).ok_or_else(|| BaseError::DatabaseError("Authentication failed".into()))?;
+			Ok(sealed.iter().rev().copied().collect())
+		}
+	}
+	set_key_provider(ReversingKeyProvider);
+
+	#[aggregate]
+	#[derive(Clone)]
+	pub struct Customer {
+		id: i64,
+		#[encrypted_column]
+		ssn: String,
+	}
+
+	let customer = Customer {
+		id: 1,
+		ssn: "900101".into(),
+		..Default::default()
+	};
+	let adapter = CustomerAdapter::try_from(customer.clone()).unwrap();
+	assert_eq!(adapter.id, 1);
+	assert_ne!(adapter.ssn, "900101");
+
+	// Ciphertext copied to another customer doesn't decrypt
+	let copied = CustomerAdapter { id: 3, ssn: adapter.ssn.clone() };
+	assert!(matches!(Customer::try_from(copied), Err(BaseError::DatabaseError(_))));
+
+	let loaded = Customer::try_from(adapter).unwrap();
+	assert_eq!(loaded.ssn, "900101");
+
+	// Corrupt row fails to load instead of panicking
+	let corrupt = CustomerAdapter { id: 2, ssn: "not base64!".into() };
+	assert!(matches!(Customer::try_from(corrupt), Err(BaseError::DatabaseError(_))));
+}
+
+#[test]
+fn test_conversion_by_reference_and_partial_update() {
+	#[aggregate]
+	#[derive(Clone)]
+	pub struct Profile {
+		#[adapter_ignore]
+		id: i32,
+		name: String,
+		bio: String,
+	}
+
+	let mut profile = Profile {
+		id: 1,
+		name: "migo".into(),
+		bio: "".into(),
+		..Default::default()
+	};
+	let adapter = ProfileAdapter::from(&profile);
+	assert_eq!(adapter.name, "migo");
+
+	let patch: ProfilePartialAdapter = serde_json::from_str(r#"{"bio":"rustacean"}"#).unwrap();
+	patch.apply_to(&mut profile);
+	assert_eq!(profile.name, "migo");
+	assert_eq!(profile.bio, "rustacean");
+	assert_eq!(profile.dirty_fields(), vec!["bio"]);
+}
+
+#[tokio::test]
+async fn test_reference_loader() {
+	#[aggregate]
+	pub struct Customer {
+		#[adapter_ignore]
+		id: i64,
+		name: String,
+	}
+
+	#[aggregate]
+	pub struct Order {
+		#[adapter_ignore]
+		id: i64,
+		#[reference(Customer)]
+		customer_id: i64,
+		#[reference(Customer)]
+		referrer: Option<i64>,
+	}
+
+	struct Repository;
+	impl TLoadAggregate<Customer, i64> for Repository {
+		async fn load_aggregate(&mut self, id: &i64) -> Result<Customer, BaseError> {
+			match id {
+				1 => Ok(Customer {
+					id: 1,
+					name: "migo".into(),
+					..Default::default()
+				}),
+				_ => Err(BaseError::NotFound),
+			}
+		}
+	}
+
+	let order = Order {
+		customer_id: 1,
+		referrer: None,
+		..Default::default()
+	};
+	assert_eq!(order.load_customer(&mut Repository).await.unwrap().name, "migo");
+	assert!(order.load_referrer(&mut Repository).await.unwrap().is_none());
+	let _adapter: OrderAdapter = order.into();
+}
+
+#[tokio::test]
+async fn test_event_hook_on_impl_blocks() {
+	struct Connection;
+	impl TConnection for Connection {}
+
+	#[derive(Debug, Clone, Serialize, TEvent)]
+	#[internally_notifiable]
+	struct OrderPlaced {
+		id: i64,
+	}
+	#[derive(Debug, Clone, Serialize, TEvent)]
+	#[internally_notifiable]
+	struct OrderUpdated {
+		changed: Vec<&'static str>,
+	}
+
+	#[derive(Default)]
+	struct Order {
+		id: i64,
+		changed: Vec<&'static str>,
+		events: std::collections::VecDeque<std::sync::Arc<dyn TEvent>>,
+	}
+	impl TAggregate for Order {
+		fn events(&self) -> &std::collections::VecDeque<std::sync::Arc<dyn TEvent>> {
+			&self.events
+		}
+		fn take_events(&mut self) -> std::collections::VecDeque<std::sync::Arc<dyn TEvent>> {
+			std::mem::take(&mut self.events)
+		}
+		fn raise_event(&mut self, event: std::sync::Arc<dyn TEvent>) {
+			self.events.push_back(event)
+		}
+		fn mutation_events(&mut self) -> std::collections::VecDeque<std::sync::Arc<dyn TEvent>> {
+			match self.changed.is_empty() {
+				true => Default::default(),
+				false => [OrderUpdated {
+					changed: std::mem::take(&mut self.changed),
+				}
+				.to_message()]
+				.into(),
+			}
+		}
+	}
+
+	trait TOrderRepository {
+		fn add(&mut self, order: &mut Order) -> impl std::future::Future<Output = Result<(), BaseError>> + Send;
+	}
+	trait TOrderUpdater {
+		fn update_order(&mut self, order: &mut Order) -> impl std::future::Future<Output = Result<(), BaseError>> + Send;
+	}
+
+	#[event_hook]
+	impl TOrderRepository for Context {
+		async fn add(&mut self, order: &mut Order) -> Result<(), BaseError> {
+			order.raise_event(OrderPlaced { id: order.id }.to_message());
+			Ok(())
+		}
+	}
+
+	#[event_hook]
+	impl TOrderUpdater for Context {
+		async fn update_order(&mut self, order: &mut Order) -> Result<(), BaseError> {
+			let _ = order.id;
+			Ok(())
+		}
+	}
+
+	let context_manager = std::sync::Arc::new(ContextManager::new(&Connection));
+	let mut context = Context::new(context_manager.clone());
+	let mut order = Order { id: 1, ..Default::default() };
+	// Event raised in the body is collected by the hook of the same method
+	context.add(&mut order).await.unwrap();
+	assert_eq!(context_manager.stats().events_raised, 1);
+	assert!(order.events.is_empty());
+	order.changed.push("amount");
+	context.update_order(&mut order).await.unwrap();
+	// Mutation without change raises nothing
+	context.update_order(&mut order).await.unwrap();
+
+	assert_eq!(context_manager.stats().events_raised, 2);
+}
+
+#[test]
+fn test_auto_event_carries_field_diff() {
+	#[aggregate(Deserialize, Clone)]
+	#[auto_event(Updated, identifier = id)]
+	pub struct Order {
+		id: i64,
+		amount: i64,
+		memo: String,
+	}
+
+	let mut order = Order {
+		id: 1,
+		amount: 10,
+		..Default::default()
+	};
+	assert!(order.mutation_events().is_empty());
+
+	order.set_amount(20);
+	order.set_amount(30);
+	order.set_memo("gift");
+	// Set back to what it was
+	order.set_memo("");
+
+	let events = order.mutation_events();
+	assert_eq!(events.len(), 1);
+	let event = events[0].downcast_ref::<OrderUpdated>().unwrap();
+	assert_eq!(event.id, 1);
+	assert_eq!(
+		event.changes,
+		vec![FieldChange {
+			field: "amount".into(),
+			old: serde_json::json!(10),
+			new: serde_json::json!(30),
+		}]
+	);
+	assert!(event.externally_notifiable());
+	assert_eq!(event.metadata().aggregate_id, "1");
+
+	// Taken once
+	assert!(order.mutation_events().is_empty());
+}
+
+#[test]
+fn test_versioned_aggregate() {
+	#[aggregate]
+	#[derive(Clone)]
+	pub struct VersionedOrder {
+		id: i64,
+		name: String,
+		#[version]
+		version: i64,
+	}
+
+	let mut order = VersionedOrder::default();
+	order.set_name("migo");
+	assert_eq!(TVersioned::version(&order), 0);
+	order.bump_version();
+	assert_eq!(order.version, 1);
+
+	// Version is persisted along with the other columns
+	let adapter = VersionedOrderAdapter::from(&order);
+	assert_eq!(adapter.version, 1);
+
+	assert_eq!(versioned_set_clause(&order.dirty_fields(), "version", 3), "name = $3, version = version + 1");
+	assert_eq!(versioned_set_clause(&[], "version", 3), "version = version + 1");
+	// Version column is bumped only once, and the other columns keep their placeholders in order
+	assert_eq!(
+		versioned_set_clause(&["version", "name", "memo"], "row_version", 3),
+		"version = $3, name = $4, memo = $5, row_version = row_version + 1"
+	);
+	assert_eq!(
+		versioned_set_clause(&["name", "row_version", "memo"], "row_version", 3),
+		"name = $3, memo = $4, row_version = row_version + 1"
+	);
+	assert!(BaseError::ConcurrencyConflict("VersionedOrder at version 1".into()).is_retryable());
+}
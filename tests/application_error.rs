@@ -25,3 +25,73 @@ fn application_error_derive_test() {
 		}
 	}
 }
+
+#[test]
+fn application_error_constraint_mapping_test() {
+	#[derive(Debug, ApplicationError)]
+	#[allow(dead_code)]
+	enum OrderError {
+		StopSentinel,
+		StopSentinelWithEvent(std::sync::Arc<dyn TEvent>),
+		DatabaseError(String),
+		#[constraint("orders_email_key")]
+		DuplicateEmail,
+		BaseError(BaseError),
+	}
+
+	let err: OrderError = BaseError::ConstraintViolation {
+		constraint: "orders_email_key".into(),
+		message: "duplicate key value violates unique constraint".into(),
+	}
+	.into();
+	assert!(matches!(err, OrderError::DuplicateEmail));
+
+	let err: OrderError = BaseError::ConstraintViolation {
+		constraint: "orders_pkey".into(),
+		message: "duplicate key value violates unique constraint".into(),
+	}
+	.into();
+	assert!(matches!(err, OrderError::DatabaseError(msg) if msg == "duplicate key value violates unique constraint"));
+}
+
+#[test]
+fn application_error_keeps_base_error_test() {
+	#[derive(Debug, ApplicationError)]
+	#[allow(dead_code)]
+	enum OrderError {
+		StopSentinel,
+		StopSentinelWithEvent(std::sync::Arc<dyn TEvent>),
+		DatabaseError(String),
+		BaseError(BaseError),
+	}
+
+	let err: OrderError = BaseError::TransactionConflict("deadlock detected".into()).into();
+	let err: BaseError = err.into();
+	assert!(err.is_retryable());
+}
+
+#[test]
+fn application_error_http_status_test() {
+	#[derive(Debug, ApplicationError)]
+	#[allow(dead_code)]
+	enum OrderError {
+		StopSentinel,
+		StopSentinelWithEvent(std::sync::Arc<dyn TEvent>),
+		DatabaseError(String),
+		#[constraint("orders_email_key")]
+		DuplicateEmail,
+		#[http_status(404)]
+		OrderNotFound {
+			order_id: i64,
+		},
+		#[http_status(403)]
+		Forbidden(String),
+		BaseError(BaseError),
+	}
+
+	assert_eq!(OrderError::OrderNotFound { order_id: 1 }.http_status(), 404);
+	assert_eq!(OrderError::Forbidden("other tenant".into()).http_status(), 403);
+	assert_eq!(OrderError::DuplicateEmail.http_status(), 409);
+	assert_eq!(OrderError::BaseError(BaseError::NotFound).http_status(), 404);
+	assert_eq!(OrderError::DatabaseError("timeout".into()).http_status(), 500);
+}
@@ -0,0 +1,31 @@
+/// Registrations that must be rejected at compile time. Run with `TRYBUILD=overwrite` to update the expected errors.
+/// Run only with the default features, as the expected errors list implementors of the missing trait, which optional dependencies add to.
+#[test]
+#[cfg_attr(
+	any(
+		feature = "backtrace",
+		feature = "tracing",
+		feature = "sqlx-postgres",
+		feature = "sqlx-sqlite",
+		feature = "mongodb",
+		feature = "msgpack",
+		feature = "simd-json",
+		feature = "opentelemetry",
+		feature = "metrics",
+		feature = "redis",
+		feature = "regex",
+		feature = "encryption-ring",
+		feature = "foldhash",
+		feature = "ruva-kafka",
+		feature = "ruva-axum",
+		feature = "ruva-tonic",
+		feature = "mock",
+		feature = "typescript",
+		feature = "utoipa"
+	),
+	ignore = "expected errors are recorded with the default features"
+)]
+fn test_compile_fail() {
+	let cases = trybuild::TestCases::new();
+	cases.compile_fail("tests/ui/*.rs");
+}
@@ -0,0 +1,34 @@
+use ruva::*;
+
+declare_dependency! {
+	pub trait TPaymentGateway {
+		fn charge(&self, amount: i64) -> Result<i64, BaseError>;
+	}
+}
+
+struct Connection;
+impl TConnection for Connection {}
+
+#[test]
+fn test_resolve_declared_dependency() {
+	struct Gateway;
+	impl TPaymentGateway for Gateway {
+		fn charge(&self, amount: i64) -> Result<i64, BaseError> {
+			Ok(amount)
+		}
+	}
+
+	<dyn TPaymentGateway>::register(Gateway);
+	let context_manager = ContextManager::new(&Connection);
+	assert_eq!(<dyn TPaymentGateway>::resolve(&context_manager).charge(100).unwrap(), 100);
+}
+
+#[cfg(feature = "mock")]
+#[test]
+fn test_resolve_mocked_dependency() {
+	let mut gateway = MockTPaymentGateway::new();
+	gateway.expect_charge().times(1).returning(|_| Err(BaseError::ServiceError));
+
+	let context_manager = ContextManager::new(&Connection).with_dependency::<dyn TPaymentGateway>(std::sync::Arc::new(gateway));
+	assert!(<dyn TPaymentGateway>::resolve(&context_manager).charge(100).is_err());
+}
@@ -0,0 +1,290 @@
+use ruva::*;
+use std::sync::{Arc, Mutex};
+
+static RECORDED: std::sync::LazyLock<Mutex<Vec<String>>> = std::sync::LazyLock::new(Default::default);
+
+struct TestConnection;
+impl TConnection for TestConnection {}
+
+#[derive(Debug, ApplicationError)]
+#[allow(dead_code)]
+enum TestError {
+	StopSentinel,
+	StopSentinelWithEvent(Arc<dyn TEvent>),
+	DatabaseError(String),
+	BaseError(BaseError),
+}
+
+#[derive(Debug, Serialize, ApplicationResponse)]
+enum TestResponse {
+	Done,
+}
+
+#[derive(Debug, Clone, Serialize, TEvent)]
+#[internally_notifiable]
+struct ItemImported {
+	id: i64,
+}
+
+#[derive(Debug, Clone, Serialize, TEvent)]
+#[internally_notifiable]
+struct ImportFinished {
+	count: usize,
+}
+
+#[derive(Debug, Clone, Serialize, TEvent)]
+#[internally_notifiable]
+struct InvoiceRequested {
+	order_id: i64,
+}
+
+#[derive(Debug, Clone, Serialize, TEvent)]
+#[internally_notifiable]
+struct LegacyOrderMigrated {
+	id: i64,
+}
+
+static MIGRATED: std::sync::LazyLock<Mutex<Vec<String>>> = std::sync::LazyLock::new(Default::default);
+/// Projection of the order 40 fails once
+static FAIL_MIGRATION: std::sync::atomic::AtomicBool = std::sync::atomic::AtomicBool::new(true);
+
+static INVOICED: std::sync::LazyLock<Mutex<Vec<String>>> = std::sync::LazyLock::new(Default::default);
+
+struct TestEventHandler;
+impl TestEventHandler {
+	async fn issue_invoice(self, event: InvoiceRequested) -> Result<(), TestError> {
+		INVOICED.lock().unwrap().push(format!("default:{}", event.order_id));
+		Ok(())
+	}
+	async fn upsert_items(self, events: Vec<ItemImported>) -> Result<(), TestError> {
+		RECORDED
+			.lock()
+			.unwrap()
+			.push(format!("batch:{}", events.iter().map(|e| e.id.to_string()).collect::<Vec<_>>().join(",")));
+		Ok(())
+	}
+	async fn notify(self, event: ImportFinished) -> Result<(), TestError> {
+		RECORDED.lock().unwrap().push(format!("finished:{}", event.count));
+		Ok(())
+	}
+	async fn audit(self, event: ImportFinished) -> Result<(), TestError> {
+		RECORDED.lock().unwrap().push(format!("audit:{}", event.count));
+		Ok(())
+	}
+	async fn project_order(self, event: LegacyOrderMigrated) -> Result<(), TestError> {
+		if event.id == 40 && FAIL_MIGRATION.swap(false, std::sync::atomic::Ordering::SeqCst) {
+			return Err(TestError::DatabaseError("Connection reset".into()));
+		}
+		MIGRATED.lock().unwrap().push(format!("project:{}", event.id));
+		Ok(())
+	}
+	async fn send_mail(self, event: LegacyOrderMigrated) -> Result<(), TestError> {
+		MIGRATED.lock().unwrap().push(format!("mail:{}", event.id));
+		Ok(())
+	}
+}
+
+init_event_handler!(
+	TestError,
+	|_ctx| TestEventHandler,
+	#[batch(2)]
+	ItemImported: [upsert_items],
+	ImportFinished: [notify, #[order = 1] audit],
+	InvoiceRequested: [issue_invoice],
+	LegacyOrderMigrated: [project_order, send_mail],
+);
+
+struct AcmeEventHandler;
+impl AcmeEventHandler {
+	async fn issue_invoice(self, event: InvoiceRequested) -> Result<(), TestError> {
+		INVOICED.lock().unwrap().push(format!("acme:{}", event.order_id));
+		Ok(())
+	}
+}
+
+#[allow(dead_code)]
+#[into_command]
+struct ImportItems {
+	ids: Vec<i64>,
+}
+
+struct ImportService(ImportItems, AtomicContextManager);
+impl TCommandService<TestResponse, TestError> for ImportService {
+	async fn execute(self) -> Result<TestResponse, TestError> {
+		let ImportService(cmd, context_manager) = self;
+		let mut context = Context::new(context_manager);
+		let mut events = cmd.ids.iter().map(|id| ItemImported { id: *id }.to_message()).collect::<std::collections::VecDeque<_>>();
+		events.push_back(ImportFinished { count: cmd.ids.len() }.to_message());
+		context.set_current_events(events);
+		context.send_internally_notifiable_messages().await;
+		Ok(TestResponse::Done)
+	}
+}
+
+impl TMessageBus<TestResponse, TestError, ImportItems> for MessageBus {
+	fn command_handler(&self, context_manager: AtomicContextManager, cmd: ImportItems) -> impl TCommandService<TestResponse, TestError> {
+		ImportService(cmd, context_manager)
+	}
+}
+
+#[into_command]
+#[derive(TCommandSpec)]
+#[command_spec(response = TestResponse, error = TestError)]
+struct Ping;
+
+struct PingService;
+impl TCommandService<TestResponse, TestError> for PingService {
+	async fn execute(self) -> Result<TestResponse, TestError> {
+		Ok(TestResponse::Done)
+	}
+}
+
+// Served by the blanket `TMessageBus` implementation of `MessageBus`
+impl TCommandRoute for Ping {
+	fn command_handler(_context_manager: AtomicContextManager, _cmd: Ping) -> impl TCommandService<TestResponse, TestError> {
+		PingService
+	}
+}
+
+#[tokio::test]
+async fn test_dispatch_infers_response_from_command_spec() {
+	let res = MessageBus.dispatch(Ping, &TestConnection).await.unwrap();
+	assert!(matches!(res, TestResponse::Done));
+}
+
+#[tokio::test]
+async fn test_batch_and_ordered_event_handlers() {
+	let res = MessageBus.execute_and_wait(ImportItems { ids: vec![1, 2, 3] }, &TestConnection).await.unwrap();
+	assert!(matches!(res, TestResponse::Done));
+
+	assert_eq!(
+		*RECORDED.lock().unwrap(),
+		vec!["batch:1,2".to_string(), "batch:3".to_string(), "audit:3".to_string(), "finished:3".to_string()]
+	);
+}
+
+#[tokio::test]
+async fn test_progress_reports_done_without_events() {
+	let (res, mut progress) = MessageBus.execute_and_forget_with_progress(Ping, &TestConnection).await.unwrap();
+	assert!(matches!(res.result(), TestResponse::Done));
+	assert_eq!(progress.recv().await.unwrap(), EventProgress::Done);
+}
+
+#[derive(Deserialize)]
+struct ExternalPinged {}
+
+#[tokio::test]
+async fn test_event_policies_translate_external_events() {
+	let dead_letters = Arc::new(InMemoryDeadLetterStore::default());
+	let policies = EventPolicies::new(&TestConnection)
+		.register(on_event::<ExternalPinged>().dispatch(|_| Ping))
+		.dead_letter(dead_letters.clone());
+
+	assert_eq!(policies.handle("ExternalPinged", "{}").await.unwrap(), PolicyOutcome::Dispatched);
+	assert_eq!(policies.handle("Unknown", "{}").await.unwrap(), PolicyOutcome::Ignored);
+	assert!(matches!(policies.handle("ExternalPinged", "not json").await.unwrap(), PolicyOutcome::DeadLettered(_)));
+	let dead_letters = dead_letters.fetch(10).await.unwrap();
+	assert_eq!(dead_letters.len(), 1);
+	assert_eq!(
+		(dead_letters[0].topic.as_str(), dead_letters[0].handler_index, dead_letters[0].payload.as_str()),
+		("ExternalPinged", None, "not json")
+	);
+	assert!(dead_letters[0].error.contains("Failed to deserialize"));
+
+	// Without store, the error is returned as it is
+	let policies = EventPolicies::new(&TestConnection).register(on_event::<ExternalPinged>().dispatch(|_| Ping));
+	assert!(matches!(policies.handle("ExternalPinged", "not json").await, Err(BaseError::DatabaseError(err)) if err.starts_with("Failed to deserialize")));
+}
+
+#[tokio::test]
+async fn test_tenant_event_handlers_override_default() {
+	init_tenant_event_handler!("acme", TestError, |_ctx| AcmeEventHandler, InvoiceRequested: [issue_invoice]);
+
+	let events = || vec![InvoiceRequested { order_id: 1 }.to_message()];
+	MessageBus.handle_events(events(), ContextManager::new(&TestConnection).with_tenant("acme")).await.unwrap();
+	MessageBus.handle_events(events(), ContextManager::new(&TestConnection).with_tenant("other")).await.unwrap();
+	MessageBus.handle_events(events(), ContextManager::new(&TestConnection)).await.unwrap();
+
+	assert_eq!(*INVOICED.lock().unwrap(), vec!["acme:1".to_string(), "default:1".to_string(), "default:1".to_string()]);
+}
+
+#[tokio::test]
+async fn test_dyn_message_bus_routes_by_command_type() {
+	#[derive(Debug)]
+	struct Unregistered;
+	impl TCommand for Unregistered {}
+
+	let bus = DynMessageBus::new().register::<Ping>();
+	assert!(bus.is_registered::<Ping>());
+
+	let res = bus.dispatch(AnyCommand::new(Ping), &TestConnection).await.unwrap();
+	assert_eq!(res, serde_json::json!("Done"));
+
+	let err = bus.dispatch(AnyCommand::new(Unregistered), &TestConnection).await.unwrap_err();
+	assert!(matches!(err, BaseError::HandlerNotFound(name) if name.ends_with("Unregistered")));
+}
+
+#[tokio::test]
+async fn test_custom_bus_on_spi() {
+	use ruva::spi;
+
+	static AUDITED: std::sync::atomic::AtomicUsize = std::sync::atomic::AtomicUsize::new(0);
+	struct AuditBus;
+	impl spi::TEventBus<TestError> for AuditBus {
+		fn event_handler(&self) -> &'static spi::TEventHandler<TestError> {
+			static EVENT_HANDLERS: std::sync::LazyLock<spi::TEventHandler<TestError>> = std::sync::LazyLock::new(|| {
+				let handler: spi::Handlers<TestError> = vec![Box::new(|_event: Arc<dyn spi::TEvent>, _context_manager: spi::AtomicContextManager| {
+					spi::sandboxed(
+						"ItemImported",
+						"audit",
+						Box::pin(async {
+							AUDITED.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+							Ok(())
+						}),
+					)
+				})];
+				let mut handlers = spi::TEventHandler::default();
+				handlers.insert("ItemImported".to_string(), spi::EventHandlers::Sync(handler));
+				handlers
+			});
+			&EVENT_HANDLERS
+		}
+	}
+
+	assert_eq!(spi::SPI_VERSION, 1);
+	spi::TEventBus::handle_events(&AuditBus, vec![ItemImported { id: 1 }.to_message()], spi::ContextManager::new(&TestConnection))
+		.await
+		.unwrap();
+	assert_eq!(AUDITED.load(std::sync::atomic::Ordering::SeqCst), 1);
+}
+
+#[tokio::test]
+async fn test_import_events_through_selected_handlers() {
+	let checkpoint = Arc::new(InMemoryCheckpointStore::default());
+	checkpoint.save("legacy-orders", 10).await.unwrap();
+	let progress = Arc::new(Mutex::new(vec![]));
+
+	// Exported out of order
+	let events = || [30, 10, 50, 20, 40].map(|id| (id, LegacyOrderMigrated { id }.to_message())).to_vec();
+	let options = || {
+		ImportOptions::new("legacy-orders", &TestConnection)
+			.batch_size(2)
+			.handler("LegacyOrderMigrated", "project_order")
+			.checkpoint(checkpoint.clone())
+			.on_progress({
+				let progress = progress.clone();
+				move |p| progress.lock().unwrap().push((p.imported, p.finished))
+			})
+	};
+
+	// Resumed after the first event, without `send_mail`, and stopped by the failure in the second batch
+	assert!(MessageBus.import_events(events(), options()).await.is_err());
+	assert_eq!(*MIGRATED.lock().unwrap(), ["project:20", "project:30"]);
+	assert_eq!(checkpoint.load("legacy-orders").await.unwrap(), Some(30));
+
+	let done = MessageBus.import_events(events(), options()).await.unwrap();
+	assert_eq!(*MIGRATED.lock().unwrap(), ["project:20", "project:30", "project:40", "project:50"]);
+	assert_eq!(*progress.lock().unwrap(), vec![(3, false), (5, true)]);
+	assert_eq!((done.imported, done.total, done.finished), (5, 5, true));
+	assert_eq!(checkpoint.load("legacy-orders").await.unwrap(), Some(50));
+}
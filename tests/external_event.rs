@@ -28,5 +28,78 @@ fn test_declare_external_event() {
 	assert_eq!(metadata.aggregate_id, "1");
 	assert_eq!(metadata.aggregate_name, "SomeAggregate");
 	assert_eq!(metadata.topic, "SomeExternalEvent");
+	assert_eq!(metadata.version, INITIAL_EVENT_VERSION);
 	assert_eq!(event.state(), "{\"id\":1,\"name\":\"migo\",\"foo\":2}");
 }
+
+#[derive(Debug, Clone, Serialize, Deserialize, TEvent)]
+#[internally_notifiable]
+pub struct OrderPlaced {
+	id: i64,
+}
+
+topics!(OrderPlaced);
+
+#[test]
+fn test_typed_topics() {
+	assert_eq!(topics::ORDER_PLACED.name(), "OrderPlaced");
+	assert_eq!(topics::ORDER_PLACED.name(), OrderPlaced { id: 1 }.metadata().topic);
+	assert_eq!(topics::ALL, &["OrderPlaced"]);
+}
+
+#[test]
+fn test_event_version_is_carried_to_outbox() {
+	#[aggregate(Serialize, Debug)]
+	pub struct Payment {
+		#[adapter_ignore]
+		id: i64,
+	}
+
+	#[derive(Debug, Clone, Serialize, TEvent)]
+	#[event_version(3)]
+	#[externally_notifiable(Payment)]
+	pub struct PaymentDone {
+		#[identifier]
+		id: i64,
+	}
+
+	#[derive(Debug, Clone, Serialize, TEvent)]
+	#[internally_notifiable]
+	#[event_version(2)]
+	pub struct PaymentRefunded {
+		id: i64,
+	}
+
+	let done = PaymentDone { id: 1 };
+	assert_eq!(done.metadata().aggregate_id, "1");
+	assert_eq!(done.outbox().unwrap().version, 3);
+	assert_eq!(PaymentRefunded { id: 1 }.metadata().version, 2);
+}
+
+#[test]
+fn test_publish_class_is_carried_to_outbox() {
+	#[aggregate(Serialize, Debug)]
+	pub struct Catalog {
+		#[adapter_ignore]
+		id: i64,
+	}
+
+	#[derive(Debug, Clone, Serialize, TEvent)]
+	#[externally_notifiable(Catalog)]
+	#[publish_class(bulk)]
+	pub struct ProductReindexed {
+		#[identifier]
+		id: i64,
+	}
+
+	#[derive(Debug, Clone, Serialize, TEvent)]
+	#[internally_notifiable]
+	#[publish_class(low)]
+	pub struct CatalogArchived {
+		id: i64,
+	}
+
+	assert_eq!(ProductReindexed { id: 1 }.outbox().unwrap().publish_class, PublishClass::Bulk);
+	assert_eq!(CatalogArchived { id: 1 }.metadata().publish_class, PublishClass::Low);
+	assert_eq!(OrderPlaced { id: 1 }.outbox().unwrap().publish_class, PublishClass::Realtime);
+}
@@ -0,0 +1,99 @@
+#![cfg(feature = "ruva-tonic")]
+use ruva::tonic::codegen::{http, Body as _, Service};
+use ruva::*;
+
+struct TestConnection;
+impl TConnection for TestConnection {}
+
+#[derive(Debug, ApplicationError)]
+#[allow(dead_code)]
+enum TestError {
+	StopSentinel,
+	StopSentinelWithEvent(std::sync::Arc<dyn TEvent>),
+	DatabaseError(String),
+	#[http_status(404)]
+	OrderNotFound,
+	BaseError(BaseError),
+}
+
+#[derive(Debug, Serialize)]
+struct Placed {
+	order_id: i64,
+}
+impl ApplicationResponse for Placed {}
+
+impl TEventBus<TestError> for MessageBus {
+	fn event_handler(&self) -> &'static TEventHandler<TestError> {
+		static EVENT_HANDLER: std::sync::LazyLock<TEventHandler<TestError>> = std::sync::LazyLock::new(Default::default);
+		&EVENT_HANDLER
+	}
+}
+
+#[derive(Debug, Deserialize, TCommandSpec)]
+#[command_spec(response = Placed, error = TestError)]
+struct MakeOrder {
+	order_id: i64,
+}
+impl TCommand for MakeOrder {}
+
+struct MakeOrderService(MakeOrder);
+impl TCommandService<Placed, TestError> for MakeOrderService {
+	async fn execute(self) -> Result<Placed, TestError> {
+		match self.0.order_id {
+			0 => Err(TestError::OrderNotFound),
+			order_id => Ok(Placed { order_id }),
+		}
+	}
+}
+impl TCommandRoute for MakeOrder {
+	fn command_handler(_: AtomicContextManager, cmd: Self) -> impl TCommandService<Placed, TestError> {
+		MakeOrderService(cmd)
+	}
+}
+
+grpc_command_service!(OrderService = "order.OrderService" { MakeOrder });
+
+fn grpc_request(method: &str, message: &str) -> http::Request<String> {
+	// Uncompressed flag and big endian length prefix
+	let mut frame = vec![0u8];
+	frame.extend((message.len() as u32).to_be_bytes());
+	frame.extend(message.as_bytes());
+	http::Request::builder()
+		.method("POST")
+		.uri(format!("/order.OrderService/{}", method))
+		.header("content-type", "application/grpc")
+		.body(String::from_utf8(frame).unwrap())
+		.unwrap()
+}
+
+async fn call(method: &str, message: &str) -> (Option<String>, Option<String>) {
+	let response = OrderService::new(&TestConnection).call(grpc_request(method, message)).await.unwrap();
+	let header_status = response.headers().get("grpc-status").map(|v| v.to_str().unwrap().to_string());
+	let mut body = response.into_body();
+	let mut message = None;
+	let mut trailer_status = None;
+	while let Some(frame) = std::future::poll_fn(|cx| std::pin::Pin::new(&mut body).poll_frame(cx)).await {
+		let frame = frame.unwrap();
+		if let Some(data) = frame.data_ref() {
+			message = Some(String::from_utf8(data[5..].to_vec()).unwrap());
+		} else if let Some(trailers) = frame.trailers_ref() {
+			trailer_status = trailers.get("grpc-status").map(|v| v.to_str().unwrap().to_string());
+		}
+	}
+	(message, header_status.or(trailer_status))
+}
+
+#[tokio::test]
+async fn test_grpc_command_service() {
+	let (message, status) = call("MakeOrder", r#"{"order_id":7}"#).await;
+	assert_eq!(message.as_deref(), Some(r#"{"order_id":7}"#));
+	assert_eq!(status.as_deref(), Some("0"));
+
+	// NotFound
+	let (_, status) = call("MakeOrder", r#"{"order_id":0}"#).await;
+	assert_eq!(status.as_deref(), Some("5"));
+
+	// Unimplemented
+	let (_, status) = call("CancelOrder", "{}").await;
+	assert_eq!(status.as_deref(), Some("12"));
+}
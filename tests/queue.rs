@@ -0,0 +1,140 @@
+use ruva::*;
+
+struct TestConnection;
+impl TConnection for TestConnection {}
+
+#[derive(Debug, ApplicationError)]
+#[allow(dead_code)]
+enum TestError {
+	StopSentinel,
+	StopSentinelWithEvent(std::sync::Arc<dyn TEvent>),
+	DatabaseError(String),
+	BaseError(BaseError),
+}
+
+#[derive(Debug, PartialEq, Serialize)]
+struct Report {
+	rows: usize,
+	requested_by: Actor,
+}
+impl ApplicationResponse for Report {}
+
+impl TEventBus<TestError> for MessageBus {
+	fn event_handler(&self) -> &'static TEventHandler<TestError> {
+		static EVENT_HANDLER: std::sync::LazyLock<TEventHandler<TestError>> = std::sync::LazyLock::new(Default::default);
+		&EVENT_HANDLER
+	}
+}
+
+#[derive(Debug, Serialize, Deserialize, TCommandSpec)]
+#[command_spec(response = Report, error = TestError)]
+struct GenerateReport {
+	rows: usize,
+}
+impl TCommand for GenerateReport {}
+
+struct GenerateReportService(GenerateReport, AtomicContextManager);
+impl TCommandService<Report, TestError> for GenerateReportService {
+	async fn execute(self) -> Result<Report, TestError> {
+		if self.0.rows == 0 {
+			return Err(BaseError::NotFound.into());
+		}
+		Ok(Report {
+			rows: self.0.rows,
+			requested_by: self.1.actor.clone(),
+		})
+	}
+}
+
+impl TCommandRoute for GenerateReport {
+	fn command_handler(context_manager: AtomicContextManager, cmd: Self) -> impl TCommandService<Report, TestError> {
+		GenerateReportService(cmd, context_manager)
+	}
+}
+
+fn kim() -> Actor {
+	Actor::User("kim".into())
+}
+
+fn context_manager() -> ContextManager {
+	ContextManager::new(&TestConnection).with_actor(kim())
+}
+
+#[tokio::test]
+async fn test_queued_command_runs_with_its_context() {
+	let queue = CommandQueue::new(&TestConnection, InMemoryCommandQueueStore::default()).register::<GenerateReport>(ExecutionStrategy::Queued);
+
+	let Ok(Execution::Queued(ticket_id)) = queue.dispatch(GenerateReport { rows: 3 }, context_manager()).await else {
+		panic!("Queued command must be answered with ticket");
+	};
+	assert_eq!(queue.status(ticket_id, &kim()).await.unwrap().unwrap().status, TicketStatus::Queued);
+
+	assert_eq!(queue.process_next().await.unwrap(), Some(ticket_id));
+	let ticket = queue.status(ticket_id, &kim()).await.unwrap().unwrap();
+	assert_eq!(ticket.status, TicketStatus::Succeeded);
+	assert_eq!(ticket.response.as_deref(), Some(r#"{"rows":3,"requested_by":{"User":"kim"}}"#));
+	assert_eq!(queue.process_next().await.unwrap(), None);
+
+	// Failure is kept in the ticket
+	let ticket_id = queue.enqueue(GenerateReport { rows: 0 }, &context_manager()).await.unwrap();
+	queue.process_next().await.unwrap();
+	let ticket = queue.status(ticket_id, &kim()).await.unwrap().unwrap();
+	assert_eq!((ticket.status, ticket.response), (TicketStatus::Failed, None));
+	assert!(ticket.error.unwrap().contains("NotFound"));
+}
+
+#[tokio::test]
+async fn test_execution_strategy_changed_at_runtime() {
+	let queue = CommandQueue::new(&TestConnection, InMemoryCommandQueueStore::default()).register::<GenerateReport>(ExecutionStrategy::Queued);
+	queue.set_strategy::<GenerateReport>(ExecutionStrategy::Inline);
+	assert_eq!(queue.strategy::<GenerateReport>(), ExecutionStrategy::Inline);
+
+	let Ok(Execution::Done(report)) = queue.dispatch(GenerateReport { rows: 3 }, context_manager()).await else {
+		panic!("Inline command must be answered with response");
+	};
+	assert_eq!(report.requested_by, Actor::User("kim".into()));
+	assert_eq!(queue.process_next().await.unwrap(), None);
+
+	// Unregistered command is not queued
+	let unregistered = CommandQueue::new(&TestConnection, InMemoryCommandQueueStore::default());
+	assert!(matches!(unregistered.enqueue(GenerateReport { rows: 3 }, &context_manager()).await, Err(BaseError::HandlerNotFound(_))));
+}
+
+#[tokio::test]
+async fn test_ticket_is_queried_by_its_owner() {
+	let queue = CommandQueue::new(&TestConnection, InMemoryCommandQueueStore::default()).register::<GenerateReport>(ExecutionStrategy::Queued);
+	let ticket_id = queue.enqueue(GenerateReport { rows: 3 }, &context_manager()).await.unwrap();
+
+	let impersonated = Actor::Impersonated {
+		admin: "admin".into(),
+		as_user: "kim".into(),
+	};
+	assert_eq!(queue.status(ticket_id, &impersonated).await.unwrap().unwrap().owner, kim());
+	assert!(queue.status(ticket_id, &Actor::System("report-monitor".into())).await.unwrap().is_some());
+	assert!(matches!(queue.status(ticket_id, &Actor::User("lee".into())).await, Err(BaseError::Forbidden(_))));
+	assert!(matches!(queue.status(ticket_id, &Actor::Anonymous).await, Err(BaseError::Forbidden(_))));
+}
+
+#[tokio::test]
+async fn test_ticket_of_crashed_worker_is_claimed_again() {
+	let store = std::sync::Arc::new(InMemoryCommandQueueStore::default());
+	let queue = CommandQueue::new(&TestConnection, store.clone()).register::<GenerateReport>(ExecutionStrategy::Queued);
+	let ticket_id = queue.enqueue(GenerateReport { rows: 3 }, &context_manager()).await.unwrap();
+
+	// Worker claimed it with a lease that is over, and crashed
+	let claimed = store.claim_next(clock().now() - std::time::Duration::from_secs(1)).await.unwrap().unwrap();
+	assert_eq!((claimed.id, claimed.status), (ticket_id, TicketStatus::Running));
+
+	assert_eq!(queue.process_next().await.unwrap(), Some(ticket_id));
+	let ticket = queue.status(ticket_id, &kim()).await.unwrap().unwrap();
+	assert_eq!((ticket.status, ticket.claimed_until), (TicketStatus::Succeeded, None));
+
+	// The worker comes back with the result, which doesn't overwrite the one of the worker that claimed it again
+	assert!(!store.finish(ticket_id, claimed.claimed_until.unwrap(), Err("timed out".into())).await.unwrap());
+	assert_eq!(queue.status(ticket_id, &kim()).await.unwrap().unwrap().status, TicketStatus::Succeeded);
+
+	// Claim still held is not taken
+	queue.enqueue(GenerateReport { rows: 3 }, &context_manager()).await.unwrap();
+	store.claim_next(clock().now() + std::time::Duration::from_secs(60)).await.unwrap().unwrap();
+	assert_eq!(queue.process_next().await.unwrap(), None);
+}
@@ -0,0 +1,220 @@
+#![cfg(feature = "sqlx-sqlite")]
+use ruva::*;
+use std::sync::Arc;
+
+#[aggregate(Serialize, Debug)]
+pub struct Order {
+	#[adapter_ignore]
+	id: i64,
+}
+
+#[derive(Debug, Clone, Serialize, TEvent)]
+#[externally_notifiable(Order)]
+pub struct OrderPlaced {
+	#[identifier]
+	id: i64,
+}
+
+async fn pool() -> &'static sqlx::SqlitePool {
+	let pool = sqlx::sqlite::SqlitePoolOptions::new().max_connections(1).connect("sqlite::memory:").await.unwrap();
+	create_sqlite_schema(&pool).await.unwrap();
+	Box::leak(Box::new(pool))
+}
+
+#[tokio::test]
+async fn test_sqlite_unit_of_work_saves_outbox() {
+	let pool = pool().await;
+
+	let committed = Arc::new(ContextManager::new(pool));
+	let mut ctx = Context::new(committed.clone());
+	ctx.begin().await.unwrap();
+	ctx.raise(OrderPlaced { id: 1 });
+	ctx.commit().await.unwrap();
+
+	// Rolled back
+	let rolled_back = Arc::new(ContextManager::new(pool));
+	let mut ctx = Context::new(rolled_back.clone());
+	ctx.begin().await.unwrap();
+	ctx.raise(OrderPlaced { id: 2 });
+	ctx.process_external_events().await.unwrap();
+	ctx.rollback().await.unwrap();
+
+	let outboxes = pool.fetch_unprocessed(10).await.unwrap();
+	assert_eq!(outboxes.len(), 1);
+	assert_eq!(
+		(outboxes[0].aggregate_name.as_str(), outboxes[0].aggregate_id.as_str(), outboxes[0].topic.as_str()),
+		("Order", "1", "OrderPlaced")
+	);
+	assert_eq!(outboxes[0].version, INITIAL_EVENT_VERSION);
+	assert_eq!(pool.backlog().await.unwrap().unwrap().count, 1);
+	assert_eq!(committed.stats().consistency_token, ConsistencyToken::from_iter([outboxes[0].id]));
+	assert!(rolled_back.stats().consistency_token.is_empty());
+
+	pool.mark_processed(outboxes[0].id).await.unwrap();
+	assert!(pool.fetch_unprocessed(10).await.unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn test_record_only_handler_does_not_write_outbox() {
+	let pool = pool().await;
+
+	handler_sandbox().record_only("OrderPlaced", "republish");
+	let context_manager = Arc::new(ContextManager::new(pool));
+	sandboxed::<BaseError>(
+		"OrderPlaced",
+		"republish",
+		Box::pin(async move {
+			let mut ctx = Context::new(context_manager);
+			ctx.begin().await?;
+			ctx.raise(OrderPlaced { id: 3 });
+			ctx.commit().await
+		}),
+	)
+	.await
+	.unwrap();
+	handler_sandbox().execute("OrderPlaced", "republish");
+
+	assert!(pool.fetch_unprocessed(10).await.unwrap().is_empty());
+	let report = handler_sandbox().take_report();
+	assert!(report
+		.iter()
+		.any(|effect| effect.handler == "republish" && effect.client == "outbox" && effect.description == "publish OrderPlaced of Order 3"));
+}
+
+#[tokio::test]
+async fn test_outbox_history_keeps_every_column() {
+	let pool = pool().await;
+	sqlx::query(
+		"INSERT INTO service_outbox (id, aggregate_id, aggregate_name, topic, state, processed, create_dt, sequence, version, publish_class, trace_context, correlation_id, causation_id)
+         VALUES (1, '1', 'Order', 'OrderPlaced', '{}', TRUE, '2024-01-01T00:00:00Z', 3, 2, 1, '00-trace', 'correlation', 'cause')",
+	)
+	.execute(pool)
+	.await
+	.unwrap();
+
+	let history = pool.fetch_history(None, 10).await.unwrap();
+	assert_eq!(history.len(), 1);
+	let row = &history[0];
+	assert_eq!((row.sequence, row.version, row.publish_class.priority()), (Some(3), 2, 1));
+	assert_eq!(
+		(row.trace_context.as_deref(), row.correlation_id.as_deref(), row.causation_id.as_deref()),
+		(Some("00-trace"), Some("correlation"), Some("cause"))
+	);
+	assert!(row.processed);
+	assert!(pool.fetch_history(Some(1), 10).await.unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn test_snowflake_roundtrip_on_sqlite() {
+	let pool = pool().await;
+	let id = SnowFlake::generate();
+	let loaded: SnowFlake = sqlx::query_scalar("SELECT ?").bind(id).fetch_one(pool).await.unwrap();
+	assert_eq!(*loaded, *id);
+}
+
+#[derive(Debug)]
+pub struct PlaceOrder {
+	id: i64,
+	request_id: &'static str,
+}
+impl TCommand for PlaceOrder {}
+impl TIdempotentCommand for PlaceOrder {
+	fn idempotency_key(&self) -> Option<&str> {
+		Some(self.request_id)
+	}
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub struct OrderId(i64);
+impl ApplicationResponse for OrderId {}
+
+struct PlaceOrderHandler(PlaceOrder, Context);
+impl TCommandService<OrderId, BaseError> for PlaceOrderHandler {
+	async fn execute(self) -> Result<OrderId, BaseError> {
+		let Self(cmd, mut ctx) = self;
+		ctx.begin().await?;
+		ctx.raise(OrderPlaced { id: cmd.id });
+		ctx.commit().await?;
+		Ok(OrderId(cmd.id))
+	}
+}
+
+#[tokio::test]
+async fn test_idempotency_key_claimed_in_transaction() {
+	let pool = pool().await;
+	let place_order = |id| {
+		let context_manager = Arc::new(ContextManager::new(pool).with_idempotency_store(pool.clone()));
+		let cmd = PlaceOrder { id, request_id: "request-1" };
+		let handler = PlaceOrderHandler(PlaceOrder { ..cmd }, Context::new(context_manager.clone()));
+		IdempotencyAspect::new(&context_manager, &cmd, handler)
+	};
+
+	assert_eq!(place_order(1).execute().await.unwrap(), OrderId(1));
+	// Retry with the same key is answered with the first response without running the handler
+	assert_eq!(place_order(2).execute().await.unwrap(), OrderId(1));
+	assert_eq!(pool.fetch_unprocessed(10).await.unwrap().len(), 1);
+
+	// Key is scoped by tenant and user
+	let record = pool.get(r#"["","","request-1"]"#).await.unwrap().unwrap();
+	assert_eq!(
+		(record.command.as_str(), record.response.as_deref(), record.committed),
+		(std::any::type_name::<PlaceOrder>(), Some("1"), true)
+	);
+
+	// Only the claim that is neither committed nor completed is released
+	assert!(!pool.release(r#"["","","request-1"]"#).await.unwrap());
+	assert!(pool.claim("request-2", "PlaceOrder").await.unwrap());
+	assert!(pool.release("request-2").await.unwrap());
+	assert!(pool.get("request-2").await.unwrap().is_none());
+}
+
+#[tokio::test]
+async fn test_command_queue_on_sqlite() {
+	let pool = pool().await;
+	let now = clock().now();
+	let ticket = Ticket {
+		id: *SnowFlake::generate(),
+		command: "GenerateReport".into(),
+		payload: "{}".into(),
+		status: TicketStatus::Queued,
+		owner: Actor::User("kim".into()),
+		claimed_until: None,
+		response: None,
+		error: None,
+		queued_at: now,
+		updated_at: now,
+	};
+	pool.enqueue(&ticket).await.unwrap();
+
+	// Claim of the worker that crashed expires
+	let claimed = pool.claim_next(now - std::time::Duration::from_secs(1)).await.unwrap().unwrap();
+	assert_eq!((claimed.id, claimed.status, claimed.owner), (ticket.id, TicketStatus::Running, Actor::User("kim".into())));
+	let claimed_until = clock().now() + std::time::Duration::from_secs(60);
+	let reclaimed = pool.claim_next(claimed_until).await.unwrap().unwrap();
+	assert_eq!((reclaimed.id, reclaimed.claimed_until), (ticket.id, Some(claimed_until)));
+	assert_eq!(pool.claim_next(claimed_until).await.unwrap(), None);
+
+	// Worker whose claim has expired can't overwrite the result of the one that claimed it again
+	assert!(!pool.finish(ticket.id, claimed.claimed_until.unwrap(), Err("lease expired".into())).await.unwrap());
+	assert!(pool.finish(ticket.id, reclaimed.claimed_until.unwrap(), Ok("3".into())).await.unwrap());
+	let finished = pool.get_ticket(ticket.id).await.unwrap().unwrap();
+	assert_eq!((finished.status, finished.response.as_deref()), (TicketStatus::Succeeded, Some("3")));
+}
+
+#[tokio::test]
+async fn test_subscriptions_shared_on_sqlite() {
+	let pool = pool().await;
+	let (instance_a, instance_b) = (Subscriptions::default(), Subscriptions::default());
+	instance_a.subscribe_in(pool, "OrderPlaced", "OrderPlaced").await.unwrap();
+	instance_b.subscribe_in(pool, "OrderPlaced", "billing.orders").await.unwrap();
+
+	instance_a.restore(pool).await.unwrap();
+	assert_eq!(instance_a.route("OrderPlaced"), vec!["OrderPlaced", "billing.orders"]);
+
+	instance_a.unsubscribe_in(pool, "OrderPlaced", "OrderPlaced").await.unwrap();
+	instance_b.restore(pool).await.unwrap();
+	assert_eq!(instance_b.route("OrderPlaced"), vec!["billing.orders"]);
+
+	instance_b.persist(pool).await.unwrap();
+	assert_eq!(pool.load().await.unwrap(), vec![Subscription::new("OrderPlaced", "billing.orders")]);
+}
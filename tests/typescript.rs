@@ -0,0 +1,76 @@
+#![cfg(feature = "typescript")]
+use ruva::*;
+
+#[into_command(body(ruva::TTypeScript))]
+pub struct CreateOrder {
+	pub user_id: i64,
+	pub items: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize, TTypeScript)]
+#[serde(rename_all = "camelCase")]
+pub struct OrderLine {
+	pub item_id: SnowFlake,
+	#[serde(default)]
+	pub quantity: Option<i32>,
+	#[serde(skip)]
+	pub internal_note: String,
+}
+
+#[derive(Serialize, Deserialize, TTypeScript)]
+#[serde(tag = "type")]
+pub enum OrderResponse {
+	Created { id: i64, lines: Vec<OrderLine> },
+	Empty,
+}
+impl ApplicationResponse for OrderResponse {}
+
+#[derive(Debug, Clone, Serialize, Deserialize, TTypeScript)]
+pub enum OrderStatus {
+	Pending,
+	Shipped(String),
+}
+
+#[aggregate(Serialize, Debug)]
+pub struct Order {
+	#[adapter_ignore]
+	id: i64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, TEvent, TTypeScript)]
+#[externally_notifiable(Order)]
+pub struct OrderStatusChanged {
+	#[identifier]
+	pub id: i64,
+	pub status: OrderStatus,
+}
+
+#[test]
+fn test_typescript_declarations() {
+	assert_eq!(
+		CreateOrderBody::ts_declaration().unwrap(),
+		"export interface CreateOrderBody {\n\tuser_id: number;\n\titems: string[];\n}"
+	);
+	assert_eq!(OrderLine::ts_declaration().unwrap(), "export interface OrderLine {\n\titemId: string;\n\tquantity?: number | null;\n}");
+	assert_eq!(
+		OrderResponse::ts_declaration().unwrap(),
+		"export type OrderResponse = { type: \"Created\"; id: number; lines: OrderLine[] } | { type: \"Empty\" };"
+	);
+	assert_eq!(OrderStatus::ts_declaration().unwrap(), "export type OrderStatus = \"Pending\" | { \"Shipped\": string };");
+	assert_eq!(
+		OrderStatusChanged::ts_declaration().unwrap(),
+		"export interface OrderStatusChanged {\n\tid: number;\n\tstatus: OrderStatus;\n}"
+	);
+}
+
+#[test]
+fn test_typescript_bindings() {
+	let bindings = typescript_bindings!(CreateOrderBody, OrderLine, Vec<i64>);
+	assert!(bindings.contains("export interface CreateOrderBody"));
+	assert!(bindings.contains("export interface OrderLine"));
+	assert_eq!(bindings.matches("export").count(), 2);
+
+	let path = std::env::temp_dir().join("ruva_typescript_bindings").join("bindings.ts");
+	write_typescript(&path, &bindings).unwrap();
+	assert_eq!(std::fs::read_to_string(&path).unwrap(), bindings);
+}
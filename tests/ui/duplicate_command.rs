@@ -0,0 +1,32 @@
+use ruva::*;
+
+#[derive(Debug, Clone, ApplicationError)]
+#[allow(dead_code)]
+enum TestError {
+	StopSentinel,
+	StopSentinelWithEvent(std::sync::Arc<dyn TEvent>),
+	DatabaseError(String),
+	BaseError(BaseError),
+}
+
+#[derive(Debug)]
+struct TestResponse;
+impl ApplicationResponse for TestResponse {}
+
+#[derive(Debug)]
+struct PlaceOrder;
+impl TCommand for PlaceOrder {}
+
+async fn place_order(_cmd: PlaceOrder, _ctx: &mut Context) -> Result<TestResponse, TestError> {
+	Ok(TestResponse)
+}
+
+// Same command, written differently. Named in the last error, after the conflicting implementations.
+register_uow_services!(
+	TestResponse,
+	TestError,
+	PlaceOrder => place_order,
+	self::PlaceOrder => place_order
+);
+
+fn main() {}
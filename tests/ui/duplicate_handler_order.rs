@@ -0,0 +1,34 @@
+use ruva::*;
+
+#[derive(Debug, ApplicationError)]
+#[allow(dead_code)]
+enum TestError {
+	StopSentinel,
+	StopSentinelWithEvent(std::sync::Arc<dyn TEvent>),
+	DatabaseError(String),
+	BaseError(BaseError),
+}
+
+#[derive(Debug, Clone, Serialize, TEvent)]
+#[internally_notifiable]
+struct OrderPlaced {
+	id: i64,
+}
+
+struct Handler;
+impl Handler {
+	async fn notify(self, _event: OrderPlaced) -> Result<(), TestError> {
+		Ok(())
+	}
+	async fn audit(self, _event: OrderPlaced) -> Result<(), TestError> {
+		Ok(())
+	}
+}
+
+init_event_handler!(
+	TestError,
+	|_ctx| Handler,
+	OrderPlaced: [#[order = 1] notify, #[order = 1] audit],
+);
+
+fn main() {}
@@ -0,0 +1,15 @@
+use ruva::*;
+
+#[aggregate]
+struct Order {
+	id: i64,
+}
+
+#[derive(Debug, TEvent)]
+#[externally_notifiable(Order)]
+struct OrderPlaced {
+	#[identifier]
+	id: i64,
+}
+
+fn main() {}
@@ -0,0 +1,55 @@
+use ruva::*;
+
+#[derive(Debug, TValidate)]
+pub struct RegisterUser {
+	#[validate(length(min = 1, max = 10))]
+	#[cfg_attr(feature = "regex", validate(regex = "^[a-z0-9_]+$"))]
+	pub username: String,
+	#[validate(range(min = 14, max = 150))]
+	pub age: u32,
+	#[validate(range(min = 0, max = 9_007_199_254_740_992))]
+	pub quota: i64,
+	#[validate(length(max = 5))]
+	pub bio: Option<String>,
+	#[validate(length(min = 1))]
+	pub tags: Vec<String>,
+	pub note: String,
+}
+
+fn user() -> RegisterUser {
+	RegisterUser {
+		username: "kim_42".into(),
+		age: 30,
+		quota: 9_007_199_254_740_992,
+		bio: None,
+		tags: vec!["new".into()],
+		note: String::new(),
+	}
+}
+
+#[test]
+fn test_derive_validate() {
+	assert!(user().validate().is_ok());
+	assert!(RegisterUser { bio: Some("hi".into()), ..user() }.validate().is_ok());
+
+	let errors = RegisterUser {
+		username: "Kim Minsu The Third".into(),
+		age: 9,
+		quota: 9_007_199_254_740_993,
+		bio: Some("too long".into()),
+		tags: vec![],
+		..user()
+	}
+	.validate()
+	.unwrap_err();
+	let codes = |field| errors.field(field).map(|error| error.code.as_str()).collect::<Vec<_>>();
+	#[cfg(feature = "regex")]
+	assert_eq!(codes("username"), vec!["length", "regex"]);
+	#[cfg(not(feature = "regex"))]
+	assert_eq!(codes("username"), vec!["length"]);
+	assert_eq!(codes("age"), vec!["range"]);
+	assert_eq!(codes("quota"), vec!["range"]);
+	assert_eq!(codes("bio"), vec!["length"]);
+	assert_eq!(codes("tags"), vec!["length"]);
+	assert_eq!(errors.field("age").next().unwrap().message, "must be at least 14");
+}